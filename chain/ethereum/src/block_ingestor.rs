@@ -2,8 +2,14 @@ use std::time::Duration;
 use std::time::Instant;
 
 use graph::prelude::*;
+use tokio::timer::Delay;
 use web3::types::*;
 
+use crate::chain_head::ChainHeadBroadcaster;
+
+/// Upper bound for the delay between polls after consecutive failures.
+const MAX_POLLING_BACKOFF: Duration = Duration::from_secs(60);
+
 pub struct BlockIngestor<S>
 where
     S: ChainStore,
@@ -14,6 +20,7 @@ where
     network_name: String,
     logger: Logger,
     polling_interval: Duration,
+    chain_head_broadcaster: ChainHeadBroadcaster,
 }
 
 impl<S> BlockIngestor<S>
@@ -27,6 +34,7 @@ where
         network_name: String,
         logger_factory: &LoggerFactory,
         polling_interval: Duration,
+        chain_head_broadcaster: ChainHeadBroadcaster,
     ) -> Result<BlockIngestor<S>, Error> {
         let logger = logger_factory.component_logger(
             "BlockIngestor",
@@ -44,6 +52,7 @@ where
             network_name,
             logger,
             polling_interval,
+            chain_head_broadcaster,
         })
     }
 
@@ -51,15 +60,14 @@ where
         // Currently, there is no way to stop block ingestion, so just leak self
         let static_self: &'static _ = Box::leak(Box::new(self));
 
-        // Create stream that emits at polling interval
-        tokio::timer::Interval::new(Instant::now(), static_self.polling_interval)
-            .map_err(move |e| {
-                error!(static_self.logger, "timer::Interval failed: {:?}", e);
-            })
-            .for_each(move |_| {
-                // Attempt to poll
-                static_self.do_poll().then(move |result| {
-                    if let Err(err) = result {
+        // Poll repeatedly, counting consecutive failures to back off from an
+        // Ethereum node that is having trouble
+        future::loop_fn(0, move |consecutive_errors: u32| {
+            // Attempt to poll
+            static_self.do_poll().then(move |result| {
+                let consecutive_errors = match result {
+                    Ok(()) => 0,
+                    Err(err) => {
                         // Some polls will fail due to transient issues
                         match err {
                             EthereumAdapterError::BlockUnavailable(_) => {
@@ -76,12 +84,30 @@ where
                                 );
                             }
                         }
+                        consecutive_errors + 1
                     }
+                };
+                static_self.chain_head_broadcaster.update_head_age();
 
-                    // Continue polling even if polling failed
-                    future::ok(())
-                })
+                // Continue polling even if polling failed
+                let delay = static_self.polling_delay(consecutive_errors);
+                Delay::new(Instant::now() + delay)
+                    .map_err(move |e| {
+                        error!(static_self.logger, "timer::Delay failed: {:?}", e);
+                    })
+                    .map(move |()| future::Loop::<(), _>::Continue(consecutive_errors))
             })
+        })
+    }
+
+    /// The delay before the next poll, doubled for every consecutive failed
+    /// poll up to `MAX_POLLING_BACKOFF`.
+    fn polling_delay(&self, consecutive_errors: u32) -> Duration {
+        if consecutive_errors == 0 {
+            return self.polling_interval;
+        }
+        let backoff = self.polling_interval * 2u32.pow(consecutive_errors.min(10));
+        backoff.min(MAX_POLLING_BACKOFF.max(self.polling_interval))
     }
 
     fn do_poll<'a>(&'a self) -> impl Future<Item = (), Error = EthereumAdapterError> + 'a {
@@ -96,6 +122,8 @@ where
                 self.eth_adapter.latest_block(&self.logger)
                     // Compare latest block with head ptr, alert user if far behind
                     .and_then(move |latest_block: LightEthereumBlock| -> Box<dyn Future<Item=_, Error=_> + Send> {
+                        self.chain_head_broadcaster.observe_latest_block(&latest_block);
+
                         match head_block_ptr_opt {
                             None => {
                                 info!(
@@ -181,6 +209,15 @@ where
                         )
                     })
             })
+            // Let block streams in this process know about the new head
+            .and_then(move |()| self.broadcast_chain_head())
+    }
+
+    fn broadcast_chain_head(&self) -> Result<(), EthereumAdapterError> {
+        if let Some(head_block_ptr) = self.chain_store.chain_head_ptr()? {
+            self.chain_head_broadcaster.broadcast(head_block_ptr);
+        }
+        Ok(())
     }
 
     /// Put some blocks into the block store (if they are not there already), and try to update the
@@ -1,6 +1,7 @@
 use std::cmp;
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::mem;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Mutex;
 use std::time::{Duration, Instant};
 
@@ -11,6 +12,9 @@ use graph::prelude::{
     BlockStream as BlockStreamTrait, BlockStreamBuilder as BlockStreamBuilderTrait, *,
 };
 use tokio::timer::Delay;
+use web3::types::H256;
+
+use crate::chain_head::ChainHeadBroadcaster;
 
 const FAST_SCAN_SPEEDUP: u64 = 10;
 
@@ -25,6 +29,14 @@ lazy_static! {
         .unwrap_or("4000000".into())
         .parse::<u64>()
         .expect("invalid fast scan end block number");
+
+    /// Every how many blocks to prefetch a block of the range after the one
+    /// being scanned; 0 disables prefetching.
+    static ref ETHEREUM_BLOCK_PREFETCH_STRIDE: u64 =
+        ::std::env::var("ETHEREUM_BLOCK_PREFETCH_STRIDE")
+            .unwrap_or("0".into())
+            .parse::<u64>()
+            .expect("invalid Ethereum block prefetch stride");
 }
 
 enum BlockStreamState {
@@ -106,6 +118,14 @@ struct BlockStreamContext<S, C> {
     templates_use_calls: bool,
     logger: Logger,
     metrics: Arc<BlockStreamMetrics>,
+    /// Cancels blocks being prefetched when it is replaced or dropped.
+    prefetch_guard: Arc<Mutex<CancelGuard>>,
+    /// Hashes of prefetched blocks above the blocks scanned so far, by
+    /// block number.
+    prefetched_blocks: Arc<Mutex<BTreeMap<u64, H256>>>,
+    /// Whether blocks are being prefetched; at most one prefetch runs at a
+    /// time.
+    prefetching: Arc<AtomicBool>,
 }
 
 impl<S, C> Clone for BlockStreamContext<S, C> {
@@ -124,6 +144,9 @@ impl<S, C> Clone for BlockStreamContext<S, C> {
             templates_use_calls: self.templates_use_calls,
             logger: self.logger.clone(),
             metrics: self.metrics.clone(),
+            prefetch_guard: self.prefetch_guard.clone(),
+            prefetched_blocks: self.prefetched_blocks.clone(),
+            prefetching: self.prefetching.clone(),
         }
     }
 }
@@ -133,6 +156,10 @@ pub struct BlockStream<S, C> {
     consecutive_err_count: u32,
     chain_head_update_stream: ChainHeadUpdateStream,
     ctx: BlockStreamContext<S, C>,
+    /// Resolves when the node shuts down, which ends the stream.
+    shutdown_signal: Option<Box<dyn Future<Item = (), Error = ()> + Send>>,
+    /// Keeps a shutdown waiting until the stream has ended.
+    in_flight_work: Option<InFlightWork>,
 }
 
 impl<S, C> BlockStream<S, C>
@@ -143,6 +170,7 @@ where
     pub fn new(
         subgraph_store: Arc<S>,
         chain_store: Arc<C>,
+        chain_head_update_stream: ChainHeadUpdateStream,
         eth_adapter: Arc<dyn EthereumAdapter>,
         node_id: NodeId,
         subgraph_id: SubgraphDeploymentId,
@@ -154,11 +182,12 @@ where
         reorg_threshold: u64,
         logger: Logger,
         metrics: Arc<BlockStreamMetrics>,
+        shutdown: Option<ShutdownCoordinator>,
     ) -> Self {
         BlockStream {
             state: Mutex::new(BlockStreamState::New),
             consecutive_err_count: 0,
-            chain_head_update_stream: chain_store.chain_head_updates(),
+            chain_head_update_stream,
             ctx: BlockStreamContext {
                 subgraph_store,
                 chain_store,
@@ -173,11 +202,23 @@ where
                 start_blocks,
                 templates_use_calls,
                 metrics,
+                prefetch_guard: Default::default(),
+                prefetched_blocks: Default::default(),
+                prefetching: Default::default(),
             },
+            shutdown_signal: shutdown.as_ref().map(|shutdown| shutdown.signal()),
+            in_flight_work: shutdown.and_then(|shutdown| shutdown.begin_work()),
         }
     }
 }
 
+impl<S, C> Drop for BlockStream<S, C> {
+    fn drop(&mut self) {
+        // Stop prefetching blocks for a stream that is gone
+        *self.ctx.prefetch_guard.lock().unwrap() = CancelGuard::new();
+    }
+}
+
 impl<S, C> BlockStreamContext<S, C>
 where
     S: Store,
@@ -389,22 +430,48 @@ where
                                 cmp::min(from + speedup * *ETHEREUM_BLOCK_RANGE_SIZE - 1, to_limit)
                             };
 
+                            // Warm the chain store with the range that will be
+                            // scanned next while this one is being scanned
+                            ctx.prefetch_blocks(
+                                to + 1,
+                                cmp::min(to + (to - from + 1), head_ptr.number - reorg_threshold),
+                            );
+
                             let section = ctx.metrics.stopwatch.start_section("scan_blocks");
                             info!(ctx.logger, "Scanning blocks [{}, {}]", from, to);
+                            let ctx1 = ctx.clone();
+                            let ctx2 = ctx.clone();
                             Box::new(
-                                ctx.eth_adapter
-                                    .blocks_with_triggers(
-                                        ctx.logger.clone(),
-                                        ctx.chain_store.clone(),
-                                        ctx.metrics.ethrpc_metrics.clone(),
-                                        from,
-                                        to,
-                                        log_filter.clone(),
-                                        call_filter.clone(),
-                                        block_filter.clone(),
-                                    )
+                                ctx.discover_factory_children(&log_filter, from, to)
+                                    .and_then(move |creations| {
+                                        ctx.eth_adapter
+                                            .blocks_with_triggers(
+                                                ctx.logger.clone(),
+                                                ctx.chain_store.clone(),
+                                                ctx.metrics.ethrpc_metrics.clone(),
+                                                from,
+                                                to,
+                                                log_filter.clone(),
+                                                call_filter.clone(),
+                                                block_filter.clone(),
+                                                reorg_threshold,
+                                                // The chain head may come from a
+                                                // different Ethereum node; ask the
+                                                // one that scans the blocks
+                                                None,
+                                                None,
+                                                None,
+                                            )
+                                            .and_then(move |blocks| {
+                                                ctx2.load_calls_of_non_final_blocks(blocks)
+                                            })
+                                            .map(move |blocks| {
+                                                attach_factory_children(blocks, creations)
+                                            })
+                                    })
                                     .map(move |blocks| {
                                         section.end();
+                                        ctx1.record_prefetched_blocks_used(to, &blocks);
                                         ReconciliationStep::ProcessDescendantBlocks(blocks)
                                     }),
                             )
@@ -461,6 +528,7 @@ where
                         // so instead we will advance the subgraph ptr by one block.
                         // Note that head_ancestor is a child of subgraph_ptr.
                         let eth_adapter = self.eth_adapter.clone();
+                        let block_number = head_ancestor.block.number.unwrap().as_u64();
 
                         let block_with_calls = if !self.include_calls_in_blocks() {
                             Box::new(future::ok(EthereumBlockWithCalls {
@@ -474,7 +542,7 @@ where
                                     .calls_in_block(
                                         &logger,
                                         ctx.metrics.ethrpc_metrics.clone(),
-                                        head_ancestor.block.number.unwrap().as_u64(),
+                                        block_number,
                                         head_ancestor.block.hash.unwrap(),
                                     )
                                     .map(move |calls| EthereumBlockWithCalls {
@@ -485,21 +553,26 @@ where
                         };
 
                         Box::new(
-                            block_with_calls
-                                .and_then(move |block| {
-                                    eth_adapter.triggers_in_block(
-                                        logger,
-                                        ctx.chain_store.clone(),
-                                        ctx.metrics.ethrpc_metrics.clone(),
-                                        log_filter.clone(),
-                                        call_filter.clone(),
-                                        block_filter.clone(),
-                                        BlockFinality::NonFinal(block),
-                                    )
+                            ctx.discover_factory_children(&log_filter, block_number, block_number)
+                                .and_then(move |creations| {
+                                    block_with_calls.map(move |block| (block, creations))
                                 })
-                                .map(move |block| {
-                                    ReconciliationStep::ProcessDescendantBlocks(vec![block])
-                                }),
+                                .and_then(move |(block, creations)| {
+                                    eth_adapter
+                                        .triggers_in_block(
+                                            logger,
+                                            ctx.chain_store.clone(),
+                                            ctx.metrics.ethrpc_metrics.clone(),
+                                            log_filter.clone(),
+                                            call_filter.clone(),
+                                            block_filter.clone(),
+                                            BlockFinality::NonFinal(block),
+                                        )
+                                        .map(move |block| {
+                                            attach_factory_children(vec![block], creations)
+                                        })
+                                })
+                                .map(ReconciliationStep::ProcessDescendantBlocks),
                         )
                     } else {
                         // The subgraph ptr is not on the main chain.
@@ -512,6 +585,179 @@ where
         }
     }
 
+    /// Add the contracts that the factories of `log_filter` created in the
+    /// block range to the filter. Returns all contracts the factories
+    /// created in the range, which are recorded in the store along with
+    /// the blocks of the range, see `attach_factory_children`.
+    fn discover_factory_children(
+        &self,
+        log_filter: &EthereumLogFilter,
+        from: u64,
+        to: u64,
+    ) -> Box<dyn Future<Item = Vec<EthereumContractCreation>, Error = Error> + Send> {
+        let factories = log_filter.factories();
+        if factories.is_empty() {
+            return Box::new(future::ok(vec![]));
+        }
+
+        let log_filter = log_filter.clone();
+        let logger = self.logger.clone();
+        Box::new(
+            self.eth_adapter
+                .contracts_created_in_block_range(
+                    &self.logger,
+                    self.metrics.ethrpc_metrics.clone(),
+                    from,
+                    to,
+                    factories,
+                )
+                .map(move |creations| {
+                    let added = log_filter.add_factory_children(creations.iter().cloned());
+                    if !added.is_empty() {
+                        debug!(
+                            logger, "Found contracts created by factories";
+                            "count" => added.len(),
+                            "from" => from,
+                            "to" => to,
+                        );
+                    }
+                    creations
+                }),
+        )
+    }
+
+    /// Load the calls of the non-final blocks in `blocks`, which
+    /// `blocks_with_triggers` leaves out, if the subgraph needs calls. The
+    /// triggers of data sources created in those blocks are found in them.
+    fn load_calls_of_non_final_blocks(
+        &self,
+        blocks: Vec<EthereumBlockWithTriggers>,
+    ) -> Box<dyn Future<Item = Vec<EthereumBlockWithTriggers>, Error = Error> + Send> {
+        if !self.include_calls_in_blocks() {
+            return Box::new(future::ok(blocks));
+        }
+
+        let ctx = self.clone();
+        Box::new(
+            stream::iter_ok(blocks)
+                .and_then(move |block| {
+                    let EthereumBlockWithTriggers {
+                        ethereum_block,
+                        triggers,
+                        factory_children,
+                    } = block;
+                    let ethereum_block = match ethereum_block {
+                        BlockFinality::NonFinal(EthereumBlockWithCalls {
+                            ethereum_block,
+                            calls: None,
+                        }) => ethereum_block,
+                        ethereum_block => {
+                            return Box::new(future::ok(EthereumBlockWithTriggers {
+                                ethereum_block,
+                                triggers,
+                                factory_children,
+                            }))
+                                as Box<dyn Future<Item = _, Error = _> + Send>
+                        }
+                    };
+
+                    Box::new(
+                        ctx.eth_adapter
+                            .calls_in_block(
+                                &ctx.logger,
+                                ctx.metrics.ethrpc_metrics.clone(),
+                                ethereum_block.block.number.unwrap().as_u64(),
+                                ethereum_block.block.hash.unwrap(),
+                            )
+                            .map(move |calls| EthereumBlockWithTriggers {
+                                ethereum_block: BlockFinality::NonFinal(EthereumBlockWithCalls {
+                                    ethereum_block,
+                                    calls: Some(calls),
+                                }),
+                                triggers,
+                                factory_children,
+                            }),
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    /// Start loading every `ETHEREUM_BLOCK_PREFETCH_STRIDE`th block of the
+    /// final blocks `[from, to]` into the chain store in the background.
+    /// Prefetching is canceled when a block is reverted or the block stream
+    /// is dropped. Nothing is prefetched while the previous prefetch is
+    /// still running, so that scanning ranges faster than their blocks can
+    /// be prefetched doesn't pile up prefetches.
+    fn prefetch_blocks(&self, from: u64, to: u64) {
+        let stride = *ETHEREUM_BLOCK_PREFETCH_STRIDE;
+        if stride == 0 || from > to {
+            return;
+        }
+        if self.prefetching.swap(true, Ordering::SeqCst) {
+            debug!(
+                self.logger, "Skipping prefetching blocks while the previous prefetch runs";
+                "from" => from,
+                "to" => to,
+            );
+            return;
+        }
+
+        let metrics = self.metrics.clone();
+        let prefetched_blocks = self.prefetched_blocks.clone();
+        let prefetching = self.prefetching.clone();
+        let logger = self.logger.clone();
+        let prefetch = self
+            .eth_adapter
+            .prefetch_blocks(&self.logger, self.chain_store.clone(), from, to, stride)
+            .map_err(CancelableError::Error)
+            .cancelable(&*self.prefetch_guard.lock().unwrap(), || {
+                CancelableError::Cancel
+            })
+            .map(move |ptrs| {
+                metrics.blocks_prefetched.inc_by(ptrs.len() as f64);
+                prefetched_blocks
+                    .lock()
+                    .unwrap()
+                    .extend(ptrs.into_iter().map(|ptr| (ptr.number, ptr.hash)));
+            })
+            .map_err(move |e| match e {
+                CancelableError::Cancel => debug!(logger, "Prefetching blocks canceled"),
+                CancelableError::Error(e) => warn!(
+                    logger, "Failed to prefetch blocks";
+                    "from" => from,
+                    "to" => to,
+                    "error" => e.to_string(),
+                ),
+            })
+            .then(move |result| {
+                prefetching.store(false, Ordering::SeqCst);
+                result
+            });
+        tokio::spawn(prefetch);
+    }
+
+    /// Count the blocks in `blocks`, the blocks with triggers up to block
+    /// `to`, that had been prefetched, and forget about the prefetched
+    /// blocks up to `to`, which won't be needed anymore.
+    fn record_prefetched_blocks_used(&self, to: u64, blocks: &[EthereumBlockWithTriggers]) {
+        let mut prefetched_blocks = self.prefetched_blocks.lock().unwrap();
+        let used = blocks
+            .iter()
+            .map(|block| EthereumBlockPointer::from(&block.ethereum_block))
+            .filter(|ptr| prefetched_blocks.get(&ptr.number) == Some(&ptr.hash))
+            .count();
+        *prefetched_blocks = prefetched_blocks.split_off(&to.saturating_add(1));
+        self.metrics.prefetched_blocks_used.inc_by(used as f64);
+    }
+
+    /// Cancel prefetching and forget about the blocks prefetched so far,
+    /// which may belong to a chain that is being reverted.
+    fn cancel_prefetching(&self) {
+        *self.prefetch_guard.lock().unwrap() = CancelGuard::new();
+        self.prefetched_blocks.lock().unwrap().clear();
+    }
+
     /// Perform a reconciliation step.
     fn do_step(
         &self,
@@ -524,6 +770,8 @@ where
             ReconciliationStep::Retry => Box::new(future::ok(ReconciliationStepOutcome::MoreSteps)),
             ReconciliationStep::Done => Box::new(future::ok(ReconciliationStepOutcome::Done)),
             ReconciliationStep::RevertBlock(subgraph_ptr) => {
+                self.cancel_prefetching();
+
                 let metrics = self.metrics.clone();
                 let reverted_block_number = subgraph_ptr.number as f64;
 
@@ -557,7 +805,13 @@ where
                                     )
                                     .map_err(Error::from)
                                     .map(|()| {
+                                        // Forget the contracts that factories created in
+                                        // the reverted block.
+                                        ctx.log_filter
+                                            .factory_children()
+                                            .revert(subgraph_ptr.number);
                                         metrics.reverted_blocks.set(reverted_block_number);
+                                        metrics.set_latest_block(&parent_ptr);
                                         // At this point, the loop repeats, and we try to move
                                         // the subgraph ptr another step in the right direction.
                                         ReconciliationStepOutcome::MoreSteps
@@ -749,6 +1003,25 @@ where
     }
 }
 
+/// Hand each contract created by a factory to the first block at or after
+/// its creation, so that it is recorded, and reverted, together with that
+/// block. Contracts created after the last block go to the last block.
+fn attach_factory_children(
+    mut blocks: Vec<EthereumBlockWithTriggers>,
+    creations: Vec<EthereumContractCreation>,
+) -> Vec<EthereumBlockWithTriggers> {
+    for creation in creations {
+        let position = blocks
+            .iter()
+            .position(|block| block.ethereum_block.number() >= creation.block_number)
+            .or_else(|| blocks.len().checked_sub(1));
+        if let Some(position) = position {
+            blocks[position].factory_children.push(creation);
+        }
+    }
+    blocks
+}
+
 impl<S, C> BlockStreamTrait for BlockStream<S, C>
 where
     S: Store,
@@ -765,6 +1038,20 @@ where
     type Error = Error;
 
     fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        // End the stream once the node shuts down. The stream is only polled
+        // again after the previous block has been processed and the subgraph
+        // pointer moved past it, so no block is left half done
+        let shutting_down = match self.shutdown_signal.as_mut().map(|signal| signal.poll()) {
+            Some(Ok(Async::NotReady)) | None => false,
+            Some(_) => true,
+        };
+        if shutting_down {
+            if self.in_flight_work.take().is_some() {
+                info!(self.ctx.logger, "Stopping block stream for shutdown");
+            }
+            return Ok(Async::Ready(None));
+        }
+
         // Lock Mutex to perform a state transition
         let mut state_lock = self.state.lock().unwrap();
 
@@ -913,10 +1200,12 @@ where
 pub struct BlockStreamBuilder<S, C, M> {
     subgraph_store: Arc<S>,
     chain_stores: HashMap<String, Arc<C>>,
+    chain_head_broadcasters: HashMap<String, ChainHeadBroadcaster>,
     eth_adapters: HashMap<String, Arc<dyn EthereumAdapter>>,
     node_id: NodeId,
     reorg_threshold: u64,
     metrics_registry: Arc<M>,
+    shutdown: Option<ShutdownCoordinator>,
 }
 
 impl<S, C, M> Clone for BlockStreamBuilder<S, C, M> {
@@ -924,10 +1213,12 @@ impl<S, C, M> Clone for BlockStreamBuilder<S, C, M> {
         BlockStreamBuilder {
             subgraph_store: self.subgraph_store.clone(),
             chain_stores: self.chain_stores.clone(),
+            chain_head_broadcasters: self.chain_head_broadcasters.clone(),
             eth_adapters: self.eth_adapters.clone(),
             node_id: self.node_id.clone(),
             reorg_threshold: self.reorg_threshold,
             metrics_registry: self.metrics_registry.clone(),
+            shutdown: self.shutdown.clone(),
         }
     }
 }
@@ -938,9 +1229,13 @@ where
     C: ChainStore,
     M: MetricsRegistry,
 {
+    /// Block streams for networks in `chain_head_broadcasters` get chain head
+    /// updates from the block ingestor in this process; all others listen
+    /// for the updates the chain store receives from other nodes.
     pub fn new(
         subgraph_store: Arc<S>,
         chain_stores: HashMap<String, Arc<C>>,
+        chain_head_broadcasters: HashMap<String, ChainHeadBroadcaster>,
         eth_adapters: HashMap<String, Arc<dyn EthereumAdapter>>,
         node_id: NodeId,
         reorg_threshold: u64,
@@ -949,12 +1244,21 @@ where
         BlockStreamBuilder {
             subgraph_store,
             chain_stores,
+            chain_head_broadcasters,
             eth_adapters,
             node_id,
             reorg_threshold,
             metrics_registry,
+            shutdown: None,
         }
     }
+
+    /// Makes the block streams end when the shutdown coordinated by
+    /// `shutdown` starts, after finishing the block they are processing.
+    pub fn with_shutdown(mut self, shutdown: ShutdownCoordinator) -> Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
 }
 
 impl<S, C, M> BlockStreamBuilderTrait for BlockStreamBuilder<S, C, M>
@@ -997,11 +1301,16 @@ where
                 &network_name
             ))
             .clone();
+        let chain_head_update_stream = match self.chain_head_broadcasters.get(&network_name) {
+            Some(broadcaster) => broadcaster.chain_head_updates(),
+            None => chain_store.chain_head_updates(),
+        };
 
         // Create the actual subgraph-specific block stream
         BlockStream::new(
             self.subgraph_store.clone(),
             chain_store,
+            chain_head_update_stream,
             eth_adapter,
             self.node_id.clone(),
             deployment_id,
@@ -1013,6 +1322,7 @@ where
             self.reorg_threshold,
             logger,
             metrics,
+            self.shutdown.clone(),
         )
     }
 }
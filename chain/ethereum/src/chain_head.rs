@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::watch;
+
+use graph::prelude::*;
+
+/// Shares the chain head of a network with all block streams for that
+/// network in this process.
+///
+/// The block ingestor is the only component that polls the Ethereum node
+/// for the chain head. Whenever it has stored a new head in the chain
+/// store, it broadcasts the head to all subscribers.
+#[derive(Clone)]
+pub struct ChainHeadBroadcaster {
+    network_name: String,
+    sender: Arc<Mutex<watch::Sender<Option<ChainHeadUpdate>>>>,
+    receiver: watch::Receiver<Option<ChainHeadUpdate>>,
+
+    /// Timestamp of the latest block the Ethereum node returned.
+    latest_block_timestamp: Arc<Mutex<Option<u64>>>,
+
+    /// Seconds since the latest block the Ethereum node returned was mined.
+    head_age: Box<Gauge>,
+}
+
+impl ChainHeadBroadcaster {
+    pub fn new<M: MetricsRegistry>(network_name: String, registry: Arc<M>) -> Self {
+        let (sender, receiver) = watch::channel(None);
+        let mut labels = HashMap::new();
+        labels.insert(String::from("network"), network_name.clone());
+        let head_age = registry
+            .new_gauge(
+                String::from("ethereum_chain_head_age"),
+                String::from("Seconds since the latest block of the Ethereum node was mined"),
+                labels,
+            )
+            .expect("failed to create `ethereum_chain_head_age` gauge");
+
+        ChainHeadBroadcaster {
+            network_name,
+            sender: Arc::new(Mutex::new(sender)),
+            receiver,
+            latest_block_timestamp: Arc::new(Mutex::new(None)),
+            head_age,
+        }
+    }
+
+    /// Returns a stream of chain head updates. If a head has been broadcast
+    /// before, the stream starts with the most recent one.
+    pub fn subscribe(&self) -> Box<dyn Stream<Item = ChainHeadUpdate, Error = ()> + Send> {
+        Box::new(
+            self.receiver
+                .clone()
+                .filter_map(|update| update)
+                .map_err(|_| ()),
+        )
+    }
+
+    /// Returns a stream that yields whenever the chain head changes, for
+    /// consumers that read the head from the chain store.
+    pub fn chain_head_updates(&self) -> ChainHeadUpdateStream {
+        Box::new(self.subscribe().map(|_| ()))
+    }
+
+    /// Records the latest block the Ethereum node returned, for the head age
+    /// metric.
+    pub fn observe_latest_block(&self, block: &LightEthereumBlock) {
+        *self.latest_block_timestamp.lock().unwrap() = Some(block.timestamp.as_u64());
+        self.update_head_age();
+    }
+
+    /// Updates the head age metric; called after every poll so that the
+    /// metric keeps growing while the Ethereum node doesn't advance.
+    pub fn update_head_age(&self) {
+        if let Some(timestamp) = *self.latest_block_timestamp.lock().unwrap() {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|now| now.as_secs())
+                .unwrap_or(0);
+            self.head_age.set(now.saturating_sub(timestamp) as f64);
+        }
+    }
+
+    /// Broadcasts `head` to all subscribers, unless it is the head that was
+    /// broadcast last.
+    pub fn broadcast(&self, head: EthereumBlockPointer) {
+        let update = ChainHeadUpdate {
+            network_name: self.network_name.clone(),
+            head_block_hash: head.hash,
+            head_block_number: head.number,
+        };
+
+        let mut sender = self.sender.lock().unwrap();
+        let unchanged = match &*self.receiver.get_ref() {
+            Some(last) => {
+                last.head_block_hash == update.head_block_hash
+                    && last.head_block_number == update.head_block_number
+            }
+            None => false,
+        };
+        if !unchanged {
+            // Broadcasting only fails if there are no receivers, but we
+            // always hold on to one
+            sender.broadcast(Some(update)).ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph::prelude::BlockStreamBuilder as BlockStreamBuilderTrait;
+    use mock::{MockEthereumAdapter, MockMetricsRegistry, MockStore};
+    use web3::types::H256;
+
+    use crate::block_stream::BlockStreamBuilder;
+
+    fn ptr(number: u64) -> EthereumBlockPointer {
+        EthereumBlockPointer {
+            hash: H256::from_low_u64_be(number),
+            number,
+        }
+    }
+
+    fn next_update(
+        stream: &mut Box<dyn Stream<Item = ChainHeadUpdate, Error = ()> + Send>,
+    ) -> Option<u64> {
+        match stream.poll() {
+            Ok(Async::Ready(Some(update))) => Some(update.head_block_number),
+            Ok(Async::NotReady) => None,
+            result => panic!("unexpected chain head update stream result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn all_subscribers_receive_chain_head_updates() {
+        let broadcaster = ChainHeadBroadcaster::new(
+            String::from("mainnet"),
+            Arc::new(MockMetricsRegistry::new()),
+        );
+        let mut first = broadcaster.subscribe();
+        let mut second = broadcaster.subscribe();
+
+        future::lazy(move || {
+            // Nothing has been broadcast yet
+            assert_eq!(next_update(&mut first), None);
+            assert_eq!(next_update(&mut second), None);
+
+            broadcaster.broadcast(ptr(1));
+            assert_eq!(next_update(&mut first), Some(1));
+            assert_eq!(next_update(&mut second), Some(1));
+
+            // Broadcasting the same head again doesn't wake up subscribers
+            broadcaster.broadcast(ptr(1));
+            assert_eq!(next_update(&mut first), None);
+            assert_eq!(next_update(&mut second), None);
+
+            // Subscribers that fall behind only see the latest head
+            broadcaster.broadcast(ptr(2));
+            assert_eq!(next_update(&mut first), Some(2));
+            broadcaster.broadcast(ptr(3));
+            assert_eq!(next_update(&mut first), Some(3));
+            assert_eq!(next_update(&mut second), Some(3));
+            assert_eq!(next_update(&mut second), None);
+
+            // Late subscribers start with the current head
+            let mut late = broadcaster.subscribe();
+            assert_eq!(next_update(&mut late), Some(3));
+
+            Ok::<(), ()>(())
+        })
+        .wait()
+        .unwrap();
+    }
+
+    #[test]
+    fn block_streams_share_the_chain_head_poller() {
+        let logger = Logger::root(slog::Discard, o!());
+        let registry = Arc::new(MockMetricsRegistry::new());
+        let store = Arc::new(MockStore::new(vec![]));
+        let broadcaster = ChainHeadBroadcaster::new(String::from("mainnet"), registry.clone());
+
+        let networks = vec![String::from("mainnet"), String::from("ropsten")];
+        let eth_adapter: Arc<dyn EthereumAdapter> = Arc::new(MockEthereumAdapter::default());
+        let builder = BlockStreamBuilder::new(
+            store.clone(),
+            networks
+                .iter()
+                .map(|network| (network.clone(), store.clone()))
+                .collect(),
+            vec![(String::from("mainnet"), broadcaster)]
+                .into_iter()
+                .collect(),
+            networks
+                .iter()
+                .map(|network| (network.clone(), eth_adapter.clone()))
+                .collect(),
+            NodeId::new("test").unwrap(),
+            50,
+            registry.clone(),
+        );
+        let build = |network: &str| {
+            let deployment_id = SubgraphDeploymentId::new("chainHead").unwrap();
+            let metrics = BlockStreamMetrics::new(
+                registry.clone(),
+                Arc::new(SubgraphEthRpcMetrics::new(
+                    registry.clone(),
+                    String::from("chainHead"),
+                )),
+                deployment_id.clone(),
+                network.to_owned(),
+                StopwatchMetrics::new(logger.clone(), deployment_id.clone(), registry.clone()),
+            );
+            builder.build(
+                logger.clone(),
+                deployment_id,
+                network.to_owned(),
+                vec![],
+                EthereumLogFilter::default(),
+                EthereumCallFilter::from_iter(vec![]),
+                EthereumBlockFilter::default(),
+                false,
+                Arc::new(metrics),
+            )
+        };
+
+        // Block streams for a network whose blocks are ingested in this
+        // process only subscribe to the broadcaster, so the block ingestor
+        // remains the only component that polls for the chain head
+        let _first = build("mainnet");
+        let _second = build("mainnet");
+        assert_eq!(store.chain_head_listeners(), 0);
+
+        // Block streams for other networks listen for chain head updates
+        // themselves
+        let _third = build("ropsten");
+        assert_eq!(store.chain_head_listeners(), 1);
+    }
+}
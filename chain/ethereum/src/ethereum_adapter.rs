@@ -1,9 +1,12 @@
 use ethabi::Token;
-use futures::future;
+use futures::future::{self, Shared};
 use futures::prelude::*;
 use lazy_static::lazy_static;
-use std::collections::HashSet;
-use std::sync::Arc;
+use std::cmp;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
 use std::time::Instant;
 
 use ethabi::ParamType;
@@ -11,13 +14,151 @@ use graph::components::ethereum::{EthereumAdapter as EthereumAdapterTrait, *};
 use graph::prelude::*;
 use web3;
 use web3::api::Web3;
+use web3::helpers::{self, CallFuture};
 use web3::transports::batch::Batch;
 use web3::types::{Filter, *};
+use web3::Transport;
 
 #[derive(Clone)]
 pub struct EthereumAdapter<T: web3::Transport> {
     web3: Arc<Web3<T>>,
     metrics: Arc<ProviderEthRpcMetrics>,
+    /// The highest chain head block number seen so far, used to decide
+    /// whether call results are final enough to be cached.
+    chain_head_number: Arc<Mutex<u64>>,
+    /// Number of blocks behind the chain head after which a block can no
+    /// longer be reorged away.
+    reorg_threshold: u64,
+    /// Results of recent requests for final blocks, used to detect a provider
+    /// that answers the same request differently when it is retried. `None`
+    /// unless the consistency check is enabled.
+    recent_results: Option<Arc<Mutex<RecentResults>>>,
+    /// Contract calls that have been sent but not answered yet, by address,
+    /// call data and block. Identical calls made in the meantime share the
+    /// result of the call in flight.
+    in_flight_calls: Arc<Mutex<HashMap<CallKey, InFlightCall>>>,
+    /// Whether the Ethereum node supports `trace_filter`.
+    traces: bool,
+    /// Whether the Ethereum node keeps the state of all blocks, once
+    /// `is_archive` found out.
+    archive: Arc<Mutex<Option<bool>>>,
+}
+
+type CallKey = (Address, Vec<u8>, EthereumBlockPointer);
+
+type InFlightCall =
+    Shared<Box<dyn Future<Item = Vec<u8>, Error = EthereumContractCallError> + Send>>;
+
+/// Copies an error of a call in flight for each of the calls sharing it.
+fn clone_call_error(e: &EthereumContractCallError) -> EthereumContractCallError {
+    use EthereumContractCallError::*;
+
+    match e {
+        ABIError(e) => ABIError(e.clone()),
+        TypeError(token, kind) => TypeError(token.clone(), kind.clone()),
+        Web3Error(web3::Error::Rpc(e)) => Web3Error(web3::Error::Rpc(e.clone())),
+        Web3Error(e) => Web3Error(web3::Error::Transport(e.to_string())),
+        Revert(reason) => Revert(reason.clone()),
+        Timeout => Timeout,
+    }
+}
+
+/// Which end of a call the addresses passed to `trace_filter` refer to.
+#[derive(Clone, Debug)]
+enum TraceAddresses {
+    /// Traces of calls to one of these addresses; all traces if empty.
+    To(Vec<H160>),
+    /// Traces of calls made by one of these addresses.
+    From(Vec<H160>),
+}
+
+/// Number of requests whose results are remembered for the consistency check.
+const RECENT_RESULTS_CAPACITY: usize = 1000;
+
+/// Number of blocks `load_blocks` loads per request it keeps in flight.
+const BLOCKS_PER_LOAD_REQUEST: usize = 4;
+
+/// Number of transaction receipts requested in one JSON-RPC batch.
+const RECEIPT_BATCH_SIZE: usize = 100;
+
+/// The block whose state `is_archive` asks for. Every Ethereum node has the
+/// state of recent blocks, but only archive nodes keep it for the first ones.
+const ARCHIVE_PROBE_BLOCK: u64 = 1;
+
+/// Parts of the error messages with which Ethereum nodes refuse requests for
+/// state they have pruned.
+const PRUNED_STATE_FINGERPRINTS: &[&str] = &[
+    // Geth
+    "missing trie node",
+    "header not found",
+    // Parity/OpenEthereum
+    "running with state pruning",
+    // Nethermind, Erigon
+    "state is not available",
+    "state not available",
+];
+
+/// What the consistency check remembers about the result of a request.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct ResultFingerprint {
+    count: usize,
+    hash: u64,
+}
+
+impl ResultFingerprint {
+    /// The fingerprint of a result given the hashes of its items, in any order.
+    fn new(item_hashes: impl Iterator<Item = u64>) -> Self {
+        let mut item_hashes: Vec<_> = item_hashes.collect();
+        item_hashes.sort();
+        ResultFingerprint {
+            count: item_hashes.len(),
+            hash: hash_of(&item_hashes),
+        }
+    }
+}
+
+/// The fingerprints of the results of the most recent requests, oldest first.
+#[derive(Default)]
+struct RecentResults(VecDeque<(u64, ResultFingerprint)>);
+
+impl RecentResults {
+    /// Remembers `result` as the latest result of `request` and returns the
+    /// result of the previous attempt if it was different.
+    fn record(&mut self, request: u64, result: ResultFingerprint) -> Option<ResultFingerprint> {
+        let previous = match self.0.iter().position(|(r, _)| *r == request) {
+            Some(index) => self.0.remove(index).map(|(_, previous)| previous),
+            None => None,
+        };
+        if self.0.len() >= RECENT_RESULTS_CAPACITY {
+            self.0.pop_front();
+        }
+        self.0.push_back((request, result));
+        previous.filter(|previous| *previous != result)
+    }
+}
+
+fn hash_of(value: impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Identifies the `eth_getLogs` calls made for `filters` regardless of the
+/// order in which their contracts and events were collected, so that the
+/// same calls are recognized across log filters and providers.
+fn eth_get_logs_key(filters: &[EthGetLogsFilter]) -> Vec<(Vec<Address>, Vec<EventSignature>)> {
+    let mut key: Vec<_> = filters
+        .iter()
+        .map(|filter| {
+            let mut contracts = filter.contracts.clone();
+            let mut event_signatures = filter.event_signatures.clone();
+            contracts.sort();
+            event_signatures.sort();
+            (contracts, event_signatures)
+        })
+        .collect();
+    key.sort();
+    key
 }
 
 lazy_static! {
@@ -65,16 +206,225 @@ where
         EthereumAdapter {
             web3: Arc::new(Web3::new(transport)),
             metrics: provider_metrics,
+            chain_head_number: Arc::new(Mutex::new(0)),
+            reorg_threshold: 50,
+            recent_results: None,
+            in_flight_calls: Default::default(),
+            traces: true,
+            archive: Default::default(),
         }
     }
 
+    /// Sets the number of blocks behind the chain head after which blocks
+    /// are considered final, 50 by default. Only final blocks have their
+    /// call results cached and are subject to the consistency check.
+    pub fn with_reorg_threshold(mut self, reorg_threshold: u64) -> Self {
+        self.reorg_threshold = reorg_threshold;
+        self
+    }
+
+    /// Marks the Ethereum node as unable to trace calls, so that subgraphs
+    /// with call handlers or block handlers with a call filter are not
+    /// started against it.
+    pub fn without_traces(mut self) -> Self {
+        self.traces = false;
+        self
+    }
+
+    /// Enables the consistency check: when a request for logs or calls in
+    /// final blocks is retried and the Ethereum node returns a different
+    /// result than on the previous attempt, the request fails with
+    /// `EthereumAdapterError::InconsistentResults` instead of returning
+    /// either result. The check is off by default since every such request
+    /// then also needs to know whether its blocks are final.
+    pub fn with_consistency_check(mut self) -> Self {
+        self.recent_results = Some(Default::default());
+        self
+    }
+
+    /// Applies the consistency check, if enabled, to `result`, the result of
+    /// `request` for the blocks `[from, to]`.
+    fn check_consistency<I, F>(
+        &self,
+        logger: &Logger,
+        method: &'static str,
+        request: u64,
+        from: u64,
+        to: u64,
+        result: F,
+        item_hash: fn(&I) -> u64,
+    ) -> Box<dyn Future<Item = Vec<I>, Error = Error> + Send>
+    where
+        I: Send + 'static,
+        F: Future<Item = Vec<I>, Error = Error> + Send + 'static,
+    {
+        let recent_results = match &self.recent_results {
+            Some(recent_results) => recent_results.clone(),
+            None => return Box::new(result),
+        };
+        let metrics = self.metrics.clone();
+
+        // Results for blocks that may still be reorged away can legitimately
+        // change between attempts
+        Box::new(self.block_is_final(logger, to).and_then(move |is_final| {
+            result.and_then(move |items| {
+                if !is_final {
+                    return Ok(items);
+                }
+
+                let current = ResultFingerprint::new(items.iter().map(item_hash));
+                match recent_results.lock().unwrap().record(request, current) {
+                    Some(previous) => {
+                        metrics.add_inconsistency(method);
+                        Err(EthereumAdapterError::InconsistentResults {
+                            method,
+                            from,
+                            to,
+                            previous: previous.count,
+                            current: current.count,
+                        }
+                        .into())
+                    }
+                    None => Ok(items),
+                }
+            })
+        }))
+    }
+
+    /// Returns whether the block with the given number is at least
+    /// `reorg_threshold` blocks behind the chain head. The chain head is the
+    /// highest one seen so far, e.g. by `latest_block`; it is only requested
+    /// from the Ethereum node if none has been seen yet.
+    fn block_is_final(
+        &self,
+        logger: &Logger,
+        block_number: u64,
+    ) -> Box<dyn Future<Item = bool, Error = Error> + Send> {
+        let reorg_threshold = self.reorg_threshold;
+        let is_final = move |head: u64| block_number.saturating_add(reorg_threshold) <= head;
+
+        let head = *self.chain_head_number.lock().unwrap();
+        if head > 0 {
+            return Box::new(future::ok(is_final(head)));
+        }
+
+        let web3 = self.web3.clone();
+        let eth = self.clone();
+        Box::new(
+            retry("eth_blockNumber RPC call", logger)
+                .limit(16)
+                .timeout_secs(*JSON_RPC_TIMEOUT)
+                .run(move || web3.eth().block_number().from_err())
+                .map_err(|e| {
+                    e.into_inner().unwrap_or_else(|| {
+                        format_err!("Ethereum node took too long to return the chain head")
+                    })
+                })
+                .map(move |head| is_final(eth.saw_chain_head(head.as_u64()))),
+        )
+    }
+
+    /// Remembers `head` if it is the highest chain head seen so far and
+    /// returns the highest one.
+    fn saw_chain_head(&self, head: u64) -> u64 {
+        let mut chain_head_number = self.chain_head_number.lock().unwrap();
+        *chain_head_number = cmp::max(*chain_head_number, head);
+        *chain_head_number
+    }
+
+    /// Makes a contract call through the call cache: a cached result is
+    /// returned if there is one, otherwise the result is requested from the
+    /// Ethereum node and cached.
+    fn cached_call(
+        &self,
+        logger: &Logger,
+        call: EthereumContractCall,
+        call_data: Vec<u8>,
+        cache: Arc<dyn EthereumCallCache>,
+    ) -> Box<dyn Future<Item = Vec<u8>, Error = EthereumContractCallError> + Send> {
+        match cache
+            .get_call(call.address, &call_data, call.block_ptr)
+            .map_err(|e| error!(logger, "call cache get error"; "error" => e.to_string()))
+            .ok()
+            .and_then(|x| x)
+        {
+            Some(result) => Box::new(future::ok(result)),
+            None => {
+                let logger = logger.clone();
+                Box::new(
+                    self.call(
+                        &logger,
+                        call.address,
+                        Bytes(call_data.clone()),
+                        Some(call.block_ptr.number.into()),
+                    )
+                    .map(move |result| {
+                        let _ = cache
+                            .set_call(call.address, &call_data, call.block_ptr, &result.0)
+                            .map_err(|e| {
+                                error!(logger, "call cache set error";
+                                               "error" => e.to_string())
+                            });
+                        result.0
+                    }),
+                )
+            }
+        }
+    }
+
+    /// Fetches the output of `call`, from the call cache if its block is
+    /// final.
+    fn call_output(
+        &self,
+        logger: &Logger,
+        call: EthereumContractCall,
+        call_data: Vec<u8>,
+        cache: Arc<dyn EthereumCallCache>,
+    ) -> impl Future<Item = Vec<u8>, Error = EthereumContractCallError> + Send {
+        // Results of calls against blocks that may still be reorged away must
+        // not be cached, the block and with it the result may change. Such
+        // calls therefore bypass the cache entirely.
+        let eth = self.clone();
+        let logger = logger.clone();
+        let logger_for_finality = logger.clone();
+        self.block_is_final(&logger, call.block_ptr.number)
+            .then(move |result| {
+                Ok(result.unwrap_or_else(|e| {
+                    error!(logger_for_finality, "call cache finality check error";
+                           "error" => e.to_string());
+                    false
+                }))
+            })
+            .and_then(move |is_final| {
+                if is_final {
+                    eth.cached_call(&logger, call, call_data, cache)
+                } else {
+                    debug!(
+                        logger,
+                        "Not using the call cache for a block that is not final";
+                        "block_number" => call.block_ptr.number,
+                        "contract" => format!("{:x}", call.address),
+                    );
+                    Box::new(
+                        eth.call(
+                            &logger,
+                            call.address,
+                            Bytes(call_data),
+                            Some(call.block_ptr.number.into()),
+                        )
+                        .map(|result| result.0),
+                    ) as Box<dyn Future<Item = _, Error = _> + Send>
+                }
+            })
+    }
+
     fn traces(
         &self,
         logger: &Logger,
         subgraph_metrics: Arc<SubgraphEthRpcMetrics>,
         from: u64,
         to: u64,
-        addresses: Vec<H160>,
+        addresses: TraceAddresses,
     ) -> impl Future<Item = Vec<Trace>, Error = Error> {
         let eth = self.clone();
         let logger = logger.to_owned();
@@ -83,15 +433,22 @@ where
             .no_limit()
             .timeout_secs(*JSON_RPC_TIMEOUT)
             .run(move || {
-                let trace_filter: TraceFilter = match addresses.len() {
-                    0 => TraceFilterBuilder::default()
+                let trace_filter: TraceFilter = match &addresses {
+                    TraceAddresses::To(addresses) if addresses.is_empty() => {
+                        TraceFilterBuilder::default()
+                            .from_block(from.into())
+                            .to_block(to.into())
+                            .build()
+                    }
+                    TraceAddresses::To(addresses) => TraceFilterBuilder::default()
                         .from_block(from.into())
                         .to_block(to.into())
+                        .to_address(addresses.clone())
                         .build(),
-                    _ => TraceFilterBuilder::default()
+                    TraceAddresses::From(addresses) => TraceFilterBuilder::default()
                         .from_block(from.into())
                         .to_block(to.into())
-                        .to_address(addresses.clone())
+                        .from_address(addresses.clone())
                         .build(),
                 };
 
@@ -208,7 +565,7 @@ where
         subgraph_metrics: Arc<SubgraphEthRpcMetrics>,
         from: u64,
         to: u64,
-        addresses: Vec<H160>,
+        addresses: TraceAddresses,
     ) -> impl Stream<Item = Trace, Error = Error> + Send {
         if from > to {
             panic!(
@@ -484,10 +841,14 @@ where
     }
 
     /// Request blocks by hash through JSON-RPC.
+    ///
+    /// Blocks are requested with up to `concurrency` requests in flight and
+    /// returned in the order in which they arrive.
     fn load_blocks_rpc(
         &self,
         logger: Logger,
         ids: Vec<H256>,
+        concurrency: usize,
     ) -> impl Stream<Item = LightEthereumBlock, Error = Error> + Send {
         let web3 = self.web3.clone();
 
@@ -503,23 +864,23 @@ where
                         .map_err(|e| e.compat())
                         .and_then(move |block| {
                             block.ok_or_else(|| {
-                                format_err!("Ethereum node did not find block {:?}", hash).compat()
+                                format_err!("Ethereum node did not find block {:#x}", hash).compat()
                             })
                         })
                 })
                 .from_err()
         }))
-        .buffered(*BLOCK_BATCH_SIZE)
+        .buffer_unordered(concurrency)
     }
 
     /// Request blocks ptrs for numbers through JSON-RPC.
     ///
     /// Reorg safety: If ids are numbers, they must be a final blocks.
-    fn load_block_ptrs_rpc(
+    fn load_blocks_by_number_rpc(
         &self,
         logger: Logger,
         block_nums: Vec<u64>,
-    ) -> impl Stream<Item = EthereumBlockPointer, Error = Error> + Send {
+    ) -> impl Stream<Item = Block<H256>, Error = Error> + Send {
         let web3 = self.web3.clone();
 
         stream::iter_ok::<_, Error>(block_nums.into_iter().map(move |block_num| {
@@ -542,7 +903,42 @@ where
                 .from_err()
         }))
         .buffered(*BLOCK_BATCH_SIZE)
-        .map(|b| b.into())
+    }
+
+    /// Request the receipts of the transactions `hashes` through JSON-RPC,
+    /// in batches of `RECEIPT_BATCH_SIZE` with up to `ETHEREUM_BLOCK_BATCH_SIZE`
+    /// batches in flight. Receipts are returned in the order of `hashes`.
+    fn transaction_receipts_rpc(
+        web3: Arc<Web3<T>>,
+        hashes: Vec<H256>,
+    ) -> impl Future<Item = Vec<Option<TransactionReceipt>>, Error = Error> + Send {
+        let batches: Vec<Vec<H256>> = hashes
+            .chunks(RECEIPT_BATCH_SIZE)
+            .map(|batch| batch.to_vec())
+            .collect();
+
+        // Empty batches are not valid in JSON-RPC, but there are none since
+        // `chunks` never yields an empty slice
+        stream::iter_ok::<_, Error>(batches.into_iter().map(move |batch| {
+            let batching_web3 = Web3::new(Batch::new(web3.transport().clone()));
+            let receipt_futures = batch
+                .into_iter()
+                .map(|tx_hash| {
+                    batching_web3
+                        .eth()
+                        .transaction_receipt(tx_hash)
+                        .from_err::<Error>()
+                })
+                .collect::<Vec<_>>();
+
+            batching_web3
+                .transport()
+                .submit_batch()
+                .from_err::<Error>()
+                .and_then(move |_| stream::futures_ordered(receipt_futures).collect())
+        }))
+        .buffered(*BLOCK_BATCH_SIZE)
+        .concat2()
     }
 }
 
@@ -600,11 +996,72 @@ where
         )
     }
 
+    fn capabilities(&self) -> NodeCapabilities {
+        NodeCapabilities {
+            traces: self.traces,
+        }
+    }
+
+    fn is_archive(&self, logger: &Logger) -> Box<dyn Future<Item = bool, Error = Error> + Send> {
+        if let Some(archive) = *self.archive.lock().unwrap() {
+            return Box::new(future::ok(archive));
+        }
+
+        let web3 = self.web3.clone();
+        let archive = self.archive.clone();
+
+        Box::new(
+            retry("eth_getBalance RPC call", logger)
+                .limit(16)
+                .timeout_secs(*JSON_RPC_TIMEOUT)
+                .run(move || {
+                    web3.eth()
+                        .balance(
+                            Address::zero(),
+                            Some(BlockNumber::Number(ARCHIVE_PROBE_BLOCK)),
+                        )
+                        .then(|result| -> Result<bool, Error> {
+                            match result {
+                                Ok(_) => Ok(true),
+                                Err(e) => {
+                                    let message = e.to_string();
+                                    if PRUNED_STATE_FINGERPRINTS
+                                        .iter()
+                                        .any(|fingerprint| message.contains(fingerprint))
+                                    {
+                                        Ok(false)
+                                    } else {
+                                        Err(e.into())
+                                    }
+                                }
+                            }
+                        })
+                })
+                .map_err(|e| {
+                    e.into_inner().unwrap_or_else(|| {
+                        format_err!(
+                            "Ethereum node took too long to return the state of block {}",
+                            ARCHIVE_PROBE_BLOCK
+                        )
+                    })
+                })
+                .map(move |is_archive| {
+                    *archive.lock().unwrap() = Some(is_archive);
+                    is_archive
+                }),
+        )
+    }
+
+    fn known_archive(&self) -> Option<bool> {
+        *self.archive.lock().unwrap()
+    }
+
     fn latest_block(
         &self,
         logger: &Logger,
     ) -> Box<dyn Future<Item = LightEthereumBlock, Error = EthereumAdapterError> + Send> {
         let web3 = self.web3.clone();
+        let eth = self.clone();
 
         Box::new(
             retry("eth_getBlockByNumber(latest) RPC call", logger)
@@ -625,6 +1082,12 @@ where
                     e.into_inner().unwrap_or_else(move || {
                         format_err!("Ethereum node took too long to return latest block").into()
                     })
+                })
+                .map(move |block| {
+                    if let Some(number) = block.number {
+                        eth.saw_chain_head(number.as_u64());
+                    }
+                    block
                 }),
         )
     }
@@ -666,7 +1129,10 @@ where
                 })
                 .map_err(move |e| {
                     e.into_inner().unwrap_or_else(move || {
-                        format_err!("Ethereum node took too long to return block {}", block_hash)
+                        format_err!(
+                            "Ethereum node took too long to return block {:#x}",
+                            block_hash
+                        )
                     })
                 }),
         )
@@ -690,6 +1156,7 @@ where
             }));
         }
         let web3 = self.web3.clone();
+        let tx_hashes: Vec<H256> = block.transactions.iter().map(|tx| tx.hash).collect();
 
         // Retry, but eventually give up.
         // A receipt might be missing because the block was uncled, and the
@@ -701,37 +1168,31 @@ where
                 .timeout_secs(*JSON_RPC_TIMEOUT)
                 .run(move || {
                     let block = block.clone();
-                    let batching_web3 = Web3::new(Batch::new(web3.transport().clone()));
-
-                    let receipt_futures = block
-                        .transactions
-                        .iter()
-                        .map(|tx| {
-                            let logger = logger.clone();
-                            let tx_hash = tx.hash;
-
-                            batching_web3
-                                .eth()
-                                .transaction_receipt(tx_hash)
-                                .from_err()
-                                .map_err(EthereumAdapterError::Unknown)
-                                .and_then(move |receipt_opt| {
-                                    receipt_opt.ok_or_else(move || {
-                                        // No receipt was returned.
-                                        //
-                                        // This can be because the Ethereum node no longer
-                                        // considers this block to be part of the main chain,
-                                        // and so the transaction is no longer in the main
-                                        // chain.  Nothing we can do from here except give up
-                                        // trying to ingest this block.
-                                        //
-                                        // This could also be because the receipt is simply not
-                                        // available yet.  For that case, we should retry until
-                                        // it becomes available.
+                    let logger = logger.clone();
+                    let tx_hashes = tx_hashes.clone();
+
+                    Self::transaction_receipts_rpc(web3.clone(), tx_hashes.clone())
+                        .map_err(EthereumAdapterError::Unknown)
+                        .and_then(move |receipts| -> Result<_, EthereumAdapterError> {
+                            let transaction_receipts = receipts
+                                .into_iter()
+                                .zip(tx_hashes)
+                                .map(|(receipt_opt, tx_hash)| {
+                                    // No receipt was returned.
+                                    //
+                                    // This can be because the Ethereum node no longer
+                                    // considers this block to be part of the main chain,
+                                    // and so the transaction is no longer in the main
+                                    // chain.  Nothing we can do from here except give up
+                                    // trying to ingest this block.
+                                    //
+                                    // This could also be because the receipt is simply not
+                                    // available yet.  For that case, we should retry until
+                                    // it becomes available.
+                                    let receipt = receipt_opt.ok_or_else(|| {
                                         EthereumAdapterError::BlockUnavailable(block_hash)
-                                    })
-                                })
-                                .and_then(move |receipt| {
+                                    })?;
+
                                     // Parity nodes seem to return receipts with no block hash
                                     // when a transaction is no longer in the main chain, so
                                     // treat that case the same as a receipt being absent
@@ -763,21 +1224,12 @@ where
                                         Ok(receipt)
                                     }
                                 })
-                        })
-                        .collect::<Vec<_>>();
+                                .collect::<Result<Vec<_>, _>>()?;
 
-                    batching_web3
-                        .transport()
-                        .submit_batch()
-                        .from_err()
-                        .map_err(EthereumAdapterError::Unknown)
-                        .and_then(move |_| {
-                            stream::futures_ordered(receipt_futures).collect().map(
-                                move |transaction_receipts| EthereumBlock {
-                                    block,
-                                    transaction_receipts,
-                                },
-                            )
+                            Ok(EthereumBlock {
+                                block,
+                                transaction_receipts,
+                            })
                         })
                 })
                 .map_err(move |e| {
@@ -792,6 +1244,107 @@ where
         )
     }
 
+    fn transaction_receipts(
+        &self,
+        logger: &Logger,
+        hashes: Vec<H256>,
+        in_final_blocks: bool,
+    ) -> Box<dyn Future<Item = Vec<Option<TransactionReceipt>>, Error = EthereumAdapterError> + Send>
+    {
+        let web3 = self.web3.clone();
+        let count = hashes.len();
+
+        Box::new(
+            retry("batch eth_getTransactionReceipt RPC call", &logger)
+                .limit(16)
+                .timeout_secs(*JSON_RPC_TIMEOUT)
+                .run({
+                    let hashes = hashes.clone();
+                    move || Self::transaction_receipts_rpc(web3.clone(), hashes.clone())
+                })
+                .map_err(move |e| {
+                    e.into_inner().unwrap_or_else(move || {
+                        format_err!(
+                            "Ethereum node took too long to return receipts for {} transaction(s)",
+                            count
+                        )
+                    })
+                })
+                .from_err()
+                .and_then(move |receipts| {
+                    if in_final_blocks {
+                        // Final blocks can't be reorged away, so the receipt
+                        // of every transaction in them must exist
+                        if let Some((_, tx_hash)) = receipts
+                            .iter()
+                            .zip(&hashes)
+                            .find(|(receipt, _)| receipt.is_none())
+                        {
+                            return Err(EthereumAdapterError::ReceiptUnavailable(*tx_hash));
+                        }
+                    }
+                    Ok(receipts)
+                }),
+        )
+    }
+
+    fn logs_for_transaction(
+        &self,
+        logger: &Logger,
+        tx_hash: H256,
+    ) -> Box<dyn Future<Item = Vec<Log>, Error = EthereumAdapterError> + Send> {
+        let web3 = self.web3.clone();
+
+        Box::new(
+            retry("eth_getTransactionReceipt RPC call", &logger)
+                .limit(16)
+                .timeout_secs(*JSON_RPC_TIMEOUT)
+                .run(move || web3.eth().transaction_receipt(tx_hash).from_err())
+                .map_err(move |e| {
+                    e.into_inner().unwrap_or_else(move || {
+                        format_err!(
+                            "Ethereum node took too long to return receipt for transaction {:#x}",
+                            tx_hash
+                        )
+                    })
+                })
+                .from_err()
+                .and_then(move |receipt_opt| {
+                    receipt_opt
+                        .map(|receipt| receipt.logs)
+                        .ok_or_else(|| EthereumAdapterError::ReceiptUnavailable(tx_hash))
+                }),
+        )
+    }
+
+    fn transaction_by_hash(
+        &self,
+        logger: &Logger,
+        tx_hash: H256,
+    ) -> Box<dyn Future<Item = Option<Transaction>, Error = EthereumAdapterError> + Send> {
+        let web3 = self.web3.clone();
+
+        Box::new(
+            retry("eth_getTransactionByHash RPC call", &logger)
+                .limit(16)
+                .timeout_secs(*JSON_RPC_TIMEOUT)
+                .run(move || {
+                    web3.eth()
+                        .transaction(TransactionId::Hash(tx_hash))
+                        .from_err()
+                })
+                .map_err(move |e| {
+                    e.into_inner().unwrap_or_else(move || {
+                        format_err!(
+                            "Ethereum node took too long to return transaction {:#x}",
+                            tx_hash
+                        )
+                    })
+                })
+                .from_err(),
+        )
+    }
+
     fn block_pointer_from_number(
         &self,
         logger: &Logger,
@@ -869,7 +1422,7 @@ where
         block_hash: H256,
     ) -> Box<dyn Future<Item = Vec<EthereumCall>, Error = Error> + Send> {
         let eth = self.clone();
-        let addresses = Vec::new();
+        let addresses = TraceAddresses::To(Vec::new());
         let calls = eth
             .trace_stream(
                 &logger,
@@ -910,7 +1463,23 @@ where
                     .filter_map(EthereumCall::try_from_trace)
                     .collect()
             });
-        Box::new(calls)
+        self.check_consistency(
+            logger,
+            "trace_filter",
+            hash_of(("trace_filter", block_number, block_hash)),
+            block_number,
+            block_number,
+            calls,
+            |call: &EthereumCall| {
+                hash_of((
+                    call.transaction_hash,
+                    call.from,
+                    call.to,
+                    &call.input.0,
+                    &call.output.0,
+                ))
+            },
+        )
     }
 
     fn logs_in_block_range(
@@ -920,16 +1489,82 @@ where
         from: u64,
         to: u64,
         log_filter: EthereumLogFilter,
+        cancel: Option<CancelHandle>,
     ) -> Box<dyn Future<Item = Vec<Log>, Error = Error> + Send> {
         let eth = self.clone();
         let logger = logger.clone();
-        Box::new(
-            stream::iter_ok(log_filter.eth_get_logs_filters().map(move |filter| {
-                eth.log_stream(logger.clone(), subgraph_metrics.clone(), from, to, filter)
-            }))
-            .buffered(*LOG_STREAM_PARALLEL_CHUNKS as usize)
-            .concat2(),
-        )
+
+        if log_filter.strategy() == LogFilterStrategy::MergeByNeighbors {
+            debug!(
+                logger,
+                "Log filter is too large for the default strategy, \
+                 merging contracts with the same events instead";
+                "edges" => log_filter.edge_count(),
+                "nodes" => log_filter.node_count(),
+            );
+        }
+
+        // Filters of contracts that all start after the range can't match
+        // any of its logs. Count what is requested so that errors can say
+        // which request failed
+        let filters: Vec<_> = log_filter
+            .eth_get_logs_filters()
+            .filter(|filter| filter.start_block <= to)
+            .collect();
+        let request = hash_of(("eth_getLogs", from, to, eth_get_logs_key(&filters)));
+        let contract_count = filters
+            .iter()
+            .flat_map(|filter| filter.contracts.iter())
+            .collect::<HashSet<_>>()
+            .len();
+        let event_signature_count = filters
+            .iter()
+            .flat_map(|filter| filter.event_signatures.iter())
+            .collect::<HashSet<_>>()
+            .len();
+
+        let logger1 = logger.clone();
+        let logger2 = logger.clone();
+        let logs = stream::iter_ok(filters.into_iter().map(move |filter| {
+            eth.log_stream(logger1.clone(), subgraph_metrics.clone(), from, to, filter)
+        }))
+        .buffered(*LOG_STREAM_PARALLEL_CHUNKS as usize)
+        .concat2()
+        .map_err(move |e| {
+            warn!(
+                logger2,
+                "Failed to get logs";
+                "from" => from,
+                "to" => to,
+                "contracts" => contract_count,
+                "event_signatures" => event_signature_count,
+                "error" => e.to_string(),
+            );
+            let context = format!(
+                "Failed to get logs for blocks [{}, {}] of {} contracts with {} event \
+                 signatures: {}",
+                from, to, contract_count, event_signature_count, e
+            );
+            e.context(context).into()
+        });
+
+        let logs = self.check_consistency(
+            &logger,
+            "eth_getLogs",
+            request,
+            from,
+            to,
+            logs,
+            |log: &Log| hash_of((log.block_hash, log.transaction_hash, log.log_index)),
+        );
+
+        // Dropping the requests when the guard is canceled aborts them
+        match cancel {
+            Some(cancel) => Box::new(logs.cancelable(&cancel, move || {
+                EthereumAdapterError::Canceled(from, to).into()
+            })),
+            None => logs,
+        }
     }
 
     fn calls_in_block_range(
@@ -942,24 +1577,69 @@ where
     ) -> Box<dyn Stream<Item = EthereumCall, Error = Error> + Send> {
         let eth = self.clone();
 
+        // Contracts that start after the range can't have been called in it,
+        // and there is nothing to trace before the earliest start block of
+        // the remaining ones
+        let call_filter = call_filter.started_by(to);
+        let from = match call_filter
+            .contract_addresses_function_signatures
+            .values()
+            .map(|(start_block, _fsigs)| *start_block)
+            .min()
+        {
+            Some(start_block) => cmp::max(from, start_block),
+            // An empty address list would trace calls to all contracts
+            None => return Box::new(stream::empty()),
+        };
+
         let addresses: Vec<H160> = call_filter
             .contract_addresses_function_signatures
-            .iter()
-            .filter(|(_addr, (start_block, _fsigs))| start_block <= &to)
-            .map(|(addr, (_start_block, _fsigs))| *addr)
-            .collect::<HashSet<H160>>()
-            .into_iter()
-            .collect::<Vec<H160>>();
+            .keys()
+            .cloned()
+            .collect();
         Box::new(
-            eth.trace_stream(&logger, subgraph_metrics, from, to, addresses)
-                .filter_map(|trace| EthereumCall::try_from_trace(&trace))
-                .filter(move |call| {
-                    // `trace_filter` can only filter by calls `to` an address and
-                    // a block range. Since subgraphs are subscribing to calls
-                    // for a specific contract function an additional filter needs
-                    // to be applied
-                    call_filter.matches(&call)
-                }),
+            eth.trace_stream(
+                &logger,
+                subgraph_metrics,
+                from,
+                to,
+                TraceAddresses::To(addresses),
+            )
+            .filter_map(|trace| EthereumCall::try_from_trace(&trace))
+            .filter(move |call| {
+                // `trace_filter` can only filter by calls `to` an address and
+                // a block range. Since subgraphs are subscribing to calls
+                // for a specific contract function an additional filter needs
+                // to be applied
+                call_filter.matches(&call)
+            }),
+        )
+    }
+
+    fn contracts_created_in_block_range(
+        &self,
+        logger: &Logger,
+        subgraph_metrics: Arc<SubgraphEthRpcMetrics>,
+        from: u64,
+        to: u64,
+        factories: Vec<Address>,
+    ) -> Box<dyn Future<Item = Vec<EthereumContractCreation>, Error = Error> + Send> {
+        // An empty address list would trace calls made by all contracts
+        if factories.is_empty() {
+            return Box::new(future::ok(vec![]));
+        }
+
+        Box::new(
+            self.clone()
+                .trace_stream(
+                    &logger,
+                    subgraph_metrics,
+                    from,
+                    to,
+                    TraceAddresses::From(factories),
+                )
+                .filter_map(|trace| EthereumContractCreation::try_from_trace(&trace))
+                .collect(),
         )
     }
 
@@ -985,58 +1665,114 @@ where
 
         // Encode the call parameters according to the ABI
         let call_data = call.function.encode_input(&call.args).unwrap();
-
-        // Check if we have it cached, if not do the call and cache.
-        Box::new(
-            match cache
-                .get_call(call.address, &call_data, call.block_ptr)
-                .map_err(|e| error!(logger, "call cache get error"; "error" => e.to_string()))
-                .ok()
-                .and_then(|x| x)
-            {
-                Some(result) => {
-                    Box::new(future::ok(result)) as Box<dyn Future<Item = _, Error = _> + Send>
-                }
+        let function = call.function.clone();
+
+        // Identical calls that are made while one of them is in flight wait
+        // for its result instead of sending another request
+        let key = (call.address, call_data.clone(), call.block_ptr);
+        let output = {
+            let mut in_flight_calls = self.in_flight_calls.lock().unwrap();
+            match in_flight_calls.get(&key) {
+                Some(output) => output.clone(),
                 None => {
-                    let cache = cache.clone();
-                    let call = call.clone();
-                    let call_data = call_data.clone();
-                    let logger = logger.clone();
-                    Box::new(
-                        self.call(
-                            &logger,
-                            call.address,
-                            Bytes(call_data.clone()),
-                            Some(call.block_ptr.number.into()),
-                        )
-                        .map(move |result| {
-                            let _ = cache
-                                .set_call(call.address, &call_data, call.block_ptr, &result.0)
-                                .map_err(|e| {
-                                    error!(logger, "call cache set error";
-                                                   "error" => e.to_string())
-                                });
-                            result.0
-                        }),
-                    )
+                    let in_flight_calls_for_removal = self.in_flight_calls.clone();
+                    let key_for_removal = key.clone();
+                    let output: Box<dyn Future<Item = _, Error = _> + Send> = Box::new(
+                        self.call_output(logger, call, call_data, cache)
+                            .then(move |result| {
+                                in_flight_calls_for_removal
+                                    .lock()
+                                    .unwrap()
+                                    .remove(&key_for_removal);
+                                result
+                            }),
+                    );
+                    let output = output.shared();
+                    in_flight_calls.insert(key, output.clone());
+                    output
                 }
             }
-            // Decode the return values according to the ABI
-            .and_then(move |output| {
-                if output.is_empty() {
-                    // We got a `0x` response. For Geth, this can mean a revert. It can also be
-                    // that the contract actually returned an empty response. A view call is meant
-                    // to return something, so we treat empty responses the same as reverts. See
-                    // support/#85 for a use case.
-                    Err(EthereumContractCallError::Revert("empty response".into()))
-                } else {
-                    // Decode failures are reverts. The reasoning is that if Solidity fails to
-                    // decode an argument, that's a revert, so the same goes for the output.
-                    call.function.decode_output(&output).map_err(|e| {
-                        EthereumContractCallError::Revert(format!("failed to decode output: {}", e))
+        };
+
+        Box::new(
+            output
+                .map(|output| (*output).clone())
+                .map_err(|e| clone_call_error(&e))
+                // Decode the return values according to the ABI
+                .and_then(move |output| {
+                    if output.is_empty() {
+                        // We got a `0x` response. For Geth, this can mean a revert. It can
+                        // also be that the contract actually returned an empty response. A
+                        // view call is meant to return something, so we treat empty responses
+                        // the same as reverts. See support/#85 for a use case.
+                        Err(EthereumContractCallError::Revert("empty response".into()))
+                    } else {
+                        // Decode failures are reverts. The reasoning is that if Solidity fails
+                        // to decode an argument, that's a revert, so the same goes for the
+                        // output.
+                        function.decode_output(&output).map_err(|e| {
+                            EthereumContractCallError::Revert(format!(
+                                "failed to decode output: {}",
+                                e
+                            ))
+                        })
+                    }
+                }),
+        )
+    }
+
+    fn get_storage_at(
+        &self,
+        logger: &Logger,
+        subgraph_metrics: Arc<SubgraphEthRpcMetrics>,
+        address: Address,
+        slot: H256,
+        block: EthereumBlockPointer,
+    ) -> Box<dyn Future<Item = H256, Error = EthereumAdapterError> + Send> {
+        let web3 = self.web3.clone();
+        let provider_metrics = self.metrics.clone();
+
+        Box::new(
+            retry("eth_getStorageAt RPC call", &logger)
+                .limit(16)
+                .timeout_secs(*JSON_RPC_TIMEOUT)
+                .run(move || {
+                    let start = Instant::now();
+                    let subgraph_metrics = subgraph_metrics.clone();
+                    let provider_metrics = provider_metrics.clone();
+
+                    // The web3 API only accepts block numbers for `eth_getStorageAt`;
+                    // to pass the block hash (EIP-1898), the request is built by hand
+                    let params = vec![
+                        helpers::serialize(&address),
+                        helpers::serialize(&slot),
+                        serde_json::json!({ "blockHash": block.hash }),
+                    ];
+                    CallFuture::new(web3.transport().execute("eth_getStorageAt", params))
+                        .then(move |result: Result<H256, web3::Error>| {
+                            let elapsed = start.elapsed().as_secs_f64();
+                            provider_metrics.observe_request(elapsed, "eth_getStorageAt");
+                            subgraph_metrics.observe_request(elapsed, "eth_getStorageAt");
+                            if result.is_err() {
+                                provider_metrics.add_error("eth_getStorageAt");
+                                subgraph_metrics.add_error("eth_getStorageAt");
+                            }
+                            result
+                        })
+                        .from_err()
+                })
+                .map_err(move |e| {
+                    e.into_inner().unwrap_or_else(move || {
+                        format_err!(
+                            "Ethereum node took too long to return storage slot {:#x} \
+                             of contract {:#x} at block {:#x}",
+                            slot,
+                            address,
+                            block.hash
+                        )
                     })
-                }
-            }),
+                })
+                .from_err(),
         )
     }
 
@@ -1061,17 +1797,35 @@ where
                     log_filter.clone(),
                     call_filter.clone(),
                     block_filter.clone(),
+                    0,
+                    // The block came from the Ethereum node, which
+                    // therefore knows about it
+                    Some(block.number()),
+                    None,
+                    None,
                 )
-                .map(|blocks| {
+                .and_then(|blocks| {
                     assert!(blocks.len() <= 1);
-                    blocks
-                        .into_iter()
-                        .next()
-                        .unwrap_or(EthereumBlockWithTriggers::new(vec![], ethereum_block))
+                    match blocks.into_iter().next() {
+                        Some(block) => Ok(block),
+                        None => EthereumBlockWithTriggers::new(vec![], ethereum_block)
+                            .map_err(Error::from),
+                    }
                 }),
             )
                 as Box<dyn Future<Item = _, Error = _> + Send>,
-            BlockFinality::NonFinal(full_block) => Box::new(future::ok({
+            BlockFinality::NonFinal(full_block)
+                if full_block.calls.is_none()
+                    && (!call_filter.is_empty() || !block_filter.contract_addresses.is_empty()) =>
+            {
+                // Without the calls of the block, its call and block triggers
+                // would be silently dropped
+                Box::new(future::err(format_err!(
+                    "the calls of block {} were not loaded, but are needed to find its triggers",
+                    full_block.ethereum_block.block.number.unwrap()
+                )))
+            }
+            BlockFinality::NonFinal(full_block) => Box::new(future::result({
                 let mut triggers = Vec::new();
                 triggers.append(&mut parse_log_triggers(
                     log_filter,
@@ -1079,7 +1833,7 @@ where
                 ));
                 triggers.append(&mut parse_call_triggers(call_filter, &full_block));
                 triggers.append(&mut parse_block_triggers(block_filter, &full_block));
-                EthereumBlockWithTriggers::new(triggers, ethereum_block)
+                EthereumBlockWithTriggers::new(triggers, ethereum_block).map_err(Error::from)
             })),
         })
     }
@@ -1092,7 +1846,7 @@ where
         block_hashes: HashSet<H256>,
     ) -> Box<dyn Stream<Item = LightEthereumBlock, Error = Error> + Send> {
         // Search for the block in the store first then use json-rpc as a backup.
-        let mut blocks = chain_store
+        let blocks = chain_store
             .blocks(block_hashes.iter().cloned().collect())
             .map_err(|e| error!(&logger, "Error accessing block cache {}", e))
             .unwrap_or_default();
@@ -1103,40 +1857,141 @@ where
                 .filter(|hash| !blocks.iter().any(|b| b.hash == Some(*hash))),
         );
 
+        // Blocks come back from the node in any order. Sort them as they
+        // arrive and write them to the block cache in batches, rather than
+        // collecting and copying all of them before sorting
+        let sorted_blocks: BTreeMap<_, _> = blocks
+            .into_iter()
+            .map(|block| ((block.number(), block.hash), block))
+            .collect();
+
         // Return a stream that lazily loads batches of blocks.
-        debug!(logger, "Requesting {} block(s)", missing_blocks.len());
+        let concurrency = load_blocks_concurrency(missing_blocks.len());
+        debug!(
+            logger,
+            "Requesting {} block(s)",
+            missing_blocks.len();
+            "concurrency" => concurrency
+        );
         Box::new(
-            self.load_blocks_rpc(logger.clone(), missing_blocks.into_iter().collect())
-                .collect()
-                .map(move |new_blocks| {
+            self.load_blocks_rpc(logger.clone(), missing_blocks, concurrency)
+                .chunks(*BLOCK_BATCH_SIZE)
+                .fold(sorted_blocks, move |mut sorted_blocks, new_blocks| {
                     if let Err(e) = chain_store.upsert_light_blocks(new_blocks.clone()) {
                         error!(logger, "Error writing to block cache {}", e);
                     }
-                    blocks.extend(new_blocks);
-                    blocks.sort_by_key(|block| block.number);
-                    stream::iter_ok(blocks)
+                    sorted_blocks.extend(
+                        new_blocks
+                            .into_iter()
+                            .map(|block| ((block.number(), block.hash), block)),
+                    );
+                    Ok::<_, Error>(sorted_blocks)
                 })
+                .map(|sorted_blocks| stream::iter_ok(sorted_blocks.into_iter().map(|(_, b)| b)))
                 .flatten_stream(),
         )
     }
 
+    fn prefetch_blocks(
+        &self,
+        logger: &Logger,
+        chain_store: Arc<dyn ChainStore>,
+        from: u64,
+        to: u64,
+        stride: u64,
+    ) -> Box<dyn Future<Item = Vec<EthereumBlockPointer>, Error = Error> + Send> {
+        let web3 = self.web3.clone();
+        let logger = logger.clone();
+        let block_numbers = (from..=to).step_by(cmp::max(stride, 1) as usize);
+
+        debug!(
+            logger, "Prefetching blocks";
+            "from" => from,
+            "to" => to,
+            "stride" => stride,
+        );
+        Box::new(
+            stream::iter_ok::<_, Error>(block_numbers.map(move |block_num| {
+                let web3 = web3.clone();
+                retry(format!("prefetch block #{}", block_num), &logger)
+                    .limit(16)
+                    .no_logging()
+                    .timeout_secs(*JSON_RPC_TIMEOUT)
+                    .run(move || {
+                        web3.eth()
+                            .block_with_txs(BlockId::Number(BlockNumber::Number(block_num)))
+                            .from_err()
+                    })
+                    .map_err(move |e| {
+                        e.into_inner().unwrap_or_else(move || {
+                            format_err!(
+                                "Ethereum node took too long to return block #{}",
+                                block_num
+                            )
+                        })
+                    })
+            }))
+            .buffer_unordered(*BLOCK_BATCH_SIZE)
+            // Blocks the Ethereum node doesn't know about are simply not prefetched
+            .filter_map(|block| block)
+            .chunks(*BLOCK_BATCH_SIZE)
+            .fold(vec![], move |mut ptrs, blocks| {
+                ptrs.extend(blocks.iter().map(EthereumBlockPointer::from));
+                chain_store.upsert_light_blocks(blocks).map(|()| ptrs)
+            }),
+        )
+    }
+
     /// Reorg safety: `to` must be a final block.
     fn block_range_to_ptrs(
         &self,
         logger: Logger,
         from: u64,
         to: u64,
+        verify_parent_links: bool,
     ) -> Box<dyn Future<Item = Vec<EthereumBlockPointer>, Error = Error> + Send> {
         // Currently we can't go to the DB for this because there might be duplicate entries for
         // the same block number.
         debug!(&logger, "Requesting hashes for blocks [{}, {}]", from, to);
         Box::new(
-            self.load_block_ptrs_rpc(logger, (from..=to).collect())
-                .collect(),
+            self.load_blocks_by_number_rpc(logger, (from..=to).collect())
+                .collect()
+                .and_then(move |blocks| -> Result<Vec<EthereumBlockPointer>, Error> {
+                    if verify_parent_links {
+                        check_parent_links(&blocks)?;
+                    }
+                    Ok(blocks.into_iter().map(EthereumBlockPointer::from).collect())
+                }),
         )
     }
 }
 
+/// The number of requests `load_blocks` keeps in flight to load
+/// `block_count` blocks: one for every `BLOCKS_PER_LOAD_REQUEST` blocks, but
+/// at least one and at most `ETHEREUM_BLOCK_BATCH_SIZE`.
+fn load_blocks_concurrency(block_count: usize) -> usize {
+    ((block_count + BLOCKS_PER_LOAD_REQUEST - 1) / BLOCKS_PER_LOAD_REQUEST)
+        .max(1)
+        .min(*BLOCK_BATCH_SIZE)
+}
+
+/// Checks that each block in `blocks` is the parent of the block that follows it.
+fn check_parent_links(blocks: &[Block<H256>]) -> Result<(), EthereumAdapterError> {
+    for (parent, block) in blocks.iter().zip(blocks.iter().skip(1)) {
+        let parent_ptr = EthereumBlockPointer::from(parent);
+        if block.parent_hash != parent_ptr.hash {
+            let block_ptr = EthereumBlockPointer::from(block);
+            return Err(EthereumAdapterError::BrokenParentLink(
+                block_ptr.number,
+                block_ptr.hash,
+                parent_ptr.hash,
+                block.parent_hash,
+            ));
+        }
+    }
+    Ok(())
+}
+
 fn parse_log_triggers(
     log_filter: EthereumLogFilter,
     block: &EthereumBlock,
@@ -1149,7 +2004,9 @@ fn parse_log_triggers(
             receipt
                 .logs
                 .iter()
-                .filter(move |log| log_filter.matches(log))
+                .filter(move |log| {
+                    log_filter.matches(log, log.block_number.map(|number| number.as_u64()))
+                })
                 .map(move |log| EthereumTrigger::Log(log.clone()))
         })
         .collect()
@@ -1174,6 +2031,7 @@ fn parse_block_triggers(
 ) -> Vec<EthereumTrigger> {
     let block_ptr = EthereumBlockPointer::from(&block.ethereum_block);
     let trigger_every_block = block_filter.trigger_every_block;
+    let transaction_triggers = block_filter.transaction_triggers(&block.ethereum_block.block);
     let call_filter = EthereumCallFilter::from(block_filter);
     let mut triggers = block.calls.as_ref().map_or(vec![], |calls| {
         calls
@@ -1184,6 +2042,7 @@ fn parse_block_triggers(
             })
             .collect::<Vec<EthereumTrigger>>()
     });
+    triggers.extend(transaction_triggers);
     if trigger_every_block {
         triggers.push(EthereumTrigger::Block(
             block_ptr,
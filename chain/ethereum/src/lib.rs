@@ -7,10 +7,14 @@ extern crate lazy_static;
 
 mod block_ingestor;
 mod block_stream;
+mod chain_head;
 mod ethereum_adapter;
+mod preflight;
 mod transport;
 
 pub use self::block_ingestor::BlockIngestor;
 pub use self::block_stream::{BlockStream, BlockStreamBuilder};
+pub use self::chain_head::ChainHeadBroadcaster;
 pub use self::ethereum_adapter::EthereumAdapter;
+pub use self::preflight::{trigger_preflight, EthereumTriggerPreflightRunner, PreflightRange};
 pub use self::transport::{EventLoopHandle, Transport};
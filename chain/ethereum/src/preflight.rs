@@ -0,0 +1,462 @@
+use futures::future::{loop_fn, Loop};
+use std::collections::HashMap;
+
+use graph::prelude::*;
+
+/// Size of the first window scanned when searching forward for the first
+/// trigger. Every window without a trigger doubles the size of the next.
+const INITIAL_WINDOW_SIZE: u64 = 1_000;
+
+/// Limits the size of windows so that a single scan doesn't overwhelm the
+/// Ethereum node.
+const MAX_WINDOW_SIZE: u64 = 100_000;
+
+lazy_static! {
+    /// Maximum number of blocks a single trigger preflight scans.
+    static ref TRIGGER_PREFLIGHT_MAX_BLOCKS: u64 =
+        ::std::env::var("GRAPH_TRIGGER_PREFLIGHT_MAX_BLOCKS")
+            .unwrap_or("100000".into())
+            .parse::<u64>()
+            .expect("invalid trigger preflight max blocks");
+}
+
+/// The blocks to scan in a trigger preflight.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PreflightRange {
+    /// Scan the blocks `from..=to`. Fails if that is more than
+    /// `GRAPH_TRIGGER_PREFLIGHT_MAX_BLOCKS` blocks.
+    Bounded { from: u64, to: u64 },
+
+    /// Scan forward from the earliest start block of the data sources, in
+    /// growing windows, until the first block with a trigger or until `to`.
+    /// Gives up after `GRAPH_TRIGGER_PREFLIGHT_MAX_BLOCKS` blocks.
+    Forward { to: u64 },
+}
+
+/// Scans `range` for blocks with triggers of `data_sources`, without
+/// processing them, to tell whether a subgraph will find any triggers and
+/// where the first one is.
+///
+/// Trigger counts cover all blocks that were scanned; when searching
+/// forward, that is everything up to the end of the window with the first
+/// trigger.
+///
+/// Reorg safety: the last block of `range` must be a final block.
+pub fn trigger_preflight(
+    logger: Logger,
+    eth_adapter: Arc<dyn EthereumAdapter>,
+    chain_store: Arc<dyn ChainStore>,
+    metrics: Arc<SubgraphEthRpcMetrics>,
+    data_sources: &[DataSource],
+    range: PreflightRange,
+) -> Box<dyn Future<Item = TriggerPreflight, Error = Error> + Send> {
+    let log_filter = EthereumLogFilter::from_data_sources(data_sources);
+    let call_filter = EthereumCallFilter::from_data_sources(data_sources);
+    let block_filter = EthereumBlockFilter::from_data_sources(data_sources);
+
+    let (from, to) = match range {
+        PreflightRange::Bounded { from, to } => (from, to),
+        PreflightRange::Forward { to } => (
+            data_sources
+                .iter()
+                .map(|data_source| data_source.source.start_block)
+                .min()
+                .unwrap_or(0),
+            to,
+        ),
+    };
+    if from > to {
+        return Box::new(future::err(format_err!(
+            "cannot scan for triggers from block {} to block {}",
+            from,
+            to
+        )));
+    }
+
+    let max_blocks = *TRIGGER_PREFLIGHT_MAX_BLOCKS;
+    let to = match range {
+        PreflightRange::Bounded { .. } if to - from >= max_blocks => {
+            return Box::new(future::err(format_err!(
+                "cannot scan more than {} blocks for triggers, but blocks {} to {} were requested",
+                max_blocks,
+                from,
+                to
+            )));
+        }
+        PreflightRange::Bounded { .. } => to,
+        PreflightRange::Forward { .. } => to.min(from.saturating_add(max_blocks.saturating_sub(1))),
+    };
+
+    let scan = move |from: u64, to: u64| {
+        debug!(logger, "Scanning blocks for triggers"; "from" => from, "to" => to);
+        eth_adapter
+            .clone()
+            .blocks_with_triggers(
+                logger.clone(),
+                chain_store.clone(),
+                metrics.clone(),
+                from,
+                to,
+                log_filter.clone(),
+                call_filter.clone(),
+                block_filter.clone(),
+                0,
+                None,
+                None,
+                None,
+            )
+            .map(move |blocks| preflight_of_blocks(from, to, &blocks))
+    };
+
+    match range {
+        PreflightRange::Bounded { .. } => Box::new(scan(from, to)),
+        PreflightRange::Forward { .. } => Box::new(loop_fn(
+            (from, INITIAL_WINDOW_SIZE, None),
+            move |(window_from, window_size, scanned): (u64, u64, Option<TriggerPreflight>)| {
+                let window_to = (window_from + window_size - 1).min(to);
+                scan(window_from, window_to).map(move |window| {
+                    let preflight = match scanned {
+                        Some(mut preflight) => {
+                            preflight.to = window.to;
+                            preflight.first_trigger_block = window.first_trigger_block;
+                            preflight.triggers += window.triggers;
+                            preflight
+                        }
+                        None => window,
+                    };
+
+                    if preflight.first_trigger_block.is_some() || window_to >= to {
+                        Loop::Break(preflight)
+                    } else {
+                        Loop::Continue((
+                            window_to + 1,
+                            (window_size * 2).min(MAX_WINDOW_SIZE),
+                            Some(preflight),
+                        ))
+                    }
+                })
+            },
+        )),
+    }
+}
+
+fn preflight_of_blocks(
+    from: u64,
+    to: u64,
+    blocks: &[EthereumBlockWithTriggers],
+) -> TriggerPreflight {
+    let mut triggers = TriggerCounts::default();
+    for trigger in blocks.iter().flat_map(|block| block.triggers.iter()) {
+        triggers.observe(trigger);
+    }
+
+    TriggerPreflight {
+        // The blocks are sorted by number
+        first_trigger_block: blocks
+            .iter()
+            .find(|block| !block.triggers.is_empty())
+            .map(|block| EthereumBlockPointer::from(&block.ethereum_block)),
+        from,
+        to,
+        triggers,
+    }
+}
+
+/// Runs trigger preflights for deployed subgraphs on the networks this node
+/// is connected to.
+pub struct EthereumTriggerPreflightRunner<L, S> {
+    link_resolver: Arc<L>,
+    chain_stores: HashMap<String, Arc<S>>,
+    eth_adapters: HashMap<String, Arc<dyn EthereumAdapter>>,
+    reorg_threshold: u64,
+
+    /// Shared by all preflights, since metrics can only be registered once.
+    metrics: Arc<SubgraphEthRpcMetrics>,
+}
+
+impl<L, S> EthereumTriggerPreflightRunner<L, S>
+where
+    L: LinkResolver,
+    S: ChainStore,
+{
+    pub fn new<M: MetricsRegistry>(
+        link_resolver: Arc<L>,
+        chain_stores: HashMap<String, Arc<S>>,
+        eth_adapters: HashMap<String, Arc<dyn EthereumAdapter>>,
+        reorg_threshold: u64,
+        registry: Arc<M>,
+    ) -> Self {
+        EthereumTriggerPreflightRunner {
+            link_resolver,
+            chain_stores,
+            eth_adapters,
+            reorg_threshold,
+            metrics: Arc::new(SubgraphEthRpcMetrics::new(
+                registry,
+                String::from("trigger_preflight"),
+            )),
+        }
+    }
+}
+
+impl<L, S> TriggerPreflightRunner for EthereumTriggerPreflightRunner<L, S>
+where
+    L: LinkResolver,
+    S: ChainStore,
+{
+    fn trigger_preflight(
+        &self,
+        logger: &Logger,
+        subgraph_id: SubgraphDeploymentId,
+        range: Option<(u64, u64)>,
+    ) -> Box<dyn Future<Item = TriggerPreflight, Error = Error> + Send> {
+        let logger = logger.new(o!("subgraph_id" => subgraph_id.to_string()));
+        let chain_stores = self.chain_stores.clone();
+        let eth_adapters = self.eth_adapters.clone();
+        let reorg_threshold = self.reorg_threshold;
+        let metrics = self.metrics.clone();
+
+        Box::new(
+            SubgraphManifest::resolve(
+                subgraph_id.to_ipfs_link(),
+                self.link_resolver.clone(),
+                logger.clone(),
+            )
+            .map_err(Error::from)
+            .and_then(move |manifest| -> Result<_, Error> {
+                let network_name = manifest.network_name()?;
+                let chain_store = chain_stores
+                    .get(&network_name)
+                    .ok_or_else(|| format_err!("no store for network `{}`", network_name))?
+                    .clone();
+                let eth_adapter = eth_adapters
+                    .get(&network_name)
+                    .ok_or_else(|| {
+                        format_err!("no Ethereum adapter for network `{}`", network_name)
+                    })?
+                    .clone();
+                Ok((manifest, chain_store, eth_adapter))
+            })
+            .and_then(move |(manifest, chain_store, eth_adapter)| {
+                // Without a range, scan up to the latest final block
+                let range: Box<dyn Future<Item = PreflightRange, Error = Error> + Send> =
+                    match range {
+                        Some((from, to)) => {
+                            Box::new(future::ok(PreflightRange::Bounded { from, to }))
+                        }
+                        None => {
+                            Box::new(eth_adapter.latest_block(&logger).map_err(Error::from).map(
+                                move |block| PreflightRange::Forward {
+                                    to: block.number().saturating_sub(reorg_threshold),
+                                },
+                            ))
+                        }
+                    };
+
+                range.and_then(move |range| {
+                    trigger_preflight(
+                        logger,
+                        eth_adapter,
+                        chain_store,
+                        metrics,
+                        &manifest.data_sources,
+                        range,
+                    )
+                })
+            }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph::data::subgraph::{Mapping, Source};
+    use mock::{FakeStore, MockEthereumAdapter, MockMetricsRegistry};
+    use web3::types::{Address, Bytes, Log, H256};
+
+    fn contract() -> Address {
+        Address::from_low_u64_be(1)
+    }
+
+    fn transfer_event() -> MappingEventHandler {
+        MappingEventHandler {
+            event: String::from("Transfer(address,address,uint256)"),
+            topic0: None,
+            handler: String::from("handleTransfer"),
+        }
+    }
+
+    fn data_source(start_block: u64) -> DataSource {
+        DataSource {
+            kind: String::from("ethereum/contract"),
+            name: String::from("Token"),
+            network: Some(String::from("mainnet")),
+            source: Source {
+                address: Some(contract()),
+                abi: String::from("Token"),
+                start_block,
+                factory: None,
+            },
+            mapping: Mapping {
+                kind: String::from("ethereum/events"),
+                api_version: String::from("0.0.3"),
+                language: String::from("wasm/assemblyscript"),
+                entities: vec![],
+                abis: vec![],
+                event_handlers: vec![transfer_event()],
+                call_handlers: vec![],
+                block_handlers: vec![],
+                link: Link {
+                    link: String::from("link"),
+                },
+                runtime: Arc::new(parity_wasm::elements::Module::default()),
+            },
+            templates: vec![],
+            ordinal: DataSourceOrdinal::default(),
+        }
+    }
+
+    fn log(block_number: u64, address: Address, topic0: H256) -> Log {
+        Log {
+            address,
+            topics: vec![topic0],
+            data: Bytes(vec![]),
+            block_hash: None,
+            block_number: Some(block_number.into()),
+            transaction_hash: Some(H256::from_low_u64_be(block_number)),
+            transaction_index: Some(0.into()),
+            log_index: None,
+            transaction_log_index: None,
+            log_type: None,
+            removed: None,
+        }
+    }
+
+    fn run_preflight(logs: Vec<Log>, start_block: u64, range: PreflightRange) -> TriggerPreflight {
+        try_preflight(logs, start_block, range, 20_000).expect("trigger preflight failed")
+    }
+
+    fn try_preflight(
+        logs: Vec<Log>,
+        start_block: u64,
+        range: PreflightRange,
+        head: u64,
+    ) -> Result<TriggerPreflight, Error> {
+        let registry = Arc::new(MockMetricsRegistry::new());
+        let metrics = Arc::new(SubgraphEthRpcMetrics::new(
+            registry,
+            String::from("preflight"),
+        ));
+        let data_sources = vec![data_source(start_block)];
+
+        trigger_preflight(
+            Logger::root(slog::Discard, o!()),
+            Arc::new(MockEthereumAdapter::with_logs(logs).with_head(head)),
+            Arc::new(FakeStore),
+            metrics,
+            &data_sources,
+            range,
+        )
+        .wait()
+    }
+
+    fn seeded_logs() -> Vec<Log> {
+        let transfer = transfer_event().topic0();
+        vec![
+            // Before the start block
+            log(10, contract(), transfer),
+            // A different contract and a different event
+            log(2_500, Address::from_low_u64_be(2), transfer),
+            log(2_600, contract(), H256::from_low_u64_be(7)),
+            // The first triggers
+            log(4_200, contract(), transfer),
+            log(4_200, contract(), transfer),
+            log(9_000, contract(), transfer),
+        ]
+    }
+
+    #[test]
+    fn bounded_preflight_reports_first_trigger_and_counts() {
+        let preflight = run_preflight(
+            seeded_logs(),
+            100,
+            PreflightRange::Bounded {
+                from: 100,
+                to: 10_000,
+            },
+        );
+
+        assert_eq!(
+            preflight.first_trigger_block,
+            Some(EthereumBlockPointer {
+                hash: MockEthereumAdapter::block_hash(4_200),
+                number: 4_200,
+            })
+        );
+        assert_eq!((preflight.from, preflight.to), (100, 10_000));
+        assert_eq!(
+            preflight.triggers,
+            TriggerCounts {
+                logs: 3,
+                calls: 0,
+                blocks: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn forward_preflight_stops_at_window_with_first_trigger() {
+        // Windows are [100, 1099], [1100, 3099] and [3100, 7099]
+        let preflight = run_preflight(seeded_logs(), 100, PreflightRange::Forward { to: 20_000 });
+
+        assert_eq!(
+            preflight.first_trigger_block.map(|ptr| ptr.number),
+            Some(4_200)
+        );
+        assert_eq!((preflight.from, preflight.to), (100, 7_099));
+        assert_eq!(preflight.triggers.logs, 2);
+    }
+
+    #[test]
+    fn forward_preflight_without_triggers_scans_up_to_the_end() {
+        let preflight = run_preflight(seeded_logs(), 9_001, PreflightRange::Forward { to: 20_000 });
+
+        assert_eq!(preflight.first_trigger_block, None);
+        assert_eq!((preflight.from, preflight.to), (9_001, 20_000));
+        assert_eq!(preflight.triggers.total(), 0);
+    }
+
+    #[test]
+    fn bounded_preflight_rejects_ranges_over_the_limit() {
+        let max_blocks = *TRIGGER_PREFLIGHT_MAX_BLOCKS;
+        let result = try_preflight(
+            seeded_logs(),
+            100,
+            PreflightRange::Bounded {
+                from: 100,
+                to: 100 + max_blocks,
+            },
+            200 + max_blocks,
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn forward_preflight_gives_up_after_the_limit() {
+        let max_blocks = *TRIGGER_PREFLIGHT_MAX_BLOCKS;
+        let preflight = try_preflight(
+            vec![],
+            100,
+            PreflightRange::Forward {
+                to: 100 + 2 * max_blocks,
+            },
+            200 + 2 * max_blocks,
+        )
+        .expect("trigger preflight failed");
+
+        assert_eq!(preflight.first_trigger_block, None);
+        assert_eq!((preflight.from, preflight.to), (100, 99 + max_blocks));
+    }
+}
@@ -1,16 +1,19 @@
 use futures::prelude::*;
-use futures::{failed, finished};
+use futures::{failed, finished, future};
 use hex_literal::hex;
-use std::collections::VecDeque;
+use std::collections::{HashSet, VecDeque};
 use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use ethabi::{Function, Param, ParamType, Token};
 use graph::components::ethereum::EthereumContractCall;
+use graph::data::subgraph::{Mapping, Source};
 use graph::prelude::EthereumAdapter as EthereumAdapterTrait;
 use graph::prelude::*;
 use graph_chain_ethereum::EthereumAdapter;
-use mock::MockMetricsRegistry;
+use mock::{FakeStore, MockEthereumAdapter, MockMetricsRegistry, MockStore};
 use web3::helpers::*;
 use web3::types::*;
 use web3::{BatchTransport, RequestId, Transport};
@@ -45,7 +48,11 @@ fn mock_block() -> Block<U256> {
 pub struct TestTransport {
     asserted: usize,
     requests: Arc<Mutex<Vec<(String, Vec<jsonrpc_core::Value>)>>>,
-    response: Arc<Mutex<VecDeque<jsonrpc_core::Value>>>,
+    response: Arc<Mutex<VecDeque<Result<jsonrpc_core::Value, jsonrpc_core::Error>>>>,
+    delay: Option<Duration>,
+    in_flight: Arc<AtomicUsize>,
+    max_in_flight: Arc<Mutex<usize>>,
+    batch_sizes: Arc<Mutex<Vec<usize>>>,
 }
 
 impl Transport for TestTransport {
@@ -62,10 +69,31 @@ impl Transport for TestTransport {
     }
 
     fn send(&self, _: RequestId, _: jsonrpc_core::Call) -> Self::Out {
-        match self.response.lock().unwrap().pop_front() {
-            Some(response) => Box::new(finished(response)),
+        let response: Self::Out = match self.response.lock().unwrap().pop_front() {
+            Some(Ok(response)) => Box::new(finished(response)),
+            Some(Err(error)) => Box::new(failed(web3::Error::Rpc(error))),
             None => Box::new(failed(web3::Error::Unreachable.into())),
+        };
+
+        let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        {
+            let mut max_in_flight = self.max_in_flight.lock().unwrap();
+            *max_in_flight = (*max_in_flight).max(in_flight);
         }
+        let response: Self::Out = match self.delay {
+            Some(delay) => Box::new(
+                tokio::timer::Delay::new(Instant::now() + delay)
+                    .map_err(|e| -> web3::Error { panic!("tokio timer error: {}", e) })
+                    .and_then(|_| response),
+            ),
+            None => response,
+        };
+
+        let in_flight = self.in_flight.clone();
+        Box::new(response.then(move |result| {
+            in_flight.fetch_sub(1, Ordering::SeqCst);
+            result
+        }))
     }
 }
 
@@ -80,24 +108,32 @@ impl BatchTransport for TestTransport {
     where
         T: IntoIterator<Item = (RequestId, jsonrpc_core::Call)>,
     {
-        Box::new(
-            stream::futures_ordered(
-                requests
-                    .into_iter()
-                    .map(|(id, req)| self.send(id, req).map(|v| Ok(v))),
-            )
-            .collect(),
-        )
+        let responses: Vec<_> = requests
+            .into_iter()
+            .map(|(id, req)| self.send(id, req).map(|v| Ok(v)))
+            .collect();
+        self.batch_sizes.lock().unwrap().push(responses.len());
+        Box::new(stream::futures_ordered(responses).collect())
     }
 }
 
 impl TestTransport {
     pub fn set_response(&mut self, value: jsonrpc_core::Value) {
-        *self.response.lock().unwrap() = vec![value].into();
+        *self.response.lock().unwrap() = vec![Ok(value)].into();
+    }
+
+    /// Delays every response by `delay`, to simulate a slow provider.
+    pub fn set_delay(&mut self, delay: Duration) {
+        self.delay = Some(delay);
     }
 
     pub fn add_response(&mut self, value: jsonrpc_core::Value) {
-        self.response.lock().unwrap().push_back(value);
+        self.response.lock().unwrap().push_back(Ok(value));
+    }
+
+    /// Makes the next request fail with `error`.
+    pub fn add_error_response(&mut self, error: jsonrpc_core::Error) {
+        self.response.lock().unwrap().push_back(Err(error));
     }
 
     pub fn assert_request(&mut self, method: &str, params: &[String]) {
@@ -119,6 +155,17 @@ impl TestTransport {
         assert_eq!(p, params);
     }
 
+    /// The largest number of requests that were waiting for a response at
+    /// the same time.
+    pub fn max_in_flight(&self) -> usize {
+        *self.max_in_flight.lock().unwrap()
+    }
+
+    /// The number of requests in each batch that was sent.
+    pub fn batch_sizes(&self) -> Vec<usize> {
+        self.batch_sizes.lock().unwrap().clone()
+    }
+
     pub fn assert_no_more_requests(&mut self) {
         let requests = self.requests.lock().unwrap();
         assert_eq!(
@@ -200,3 +247,1672 @@ fn contract_call() {
 
     assert_eq!(call_result[0], Token::Uint(U256::from(100000)));
 }
+
+/// A call cache that never has a result and records the blocks that
+/// results were looked up and cached for.
+#[derive(Default)]
+struct RecordingEthereumCallCache {
+    lookups: Mutex<Vec<EthereumBlockPointer>>,
+    writes: Mutex<Vec<EthereumBlockPointer>>,
+}
+
+impl EthereumCallCache for RecordingEthereumCallCache {
+    fn get_call(
+        &self,
+        _: ethabi::Address,
+        _: &[u8],
+        block: EthereumBlockPointer,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        self.lookups.lock().unwrap().push(block);
+        Ok(None)
+    }
+
+    fn set_call(
+        &self,
+        _: ethabi::Address,
+        _: &[u8],
+        block: EthereumBlockPointer,
+        _: &[u8],
+    ) -> Result<(), Error> {
+        self.writes.lock().unwrap().push(block);
+        Ok(())
+    }
+}
+
+/// Makes a contract call against block `block_number` while the chain
+/// head is at `head_number` and returns the cache that was used.
+fn contract_call_with_head(block_number: u64, head_number: u64) -> Arc<RecordingEthereumCallCache> {
+    let registry = Arc::new(MockMetricsRegistry::new());
+    let mut transport = TestTransport::default();
+
+    // Response to `eth_blockNumber`
+    transport.add_response(jsonrpc_core::Value::String(format!("0x{:x}", head_number)));
+    // Response to `eth_call`
+    transport.add_response(jsonrpc_core::Value::String(format!("0x{:064x}", 100)));
+
+    let logger = Logger::root(slog::Discard, o!());
+    let provider_metrics = Arc::new(ProviderEthRpcMetrics::new(registry.clone()));
+    let adapter = EthereumAdapter::new(transport.clone(), provider_metrics);
+    let cache = Arc::new(RecordingEthereumCallCache::default());
+
+    let call = EthereumContractCall {
+        address: Address::from_low_u64_be(1),
+        block_ptr: EthereumBlockPointer::from((H256::from_low_u64_be(block_number), block_number)),
+        function: Function {
+            name: "totalSupply".to_owned(),
+            inputs: vec![],
+            outputs: vec![Param {
+                name: "supply".to_owned(),
+                kind: ParamType::Uint(256),
+            }],
+            constant: true,
+        },
+        args: vec![],
+    };
+
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    let result = runtime
+        .block_on(adapter.contract_call(&logger, call, cache.clone()))
+        .unwrap();
+    assert_eq!(result, vec![Token::Uint(U256::from(100))]);
+
+    // Whether the block is final is decided before the cache is used
+    let methods: Vec<_> = transport
+        .requests
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(method, _)| method.clone())
+        .collect();
+    assert_eq!(methods, vec!["eth_blockNumber", "eth_call"]);
+
+    cache
+}
+
+#[test]
+fn contract_call_against_recent_block_bypasses_cache() {
+    let cache = contract_call_with_head(1000, 1010);
+    assert!(cache.lookups.lock().unwrap().is_empty());
+    assert!(cache.writes.lock().unwrap().is_empty());
+}
+
+#[test]
+fn contract_call_against_final_block_is_cached() {
+    let cache = contract_call_with_head(1000, 2000);
+    let block = EthereumBlockPointer::from((H256::from_low_u64_be(1000), 1000u64));
+    assert_eq!(*cache.lookups.lock().unwrap(), vec![block]);
+    assert_eq!(*cache.writes.lock().unwrap(), vec![block]);
+}
+
+#[test]
+fn contract_call_uses_the_chain_head_seen_last() {
+    let registry = Arc::new(MockMetricsRegistry::new());
+    let mut transport = TestTransport::default();
+
+    // Response to `eth_getBlockByNumber(latest)`
+    let head = mock_block_with_parent(1010, H256::from_low_u64_be(1010), H256::zero());
+    transport.add_response(serde_json::to_value(head).unwrap());
+    // Response to `eth_call`
+    transport.add_response(jsonrpc_core::Value::String(format!("0x{:064x}", 100)));
+
+    let logger = Logger::root(slog::Discard, o!());
+    let provider_metrics = Arc::new(ProviderEthRpcMetrics::new(registry.clone()));
+    let adapter =
+        EthereumAdapter::new(transport.clone(), provider_metrics).with_reorg_threshold(10);
+    let cache = Arc::new(RecordingEthereumCallCache::default());
+
+    let block = EthereumBlockPointer::from((H256::from_low_u64_be(1000), 1000u64));
+    let call = EthereumContractCall {
+        address: Address::from_low_u64_be(1),
+        block_ptr: block,
+        function: Function {
+            name: "totalSupply".to_owned(),
+            inputs: vec![],
+            outputs: vec![Param {
+                name: "supply".to_owned(),
+                kind: ParamType::Uint(256),
+            }],
+            constant: true,
+        },
+        args: vec![],
+    };
+
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.block_on(adapter.latest_block(&logger)).unwrap();
+    runtime
+        .block_on(adapter.contract_call(&logger, call, cache.clone()))
+        .unwrap();
+
+    // The block is `reorg_threshold` blocks behind the head and therefore
+    // final, without asking for the head again
+    let methods: Vec<_> = transport
+        .requests
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(method, _)| method.clone())
+        .collect();
+    assert_eq!(methods, vec!["eth_getBlockByNumber", "eth_call"]);
+    assert_eq!(*cache.writes.lock().unwrap(), vec![block]);
+}
+
+#[test]
+fn concurrent_identical_contract_calls_share_one_request() {
+    let registry = Arc::new(MockMetricsRegistry::new());
+    let mut transport = TestTransport::default();
+
+    // Responses to one `eth_blockNumber` and one `eth_call`; any further
+    // request would fail
+    transport.add_response(jsonrpc_core::Value::String(format!("0x{:x}", 1010)));
+    transport.add_response(jsonrpc_core::Value::String(format!("0x{:064x}", 100)));
+    transport.set_delay(Duration::from_millis(50));
+
+    let logger = Logger::root(slog::Discard, o!());
+    let provider_metrics = Arc::new(ProviderEthRpcMetrics::new(registry.clone()));
+    let adapter = EthereumAdapter::new(transport.clone(), provider_metrics);
+    let cache = Arc::new(RecordingEthereumCallCache::default());
+
+    let call = EthereumContractCall {
+        address: Address::from_low_u64_be(1),
+        block_ptr: EthereumBlockPointer::from((H256::from_low_u64_be(1000), 1000u64)),
+        function: Function {
+            name: "totalSupply".to_owned(),
+            inputs: vec![],
+            outputs: vec![Param {
+                name: "supply".to_owned(),
+                kind: ParamType::Uint(256),
+            }],
+            constant: true,
+        },
+        args: vec![],
+    };
+
+    let calls: Vec<_> = (0..10)
+        .map(|_| adapter.contract_call(&logger, call.clone(), cache.clone()))
+        .collect();
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    let results = runtime.block_on(future::join_all(calls)).unwrap();
+
+    assert_eq!(results.len(), 10);
+    assert!(results
+        .iter()
+        .all(|result| result == &vec![Token::Uint(U256::from(100))]));
+    let methods: Vec<_> = transport
+        .requests
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(method, _)| method.clone())
+        .collect();
+    assert_eq!(methods, vec!["eth_blockNumber", "eth_call"]);
+}
+
+fn mock_block_with_parent(number: u64, hash: H256, parent_hash: H256) -> Block<U256> {
+    Block {
+        hash: Some(hash),
+        parent_hash,
+        number: Some(U128::from(number)),
+        ..mock_block()
+    }
+}
+
+fn block_range_to_ptrs_with_parents(
+    parent_hashes: Vec<H256>,
+) -> Result<Vec<EthereumBlockPointer>, Error> {
+    let registry = Arc::new(MockMetricsRegistry::new());
+    let mut transport = TestTransport::default();
+
+    for (i, parent_hash) in parent_hashes.iter().enumerate() {
+        let number = i as u64 + 1;
+        let block = mock_block_with_parent(number, H256::from([number as u8; 32]), *parent_hash);
+        transport.add_response(serde_json::to_value(block).unwrap());
+    }
+
+    let logger = Logger::root(slog::Discard, o!());
+    let provider_metrics = Arc::new(ProviderEthRpcMetrics::new(registry.clone()));
+    let adapter = EthereumAdapter::new(transport, provider_metrics);
+
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.block_on(adapter.block_range_to_ptrs(logger, 1, parent_hashes.len() as u64, true))
+}
+
+#[test]
+fn block_range_to_ptrs_accepts_linked_chain() {
+    let ptrs = block_range_to_ptrs_with_parents(vec![
+        H256::from([0; 32]),
+        H256::from([1; 32]),
+        H256::from([2; 32]),
+    ])
+    .expect("linked chain should be accepted");
+
+    assert_eq!(
+        ptrs,
+        vec![
+            EthereumBlockPointer::from((H256::from([1; 32]), 1u64)),
+            EthereumBlockPointer::from((H256::from([2; 32]), 2u64)),
+            EthereumBlockPointer::from((H256::from([3; 32]), 3u64)),
+        ]
+    );
+}
+
+#[test]
+fn block_range_to_ptrs_detects_broken_parent_link() {
+    let err = block_range_to_ptrs_with_parents(vec![
+        H256::from([0; 32]),
+        H256::from([1; 32]),
+        H256::from([7; 32]),
+    ])
+    .expect_err("broken parent link should be detected");
+
+    match err.downcast_ref::<EthereumAdapterError>() {
+        Some(EthereumAdapterError::BrokenParentLink(number, hash, expected, actual)) => {
+            assert_eq!(*number, 3);
+            assert_eq!(*hash, H256::from([3; 32]));
+            assert_eq!(*expected, H256::from([2; 32]));
+            assert_eq!(*actual, H256::from([7; 32]));
+        }
+        _ => panic!("unexpected error: {}", err),
+    }
+}
+
+fn mock_data_source_with_event_handler() -> DataSource {
+    DataSource {
+        kind: String::from("ethereum/contract"),
+        name: String::from("example data source"),
+        network: Some(String::from("mainnet")),
+        source: Source {
+            address: Some(Address::from_str("0123123123012312312301231231230123123123").unwrap()),
+            abi: String::from("123123"),
+            start_block: 0,
+            factory: None,
+        },
+        mapping: Mapping {
+            kind: String::from("ethereum/events"),
+            api_version: String::from("0.1.0"),
+            language: String::from("wasm/assemblyscript"),
+            entities: vec![],
+            abis: vec![],
+            event_handlers: vec![MappingEventHandler {
+                event: String::from("Transfer(address,address,uint256)"),
+                topic0: None,
+                handler: String::from("handleTransfer"),
+            }],
+            call_handlers: vec![],
+            block_handlers: vec![],
+            link: Link {
+                link: "link".to_owned(),
+            },
+            runtime: Arc::new(parity_wasm::elements::Module::default()),
+        },
+        templates: vec![],
+        ordinal: DataSourceOrdinal::default(),
+    }
+}
+
+#[test]
+fn blocks_with_triggers_aborts_after_deadline() {
+    let registry = Arc::new(MockMetricsRegistry::new());
+    let mut transport = TestTransport::default();
+
+    // Fetching logs takes much longer than the deadline allows
+    let delay = Duration::from_secs(10);
+    transport.set_delay(delay);
+
+    let logger = Logger::root(slog::Discard, o!());
+    let provider_metrics = Arc::new(ProviderEthRpcMetrics::new(registry.clone()));
+    let subgraph_metrics = Arc::new(SubgraphEthRpcMetrics::new(
+        registry.clone(),
+        String::from("deadline"),
+    ));
+    let adapter = Arc::new(EthereumAdapter::new(transport, provider_metrics));
+
+    let data_source = mock_data_source_with_event_handler();
+    let log_filter = EthereumLogFilter::from_data_sources(vec![&data_source]);
+    let call_filter = EthereumCallFilter::from_data_sources(vec![&data_source]);
+    let block_filter = EthereumBlockFilter::from_data_sources(vec![&data_source]);
+
+    let start = Instant::now();
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    let err = runtime
+        .block_on(adapter.blocks_with_triggers(
+            logger,
+            Arc::new(FakeStore),
+            subgraph_metrics,
+            1,
+            10,
+            log_filter,
+            call_filter,
+            block_filter,
+            0,
+            None,
+            Some(start + Duration::from_millis(100)),
+            None,
+        ))
+        .expect_err("scan should not finish before the deadline");
+
+    assert!(start.elapsed() < delay);
+    match err.downcast_ref::<EthereumAdapterError>() {
+        Some(EthereumAdapterError::DeadlineExceeded(1, 10)) => (),
+        _ => panic!("unexpected error: {}", err),
+    }
+}
+
+#[test]
+fn blocks_with_triggers_stops_when_canceled() {
+    let registry = Arc::new(MockMetricsRegistry::new());
+    let mut transport = TestTransport::default();
+
+    // Fetching logs takes much longer than the scan is allowed to run
+    let delay = Duration::from_secs(10);
+    transport.set_delay(delay);
+
+    let logger = Logger::root(slog::Discard, o!());
+    let provider_metrics = Arc::new(ProviderEthRpcMetrics::new(registry.clone()));
+    let subgraph_metrics = Arc::new(SubgraphEthRpcMetrics::new(
+        registry.clone(),
+        String::from("canceled"),
+    ));
+    let adapter = Arc::new(EthereumAdapter::new(transport, provider_metrics));
+
+    let data_source = mock_data_source_with_event_handler();
+    let log_filter = EthereumLogFilter::from_data_sources(vec![&data_source]);
+    let call_filter = EthereumCallFilter::from_data_sources(vec![&data_source]);
+    let block_filter = EthereumBlockFilter::from_data_sources(vec![&data_source]);
+
+    let guard = CancelGuard::new();
+    let scan = adapter.blocks_with_triggers(
+        logger,
+        Arc::new(FakeStore),
+        subgraph_metrics,
+        1,
+        10,
+        log_filter,
+        call_filter,
+        block_filter,
+        0,
+        None,
+        None,
+        Some(guard.handle()),
+    );
+
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.spawn(
+        tokio::timer::Delay::new(Instant::now() + Duration::from_millis(50))
+            .map(move |_| guard.cancel())
+            .map_err(|e| -> () { panic!("tokio timer error: {}", e) }),
+    );
+    let start = Instant::now();
+    let err = runtime
+        .block_on(scan)
+        .expect_err("scan should have been canceled");
+
+    assert!(start.elapsed() < delay);
+    match err.downcast_ref::<EthereumAdapterError>() {
+        Some(EthereumAdapterError::Canceled(1, 10)) => (),
+        _ => panic!("unexpected error: {}", err),
+    }
+}
+
+#[test]
+fn logs_in_block_range_stops_when_canceled() {
+    let registry = Arc::new(MockMetricsRegistry::new());
+    let mut transport = TestTransport::default();
+
+    let delay = Duration::from_secs(10);
+    transport.set_delay(delay);
+
+    let logger = Logger::root(slog::Discard, o!());
+    let provider_metrics = Arc::new(ProviderEthRpcMetrics::new(registry.clone()));
+    let subgraph_metrics = Arc::new(SubgraphEthRpcMetrics::new(
+        registry.clone(),
+        String::from("canceled"),
+    ));
+    let adapter = EthereumAdapter::new(transport, provider_metrics);
+
+    let guard = CancelGuard::new();
+    let logs = adapter.logs_in_block_range(
+        &logger,
+        subgraph_metrics,
+        1,
+        10,
+        EthereumLogFilter::from_data_sources(vec![&mock_data_source_with_event_handler()]),
+        Some(guard.handle()),
+    );
+
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.spawn(
+        tokio::timer::Delay::new(Instant::now() + Duration::from_millis(50))
+            .map(move |_| guard.cancel())
+            .map_err(|e| -> () { panic!("tokio timer error: {}", e) }),
+    );
+    let start = Instant::now();
+    let err = runtime
+        .block_on(logs)
+        .expect_err("request should have been canceled");
+
+    assert!(start.elapsed() < delay);
+    match err.downcast_ref::<EthereumAdapterError>() {
+        Some(EthereumAdapterError::Canceled(1, 10)) => (),
+        _ => panic!("unexpected error: {}", err),
+    }
+}
+
+/// Scans blocks `[1, to]` for the triggers of the mock data source with an
+/// adapter whose latest block is `head`, treating blocks with fewer than
+/// `confirmations` blocks on top of them as non-final.
+fn blocks_with_triggers_with_head(
+    head: u64,
+    to: u64,
+    confirmations: u64,
+) -> Result<Vec<EthereumBlockWithTriggers>, Error> {
+    blocks_with_triggers_with_known_head(head, None, to, confirmations)
+}
+
+/// Like `blocks_with_triggers_with_head`, but passes `chain_head` to the
+/// scan as the latest block the caller knows about.
+fn blocks_with_triggers_with_known_head(
+    head: u64,
+    chain_head: Option<u64>,
+    to: u64,
+    confirmations: u64,
+) -> Result<Vec<EthereumBlockWithTriggers>, Error> {
+    let registry = Arc::new(MockMetricsRegistry::new());
+    let logger = Logger::root(slog::Discard, o!());
+    let subgraph_metrics = Arc::new(SubgraphEthRpcMetrics::new(
+        registry,
+        format!("provider_head_{}", head),
+    ));
+
+    let data_source = mock_data_source_with_event_handler();
+    let transfer = data_source.mapping.event_handlers[0].topic0();
+    let log = Log {
+        address: data_source.source.address.unwrap(),
+        topics: vec![transfer],
+        data: Bytes(vec![]),
+        block_hash: None,
+        block_number: Some(5.into()),
+        transaction_hash: Some(H256::from_low_u64_be(5)),
+        transaction_index: Some(0.into()),
+        log_index: None,
+        transaction_log_index: None,
+        log_type: None,
+        removed: None,
+    };
+    let adapter = Arc::new(MockEthereumAdapter::with_logs(vec![log]).with_head(head));
+
+    adapter
+        .blocks_with_triggers(
+            logger,
+            Arc::new(FakeStore),
+            subgraph_metrics,
+            1,
+            to,
+            EthereumLogFilter::from_data_sources(vec![&data_source]),
+            EthereumCallFilter::from_data_sources(vec![&data_source]),
+            EthereumBlockFilter::from_data_sources(vec![&data_source]),
+            confirmations,
+            chain_head,
+            None,
+            None,
+        )
+        .wait()
+}
+
+#[test]
+fn blocks_with_triggers_fails_if_provider_is_too_far_behind() {
+    let err = blocks_with_triggers_with_head(100, 1_000, 0)
+        .expect_err("scan should fail if the provider is behind");
+
+    match err.downcast_ref::<EthereumAdapterError>() {
+        Some(EthereumAdapterError::ProviderTooFarBehind {
+            provider_head: 100,
+            requested_to: 1_000,
+        }) => (),
+        _ => panic!("unexpected error: {}", err),
+    }
+}
+
+#[test]
+fn blocks_with_triggers_uses_the_chain_head_it_is_given() {
+    // The Ethereum node is not asked for its latest block, which would be
+    // too far behind
+    let blocks =
+        blocks_with_triggers_with_known_head(10, Some(1_000), 1_000, 100).expect("scan failed");
+
+    assert_eq!(
+        blocks
+            .iter()
+            .map(|block| match block.ethereum_block {
+                BlockFinality::Final(_) => (block.block_number(), true),
+                BlockFinality::NonFinal(_) => (block.block_number(), false),
+            })
+            .collect::<Vec<_>>(),
+        vec![(5, true), (1_000, false)]
+    );
+}
+
+#[test]
+fn blocks_with_triggers_scans_if_provider_is_caught_up() {
+    // Being a block behind is within the margin
+    let blocks = blocks_with_triggers_with_head(99, 100, 0).expect("scan failed");
+
+    assert_eq!(
+        blocks
+            .iter()
+            .map(|block| (block.block_number(), block.trigger_count()))
+            .collect::<Vec<_>>(),
+        vec![(5, 1), (100, 0)]
+    );
+}
+
+#[test]
+fn blocks_with_triggers_returns_shallow_blocks_as_non_final() {
+    let blocks = blocks_with_triggers_with_head(100, 100, 10).expect("scan failed");
+
+    // Block 5 is buried deep enough, block 100 is the head
+    assert_eq!(
+        blocks
+            .iter()
+            .map(|block| match block.ethereum_block {
+                BlockFinality::Final(_) => (block.block_number(), true),
+                BlockFinality::NonFinal(_) => (block.block_number(), false),
+            })
+            .collect::<Vec<_>>(),
+        vec![(5, true), (100, false)]
+    );
+}
+
+#[test]
+fn blocks_with_triggers_returns_deep_blocks_as_final() {
+    // Block 100 has exactly 10 blocks on top of it
+    let blocks = blocks_with_triggers_with_head(110, 100, 10).expect("scan failed");
+
+    assert!(blocks.iter().all(|block| match block.ethereum_block {
+        BlockFinality::Final(_) => true,
+        BlockFinality::NonFinal(_) => false,
+    }));
+    assert_eq!(blocks.len(), 2);
+}
+
+/// Loads blocks `[1, count]` from a node that takes a while to respond
+/// and returns them in reverse order. Returns the numbers of the loaded
+/// blocks and the largest number of concurrent requests.
+fn load_blocks_from_slow_node(count: u64) -> (Vec<u64>, usize) {
+    let registry = Arc::new(MockMetricsRegistry::new());
+    let logger = Logger::root(slog::Discard, o!());
+    let provider_metrics = Arc::new(ProviderEthRpcMetrics::new(registry));
+
+    let mut transport = TestTransport::default();
+    transport.set_delay(Duration::from_millis(10));
+    for number in (1..=count).rev() {
+        let block = Block {
+            hash: Some(H256::from_low_u64_be(number)),
+            number: Some(U128::from(number)),
+            ..mock_block()
+        };
+        transport.add_response(serde_json::to_value(block).unwrap());
+    }
+    let adapter = EthereumAdapter::new(transport.clone(), provider_metrics);
+
+    let hashes: HashSet<H256> = (1..=count).map(H256::from_low_u64_be).collect();
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    let blocks = runtime
+        .block_on(
+            adapter
+                .load_blocks(logger, Arc::new(MockStore::new(vec![])), hashes)
+                .collect(),
+        )
+        .unwrap();
+
+    (
+        blocks.iter().map(|block| block.number()).collect(),
+        transport.max_in_flight(),
+    )
+}
+
+#[test]
+fn load_blocks_limits_concurrency_of_large_loads() {
+    let (numbers, max_in_flight) = load_blocks_from_slow_node(200);
+
+    assert_eq!(numbers, (1..=200).collect::<Vec<_>>());
+    // Bounded by `ETHEREUM_BLOCK_BATCH_SIZE`
+    assert!(max_in_flight > 1 && max_in_flight <= 10);
+}
+
+#[test]
+fn load_blocks_loads_few_blocks_with_few_requests() {
+    let (numbers, max_in_flight) = load_blocks_from_slow_node(6);
+
+    assert_eq!(numbers, (1..=6).collect::<Vec<_>>());
+    assert!(max_in_flight <= 2);
+}
+
+/// An adapter whose Ethereum node returns blocks 1, 11 and 21, in that order,
+/// each after `delay`.
+fn prefetching_adapter(delay: Duration) -> (EthereumAdapter<TestTransport>, TestTransport) {
+    let registry = Arc::new(MockMetricsRegistry::new());
+    let provider_metrics = Arc::new(ProviderEthRpcMetrics::new(registry));
+
+    let mut transport = TestTransport::default();
+    transport.set_delay(delay);
+    for number in &[1, 11, 21] {
+        let block = Block {
+            hash: Some(H256::from_low_u64_be(*number)),
+            number: Some(U128::from(*number)),
+            ..mock_block()
+        };
+        transport.add_response(serde_json::to_value(block).unwrap());
+    }
+
+    (
+        EthereumAdapter::new(transport.clone(), provider_metrics),
+        transport,
+    )
+}
+
+#[test]
+fn prefetch_blocks_stores_blocks_at_stride() {
+    let logger = Logger::root(slog::Discard, o!());
+    let store = Arc::new(MockStore::new(vec![]));
+    let (adapter, mut transport) = prefetching_adapter(Duration::from_millis(1));
+
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    let mut ptrs = runtime
+        .block_on(adapter.prefetch_blocks(&logger, store.clone(), 1, 25, 10))
+        .unwrap();
+    ptrs.sort_by_key(|ptr| ptr.number);
+
+    let expected: Vec<H256> = [1, 11, 21]
+        .iter()
+        .map(|n| H256::from_low_u64_be(*n))
+        .collect();
+    assert_eq!(
+        ptrs.iter().map(|ptr| ptr.hash).collect::<Vec<_>>(),
+        expected
+    );
+    let mut stored: Vec<H256> = store
+        .light_blocks()
+        .iter()
+        .map(|block| block.hash.unwrap())
+        .collect();
+    stored.sort();
+    assert_eq!(stored, expected);
+
+    for number in &["\"0x1\"", "\"0xb\"", "\"0x15\""] {
+        transport.assert_request(
+            "eth_getBlockByNumber",
+            &[number.to_string(), "true".to_string()],
+        );
+    }
+    transport.assert_no_more_requests();
+}
+
+#[test]
+fn prefetch_blocks_stops_when_canceled() {
+    let logger = Logger::root(slog::Discard, o!());
+    let store = Arc::new(MockStore::new(vec![]));
+    let delay = Duration::from_millis(500);
+    let (adapter, _) = prefetching_adapter(delay);
+
+    let guard = CancelGuard::new();
+    let prefetch = adapter
+        .prefetch_blocks(&logger, store.clone(), 1, 25, 10)
+        .map_err(CancelableError::Error)
+        .cancelable(&guard, || CancelableError::Cancel);
+
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.spawn(
+        tokio::timer::Delay::new(Instant::now() + Duration::from_millis(50))
+            .map(move |_| guard.cancel())
+            .map_err(|e| -> () { panic!("tokio timer error: {}", e) }),
+    );
+    let start = Instant::now();
+    match runtime.block_on(prefetch) {
+        Err(CancelableError::Cancel) => (),
+        Err(CancelableError::Error(e)) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("prefetching should have been canceled"),
+    }
+    assert!(start.elapsed() < delay);
+
+    // The requests in flight were abandoned, so nothing gets stored
+    std::thread::sleep(2 * delay);
+    assert!(store.light_blocks().is_empty());
+}
+
+fn mock_receipt(tx_hash: H256) -> jsonrpc_core::Value {
+    serde_json::json!({
+        "transactionHash": tx_hash,
+        "transactionIndex": "0x0",
+        "blockHash": H256::from_low_u64_be(1),
+        "blockNumber": "0x1",
+        "cumulativeGasUsed": "0x5208",
+        "gasUsed": "0x5208",
+        "contractAddress": null,
+        "logs": [],
+        "status": "0x1",
+        "logsBloom": H2048::default(),
+    })
+}
+
+fn transaction_receipts_with_transport(
+    transport: TestTransport,
+    hashes: Vec<H256>,
+    in_final_blocks: bool,
+) -> Result<Vec<Option<TransactionReceipt>>, EthereumAdapterError> {
+    let registry = Arc::new(MockMetricsRegistry::new());
+    let logger = Logger::root(slog::Discard, o!());
+    let provider_metrics = Arc::new(ProviderEthRpcMetrics::new(registry));
+    let adapter = EthereumAdapter::new(transport, provider_metrics);
+
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.block_on(adapter.transaction_receipts(&logger, hashes, in_final_blocks))
+}
+
+#[test]
+fn transaction_receipts_are_requested_in_batches() {
+    let mut transport = TestTransport::default();
+    let hashes: Vec<H256> = (1..=250).map(H256::from_low_u64_be).collect();
+    for hash in &hashes {
+        transport.add_response(mock_receipt(*hash));
+    }
+
+    let receipts = transaction_receipts_with_transport(transport.clone(), hashes, true).unwrap();
+
+    assert_eq!(receipts.len(), 250);
+    assert!(receipts.iter().all(Option::is_some));
+    let mut batch_sizes = transport.batch_sizes();
+    batch_sizes.sort();
+    assert_eq!(batch_sizes, vec![50, 100, 100]);
+}
+
+#[test]
+fn transaction_receipts_of_no_transactions_sends_no_requests() {
+    let mut transport = TestTransport::default();
+
+    let receipts = transaction_receipts_with_transport(transport.clone(), vec![], true).unwrap();
+
+    assert!(receipts.is_empty());
+    assert!(transport.batch_sizes().is_empty());
+    transport.assert_no_more_requests();
+}
+
+#[test]
+fn transaction_receipts_are_none_if_missing_in_non_final_blocks() {
+    let mut transport = TestTransport::default();
+    let hashes: Vec<H256> = (1..=3).map(H256::from_low_u64_be).collect();
+    transport.add_response(mock_receipt(hashes[0]));
+    transport.add_response(jsonrpc_core::Value::Null);
+    transport.add_response(mock_receipt(hashes[2]));
+
+    let receipts = transaction_receipts_with_transport(transport, hashes.clone(), false).unwrap();
+
+    assert_eq!(
+        receipts
+            .iter()
+            .map(|receipt| receipt.as_ref().map(|receipt| receipt.transaction_hash))
+            .collect::<Vec<_>>(),
+        vec![Some(hashes[0]), None, Some(hashes[2])]
+    );
+}
+
+#[test]
+fn transaction_receipts_fail_if_missing_in_final_blocks() {
+    let mut transport = TestTransport::default();
+    let hashes: Vec<H256> = (1..=3).map(H256::from_low_u64_be).collect();
+    transport.add_response(mock_receipt(hashes[0]));
+    transport.add_response(jsonrpc_core::Value::Null);
+    transport.add_response(mock_receipt(hashes[2]));
+
+    let err = transaction_receipts_with_transport(transport, hashes.clone(), true)
+        .expect_err("missing receipts in final blocks should be an error");
+
+    match err {
+        EthereumAdapterError::ReceiptUnavailable(tx_hash) => assert_eq!(tx_hash, hashes[1]),
+        _ => panic!("unexpected error: {}", err),
+    }
+}
+
+/// Finds the triggers of the call filter of a single contract in a
+/// non-final block with `calls`.
+fn call_triggers_in_non_final_block(
+    calls: Option<Vec<EthereumCall>>,
+) -> Result<EthereumBlockWithTriggers, Error> {
+    let registry = Arc::new(MockMetricsRegistry::new());
+    let logger = Logger::root(slog::Discard, o!());
+    let provider_metrics = Arc::new(ProviderEthRpcMetrics::new(registry.clone()));
+    let subgraph_metrics = Arc::new(SubgraphEthRpcMetrics::new(
+        registry,
+        String::from("nonFinalCalls"),
+    ));
+    let adapter = Arc::new(EthereumAdapter::new(
+        TestTransport::default(),
+        provider_metrics,
+    ));
+
+    let mut ethereum_block = EthereumBlock::default();
+    ethereum_block.block.number = Some(U128::from(1));
+    ethereum_block.block.hash = Some(H256::from_low_u64_be(1));
+    let call_filter = vec![(0, Address::from_low_u64_be(1), [1u8; 4])]
+        .into_iter()
+        .collect();
+
+    adapter
+        .triggers_in_block(
+            logger,
+            Arc::new(FakeStore),
+            subgraph_metrics,
+            EthereumLogFilter::default(),
+            call_filter,
+            EthereumBlockFilter::default(),
+            BlockFinality::NonFinal(EthereumBlockWithCalls {
+                ethereum_block,
+                calls,
+            }),
+        )
+        .wait()
+}
+
+#[test]
+fn triggers_in_block_fails_without_the_calls_of_non_final_blocks() {
+    call_triggers_in_non_final_block(None)
+        .expect_err("call triggers can't be found without the calls of the block");
+
+    let block = call_triggers_in_non_final_block(Some(vec![]))
+        .expect("call triggers are found in the calls of the block");
+    assert!(block.triggers.is_empty());
+}
+
+fn calls_in_block_range_with_transport(
+    transport: TestTransport,
+    from: u64,
+    to: u64,
+    call_filter: EthereumCallFilter,
+) -> Vec<EthereumCall> {
+    let registry = Arc::new(MockMetricsRegistry::new());
+    let logger = Logger::root(slog::Discard, o!());
+    let provider_metrics = Arc::new(ProviderEthRpcMetrics::new(registry.clone()));
+    let subgraph_metrics = Arc::new(SubgraphEthRpcMetrics::new(
+        registry.clone(),
+        String::from("calls"),
+    ));
+    let adapter = EthereumAdapter::new(transport, provider_metrics);
+
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime
+        .block_on(
+            adapter
+                .calls_in_block_range(&logger, subgraph_metrics, from, to, call_filter)
+                .collect(),
+        )
+        .unwrap()
+}
+
+#[test]
+fn calls_in_block_range_skips_contracts_starting_after_range() {
+    let mut transport = TestTransport::default();
+    let contract = Address::from_low_u64_be(1);
+    let call_filter = vec![(5000, contract, [0u8; 4])].into_iter().collect();
+
+    let calls = calls_in_block_range_with_transport(transport.clone(), 0, 1000, call_filter);
+
+    assert!(calls.is_empty());
+    transport.assert_no_more_requests();
+}
+
+#[test]
+fn calls_in_block_range_starts_at_earliest_start_block() {
+    let mut transport = TestTransport::default();
+    transport.add_response(jsonrpc_core::Value::Array(vec![]));
+    let call_filter = vec![
+        (150, Address::from_low_u64_be(1), [0u8; 4]),
+        (5000, Address::from_low_u64_be(2), [0u8; 4]),
+    ]
+    .into_iter()
+    .collect();
+
+    let calls = calls_in_block_range_with_transport(transport.clone(), 0, 199, call_filter);
+
+    // Only the contract that exists in the range is traced, starting at the
+    // block it was created in
+    assert!(calls.is_empty());
+    let requests = transport.requests.lock().unwrap();
+    assert_eq!(requests.len(), 1);
+    let (method, params) = &requests[0];
+    assert_eq!(method, "trace_filter");
+    assert_eq!(params[0]["fromBlock"], "0x96");
+    assert_eq!(params[0]["toBlock"], "0xc7");
+    assert_eq!(
+        params[0]["toAddress"],
+        serde_json::to_value(vec![Address::from_low_u64_be(1)]).unwrap()
+    );
+}
+
+fn create_trace(from: Address, address: Address, error: Option<&str>) -> jsonrpc_core::Value {
+    let mut trace = serde_json::json!({
+        "action": {
+            "from": from,
+            "value": "0x0",
+            "gas": "0x1000",
+            "init": "0x",
+        },
+        "result": {
+            "gasUsed": "0x100",
+            "code": "0x",
+            "address": address,
+        },
+        "traceAddress": [],
+        "subtraces": 0,
+        "transactionPosition": 0,
+        "transactionHash": H256::from_low_u64_be(1),
+        "blockNumber": 150,
+        "blockHash": H256::from_low_u64_be(150),
+        "type": "create",
+    });
+    if let Some(error) = error {
+        trace["error"] = error.into();
+    }
+    trace
+}
+
+#[test]
+fn contracts_created_in_block_range_traces_calls_from_factories() {
+    let factory = Address::from_low_u64_be(100);
+    let (child1, child2, child3) = (
+        Address::from_low_u64_be(1),
+        Address::from_low_u64_be(2),
+        Address::from_low_u64_be(3),
+    );
+    let mut transport = TestTransport::default();
+    transport.add_response(jsonrpc_core::Value::Array(vec![
+        create_trace(factory, child1, None),
+        create_trace(factory, child2, None),
+        create_trace(factory, child3, Some("Out of gas")),
+    ]));
+
+    let registry = Arc::new(MockMetricsRegistry::new());
+    let logger = Logger::root(slog::Discard, o!());
+    let provider_metrics = Arc::new(ProviderEthRpcMetrics::new(registry.clone()));
+    let subgraph_metrics = Arc::new(SubgraphEthRpcMetrics::new(
+        registry.clone(),
+        String::from("factories"),
+    ));
+    let adapter = EthereumAdapter::new(transport.clone(), provider_metrics);
+
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    let creations = runtime
+        .block_on(adapter.contracts_created_in_block_range(
+            &logger,
+            subgraph_metrics,
+            100,
+            199,
+            vec![factory],
+        ))
+        .unwrap();
+
+    // Failed creations are skipped
+    assert_eq!(
+        creations,
+        vec![
+            EthereumContractCreation {
+                factory,
+                address: child1,
+                block_number: 150,
+            },
+            EthereumContractCreation {
+                factory,
+                address: child2,
+                block_number: 150,
+            },
+        ]
+    );
+    let requests = transport.requests.lock().unwrap();
+    assert_eq!(requests.len(), 1);
+    let (method, params) = &requests[0];
+    assert_eq!(method, "trace_filter");
+    assert_eq!(
+        params[0]["fromAddress"],
+        serde_json::to_value(vec![factory]).unwrap()
+    );
+    assert!(params[0].get("toAddress").map_or(true, |to| to.is_null()));
+}
+
+fn mock_log(tx_hash: H256, log_index: u64) -> Log {
+    Log {
+        address: Address::from_low_u64_be(1),
+        topics: vec![H256::from_low_u64_be(log_index)],
+        data: Bytes(vec![]),
+        block_hash: Some(H256::from([1; 32])),
+        block_number: Some(1.into()),
+        transaction_hash: Some(tx_hash),
+        transaction_index: Some(0.into()),
+        log_index: Some(log_index.into()),
+        transaction_log_index: Some(log_index.into()),
+        log_type: None,
+        removed: Some(false),
+    }
+}
+
+fn mock_receipt(tx_hash: H256, logs: Vec<Log>) -> jsonrpc_core::Value {
+    serde_json::json!({
+        "transactionHash": tx_hash,
+        "transactionIndex": "0x0",
+        "blockHash": H256::from([1; 32]),
+        "blockNumber": "0x1",
+        "cumulativeGasUsed": "0x5208",
+        "gasUsed": "0x5208",
+        "contractAddress": null,
+        "logs": logs,
+        "status": "0x1",
+        "logsBloom": H2048::default(),
+    })
+}
+
+fn logs_for_transaction_with_response(
+    tx_hash: H256,
+    response: jsonrpc_core::Value,
+) -> Result<Vec<Log>, EthereumAdapterError> {
+    let registry = Arc::new(MockMetricsRegistry::new());
+    let mut transport = TestTransport::default();
+    transport.add_response(response);
+
+    let logger = Logger::root(slog::Discard, o!());
+    let provider_metrics = Arc::new(ProviderEthRpcMetrics::new(registry.clone()));
+    let adapter = EthereumAdapter::new(transport.clone(), provider_metrics);
+
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    let result = runtime.block_on(adapter.logs_for_transaction(&logger, tx_hash));
+
+    transport.assert_request(
+        "eth_getTransactionReceipt",
+        &[serde_json::to_string(&tx_hash).unwrap()],
+    );
+    transport.assert_no_more_requests();
+    result
+}
+
+#[test]
+fn logs_for_transaction_returns_receipt_logs() {
+    let tx_hash = H256::from([7; 32]);
+    let logs = vec![mock_log(tx_hash, 0), mock_log(tx_hash, 1)];
+
+    let result = logs_for_transaction_with_response(tx_hash, mock_receipt(tx_hash, logs.clone()))
+        .expect("failed to load transaction logs");
+
+    assert_eq!(result, logs);
+}
+
+#[test]
+fn logs_for_transaction_without_logs_is_empty() {
+    let tx_hash = H256::from([7; 32]);
+
+    let result = logs_for_transaction_with_response(tx_hash, mock_receipt(tx_hash, vec![]))
+        .expect("failed to load transaction logs");
+
+    assert!(result.is_empty());
+}
+
+#[test]
+fn logs_for_transaction_fails_without_receipt() {
+    let tx_hash = H256::from([7; 32]);
+
+    let err = logs_for_transaction_with_response(tx_hash, jsonrpc_core::Value::Null)
+        .expect_err("missing receipt should be an error");
+
+    match err {
+        EthereumAdapterError::ReceiptUnavailable(hash) => assert_eq!(hash, tx_hash),
+        err => panic!("unexpected error: {}", err),
+    }
+}
+
+fn mock_transaction(tx_hash: H256) -> Transaction {
+    Transaction {
+        hash: tx_hash,
+        nonce: U256::from(3),
+        block_hash: Some(H256::from([1; 32])),
+        block_number: Some(U256::from(1)),
+        transaction_index: Some(0.into()),
+        from: Address::from_low_u64_be(10),
+        to: Some(Address::from_low_u64_be(11)),
+        value: U256::from(1_000),
+        gas_price: U256::from(20),
+        gas: U256::from(21_000),
+        ..Default::default()
+    }
+}
+
+fn transaction_by_hash_with_response(
+    tx_hash: H256,
+    response: jsonrpc_core::Value,
+) -> Option<Transaction> {
+    let registry = Arc::new(MockMetricsRegistry::new());
+    let mut transport = TestTransport::default();
+    transport.add_response(response);
+
+    let logger = Logger::root(slog::Discard, o!());
+    let provider_metrics = Arc::new(ProviderEthRpcMetrics::new(registry.clone()));
+    let adapter = EthereumAdapter::new(transport.clone(), provider_metrics);
+
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    let result = runtime
+        .block_on(adapter.transaction_by_hash(&logger, tx_hash))
+        .expect("failed to load transaction");
+
+    transport.assert_request(
+        "eth_getTransactionByHash",
+        &[serde_json::to_string(&tx_hash).unwrap()],
+    );
+    transport.assert_no_more_requests();
+    result
+}
+
+#[test]
+fn transaction_by_hash_returns_known_transaction() {
+    let tx_hash = H256::from([7; 32]);
+    let transaction = mock_transaction(tx_hash);
+
+    let result =
+        transaction_by_hash_with_response(tx_hash, serde_json::to_value(&transaction).unwrap());
+
+    assert_eq!(result, Some(transaction));
+}
+
+#[test]
+fn transaction_by_hash_returns_none_for_unknown_transaction() {
+    let tx_hash = H256::from([7; 32]);
+
+    let result = transaction_by_hash_with_response(tx_hash, jsonrpc_core::Value::Null);
+
+    assert_eq!(result, None);
+}
+
+fn get_storage_at_with_response(response: jsonrpc_core::Value) -> H256 {
+    let registry = Arc::new(MockMetricsRegistry::new());
+    let mut transport = TestTransport::default();
+    transport.add_response(response);
+
+    let logger = Logger::root(slog::Discard, o!());
+    let provider_metrics = Arc::new(ProviderEthRpcMetrics::new(registry.clone()));
+    let subgraph_metrics = Arc::new(SubgraphEthRpcMetrics::new(
+        registry.clone(),
+        String::from("storage"),
+    ));
+    let adapter = EthereumAdapter::new(transport.clone(), provider_metrics);
+
+    let address = Address::from_low_u64_be(7);
+    let slot = H256::from_low_u64_be(3);
+    let block = EthereumBlockPointer {
+        hash: H256::from([1; 32]),
+        number: 10,
+    };
+
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    let value = runtime
+        .block_on(adapter.get_storage_at(&logger, subgraph_metrics, address, slot, block))
+        .expect("failed to read storage slot");
+
+    // The block is passed by hash, so the read can't silently return the
+    // state of a block that replaced it in a reorg
+    transport.assert_request(
+        "eth_getStorageAt",
+        &[
+            serde_json::to_string(&address).unwrap(),
+            serde_json::to_string(&slot).unwrap(),
+            serde_json::to_string(&serde_json::json!({ "blockHash": block.hash })).unwrap(),
+        ],
+    );
+    transport.assert_no_more_requests();
+    value
+}
+
+#[test]
+fn get_storage_at_returns_slot_value() {
+    let value = H256::from([0xab; 32]);
+
+    let result = get_storage_at_with_response(serde_json::to_value(value).unwrap());
+
+    assert_eq!(result, value);
+}
+
+#[test]
+fn get_storage_at_decodes_zero_padded_value() {
+    let result = get_storage_at_with_response(jsonrpc_core::Value::String(
+        "0x000000000000000000000000000000000000000000000000000000000000002a".to_owned(),
+    ));
+
+    assert_eq!(result, H256::from_low_u64_be(42));
+}
+
+/// Asks an Ethereum node that answers the first `eth_getBalance` request
+/// with `response` whether it is an archive node, twice.
+fn is_archive_with_response(response: Result<jsonrpc_core::Value, jsonrpc_core::Error>) -> bool {
+    let registry = Arc::new(MockMetricsRegistry::new());
+    let mut transport = TestTransport::default();
+    match response {
+        Ok(value) => transport.add_response(value),
+        Err(error) => transport.add_error_response(error),
+    }
+
+    let logger = Logger::root(slog::Discard, o!());
+    let provider_metrics = Arc::new(ProviderEthRpcMetrics::new(registry));
+    let adapter = EthereumAdapter::new(transport.clone(), provider_metrics);
+
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    let is_archive = runtime
+        .block_on(adapter.is_archive(&logger))
+        .expect("failed to probe for an archive node");
+
+    // The answer is cached, so asking again doesn't send another request
+    assert_eq!(
+        runtime.block_on(adapter.is_archive(&logger)).unwrap(),
+        is_archive
+    );
+    transport.assert_request(
+        "eth_getBalance",
+        &[
+            serde_json::to_string(&Address::zero()).unwrap(),
+            String::from("\"0x1\""),
+        ],
+    );
+    transport.assert_no_more_requests();
+    is_archive
+}
+
+#[test]
+fn is_archive_if_old_state_is_available() {
+    assert!(is_archive_with_response(Ok(jsonrpc_core::Value::String(
+        "0x0".to_owned()
+    ))));
+}
+
+#[test]
+fn is_not_archive_if_old_state_is_pruned() {
+    assert!(!is_archive_with_response(Err(jsonrpc_core::Error {
+        code: jsonrpc_core::ErrorCode::ServerError(-32000),
+        message: String::from("missing trie node 4f1a2e6b7c0d9a8b (path )"),
+        data: None,
+    })));
+}
+
+/// Requests the logs of the mock data source in block 1 once per entry of
+/// `attempts`, with the Ethereum node returning that entry's logs, while the
+/// chain head is at `head`. Returns the result of every attempt and the
+/// number of inconsistencies counted.
+fn logs_in_block_across_attempts(
+    head: u64,
+    attempts: Vec<Vec<Log>>,
+) -> (Vec<Result<Vec<Log>, Error>>, f64) {
+    let log_filter =
+        EthereumLogFilter::from_data_sources(vec![&mock_data_source_with_event_handler()]);
+    logs_in_block_across_filtered_attempts(
+        head,
+        attempts
+            .into_iter()
+            .map(|logs| (log_filter.clone(), logs))
+            .collect(),
+    )
+}
+
+/// Like `logs_in_block_across_attempts`, but each attempt requests the logs
+/// with its own log filter.
+fn logs_in_block_across_filtered_attempts(
+    head: u64,
+    attempts: Vec<(EthereumLogFilter, Vec<Log>)>,
+) -> (Vec<Result<Vec<Log>, Error>>, f64) {
+    let registry = Arc::new(MockMetricsRegistry::new());
+    let mut transport = TestTransport::default();
+
+    let logger = Logger::root(slog::Discard, o!());
+    let provider_metrics = Arc::new(ProviderEthRpcMetrics::new(registry.clone()));
+    let subgraph_metrics = Arc::new(SubgraphEthRpcMetrics::new(
+        registry.clone(),
+        String::from("consistency"),
+    ));
+    let adapter =
+        EthereumAdapter::new(transport.clone(), provider_metrics.clone()).with_consistency_check();
+
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    let results = attempts
+        .into_iter()
+        .enumerate()
+        .map(|(i, (log_filter, logs))| {
+            // Once a chain head is known, it is not requested again
+            if i == 0 {
+                transport.add_response(jsonrpc_core::Value::String(format!("0x{:x}", head)));
+            }
+            transport.add_response(serde_json::to_value(logs).unwrap());
+
+            runtime.block_on(adapter.logs_in_block_range(
+                &logger,
+                subgraph_metrics.clone(),
+                1,
+                1,
+                log_filter,
+                None,
+            ))
+        })
+        .collect();
+
+    (results, provider_metrics.inconsistencies("eth_getLogs"))
+}
+
+#[test]
+fn consistency_check_fails_on_different_logs_for_final_block() {
+    let tx_hash = H256::from([7; 32]);
+    let logs = vec![mock_log(tx_hash, 0), mock_log(tx_hash, 1)];
+
+    let (results, inconsistencies) =
+        logs_in_block_across_attempts(1000, vec![logs.clone(), logs[..1].to_vec()]);
+
+    assert_eq!(results[0].as_ref().unwrap(), &logs);
+    let err = results[1]
+        .as_ref()
+        .expect_err("different logs on retry should be an error");
+    match err.downcast_ref::<EthereumAdapterError>() {
+        Some(EthereumAdapterError::InconsistentResults {
+            method: "eth_getLogs",
+            from: 1,
+            to: 1,
+            previous: 2,
+            current: 1,
+        }) => (),
+        _ => panic!("unexpected error: {}", err),
+    }
+    assert_eq!(inconsistencies, 1.0);
+}
+
+#[test]
+fn consistency_check_accepts_identical_logs_for_final_block() {
+    let tx_hash = H256::from([7; 32]);
+    let logs = vec![mock_log(tx_hash, 0), mock_log(tx_hash, 1)];
+
+    // The order in which the logs are returned doesn't matter
+    let (results, inconsistencies) = logs_in_block_across_attempts(
+        1000,
+        vec![logs.clone(), logs.iter().rev().cloned().collect()],
+    );
+
+    assert!(results.iter().all(|result| result.is_ok()));
+    assert_eq!(inconsistencies, 0.0);
+}
+
+#[test]
+fn consistency_check_recognizes_requests_regardless_of_the_filter_order() {
+    let tx_hash = H256::from([7; 32]);
+    let logs = vec![mock_log(tx_hash, 0), mock_log(tx_hash, 1)];
+
+    // The same contract and events, collected in a different order
+    let log_filter = |events: &[&str]| {
+        let mut data_source = mock_data_source_with_event_handler();
+        data_source.mapping.event_handlers = events
+            .iter()
+            .map(|event| MappingEventHandler {
+                event: event.to_string(),
+                topic0: None,
+                handler: String::from("handleEvent"),
+            })
+            .collect();
+        EthereumLogFilter::from_data_sources(vec![&data_source])
+    };
+    let transfer = "Transfer(address,address,uint256)";
+    let approval = "Approval(address,address,uint256)";
+
+    let (_, inconsistencies) = logs_in_block_across_filtered_attempts(
+        1000,
+        vec![
+            (log_filter(&[transfer, approval]), logs.clone()),
+            (log_filter(&[approval, transfer]), logs[..1].to_vec()),
+        ],
+    );
+
+    assert_eq!(inconsistencies, 1.0);
+}
+
+#[test]
+fn consistency_check_ignores_blocks_that_are_not_final() {
+    let tx_hash = H256::from([7; 32]);
+    let logs = vec![mock_log(tx_hash, 0), mock_log(tx_hash, 1)];
+
+    let (results, inconsistencies) =
+        logs_in_block_across_attempts(10, vec![logs.clone(), logs[..1].to_vec()]);
+
+    assert_eq!(results[1].as_ref().unwrap(), &logs[..1].to_vec());
+    assert_eq!(inconsistencies, 0.0);
+}
+
+#[test]
+fn logs_in_block_range_filters_by_event_signatures() {
+    let registry = Arc::new(MockMetricsRegistry::new());
+    let mut transport = TestTransport::default();
+    transport.add_response(serde_json::to_value(Vec::<Log>::new()).unwrap());
+
+    let logger = Logger::root(slog::Discard, o!());
+    let provider_metrics = Arc::new(ProviderEthRpcMetrics::new(registry.clone()));
+    let subgraph_metrics = Arc::new(SubgraphEthRpcMetrics::new(
+        registry.clone(),
+        String::from("topics"),
+    ));
+    let adapter = EthereumAdapter::new(transport.clone(), provider_metrics);
+
+    let mut data_source = mock_data_source_with_event_handler();
+    data_source
+        .mapping
+        .event_handlers
+        .push(MappingEventHandler {
+            event: String::from("Approval(address,address,uint256)"),
+            topic0: None,
+            handler: String::from("handleApproval"),
+        });
+    let signatures: Vec<_> = data_source
+        .mapping
+        .event_handlers
+        .iter()
+        .map(|handler| serde_json::to_value(handler.topic0()).unwrap())
+        .collect();
+
+    tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(adapter.logs_in_block_range(
+            &logger,
+            subgraph_metrics,
+            1,
+            10,
+            EthereumLogFilter::from_data_sources(vec![&data_source]),
+            None,
+        ))
+        .expect("failed to request logs");
+
+    // The Ethereum node filters by event signature, as the first topic
+    let requests = transport.requests.lock().unwrap();
+    assert_eq!(requests.len(), 1);
+    let (method, params) = &requests[0];
+    assert_eq!(method, "eth_getLogs");
+    let topics = match params[0].get("topics") {
+        Some(jsonrpc_core::Value::Array(topics)) => topics,
+        topics => panic!("unexpected topics: {:?}", topics),
+    };
+    assert!(topics[1..].iter().all(|topic| topic.is_null()));
+    let mut topic0 = match &topics[0] {
+        jsonrpc_core::Value::Array(topic0) => topic0.clone(),
+        topic0 => panic!("unexpected topic0: {:?}", topic0),
+    };
+    topic0.sort_by_key(|signature| signature.to_string());
+    let mut expected = signatures;
+    expected.sort_by_key(|signature| signature.to_string());
+    assert_eq!(topic0, expected);
+    assert_eq!(
+        params[0].get("address"),
+        Some(&serde_json::json!([data_source.source.address.unwrap()]))
+    );
+}
+
+#[test]
+fn logs_in_block_range_skips_filters_that_start_after_the_range() {
+    let registry = Arc::new(MockMetricsRegistry::new());
+    let mut transport = TestTransport::default();
+    transport.add_response(serde_json::to_value(Vec::<Log>::new()).unwrap());
+
+    let logger = Logger::root(slog::Discard, o!());
+    let provider_metrics = Arc::new(ProviderEthRpcMetrics::new(registry.clone()));
+    let subgraph_metrics = Arc::new(SubgraphEthRpcMetrics::new(
+        registry.clone(),
+        String::from("start_blocks"),
+    ));
+    let adapter = EthereumAdapter::new(transport.clone(), provider_metrics);
+
+    // E.g. a data source created from a template at block 100
+    let mut data_source = mock_data_source_with_event_handler();
+    data_source.source.start_block = 100;
+    let log_filter = EthereumLogFilter::from_data_sources(vec![&data_source]);
+
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    let logs = runtime
+        .block_on(adapter.logs_in_block_range(
+            &logger,
+            subgraph_metrics.clone(),
+            1,
+            99,
+            log_filter.clone(),
+            None,
+        ))
+        .expect("failed to request logs");
+    assert!(logs.is_empty());
+    assert!(transport.requests.lock().unwrap().is_empty());
+
+    // Ranges that reach the start block are requested
+    runtime
+        .block_on(adapter.logs_in_block_range(&logger, subgraph_metrics, 90, 100, log_filter, None))
+        .expect("failed to request logs");
+    assert_eq!(transport.requests.lock().unwrap().len(), 1);
+}
+
+#[test]
+fn logs_in_block_range_errors_describe_the_request() {
+    let registry = Arc::new(MockMetricsRegistry::new());
+    let mut transport = TestTransport::default();
+
+    // The error an Ethereum node returns when a request would return too many
+    // logs is not retried, and fails for a single block
+    transport.add_error_response(jsonrpc_core::Error {
+        code: jsonrpc_core::ErrorCode::ServerError(-32005),
+        message: String::from("query returned more than 10000 results"),
+        data: None,
+    });
+
+    let logger = Logger::root(slog::Discard, o!());
+    let provider_metrics = Arc::new(ProviderEthRpcMetrics::new(registry.clone()));
+    let subgraph_metrics = Arc::new(SubgraphEthRpcMetrics::new(
+        registry.clone(),
+        String::from("errors"),
+    ));
+    let adapter = EthereumAdapter::new(transport.clone(), provider_metrics);
+
+    let err = tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(adapter.logs_in_block_range(
+            &logger,
+            subgraph_metrics,
+            7,
+            7,
+            EthereumLogFilter::from_data_sources(vec![&mock_data_source_with_event_handler()]),
+            None,
+        ))
+        .expect_err("requesting too many logs should fail");
+
+    let message = err.to_string();
+    assert!(
+        message.starts_with(
+            "Failed to get logs for blocks [7, 7] of 1 contracts with 1 event signatures: "
+        ),
+        "unexpected error: {}",
+        message
+    );
+    assert!(message.contains("query returned more than 10000 results"));
+
+    // The error of the Ethereum node is kept as the cause
+    assert_eq!(err.iter_chain().count(), 2);
+    assert!(err
+        .find_root_cause()
+        .to_string()
+        .contains("ServerError(-32005)"));
+}
+
+#[test]
+fn block_stream_metrics_track_the_latest_block() {
+    let registry = Arc::new(MockMetricsRegistry::new());
+    let logger = Logger::root(slog::Discard, o!());
+    let deployment_id = SubgraphDeploymentId::new("metrics").unwrap();
+    let sync_statuses = Arc::new(DeploymentSyncStatuses::new());
+    let metrics = BlockStreamMetrics::new(
+        registry.clone(),
+        Arc::new(SubgraphEthRpcMetrics::new(
+            registry.clone(),
+            String::from("metrics"),
+        )),
+        deployment_id.clone(),
+        String::from("mainnet"),
+        StopwatchMetrics::new(logger, deployment_id.clone(), registry.clone()),
+    )
+    .with_sync_statuses(sync_statuses.clone());
+
+    // A stream that advances the deployment by two blocks
+    let ptrs = vec![
+        EthereumBlockPointer::from((H256::from_low_u64_be(1), 1u64)),
+        EthereumBlockPointer::from((H256::from_low_u64_be(2), 2u64)),
+    ];
+    let latest_hash = |metrics: &BlockStreamMetrics| {
+        let families = metrics.latest_block_hash.collect();
+        let hashes: Vec<_> = families[0]
+            .get_metric()
+            .iter()
+            .map(|metric| {
+                assert_eq!(metric.get_gauge().get_value(), 1.0);
+                metric.get_label()[0].get_value().to_owned()
+            })
+            .collect();
+        hashes
+    };
+
+    stream::iter_ok::<_, ()>(ptrs.clone())
+        .take(1)
+        .for_each(|ptr| Ok(metrics.block_processed(&ptr)))
+        .wait()
+        .unwrap();
+    assert_eq!(metrics.latest_block_number.get(), 1.0);
+    assert_eq!(latest_hash(&metrics), vec![ptrs[0].hash_hex()]);
+    assert_eq!(metrics.blocks_processed.get(), 1.0);
+
+    stream::iter_ok::<_, ()>(ptrs.clone())
+        .skip(1)
+        .for_each(|ptr| Ok(metrics.block_processed(&ptr)))
+        .wait()
+        .unwrap();
+    assert_eq!(metrics.latest_block_number.get(), 2.0);
+    // The hash of the previous block is replaced, not added to
+    assert_eq!(latest_hash(&metrics), vec![ptrs[1].hash_hex()]);
+    assert_eq!(metrics.blocks_processed.get(), 2.0);
+
+    // Reverting moves the latest block back without counting it
+    metrics.set_latest_block(&ptrs[0]);
+    assert_eq!(metrics.latest_block_number.get(), 1.0);
+    assert_eq!(latest_hash(&metrics), vec![ptrs[0].hash_hex()]);
+    assert_eq!(metrics.blocks_processed.get(), 2.0);
+
+    // The sync status is read back from the gauges of the deployment
+    assert!(metrics.blocks_per_second.get() > 0.0);
+    metrics.blocks_behind.set(10.0);
+    let status = sync_statuses
+        .get(&deployment_id)
+        .expect("the deployment has block stream metrics");
+    assert_eq!(status.latest_block, 1);
+    assert_eq!(status.chain_head_block, 11);
+    assert_eq!(status.blocks_behind(), 10);
+    assert!(status.estimated_seconds_to_sync().is_some());
+    assert_eq!(
+        sync_statuses.get(&SubgraphDeploymentId::new("unknown").unwrap()),
+        None
+    );
+
+    // Unregistering the metrics makes the sync status unknown
+    metrics.unregister(registry);
+    assert_eq!(sync_statuses.get(&deployment_id), None);
+}
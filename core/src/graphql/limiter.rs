@@ -0,0 +1,312 @@
+use futures::future;
+use futures::sync::oneshot;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use graph::prelude::*;
+
+struct QueryLimiterMetrics {
+    in_flight: Box<GaugeVec>,
+    queued: Box<GaugeVec>,
+}
+
+impl QueryLimiterMetrics {
+    fn new<M: MetricsRegistry>(registry: Arc<M>) -> Self {
+        let in_flight = registry
+            .new_gauge_vec(
+                String::from("query_executions_in_flight"),
+                String::from("Counts the GraphQL queries running for a subgraph deployment"),
+                HashMap::new(),
+                vec![String::from("deployment")],
+            )
+            .expect("failed to create `query_executions_in_flight` gauge");
+        let queued = registry
+            .new_gauge_vec(
+                String::from("query_executions_queued"),
+                String::from("Counts the GraphQL queries waiting to run for a subgraph deployment"),
+                HashMap::new(),
+                vec![String::from("deployment")],
+            )
+            .expect("failed to create `query_executions_queued` gauge");
+        Self { in_flight, queued }
+    }
+
+    fn set(&self, deployment: &SubgraphDeploymentId, queue: &DeploymentQueue) {
+        self.in_flight
+            .with_label_values(&[deployment.as_str()])
+            .set(queue.in_flight as f64);
+        self.queued
+            .with_label_values(&[deployment.as_str()])
+            .set(queue.waiting.len() as f64);
+    }
+}
+
+/// The queries of one deployment that are running or waiting to run.
+#[derive(Default)]
+struct DeploymentQueue {
+    in_flight: usize,
+
+    /// Queries waiting for a permit, in the order in which they arrived.
+    waiting: VecDeque<oneshot::Sender<QueryPermit>>,
+}
+
+struct Inner {
+    max_in_flight: usize,
+    max_queued: usize,
+    retry_after: Duration,
+    metrics: QueryLimiterMetrics,
+    queues: Mutex<HashMap<SubgraphDeploymentId, DeploymentQueue>>,
+}
+
+/// Limits the number of queries that run concurrently for each deployment.
+///
+/// Queries that exceed the limit wait for a running query of the same
+/// deployment to finish and are started in the order in which they arrived.
+/// If too many queries are waiting already, they fail with
+/// `QueryExecutionError::DeploymentBusy` right away. Deployments are limited
+/// independently, so a busy deployment never holds up queries of another.
+#[derive(Clone)]
+pub struct QueryLimiter {
+    inner: Arc<Inner>,
+}
+
+impl QueryLimiter {
+    /// Creates a limiter that runs up to `max_in_flight` queries per
+    /// deployment and lets up to `max_queued` more wait. Queries turned away
+    /// are told to retry after `retry_after`.
+    pub fn new<M: MetricsRegistry>(
+        registry: Arc<M>,
+        max_in_flight: usize,
+        max_queued: usize,
+        retry_after: Duration,
+    ) -> Self {
+        assert!(
+            max_in_flight > 0,
+            "at least one query must be allowed to run"
+        );
+        QueryLimiter {
+            inner: Arc::new(Inner {
+                max_in_flight,
+                max_queued,
+                retry_after,
+                metrics: QueryLimiterMetrics::new(registry),
+                queues: Mutex::new(HashMap::new()),
+            }),
+        }
+    }
+
+    /// Waits until a query of `deployment` may run. The query may run for as
+    /// long as the returned permit is held.
+    pub fn acquire(
+        &self,
+        deployment: &SubgraphDeploymentId,
+    ) -> Box<dyn Future<Item = QueryPermit, Error = QueryExecutionError> + Send> {
+        let mut queues = self.inner.queues.lock().unwrap();
+        let queue = queues.entry(deployment.clone()).or_default();
+
+        // Forget about queries whose clients gave up waiting
+        queue.waiting.retain(|sender| !sender.is_canceled());
+
+        let result: Box<dyn Future<Item = _, Error = _> + Send> =
+            if queue.in_flight < self.inner.max_in_flight {
+                queue.in_flight += 1;
+                Box::new(future::ok(QueryPermit {
+                    inner: Some(self.inner.clone()),
+                    deployment: deployment.clone(),
+                }))
+            } else if queue.waiting.len() < self.inner.max_queued {
+                let (sender, receiver) = oneshot::channel();
+                queue.waiting.push_back(sender);
+                let deployment = deployment.clone();
+                let retry_after = self.inner.retry_after;
+                // The sender is only dropped without sending if the limiter
+                // goes away, which tells the query to try again later
+                Box::new(
+                    receiver.map_err(move |_| QueryExecutionError::DeploymentBusy {
+                        deployment,
+                        retry_after,
+                    }),
+                )
+            } else {
+                Box::new(future::err(QueryExecutionError::DeploymentBusy {
+                    deployment: deployment.clone(),
+                    retry_after: self.inner.retry_after,
+                }))
+            };
+
+        self.inner.metrics.set(deployment, queue);
+        result
+    }
+}
+
+/// Permission for a query of a deployment to run. Dropping the permit
+/// passes it on to the next waiting query of the deployment.
+pub struct QueryPermit {
+    inner: Option<Arc<Inner>>,
+    deployment: SubgraphDeploymentId,
+}
+
+impl Drop for QueryPermit {
+    fn drop(&mut self) {
+        let inner = match self.inner.take() {
+            Some(inner) => inner,
+            None => return,
+        };
+
+        loop {
+            // Don't hold the lock while handing the permit over, since
+            // a permit that can't be delivered is dropped right away
+            let next = {
+                let mut queues = inner.queues.lock().unwrap();
+                let queue = queues
+                    .get_mut(&self.deployment)
+                    .expect("running queries have a queue");
+                let next = queue.waiting.pop_front();
+                if next.is_none() {
+                    queue.in_flight -= 1;
+                }
+                inner.metrics.set(&self.deployment, queue);
+                if queue.in_flight == 0 {
+                    queues.remove(&self.deployment);
+                }
+                next
+            };
+
+            let sender = match next {
+                Some(sender) => sender,
+                None => return,
+            };
+            let permit = QueryPermit {
+                inner: Some(inner.clone()),
+                deployment: self.deployment.clone(),
+            };
+            match sender.send(permit) {
+                Ok(()) => return,
+                // The waiting query was canceled; disarm the permit and try
+                // the next one instead
+                Err(mut permit) => {
+                    permit.inner = None;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph::prelude::tokio::runtime::Runtime;
+    use graph_mock::MockMetricsRegistry;
+
+    fn limiter(max_in_flight: usize, max_queued: usize) -> QueryLimiter {
+        QueryLimiter::new(
+            Arc::new(MockMetricsRegistry::new()),
+            max_in_flight,
+            max_queued,
+            Duration::from_secs(3),
+        )
+    }
+
+    fn gauge(gauges: &GaugeVec, deployment: &SubgraphDeploymentId) -> f64 {
+        gauges.with_label_values(&[deployment.as_str()]).get()
+    }
+
+    #[test]
+    fn busy_deployments_do_not_block_other_deployments() {
+        let slow = SubgraphDeploymentId::new("slow").unwrap();
+        let fast = SubgraphDeploymentId::new("fast").unwrap();
+        let limiter = limiter(2, 3);
+
+        // Two queries of the slow deployment run, three wait and the rest
+        // are turned away
+        let running: Vec<_> = (0..2)
+            .map(|_| limiter.acquire(&slow).wait().expect("slow query runs"))
+            .collect();
+        let waiting: Vec<_> = (0..3).map(|_| limiter.acquire(&slow)).collect();
+        let busy: Vec<_> = (0..5)
+            .map(|_| {
+                limiter
+                    .acquire(&slow)
+                    .wait()
+                    .err()
+                    .expect("slow query is turned away")
+            })
+            .collect();
+        assert_eq!(gauge(&limiter.inner.metrics.in_flight, &slow), 2.0);
+        assert_eq!(gauge(&limiter.inner.metrics.queued, &slow), 3.0);
+        for e in busy {
+            match e {
+                QueryExecutionError::DeploymentBusy {
+                    deployment,
+                    retry_after,
+                } => {
+                    assert_eq!(deployment, slow);
+                    assert_eq!(retry_after, Duration::from_secs(3));
+                }
+                e => panic!("unexpected error: {}", e),
+            }
+        }
+
+        // The fast deployment runs its query right away
+        let fast_permit = limiter.acquire(&fast).wait().expect("fast query runs");
+        assert_eq!(gauge(&limiter.inner.metrics.in_flight, &fast), 1.0);
+        drop(fast_permit);
+        assert_eq!(gauge(&limiter.inner.metrics.in_flight, &fast), 0.0);
+
+        // The waiting queries of the slow deployment run once the running
+        // ones finish
+        drop(running);
+        for query in waiting {
+            drop(query.wait().expect("waiting slow query runs"));
+        }
+        assert_eq!(gauge(&limiter.inner.metrics.in_flight, &slow), 0.0);
+        assert_eq!(gauge(&limiter.inner.metrics.queued, &slow), 0.0);
+    }
+
+    #[test]
+    fn waiting_queries_run_in_order_of_arrival() {
+        let deployment = SubgraphDeploymentId::new("fifo").unwrap();
+        let limiter = limiter(1, 10);
+
+        let first = limiter.acquire(&deployment).wait().unwrap();
+        let mut second = limiter.acquire(&deployment);
+        let mut third = limiter.acquire(&deployment);
+        assert_eq!(gauge(&limiter.inner.metrics.queued, &deployment), 2.0);
+
+        let mut runtime = Runtime::new().unwrap();
+        runtime
+            .block_on(future::lazy(move || {
+                assert!(second.poll().unwrap().is_not_ready());
+                assert!(third.poll().unwrap().is_not_ready());
+
+                drop(first);
+                let second = match second.poll().unwrap() {
+                    Async::Ready(permit) => permit,
+                    Async::NotReady => panic!("the second query runs after the first"),
+                };
+                assert!(third.poll().unwrap().is_not_ready());
+
+                drop(second);
+                assert!(third.poll().unwrap().is_ready());
+                Ok::<_, ()>(())
+            }))
+            .unwrap();
+    }
+
+    #[test]
+    fn canceled_queries_give_up_their_place_in_the_queue() {
+        let deployment = SubgraphDeploymentId::new("canceled").unwrap();
+        let limiter = limiter(1, 1);
+
+        let permit = limiter.acquire(&deployment).wait().unwrap();
+        let canceled = limiter.acquire(&deployment);
+        drop(canceled);
+
+        // The canceled query no longer takes up the only place in the queue
+        let waiting = limiter.acquire(&deployment);
+        drop(permit);
+        assert!(waiting.wait().is_ok());
+        assert_eq!(gauge(&limiter.inner.metrics.in_flight, &deployment), 0.0);
+    }
+}
@@ -1,3 +1,5 @@
+mod limiter;
 mod runner;
 
+pub use self::limiter::QueryLimiter;
 pub use self::runner::GraphQlRunner;
@@ -1,5 +1,8 @@
 use futures::future;
+use futures::sync::mpsc;
 use std::env;
+use std::io::{self, Write};
+use std::mem;
 use std::str::FromStr;
 use std::time::{Duration, Instant};
 
@@ -8,10 +11,19 @@ use graph_graphql::prelude::*;
 
 use lazy_static::lazy_static;
 
+use super::QueryLimiter;
+
 /// GraphQL runner implementation for The Graph.
 pub struct GraphQlRunner<S> {
     logger: Logger,
-    store: Arc<S>,
+    multiplexer: SubscriptionMultiplexer,
+    authorizer: Arc<dyn Authorizer>,
+    limiter: Option<QueryLimiter>,
+    shutdown: Option<ShutdownCoordinator>,
+    sync_statuses: Option<Arc<DeploymentSyncStatuses>>,
+    metrics: Arc<QueryMetrics>,
+    /// Queries use clones of this resolver, which share what it caches.
+    resolver: StoreResolver<S>,
 }
 
 lazy_static! {
@@ -30,11 +42,130 @@ lazy_static! {
         .map(|s| u8::from_str(&s)
             .unwrap_or_else(|_| panic!("failed to parse env var GRAPH_GRAPHQL_MAX_DEPTH")))
         .unwrap_or(u8::max_value());
+    static ref GRAPHQL_MAX_RESPONSE_SIZE: Option<usize> =
+        env::var("GRAPH_GRAPHQL_MAX_RESPONSE_SIZE")
+            .ok()
+            .map(|s| usize::from_str(&s).unwrap_or_else(|_| panic!(
+                "failed to parse env var GRAPH_GRAPHQL_MAX_RESPONSE_SIZE"
+            )));
+    static ref GRAPHQL_MAX_FIELDS_PER_SELECTION: Option<usize> =
+        env::var("GRAPH_GRAPHQL_MAX_FIELDS_PER_SELECTION")
+            .ok()
+            .map(|s| usize::from_str(&s).unwrap_or_else(|_| panic!(
+                "failed to parse env var GRAPH_GRAPHQL_MAX_FIELDS_PER_SELECTION"
+            )));
     static ref GRAPHQL_MAX_FIRST: u32 = env::var("GRAPH_GRAPHQL_MAX_FIRST")
         .ok()
         .map(|s| u32::from_str(&s)
             .unwrap_or_else(|_| panic!("failed to parse env var GRAPH_GRAPHQL_MAX_FIRST")))
         .unwrap_or(1000);
+    static ref GRAPHQL_MAX_IN_LIST_LENGTH: Option<usize> =
+        env::var("GRAPH_GRAPHQL_MAX_IN_LIST_LENGTH")
+            .ok()
+            .map(|s| usize::from_str(&s).unwrap_or_else(|_| panic!(
+                "failed to parse env var GRAPH_GRAPHQL_MAX_IN_LIST_LENGTH"
+            )));
+    static ref GRAPHQL_IN_LIST_CHUNK_SIZE: Option<usize> =
+        env::var("GRAPH_GRAPHQL_IN_LIST_CHUNK_SIZE")
+            .ok()
+            .map(|s| usize::from_str(&s).unwrap_or_else(|_| panic!(
+                "failed to parse env var GRAPH_GRAPHQL_IN_LIST_CHUNK_SIZE"
+            )));
+    static ref GRAPHQL_CLAMP_INT_OVERFLOW: bool = env::var("GRAPH_GRAPHQL_CLAMP_INT_OVERFLOW")
+        .map(|value| value == "true")
+        .unwrap_or(false);
+    static ref GRAPHQL_SUBSCRIPTION_BUFFER_SIZE: usize =
+        env::var("GRAPH_GRAPHQL_SUBSCRIPTION_BUFFER_SIZE")
+            .ok()
+            .map(|s| usize::from_str(&s).unwrap_or_else(|_| panic!(
+                "failed to parse env var GRAPH_GRAPHQL_SUBSCRIPTION_BUFFER_SIZE"
+            )))
+            .unwrap_or(100);
+    static ref GRAPHQL_MAX_CONCURRENT_QUERIES_PER_DEPLOYMENT: Option<usize> =
+        env::var("GRAPH_GRAPHQL_MAX_CONCURRENT_QUERIES_PER_DEPLOYMENT")
+            .ok()
+            .map(|s| usize::from_str(&s).unwrap_or_else(|_| panic!(
+                "failed to parse env var GRAPH_GRAPHQL_MAX_CONCURRENT_QUERIES_PER_DEPLOYMENT"
+            )));
+    static ref GRAPHQL_MAX_QUEUED_QUERIES_PER_DEPLOYMENT: usize =
+        env::var("GRAPH_GRAPHQL_MAX_QUEUED_QUERIES_PER_DEPLOYMENT")
+            .ok()
+            .map(|s| usize::from_str(&s).unwrap_or_else(|_| panic!(
+                "failed to parse env var GRAPH_GRAPHQL_MAX_QUEUED_QUERIES_PER_DEPLOYMENT"
+            )))
+            .unwrap_or(100);
+    static ref GRAPHQL_BUSY_RETRY_AFTER: Duration = env::var("GRAPH_GRAPHQL_BUSY_RETRY_AFTER")
+        .ok()
+        .map(|s| Duration::from_secs(
+            u64::from_str(&s).unwrap_or_else(|_| panic!(
+                "failed to parse env var GRAPH_GRAPHQL_BUSY_RETRY_AFTER"
+            ))
+        ))
+        .unwrap_or(Duration::from_secs(1));
+    static ref GRAPHQL_STREAM_BATCH_SIZE: Option<usize> =
+        env::var("GRAPH_GRAPHQL_STREAM_BATCH_SIZE")
+            .ok()
+            .map(|s| usize::from_str(&s).unwrap_or_else(|_| panic!(
+                "failed to parse env var GRAPH_GRAPHQL_STREAM_BATCH_SIZE"
+            )));
+    static ref GRAPHQL_SYNC_WARNING_BLOCKS: Option<u64> =
+        env::var("GRAPH_GRAPHQL_SYNC_WARNING_BLOCKS")
+            .ok()
+            .map(|s| u64::from_str(&s).unwrap_or_else(|_| panic!(
+                "failed to parse env var GRAPH_GRAPHQL_SYNC_WARNING_BLOCKS"
+            )));
+}
+
+/// The size of the chunks in which the JSON of query results is sent.
+const JSON_CHUNK_SIZE: usize = 64 * 1024;
+
+/// How many chunks of JSON a query writes ahead of those that have been sent.
+const JSON_CHUNKS_AHEAD: usize = 4;
+
+/// Writes JSON to a channel in chunks of `JSON_CHUNK_SIZE`, waiting while
+/// the receiver is `JSON_CHUNKS_AHEAD` chunks behind. Fails once the
+/// receiver is gone, e.g. because the client closed the connection.
+struct JsonChunkWriter {
+    chunk: Vec<u8>,
+    sender: Option<mpsc::Sender<Result<Vec<u8>, QueryError>>>,
+}
+
+impl JsonChunkWriter {
+    fn new(sender: mpsc::Sender<Result<Vec<u8>, QueryError>>) -> Self {
+        JsonChunkWriter {
+            chunk: Vec::with_capacity(JSON_CHUNK_SIZE),
+            sender: Some(sender),
+        }
+    }
+}
+
+impl Write for JsonChunkWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.chunk.extend_from_slice(buf);
+        if self.chunk.len() >= JSON_CHUNK_SIZE {
+            self.flush()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.chunk.is_empty() {
+            return Ok(());
+        }
+        let chunk = mem::replace(&mut self.chunk, Vec::with_capacity(JSON_CHUNK_SIZE));
+        let sender = self
+            .sender
+            .take()
+            .and_then(|sender| sender.send(Ok(chunk)).wait().ok())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::BrokenPipe,
+                    "the query result is no longer received",
+                )
+            })?;
+        self.sender = Some(sender);
+        Ok(())
+    }
 }
 
 impl<S> GraphQlRunner<S>
@@ -42,11 +173,148 @@ where
     S: Store,
 {
     /// Creates a new query runner.
-    pub fn new(logger: &Logger, store: Arc<S>) -> Self {
+    pub fn new<M: MetricsRegistry>(logger: &Logger, store: Arc<S>, registry: Arc<M>) -> Self {
+        let logger = logger.new(o!("component" => "GraphQlRunner"));
+        let limiter = GRAPHQL_MAX_CONCURRENT_QUERIES_PER_DEPLOYMENT.map(|max_in_flight| {
+            QueryLimiter::new(
+                registry.clone(),
+                max_in_flight,
+                *GRAPHQL_MAX_QUEUED_QUERIES_PER_DEPLOYMENT,
+                *GRAPHQL_BUSY_RETRY_AFTER,
+            )
+        });
+        let metrics = Arc::new(QueryMetrics::new(registry.clone()));
+        let multiplexer =
+            SubscriptionMultiplexer::new(&logger, registry, *GRAPHQL_SUBSCRIPTION_BUFFER_SIZE);
+        let resolver = StoreResolver::new(&logger, store);
         GraphQlRunner {
-            logger: logger.new(o!("component" => "GraphQlRunner")),
-            store,
+            logger,
+            multiplexer,
+            authorizer: Arc::new(AllowAll),
+            limiter,
+            shutdown: None,
+            sync_statuses: None,
+            metrics,
+            resolver,
+        }
+    }
+
+    /// Restricts the fields that queries and subscriptions may select to
+    /// those `authorizer` allows; by default, all fields may be selected.
+    pub fn with_authorizer(mut self, authorizer: Arc<dyn Authorizer>) -> Self {
+        self.authorizer = authorizer;
+        self
+    }
+
+    /// Makes queries count as in-flight work of a shutdown coordinated by
+    /// `shutdown`, which turns new queries away once it has started.
+    pub fn with_shutdown(mut self, shutdown: ShutdownCoordinator) -> Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
+
+    /// Reports how far deployments have synced, e.g. in `_meta`, from the
+    /// block streams that record their status in `sync_statuses`.
+    pub fn with_sync_statuses(mut self, sync_statuses: Arc<DeploymentSyncStatuses>) -> Self {
+        self.sync_statuses = Some(sync_statuses);
+        self
+    }
+
+    fn query_context(&self, max_first: u32) -> QueryContext {
+        let mut context = QueryContext::new(
+            self.logger.clone(),
+            GRAPHQL_QUERY_TIMEOUT.map(|t| Instant::now() + t),
+            max_first,
+        );
+        if let Some(max_in_list_length) = *GRAPHQL_MAX_IN_LIST_LENGTH {
+            context.max_in_list_length = max_in_list_length;
         }
+        if let Some(in_list_chunk_size) = *GRAPHQL_IN_LIST_CHUNK_SIZE {
+            context.in_list_chunk_size = in_list_chunk_size;
+        }
+        context.stream_batch_size = *GRAPHQL_STREAM_BATCH_SIZE;
+        context.clamp_int_overflow = *GRAPHQL_CLAMP_INT_OVERFLOW;
+        if let Some(sync_warning_blocks) = *GRAPHQL_SYNC_WARNING_BLOCKS {
+            context.sync_warning_blocks = sync_warning_blocks;
+        }
+        context.shutdown = self.shutdown.clone();
+        context.metrics = Some(self.metrics.clone());
+        context
+    }
+
+    /// The options for running queries with the limits from the environment.
+    fn query_options(&self) -> QueryExecutionOptions<StoreResolver<S>> {
+        QueryExecutionOptions {
+            context: self.query_context(*GRAPHQL_MAX_FIRST),
+            resolver: self.store_resolver(),
+            max_complexity: *GRAPHQL_MAX_COMPLEXITY,
+            max_depth: *GRAPHQL_MAX_DEPTH,
+            max_response_size: *GRAPHQL_MAX_RESPONSE_SIZE,
+            max_fields_per_selection: *GRAPHQL_MAX_FIELDS_PER_SELECTION,
+            authorizer: self.authorizer.clone(),
+        }
+    }
+
+    /// A resolver that reads entities from the store and the sync status of
+    /// deployments from the block streams running on this node.
+    fn store_resolver(&self) -> StoreResolver<S> {
+        let resolver = self.resolver.clone();
+        match &self.sync_statuses {
+            Some(sync_statuses) => resolver.with_sync_statuses(sync_statuses.clone()),
+            None => resolver,
+        }
+    }
+
+    /// Executes `query` with `execute` once the limiter lets a query of its
+    /// deployment run, unless the node is shutting down.
+    fn execute<T, F>(
+        &self,
+        query: Query,
+        options: QueryExecutionOptions<StoreResolver<S>>,
+        execute: F,
+    ) -> Box<dyn Future<Item = T, Error = QueryError> + Send>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Query, QueryExecutionOptions<StoreResolver<S>>) -> T + Send + 'static,
+    {
+        // Turn the query away as a whole, rather than with an error in its
+        // result, so that clients can tell to retry it elsewhere
+        if let Some(shutdown) = &self.shutdown {
+            if shutdown.is_shutting_down() {
+                return Box::new(future::err(QueryError::from(
+                    QueryExecutionError::ShuttingDown,
+                )));
+            }
+        }
+
+        // Queries that waited in the limiter while the shutdown started are
+        // turned away the same way
+        let shutdown = self.shutdown.clone();
+        let run = move |query: Query, options: QueryExecutionOptions<StoreResolver<S>>| {
+            let _in_flight = match &shutdown {
+                Some(shutdown) => match shutdown.begin_work() {
+                    Some(work) => Some(work),
+                    None => return Err(QueryError::from(QueryExecutionError::ShuttingDown)),
+                },
+                None => None,
+            };
+            Ok(execute(&query, options))
+        };
+
+        let limiter = match &self.limiter {
+            Some(limiter) => limiter,
+            None => return Box::new(future::lazy(move || run(query, options))),
+        };
+        Box::new(
+            limiter
+                .acquire(&query.schema.id)
+                .map_err(QueryError::from)
+                .and_then(move |permit| {
+                    let result = run(query, options);
+                    drop(permit);
+                    result
+                }),
+        )
     }
 }
 
@@ -55,18 +323,41 @@ where
     S: Store,
 {
     fn run_query(&self, query: Query) -> QueryResultFuture {
-        let result = execute_query(
-            &query,
-            QueryExecutionOptions {
-                logger: self.logger.clone(),
-                resolver: StoreResolver::new(&self.logger, self.store.clone()),
-                deadline: GRAPHQL_QUERY_TIMEOUT.map(|t| Instant::now() + t),
-                max_complexity: *GRAPHQL_MAX_COMPLEXITY,
-                max_depth: *GRAPHQL_MAX_DEPTH,
-                max_first: *GRAPHQL_MAX_FIRST,
-            },
-        );
-        Box::new(future::ok(result))
+        self.execute(query, self.query_options(), execute_query)
+    }
+
+    fn run_query_to_json(&self, query: Query) -> QueryJsonFuture {
+        let (sender, receiver) = mpsc::channel(JSON_CHUNKS_AHEAD);
+
+        // The query runs on its own, writing its JSON while it is being
+        // sent; errors that end it early follow the JSON written until then
+        let error_sender = sender.clone();
+        let execution = self
+            .execute(query, self.query_options(), move |query, options| {
+                let mut writer = JsonChunkWriter::new(sender);
+                execute_query_to_writer(query, options, &mut writer)
+                    .and_then(|()| writer.flush())
+                    .map_err(|e| {
+                        QueryError::from(QueryExecutionError::ResponseWriteError(e.to_string()))
+                    })
+            })
+            .and_then(|result| result)
+            .or_else(move |e| error_sender.send(Err(e)).then(|_| Ok(())));
+        tokio::spawn(graph::util::futures::blocking(execution));
+
+        // Fail as a whole if the query fails before writing any JSON
+        Box::new(
+            receiver
+                .then(|chunk| match chunk {
+                    Ok(chunk) => chunk,
+                    Err(()) => unreachable!("channel receivers don't fail"),
+                })
+                .into_future()
+                .map(|(first, rest)| -> QueryJsonStream {
+                    Box::new(stream::iter_ok(first).chain(rest))
+                })
+                .map_err(|(e, _)| e),
+        )
     }
 
     fn run_query_with_complexity(
@@ -76,18 +367,19 @@ where
         max_depth: Option<u8>,
         max_first: Option<u32>,
     ) -> QueryResultFuture {
-        let result = execute_query(
-            &query,
+        self.execute(
+            query,
             QueryExecutionOptions {
-                logger: self.logger.clone(),
-                resolver: StoreResolver::new(&self.logger, self.store.clone()),
-                deadline: GRAPHQL_QUERY_TIMEOUT.map(|t| Instant::now() + t),
+                context: self.query_context(max_first.unwrap_or(*GRAPHQL_MAX_FIRST)),
+                resolver: self.store_resolver(),
                 max_complexity: max_complexity,
                 max_depth: max_depth.unwrap_or(*GRAPHQL_MAX_DEPTH),
-                max_first: max_first.unwrap_or(*GRAPHQL_MAX_FIRST),
+                max_response_size: *GRAPHQL_MAX_RESPONSE_SIZE,
+                max_fields_per_selection: *GRAPHQL_MAX_FIELDS_PER_SELECTION,
+                authorizer: self.authorizer.clone(),
             },
-        );
-        Box::new(future::ok(result))
+            execute_query,
+        )
     }
 
     fn run_subscription(&self, subscription: Subscription) -> SubscriptionResultFuture {
@@ -95,11 +387,14 @@ where
             &subscription,
             SubscriptionExecutionOptions {
                 logger: self.logger.clone(),
-                resolver: StoreResolver::new(&self.logger, self.store.clone()),
+                resolver: self
+                    .store_resolver()
+                    .with_multiplexer(self.multiplexer.clone()),
                 timeout: GRAPHQL_QUERY_TIMEOUT.clone(),
                 max_complexity: *GRAPHQL_MAX_COMPLEXITY,
                 max_depth: *GRAPHQL_MAX_DEPTH,
                 max_first: *GRAPHQL_MAX_FIRST,
+                authorizer: self.authorizer.clone(),
             },
         );
 
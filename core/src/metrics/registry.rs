@@ -305,4 +305,104 @@ impl MetricsRegistryTrait for MetricsRegistry {
             }
         };
     }
+
+    fn gather(&self) -> Vec<MetricFamily> {
+        self.registry.gather()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The `deployment` labels of the series in the metric family `name`.
+    fn deployments(registry: &Registry, name: &str) -> Vec<String> {
+        let families: Vec<_> = registry
+            .gather()
+            .into_iter()
+            .filter(|family| family.get_name() == name)
+            .collect();
+        assert!(families.len() <= 1, "`{}` is not a single family", name);
+        let mut deployments: Vec<_> = families
+            .iter()
+            .flat_map(|family| family.get_metric())
+            .flat_map(|metric| metric.get_label())
+            .filter(|label| label.get_name() == "deployment")
+            .map(|label| label.get_value().to_owned())
+            .collect();
+        deployments.sort();
+        deployments
+    }
+
+    fn block_stream_metrics(
+        registry: Arc<MetricsRegistry>,
+        deployment: &str,
+    ) -> BlockStreamMetrics {
+        let logger = Logger::root(slog::Discard, o!());
+        let deployment_id = SubgraphDeploymentId::new(deployment).unwrap();
+        let metrics = BlockStreamMetrics::new(
+            registry.clone(),
+            Arc::new(SubgraphEthRpcMetrics::new(
+                registry.clone(),
+                deployment.to_owned(),
+            )),
+            deployment_id.clone(),
+            String::from("mainnet"),
+            StopwatchMetrics::new(logger, deployment_id, registry),
+        );
+        metrics.blocks_behind.set(1.0);
+        metrics.ethrpc_metrics.observe_request(0.5, "eth_getLogs");
+        metrics.ethrpc_metrics.add_error("eth_getLogs");
+        metrics
+    }
+
+    #[test]
+    fn deployment_metrics_share_families_and_are_unregistered() {
+        let prometheus_registry = Arc::new(Registry::new());
+        let registry = Arc::new(MetricsRegistry::new(
+            Logger::root(slog::Discard, o!()),
+            prometheus_registry.clone(),
+        ));
+
+        let metrics1 = block_stream_metrics(registry.clone(), "deployment1");
+        let _metrics2 = block_stream_metrics(registry.clone(), "deployment2");
+
+        for name in &[
+            "subgraph_blocks_behind",
+            "subgraph_eth_rpc_request_duration",
+            "subgraph_eth_rpc_request_latency",
+            "subgraph_eth_rpc_errors",
+        ] {
+            assert_eq!(
+                deployments(&prometheus_registry, name),
+                vec!["deployment1", "deployment2"]
+            );
+        }
+
+        metrics1.unregister(registry);
+
+        for name in &[
+            "subgraph_blocks_behind",
+            "subgraph_eth_rpc_request_duration",
+            "subgraph_eth_rpc_request_latency",
+            "subgraph_eth_rpc_errors",
+        ] {
+            assert_eq!(deployments(&prometheus_registry, name), vec!["deployment2"]);
+        }
+        assert_eq!(registry_errors(&prometheus_registry), 0.0);
+    }
+
+    /// The number of failed registrations and unregistrations.
+    fn registry_errors(registry: &Registry) -> f64 {
+        registry
+            .gather()
+            .into_iter()
+            .filter(|family| {
+                family.get_name() == "metrics_register_errors"
+                    || family.get_name() == "metrics_unregister_errors"
+            })
+            .flat_map(|family| family.get_metric().to_vec())
+            .map(|metric| metric.get_counter().get_value())
+            .sum()
+    }
 }
@@ -22,12 +22,17 @@ pub struct SubgraphInstance<T: RuntimeHostBuilder> {
     /// Runtime hosts, one for each data source mapping.
     ///
     /// The runtime hosts are created and added in the same order the
-    /// data sources appear in the subgraph manifest. Incoming block
-    /// stream events are processed by the mappings in this same order.
+    /// data sources appear in the subgraph manifest, followed by dynamic
+    /// data sources. Incoming block stream events are processed by the
+    /// mappings in the order of their `DataSourceOrdinal`.
     hosts: Vec<Arc<T::Host>>,
 
     /// Maps a serialized module to a channel to the thread in which the module is instantiated.
     module_cache: HashMap<Vec<u8>, Sender<T::Req>>,
+
+    /// Contracts created by the factories of the subgraph, shared with the
+    /// log filter of the block stream that discovers them.
+    factory_children: FactoryChildren,
 }
 
 impl<T> SubgraphInstance<T>
@@ -39,6 +44,7 @@ where
         manifest: SubgraphManifest,
         host_builder: T,
         host_metrics: Arc<HostMetrics>,
+        factory_children: FactoryChildren,
     ) -> Result<Self, Error> {
         let subgraph_id = manifest.id.clone();
         let network = manifest.network_name()?;
@@ -50,6 +56,7 @@ where
             network,
             hosts: Vec::new(),
             module_cache: HashMap::new(),
+            factory_children,
         };
 
         // Create a new runtime host for each data source in the subgraph manifest;
@@ -112,10 +119,87 @@ where
             top_level_templates,
             mapping_request_sender,
             host_metrics,
+            self.factory_children.clone(),
         )
     }
 }
 
+/// Orders the hosts matching a trigger by their data source ordinal, so that
+/// the trigger is processed the same way no matter how the hosts were
+/// collected. The sort is stable, hosts with equal ordinals keep their order.
+fn in_processing_order<H: RuntimeHost>(hosts: impl Iterator<Item = Arc<H>>) -> Vec<Arc<H>> {
+    let mut hosts: Vec<_> = hosts.collect();
+    hosts.sort_by_key(|host| host.data_source_ordinal());
+    hosts
+}
+
+/// Processes a trigger in all hosts that match it, one after the other and in
+/// the order of their data source ordinals.
+fn process_trigger_in_hosts<H: RuntimeHost>(
+    logger: &Logger,
+    hosts: impl Iterator<Item = Arc<H>>,
+    block: Arc<LightEthereumBlock>,
+    trigger: EthereumTrigger,
+    state: BlockState,
+) -> Box<dyn Future<Item = BlockState, Error = Error> + Send> {
+    let logger = logger.to_owned();
+    match trigger {
+        EthereumTrigger::Log(log) => {
+            let transaction = block
+                .transaction_for_log(&log)
+                .map(Arc::new)
+                .ok_or_else(|| format_err!("Found no transaction for event"));
+            let matching_hosts = in_processing_order(hosts.filter(|host| host.matches_log(&log)));
+            let log = Arc::new(log);
+
+            // Process the log in each host in the order in which the corresponding
+            // data sources appear in the subgraph manifest or have been created
+            Box::new(future::result(transaction).and_then(|transaction| {
+                stream::iter_ok(matching_hosts).fold(state, move |state, host| {
+                    host.process_log(
+                        logger.clone(),
+                        block.clone(),
+                        transaction.clone(),
+                        log.clone(),
+                        state,
+                    )
+                })
+            }))
+        }
+        EthereumTrigger::Call(call) => {
+            let transaction = block
+                .transaction_for_call(&call)
+                .map(Arc::new)
+                .ok_or_else(|| format_err!("Found no transaction for call"));
+            let matching_hosts = in_processing_order(hosts.filter(|host| host.matches_call(&call)));
+            let call = Arc::new(call);
+
+            Box::new(future::result(transaction).and_then(|transaction| {
+                stream::iter_ok(matching_hosts).fold(state, move |state, host| {
+                    host.process_call(
+                        logger.clone(),
+                        block.clone(),
+                        transaction.clone(),
+                        call.clone(),
+                        state,
+                    )
+                })
+            }))
+        }
+        EthereumTrigger::Block(ptr, trigger_type) => {
+            let matching_hosts = in_processing_order(
+                hosts.filter(|host| host.matches_block(trigger_type.clone(), ptr.number)),
+            );
+
+            Box::new(
+                stream::iter_ok(matching_hosts).fold(state, move |state, host| {
+                    host.process_block(logger.clone(), block.clone(), trigger_type.clone(), state)
+                }),
+            )
+        }
+    }
+}
+
 impl<T> SubgraphInstanceTrait<T::Host> for SubgraphInstance<T>
 where
     T: RuntimeHostBuilder,
@@ -148,71 +232,7 @@ where
         trigger: EthereumTrigger,
         state: BlockState,
     ) -> Box<dyn Future<Item = BlockState, Error = Error> + Send> {
-        let logger = logger.to_owned();
-        match trigger {
-            EthereumTrigger::Log(log) => {
-                let transaction = block
-                    .transaction_for_log(&log)
-                    .map(Arc::new)
-                    .ok_or_else(|| format_err!("Found no transaction for event"));
-                let matching_hosts: Vec<_> = hosts.filter(|host| host.matches_log(&log)).collect();
-                let log = Arc::new(log);
-
-                // Process the log in each host in the same order the corresponding data
-                // sources appear in the subgraph manifest
-                Box::new(future::result(transaction).and_then(|transaction| {
-                    stream::iter_ok(matching_hosts).fold(state, move |state, host| {
-                        host.process_log(
-                            logger.clone(),
-                            block.clone(),
-                            transaction.clone(),
-                            log.clone(),
-                            state,
-                        )
-                    })
-                }))
-            }
-            EthereumTrigger::Call(call) => {
-                let transaction = block
-                    .transaction_for_call(&call)
-                    .map(Arc::new)
-                    .ok_or_else(|| format_err!("Found no transaction for call"));
-                let matching_hosts: Vec<_> = hosts
-                    .into_iter()
-                    .filter(|host| host.matches_call(&call))
-                    .collect();
-                let call = Arc::new(call);
-
-                Box::new(future::result(transaction).and_then(|transaction| {
-                    stream::iter_ok(matching_hosts).fold(state, move |state, host| {
-                        host.process_call(
-                            logger.clone(),
-                            block.clone(),
-                            transaction.clone(),
-                            call.clone(),
-                            state,
-                        )
-                    })
-                }))
-            }
-            EthereumTrigger::Block(ptr, trigger_type) => {
-                let matching_hosts: Vec<_> = hosts
-                    .into_iter()
-                    .filter(|host| host.matches_block(trigger_type.clone(), ptr.number))
-                    .collect();
-
-                Box::new(
-                    stream::iter_ok(matching_hosts).fold(state, move |state, host| {
-                        host.process_block(
-                            logger.clone(),
-                            block.clone(),
-                            trigger_type.clone(),
-                            state,
-                        )
-                    }),
-                )
-            }
-        }
+        process_trigger_in_hosts(logger, hosts, block, trigger, state)
     }
 
     fn add_dynamic_data_source(
@@ -242,3 +262,149 @@ where
         Ok(host)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use web3::types::{Address, Bytes, Transaction, H256};
+
+    /// A host that handles every log by recording its name.
+    #[derive(Debug)]
+    struct MockHost {
+        name: &'static str,
+        ordinal: DataSourceOrdinal,
+        processed: Arc<Mutex<Vec<&'static str>>>,
+    }
+
+    impl RuntimeHost for MockHost {
+        fn data_source_ordinal(&self) -> DataSourceOrdinal {
+            self.ordinal
+        }
+
+        fn matches_log(&self, _: &Log) -> bool {
+            true
+        }
+
+        fn matches_call(&self, _: &EthereumCall) -> bool {
+            false
+        }
+
+        fn matches_block(&self, _: EthereumBlockTriggerType, _: u64) -> bool {
+            false
+        }
+
+        fn process_log(
+            &self,
+            _: Logger,
+            _: Arc<LightEthereumBlock>,
+            _: Arc<Transaction>,
+            _: Arc<Log>,
+            state: BlockState,
+        ) -> Box<dyn Future<Item = BlockState, Error = Error> + Send> {
+            self.processed.lock().unwrap().push(self.name);
+            Box::new(future::ok(state))
+        }
+
+        fn process_call(
+            &self,
+            _: Logger,
+            _: Arc<LightEthereumBlock>,
+            _: Arc<Transaction>,
+            _: Arc<EthereumCall>,
+            _: BlockState,
+        ) -> Box<dyn Future<Item = BlockState, Error = Error> + Send> {
+            unimplemented!()
+        }
+
+        fn process_block(
+            &self,
+            _: Logger,
+            _: Arc<LightEthereumBlock>,
+            _: EthereumBlockTriggerType,
+            _: BlockState,
+        ) -> Box<dyn Future<Item = BlockState, Error = Error> + Send> {
+            unimplemented!()
+        }
+    }
+
+    /// Processes a single log in hosts with the given names and ordinals,
+    /// passed in the given order, and returns the names in the order in
+    /// which the hosts processed the log.
+    fn processing_order(hosts: Vec<(&'static str, DataSourceOrdinal)>) -> Vec<&'static str> {
+        let logger = Logger::root(slog::Discard, o!());
+        let processed = Arc::new(Mutex::new(vec![]));
+        let hosts = hosts.into_iter().map(|(name, ordinal)| {
+            Arc::new(MockHost {
+                name,
+                ordinal,
+                processed: processed.clone(),
+            })
+        });
+
+        let transaction_hash = H256::from_low_u64_be(1);
+        let block = LightEthereumBlock {
+            number: Some(10.into()),
+            transactions: vec![Transaction {
+                hash: transaction_hash,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let log = Log {
+            address: Address::from_low_u64_be(1),
+            topics: vec![H256::from_low_u64_be(2)],
+            data: Bytes(vec![]),
+            block_hash: None,
+            block_number: Some(10.into()),
+            transaction_hash: Some(transaction_hash),
+            transaction_index: Some(0.into()),
+            log_index: Some(0.into()),
+            transaction_log_index: None,
+            log_type: None,
+            removed: None,
+        };
+
+        process_trigger_in_hosts(
+            &logger,
+            hosts,
+            Arc::new(block),
+            EthereumTrigger::Log(log),
+            BlockState::default(),
+        )
+        .wait()
+        .unwrap();
+
+        let processed = processed.lock().unwrap().clone();
+        processed
+    }
+
+    #[test]
+    fn static_data_sources_process_events_in_manifest_order() {
+        assert_eq!(
+            processing_order(vec![
+                ("second", DataSourceOrdinal::Static(1)),
+                ("first", DataSourceOrdinal::Static(0)),
+            ]),
+            vec!["first", "second"]
+        );
+    }
+
+    #[test]
+    fn dynamic_data_sources_process_events_in_creation_order() {
+        assert_eq!(
+            processing_order(vec![
+                ("created later in block 9", DataSourceOrdinal::Dynamic(9, 1)),
+                ("created in block 10", DataSourceOrdinal::Dynamic(10, 0)),
+                ("static", DataSourceOrdinal::Static(3)),
+                ("created first in block 9", DataSourceOrdinal::Dynamic(9, 0)),
+            ]),
+            vec![
+                "static",
+                "created first in block 9",
+                "created later in block 9",
+                "created in block 10",
+            ]
+        );
+    }
+}
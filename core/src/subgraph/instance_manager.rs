@@ -1,17 +1,30 @@
 use futures::future::{loop_fn, Loop};
 use futures::sync::mpsc::{channel, Receiver, Sender};
+use lazy_static::lazy_static;
 use std::collections::HashMap;
+use std::env;
 use std::sync::RwLock;
 use std::time::Instant;
 use uuid::Uuid;
 
 use graph::data::subgraph::schema::{
-    DynamicEthereumContractDataSourceEntity, SubgraphDeploymentEntity,
+    DynamicEthereumContractDataSourceEntity, FactoryChildEntity, SubgraphDeploymentEntity,
 };
 use graph::prelude::{SubgraphInstance as SubgraphInstanceTrait, *};
 
 use super::SubgraphInstance;
 
+lazy_static! {
+    /// Whether subgraphs that need traces are started without their call
+    /// handlers and block handlers with a call filter when the Ethereum node
+    /// for their network can't trace calls. This makes their results
+    /// non-deterministic, which is why such subgraphs fail by default.
+    static ref SKIP_CALL_HANDLERS_WITHOUT_TRACES: bool =
+        env::var("GRAPH_SKIP_CALL_HANDLERS_WITHOUT_TRACES")
+            .map(|value| value == "true")
+            .unwrap_or(false);
+}
+
 type SharedInstanceKeepAliveMap = Arc<RwLock<HashMap<SubgraphDeploymentId, CancelGuard>>>;
 
 struct IndexingInputs<B, S> {
@@ -22,6 +35,9 @@ struct IndexingInputs<B, S> {
     eth_adapter: Arc<dyn EthereumAdapter>,
     stream_builder: B,
     templates_use_calls: bool,
+    /// Whether call handlers and block handlers with a call filter are
+    /// skipped because the Ethereum node can't trace calls.
+    skip_call_handlers: bool,
     top_level_templates: Vec<DataSourceTemplate>,
 }
 
@@ -171,6 +187,9 @@ impl SubgraphInstanceManager {
         host_builder: impl RuntimeHostBuilder,
         block_stream_builder: B,
         metrics_registry: Arc<M>,
+        handler_stats: Arc<dyn HandlerStatsRegistry>,
+        indexing_progress: Arc<dyn IndexingProgressRegistry>,
+        sync_statuses: Arc<DeploymentSyncStatuses>,
     ) -> Self
     where
         S: Store + ChainStore + SubgraphDeploymentStore + EthereumCallCache,
@@ -192,6 +211,9 @@ impl SubgraphInstanceManager {
             host_builder,
             block_stream_builder,
             metrics_registry.clone(),
+            handler_stats,
+            indexing_progress,
+            sync_statuses,
         );
 
         SubgraphInstanceManager {
@@ -209,6 +231,9 @@ impl SubgraphInstanceManager {
         host_builder: impl RuntimeHostBuilder,
         block_stream_builder: B,
         metrics_registry: Arc<M>,
+        handler_stats: Arc<dyn HandlerStatsRegistry>,
+        indexing_progress: Arc<dyn IndexingProgressRegistry>,
+        sync_statuses: Arc<DeploymentSyncStatuses>,
     ) where
         S: Store + ChainStore + SubgraphDeploymentStore + EthereumCallCache,
         B: BlockStreamBuilder,
@@ -256,6 +281,9 @@ impl SubgraphInstanceManager {
                                     .clone(),
                                 manifest,
                                 metrics_registry_for_subgraph.clone(),
+                                handler_stats.clone(),
+                                indexing_progress.clone(),
+                                sync_statuses.clone(),
                             )
                             .map_err(|err| {
                                 error!(
@@ -301,6 +329,9 @@ impl SubgraphInstanceManager {
         eth_adapter: Arc<dyn EthereumAdapter>,
         manifest: SubgraphManifest,
         registry: Arc<M>,
+        handler_stats: Arc<dyn HandlerStatsRegistry>,
+        indexing_progress: Arc<dyn IndexingProgressRegistry>,
+        sync_statuses: Arc<DeploymentSyncStatuses>,
     ) -> Result<(), Error>
     where
         B: BlockStreamBuilder,
@@ -327,18 +358,68 @@ impl SubgraphInstanceManager {
 
         // Obtain filters from the manifest
         let log_filter = EthereumLogFilter::from_data_sources(&manifest.data_sources);
-        let call_filter = EthereumCallFilter::from_data_sources(&manifest.data_sources);
-        let block_filter = EthereumBlockFilter::from_data_sources(&manifest.data_sources);
+        let mut call_filter = EthereumCallFilter::from_data_sources(&manifest.data_sources);
+        let mut block_filter = EthereumBlockFilter::from_data_sources(&manifest.data_sources);
         let start_blocks = manifest.start_blocks();
 
+        // Contracts created by factories in blocks that were already
+        // processed are not discovered again
+        if !log_filter.factories().is_empty() {
+            let factory_children = store
+                .find(FactoryChildEntity::query_for_deployment(&manifest.id))?
+                .iter()
+                .filter_map(FactoryChildEntity::creation)
+                .collect::<Vec<_>>();
+            log_filter.add_factory_children(factory_children);
+        }
+
         // Identify whether there are templates with call handlers or
         // block handlers with call filters; in this case, we need to
         // include calls in all blocks so we cen reprocess the block
         // when new dynamic data sources are being created
-        let templates_use_calls = templates.iter().any(|template| {
+        let mut templates_use_calls = templates.iter().any(|template| {
             template.has_call_handler() || template.has_block_handler_with_call_filter()
         });
 
+        // Make sure the Ethereum node can provide all the triggers that the
+        // subgraph needs before scanning any blocks
+        let required_capabilities =
+            NodeCapabilities::required_by(&call_filter, &block_filter, templates_use_calls);
+        let skip_call_handlers = match negotiate_capabilities(
+            &required_capabilities,
+            eth_adapter.as_ref(),
+            &network_name,
+            *SKIP_CALL_HANDLERS_WITHOUT_TRACES,
+        ) {
+            Ok(skip_call_handlers) => skip_call_handlers,
+            Err(message) => {
+                store.apply_metadata_operations(
+                    SubgraphDeploymentEntity::fatal_error_operations(
+                        &deployment_id,
+                        message.clone(),
+                    ),
+                )?;
+                return Err(format_err!("{}", message));
+            }
+        };
+        if skip_call_handlers {
+            warn!(
+                logger,
+                "Skipping call handlers and block handlers with a call filter \
+                 since the Ethereum node can't trace calls; the results of the \
+                 subgraph are non-deterministic";
+                "network" => &network_name
+            );
+            let (skipped_call_filter, skipped_block_filter) =
+                call_and_block_filters(&manifest.data_sources, true);
+            call_filter = skipped_call_filter;
+            block_filter = skipped_block_filter;
+            templates_use_calls = false;
+            store.apply_metadata_operations(
+                SubgraphDeploymentEntity::mark_non_deterministic_operations(&deployment_id),
+            )?;
+        }
+
         let top_level_templates = manifest.templates.clone();
 
         // Create a subgraph instance from the manifest; this moves
@@ -352,21 +433,33 @@ impl SubgraphInstanceManager {
         let subgraph_metrics_unregister = subgraph_metrics.clone();
         let host_metrics = Arc::new(HostMetrics::new(
             registry.clone(),
-            deployment_id.clone().to_string(),
+            deployment_id.clone(),
             stopwatch_metrics.clone(),
+            handler_stats,
         ));
         let ethrpc_metrics = Arc::new(SubgraphEthRpcMetrics::new(
             registry.clone(),
             deployment_id.to_string(),
         ));
-        let block_stream_metrics = Arc::new(BlockStreamMetrics::new(
-            registry.clone(),
-            ethrpc_metrics.clone(),
-            deployment_id.clone(),
-            stopwatch_metrics,
-        ));
-        let instance =
-            SubgraphInstance::from_manifest(&logger, manifest, host_builder, host_metrics.clone())?;
+        let block_stream_metrics = Arc::new(
+            BlockStreamMetrics::new(
+                registry.clone(),
+                ethrpc_metrics.clone(),
+                deployment_id.clone(),
+                network_name.clone(),
+                stopwatch_metrics,
+            )
+            .with_indexing_progress(indexing_progress)
+            .with_sync_statuses(sync_statuses),
+        );
+        let block_stream_metrics_unregister = block_stream_metrics.clone();
+        let instance = SubgraphInstance::from_manifest(
+            &logger,
+            manifest,
+            host_builder,
+            host_metrics.clone(),
+            log_filter.factory_children(),
+        )?;
 
         // The subgraph state tracks the state of the subgraph instance over time
         let ctx = IndexingContext {
@@ -378,6 +471,7 @@ impl SubgraphInstanceManager {
                 eth_adapter,
                 stream_builder,
                 templates_use_calls,
+                skip_call_handlers,
                 top_level_templates,
             },
             state: IndexingState {
@@ -405,7 +499,8 @@ impl SubgraphInstanceManager {
         let subgraph_runner =
             graph::util::futures::blocking(loop_fn(ctx, move |ctx| run_subgraph(ctx))).then(
                 move |res| {
-                    subgraph_metrics_unregister.unregister(registry);
+                    subgraph_metrics_unregister.unregister(registry.clone());
+                    block_stream_metrics_unregister.unregister(registry);
                     future::result(res)
                 },
             );
@@ -530,7 +625,15 @@ where
             })
         })
         .then(move |res| match res {
-            Ok(_) => unreachable!("block stream finished without error"),
+            // Block streams only end when the node shuts down
+            Ok(_) => {
+                info!(
+                    logger_for_err,
+                    "Subgraph block stream stopped for shutdown";
+                    "id" => id_for_err.to_string(),
+                );
+                Err(())
+            }
             Err(StreamEnd::NeedsRestart(mut ctx)) => {
                 // Increase the restart counter
                 ctx.state.restarts += 1;
@@ -566,7 +669,7 @@ where
 
                 // Set subgraph status to Failed
                 let status_ops =
-                    SubgraphDeploymentEntity::update_failed_operations(&id_for_err, true);
+                    SubgraphDeploymentEntity::fatal_error_operations(&id_for_err, e.to_string());
                 if let Err(e) = store_for_err.apply_metadata_operations(status_ops) {
                     error!(
                         logger_for_err,
@@ -594,6 +697,7 @@ where
     S: ChainStore + Store + EthereumCallCache + SubgraphDeploymentStore,
 {
     let triggers = block.triggers;
+    let factory_children = block.factory_children;
     let block = block.ethereum_block;
 
     let block_ptr = EthereumBlockPointer::from(&block);
@@ -642,9 +746,12 @@ where
         // Note that this algorithm processes data sources spawned on the same block _breadth
         // first_ on the tree implied by the parent-child relationship between data sources. Only a
         // very contrived subgraph would be able to observe this.
+        //
+        // The number of data sources created so far in this block is tracked so that each of them
+        // gets its position in the creation order.
         loop_fn(
-            (ctx, block_state),
-            move |(mut ctx, mut block_state)| -> Box<dyn Future<Item = _, Error = _> + Send> {
+            (ctx, block_state, 0),
+            move |(mut ctx, mut block_state, created)| -> Box<dyn Future<Item = _, Error = _> + Send> {
                 if block_state.created_data_sources.is_empty() {
                     // No new data sources, nothing to do.
                     return Box::new(future::ok(Loop::Break((ctx, block_state))));
@@ -655,13 +762,18 @@ where
                     logger.clone(),
                     &mut ctx,
                     host_metrics.clone(),
+                    block_ptr_for_new_data_sources.number,
+                    created,
                     block_state.created_data_sources.drain(..),
                 ) {
                     Ok(ok) => ok,
                     Err(err) => return Box::new(future::err(err.into())),
                 };
+                let created = created + data_sources.len();
 
                 // Reprocess the triggers from this block that match the new data sources
+                let (call_filter, block_filter) =
+                    call_and_block_filters(&data_sources, ctx.inputs.skip_call_handlers);
                 let logger = logger.clone();
                 let logger1 = logger.clone();
                 let light_block = light_block.clone();
@@ -673,8 +785,8 @@ where
                             ctx.inputs.store.clone(),
                             ctx.ethrpc_metrics.clone(),
                             EthereumLogFilter::from_data_sources(data_sources.iter()),
-                            EthereumCallFilter::from_data_sources(data_sources.iter()),
-                            EthereumBlockFilter::from_data_sources(data_sources.iter()),
+                            call_filter,
+                            block_filter,
                             block.clone(),
                         )
                         .and_then(move |block_with_triggers| {
@@ -717,8 +829,8 @@ where
                                             block_state,
                                         )
                                     })
-                                    .and_then(|block_state| {
-                                        future::ok(Loop::Continue((ctx, block_state)))
+                                    .and_then(move |block_state| {
+                                        future::ok(Loop::Continue((ctx, block_state, created)))
                                     }),
                             )
                         }),
@@ -729,12 +841,26 @@ where
         .from_err()
     })
     // Apply entity operations and advance the stream
-    .and_then(move |(ctx, block_state, needs_restart)| {
+    .and_then(move |(ctx, mut block_state, needs_restart)| {
         // Avoid writing to store if block stream has been canceled
         if block_stream_cancel_handle.is_canceled() {
             return Err(CancelableError::Cancel);
         }
 
+        // Record the contracts created by factories along with this block,
+        // so that they are reverted together with it
+        for creation in factory_children {
+            let entity = FactoryChildEntity::new(
+                ctx.inputs.deployment_id.clone(),
+                creation.factory,
+                creation.address,
+                creation.block_number,
+            );
+            block_state
+                .entity_cache
+                .append(entity.write_entity_operations());
+        }
+
         let section = ctx.host_metrics.stopwatch.start_section("as_modifications");
         let mods = block_state
             .entity_cache
@@ -763,6 +889,7 @@ where
             .map(|should_migrate| {
                 let elapsed = start.elapsed().as_secs_f64();
                 metrics.block_ops_transaction_duration.observe(elapsed);
+                ctx.block_stream_metrics.block_processed(&block_ptr_after);
                 if should_migrate {
                     ctx.inputs.store.migrate_subgraph_deployment(
                         &logger1,
@@ -828,6 +955,8 @@ fn create_dynamic_data_sources<B, T: RuntimeHostBuilder, S>(
     logger: Logger,
     ctx: &mut IndexingContext<B, T, S>,
     host_metrics: Arc<HostMetrics>,
+    block_number: u64,
+    first_index: usize,
     created_data_sources: impl Iterator<Item = DataSourceTemplateInfo>,
 ) -> Result<(Vec<DataSource>, Vec<Arc<T::Host>>), Error>
 where
@@ -837,9 +966,13 @@ where
     let mut data_sources = vec![];
     let mut runtime_hosts = vec![];
 
-    for info in created_data_sources {
+    for (i, info) in created_data_sources.enumerate() {
         // Try to instantiate a data source from the template
-        let data_source = DataSource::try_from_template(info.template, &info.params)?;
+        let data_source = DataSource::try_from_template(
+            info.template,
+            &info.params,
+            DataSourceOrdinal::Dynamic(block_number, first_index + i),
+        )?;
         let host_metrics = host_metrics.clone();
 
         // Try to create a runtime host for the data source
@@ -893,13 +1026,99 @@ fn persist_dynamic_data_sources<B, T: RuntimeHostBuilder, S>(
         .log_filter
         .extend(EthereumLogFilter::from_data_sources(&data_sources));
 
-    // Merge call filters from data sources into the block stream builder
-    ctx.state
-        .call_filter
-        .extend(EthereumCallFilter::from_data_sources(&data_sources));
+    // Merge call and block filters from data sources into the block stream builder
+    let (call_filter, block_filter) =
+        call_and_block_filters(&data_sources, ctx.inputs.skip_call_handlers);
+    ctx.state.call_filter.extend(call_filter);
+    ctx.state.block_filter.extend(block_filter);
+}
 
-    // Merge block filters from data sources into the block stream builder
-    ctx.state
-        .block_filter
-        .extend(EthereumBlockFilter::from_data_sources(&data_sources));
+/// The call and block filters for `data_sources`. If call handlers are
+/// skipped, the filters leave out everything that needs traces.
+fn call_and_block_filters(
+    data_sources: &[DataSource],
+    skip_call_handlers: bool,
+) -> (EthereumCallFilter, EthereumBlockFilter) {
+    let mut call_filter = EthereumCallFilter::from_data_sources(data_sources);
+    let mut block_filter = EthereumBlockFilter::from_data_sources(data_sources);
+    if skip_call_handlers {
+        call_filter.contract_addresses_function_signatures.clear();
+        block_filter.contract_addresses.clear();
+    }
+    (call_filter, block_filter)
+}
+
+/// Checks that `eth_adapter` has the `required` capabilities. Returns
+/// whether call handlers and block handlers with a call filter have to be
+/// skipped because the adapter can't trace calls and
+/// `skip_call_handlers_without_traces` allows that, or the error that fails
+/// the subgraph otherwise.
+fn negotiate_capabilities(
+    required: &NodeCapabilities,
+    eth_adapter: &dyn EthereumAdapter,
+    network_name: &str,
+    skip_call_handlers_without_traces: bool,
+) -> Result<bool, String> {
+    let missing = required.missing_from(&eth_adapter.capabilities());
+    if missing.is_empty() {
+        Ok(false)
+    } else if missing == vec!["traces"] && skip_call_handlers_without_traces {
+        Ok(true)
+    } else {
+        Err(format!(
+            "Subgraph requires capabilities that the Ethereum node for network `{}` \
+             does not support: {}; call handlers and block handlers with a call filter \
+             need traces",
+            network_name,
+            missing.join(", ")
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph_mock::MockEthereumAdapter;
+
+    const TRACES: NodeCapabilities = NodeCapabilities { traces: true };
+    const NO_TRACES: NodeCapabilities = NodeCapabilities { traces: false };
+
+    #[test]
+    fn subgraphs_that_need_traces_fail_without_them() {
+        let adapter = MockEthereumAdapter::default().without_traces();
+
+        assert_eq!(
+            negotiate_capabilities(&TRACES, &adapter, "mainnet", false),
+            Err(String::from(
+                "Subgraph requires capabilities that the Ethereum node for network \
+                 `mainnet` does not support: traces; call handlers and block handlers \
+                 with a call filter need traces"
+            ))
+        );
+
+        // Subgraphs without call handlers don't care
+        assert_eq!(
+            negotiate_capabilities(&NO_TRACES, &adapter, "mainnet", false),
+            Ok(false)
+        );
+        assert_eq!(
+            negotiate_capabilities(&TRACES, &MockEthereumAdapter::default(), "mainnet", false),
+            Ok(false)
+        );
+    }
+
+    #[test]
+    fn call_handlers_are_skipped_without_traces_if_allowed() {
+        let adapter = MockEthereumAdapter::default().without_traces();
+        assert_eq!(
+            negotiate_capabilities(&TRACES, &adapter, "mainnet", true),
+            Ok(true)
+        );
+
+        // Call handlers are only skipped if the node can't trace calls
+        assert_eq!(
+            negotiate_capabilities(&TRACES, &MockEthereumAdapter::default(), "mainnet", true),
+            Ok(false)
+        );
+    }
 }
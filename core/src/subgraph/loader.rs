@@ -44,6 +44,8 @@ where
                 query deployment($id: ID!, $skip: Int!) {
                   subgraphDeployment(id: $id) {
                     dynamicDataSources(orderBy: id, skip: $skip) {
+                      ethereumBlockNumber
+                      creationIndex
                       kind
                       network
                       name
@@ -89,6 +91,9 @@ where
                 ]
                 .into_iter(),
             ))),
+            dry_run: false,
+            sync_warning: false,
+            query_id: None,
         })
     }
 
@@ -127,7 +132,7 @@ where
         &self,
         deployment_id: SubgraphDeploymentId,
         query_result: q::Value,
-    ) -> Result<Vec<EthereumContractDataSourceEntity>, Error> {
+    ) -> Result<Vec<(DataSourceOrdinal, EthereumContractDataSourceEntity)>, Error> {
         let data = match query_result {
             q::Value::Object(obj) => Ok(obj),
             _ => Err(format_err!(
@@ -166,9 +171,13 @@ where
             )),
         }?;
 
-        // Parse the raw data sources into typed entities
+        // Parse the raw data sources into typed entities, along with their
+        // position in the order in which data sources were created
         let entities = values.iter().try_fold(vec![], |mut entities, value| {
-            entities.push(EthereumContractDataSourceEntity::try_from_value(value)?);
+            entities.push((
+                Self::parse_ordinal(value)?,
+                EthereumContractDataSourceEntity::try_from_value(value)?,
+            ));
             Ok(entities)
         }) as Result<Vec<_>, Error>;
 
@@ -181,14 +190,36 @@ where
         })
     }
 
+    fn parse_ordinal(value: &q::Value) -> Result<DataSourceOrdinal, Error> {
+        let map = match value {
+            q::Value::Object(map) => Ok(map),
+            _ => Err(format_err!(
+                "Cannot parse value into a dynamic data source: {:?}",
+                value
+            )),
+        }?;
+
+        // Data sources created before the creation index was recorded
+        // don't have one; they keep the order in which they are loaded
+        let block_number: BigInt = map.get_required("ethereumBlockNumber")?;
+        let index: Option<BigInt> = map.get_optional("creationIndex")?;
+        Ok(DataSourceOrdinal::Dynamic(
+            block_number.to_u64(),
+            index.map_or(0, |index| index.to_u64() as usize),
+        ))
+    }
+
     fn convert_to_unresolved_data_sources(
         &self,
-        entities: Vec<EthereumContractDataSourceEntity>,
+        entities: Vec<(DataSourceOrdinal, EthereumContractDataSourceEntity)>,
     ) -> Vec<UnresolvedDataSource> {
         // Turn the entities into unresolved data sources
         entities
             .into_iter()
-            .map(Into::into)
+            .map(|(ordinal, entity)| UnresolvedDataSource {
+                ordinal,
+                ..entity.into()
+            })
             .collect::<Vec<UnresolvedDataSource>>()
     }
 
@@ -271,12 +302,19 @@ where
                         }
                     })
             })
-            .map(move |state| {
+            .map(move |mut state| {
                 trace!(
                     timing_logger,
                     "Loaded dynamic data sources";
                     "ms" => start_time.elapsed().as_millis()
                 );
+
+                // Return the data sources in the order in which they were
+                // created; the sort is stable so that data sources without a
+                // creation index keep the order they were loaded in
+                state
+                    .data_sources
+                    .sort_by_key(|data_source| data_source.ordinal);
                 state.data_sources
             }),
         )
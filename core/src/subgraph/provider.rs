@@ -176,7 +176,10 @@ where
                     );
 
                     let _ = store.apply_metadata_operations(
-                        SubgraphDeploymentEntity::update_failed_operations(&subgraph_id, true),
+                        SubgraphDeploymentEntity::fatal_error_operations(
+                            &subgraph_id,
+                            e.to_string(),
+                        ),
                     );
                     e
                 }),
@@ -16,7 +16,7 @@ lazy_static! {
 use super::validation;
 use graph::data::subgraph::schema::{
     generate_entity_id, SubgraphDeploymentAssignmentEntity, SubgraphDeploymentEntity,
-    SubgraphEntity, SubgraphVersionEntity, TypedEntity,
+    SubgraphDeploymentPastAssignmentEntity, SubgraphEntity, SubgraphVersionEntity, TypedEntity,
 };
 use graph::prelude::{
     CreateSubgraphResult, SubgraphAssignmentProvider as SubgraphAssignmentProviderTrait,
@@ -304,12 +304,13 @@ where
         let logger = self.logger_factory.subgraph_logger(&hash);
         let logger2 = logger.clone();
         let logger3 = logger.clone();
+        let logger4 = logger.clone();
         let name_inner = name.clone();
 
         Box::new(
             SubgraphManifest::resolve(hash.to_ipfs_link(), self.resolver.clone(), logger.clone())
                 .map_err(SubgraphRegistrarError::ResolveError)
-                .and_then(validation::validate_manifest)
+                .and_then(move |manifest| validation::validate_manifest(manifest, &logger4))
                 .and_then(move |manifest| {
                     manifest
                         .network_name()
@@ -518,14 +519,14 @@ fn resolve_subgraph_chain_blocks(
             Error = SubgraphRegistrarError,
         > + Send,
 > {
+    let chain_store_for_head = chain_store.clone();
+    let logger_for_head = logger.clone();
     Box::new(
         // If the minimum start block is 0 (i.e. the genesis block),
         // return `None` to start indexing from the genesis block. Otherwise
         // return a block pointer for the block with number `min_start_block - 1`.
         match manifest
-            .start_blocks()
-            .into_iter()
-            .min()
+            .min_start_block()
             .expect("cannot identify minimum start block because there are no data sources")
         {
             0 => Box::new(future::ok(None)) as Box<dyn Future<Item = _, Error = _> + Send>,
@@ -533,12 +534,35 @@ fn resolve_subgraph_chain_blocks(
                 ethereum_adapter
                     .block_pointer_from_number(logger, min_start_block - 1)
                     .map(Some)
-                    .map_err(move |_| {
-                        SubgraphRegistrarError::ManifestValidationError(vec![
-                            SubgraphManifestValidationError::BlockNotFound(
-                                min_start_block.to_string(),
-                            ),
-                        ])
+                    .or_else(move |_| {
+                        // A start block after the chain head is most likely a
+                        // typo, but it may also be a block that simply hasn't
+                        // been mined yet. Nothing can be indexed before it, so
+                        // the deployment starts at the chain head and waits
+                        match chain_store_for_head.chain_head_ptr() {
+                            Ok(Some(head)) if min_start_block - 1 > head.number => {
+                                let data_sources = manifest
+                                    .data_sources_starting_after(head.number)
+                                    .into_iter()
+                                    .map(|data_source| data_source.name.as_str())
+                                    .collect::<Vec<_>>()
+                                    .join(", ");
+                                warn!(
+                                    logger_for_head,
+                                    "Start block is after the chain head, the subgraph \
+                                     will not index anything until the chain reaches it";
+                                    "start_block" => min_start_block,
+                                    "chain_head" => head.number,
+                                    "data_sources" => data_sources,
+                                );
+                                Ok(Some(head))
+                            }
+                            _ => Err(SubgraphRegistrarError::ManifestValidationError(vec![
+                                SubgraphManifestValidationError::BlockNotFound(
+                                    min_start_block.to_string(),
+                                ),
+                            ])),
+                        }
                     }),
             ) as Box<dyn Future<Item = _, Error = _> + Send>,
         }
@@ -1094,10 +1118,27 @@ fn reassign_subgraph(
         entity_ids: vec![hash.clone().to_string()],
     });
 
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    // Keep a record of the assignment that is replaced
+    if let Some(past_assignment) = current_deployment.first().and_then(|assignment| {
+        SubgraphDeploymentPastAssignmentEntity::from_assignment(hash.clone(), assignment, now)
+    }) {
+        ops.extend(
+            past_assignment
+                .write_operations()
+                .into_iter()
+                .map(|op| op.into()),
+        );
+    }
+
     // Create the assignment update operations.
     // Note: This will also generate a remove operation for the existing subgraph assignment.
     ops.extend(
-        SubgraphDeploymentAssignmentEntity::new(node_id)
+        SubgraphDeploymentAssignmentEntity::new(node_id, now)
             .write_operations(&hash.clone())
             .into_iter()
             .map(|op| op.into()),
@@ -1,7 +1,9 @@
 use graph::prelude::*;
+use graph_graphql::prelude::{api_schema_with_pluralization, Pluralization};
 
 pub fn validate_manifest(
     manifest: SubgraphManifest,
+    logger: &Logger,
 ) -> Result<SubgraphManifest, SubgraphRegistrarError> {
     let mut errors: Vec<SubgraphManifestValidationError> = Vec::new();
 
@@ -24,31 +26,33 @@ pub fn validate_manifest(
         errors.push(SubgraphManifestValidationError::SourceAddressRequired)
     }
 
-    // Validate that there are no more than one of each type of
-    // block_handler in each data source.
-    let has_too_many_block_handlers = manifest.data_sources.iter().any(|data_source| {
-        if data_source.mapping.block_handlers.is_empty() {
-            return false;
-        }
-
-        let mut non_filtered_block_handler_count = 0;
-        let mut call_filtered_block_handler_count = 0;
-        data_source
-            .mapping
-            .block_handlers
-            .iter()
-            .for_each(|block_handler| {
-                if block_handler.filter.is_none() {
-                    non_filtered_block_handler_count += 1
-                } else {
-                    call_filtered_block_handler_count += 1
-                }
-            });
-        return non_filtered_block_handler_count > 1 || call_filtered_block_handler_count > 1;
-    });
+    // Validate that source addresses are well-formed. Subgraphs that are
+    // already deployed are not checked again when they start
+    if let Err(e) = manifest.validate_source_addresses(logger) {
+        errors.push(e)
+    }
+
+    // Validate that entity types have an `id: ID!` field. This is only required
+    // of new deployments, existing ones are loaded without this check
+    if let Err(e) = manifest.schema.validate_id_fields() {
+        errors.push(SubgraphManifestValidationError::SchemaValidationError(e))
+    }
 
-    if has_too_many_block_handlers {
-        errors.push(SubgraphManifestValidationError::DataSourceBlockHandlerLimitExceeded)
+    // Validate that the GraphQL API can be derived from the schema, e.g. that
+    // no two types generate the same query field
+    if let Err(e) = api_schema_with_pluralization(
+        logger,
+        &manifest.schema.document,
+        Pluralization::for_api_versions(
+            manifest
+                .data_sources
+                .iter()
+                .map(|data_source| data_source.mapping.api_version.as_str()),
+        ),
+    ) {
+        errors.push(SubgraphManifestValidationError::InvalidApiSchema(
+            e.to_string(),
+        ))
     }
 
     if errors.is_empty() {
@@ -1,7 +1,10 @@
 // Tests for graphql interfaces.
 
 use graph::prelude::*;
-use graph_graphql::prelude::{execute_query, QueryExecutionOptions, StoreResolver};
+use graph_graphql::prelude::{
+    execute_query, AllowAll, Authorizer, QueryContext, QueryExecutionOptions, StaticAuthorizer,
+    StoreResolver,
+};
 use test_store::*;
 
 // `entities` is `(entity, type)`.
@@ -10,6 +13,16 @@ fn insert_and_query(
     schema: &str,
     entities: Vec<(Entity, &str)>,
     query: &str,
+) -> Result<QueryResult, StoreError> {
+    insert_and_query_authorized(subgraph_id, schema, entities, query, Arc::new(AllowAll))
+}
+
+fn insert_and_query_authorized(
+    subgraph_id: &str,
+    schema: &str,
+    entities: Vec<(Entity, &str)>,
+    query: &str,
+    authorizer: Arc<dyn Authorizer>,
 ) -> Result<QueryResult, StoreError> {
     let subgraph_id = SubgraphDeploymentId::new(subgraph_id).unwrap();
     let schema = Schema::parse(schema, subgraph_id.clone()).unwrap();
@@ -23,6 +36,7 @@ fn insert_and_query(
         schema: schema.clone(),
         data_sources: vec![],
         templates: vec![],
+        raw: None,
     };
 
     let logger = Logger::root(slog::Discard, o!());
@@ -55,18 +69,22 @@ fn insert_and_query(
     let resolver = StoreResolver::new(&logger, STORE.clone());
 
     let options = QueryExecutionOptions {
-        logger,
+        context: QueryContext::new(logger, None, std::u32::MAX),
         resolver,
-        deadline: None,
         max_complexity: None,
         max_depth: 100,
-        max_first: std::u32::MAX,
+        max_response_size: None,
+        max_fields_per_selection: None,
+        authorizer,
     };
     let document = graphql_parser::parse_query(query).unwrap();
     let query = Query {
         schema: STORE.api_schema(&subgraph_id).unwrap(),
         document,
         variables: None,
+        dry_run: false,
+        sync_warning: false,
+        query_id: None,
     };
     Ok(execute_query(&query, options))
 }
@@ -448,3 +466,35 @@ fn invalid_fragment() {
         e => panic!("error {} is not the expected one", e),
     }
 }
+
+#[test]
+fn interface_fields_are_denied_if_denied_in_an_implementation() {
+    let subgraph_id = "InterfaceFieldsDeniedInImplementation";
+    let schema = "interface Legged { legs: Int }
+                  type Animal implements Legged @entity { id: ID!, legs: Int }
+                  type Furniture implements Legged @entity { id: ID!, legs: Int }";
+    let authorizer = Arc::new(StaticAuthorizer::new().deny_type("Furniture"));
+
+    let query = "query { leggeds(first: 100) { legs } }";
+    let res = insert_and_query_authorized(subgraph_id, schema, vec![], query, authorizer.clone())
+        .unwrap();
+    assert!(res.data.is_none());
+    match res.errors.unwrap().as_slice() {
+        [QueryError::ExecutionError(QueryExecutionError::Unauthorized(_, type_name, field))] => {
+            assert_eq!(type_name, "Furniture");
+            assert_eq!(field, "legs");
+        }
+        errors => panic!("expected an unauthorized error, got {:?}", errors),
+    }
+
+    // Inline fragments on the denied type are rejected, too
+    let query = "query { leggeds(first: 100) { ... on Furniture { id } } }";
+    let res = insert_and_query_authorized(subgraph_id, schema, vec![], query, authorizer).unwrap();
+    match res.errors.unwrap().as_slice() {
+        [QueryError::ExecutionError(QueryExecutionError::Unauthorized(_, type_name, field))] => {
+            assert_eq!(type_name, "Furniture");
+            assert_eq!(field, "id");
+        }
+        errors => panic!("expected an unauthorized error, got {:?}", errors),
+    }
+}
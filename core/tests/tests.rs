@@ -17,7 +17,7 @@ use std::time::Instant;
 use graph::prelude::*;
 
 use graph_core::LinkResolver;
-use graph_mock::{MockEthereumAdapter, MockStore};
+use graph_mock::{MockEthereumAdapter, MockMetricsRegistry, MockStore};
 
 use crate::tokio::timer::Delay;
 
@@ -71,6 +71,10 @@ fn multiple_data_sources_per_subgraph() {
     struct MockRuntimeHost {}
 
     impl RuntimeHost for MockRuntimeHost {
+        fn data_source_ordinal(&self) -> DataSourceOrdinal {
+            DataSourceOrdinal::default()
+        }
+
         fn matches_log(&self, _: &Log) -> bool {
             true
         }
@@ -178,6 +182,9 @@ fn multiple_data_sources_per_subgraph() {
                 host_builder.clone(),
                 block_stream_builder.clone(),
                 metrics_registry,
+                Arc::new(InMemoryHandlerStatsRegistry::new()),
+                Arc::new(InMemoryIndexingProgressRegistry::new()),
+                Arc::new(DeploymentSyncStatuses::new()),
             );
 
             // Load a subgraph with two data sources
@@ -258,7 +265,12 @@ fn subgraph_provider_events() {
                     .into_iter()
                     .map(|e| ("mainnet".to_string(), e))
                     .collect();
-            let graphql_runner = Arc::new(graph_core::GraphQlRunner::new(&logger, store.clone()));
+            let metrics_registry = Arc::new(MockMetricsRegistry::new());
+            let graphql_runner = Arc::new(graph_core::GraphQlRunner::new(
+                &logger,
+                store.clone(),
+                metrics_registry,
+            ));
             let mut provider = graph_core::SubgraphAssignmentProvider::new(
                 &logger_factory,
                 resolver.clone(),
@@ -1,25 +1,99 @@
 use ethabi::{Bytes, Error as ABIError, Function, ParamType, Token};
 use failure::SyncFailure;
 use futures::Future;
+use lazy_static::lazy_static;
 use petgraph::graphmap::GraphMap;
+use prometheus::proto;
 use std::cmp;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::env;
 use std::fmt;
+use std::sync::{Mutex, RwLock};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use tiny_keccak::keccak256;
 use web3::types::*;
 
 use super::types::*;
-use crate::components::metrics::{CounterVec, GaugeVec, HistogramVec};
+use crate::components::metrics::{CounterVec, GaugeVec, HistogramVec, MetricFamily};
 use crate::prelude::*;
 
 pub type EventSignature = H256;
 
+lazy_static! {
+    /// Number of (contract, event) pairs in a log filter above which the
+    /// `eth_getLogs` filters are built with the cheaper
+    /// `LogFilterStrategy::MergeByNeighbors` strategy.
+    static ref LOG_FILTER_EDGE_THRESHOLD: usize =
+        env::var("GRAPH_ETHEREUM_LOG_FILTER_EDGE_THRESHOLD")
+            .map(|s| {
+                s.parse::<usize>()
+                    .expect("invalid GRAPH_ETHEREUM_LOG_FILTER_EDGE_THRESHOLD env var")
+            })
+            .unwrap_or(10_000);
+
+    /// Number of blocks that the end of a scanned block range may be ahead
+    /// of the latest block of the Ethereum node, to allow for nodes that
+    /// are only slightly out of sync with each other.
+    static ref PROVIDER_HEAD_MARGIN: u64 =
+        env::var("GRAPH_ETHEREUM_PROVIDER_HEAD_MARGIN")
+            .map(|s| {
+                s.parse::<u64>()
+                    .expect("invalid GRAPH_ETHEREUM_PROVIDER_HEAD_MARGIN env var")
+            })
+            .unwrap_or(5);
+
+    /// Maximum number of contracts created by a factory that are put into
+    /// the address list of a single `eth_getLogs` filter.
+    static ref FACTORY_CHILDREN_CHUNK_SIZE: usize =
+        env::var("GRAPH_ETHEREUM_FACTORY_CHILDREN_CHUNK_SIZE")
+            .map(|s| {
+                s.parse::<usize>()
+                    .expect("invalid GRAPH_ETHEREUM_FACTORY_CHILDREN_CHUNK_SIZE env var")
+            })
+            .unwrap_or(500);
+}
+
 /// A collection of attributes that (kind of) uniquely identify an Ethereum blockchain.
+#[derive(Clone, Debug)]
 pub struct EthereumNetworkIdentifier {
     pub net_version: String,
     pub genesis_block_hash: H256,
 }
 
+/// Optional features of an Ethereum node that some subgraphs depend on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NodeCapabilities {
+    /// Whether the node can trace the calls made in a block, which call
+    /// handlers and block handlers with a call filter need.
+    pub traces: bool,
+}
+
+impl NodeCapabilities {
+    /// The capabilities needed to find the triggers of the given filters.
+    /// `templates_use_calls` is whether data sources created from templates
+    /// later on may need calls.
+    pub fn required_by(
+        call_filter: &EthereumCallFilter,
+        block_filter: &EthereumBlockFilter,
+        templates_use_calls: bool,
+    ) -> Self {
+        NodeCapabilities {
+            traces: templates_use_calls
+                || !call_filter.is_empty()
+                || !block_filter.contract_addresses.is_empty(),
+        }
+    }
+
+    /// The names of the capabilities in `self` that `available` lacks.
+    pub fn missing_from(&self, available: &NodeCapabilities) -> Vec<&'static str> {
+        let mut missing = vec![];
+        if self.traces && !available.traces {
+            missing.push("traces");
+        }
+        missing
+    }
+}
+
 /// A request for the state of a contract at a specific block hash and address.
 pub struct EthereumContractStateRequest {
     pub address: Address,
@@ -48,8 +122,9 @@ pub struct EthereumContractCall {
 
 #[derive(Fail, Debug)]
 pub enum EthereumContractCallError {
+    /// Shared so that calls in flight can hand the same error to everyone waiting on them
     #[fail(display = "ABI error: {}", _0)]
-    ABIError(SyncFailure<ABIError>),
+    ABIError(Arc<SyncFailure<ABIError>>),
     /// `Token` is not of expected `ParamType`
     #[fail(display = "type mismatch, token {:?} is not of kind {:?}", _0, _1)]
     TypeError(Token, ParamType),
@@ -63,7 +138,7 @@ pub enum EthereumContractCallError {
 
 impl From<ABIError> for EthereumContractCallError {
     fn from(e: ABIError) -> Self {
-        EthereumContractCallError::ABIError(SyncFailure::new(e))
+        EthereumContractCallError::ABIError(Arc::new(SyncFailure::new(e)))
     }
 }
 
@@ -72,11 +147,80 @@ pub enum EthereumAdapterError {
     /// The Ethereum node does not know about this block for some reason, probably because it
     /// disappeared in a chain reorg.
     #[fail(
-        display = "Block data unavailable, block was likely uncled (block hash = {:?})",
+        display = "Block data unavailable, block was likely uncled (block hash = {:#x})",
         _0
     )]
     BlockUnavailable(H256),
 
+    /// The Ethereum node has no receipt for this transaction, either because it doesn't know
+    /// about the transaction or because the transaction has not been mined yet.
+    #[fail(
+        display = "Transaction receipt unavailable (transaction hash = {:#x})",
+        _0
+    )]
+    ReceiptUnavailable(H256),
+
+    /// A block loaded as part of a block range does not build on the block before it,
+    /// probably because of a chain reorg while the range was being loaded.
+    #[fail(
+        display = "Block {} ({:?}) does not build on the previous block in the range, expected parent {:?} but got {:?}; the chain was likely reorganized",
+        _0, _1, _2, _3
+    )]
+    BrokenParentLink(u64, H256, H256, H256),
+
+    /// Scanning the block range `[from, to]` for triggers did not finish before the deadline.
+    /// No block of the range was returned, so the caller can resume from `from`, e.g. with a
+    /// smaller range.
+    #[fail(
+        display = "Scanning blocks [{}, {}] for triggers did not finish before the deadline",
+        _0, _1
+    )]
+    DeadlineExceeded(u64, u64),
+
+    /// Scanning the block range `[from, to]` for triggers was canceled, e.g. because the
+    /// subgraph deployment it was for was stopped. No block of the range was returned.
+    #[fail(display = "Scanning blocks [{}, {}] for triggers was canceled", _0, _1)]
+    Canceled(u64, u64),
+
+    /// The Ethereum node's latest block is too far behind the end of a block range that was
+    /// requested, so scanning the range would silently miss blocks the node doesn't know yet.
+    #[fail(
+        display = "Ethereum node is too far behind: its latest block is {} but block {} was requested",
+        provider_head, requested_to
+    )]
+    ProviderTooFarBehind {
+        provider_head: u64,
+        requested_to: u64,
+    },
+
+    /// A retried request for final blocks returned a different result than the previous
+    /// attempt, e.g. because the Ethereum node is behind a load balancer mixing archive and
+    /// pruned nodes. Neither result can be trusted to be complete.
+    #[fail(
+        display = "Ethereum node returned inconsistent results for `{}` in blocks [{}, {}]: {} item(s) on the previous attempt, {} now",
+        method, from, to, previous, current
+    )]
+    InconsistentResults {
+        method: &'static str,
+        from: u64,
+        to: u64,
+        previous: usize,
+        current: usize,
+    },
+
+    /// A log or call of a final block belongs to a transaction that the block doesn't have, so
+    /// its handlers couldn't be given the transaction. The Ethereum node returned inconsistent
+    /// data for the block.
+    #[fail(
+        display = "Block {} ({:#x}) has no transaction with index {} for one of its triggers",
+        block_number, block_hash, transaction_index
+    )]
+    TransactionNotInBlock {
+        block_number: u64,
+        block_hash: H256,
+        transaction_index: u64,
+    },
+
     /// An unexpected error occurred.
     #[fail(display = "Ethereum adapter error: {}", _0)]
     Unknown(Error),
@@ -99,11 +243,14 @@ enum LogFilterNode {
 pub struct EthGetLogsFilter {
     pub contracts: Vec<Address>,
     pub event_signatures: Vec<EventSignature>,
+    /// The earliest start block of the (contract, event) pairs the filter
+    /// covers. The filter can't match any logs from before that block.
+    pub start_block: u64,
 }
 
 impl fmt::Display for EthGetLogsFilter {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        if self.contracts.len() == 1 {
+        if self.contracts.len() == 1 && self.event_signatures.len() > 1 {
             write!(
                 f,
                 "contract {:?}, {} events",
@@ -118,20 +265,117 @@ impl fmt::Display for EthGetLogsFilter {
                 self.contracts.len()
             )
         } else {
-            write!(f, "unreachable")
+            write!(
+                f,
+                "{} contracts, {} events",
+                self.contracts.len(),
+                self.event_signatures.len()
+            )
         }
     }
 }
 
+/// How `EthereumLogFilter::eth_get_logs_filters` turns a log filter into
+/// `eth_getLogs` filters.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LogFilterStrategy {
+    /// Repeatedly turn the edges of the contract or event with the most
+    /// edges into a filter. This produces few filters but is quadratic in
+    /// the size of the filter.
+    MaxCardinality,
+
+    /// Turn each group of contracts that have exactly the same events into
+    /// a filter. This is cheap to compute but may produce more filters.
+    MergeByNeighbors,
+}
+
+/// The contracts created by the factories of a deployment's data sources,
+/// with the factory and the block that created each of them. Clones share
+/// the same contracts, so that the log filter that discovers them and the
+/// runtime hosts that handle their events agree on them.
+#[derive(Clone, Debug, Default)]
+pub struct FactoryChildren(Arc<RwLock<HashMap<Address, (Address, u64)>>>);
+
+impl FactoryChildren {
+    /// The factory that created the contract at `address`, if it is known.
+    pub fn factory_of(&self, address: &Address) -> Option<Address> {
+        self.0
+            .read()
+            .unwrap()
+            .get(address)
+            .map(|(factory, _)| *factory)
+    }
+
+    /// Adds the `creations` whose factory `is_factory` accepts. Returns the
+    /// ones that were not known yet.
+    fn add(
+        &self,
+        creations: impl IntoIterator<Item = EthereumContractCreation>,
+        is_factory: impl Fn(&Address) -> bool,
+    ) -> Vec<EthereumContractCreation> {
+        let mut children = self.0.write().unwrap();
+        creations
+            .into_iter()
+            .filter(|creation| {
+                is_factory(&creation.factory)
+                    && children
+                        .insert(creation.address, (creation.factory, creation.block_number))
+                        .is_none()
+            })
+            .collect()
+    }
+
+    /// Forgets the contracts created in block `block_number` or later, for
+    /// example because that block was reverted.
+    pub fn revert(&self, block_number: u64) {
+        self.0
+            .write()
+            .unwrap()
+            .retain(|_, (_, created_at)| *created_at < block_number);
+    }
+
+    /// The known contracts, grouped by their factory and sorted.
+    fn by_factory(&self) -> BTreeMap<Address, Vec<Address>> {
+        let mut by_factory: BTreeMap<Address, Vec<Address>> = BTreeMap::new();
+        for (child, (factory, _)) in self.0.read().unwrap().iter() {
+            by_factory
+                .entry(*factory)
+                .or_insert_with(Vec::new)
+                .push(*child);
+        }
+        for children in by_factory.values_mut() {
+            children.sort();
+        }
+        by_factory
+    }
+
+    fn is_shared_with(&self, other: &FactoryChildren) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+
+    fn extend(&self, other: &FactoryChildren) {
+        let children = other.0.read().unwrap().clone();
+        self.0.write().unwrap().extend(children);
+    }
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct EthereumLogFilter {
     /// Log filters can be represented as a bipartite graph between contracts and events. An edge
     /// exists between a contract and an event if a data source for the contract has a trigger for
-    /// the event.
-    contracts_and_events_graph: GraphMap<LogFilterNode, (), petgraph::Undirected>,
+    /// the event. Each edge is weighted with the earliest start block of those data sources.
+    contracts_and_events_graph: GraphMap<LogFilterNode, u64, petgraph::Undirected>,
 
     // Event sigs with no associated address, matching on all addresses.
     wildcard_events: HashSet<EventSignature>,
+
+    // Event sigs of data sources that cover all contracts created by a
+    // factory, keyed by the address of the factory.
+    factories: HashMap<Address, HashSet<EventSignature>>,
+
+    // The contracts created by the factories so far. They are added while
+    // blocks are scanned and are shared by all clones of the filter.
+    factory_children: FactoryChildren,
 }
 
 impl EthereumLogFilter {
@@ -143,8 +387,10 @@ impl EthereumLogFilter {
         true // not even wrong
     }
 
-    /// Check if this filter matches the specified `Log`.
-    pub fn matches(&self, log: &Log) -> bool {
+    /// Check if this filter matches the specified `Log`. If the number of the
+    /// block containing the log is given, logs of (contract, event) pairs
+    /// from before the start block of their data sources don't match.
+    pub fn matches(&self, log: &Log, block_number: Option<u64>) -> bool {
         // First topic should be event sig
         match log.topics.first() {
             None => false,
@@ -156,25 +402,65 @@ impl EthereumLogFilter {
                 let contract = LogFilterNode::Contract(log.address.clone());
                 let event = LogFilterNode::Event(*sig);
                 self.contracts_and_events_graph
-                    .all_edges()
-                    .any(|(s, t, ())| {
-                        (s == contract && t == event) || (t == contract && s == event)
+                    .edge_weight(contract, event)
+                    .map_or(false, |start_block| {
+                        block_number.map_or(true, |number| number >= *start_block)
                     })
                     || self.wildcard_events.contains(sig)
+                    || self.matches_factory_child(&log.address, sig)
             }
         }
     }
 
+    /// Check if `address` was created by a factory with a data source for
+    /// the event `sig`.
+    fn matches_factory_child(&self, address: &Address, sig: &EventSignature) -> bool {
+        self.factory_children
+            .factory_of(address)
+            .and_then(|factory| self.factories.get(&factory))
+            .map_or(false, |events| events.contains(sig))
+    }
+
+    /// The addresses of the factories whose created contracts are in scope
+    /// of the filter.
+    pub fn factories(&self) -> Vec<Address> {
+        self.factories.keys().cloned().collect()
+    }
+
+    /// The contracts created by the factories of the filter, shared with
+    /// the filter.
+    pub fn factory_children(&self) -> FactoryChildren {
+        self.factory_children.clone()
+    }
+
+    /// Adds contracts created by factories to the filter. Contracts
+    /// created by addresses that are not factories of the filter are
+    /// ignored. Returns the contracts that were not yet in the filter.
+    pub fn add_factory_children(
+        &self,
+        creations: impl IntoIterator<Item = EthereumContractCreation>,
+    ) -> Vec<EthereumContractCreation> {
+        self.factory_children
+            .add(creations, |factory| self.factories.contains_key(factory))
+    }
+
     pub fn from_data_sources<'a>(iter: impl IntoIterator<Item = &'a DataSource>) -> Self {
         let mut this = EthereumLogFilter::default();
         for ds in iter {
             for event_sig in ds.mapping.event_handlers.iter().map(|e| e.topic0()) {
+                if let Some(factory) = ds.source.factory {
+                    this.factories
+                        .entry(factory)
+                        .or_insert_with(HashSet::new)
+                        .insert(event_sig);
+                    continue;
+                }
                 match ds.source.address {
                     Some(contract) => {
-                        this.contracts_and_events_graph.add_edge(
+                        this.add_edge(
                             LogFilterNode::Contract(contract),
                             LogFilterNode::Event(event_sig),
-                            (),
+                            ds.source.start_block,
                         );
                     }
                     None => {
@@ -186,17 +472,68 @@ impl EthereumLogFilter {
         this
     }
 
+    /// Adds an edge between a contract and an event, keeping the earlier
+    /// start block if there already is one.
+    fn add_edge(&mut self, s: LogFilterNode, t: LogFilterNode, start_block: u64) {
+        match self.contracts_and_events_graph.edge_weight_mut(s, t) {
+            Some(existing_start_block) => {
+                *existing_start_block = cmp::min(*existing_start_block, start_block)
+            }
+            None => {
+                self.contracts_and_events_graph.add_edge(s, t, start_block);
+            }
+        }
+    }
+
     /// Extends this log filter with another one.
     pub fn extend(&mut self, other: EthereumLogFilter) {
         // Destructure to make sure we're checking all fields.
         let EthereumLogFilter {
             contracts_and_events_graph,
             wildcard_events,
+            factories,
+            factory_children,
         } = other;
-        for (s, t, ()) in contracts_and_events_graph.all_edges() {
-            self.contracts_and_events_graph.add_edge(s, t, ());
+        for (s, t, start_block) in contracts_and_events_graph.all_edges() {
+            self.add_edge(s, t, *start_block);
         }
         self.wildcard_events.extend(wildcard_events);
+        for (factory, events) in factories {
+            self.factories
+                .entry(factory)
+                .or_insert_with(HashSet::new)
+                .extend(events);
+        }
+        if !self.factory_children.is_shared_with(&factory_children) {
+            self.factory_children.extend(&factory_children);
+        }
+    }
+
+    /// The number of (contract, event) pairs in the filter.
+    pub fn edge_count(&self) -> usize {
+        self.contracts_and_events_graph.edge_count()
+    }
+
+    /// The number of contracts and events in the filter, not counting
+    /// wildcard events.
+    pub fn node_count(&self) -> usize {
+        self.contracts_and_events_graph.node_count()
+    }
+
+    /// The strategy that `eth_get_logs_filters` uses for this filter, based
+    /// on its size.
+    pub fn strategy(&self) -> LogFilterStrategy {
+        self.strategy_for_edge_threshold(*LOG_FILTER_EDGE_THRESHOLD)
+    }
+
+    /// The strategy for this filter if filters with more than
+    /// `edge_threshold` edges are to be merged by neighbors.
+    pub fn strategy_for_edge_threshold(&self, edge_threshold: usize) -> LogFilterStrategy {
+        if self.edge_count() > edge_threshold {
+            LogFilterStrategy::MergeByNeighbors
+        } else {
+            LogFilterStrategy::MaxCardinality
+        }
     }
 
     /// An empty filter is one that never matches.
@@ -205,14 +542,27 @@ impl EthereumLogFilter {
         let EthereumLogFilter {
             contracts_and_events_graph,
             wildcard_events,
+            factories,
+            factory_children: _,
         } = self;
-        contracts_and_events_graph.edge_count() == 0 && wildcard_events.is_empty()
+        contracts_and_events_graph.edge_count() == 0
+            && wildcard_events.is_empty()
+            && factories.is_empty()
     }
 
     /// Filters for `eth_getLogs` calls. The filters will not return false positives. This attempts
     /// to balance between having granular filters but too many calls and having few calls but too
     /// broad filters causing the Ethereum endpoint to timeout.
     pub fn eth_get_logs_filters(self) -> impl Iterator<Item = EthGetLogsFilter> {
+        let strategy = self.strategy();
+        self.eth_get_logs_filters_with(strategy)
+    }
+
+    /// Filters for `eth_getLogs` calls, built with the given strategy.
+    pub fn eth_get_logs_filters_with(
+        self,
+        strategy: LogFilterStrategy,
+    ) -> impl Iterator<Item = EthGetLogsFilter> {
         let mut filters = Vec::new();
 
         // First add the wildcard event filters.
@@ -220,9 +570,26 @@ impl EthereumLogFilter {
             filters.push(EthGetLogsFilter {
                 contracts: vec![],
                 event_signatures: vec![wildcard_event],
+                start_block: 0,
             })
         }
 
+        // Then the contracts created by each factory, with the events of
+        // the factory, in chunks to keep the address lists manageable
+        for (factory, children) in self.factory_children.by_factory() {
+            let event_signatures: Vec<_> = match self.factories.get(&factory) {
+                Some(events) => events.iter().cloned().collect(),
+                None => continue,
+            };
+            for chunk in children.chunks(*FACTORY_CHILDREN_CHUNK_SIZE) {
+                filters.push(EthGetLogsFilter {
+                    contracts: chunk.to_vec(),
+                    event_signatures: event_signatures.clone(),
+                    start_block: 0,
+                });
+            }
+        }
+
         // The current algorithm is to repeatedly find the maximum cardinality vertex and turn all
         // of its edges into a filter. This is nice because it is neutral between filtering by
         // contract or by events, if there are many events that appear on only one data source
@@ -235,19 +602,31 @@ impl EthereumLogFilter {
         // One optimization we're not doing is to merge nodes that have the same neighbors into a
         // single node. For example if a subgraph has two data sources, each with the same two
         // events, we could cover that with a single filter and no false positives. However that
-        // might cause the filter to become too broad, so at the moment it seems excessive.
+        // might cause the filter to become too broad, so we only do that for filters that are
+        // too large for the max cardinality search.
         let mut g = self.contracts_and_events_graph;
+        if strategy == LogFilterStrategy::MergeByNeighbors {
+            filters.extend(Self::merge_by_neighbors(&g));
+            return filters.into_iter();
+        }
         while g.edge_count() > 0 {
             // If there are edges, there are vertexes.
             let max_vertex = g.nodes().max_by_key(|&n| g.neighbors(n).count()).unwrap();
+            let start_block = g
+                .edges(max_vertex)
+                .map(|(_, _, start_block)| *start_block)
+                .min()
+                .unwrap();
             let mut filter = match max_vertex {
                 LogFilterNode::Contract(address) => EthGetLogsFilter {
                     contracts: vec![address],
                     event_signatures: vec![],
+                    start_block,
                 },
                 LogFilterNode::Event(event_sig) => EthGetLogsFilter {
                     contracts: vec![],
                     event_signatures: vec![event_sig],
+                    start_block,
                 },
             };
             for neighbor in g.neighbors(max_vertex) {
@@ -267,6 +646,46 @@ impl EthereumLogFilter {
         }
         filters.into_iter()
     }
+
+    /// Groups contracts by their set of events and turns each group into a
+    /// filter. All contracts in a group have exactly the same events, so
+    /// the filters have no false positives.
+    fn merge_by_neighbors(
+        g: &GraphMap<LogFilterNode, u64, petgraph::Undirected>,
+    ) -> Vec<EthGetLogsFilter> {
+        let mut contracts_by_events: BTreeMap<BTreeSet<EventSignature>, (Vec<Address>, u64)> =
+            BTreeMap::new();
+        for node in g.nodes() {
+            if let LogFilterNode::Contract(address) = node {
+                let events = g
+                    .neighbors(node)
+                    .filter_map(|neighbor| match neighbor {
+                        LogFilterNode::Event(event_sig) => Some(event_sig),
+                        LogFilterNode::Contract(_) => None,
+                    })
+                    .collect();
+                let start_block = g
+                    .edges(node)
+                    .map(|(_, _, start_block)| *start_block)
+                    .min()
+                    .unwrap_or(0);
+                let (contracts, group_start_block) = contracts_by_events
+                    .entry(events)
+                    .or_insert_with(|| (vec![], start_block));
+                contracts.push(address);
+                *group_start_block = cmp::min(*group_start_block, start_block);
+            }
+        }
+
+        contracts_by_events
+            .into_iter()
+            .map(|(events, (contracts, start_block))| EthGetLogsFilter {
+                contracts,
+                event_signatures: events.into_iter().collect(),
+                start_block,
+            })
+            .collect()
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -278,32 +697,24 @@ pub struct EthereumCallFilter {
 
 impl EthereumCallFilter {
     pub fn matches(&self, call: &EthereumCall) -> bool {
-        // Ensure the call is to a contract the filter expressed an interest in
-        if !self
-            .contract_addresses_function_signatures
-            .contains_key(&call.to)
-        {
-            return false;
-        }
-        // If the call is to a contract with no specified functions, keep the call
-        if self
-            .contract_addresses_function_signatures
-            .get(&call.to)
-            .unwrap()
-            .1
-            .is_empty()
-        {
+        self.matches_input(&call.to, &call.input.0)
+    }
+
+    /// Whether a call to the contract `to` with the call data `input` matches.
+    fn matches_input(&self, to: &Address, input: &[u8]) -> bool {
+        match self.contract_addresses_function_signatures.get(to) {
+            // The filter has no interest in the contract
+            None => false,
+
             // Allow the ability to match on calls to a contract generally
             // If you want to match on a generic call to contract this limits you
             // from matching with a specific call to a contract
-            return true;
+            Some((_, signatures)) if signatures.is_empty() => true,
+
+            // Ensure the call is to run a function the filter expressed an
+            // interest in; input too short to hold a function selector never is
+            Some((_, signatures)) => input.len() >= 4 && signatures.contains(&input[..4]),
         }
-        // Ensure the call is to run a function the filter expressed an interest in
-        self.contract_addresses_function_signatures
-            .get(&call.to)
-            .unwrap()
-            .1
-            .contains(&call.input.0[..4])
     }
 
     pub fn from_data_sources<'a>(iter: impl IntoIterator<Item = &'a DataSource>) -> Self {
@@ -357,13 +768,36 @@ impl EthereumCallFilter {
         contract_addresses_function_signatures.is_empty()
     }
 
+    /// Returns a filter with only the addresses whose start block is at most
+    /// `block`, i.e. the contracts that can have been called by then.
+    pub fn started_by(&self, block: u64) -> Self {
+        EthereumCallFilter {
+            contract_addresses_function_signatures: self
+                .contract_addresses_function_signatures
+                .iter()
+                .filter(|(_addr, (start_block, _fsigs))| *start_block <= block)
+                .map(|(addr, entry)| (*addr, entry.clone()))
+                .collect(),
+        }
+    }
+
+    /// Returns the distinct start blocks of the addresses in the filter, in
+    /// ascending order. A start block of 0 means the contract is watched
+    /// from the genesis block and is included like any other.
     pub fn start_blocks(&self) -> Vec<u64> {
         self.contract_addresses_function_signatures
             .values()
-            .filter(|(start_block, _fn_sigs)| start_block > &0)
             .map(|(start_block, _fn_sigs)| *start_block)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
             .collect()
     }
+
+    /// Returns the earliest start block of all addresses in the filter, or
+    /// `None` if the filter is empty.
+    pub fn min_start_block(&self) -> Option<u64> {
+        self.start_blocks().into_iter().next()
+    }
 }
 
 impl FromIterator<(u64, Address, [u8; 4])> for EthereumCallFilter {
@@ -406,6 +840,9 @@ impl From<EthereumBlockFilter> for EthereumCallFilter {
 #[derive(Clone, Debug, Default)]
 pub struct EthereumBlockFilter {
     pub contract_addresses: HashSet<(u64, Address)>,
+    /// Addresses for which blocks containing a transaction sent to the address
+    /// trigger, along with the block from which on they do.
+    pub transaction_addresses: HashSet<(u64, Address)>,
     pub trigger_every_block: bool,
 }
 
@@ -414,32 +851,33 @@ impl EthereumBlockFilter {
         iter.into_iter()
             .filter(|data_source| data_source.source.address.is_some())
             .fold(Self::default(), |mut filter_opt, data_source| {
-                let has_block_handler_with_call_filter = data_source
-                    .mapping
-                    .block_handlers
-                    .clone()
-                    .into_iter()
-                    .any(|block_handler| match block_handler.filter {
-                        Some(ref filter) if *filter == BlockHandlerFilter::Call => return true,
-                        _ => return false,
-                    });
-
-                let has_block_handler_without_filter = data_source
-                    .mapping
-                    .block_handlers
-                    .clone()
-                    .into_iter()
-                    .any(|block_handler| block_handler.filter.is_none());
+                let mut has_block_handler_with_call_filter = false;
+                let mut has_block_handler_with_transaction_filter = false;
+                let mut has_block_handler_without_filter = false;
+                for block_handler in data_source.mapping.block_handlers.iter() {
+                    match block_handler.filter {
+                        Some(BlockHandlerFilter::Call) => has_block_handler_with_call_filter = true,
+                        Some(BlockHandlerFilter::Transaction) => {
+                            has_block_handler_with_transaction_filter = true
+                        }
+                        None => has_block_handler_without_filter = true,
+                    }
+                }
+
+                let source_address = (
+                    data_source.source.start_block,
+                    data_source.source.address.unwrap().to_owned(),
+                );
 
                 filter_opt.extend(Self {
                     trigger_every_block: has_block_handler_without_filter,
                     contract_addresses: if has_block_handler_with_call_filter {
-                        vec![(
-                            data_source.source.start_block,
-                            data_source.source.address.unwrap().to_owned(),
-                        )]
-                        .into_iter()
-                        .collect()
+                        vec![source_address].into_iter().collect()
+                    } else {
+                        HashSet::default()
+                    },
+                    transaction_addresses: if has_block_handler_with_transaction_filter {
+                        vec![source_address].into_iter().collect()
                     } else {
                         HashSet::default()
                     },
@@ -469,14 +907,63 @@ impl EthereumBlockFilter {
                 addresses
             },
         );
+
+        // Keep the earliest start block for addresses present in both filters
+        let mut transaction_addresses: HashMap<Address, u64> = HashMap::new();
+        for (start_block, address) in self
+            .transaction_addresses
+            .drain()
+            .chain(other.transaction_addresses.into_iter())
+        {
+            let entry = transaction_addresses.entry(address).or_insert(start_block);
+            *entry = cmp::min(*entry, start_block);
+        }
+        self.transaction_addresses = transaction_addresses
+            .into_iter()
+            .map(|(address, start_block)| (start_block, address))
+            .collect();
     }
 
+    /// Returns the distinct start blocks of the addresses in
+    /// `contract_addresses` and `transaction_addresses`, in ascending
+    /// order, including start blocks of 0.
     pub fn start_blocks(&self) -> Vec<u64> {
         self.contract_addresses
             .iter()
-            .cloned()
-            .filter(|(start_block, _fn_sigs)| start_block > &0)
-            .map(|(start_block, _fn_sigs)| start_block)
+            .chain(self.transaction_addresses.iter())
+            .map(|(start_block, _address)| *start_block)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    /// Returns the earliest start block of all addresses in the filter, or
+    /// `None` if the filter has no addresses.
+    pub fn min_start_block(&self) -> Option<u64> {
+        self.start_blocks().into_iter().next()
+    }
+
+    /// Returns a block trigger for every address in `transaction_addresses`
+    /// that is the recipient of a transaction in `block`.
+    pub fn transaction_triggers(&self, block: &LightEthereumBlock) -> Vec<EthereumTrigger> {
+        let block_ptr = EthereumBlockPointer::from(block);
+        let recipients: HashSet<Address> = block
+            .transactions
+            .iter()
+            .filter_map(|transaction| transaction.to)
+            .collect();
+
+        self.transaction_addresses
+            .iter()
+            .filter(|(start_block, address)| {
+                *start_block <= block_ptr.number && recipients.contains(address)
+            })
+            .map(|(_, address)| {
+                EthereumTrigger::Block(
+                    block_ptr,
+                    EthereumBlockTriggerType::WithTransactionTo(*address),
+                )
+            })
             .collect()
     }
 }
@@ -485,6 +972,7 @@ impl EthereumBlockFilter {
 pub struct ProviderEthRpcMetrics {
     request_duration: Box<HistogramVec>,
     errors: Box<CounterVec>,
+    inconsistencies: Box<CounterVec>,
 }
 
 impl ProviderEthRpcMetrics {
@@ -506,9 +994,21 @@ impl ProviderEthRpcMetrics {
                 vec![String::from("method")],
             )
             .unwrap();
+        let inconsistencies = registry
+            .new_counter_vec(
+                String::from("provider_inconsistency"),
+                String::from(
+                    "Counts retried eth rpc requests that returned a different result \
+                     than the previous attempt",
+                ),
+                HashMap::new(),
+                vec![String::from("method")],
+            )
+            .unwrap();
         Self {
             request_duration,
             errors,
+            inconsistencies,
         }
     }
 
@@ -521,47 +1021,177 @@ impl ProviderEthRpcMetrics {
     pub fn add_error(&self, method: &str) {
         self.errors.with_label_values(vec![method].as_slice()).inc();
     }
+
+    pub fn add_inconsistency(&self, method: &str) {
+        self.inconsistencies
+            .with_label_values(vec![method].as_slice())
+            .inc();
+    }
+
+    /// The number of inconsistent results detected for `method`.
+    pub fn inconsistencies(&self, method: &str) -> f64 {
+        self.inconsistencies
+            .with_label_values(vec![method].as_slice())
+            .get()
+    }
 }
 
+/// Eth rpc metrics of a subgraph deployment. All deployments share the
+/// same metric families and are told apart by their `deployment` label.
 #[derive(Clone)]
 pub struct SubgraphEthRpcMetrics {
     request_duration: Box<GaugeVec>,
+    request_latency: Box<HistogramVec>,
     errors: Box<CounterVec>,
 }
 
 impl SubgraphEthRpcMetrics {
     pub fn new<M: MetricsRegistry>(registry: Arc<M>, subgraph_hash: String) -> Self {
+        let mut deployment_label = HashMap::new();
+        deployment_label.insert(String::from("deployment"), subgraph_hash);
         let request_duration = registry
             .new_gauge_vec(
-                format!("subgraph_eth_rpc_request_duration_{}", subgraph_hash),
+                String::from("subgraph_eth_rpc_request_duration"),
                 String::from("Measures eth rpc request duration for a subgraph deployment"),
-                HashMap::new(),
+                deployment_label.clone(),
+                vec![String::from("method")],
+            )
+            .unwrap();
+        let request_latency = registry
+            .new_histogram_vec(
+                String::from("subgraph_eth_rpc_request_latency"),
+                String::from("Measures eth rpc request latency for a subgraph deployment"),
+                deployment_label.clone(),
                 vec![String::from("method")],
+                vec![0.05, 0.2, 0.5, 1.0, 3.0, 5.0],
             )
             .unwrap();
         let errors = registry
             .new_counter_vec(
-                format!("subgraph_eth_rpc_errors_{}", subgraph_hash),
+                String::from("subgraph_eth_rpc_errors"),
                 String::from("Counts eth rpc request errors for a subgraph deployment"),
-                HashMap::new(),
+                deployment_label,
                 vec![String::from("method")],
             )
             .unwrap();
         Self {
             request_duration,
+            request_latency,
             errors,
         }
     }
 
+    /// Unregisters the metrics of the deployment, which drops its series
+    /// from the shared metric families.
+    pub fn unregister<M: MetricsRegistry>(&self, registry: Arc<M>) {
+        registry.unregister(self.request_duration.clone());
+        registry.unregister(self.request_latency.clone());
+        registry.unregister(self.errors.clone());
+    }
+
     pub fn observe_request(&self, duration: f64, method: &str) {
         self.request_duration
             .with_label_values(vec![method].as_slice())
             .set(duration);
+        self.request_latency
+            .with_label_values(vec![method].as_slice())
+            .observe(duration);
     }
 
     pub fn add_error(&self, method: &str) {
         self.errors.with_label_values(vec![method].as_slice()).inc();
     }
+
+    /// Summarizes the eth rpc requests of `deployment` per method from the
+    /// metric `families` gathered from a registry, ordered by method.
+    pub fn method_stats(families: &[MetricFamily], deployment: &str) -> Vec<EthRpcMethodStats> {
+        let mut stats = BTreeMap::new();
+        for family in families {
+            for metric in family.get_metric() {
+                if metric_label(metric, "deployment") != Some(deployment) {
+                    continue;
+                }
+                let method = match metric_label(metric, "method") {
+                    Some(method) => method,
+                    None => continue,
+                };
+                let method_stats = stats
+                    .entry(method)
+                    .or_insert_with(EthRpcMethodStats::default);
+                match family.get_name() {
+                    "subgraph_eth_rpc_request_latency" => {
+                        let histogram = metric.get_histogram();
+                        method_stats.requests = histogram.get_sample_count();
+                        method_stats.p50 = histogram_quantile(0.5, histogram);
+                        method_stats.p95 = histogram_quantile(0.95, histogram);
+                    }
+                    "subgraph_eth_rpc_errors" => {
+                        method_stats.errors = metric.get_counter().get_value() as u64;
+                    }
+                    _ => (),
+                }
+            }
+        }
+        stats
+            .into_iter()
+            .map(|(method, stats)| EthRpcMethodStats {
+                method: method.to_owned(),
+                ..stats
+            })
+            .collect()
+    }
+}
+
+/// The eth rpc requests a subgraph deployment made for one method.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EthRpcMethodStats {
+    pub method: String,
+    pub requests: u64,
+    pub errors: u64,
+    /// Estimated median request duration in seconds; `None` if there were
+    /// no requests.
+    pub p50: Option<f64>,
+    /// Estimated 95th percentile of the request duration in seconds.
+    pub p95: Option<f64>,
+}
+
+fn metric_label<'a>(metric: &'a proto::Metric, name: &str) -> Option<&'a str> {
+    metric
+        .get_label()
+        .iter()
+        .find(|label| label.get_name() == name)
+        .map(|label| label.get_value())
+}
+
+/// Estimates the `q` quantile of the observations in `histogram` by linear
+/// interpolation within the bucket it falls into, the way Prometheus'
+/// `histogram_quantile` does.
+fn histogram_quantile(q: f64, histogram: &proto::Histogram) -> Option<f64> {
+    let count = histogram.get_sample_count();
+    if count == 0 {
+        return None;
+    }
+
+    let rank = q * count as f64;
+    let mut lower_bound = 0.0;
+    let mut lower_count = 0;
+    for bucket in histogram.get_bucket() {
+        let upper_bound = bucket.get_upper_bound();
+        let upper_count = bucket.get_cumulative_count();
+        if upper_count as f64 >= rank {
+            return Some(
+                lower_bound
+                    + (upper_bound - lower_bound) * (rank - lower_count as f64)
+                        / (upper_count - lower_count) as f64,
+            );
+        }
+        lower_bound = upper_bound;
+        lower_count = upper_count;
+    }
+
+    // The quantile lies above the largest bucket bound, which is the best
+    // estimate there is
+    Some(lower_bound)
 }
 
 #[derive(Clone)]
@@ -569,38 +1199,284 @@ pub struct BlockStreamMetrics {
     pub ethrpc_metrics: Arc<SubgraphEthRpcMetrics>,
     pub blocks_behind: Box<Gauge>,
     pub reverted_blocks: Box<Gauge>,
+    /// The number of the latest block the deployment has processed.
+    pub latest_block_number: Box<Gauge>,
+    /// The hash of the latest block the deployment has processed, as the only
+    /// `hash` label with a value of 1.
+    pub latest_block_hash: Box<GaugeVec>,
+    /// The number of blocks processed since the deployment was started.
+    pub blocks_processed: Box<Counter>,
+    /// The rate at which the deployment processed its most recent blocks.
+    pub blocks_per_second: Box<Gauge>,
+    /// The number of blocks the block stream loaded into the chain store
+    /// ahead of scanning them.
+    pub blocks_prefetched: Box<Counter>,
+    /// The number of prefetched blocks that turned out to contain triggers
+    /// and were therefore read from the chain store.
+    pub prefetched_blocks_used: Box<Counter>,
     pub stopwatch: StopwatchMetrics,
+    /// The times at which the most recent blocks were processed.
+    recent_blocks: Arc<Mutex<VecDeque<Instant>>>,
+    deployment_id: SubgraphDeploymentId,
+    /// Where samples of the progress of the deployment are recorded.
+    indexing_progress: Option<Arc<dyn IndexingProgressRegistry>>,
+    /// Where the sync status of the deployment is looked up.
+    sync_statuses: Option<Arc<DeploymentSyncStatuses>>,
 }
 
 impl BlockStreamMetrics {
+    /// The number of most recently processed blocks that
+    /// `blocks_per_second` is measured over.
+    const RATE_WINDOW: usize = 100;
+
     pub fn new<M: MetricsRegistry>(
         registry: Arc<M>,
         ethrpc_metrics: Arc<SubgraphEthRpcMetrics>,
         deployment_id: SubgraphDeploymentId,
+        network: String,
         stopwatch: StopwatchMetrics,
     ) -> Self {
+        // Label these by deployment and network, so each of them is a single
+        // family
+        let mut deployment_label = HashMap::new();
+        deployment_label.insert(String::from("deployment"), deployment_id.to_string());
+        deployment_label.insert(String::from("network"), network);
+
         let blocks_behind = registry
             .new_gauge(
-                format!("subgraph_blocks_behind_{}", deployment_id.to_string()),
+                String::from("subgraph_blocks_behind"),
                 String::from(
                     "Track the number of blocks a subgraph deployment is behind the HEAD block",
                 ),
-                HashMap::new(),
+                deployment_label.clone(),
             )
             .expect("failed to create `subgraph_blocks_behind` gauge");
         let reverted_blocks = registry
             .new_gauge(
-                format!("subgraph_reverted_blocks_{}", deployment_id.to_string()),
+                String::from("subgraph_reverted_blocks"),
                 String::from("Track the last reverted block for a subgraph deployment"),
-                HashMap::new(),
+                deployment_label.clone(),
             )
             .expect("Failed to create `subgraph_reverted_blocks` gauge");
+        let latest_block_number = registry
+            .new_gauge(
+                String::from("subgraph_latest_block_number"),
+                String::from("The number of the latest block a subgraph deployment has processed"),
+                deployment_label.clone(),
+            )
+            .expect("failed to create `subgraph_latest_block_number` gauge");
+        let latest_block_hash = registry
+            .new_gauge_vec(
+                String::from("subgraph_latest_block_hash"),
+                String::from("The hash of the latest block a subgraph deployment has processed"),
+                deployment_label.clone(),
+                vec![String::from("hash")],
+            )
+            .expect("failed to create `subgraph_latest_block_hash` gauge");
+        let blocks_processed = registry
+            .new_counter(
+                String::from("subgraph_blocks_processed"),
+                String::from(
+                    "Counts the blocks a subgraph deployment has processed since it was started",
+                ),
+                deployment_label.clone(),
+            )
+            .expect("failed to create `subgraph_blocks_processed` counter");
+        let blocks_per_second = registry
+            .new_gauge(
+                String::from("subgraph_blocks_per_second"),
+                String::from(
+                    "The rate at which a subgraph deployment processed its most recent blocks",
+                ),
+                deployment_label.clone(),
+            )
+            .expect("failed to create `subgraph_blocks_per_second` gauge");
+        let blocks_prefetched = registry
+            .new_counter(
+                String::from("subgraph_blocks_prefetched"),
+                String::from(
+                    "Counts the blocks loaded into the chain store ahead of scanning them",
+                ),
+                deployment_label.clone(),
+            )
+            .expect("failed to create `subgraph_blocks_prefetched` counter");
+        let prefetched_blocks_used = registry
+            .new_counter(
+                String::from("subgraph_prefetched_blocks_used"),
+                String::from("Counts the prefetched blocks that were needed to process triggers"),
+                deployment_label,
+            )
+            .expect("failed to create `subgraph_prefetched_blocks_used` counter");
+
         Self {
             ethrpc_metrics,
             blocks_behind,
             reverted_blocks,
+            latest_block_number,
+            latest_block_hash,
+            blocks_processed,
+            blocks_per_second,
+            blocks_prefetched,
+            prefetched_blocks_used,
             stopwatch,
+            recent_blocks: Arc::new(Mutex::new(VecDeque::with_capacity(Self::RATE_WINDOW))),
+            deployment_id,
+            indexing_progress: None,
+            sync_statuses: None,
+        }
+    }
+
+    /// Records samples of the progress of the deployment in `registry` as
+    /// it processes blocks.
+    pub fn with_indexing_progress(mut self, registry: Arc<dyn IndexingProgressRegistry>) -> Self {
+        self.indexing_progress = Some(registry);
+        self
+    }
+
+    /// Makes the sync status of the deployment available through
+    /// `sync_statuses` until the metrics are unregistered.
+    pub fn with_sync_statuses(mut self, sync_statuses: Arc<DeploymentSyncStatuses>) -> Self {
+        sync_statuses.insert(
+            self.deployment_id.clone(),
+            SyncStatusGauges {
+                latest_block_number: self.latest_block_number.clone(),
+                blocks_behind: self.blocks_behind.clone(),
+                blocks_per_second: self.blocks_per_second.clone(),
+            },
+        );
+        self.sync_statuses = Some(sync_statuses);
+        self
+    }
+
+    /// Unregisters the metrics of the deployment, including its eth rpc
+    /// metrics and its sync status, when it is removed or reassigned. This drops its series
+    /// from the shared metric families, and allows registering them again
+    /// when the deployment is restarted.
+    pub fn unregister<M: MetricsRegistry>(&self, registry: Arc<M>) {
+        self.ethrpc_metrics.unregister(registry.clone());
+        registry.unregister(self.blocks_behind.clone());
+        registry.unregister(self.reverted_blocks.clone());
+        registry.unregister(self.latest_block_number.clone());
+        registry.unregister(self.latest_block_hash.clone());
+        registry.unregister(self.blocks_processed.clone());
+        registry.unregister(self.blocks_per_second.clone());
+        registry.unregister(self.blocks_prefetched.clone());
+        registry.unregister(self.prefetched_blocks_used.clone());
+        if let Some(sync_statuses) = &self.sync_statuses {
+            sync_statuses.remove(&self.deployment_id);
+        }
+    }
+
+    /// Records that the block pointer of the deployment moved to `ptr`.
+    pub fn set_latest_block(&self, ptr: &EthereumBlockPointer) {
+        self.latest_block_number.set(ptr.number as f64);
+        self.latest_block_hash.reset();
+        self.latest_block_hash
+            .with_label_values(&[&ptr.hash_hex()])
+            .set(1.0);
+    }
+
+    /// Records that the deployment processed the block `ptr`.
+    pub fn block_processed(&self, ptr: &EthereumBlockPointer) {
+        self.set_latest_block(ptr);
+        self.blocks_processed.inc();
+
+        if let Some(indexing_progress) = &self.indexing_progress {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("system time is before the epoch")
+                .as_secs();
+            indexing_progress.record(&self.deployment_id, timestamp, ptr.number);
+        }
+
+        let now = Instant::now();
+        let mut recent_blocks = self.recent_blocks.lock().unwrap();
+        if recent_blocks.len() == Self::RATE_WINDOW {
+            recent_blocks.pop_front();
         }
+        recent_blocks.push_back(now);
+        let elapsed = now.duration_since(recent_blocks[0]);
+        let elapsed = elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 / 1e9;
+        if elapsed > 0.0 {
+            self.blocks_per_second
+                .set((recent_blocks.len() - 1) as f64 / elapsed);
+        }
+    }
+}
+
+/// How far a subgraph deployment has synced, as reported by the metrics of
+/// its block stream.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeploymentSyncStatus {
+    /// The number of the latest block the deployment has processed.
+    pub latest_block: u64,
+    /// The number of the chain head block the deployment is syncing to.
+    pub chain_head_block: u64,
+    /// The rate at which the deployment processed its most recent blocks;
+    /// `None` if it hasn't processed enough blocks to tell.
+    pub blocks_per_second: Option<f64>,
+}
+
+impl DeploymentSyncStatus {
+    pub fn blocks_behind(&self) -> u64 {
+        self.chain_head_block.saturating_sub(self.latest_block)
+    }
+
+    /// Estimates the number of seconds until the deployment reaches the
+    /// chain head at its recent rate of processing blocks.
+    pub fn estimated_seconds_to_sync(&self) -> Option<u64> {
+        match self.blocks_per_second {
+            Some(rate) if rate > 0.0 => Some((self.blocks_behind() as f64 / rate).ceil() as u64),
+            _ => None,
+        }
+    }
+}
+
+/// The block stream gauges of a deployment that its sync status is read
+/// from.
+struct SyncStatusGauges {
+    latest_block_number: Box<Gauge>,
+    blocks_behind: Box<Gauge>,
+    blocks_per_second: Box<Gauge>,
+}
+
+/// Keeps the block stream gauges of the deployments running on this node by
+/// deployment, so that their sync status can be read without gathering all
+/// metrics of the node.
+#[derive(Default)]
+pub struct DeploymentSyncStatuses {
+    gauges: RwLock<HashMap<SubgraphDeploymentId, SyncStatusGauges>>,
+}
+
+impl DeploymentSyncStatuses {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn insert(&self, deployment: SubgraphDeploymentId, gauges: SyncStatusGauges) {
+        self.gauges.write().unwrap().insert(deployment, gauges);
+    }
+
+    fn remove(&self, deployment: &SubgraphDeploymentId) {
+        self.gauges.write().unwrap().remove(deployment);
+    }
+
+    /// The sync status of `deployment`, or `None` if it has no block
+    /// stream metrics, e.g. because it isn't running on this node.
+    pub fn get(&self, deployment: &SubgraphDeploymentId) -> Option<DeploymentSyncStatus> {
+        let gauges = self.gauges.read().unwrap();
+        let gauges = gauges.get(deployment)?;
+        let latest_block = gauges.latest_block_number.get() as u64;
+        let blocks_per_second = gauges.blocks_per_second.get();
+        Some(DeploymentSyncStatus {
+            latest_block,
+            chain_head_block: latest_block + gauges.blocks_behind.get() as u64,
+            blocks_per_second: if blocks_per_second > 0.0 {
+                Some(blocks_per_second)
+            } else {
+                None
+            },
+        })
     }
 }
 
@@ -616,6 +1492,22 @@ pub trait EthereumAdapter: Send + Sync + 'static {
         logger: &Logger,
     ) -> Box<dyn Future<Item = EthereumNetworkIdentifier, Error = Error> + Send>;
 
+    /// The optional features that the Ethereum node behind this adapter
+    /// supports.
+    fn capabilities(&self) -> NodeCapabilities;
+
+    /// Find out whether the Ethereum node is an archive node, i.e. whether it
+    /// keeps the state of old blocks that `contract_call` and `get_storage_at`
+    /// need for historical requests. Nodes that prune that state fail such
+    /// requests with errors like "missing trie node".
+    ///
+    /// The node is asked once; later calls return the same answer.
+    fn is_archive(&self, logger: &Logger) -> Box<dyn Future<Item = bool, Error = Error> + Send>;
+
+    /// Whether the Ethereum node is an archive node, if `is_archive` has
+    /// found out already. Never asks the Ethereum node.
+    fn known_archive(&self) -> Option<bool>;
+
     /// Find the most recent block.
     fn latest_block(
         &self,
@@ -628,7 +1520,7 @@ pub trait EthereumAdapter: Send + Sync + 'static {
         block_hash: H256,
     ) -> Box<dyn Future<Item = LightEthereumBlock, Error = Error> + Send>;
 
-    /// Load Ethereum blocks in bulk, returning results as they come back as a Stream.
+    /// Load Ethereum blocks in bulk, returning them as a Stream sorted by block number.
     /// May use the `chain_store` as a cache.
     fn load_blocks(
         &self,
@@ -637,12 +1529,34 @@ pub trait EthereumAdapter: Send + Sync + 'static {
         block_hashes: HashSet<H256>,
     ) -> Box<dyn Stream<Item = LightEthereumBlock, Error = Error> + Send>;
 
+    /// Load the blocks `from`, `from + stride`, ... up to `to` into `chain_store` ahead of
+    /// time, so that they are cached by the time they are needed. Resolves to pointers to
+    /// the blocks that were stored. Blocks are requested by number, so `to` must be a final
+    /// block.
+    ///
+    /// Adapters that can't prefetch blocks don't store anything.
+    fn prefetch_blocks(
+        &self,
+        _logger: &Logger,
+        _chain_store: Arc<dyn ChainStore>,
+        _from: u64,
+        _to: u64,
+        _stride: u64,
+    ) -> Box<dyn Future<Item = Vec<EthereumBlockPointer>, Error = Error> + Send> {
+        Box::new(futures::future::ok(vec![]))
+    }
+
     /// Reorg safety: `to` must be a final block.
+    ///
+    /// If `verify_parent_links` is set, fails with `EthereumAdapterError::BrokenParentLink`
+    /// unless every returned block is the parent of the block that follows it. This detects
+    /// reorgs that happen while the range is being loaded.
     fn block_range_to_ptrs(
         &self,
         logger: Logger,
         from: u64,
         to: u64,
+        verify_parent_links: bool,
     ) -> Box<dyn Future<Item = Vec<EthereumBlockPointer>, Error = Error> + Send>;
 
     /// Find a block by its hash.
@@ -659,6 +1573,38 @@ pub trait EthereumAdapter: Send + Sync + 'static {
         block: LightEthereumBlock,
     ) -> Box<dyn Future<Item = EthereumBlock, Error = EthereumAdapterError> + Send>;
 
+    /// Load the logs emitted by the transaction `tx_hash`, from its receipt.
+    ///
+    /// Fails with `EthereumAdapterError::ReceiptUnavailable` if the Ethereum node has no
+    /// receipt for the transaction.
+    fn logs_for_transaction(
+        &self,
+        logger: &Logger,
+        tx_hash: H256,
+    ) -> Box<dyn Future<Item = Vec<Log>, Error = EthereumAdapterError> + Send>;
+
+    /// Find the transaction `tx_hash`, e.g. to give handlers the sender, recipient, value
+    /// and gas of the transaction that emitted a log or made a call. Resolves to `None` if
+    /// the Ethereum node doesn't know the transaction.
+    fn transaction_by_hash(
+        &self,
+        logger: &Logger,
+        tx_hash: H256,
+    ) -> Box<dyn Future<Item = Option<Transaction>, Error = EthereumAdapterError> + Send>;
+
+    /// Load the receipts of the transactions `hashes`, in the same order. A receipt is `None`
+    /// if the Ethereum node doesn't have it.
+    ///
+    /// The node can't tell which block a transaction without a receipt is in, so callers
+    /// say whether the transactions are in final blocks. Final blocks can't be reorged away,
+    /// so for them a missing receipt fails with `EthereumAdapterError::ReceiptUnavailable`.
+    fn transaction_receipts(
+        &self,
+        logger: &Logger,
+        hashes: Vec<H256>,
+        in_final_blocks: bool,
+    ) -> Box<dyn Future<Item = Vec<Option<TransactionReceipt>>, Error = EthereumAdapterError> + Send>;
+
     /// Load block pointer for the specified `block number`.
     fn block_pointer_from_number(
         &self,
@@ -719,6 +1665,27 @@ pub trait EthereumAdapter: Send + Sync + 'static {
     /// reorgs.
     /// It is recommended that `to` be far behind the block number of latest block the Ethereum
     /// node is aware of.
+    ///
+    /// Blocks that have fewer than `confirmations` blocks on top of them, according to the
+    /// latest block of the Ethereum node, are returned as `BlockFinality::NonFinal` so that
+    /// callers can be prepared for them to be reorged; all other blocks are returned as
+    /// `BlockFinality::Final`. With `confirmations` set to 0, all blocks are final. The calls of
+    /// non-final blocks are not loaded; callers that process the triggers of data sources
+    /// created in a block, which `triggers_in_block` finds in its calls, must load them with
+    /// `calls_in_block`.
+    ///
+    /// Callers that already know the number of the latest block of the Ethereum node, e.g.
+    /// because they just received a block from it, pass it as `chain_head`; otherwise, it is
+    /// requested from the Ethereum node. The chain head in the chain store can't be used for
+    /// this, since it may have come from a different Ethereum node.
+    ///
+    /// If a `deadline` is given and the scan has not finished by then, it is aborted and fails
+    /// with `EthereumAdapterError::DeadlineExceeded`. Likewise, if a `cancel` handle is given
+    /// and its guard is canceled or dropped before the scan has finished, the scan is aborted
+    /// and fails with `EthereumAdapterError::Canceled`.
+    ///
+    /// Fails with `EthereumAdapterError::ProviderTooFarBehind` before scanning anything if the
+    /// Ethereum node doesn't know about `to` yet, see `ensure_provider_caught_up`.
     fn blocks_with_triggers(
         self: Arc<Self>,
         logger: Logger,
@@ -729,47 +1696,112 @@ pub trait EthereumAdapter: Send + Sync + 'static {
         log_filter: EthereumLogFilter,
         call_filter: EthereumCallFilter,
         block_filter: EthereumBlockFilter,
+        confirmations: u64,
+        chain_head: Option<u64>,
+        deadline: Option<Instant>,
+        cancel: Option<CancelHandle>,
     ) -> Box<dyn Future<Item = Vec<EthereumBlockWithTriggers>, Error = Error> + Send> {
-        // Each trigger filter needs to be queried for the same block range
-        // and the blocks yielded need to be deduped. If any error occurs
-        // while searching for a trigger type, the entire operation fails.
-        let eth = self.clone();
-        let mut trigger_futs: futures::stream::FuturesUnordered<
-            Box<dyn Future<Item = Vec<EthereumTrigger>, Error = Error> + Send>,
-        > = futures::stream::FuturesUnordered::new();
-
-        // Scan the block range from triggers to find relevant blocks
-        if !log_filter.is_empty() {
-            trigger_futs.push(Box::new(
-                eth.logs_in_block_range(&logger, subgraph_metrics.clone(), from, to, log_filter)
-                    .map(|logs: Vec<Log>| logs.into_iter().map(EthereumTrigger::Log).collect()),
-            ))
-        }
-
-        if !call_filter.is_empty() {
-            trigger_futs.push(Box::new(
-                eth.calls_in_block_range(&logger, subgraph_metrics.clone(), from, to, call_filter)
+        let caught_up = self
+            .ensure_provider_caught_up(&logger, to, chain_head)
+            .map_err(Error::from);
+
+        let log_cancel = cancel.clone();
+        let blocks = caught_up.and_then(move |provider_head| {
+            // Each trigger filter needs to be queried for the same block range
+            // and the blocks yielded need to be deduped. If any error occurs
+            // while searching for a trigger type, the entire operation fails.
+            let eth = self.clone();
+            let mut trigger_futs: futures::stream::FuturesUnordered<
+                Box<dyn Future<Item = Vec<EthereumTrigger>, Error = Error> + Send>,
+            > = futures::stream::FuturesUnordered::new();
+
+            // Scan the block range from triggers to find relevant blocks
+            if !log_filter.is_empty() {
+                // An `eth_getLogs` filter only knows the earliest start block
+                // of its contracts, so logs of contracts from before their
+                // own start block are dropped here
+                let start_block_filter = log_filter.clone();
+                trigger_futs.push(Box::new(
+                    eth.logs_in_block_range(
+                        &logger,
+                        subgraph_metrics.clone(),
+                        from,
+                        to,
+                        log_filter,
+                        log_cancel,
+                    )
+                    .map(move |logs: Vec<Log>| {
+                        logs.into_iter()
+                            .filter(|log| {
+                                let number = log.block_number.map(|number| number.as_u64());
+                                start_block_filter.matches(log, number)
+                            })
+                            .map(EthereumTrigger::Log)
+                            .collect()
+                    }),
+                ))
+            }
+
+            if !call_filter.is_empty() {
+                trigger_futs.push(Box::new(
+                    eth.calls_in_block_range(
+                        &logger,
+                        subgraph_metrics.clone(),
+                        from,
+                        to,
+                        call_filter,
+                    )
                     .map(EthereumTrigger::Call)
                     .collect(),
-            ));
-        }
+                ));
+            }
 
-        if block_filter.trigger_every_block {
-            trigger_futs.push(Box::new(
-                self.block_range_to_ptrs(logger.clone(), from, to)
-                    .map(move |ptrs| {
-                        ptrs.into_iter()
-                            .map(|ptr| EthereumTrigger::Block(ptr, EthereumBlockTriggerType::Every))
-                            .collect()
-                    }),
-            ))
-        } else if !block_filter.contract_addresses.is_empty() {
-            // To determine which blocks include a call to addresses
-            // in the block filter, transform the `block_filter` into
-            // a `call_filter` and run `blocks_with_calls`
-            let call_filter = EthereumCallFilter::from(block_filter);
-            trigger_futs.push(Box::new(
-                eth.calls_in_block_range(&logger, subgraph_metrics.clone(), from, to, call_filter)
+            if !block_filter.transaction_addresses.is_empty() {
+                // To determine which blocks include a transaction to addresses
+                // in the block filter, load every block in the range and look
+                // at the recipients of its transactions; this needs no traces
+                let eth = eth.clone();
+                let logger = logger.clone();
+                let chain_store = chain_store.clone();
+                let block_filter = block_filter.clone();
+                trigger_futs.push(Box::new(
+                    self.block_range_to_ptrs(logger.clone(), from, to, true)
+                        .and_then(move |ptrs| {
+                            eth.load_blocks(
+                                logger,
+                                chain_store,
+                                ptrs.into_iter().map(|ptr| ptr.hash).collect(),
+                            )
+                            .map(move |block| block_filter.transaction_triggers(&block))
+                            .concat2()
+                        }),
+                ))
+            }
+
+            if block_filter.trigger_every_block {
+                trigger_futs.push(Box::new(
+                    self.block_range_to_ptrs(logger.clone(), from, to, true)
+                        .map(move |ptrs| {
+                            ptrs.into_iter()
+                                .map(|ptr| {
+                                    EthereumTrigger::Block(ptr, EthereumBlockTriggerType::Every)
+                                })
+                                .collect()
+                        }),
+                ))
+            } else if !block_filter.contract_addresses.is_empty() {
+                // To determine which blocks include a call to addresses
+                // in the block filter, transform the `block_filter` into
+                // a `call_filter` and run `blocks_with_calls`
+                let call_filter = EthereumCallFilter::from(block_filter);
+                trigger_futs.push(Box::new(
+                    eth.calls_in_block_range(
+                        &logger,
+                        subgraph_metrics.clone(),
+                        from,
+                        to,
+                        call_filter,
+                    )
                     .map(|call| {
                         EthereumTrigger::Block(
                             EthereumBlockPointer::from(&call),
@@ -777,11 +1809,10 @@ pub trait EthereumAdapter: Send + Sync + 'static {
                         )
                     })
                     .collect(),
-            ));
-        }
+                ));
+            }
 
-        let logger1 = logger.clone();
-        Box::new(
+            let logger1 = logger.clone();
             trigger_futs
                 .concat2()
                 .join(self.clone().block_hash_by_block_number(&logger, to))
@@ -803,24 +1834,107 @@ pub trait EthereumAdapter: Send + Sync + 'static {
                     (block_hashes, triggers_by_block)
                 })
                 .and_then(move |(block_hashes, mut triggers_by_block)| {
+                    let eth = self.clone();
+                    let logger = logger1.clone();
                     self.load_blocks(logger1, chain_store, block_hashes)
-                        .map(move |block| {
-                            EthereumBlockWithTriggers::new(
-                                // All blocks with triggers are in `triggers_by_block`, and will be
-                                // accessed here exactly once.
-                                triggers_by_block.remove(&block.number()).unwrap(),
-                                BlockFinality::Final(block),
-                            )
+                        .and_then(move |block| {
+                            // All blocks with triggers are in `triggers_by_block`, and will be
+                            // accessed here exactly once.
+                            let triggers = triggers_by_block.remove(&block.number()).unwrap();
+
+                            if provider_head.saturating_sub(block.number()) >= confirmations {
+                                return Box::new(future::result(
+                                    EthereumBlockWithTriggers::new(
+                                        triggers,
+                                        BlockFinality::Final(block),
+                                    )
+                                    .map_err(Error::from),
+                                ))
+                                    as Box<dyn Future<Item = _, Error = _> + Send>;
+                            }
+
+                            // The block may still be reorged, which needs the full block
+                            Box::new(eth.load_full_block(&logger, block).from_err().and_then(
+                                move |ethereum_block| {
+                                    EthereumBlockWithTriggers::new(
+                                        triggers,
+                                        BlockFinality::NonFinal(EthereumBlockWithCalls {
+                                            ethereum_block,
+                                            calls: None,
+                                        }),
+                                    )
+                                    .map_err(Error::from)
+                                },
+                            ))
                         })
+                        // `load_blocks` returns blocks sorted by number
                         .collect()
-                        .map(|mut blocks| {
-                            blocks.sort_by_key(|block| block.ethereum_block.number());
-                            blocks
-                        })
-                }),
-        )
+                })
+        });
+
+        // Dropping the composed futures when the deadline passes or the scan is
+        // canceled cancels all outstanding requests for the range
+        let blocks: Box<dyn Future<Item = _, Error = _> + Send> = match deadline {
+            Some(deadline) => Box::new(tokio::timer::Timeout::new_at(blocks, deadline).map_err(
+                move |e| {
+                    if e.is_elapsed() {
+                        EthereumAdapterError::DeadlineExceeded(from, to).into()
+                    } else if e.is_timer() {
+                        format_err!("tokio timer error: {}", e.into_timer().unwrap())
+                    } else {
+                        e.into_inner().unwrap()
+                    }
+                },
+            )),
+            None => Box::new(blocks),
+        };
+
+        match cancel {
+            Some(cancel) => Box::new(blocks.cancelable(&cancel, move || {
+                EthereumAdapterError::Canceled(from, to).into()
+            })),
+            None => blocks,
+        }
     }
 
+    /// Fails with `EthereumAdapterError::ProviderTooFarBehind` if the latest block of the
+    /// Ethereum node is more than a few blocks behind `to`. Scanning up to `to` would then
+    /// silently produce incomplete results, e.g. when the chain head was taken from a
+    /// different, more advanced node.
+    ///
+    /// Returns the number of the latest block of the Ethereum node, which is only requested
+    /// from the Ethereum node if it isn't given as `chain_head`.
+    fn ensure_provider_caught_up(
+        &self,
+        logger: &Logger,
+        to: u64,
+        chain_head: Option<u64>,
+    ) -> Box<dyn Future<Item = u64, Error = EthereumAdapterError> + Send> {
+        let caught_up = move |provider_head: u64| {
+            if to > provider_head.saturating_add(*PROVIDER_HEAD_MARGIN) {
+                Err(EthereumAdapterError::ProviderTooFarBehind {
+                    provider_head,
+                    requested_to: to,
+                })
+            } else {
+                Ok(provider_head)
+            }
+        };
+
+        match chain_head {
+            Some(chain_head) => Box::new(future::result(caught_up(chain_head))),
+            None => Box::new(
+                self.latest_block(logger)
+                    .and_then(move |block| caught_up(block.number())),
+            ),
+        }
+    }
+
+    /// Loads the logs of the blocks `[from, to]` that match `log_filter`.
+    ///
+    /// If a `cancel` handle is given and its guard is canceled or dropped before all logs have
+    /// been loaded, the requests are aborted and the call fails with
+    /// `EthereumAdapterError::Canceled`.
     fn logs_in_block_range(
         &self,
         logger: &Logger,
@@ -828,6 +1942,7 @@ pub trait EthereumAdapter: Send + Sync + 'static {
         from: u64,
         to: u64,
         log_filter: EthereumLogFilter,
+        cancel: Option<CancelHandle>,
     ) -> Box<dyn Future<Item = Vec<Log>, Error = Error> + Send>;
 
     fn calls_in_block_range(
@@ -839,6 +1954,17 @@ pub trait EthereumAdapter: Send + Sync + 'static {
         call_filter: EthereumCallFilter,
     ) -> Box<dyn Stream<Item = EthereumCall, Error = Error> + Send>;
 
+    /// Find the contracts that the `factories` created in the block range,
+    /// from the `create` traces of the range.
+    fn contracts_created_in_block_range(
+        &self,
+        logger: &Logger,
+        subgraph_metrics: Arc<SubgraphEthRpcMetrics>,
+        from: u64,
+        to: u64,
+        factories: Vec<Address>,
+    ) -> Box<dyn Future<Item = Vec<EthereumContractCreation>, Error = Error> + Send>;
+
     /// Call the function of a smart contract.
     fn contract_call(
         &self,
@@ -847,6 +1973,20 @@ pub trait EthereumAdapter: Send + Sync + 'static {
         cache: Arc<dyn EthereumCallCache>,
     ) -> Box<dyn Future<Item = Vec<Token>, Error = EthereumContractCallError> + Send>;
 
+    /// Read the storage slot `slot` of the contract at `address` as of `block`.
+    ///
+    /// The block is identified by its hash (EIP-1898) rather than its number, so the
+    /// read fails instead of returning the state of a different block if `block` has
+    /// been removed from the main chain by a reorg.
+    fn get_storage_at(
+        &self,
+        logger: &Logger,
+        subgraph_metrics: Arc<SubgraphEthRpcMetrics>,
+        address: Address,
+        slot: H256,
+        block: EthereumBlockPointer,
+    ) -> Box<dyn Future<Item = H256, Error = EthereumAdapterError> + Send>;
+
     fn triggers_in_block(
         self: Arc<Self>,
         logger: Logger,
@@ -861,16 +2001,21 @@ pub trait EthereumAdapter: Send + Sync + 'static {
 
 #[cfg(test)]
 mod tests {
-    use super::EthereumCallFilter;
+    use super::{
+        EthGetLogsFilter, EthereumBlockFilter, EthereumCallFilter, EthereumLogFilter,
+        LogFilterNode, LogFilterStrategy,
+    };
+    use crate::components::ethereum::{
+        EthereumBlockTriggerType, EthereumContractCreation, EthereumTrigger, LightEthereumBlock,
+    };
 
-    use web3::types::Address;
+    use web3::types::{Address, Bytes, Log, Transaction, H256};
 
     use std::collections::{HashMap, HashSet};
     use std::iter::FromIterator;
 
-    #[test]
-    fn extending_ethereum_call_filter() {
-        let mut base = EthereumCallFilter {
+    fn base_call_filter() -> EthereumCallFilter {
+        EthereumCallFilter {
             contract_addresses_function_signatures: HashMap::from_iter(vec![
                 (
                     Address::from_low_u64_be(0),
@@ -881,8 +2026,11 @@ mod tests {
                     (1, HashSet::from_iter(vec![[1u8; 4]])),
                 ),
             ]),
-        };
-        let extension = EthereumCallFilter {
+        }
+    }
+
+    fn call_filter_extension() -> EthereumCallFilter {
+        EthereumCallFilter {
             contract_addresses_function_signatures: HashMap::from_iter(vec![
                 (
                     Address::from_low_u64_be(0),
@@ -893,8 +2041,13 @@ mod tests {
                     (3, HashSet::from_iter(vec![[3u8; 4]])),
                 ),
             ]),
-        };
-        base.extend(extension);
+        }
+    }
+
+    #[test]
+    fn extending_ethereum_call_filter() {
+        let mut base = base_call_filter();
+        base.extend(call_filter_extension());
 
         assert_eq!(
             base.contract_addresses_function_signatures
@@ -912,4 +2065,512 @@ mod tests {
             Some(&(1, HashSet::from_iter(vec![[1u8; 4]])))
         );
     }
+
+    #[test]
+    fn call_filter_matches_selectors_of_contracts() {
+        // A contract whose calls all match
+        let mut filter = base_call_filter();
+        filter.extend(call_filter_extension());
+        filter.extend(EthereumCallFilter {
+            contract_addresses_function_signatures: HashMap::from_iter(vec![(
+                Address::from_low_u64_be(4),
+                (4, HashSet::new()),
+            )]),
+        });
+
+        // What matching did before it looked up each contract only once
+        let matched_before = |to: &Address, input: &[u8]| {
+            if !filter
+                .contract_addresses_function_signatures
+                .contains_key(to)
+            {
+                return false;
+            }
+            if filter
+                .contract_addresses_function_signatures
+                .get(to)
+                .unwrap()
+                .1
+                .is_empty()
+            {
+                return true;
+            }
+            filter
+                .contract_addresses_function_signatures
+                .get(to)
+                .unwrap()
+                .1
+                .contains(&input[..4])
+        };
+
+        for contract in 0..6 {
+            for selector in 0..5 {
+                let to = Address::from_low_u64_be(contract);
+                let input = [selector, selector, selector, selector, 0xff];
+                assert_eq!(
+                    filter.matches_input(&to, &input),
+                    matched_before(&to, &input),
+                    "contract {}, selector {}",
+                    contract,
+                    selector
+                );
+            }
+        }
+
+        // Input without a complete selector only matches contracts whose
+        // calls all match
+        assert!(!filter.matches_input(&Address::from_low_u64_be(0), &[0u8; 3]));
+        assert!(filter.matches_input(&Address::from_low_u64_be(4), &[]));
+
+        // Many calls, as in a block with a lot of traces
+        let calls: Vec<_> = (0..100_000u64)
+            .map(|i| {
+                let selector = (i % 5) as u8;
+                (Address::from_low_u64_be(i % 6), [selector; 4])
+            })
+            .collect();
+        let matched = calls
+            .iter()
+            .filter(|(to, input)| filter.matches_input(to, input))
+            .count();
+        let expected = calls
+            .iter()
+            .filter(|(to, input)| matched_before(to, input))
+            .count();
+        assert_eq!(matched, expected);
+        assert!(0 < matched && matched < calls.len(), "{} matched", matched);
+    }
+
+    fn block_with_recipients(number: u64, recipients: Vec<Option<Address>>) -> LightEthereumBlock {
+        LightEthereumBlock {
+            hash: Some(H256::from_low_u64_be(number)),
+            number: Some(number.into()),
+            transactions: recipients
+                .into_iter()
+                .map(|to| Transaction {
+                    to,
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn block_filter_triggers_on_transactions_to_contract() {
+        let contract = Address::from_low_u64_be(1);
+        let other = Address::from_low_u64_be(2);
+        let filter = EthereumBlockFilter {
+            transaction_addresses: HashSet::from_iter(vec![(0, contract)]),
+            ..Default::default()
+        };
+
+        // Several transactions to the contract only trigger once
+        let block = block_with_recipients(7, vec![Some(other), Some(contract), Some(contract)]);
+        let triggers = filter.transaction_triggers(&block);
+        assert_eq!(triggers.len(), 1);
+        match &triggers[0] {
+            EthereumTrigger::Block(ptr, EthereumBlockTriggerType::WithTransactionTo(address)) => {
+                assert_eq!(ptr.number, 7);
+                assert_eq!(address, &contract);
+            }
+            trigger => panic!("unexpected trigger: {:?}", trigger),
+        }
+
+        // Transactions to other addresses and contract creations don't trigger
+        let block = block_with_recipients(8, vec![Some(other), None]);
+        assert!(filter.transaction_triggers(&block).is_empty());
+    }
+
+    #[test]
+    fn block_filter_respects_transaction_start_blocks() {
+        let contract = Address::from_low_u64_be(1);
+        let mut filter = EthereumBlockFilter {
+            transaction_addresses: HashSet::from_iter(vec![(10, contract)]),
+            ..Default::default()
+        };
+
+        let block = block_with_recipients(7, vec![Some(contract)]);
+        assert!(filter.transaction_triggers(&block).is_empty());
+
+        // Extending with an earlier start block for the same address keeps
+        // the earliest one
+        filter.extend(EthereumBlockFilter {
+            transaction_addresses: HashSet::from_iter(vec![(5, contract)]),
+            ..Default::default()
+        });
+        assert_eq!(
+            filter.transaction_addresses,
+            HashSet::from_iter(vec![(5, contract)])
+        );
+        assert_eq!(filter.start_blocks(), vec![5]);
+        assert_eq!(filter.transaction_triggers(&block).len(), 1);
+    }
+
+    #[test]
+    fn call_filter_start_blocks_include_genesis() {
+        let filter = EthereumCallFilter::from_iter(vec![
+            (0, Address::from_low_u64_be(1), [1u8; 4]),
+            (7, Address::from_low_u64_be(2), [2u8; 4]),
+        ]);
+
+        let mut start_blocks = filter.start_blocks();
+        start_blocks.sort();
+        assert_eq!(start_blocks, vec![0, 7]);
+        assert_eq!(filter.min_start_block(), Some(0));
+
+        let empty = EthereumCallFilter::from_iter(vec![]);
+        assert_eq!(empty.min_start_block(), None);
+    }
+
+    #[test]
+    fn block_filter_start_blocks_include_genesis() {
+        let mut filter = EthereumBlockFilter {
+            contract_addresses: HashSet::from_iter(vec![(3, Address::from_low_u64_be(1))]),
+            ..Default::default()
+        };
+        assert_eq!(filter.min_start_block(), Some(3));
+
+        filter.extend(EthereumBlockFilter {
+            transaction_addresses: HashSet::from_iter(vec![(0, Address::from_low_u64_be(2))]),
+            ..Default::default()
+        });
+        let mut start_blocks = filter.start_blocks();
+        start_blocks.sort();
+        assert_eq!(start_blocks, vec![0, 3]);
+        assert_eq!(filter.min_start_block(), Some(0));
+
+        assert_eq!(EthereumBlockFilter::default().min_start_block(), None);
+    }
+
+    #[test]
+    fn filter_start_blocks_are_distinct_and_ordered() {
+        let call_filter = EthereumCallFilter::from_iter(vec![
+            (9, Address::from_low_u64_be(1), [1u8; 4]),
+            (4, Address::from_low_u64_be(2), [2u8; 4]),
+            (9, Address::from_low_u64_be(3), [3u8; 4]),
+        ]);
+        assert_eq!(call_filter.start_blocks(), vec![4, 9]);
+        assert_eq!(call_filter.min_start_block(), Some(4));
+
+        let block_filter = EthereumBlockFilter {
+            contract_addresses: HashSet::from_iter(vec![
+                (6, Address::from_low_u64_be(1)),
+                (2, Address::from_low_u64_be(2)),
+            ]),
+            transaction_addresses: HashSet::from_iter(vec![(6, Address::from_low_u64_be(3))]),
+            ..Default::default()
+        };
+        assert_eq!(block_filter.start_blocks(), vec![2, 6]);
+        assert_eq!(block_filter.min_start_block(), Some(2));
+    }
+
+    #[test]
+    fn only_call_triggers_require_traces() {
+        let contract = Address::from_low_u64_be(1);
+        let traces = NodeCapabilities { traces: true };
+        let no_traces = NodeCapabilities { traces: false };
+
+        let no_calls = EthereumCallFilter::from_iter(vec![]);
+        let block_filter = EthereumBlockFilter {
+            transaction_addresses: HashSet::from_iter(vec![(0, contract)]),
+            trigger_every_block: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            NodeCapabilities::required_by(&no_calls, &block_filter, false),
+            no_traces
+        );
+        assert_eq!(
+            NodeCapabilities::required_by(&no_calls, &block_filter, true),
+            traces
+        );
+
+        let calls = EthereumCallFilter::from_iter(vec![(0, contract, [1u8; 4])]);
+        assert_eq!(
+            NodeCapabilities::required_by(&calls, &block_filter, false),
+            traces
+        );
+
+        let block_filter = EthereumBlockFilter {
+            contract_addresses: HashSet::from_iter(vec![(0, contract)]),
+            ..Default::default()
+        };
+        assert_eq!(
+            NodeCapabilities::required_by(&no_calls, &block_filter, false),
+            traces
+        );
+
+        assert_eq!(traces.missing_from(&no_traces), vec!["traces"]);
+        assert!(no_traces.missing_from(&no_traces).is_empty());
+        assert!(traces.missing_from(&traces).is_empty());
+    }
+
+    fn log_filter(pairs: impl IntoIterator<Item = (Address, H256)>) -> EthereumLogFilter {
+        let mut filter = EthereumLogFilter::default();
+        for (contract, event) in pairs {
+            filter.add_edge(
+                LogFilterNode::Contract(contract),
+                LogFilterNode::Event(event),
+                0,
+            );
+        }
+        filter
+    }
+
+    #[test]
+    fn small_log_filters_use_max_cardinality_strategy() {
+        let filter = log_filter(vec![
+            (Address::from_low_u64_be(1), H256::from_low_u64_be(1)),
+            (Address::from_low_u64_be(2), H256::from_low_u64_be(1)),
+        ]);
+
+        assert_eq!(filter.edge_count(), 2);
+        assert_eq!(filter.node_count(), 3);
+        let strategy = filter.strategy_for_edge_threshold(10_000);
+        assert_eq!(strategy, LogFilterStrategy::MaxCardinality);
+
+        let filters: Vec<_> = filter.eth_get_logs_filters_with(strategy).collect();
+        assert_eq!(filters.len(), 1);
+        assert_eq!(filters[0].event_signatures, vec![H256::from_low_u64_be(1)]);
+        assert_eq!(filters[0].contracts.len(), 2);
+    }
+
+    #[test]
+    fn large_log_filters_merge_contracts_with_the_same_events() {
+        // Two groups of contracts that each share the same 100 events, with
+        // more edges than a threshold of 10000
+        let group_a = (0..60).flat_map(|contract| {
+            (0..100).map(move |event| {
+                (
+                    Address::from_low_u64_be(contract),
+                    H256::from_low_u64_be(event),
+                )
+            })
+        });
+        let group_b = (60..110).flat_map(|contract| {
+            (100..200).map(move |event| {
+                (
+                    Address::from_low_u64_be(contract),
+                    H256::from_low_u64_be(event),
+                )
+            })
+        });
+        let filter = log_filter(group_a.chain(group_b));
+
+        assert_eq!(filter.edge_count(), 11000);
+        assert_eq!(filter.node_count(), 310);
+        let strategy = filter.strategy_for_edge_threshold(10_000);
+        assert_eq!(strategy, LogFilterStrategy::MergeByNeighbors);
+
+        // Each group becomes a single filter with all of its contracts and
+        // events
+        let mut filters: Vec<_> = filter.eth_get_logs_filters_with(strategy).collect();
+        filters.sort_by_key(|filter| filter.contracts.len());
+        assert_eq!(filters.len(), 2);
+        assert_eq!(filters[0].contracts.len(), 50);
+        assert_eq!(
+            filters[0].event_signatures,
+            (100..200).map(H256::from_low_u64_be).collect::<Vec<_>>()
+        );
+        assert_eq!(filters[1].contracts.len(), 60);
+        assert_eq!(
+            filters[1].event_signatures,
+            (0..100).map(H256::from_low_u64_be).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn merging_by_neighbors_keeps_contracts_with_different_events_apart() {
+        let filter = log_filter(vec![
+            (Address::from_low_u64_be(1), H256::from_low_u64_be(1)),
+            (Address::from_low_u64_be(1), H256::from_low_u64_be(2)),
+            (Address::from_low_u64_be(2), H256::from_low_u64_be(1)),
+        ]);
+
+        let mut filters: Vec<_> = filter
+            .eth_get_logs_filters_with(LogFilterStrategy::MergeByNeighbors)
+            .collect();
+        filters.sort_by_key(|filter| filter.event_signatures.len());
+        assert_eq!(filters.len(), 2);
+        assert_eq!(filters[0].contracts, vec![Address::from_low_u64_be(2)]);
+        assert_eq!(filters[0].event_signatures, vec![H256::from_low_u64_be(1)]);
+        assert_eq!(filters[1].contracts, vec![Address::from_low_u64_be(1)]);
+        assert_eq!(
+            filters[1].event_signatures,
+            vec![H256::from_low_u64_be(1), H256::from_low_u64_be(2)]
+        );
+    }
+
+    #[test]
+    fn eth_get_logs_filters_describe_their_contracts_and_events() {
+        let contracts: Vec<_> = (1..3).map(Address::from_low_u64_be).collect();
+        let events: Vec<_> = (1..4).map(H256::from_low_u64_be).collect();
+        let filter = |contracts: &[Address], events: &[H256]| {
+            EthGetLogsFilter {
+                contracts: contracts.to_vec(),
+                event_signatures: events.to_vec(),
+                start_block: 0,
+            }
+            .to_string()
+        };
+
+        assert_eq!(
+            filter(&contracts[..1], &events),
+            format!("contract {:?}, 3 events", contracts[0])
+        );
+        assert_eq!(
+            filter(&contracts, &events[..1]),
+            format!("event {:?}, 2 contracts", events[0])
+        );
+        // Merging contracts with the same events produces filters with
+        // several contracts and several events
+        assert_eq!(filter(&contracts, &events), "2 contracts, 3 events");
+    }
+
+    fn log(address: Address, event: H256) -> Log {
+        Log {
+            address,
+            topics: vec![event],
+            data: Bytes(vec![]),
+            block_hash: None,
+            block_number: None,
+            transaction_hash: None,
+            transaction_index: None,
+            log_index: None,
+            transaction_log_index: None,
+            log_type: None,
+            removed: None,
+        }
+    }
+
+    #[test]
+    fn log_filter_matches_logs_from_the_start_block_of_their_edge() {
+        let (late_contract, contract) = (Address::from_low_u64_be(1), Address::from_low_u64_be(2));
+        let event = H256::from_low_u64_be(1);
+
+        let mut filter = log_filter(vec![(contract, event)]);
+        filter.add_edge(
+            LogFilterNode::Contract(late_contract),
+            LogFilterNode::Event(event),
+            200,
+        );
+        let late_log = log(late_contract, event);
+
+        assert!(!filter.matches(&late_log, Some(100)));
+        assert!(filter.matches(&late_log, Some(200)));
+        assert!(filter.matches(&late_log, Some(300)));
+        assert!(filter.matches(&late_log, None));
+        assert!(filter.matches(&log(contract, event), Some(100)));
+
+        // Merging keeps the earlier start block of an edge
+        filter.extend(log_filter(vec![(late_contract, event)]));
+        assert!(filter.matches(&late_log, Some(100)));
+    }
+
+    #[test]
+    fn eth_get_logs_filters_carry_the_earliest_start_block_of_their_edges() {
+        let (early, late, later) = (
+            Address::from_low_u64_be(1),
+            Address::from_low_u64_be(2),
+            Address::from_low_u64_be(3),
+        );
+        let (event, late_event) = (H256::from_low_u64_be(1), H256::from_low_u64_be(2));
+
+        let mut filter = EthereumLogFilter::default();
+        for (contract, event, start_block) in vec![
+            (early, event, 0),
+            (late, late_event, 200),
+            (later, late_event, 300),
+        ] {
+            filter.add_edge(
+                LogFilterNode::Contract(contract),
+                LogFilterNode::Event(event),
+                start_block,
+            );
+        }
+
+        for strategy in vec![
+            LogFilterStrategy::MaxCardinality,
+            LogFilterStrategy::MergeByNeighbors,
+        ] {
+            let mut filters: Vec<_> = filter
+                .clone()
+                .eth_get_logs_filters_with(strategy)
+                .map(|filter| (filter.event_signatures, filter.start_block))
+                .collect();
+            filters.sort();
+            assert_eq!(filters, vec![(vec![event], 0), (vec![late_event], 200)]);
+        }
+
+        // Merging keeps the earlier start block of an edge
+        filter.extend(log_filter(vec![(later, late_event)]));
+        let mut start_blocks: Vec<_> = filter
+            .eth_get_logs_filters()
+            .map(|filter| filter.start_block)
+            .collect();
+        start_blocks.sort();
+        assert_eq!(start_blocks, vec![0, 0]);
+    }
+
+    #[test]
+    fn log_filter_matches_contracts_created_by_factories() {
+        let factory = Address::from_low_u64_be(100);
+        let other_factory = Address::from_low_u64_be(200);
+        let event = H256::from_low_u64_be(1);
+        let (child1, child2, child3) = (
+            Address::from_low_u64_be(1),
+            Address::from_low_u64_be(2),
+            Address::from_low_u64_be(3),
+        );
+
+        let mut filter = EthereumLogFilter::default();
+        filter
+            .factories
+            .insert(factory, HashSet::from_iter(vec![event]));
+        assert!(!filter.is_empty());
+
+        let creation = |factory, address, block_number| EthereumContractCreation {
+            factory,
+            address,
+            block_number,
+        };
+
+        // Children of other factories are ignored, and children that are
+        // already known are not reported again
+        let added = filter.clone().add_factory_children(vec![
+            creation(factory, child1, 10),
+            creation(factory, child2, 20),
+            creation(other_factory, child3, 20),
+        ]);
+        assert_eq!(
+            added,
+            vec![creation(factory, child1, 10), creation(factory, child2, 20)]
+        );
+        assert!(filter
+            .add_factory_children(vec![creation(factory, child1, 10)])
+            .is_empty());
+
+        assert!(filter.matches(&log(child1, event), None));
+        assert!(filter.matches(&log(child2, event), None));
+        assert!(!filter.matches(&log(child2, H256::from_low_u64_be(2)), None));
+        assert!(!filter.matches(&log(child3, event), None));
+        assert!(!filter.matches(&log(factory, event), None));
+
+        // The children are shared with the runtime hosts
+        let children = filter.factory_children();
+        assert_eq!(children.factory_of(&child2), Some(factory));
+        assert_eq!(children.factory_of(&child3), None);
+
+        let filters: Vec<_> = filter.eth_get_logs_filters().collect();
+        assert_eq!(filters.len(), 1);
+        assert_eq!(filters[0].contracts, vec![child1, child2]);
+        assert_eq!(filters[0].event_signatures, vec![event]);
+
+        // Children created in reverted blocks are forgotten
+        children.revert(20);
+        assert!(filter.matches(&log(child1, event), None));
+        assert!(!filter.matches(&log(child2, event), None));
+        assert_eq!(children.factory_of(&child2), None);
+    }
 }
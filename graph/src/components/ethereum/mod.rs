@@ -1,19 +1,23 @@
 mod adapter;
 mod listener;
+mod preflight;
 mod stream;
 mod types;
 
 pub use self::adapter::{
-    BlockStreamMetrics, EthGetLogsFilter, EthereumAdapter, EthereumAdapterError,
-    EthereumBlockFilter, EthereumCallFilter, EthereumContractCall, EthereumContractCallError,
-    EthereumContractState, EthereumContractStateError, EthereumContractStateRequest,
-    EthereumLogFilter, EthereumNetworkIdentifier, ProviderEthRpcMetrics, SubgraphEthRpcMetrics,
+    BlockStreamMetrics, DeploymentSyncStatus, DeploymentSyncStatuses, EthGetLogsFilter, EthRpcMethodStats, EthereumAdapter,
+    EthereumAdapterError, EthereumBlockFilter, EthereumCallFilter, EthereumContractCall,
+    EthereumContractCallError, EthereumContractState, EthereumContractStateError,
+    EthereumContractStateRequest, EthereumLogFilter, EthereumNetworkIdentifier, EventSignature,
+    FactoryChildren, LogFilterStrategy, NodeCapabilities, ProviderEthRpcMetrics, SubgraphEthRpcMetrics,
 };
 pub use self::listener::{ChainHeadUpdate, ChainHeadUpdateListener, ChainHeadUpdateStream};
+pub use self::preflight::{TriggerCounts, TriggerPreflight, TriggerPreflightRunner};
 pub use self::stream::{BlockStream, BlockStreamBuilder};
 pub use self::types::{
     BlockFinality, EthereumBlock, EthereumBlockData, EthereumBlockPointer,
     EthereumBlockTriggerType, EthereumBlockWithCalls, EthereumBlockWithTriggers, EthereumCall,
-    EthereumCallData, EthereumEventData, EthereumTransactionData, EthereumTrigger,
+    EthereumCallData, EthereumContractCreation, EthereumEventData, EthereumTransactionData,
+    EthereumTrigger,
     LightEthereumBlock, LightEthereumBlockExt,
 };
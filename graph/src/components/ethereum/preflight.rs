@@ -0,0 +1,63 @@
+use failure::Error;
+use std::ops::AddAssign;
+
+use crate::prelude::*;
+
+/// Number of triggers that each kind of filter matched.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TriggerCounts {
+    pub logs: u64,
+    pub calls: u64,
+    pub blocks: u64,
+}
+
+impl TriggerCounts {
+    pub fn observe(&mut self, trigger: &EthereumTrigger) {
+        match trigger {
+            EthereumTrigger::Log(_) => self.logs += 1,
+            EthereumTrigger::Call(_) => self.calls += 1,
+            EthereumTrigger::Block(..) => self.blocks += 1,
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.logs + self.calls + self.blocks
+    }
+}
+
+impl AddAssign for TriggerCounts {
+    fn add_assign(&mut self, other: TriggerCounts) {
+        self.logs += other.logs;
+        self.calls += other.calls;
+        self.blocks += other.blocks;
+    }
+}
+
+/// The outcome of scanning a block range for the triggers of a subgraph,
+/// without processing them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TriggerPreflight {
+    /// The first block in the scanned range that contains a trigger.
+    pub first_trigger_block: Option<EthereumBlockPointer>,
+
+    /// The first and last block that were scanned.
+    pub from: u64,
+    pub to: u64,
+
+    /// Triggers matched in the scanned blocks.
+    pub triggers: TriggerCounts,
+}
+
+/// Runs trigger preflights for subgraph deployments, for example on behalf
+/// of the index node.
+pub trait TriggerPreflightRunner: Send + Sync + 'static {
+    /// Scans the blocks `from..=to` for the triggers of `subgraph_id`. Without
+    /// a range, scans forward from the earliest start block of the subgraph
+    /// until the first block with a trigger.
+    fn trigger_preflight(
+        &self,
+        logger: &Logger,
+        subgraph_id: SubgraphDeploymentId,
+        range: Option<(u64, u64)>,
+    ) -> Box<dyn Future<Item = TriggerPreflight, Error = Error> + Send>;
+}
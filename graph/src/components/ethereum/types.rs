@@ -1,12 +1,20 @@
 use ethabi::LogParam;
+use failure::{format_err, Error};
+use graphql_parser::query as q;
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::str::FromStr;
 use web3::types::*;
 
+use super::EthereumAdapterError;
+use crate::data::graphql::{ToGraphQlValue, ValueMap};
+
 pub type LightEthereumBlock = Block<Transaction>;
 
 pub trait LightEthereumBlockExt {
     fn number(&self) -> u64;
+    fn transaction_at(&self, index: u64) -> Option<&Transaction>;
     fn transaction_for_log(&self, log: &Log) -> Option<Transaction>;
     fn transaction_for_call(&self, call: &EthereumCall) -> Option<Transaction>;
     fn parent_ptr(&self) -> Option<EthereumBlockPointer>;
@@ -17,16 +25,33 @@ impl LightEthereumBlockExt for LightEthereumBlock {
         self.number.unwrap().as_u64()
     }
 
+    /// Returns the transaction with the given index in this block.
+    fn transaction_at(&self, index: u64) -> Option<&Transaction> {
+        self.transactions
+            .iter()
+            .find(|tx| tx.transaction_index.map(|i| i.as_u64()) == Some(index))
+    }
+
+    /// Finds the transaction of `log` by its hash or, if the node didn't
+    /// return one, by its index.
     fn transaction_for_log(&self, log: &Log) -> Option<Transaction> {
-        log.transaction_hash
-            .and_then(|hash| self.transactions.iter().find(|tx| tx.hash == hash))
-            .cloned()
+        match log.transaction_hash {
+            Some(hash) => self.transactions.iter().find(|tx| tx.hash == hash),
+            None => log
+                .transaction_index
+                .and_then(|index| self.transaction_at(index.as_u64())),
+        }
+        .cloned()
     }
 
+    /// Finds the transaction of `call` by its hash or, if the trace didn't
+    /// include one, by its index.
     fn transaction_for_call(&self, call: &EthereumCall) -> Option<Transaction> {
-        call.transaction_hash
-            .and_then(|hash| self.transactions.iter().find(|tx| tx.hash == hash))
-            .cloned()
+        match call.transaction_hash {
+            Some(hash) => self.transactions.iter().find(|tx| tx.hash == hash),
+            None => self.transaction_at(call.transaction_index),
+        }
+        .cloned()
     }
 
     fn parent_ptr(&self) -> Option<EthereumBlockPointer> {
@@ -72,10 +97,31 @@ impl BlockFinality {
 pub struct EthereumBlockWithTriggers {
     pub ethereum_block: BlockFinality,
     pub triggers: Vec<EthereumTrigger>,
+    /// Contracts created by the factories of the deployment that are
+    /// recorded when this block is processed, so that they are reverted
+    /// along with it. These are the contracts created in this block and in
+    /// the blocks without triggers right before it.
+    pub factory_children: Vec<EthereumContractCreation>,
 }
 
 impl EthereumBlockWithTriggers {
-    pub fn new(mut triggers: Vec<EthereumTrigger>, ethereum_block: BlockFinality) -> Self {
+    /// Fails with `EthereumAdapterError::TransactionNotInBlock` if a trigger
+    /// of a final block belongs to a transaction the block doesn't have.
+    pub fn new(
+        mut triggers: Vec<EthereumTrigger>,
+        ethereum_block: BlockFinality,
+    ) -> Result<Self, EthereumAdapterError> {
+        // Logs and traces may come without the hash of their transaction;
+        // take it from the transactions of the block so that it is known
+        // to handlers and in errors without fetching receipts
+        let (block, is_final) = match &ethereum_block {
+            BlockFinality::Final(block) => (block, true),
+            BlockFinality::NonFinal(block) => (&block.ethereum_block.block, false),
+        };
+        for trigger in triggers.iter_mut() {
+            trigger.fill_transaction_hash(block, is_final)?;
+        }
+
         // Sort the triggers
         triggers.sort_by(|a, b| {
             let a_tx_index = a.transaction_index();
@@ -92,10 +138,34 @@ impl EthereumBlockWithTriggers {
             a_tx_index.unwrap().cmp(&b_tx_index.unwrap())
         });
 
-        EthereumBlockWithTriggers {
+        Ok(EthereumBlockWithTriggers {
             ethereum_block,
             triggers,
-        }
+            factory_children: vec![],
+        })
+    }
+
+    pub fn block_number(&self) -> u64 {
+        self.ethereum_block.number()
+    }
+
+    pub fn block_hash(&self) -> H256 {
+        EthereumBlockPointer::from(&self.ethereum_block).hash
+    }
+
+    pub fn trigger_count(&self) -> usize {
+        self.triggers.len()
+    }
+
+    /// Returns the number of log, call and block triggers, in that order.
+    pub fn trigger_counts_by_kind(&self) -> (usize, usize, usize) {
+        self.triggers
+            .iter()
+            .fold((0, 0, 0), |(logs, calls, blocks), trigger| match trigger {
+                EthereumTrigger::Log(_) => (logs + 1, calls, blocks),
+                EthereumTrigger::Call(_) => (logs, calls + 1, blocks),
+                EthereumTrigger::Block(_, _) => (logs, calls, blocks + 1),
+            })
     }
 }
 
@@ -163,6 +233,37 @@ impl EthereumCall {
     }
 }
 
+/// A contract created by a factory contract.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EthereumContractCreation {
+    pub factory: Address,
+    pub address: Address,
+    pub block_number: u64,
+}
+
+impl EthereumContractCreation {
+    pub fn try_from_trace(trace: &Trace) -> Option<Self> {
+        // Contracts whose creation failed don't exist
+        if trace.error.is_some() {
+            return None;
+        }
+        let factory = match &trace.action {
+            Action::Create(create) => create.from,
+            _ => return None,
+        };
+        let address = match &trace.result {
+            Some(Res::Create(result)) => result.address,
+            _ => return None,
+        };
+
+        Some(EthereumContractCreation {
+            factory,
+            address,
+            block_number: trace.block_number,
+        })
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum EthereumTrigger {
     Block(EthereumBlockPointer, EthereumBlockTriggerType),
@@ -174,9 +275,48 @@ pub enum EthereumTrigger {
 pub enum EthereumBlockTriggerType {
     Every,
     WithCallTo(Address),
+    WithTransactionTo(Address),
 }
 
 impl EthereumTrigger {
+    /// Sets the transaction hash of a log or call trigger that doesn't have
+    /// one to the hash of the transaction at its index in `block`.
+    ///
+    /// If `block` has no such transaction, this fails with
+    /// `EthereumAdapterError::TransactionNotInBlock` for a final block. For
+    /// a non-final block, which may still change, the hash stays `None`;
+    /// processing the trigger then fails since its handlers can't be given
+    /// a transaction.
+    fn fill_transaction_hash(
+        &mut self,
+        block: &LightEthereumBlock,
+        is_final: bool,
+    ) -> Result<(), EthereumAdapterError> {
+        let (hash, index) = match self {
+            EthereumTrigger::Log(log) => (
+                &mut log.transaction_hash,
+                log.transaction_index.map(|index| index.as_u64()),
+            ),
+            EthereumTrigger::Call(call) => {
+                (&mut call.transaction_hash, Some(call.transaction_index))
+            }
+            EthereumTrigger::Block(_, _) => return Ok(()),
+        };
+        let index = match index {
+            Some(index) if hash.is_none() => index,
+            _ => return Ok(()),
+        };
+        *hash = block.transaction_at(index).map(|tx| tx.hash);
+        if hash.is_none() && is_final {
+            return Err(EthereumAdapterError::TransactionNotInBlock {
+                block_number: block.number(),
+                block_hash: block.hash.unwrap_or_default(),
+                transaction_index: index,
+            });
+        }
+        Ok(())
+    }
+
     fn transaction_index(&self) -> Option<u64> {
         match self {
             // We only handle logs that are in a block and therefore have a `transaction_index`.
@@ -360,6 +500,28 @@ impl EthereumBlockPointer {
     pub fn hash_hex(&self) -> String {
         format!("{:x}", self.hash)
     }
+
+    /// The pointer as a GraphQL object `{ hash, number }`, with the hash as a
+    /// `0x`-prefixed hex string and the number as a decimal string.
+    pub fn to_object_value(&self) -> q::Value {
+        let mut object = BTreeMap::new();
+        object.insert("hash".to_owned(), self.hash.to_graphql_value());
+        object.insert(
+            "number".to_owned(),
+            q::Value::String(self.number.to_string()),
+        );
+        q::Value::Object(object)
+    }
+
+    /// Parses a GraphQL object `{ hash, number }` in the format produced by
+    /// `to_object_value`.
+    pub fn from_object_value(value: &q::Value) -> Result<Self, Error> {
+        let hash = value.get_required::<H256>("hash")?;
+        let number = value.get_required::<String>("number")?;
+        let number = u64::from_str(&number)
+            .map_err(|e| format_err!("Cannot parse block number `{}`: {}", number, e))?;
+        Ok(EthereumBlockPointer { hash, number })
+    }
 }
 
 impl<T> From<Block<T>> for EthereumBlockPointer {
@@ -450,3 +612,241 @@ impl From<EthereumBlockPointer> for u64 {
         ptr.number
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(transaction_index: u64) -> Log {
+        Log {
+            address: Address::from_low_u64_be(1),
+            topics: vec![],
+            data: Bytes(vec![]),
+            block_hash: Some(H256::from_low_u64_be(42)),
+            block_number: Some(42.into()),
+            transaction_hash: None,
+            transaction_index: Some(transaction_index.into()),
+            log_index: None,
+            transaction_log_index: None,
+            log_type: None,
+            removed: None,
+        }
+    }
+
+    fn call(transaction_index: u64) -> EthereumCall {
+        EthereumCall {
+            from: Address::from_low_u64_be(2),
+            to: Address::from_low_u64_be(1),
+            value: U256::zero(),
+            gas_used: U256::zero(),
+            input: Bytes(vec![]),
+            output: Bytes(vec![]),
+            block_number: 42,
+            block_hash: H256::from_low_u64_be(42),
+            transaction_hash: None,
+            transaction_index,
+        }
+    }
+
+    #[test]
+    fn block_pointers_round_trip_through_object_values() {
+        let ptr = EthereumBlockPointer {
+            hash: H256::from_low_u64_be(0xdead_beef),
+            number: 9_007_199_254_740_993,
+        };
+
+        let value = ptr.to_object_value();
+        assert_eq!(
+            value.get_required::<String>("hash").unwrap(),
+            format!("0x{}", ptr.hash_hex())
+        );
+        assert_eq!(
+            value.get_required::<q::Value>("number").unwrap(),
+            q::Value::String("9007199254740993".to_owned())
+        );
+        assert_eq!(
+            EthereumBlockPointer::from_object_value(&value).unwrap(),
+            ptr
+        );
+
+        // Numbers that aren't strings of a `u64` are rejected
+        let mut object = match value {
+            q::Value::Object(object) => object,
+            _ => unreachable!(),
+        };
+        object.insert("number".to_owned(), q::Value::String("-1".to_owned()));
+        assert!(
+            EthereumBlockPointer::from_object_value(&q::Value::Object(object.clone())).is_err()
+        );
+        object.insert("number".to_owned(), q::Value::Int(1.into()));
+        assert!(EthereumBlockPointer::from_object_value(&q::Value::Object(object)).is_err());
+    }
+
+    #[test]
+    fn block_with_triggers_counts_triggers_by_kind() {
+        let mut block = LightEthereumBlock::default();
+        block.number = Some(42.into());
+        block.hash = Some(H256::from_low_u64_be(42));
+        let block_ptr = EthereumBlockPointer::from(&block);
+
+        let block_with_triggers = EthereumBlockWithTriggers::new(
+            vec![
+                EthereumTrigger::Block(block_ptr.clone(), EthereumBlockTriggerType::Every),
+                EthereumTrigger::Log(log(0)),
+                EthereumTrigger::Call(call(1)),
+                EthereumTrigger::Log(log(2)),
+                EthereumTrigger::Block(
+                    block_ptr,
+                    EthereumBlockTriggerType::WithCallTo(Address::from_low_u64_be(1)),
+                ),
+                EthereumTrigger::Log(log(3)),
+            ],
+            BlockFinality::Final(block),
+        )
+        .unwrap();
+
+        assert_eq!(block_with_triggers.block_number(), 42);
+        assert_eq!(block_with_triggers.block_hash(), H256::from_low_u64_be(42));
+        assert_eq!(block_with_triggers.trigger_count(), 6);
+        assert_eq!(block_with_triggers.trigger_counts_by_kind(), (3, 1, 2));
+
+        let empty = EthereumBlockWithTriggers::new(
+            vec![],
+            BlockFinality::Final(LightEthereumBlock {
+                number: Some(7.into()),
+                hash: Some(H256::from_low_u64_be(7)),
+                ..Default::default()
+            }),
+        )
+        .unwrap();
+        assert_eq!(empty.block_number(), 7);
+        assert_eq!(empty.trigger_count(), 0);
+        assert_eq!(empty.trigger_counts_by_kind(), (0, 0, 0));
+    }
+
+    fn transaction(index: u64, from: u64) -> Transaction {
+        Transaction {
+            hash: H256::from_low_u64_be(100 + index),
+            transaction_index: Some(index.into()),
+            from: Address::from_low_u64_be(from),
+            to: Some(Address::from_low_u64_be(1)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn triggers_get_transaction_hashes_and_senders_from_the_block() {
+        let block = LightEthereumBlock {
+            number: Some(42.into()),
+            hash: Some(H256::from_low_u64_be(42)),
+            transactions: vec![transaction(0, 10), transaction(1, 11), transaction(2, 12)],
+            ..Default::default()
+        };
+
+        let mut log_with_hash = log(2);
+        log_with_hash.transaction_hash = Some(H256::from_low_u64_be(102));
+        let block_with_triggers = EthereumBlockWithTriggers::new(
+            vec![
+                EthereumTrigger::Log(log(1)),
+                EthereumTrigger::Call(call(0)),
+                EthereumTrigger::Log(log_with_hash),
+            ],
+            BlockFinality::Final(block.clone()),
+        )
+        .unwrap();
+
+        let triggers = &block_with_triggers.triggers;
+        match (&triggers[0], &triggers[1], &triggers[2]) {
+            (
+                EthereumTrigger::Call(call),
+                EthereumTrigger::Log(log1),
+                EthereumTrigger::Log(log2),
+            ) => {
+                assert_eq!(call.transaction_hash, Some(H256::from_low_u64_be(100)));
+                assert_eq!(log1.transaction_hash, Some(H256::from_low_u64_be(101)));
+                assert_eq!(log2.transaction_hash, Some(H256::from_low_u64_be(102)));
+
+                // Handlers receive the hash, index and sender of the transaction
+                let tx = EthereumTransactionData::from(&block.transaction_for_call(call).unwrap());
+                assert_eq!(tx.hash, H256::from_low_u64_be(100));
+                assert_eq!(tx.index, 0.into());
+                assert_eq!(tx.from, Address::from_low_u64_be(10));
+
+                let tx = EthereumTransactionData::from(&block.transaction_for_log(log1).unwrap());
+                assert_eq!(tx.hash, H256::from_low_u64_be(101));
+                assert_eq!(tx.index, 1.into());
+                assert_eq!(tx.from, Address::from_low_u64_be(11));
+
+                let tx = EthereumTransactionData::from(&block.transaction_for_log(log2).unwrap());
+                assert_eq!(tx.from, Address::from_low_u64_be(12));
+            }
+            triggers => panic!("unexpected triggers: {:?}", triggers),
+        }
+    }
+
+    #[test]
+    fn triggers_of_final_blocks_need_a_transaction_in_the_block() {
+        let block = LightEthereumBlock {
+            number: Some(42.into()),
+            hash: Some(H256::from_low_u64_be(42)),
+            transactions: vec![transaction(0, 10)],
+            ..Default::default()
+        };
+
+        // There is no transaction with index 7 in the block
+        match EthereumBlockWithTriggers::new(
+            vec![EthereumTrigger::Log(log(7))],
+            BlockFinality::Final(block.clone()),
+        ) {
+            Err(EthereumAdapterError::TransactionNotInBlock {
+                block_number: 42,
+                transaction_index: 7,
+                ..
+            }) => (),
+            result => panic!(
+                "unexpected result: {:?}",
+                result.map(|block| block.triggers)
+            ),
+        }
+
+        // Non-final blocks may still change and keep the trigger without a
+        // transaction hash
+        let block_with_triggers = EthereumBlockWithTriggers::new(
+            vec![EthereumTrigger::Log(log(7))],
+            BlockFinality::NonFinal(EthereumBlockWithCalls {
+                ethereum_block: EthereumBlock {
+                    block,
+                    transaction_receipts: vec![],
+                },
+                calls: None,
+            }),
+        )
+        .unwrap();
+        match &block_with_triggers.triggers[..] {
+            [EthereumTrigger::Log(log)] => assert_eq!(log.transaction_hash, None),
+            triggers => panic!("unexpected triggers: {:?}", triggers),
+        }
+    }
+
+    #[test]
+    fn transactions_are_found_by_index_without_hash() {
+        let block = LightEthereumBlock {
+            transactions: vec![transaction(0, 10), transaction(1, 11)],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            block.transaction_for_log(&log(1)).map(|tx| tx.from),
+            Some(Address::from_low_u64_be(11))
+        );
+        assert_eq!(
+            block.transaction_for_call(&call(0)).map(|tx| tx.from),
+            Some(Address::from_low_u64_be(10))
+        );
+
+        // A hash that doesn't match any transaction isn't resolved by index
+        let mut unknown = log(1);
+        unknown.transaction_hash = Some(H256::from_low_u64_be(999));
+        assert!(block.transaction_for_log(&unknown).is_none());
+    }
+}
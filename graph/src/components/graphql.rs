@@ -1,11 +1,20 @@
 use futures::prelude::*;
+use futures::stream;
 
-use crate::data::query::{Query, QueryError, QueryResult};
+use crate::data::query::{Query, QueryError, QueryExecutionError, QueryResult};
 use crate::data::subscription::{Subscription, SubscriptionError, SubscriptionResult};
 
 /// Future for query results.
 pub type QueryResultFuture = Box<dyn Future<Item = QueryResult, Error = QueryError> + Send>;
 
+/// The JSON of a query result, in the chunks in which it is written. An
+/// error ends the stream early; the JSON written until then is incomplete.
+pub type QueryJsonStream = Box<dyn Stream<Item = Vec<u8>, Error = QueryError> + Send>;
+
+/// Future for query results that are serialized as JSON. It fails if the
+/// query fails before any of its JSON has been written.
+pub type QueryJsonFuture = Box<dyn Future<Item = QueryJsonStream, Error = QueryError> + Send>;
+
 /// Future for subscription results.
 pub type SubscriptionResultFuture =
     Box<dyn Future<Item = SubscriptionResult, Error = SubscriptionError> + Send>;
@@ -15,6 +24,18 @@ pub trait GraphQlRunner: Send + Sync + 'static {
     /// Runs a GraphQL query and returns its result.
     fn run_query(&self, query: Query) -> QueryResultFuture;
 
+    /// Runs a GraphQL query and returns its result serialized as JSON.
+    /// Runners may write the entities of large results as they load them,
+    /// rather than building the whole result in memory first.
+    fn run_query_to_json(&self, query: Query) -> QueryJsonFuture {
+        Box::new(self.run_query(query).and_then(|result| {
+            let json = serde_json::to_vec(&result).map_err(|e| {
+                QueryError::from(QueryExecutionError::ResponseWriteError(e.to_string()))
+            })?;
+            Ok(Box::new(stream::once(Ok(json))) as QueryJsonStream)
+        }))
+    }
+
     /// Runs a GraphqL query up to the given complexity. Overrides the global complexity limit.
     fn run_query_with_complexity(
         &self,
@@ -1,4 +1,5 @@
 pub use prometheus::core::Collector;
+pub use prometheus::proto::MetricFamily;
 pub use prometheus::{
     Counter, CounterVec, Error as PrometheusError, Gauge, GaugeVec, Histogram, HistogramOpts,
     HistogramVec, Opts, Registry,
@@ -9,6 +10,12 @@ use std::collections::HashMap;
 /// Metrics for measuring where time is spent during indexing.
 pub mod stopwatch;
 
+/// Execution statistics of subgraph handlers.
+pub mod handler_stats;
+
+/// Samples of the progress subgraph deployments make over time.
+pub mod indexing_progress;
+
 pub trait MetricsRegistry: Send + Sync + 'static {
     fn new_gauge(
         &self,
@@ -60,4 +67,7 @@ pub trait MetricsRegistry: Send + Sync + 'static {
     ) -> Result<Box<HistogramVec>, PrometheusError>;
 
     fn unregister(&self, metric: Box<dyn Collector>);
+
+    /// Collects the current values of all registered metrics.
+    fn gather(&self) -> Vec<MetricFamily>;
 }
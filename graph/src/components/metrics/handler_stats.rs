@@ -0,0 +1,271 @@
+use crate::prelude::*;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// The kind of trigger a handler is run for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HandlerKind {
+    Event,
+    Call,
+    Block,
+}
+
+impl fmt::Display for HandlerKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HandlerKind::Event => write!(f, "event"),
+            HandlerKind::Call => write!(f, "call"),
+            HandlerKind::Block => write!(f, "block"),
+        }
+    }
+}
+
+/// Execution statistics of a single handler of a subgraph.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HandlerStats {
+    /// The name of the handler function.
+    pub handler: String,
+    pub kind: HandlerKind,
+    /// How often the handler was run.
+    pub count: u64,
+    /// How many of those runs failed.
+    pub failed_count: u64,
+    /// Time spent in all runs of the handler.
+    pub total_duration: Duration,
+    /// Time spent in the slowest run of the handler.
+    pub max_duration: Duration,
+}
+
+impl HandlerStats {
+    fn new(handler: String, kind: HandlerKind) -> Self {
+        HandlerStats {
+            handler,
+            kind,
+            count: 0,
+            failed_count: 0,
+            total_duration: Duration::from_secs(0),
+            max_duration: Duration::from_secs(0),
+        }
+    }
+
+    fn observe(&mut self, duration: Duration, failed: bool) {
+        self.count += 1;
+        if failed {
+            self.failed_count += 1;
+        }
+        self.total_duration += duration;
+        self.max_duration = self.max_duration.max(duration);
+    }
+}
+
+/// Collects execution statistics of subgraph handlers.
+pub trait HandlerStatsRegistry: fmt::Debug + Send + Sync + 'static {
+    /// Records a run of `handler` that took `duration` and, if `failed` is
+    /// set, returned an error.
+    fn observe(
+        &self,
+        subgraph_id: &SubgraphDeploymentId,
+        kind: HandlerKind,
+        handler: &str,
+        duration: Duration,
+        failed: bool,
+    );
+
+    /// Returns the statistics of all handlers of a subgraph that have run,
+    /// ordered by kind and handler name.
+    fn handler_stats(&self, subgraph_id: &SubgraphDeploymentId) -> Vec<HandlerStats>;
+}
+
+/// The statistics of the handlers of one deployment, and when they were
+/// last updated, counted in runs of any handler of any deployment.
+#[derive(Debug)]
+struct DeploymentHandlerStats {
+    last_observed: u64,
+    handlers: BTreeMap<(HandlerKind, String), HandlerStats>,
+}
+
+#[derive(Debug, Default)]
+struct HandlerStatsState {
+    observations: u64,
+    deployments: HashMap<SubgraphDeploymentId, DeploymentHandlerStats>,
+}
+
+/// A `HandlerStatsRegistry` that keeps statistics in memory for a bounded
+/// number of deployments. They are therefore specific to this node and lost
+/// when it restarts.
+#[derive(Debug)]
+pub struct InMemoryHandlerStatsRegistry {
+    max_deployments: usize,
+    state: Mutex<HandlerStatsState>,
+}
+
+impl InMemoryHandlerStatsRegistry {
+    /// Keeps the statistics of up to 1000 deployments.
+    pub fn new() -> Self {
+        Self::with_max_deployments(1000)
+    }
+
+    /// Keeps the statistics of up to `max_deployments` deployments. When
+    /// a handler of another deployment runs, the statistics of the
+    /// deployment whose handlers ran least recently are dropped.
+    pub fn with_max_deployments(max_deployments: usize) -> Self {
+        InMemoryHandlerStatsRegistry {
+            max_deployments,
+            state: Mutex::new(HandlerStatsState::default()),
+        }
+    }
+}
+
+impl Default for InMemoryHandlerStatsRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HandlerStatsRegistry for InMemoryHandlerStatsRegistry {
+    fn observe(
+        &self,
+        subgraph_id: &SubgraphDeploymentId,
+        kind: HandlerKind,
+        handler: &str,
+        duration: Duration,
+        failed: bool,
+    ) {
+        let mut state = self.state.lock().unwrap();
+        state.observations += 1;
+        let observations = state.observations;
+
+        if !state.deployments.contains_key(subgraph_id)
+            && state.deployments.len() >= self.max_deployments
+        {
+            let least_recent = state
+                .deployments
+                .iter()
+                .min_by_key(|(_, stats)| stats.last_observed)
+                .map(|(id, _)| id.clone());
+            if let Some(id) = least_recent {
+                state.deployments.remove(&id);
+            }
+        }
+
+        let stats = state
+            .deployments
+            .entry(subgraph_id.clone())
+            .or_insert_with(|| DeploymentHandlerStats {
+                last_observed: observations,
+                handlers: BTreeMap::new(),
+            });
+        stats.last_observed = observations;
+        stats
+            .handlers
+            .entry((kind, handler.to_owned()))
+            .or_insert_with(|| HandlerStats::new(handler.to_owned(), kind))
+            .observe(duration, failed);
+    }
+
+    fn handler_stats(&self, subgraph_id: &SubgraphDeploymentId) -> Vec<HandlerStats> {
+        self.state
+            .lock()
+            .unwrap()
+            .deployments
+            .get(subgraph_id)
+            .map_or(vec![], |stats| stats.handlers.values().cloned().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aggregates_runs_per_subgraph_and_handler() {
+        let registry = InMemoryHandlerStatsRegistry::new();
+        let subgraph = SubgraphDeploymentId::new("handlerStats").unwrap();
+        let other_subgraph = SubgraphDeploymentId::new("otherHandlerStats").unwrap();
+
+        registry.observe(
+            &subgraph,
+            HandlerKind::Event,
+            "handleTransfer",
+            Duration::from_millis(30),
+            false,
+        );
+        registry.observe(
+            &subgraph,
+            HandlerKind::Event,
+            "handleTransfer",
+            Duration::from_millis(50),
+            true,
+        );
+        registry.observe(
+            &subgraph,
+            HandlerKind::Block,
+            "handleBlock",
+            Duration::from_millis(5),
+            false,
+        );
+        registry.observe(
+            &other_subgraph,
+            HandlerKind::Call,
+            "handleMint",
+            Duration::from_millis(1),
+            false,
+        );
+
+        assert_eq!(
+            registry.handler_stats(&subgraph),
+            vec![
+                HandlerStats {
+                    handler: "handleTransfer".to_owned(),
+                    kind: HandlerKind::Event,
+                    count: 2,
+                    failed_count: 1,
+                    total_duration: Duration::from_millis(80),
+                    max_duration: Duration::from_millis(50),
+                },
+                HandlerStats {
+                    handler: "handleBlock".to_owned(),
+                    kind: HandlerKind::Block,
+                    count: 1,
+                    failed_count: 0,
+                    total_duration: Duration::from_millis(5),
+                    max_duration: Duration::from_millis(5),
+                },
+            ]
+        );
+        assert_eq!(registry.handler_stats(&other_subgraph).len(), 1);
+        assert_eq!(
+            registry.handler_stats(&SubgraphDeploymentId::new("unknown").unwrap()),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn drops_the_deployment_whose_handlers_ran_least_recently() {
+        let registry = InMemoryHandlerStatsRegistry::with_max_deployments(2);
+        let ids: Vec<_> = ["first", "second", "third"]
+            .iter()
+            .map(|id| SubgraphDeploymentId::new(*id).unwrap())
+            .collect();
+        let observe = |id| {
+            registry.observe(
+                id,
+                HandlerKind::Event,
+                "handleTransfer",
+                Duration::from_millis(1),
+                false,
+            )
+        };
+
+        observe(&ids[0]);
+        observe(&ids[1]);
+        observe(&ids[0]);
+        observe(&ids[2]);
+
+        assert_eq!(registry.handler_stats(&ids[0])[0].count, 2);
+        assert_eq!(registry.handler_stats(&ids[1]), vec![]);
+        assert_eq!(registry.handler_stats(&ids[2])[0].count, 1);
+    }
+}
@@ -0,0 +1,151 @@
+use crate::prelude::*;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// The latest block a subgraph deployment had processed at some point in
+/// time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProgressSample {
+    /// When the sample was taken, in seconds since the Unix epoch.
+    pub timestamp: u64,
+    pub block_number: u64,
+    /// Whether this is the first sample since the node started, which
+    /// doesn't tell how fast the deployment progressed before that.
+    pub first: bool,
+}
+
+/// Collects samples of the progress subgraph deployments make.
+pub trait IndexingProgressRegistry: Send + Sync + 'static {
+    /// Records that the deployment processed the block `block_number` at
+    /// `timestamp`, in seconds since the Unix epoch.
+    fn record(&self, subgraph_id: &SubgraphDeploymentId, timestamp: u64, block_number: u64);
+
+    /// Returns the samples of a deployment taken at or after `since`, in
+    /// seconds since the Unix epoch, oldest first.
+    fn samples(&self, subgraph_id: &SubgraphDeploymentId, since: u64) -> Vec<ProgressSample>;
+}
+
+/// An `IndexingProgressRegistry` that keeps a bounded number of samples per
+/// deployment in memory. They are therefore specific to this node and lost
+/// when it restarts.
+pub struct InMemoryIndexingProgressRegistry {
+    interval: Duration,
+    max_samples: usize,
+    samples: Mutex<HashMap<SubgraphDeploymentId, VecDeque<ProgressSample>>>,
+}
+
+impl InMemoryIndexingProgressRegistry {
+    /// Keeps one sample per minute for the last 24 hours.
+    pub fn new() -> Self {
+        Self::with_sampling(Duration::from_secs(60), 24 * 60)
+    }
+
+    /// Keeps one sample per `interval`, and at most `max_samples` samples
+    /// per deployment.
+    pub fn with_sampling(interval: Duration, max_samples: usize) -> Self {
+        InMemoryIndexingProgressRegistry {
+            interval,
+            max_samples,
+            samples: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl Default for InMemoryIndexingProgressRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IndexingProgressRegistry for InMemoryIndexingProgressRegistry {
+    fn record(&self, subgraph_id: &SubgraphDeploymentId, timestamp: u64, block_number: u64) {
+        let mut samples = self.samples.lock().unwrap();
+        let samples = samples
+            .entry(subgraph_id.clone())
+            .or_insert_with(VecDeque::new);
+
+        // Blocks processed within an interval of the last sample aren't
+        // sampled
+        let first = match samples.back() {
+            Some(last) if timestamp < last.timestamp + self.interval.as_secs() => return,
+            Some(_) => false,
+            None => true,
+        };
+
+        if samples.len() >= self.max_samples {
+            samples.pop_front();
+        }
+        samples.push_back(ProgressSample {
+            timestamp,
+            block_number,
+            first,
+        });
+    }
+
+    fn samples(&self, subgraph_id: &SubgraphDeploymentId, since: u64) -> Vec<ProgressSample> {
+        self.samples
+            .lock()
+            .unwrap()
+            .get(subgraph_id)
+            .map_or(vec![], |samples| {
+                samples
+                    .iter()
+                    .filter(|sample| sample.timestamp >= since)
+                    .cloned()
+                    .collect()
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_once_per_interval_and_keeps_the_most_recent_samples() {
+        let registry = InMemoryIndexingProgressRegistry::with_sampling(Duration::from_secs(60), 3);
+        let subgraph = SubgraphDeploymentId::new("indexingProgress").unwrap();
+        let other_subgraph = SubgraphDeploymentId::new("otherIndexingProgress").unwrap();
+
+        // Ten blocks every 10 seconds for five minutes
+        for i in 0..30 {
+            registry.record(&subgraph, 1000 + i * 10, i * 10);
+        }
+        registry.record(&other_subgraph, 1000, 5);
+
+        assert_eq!(
+            registry.samples(&subgraph, 0),
+            vec![
+                ProgressSample {
+                    timestamp: 1120,
+                    block_number: 120,
+                    first: false,
+                },
+                ProgressSample {
+                    timestamp: 1180,
+                    block_number: 180,
+                    first: false,
+                },
+                ProgressSample {
+                    timestamp: 1240,
+                    block_number: 240,
+                    first: false,
+                },
+            ]
+        );
+        assert_eq!(registry.samples(&subgraph, 1180).len(), 2);
+        assert_eq!(
+            registry.samples(&other_subgraph, 0),
+            vec![ProgressSample {
+                timestamp: 1000,
+                block_number: 5,
+                first: true,
+            }]
+        );
+        assert_eq!(
+            registry.samples(&SubgraphDeploymentId::new("unknown").unwrap(), 0),
+            vec![]
+        );
+    }
+}
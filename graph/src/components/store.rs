@@ -3,12 +3,12 @@ use futures::stream::poll_fn;
 use futures::{Async, Future, Poll, Stream};
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::env;
 use std::fmt;
 use std::str::FromStr;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use web3::types::H256;
 
 use crate::data::store::*;
@@ -87,6 +87,17 @@ pub enum EntityOrder {
     Descending,
 }
 
+/// An attribute of referenced entities to order entities by, e.g. the `name`
+/// of the `owner` of a token.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EntityOrderByChild {
+    /// The attribute of the queried entities that references other entities.
+    pub relation: String,
+
+    /// The attribute of the referenced entities to order by.
+    pub attribute: String,
+}
+
 /// How many entities to return, how many to skip etc.
 #[derive(Clone, Debug, PartialEq)]
 pub struct EntityRange {
@@ -123,11 +134,31 @@ pub struct EntityQuery {
     /// An optional attribute to order the entities by.
     pub order_by: Option<(String, ValueType)>,
 
+    /// An optional attribute of referenced entities to order the entities
+    /// by, instead of `order_by`. Entities that reference the same entity
+    /// are ordered by their ID.
+    pub order_by_child: Option<EntityOrderByChild>,
+
     /// The direction to order entities in.
     pub order_direction: Option<EntityOrder>,
 
     /// A range to limit the size of the result.
     pub range: EntityRange,
+
+    /// The attributes to load for each entity, or `None` to load all of
+    /// them. Stores always return the `id` of entities, and may return more
+    /// attributes than requested.
+    pub select: Option<BTreeSet<String>>,
+
+    /// The ID of the GraphQL query this query is made for, if any, so that
+    /// log messages about it can be correlated with those of the GraphQL
+    /// query.
+    pub query_id: Option<String>,
+
+    /// The number of the block to query the entities at, or `None` to query
+    /// the latest version of them. Stores that don't keep the history of
+    /// entities don't support querying them at a block.
+    pub block: Option<u64>,
 }
 
 impl EntityQuery {
@@ -141,8 +172,12 @@ impl EntityQuery {
             entity_types,
             filter: None,
             order_by: None,
+            order_by_child: None,
             order_direction: None,
             range,
+            select: None,
+            query_id: None,
+            block: None,
         }
     }
 
@@ -890,6 +925,36 @@ pub trait Store: Send + Sync + 'static {
             );
         }
 
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        // Keep a record of the assignments that are removed
+        ops.extend(removed_assignments.iter().flat_map(|deployment_id| {
+            match self.get(SubgraphDeploymentAssignmentEntity::key(
+                deployment_id.clone(),
+            )) {
+                Ok(assignment) => assignment
+                    .and_then(|assignment| {
+                        SubgraphDeploymentPastAssignmentEntity::from_assignment(
+                            deployment_id.clone(),
+                            &assignment,
+                            now,
+                        )
+                    })
+                    .map_or(vec![], |past| past.write_operations()),
+                Err(e) => {
+                    warn!(
+                        logger,
+                        "Failed to record the removed assignment of a subgraph deployment";
+                        "subgraph_id" => deployment_id.to_string(),
+                        "error" => e.to_string()
+                    );
+                    vec![]
+                }
+            }
+        }));
         ops.extend(removed_assignments.into_iter().map(|deployment_id| {
             MetadataOperation::Remove {
                 entity: SubgraphDeploymentAssignmentEntity::TYPENAME.to_owned(),
@@ -901,6 +966,7 @@ pub trait Store: Send + Sync + 'static {
                 node_id
                     .clone()
                     .expect("Cannot create new subgraph deployment assignment without node ID"),
+                now,
             )
             .write_operations(deployment_id)
         }));
@@ -935,6 +1001,31 @@ pub trait Store: Send + Sync + 'static {
             .unwrap_or(Ok(false))
     }
 
+    /// Return the number of the block at which the deployment with the given
+    /// id failed together with the error that made it fail, or `None` if the
+    /// deployment has not failed. Errors from the store are passed back up
+    fn deployment_failure(&self, id: SubgraphDeploymentId) -> Result<Option<(u64, String)>, Error> {
+        let entity = match self.get(SubgraphDeploymentEntity::key(id.clone()))? {
+            Some(entity) => entity,
+            None => return Ok(None),
+        };
+
+        match entity.get("failed") {
+            Some(Value::Bool(true)) => (),
+            _ => return Ok(None),
+        }
+
+        let message = match entity.get("fatalError") {
+            Some(Value::String(message)) => message.clone(),
+            _ => String::from("unknown error"),
+        };
+        let block = match entity.get("latestEthereumBlockNumber") {
+            Some(Value::BigInt(number)) => number.to_u64(),
+            _ => 0,
+        };
+        Ok(Some((block, message)))
+    }
+
     /// Create a new subgraph deployment. The deployment must not exist yet. `ops`
     /// needs to contain all the operations on subgraphs and subgraph deployments to
     /// create the deployment, including any assignments as a current or pending
@@ -970,6 +1061,18 @@ pub trait Store: Send + Sync + 'static {
     );
 }
 
+/// The files a subgraph deployment was registered with.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SubgraphDeploymentFiles {
+    /// The text of the manifest, as it was loaded from IPFS. Only known for
+    /// deployments that were registered after the text was stored with
+    /// them.
+    pub manifest: Option<String>,
+
+    /// The GraphQL schema supplied by the user.
+    pub schema: String,
+}
+
 pub trait SubgraphDeploymentStore: Send + Sync + 'static {
     /// Return the GraphQL schema supplied by the user
     fn input_schema(&self, subgraph_id: &SubgraphDeploymentId) -> Result<Arc<Schema>, Error>;
@@ -983,6 +1086,13 @@ pub trait SubgraphDeploymentStore: Send + Sync + 'static {
     /// store internals that should really be hidden and should be used
     /// sparingly and only when absolutely needed
     fn uses_relational_schema(&self, subgraph_id: &SubgraphDeploymentId) -> Result<bool, Error>;
+
+    /// Return the manifest and schema the subgraph was registered with, or
+    /// `None` if there is no such subgraph
+    fn deployment_files(
+        &self,
+        subgraph_id: &SubgraphDeploymentId,
+    ) -> Result<Option<SubgraphDeploymentFiles>, Error>;
 }
 
 /// Common trait for blockchain store implementations.
@@ -4,6 +4,7 @@ use futures::sync::mpsc;
 use std::collections::HashMap;
 use std::fmt;
 use std::sync::Arc;
+use std::time::Duration;
 
 use crate::components::metrics::HistogramVec;
 use crate::prelude::*;
@@ -11,6 +12,10 @@ use web3::types::{Log, Transaction};
 
 /// Common trait for runtime host implementations.
 pub trait RuntimeHost: Send + Sync + Debug + 'static {
+    /// Returns the position of the host's data source in the order in which
+    /// hosts process a trigger they all match.
+    fn data_source_ordinal(&self) -> DataSourceOrdinal;
+
     /// Returns true if the RuntimeHost has a handler for an Ethereum event.
     fn matches_log(&self, log: &Log) -> bool;
 
@@ -51,8 +56,10 @@ pub trait RuntimeHost: Send + Sync + Debug + 'static {
 }
 
 pub struct HostMetrics {
+    subgraph_id: SubgraphDeploymentId,
     handler_execution_time: Box<HistogramVec>,
     host_fn_execution_time: Box<HistogramVec>,
+    handler_stats: Arc<dyn HandlerStatsRegistry>,
     pub stopwatch: StopwatchMetrics,
 }
 
@@ -66,12 +73,13 @@ impl fmt::Debug for HostMetrics {
 impl HostMetrics {
     pub fn new<M: MetricsRegistry>(
         registry: Arc<M>,
-        subgraph_hash: String,
+        subgraph_id: SubgraphDeploymentId,
         stopwatch: StopwatchMetrics,
+        handler_stats: Arc<dyn HandlerStatsRegistry>,
     ) -> Self {
         let handler_execution_time = registry
             .new_histogram_vec(
-                format!("subgraph_handler_execution_time_{}", subgraph_hash),
+                format!("subgraph_handler_execution_time_{}", subgraph_id),
                 String::from("Measures the execution time for handlers"),
                 HashMap::new(),
                 vec![String::from("handler")],
@@ -80,7 +88,7 @@ impl HostMetrics {
             .expect("failed to create `subgraph_handler_execution_time` histogram");
         let host_fn_execution_time = registry
             .new_histogram_vec(
-                format!("subgraph_host_fn_execution_time_{}", subgraph_hash),
+                format!("subgraph_host_fn_execution_time_{}", subgraph_id),
                 String::from("Measures the execution time for host functions"),
                 HashMap::new(),
                 vec![String::from("host_fn_name")],
@@ -88,16 +96,26 @@ impl HostMetrics {
             )
             .expect("failed to create `subgraph_host_fn_execution_time` histogram");
         Self {
+            subgraph_id,
             handler_execution_time,
             host_fn_execution_time,
+            handler_stats,
             stopwatch,
         }
     }
 
-    pub fn observe_handler_execution_time(&self, duration: f64, handler: String) {
+    pub fn observe_handler_execution_time(
+        &self,
+        duration: Duration,
+        kind: HandlerKind,
+        handler: String,
+        failed: bool,
+    ) {
         self.handler_execution_time
             .with_label_values(vec![handler.as_ref()].as_slice())
-            .observe(duration);
+            .observe(duration.as_secs_f64());
+        self.handler_stats
+            .observe(&self.subgraph_id, kind, &handler, duration, failed);
     }
 
     pub fn observe_host_fn_execution_time(&self, duration: f64, fn_name: String) {
@@ -120,6 +138,7 @@ pub trait RuntimeHostBuilder: Clone + Send + Sync + 'static {
         top_level_templates: Vec<DataSourceTemplate>,
         mapping_request_sender: mpsc::Sender<Self::Req>,
         metrics: Arc<HostMetrics>,
+        factory_children: FactoryChildren,
     ) -> Result<Self::Host, Error>;
 
     /// Spawn a mapping and return a channel for mapping requests. The sender should be able to be
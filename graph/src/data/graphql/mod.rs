@@ -10,6 +10,9 @@ mod values;
 pub use self::serialization::SerializableValue;
 
 pub use self::values::{
+    // Trait for converting Ethereum types into GraphQL values.
+    ToGraphQlValue,
+
     // Trait for converting from GraphQL values into other types.
     TryFromValue,
 
@@ -18,4 +21,7 @@ pub use self::values::{
 
     // Trait for plucking typed values out of a GraphQL value maps.
     ValueMap,
+
+    // Trait for deep-merging GraphQL values.
+    ValueMerge,
 };
@@ -30,9 +30,47 @@ pub enum SchemaValidationError {
     CannotImplement(String, String, Strings), // (type, interface, missing_fields)
     #[fail(
         display = "Field `{}` in type `{}` has invalid @derivedFrom: {}",
-        _1, _0, _2
+        field, type_name, reason
     )]
-    DerivedFromInvalid(String, String, String), // (type, field, reason)
+    InvalidDerivedFrom {
+        type_name: String,
+        field: String,
+        reason: String,
+    },
+    #[fail(display = "Type `{}` must have a field `id: ID!`", type_name)]
+    MissingIdField { type_name: String },
+    #[fail(display = "Type `{}` has invalid @index: {}", _0, _1)]
+    IndexInvalid(String, String), // (type, reason)
+    #[fail(
+        display = "Type `{}` has invalid @entity: `name` must be a non-empty string",
+        _0
+    )]
+    EntityNameInvalid(String),
+    #[fail(
+        display = "Types `{}` and `{}` are both stored as entity type `{}`",
+        _1, _2, _0
+    )]
+    EntityNameCollision(String, String, String), // (stored name, type, type)
+    #[fail(display = "There is no {} `{}` to extend", _0, _1)]
+    ExtendedTypeMissing(String, String), // (kind, type)
+    #[fail(display = "Extension of type `{}` redefines `{}`", _0, _1)]
+    ExtensionRedefinition(String, String), // (type, field or enum value)
+    #[fail(
+        display = "Cannot extend `{}`, only object, interface and enum types can be extended",
+        _0
+    )]
+    ExtensionUnsupported(String),
+    #[fail(
+        display = "Interface `{}` implements itself, directly or through other interfaces",
+        _0
+    )]
+    InterfaceCycle(String),
+    #[fail(
+        display = "Interface `{}` cannot implement `{}` because it redefines field `{}` \
+                   with a different type",
+        _0, _1, _2
+    )]
+    InterfaceFieldConflict(String, String, String), // (interface, parent, field)
 }
 
 /// Validates whether a GraphQL schema is compatible with The Graph.
@@ -60,6 +98,48 @@ fn validate_schema_types(schema: &Document) -> Result<(), SchemaValidationError>
     }
 }
 
+/// Validates that all entity types, i.e. object types with an `@entity`
+/// directive, declare an `id: ID!` field. Interface types are only checked if
+/// `interfaces_require_id` is set.
+///
+/// The root `Query` and `Subscription` types are not entities and are exempt,
+/// even if they carry an `@entity` directive.
+pub(crate) fn validate_id_fields(
+    schema: &Document,
+    interfaces_require_id: bool,
+) -> Result<(), SchemaValidationError> {
+    fn has_id_field(fields: &[Field]) -> bool {
+        fields.iter().any(|field| {
+            field.name == "id"
+                && field.field_type == Type::NonNullType(Box::new(Type::NamedType("ID".to_owned())))
+        })
+    }
+
+    let missing_id = schema.definitions.iter().find_map(|d| match d {
+        Definition::TypeDefinition(TypeDefinition::Object(t))
+            if t.name != "Query"
+                && t.name != "Subscription"
+                && get_object_type_directive(t, String::from("entity")).is_some()
+                && !has_id_field(&t.fields) =>
+        {
+            Some(&t.name)
+        }
+        Definition::TypeDefinition(TypeDefinition::Interface(t))
+            if interfaces_require_id && !has_id_field(&t.fields) =>
+        {
+            Some(&t.name)
+        }
+        _ => None,
+    });
+
+    match missing_id {
+        Some(type_name) => Err(SchemaValidationError::MissingIdField {
+            type_name: type_name.to_owned(),
+        }),
+        None => Ok(()),
+    }
+}
+
 /// Validate `interfaceethat `object` implements `interface`.
 pub(crate) fn validate_interface_implementation(
     object: &ObjectType,
@@ -147,13 +227,13 @@ fn find_derived_from<'a>(field: &'a Field) -> Option<&'a Directive> {
 /// Check `@derivedFrom` annotations for various problems. This follows the
 /// corresponding checks in graph-cli
 fn validate_derived_from(schema: &Document) -> Result<(), SchemaValidationError> {
-    // Helper to construct a DerivedFromInvalid
+    // Helper to construct an InvalidDerivedFrom
     fn invalid(object_type: &ObjectType, field_name: &str, reason: &str) -> SchemaValidationError {
-        SchemaValidationError::DerivedFromInvalid(
-            object_type.name.to_owned(),
-            field_name.to_owned(),
-            reason.to_owned(),
-        )
+        SchemaValidationError::InvalidDerivedFrom {
+            type_name: object_type.name.to_owned(),
+            field: field_name.to_owned(),
+            reason: reason.to_owned(),
+        }
     }
 
     let type_definitions = get_object_type_definitions(schema);
@@ -304,8 +384,10 @@ type Account implements Address @entity { id: ID!, txn: Transaction! @derivedFro
         let document = graphql_parser::parse_schema(&raw).expect("Failed to parse raw schema");
         match validate_derived_from(&document) {
             Err(ref e) => match e {
-                SchemaValidationError::DerivedFromInvalid(_, _, msg) => assert_eq!(errmsg, msg),
-                _ => panic!("expected variant SchemaValidationError::DerivedFromInvalid"),
+                SchemaValidationError::InvalidDerivedFrom { reason, .. } => {
+                    assert_eq!(errmsg, reason)
+                }
+                _ => panic!("expected variant SchemaValidationError::InvalidDerivedFrom"),
             },
             Ok(_) => {
                 if errmsg != "ok" {
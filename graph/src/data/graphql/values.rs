@@ -3,13 +3,37 @@ use graphql_parser::query::{Name, Value};
 use std::collections::{BTreeMap, HashMap};
 use std::str::FromStr;
 
-use crate::prelude::{format_err, BigInt};
-use web3::types::{H160, H256};
+use crate::prelude::{format_err, BigInt, QueryExecutionError};
+use web3::types::{H160, H256, U256};
 
 pub trait TryFromValue: Sized {
     fn try_from_value(value: &Value) -> Result<Self, Error>;
 }
 
+/// Converts Ethereum hashes, addresses and numbers into GraphQL values. All
+/// of them are represented as lowercase, `0x`-prefixed hex strings.
+pub trait ToGraphQlValue {
+    fn to_graphql_value(&self) -> Value;
+}
+
+impl ToGraphQlValue for H160 {
+    fn to_graphql_value(&self) -> Value {
+        Value::String(format!("{:#x}", self))
+    }
+}
+
+impl ToGraphQlValue for H256 {
+    fn to_graphql_value(&self) -> Value {
+        Value::String(format!("{:#x}", self))
+    }
+}
+
+impl ToGraphQlValue for U256 {
+    fn to_graphql_value(&self) -> Value {
+        Value::String(format!("{:#x}", self))
+    }
+}
+
 impl TryFromValue for Value {
     fn try_from_value(value: &Value) -> Result<Self, Error> {
         Ok(value.clone())
@@ -84,6 +108,20 @@ impl TryFromValue for H256 {
     }
 }
 
+impl TryFromValue for U256 {
+    fn try_from_value(value: &Value) -> Result<Self, Error> {
+        match value {
+            Value::String(s) => {
+                // `U256::from_str` takes a hex string with no leading `0x`.
+                let string = s.trim_start_matches("0x");
+                U256::from_str(string)
+                    .map_err(|e| format_err!("Cannot parse U256 value from string `{}`: {}", s, e))
+            }
+            _ => Err(format_err!("Cannot parse value into an U256: {:?}", value)),
+        }
+    }
+}
+
 impl TryFromValue for BigInt {
     fn try_from_value(value: &Value) -> Result<Self, Error> {
         match value {
@@ -219,3 +257,174 @@ impl ValueList for Vec<Value> {
         })
     }
 }
+
+pub trait ValueMerge: Sized {
+    fn merge(self, other: Self) -> Result<Self, QueryExecutionError>;
+}
+
+impl ValueMerge for Value {
+    /// Deep-merges two values: objects are merged field by field and lists of
+    /// the same length item by item, while `null` gives way to any other value.
+    /// Fails with `QueryExecutionError::MergeConflict` if two scalars differ,
+    /// or if the two values are of different kinds.
+    fn merge(self, other: Value) -> Result<Value, QueryExecutionError> {
+        merge_at(&mut vec![], self, other)
+    }
+}
+
+/// Merges `left` and `right`, which are found at `path` in the values being
+/// merged.
+fn merge_at(
+    path: &mut Vec<String>,
+    left: Value,
+    right: Value,
+) -> Result<Value, QueryExecutionError> {
+    match (left, right) {
+        (Value::Null, value) | (value, Value::Null) => Ok(value),
+        (Value::Object(mut left), Value::Object(right)) => {
+            for (key, right_value) in right {
+                let value = match left.remove(&key) {
+                    Some(left_value) => {
+                        path.push(key.clone());
+                        let value = merge_at(path, left_value, right_value)?;
+                        path.pop();
+                        value
+                    }
+                    None => right_value,
+                };
+                left.insert(key, value);
+            }
+            Ok(Value::Object(left))
+        }
+        (Value::List(left), Value::List(right)) if left.len() == right.len() => left
+            .into_iter()
+            .zip(right)
+            .enumerate()
+            .map(|(i, (left, right))| {
+                path.push(i.to_string());
+                let value = merge_at(path, left, right);
+                path.pop();
+                value
+            })
+            .collect::<Result<_, _>>()
+            .map(Value::List),
+        (left, right) => {
+            if left == right {
+                Ok(left)
+            } else {
+                Err(QueryExecutionError::MergeConflict(path.join(".")))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object_value(fields: Vec<(&str, Value)>) -> Value {
+        Value::Object(
+            fields
+                .into_iter()
+                .map(|(name, value)| (name.to_owned(), value))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn h160_round_trips_through_graphql_values() {
+        let address = H160::from_low_u64_be(0xabcdef);
+        let value = address.to_graphql_value();
+
+        assert_eq!(
+            value,
+            Value::String(String::from("0x0000000000000000000000000000000000abcdef"))
+        );
+        assert_eq!(H160::try_from_value(&value).unwrap(), address);
+        assert_eq!(
+            H160::try_from_value(&Value::String(String::from(
+                "0000000000000000000000000000000000ABCDEF"
+            )))
+            .unwrap(),
+            address
+        );
+    }
+
+    #[test]
+    fn h256_round_trips_through_graphql_values() {
+        let hash = H256::from_low_u64_be(0xabcdef);
+        let value = hash.to_graphql_value();
+
+        assert_eq!(value, Value::String(format!("0x{}abcdef", "0".repeat(58))));
+        assert_eq!(H256::try_from_value(&value).unwrap(), hash);
+        assert_eq!(
+            H256::try_from_value(&Value::String(format!("{}abcdef", "0".repeat(58)))).unwrap(),
+            hash
+        );
+    }
+
+    #[test]
+    fn u256_round_trips_through_graphql_values() {
+        let number = U256::from(0xabcdef);
+        let value = number.to_graphql_value();
+
+        assert_eq!(value, Value::String(String::from("0xabcdef")));
+        assert_eq!(U256::try_from_value(&value).unwrap(), number);
+        assert_eq!(
+            U256::try_from_value(&Value::String(String::from("abcdef"))).unwrap(),
+            number
+        );
+    }
+
+    #[test]
+    fn merging_values_combines_objects_and_lists() {
+        let left = object_value(vec![
+            ("name", Value::String(String::from("subgraph"))),
+            ("health", Value::Null),
+            (
+                "versions",
+                Value::List(vec![object_value(vec![("id", Value::Int(1.into()))])]),
+            ),
+        ]);
+        let right = object_value(vec![
+            ("health", Value::Enum(String::from("healthy"))),
+            ("name", Value::String(String::from("subgraph"))),
+            (
+                "versions",
+                Value::List(vec![object_value(vec![("synced", Value::Boolean(true))])]),
+            ),
+        ]);
+
+        assert_eq!(
+            left.merge(right).unwrap(),
+            object_value(vec![
+                ("name", Value::String(String::from("subgraph"))),
+                ("health", Value::Enum(String::from("healthy"))),
+                (
+                    "versions",
+                    Value::List(vec![object_value(vec![
+                        ("id", Value::Int(1.into())),
+                        ("synced", Value::Boolean(true)),
+                    ])]),
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn merging_different_scalars_is_a_conflict() {
+        let left = object_value(vec![(
+            "versions",
+            Value::List(vec![object_value(vec![("synced", Value::Boolean(true))])]),
+        )]);
+        let right = object_value(vec![(
+            "versions",
+            Value::List(vec![object_value(vec![("synced", Value::Boolean(false))])]),
+        )]);
+
+        match left.merge(right) {
+            Err(QueryExecutionError::MergeConflict(path)) => assert_eq!(path, "versions.0.synced"),
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+}
@@ -7,7 +7,9 @@ use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
 use std::string::FromUtf8Error;
+use std::time::Duration;
 
+use super::InputValueLimitError;
 use crate::components::store::StoreError;
 use crate::data::subgraph::*;
 
@@ -33,6 +35,11 @@ pub enum QueryExecutionError {
     OrderByNotSupportedForType(String),
     FilterNotSupportedError(String, String),
     UnknownField(Pos, String, String),
+    UnknownArgument {
+        pos: Pos,
+        field: String,
+        argument: String,
+    },
     EmptyQuery,
     MultipleSubscriptionFields,
     SubgraphDeploymentIdError(String),
@@ -41,6 +48,7 @@ pub enum QueryExecutionError {
     EntityFieldError(String, String),
     ListTypesError(String, Vec<String>),
     ListFilterError(String),
+    ListTooLong { filter: String, length: usize, max: usize },
     ValueParseError(String, String),
     AttributeTypeError(String, String),
     EntityParseError(String),
@@ -53,7 +61,38 @@ pub enum QueryExecutionError {
     ScalarCoercionError(Pos, String, q::Value, String),
     TooComplex(u64, u64), // (complexity, max_complexity)
     TooDeep(u8),          // max_depth
+    TooManyFields { found: usize, max: usize },
     UndefinedFragment(String),
+    SubgraphFailed { block: u64, message: String },
+    TriggerPreflightError(String),
+    ResponseTooLarge { bytes: usize, max: usize },
+    MergeConflict(String),
+    ResponseWriteError(String),
+    Unauthorized(Pos, String, String),
+    IntOverflow {
+        pos: Pos,
+        entity: String,
+        field: String,
+        value: String,
+    },
+    InvalidEnumValue {
+        pos: Pos,
+        enum_name: String,
+        value: String,
+        allowed: Vec<String>,
+        suggestion: Option<String>,
+    },
+    DeploymentBusy {
+        deployment: SubgraphDeploymentId,
+        retry_after: Duration,
+    },
+    ShuttingDown,
+    InputValueTooLarge {
+        pos: Pos,
+        argument: String,
+        error: InputValueLimitError,
+    },
+    VariablesTooLarge(InputValueLimitError),
 }
 
 impl Error for QueryExecutionError {
@@ -124,6 +163,9 @@ impl fmt::Display for QueryExecutionError {
             UnknownField(_, t, s) => {
                 write!(f, "Type `{}` has no field `{}`", t, s)
             }
+            UnknownArgument { field, argument, .. } => {
+                write!(f, "Field `{}` has no argument `{}`", field, argument)
+            }
             EmptyQuery => write!(f, "The query is empty"),
             MultipleSubscriptionFields => write!(
                 f,
@@ -156,6 +198,12 @@ impl fmt::Display for QueryExecutionError {
             ListFilterError(s) => {
                 write!(f, "Non-list value passed to `{}` filter", s)
             }
+            ListTooLong { filter, length, max } => write!(
+                f,
+                "{} values passed to `{}` filter, but at most {} are allowed; \
+                 split the query into several queries with fewer values each",
+                length, filter, max
+            ),
             ValueParseError(t, e) => {
                 write!(f, "Failed to decode `{}` value: `{}`", t, e)
             }
@@ -193,7 +241,57 @@ impl fmt::Display for QueryExecutionError {
                            return smaller collections", complexity, max_complexity)
             }
             TooDeep(max_depth) => write!(f, "query has a depth that exceeds the limit of `{}`", max_depth),
+            TooManyFields { found, max } => {
+                write!(f, "query selects `{}` fields in one selection set and thereby exceeds \
+                           the limit of `{}` fields per selection set. Possible solutions are \
+                           selecting fewer fields or splitting the query into several queries",
+                       found, max)
+            }
             UndefinedFragment(frag_name) => write!(f, "fragment `{}` is not defined", frag_name),
+            SubgraphFailed { block, message } => {
+                write!(f, "Subgraph failed at block {}: {}; pass `subgraphError: allow` \
+                           to query its data up to that block", block, message)
+            }
+            TriggerPreflightError(e) => write!(f, "Trigger preflight failed: {}", e),
+            ResponseTooLarge { bytes, max } => {
+                write!(f, "query response of about `{}` bytes exceeds the limit of `{}` bytes \
+                           and was cut short. Possible solutions are querying fewer fields or \
+                           using `first` to return smaller collections", bytes, max)
+            }
+            MergeConflict(path) => {
+                write!(f, "Conflicting values for `{}` when combining query results", path)
+            }
+            ResponseWriteError(e) => write!(f, "Failed to write the query response: {}", e),
+            Unauthorized(_, t, s) => {
+                write!(f, "Access to field `{}` of type `{}` is not authorized", s, t)
+            }
+            IntOverflow { entity, field, value, .. } => {
+                write!(f, "Value `{}` of field `{}` of {} is out of range for type `Int`; \
+                           values that need more than 32 bits have to be stored in \
+                           `BigInt` fields", value, field, entity)
+            }
+            InvalidEnumValue { enum_name, value, allowed, suggestion, .. } => {
+                write!(f, "Value `{}` is not a value of enum type `{}`.", value, enum_name)?;
+                if let Some(suggestion) = suggestion {
+                    write!(f, " Did you mean `{}`?", suggestion)?;
+                }
+                write!(f, " Allowed values are: {}",
+                       allowed.iter().map(|value| format!("`{}`", value)).collect::<Vec<_>>().join(", "))
+            }
+            DeploymentBusy { deployment, retry_after } => {
+                write!(f, "Too many queries for subgraph deployment `{}` are running or \
+                           waiting to run; retry after {} seconds",
+                       deployment, retry_after.as_secs().max(1))
+            }
+            ShuttingDown => {
+                write!(f, "The node is shutting down and no longer runs queries")
+            }
+            InputValueTooLarge { argument, error, .. } => {
+                write!(f, "Invalid value for argument `{}`: {}", argument, error)
+            }
+            VariablesTooLarge(error) => {
+                write!(f, "Invalid query variables: {}", error)
+            }
         }
     }
 }
@@ -334,7 +432,12 @@ impl Serialize for QueryError {
             | QueryError::ExecutionError(AmbiguousDerivedFromResult(pos, _, _, _))
             | QueryError::ExecutionError(EnumCoercionError(pos, _, _, _, _))
             | QueryError::ExecutionError(ScalarCoercionError(pos, _, _, _))
-            | QueryError::ExecutionError(UnknownField(pos, _, _)) => {
+            | QueryError::ExecutionError(UnknownField(pos, _, _))
+            | QueryError::ExecutionError(UnknownArgument { pos, .. })
+            | QueryError::ExecutionError(Unauthorized(pos, _, _))
+            | QueryError::ExecutionError(IntOverflow { pos, .. })
+            | QueryError::ExecutionError(InvalidEnumValue { pos, .. })
+            | QueryError::ExecutionError(InputValueTooLarge { pos, .. }) => {
                 let mut location = HashMap::new();
                 location.insert("line", pos.line);
                 location.insert("column", pos.column);
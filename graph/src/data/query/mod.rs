@@ -3,5 +3,5 @@ mod query;
 mod result;
 
 pub use self::error::{QueryError, QueryExecutionError};
-pub use self::query::{Query, QueryVariables};
-pub use self::result::QueryResult;
+pub use self::query::{InputValueLimitError, InputValueLimits, Query, QueryVariables};
+pub use self::result::{QueryCostEstimate, QueryExtensions, QueryResult};
@@ -1,76 +1,457 @@
+use failure::{format_err, Error, Fail};
 use graphql_parser::query as q;
-use serde::de::Deserializer;
+use lazy_static::lazy_static;
+use serde::de::{self, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
 use serde::Deserialize;
+use std::cell::Cell;
 use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::env;
+use std::fmt;
 use std::ops::{Deref, DerefMut};
+use std::str::FromStr;
 use std::sync::Arc;
 
 use crate::data::schema::Schema;
+use crate::data::store::scalar::BigDecimal;
 
-fn deserialize_number<'de, D>(deserializer: D) -> Result<q::Number, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let i: i32 = Deserialize::deserialize(deserializer)?;
-    Ok(q::Number::from(i))
-}
-
-fn deserialize_list<'de, D>(deserializer: D) -> Result<Vec<q::Value>, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let values: Vec<DeserializableGraphQlValue> = Deserialize::deserialize(deserializer)?;
-    Ok(values.into_iter().map(|v| v.0).collect())
-}
-
-fn deserialize_object<'de, D>(deserializer: D) -> Result<BTreeMap<String, q::Value>, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let pairs: BTreeMap<String, DeserializableGraphQlValue> =
-        Deserialize::deserialize(deserializer)?;
-    Ok(pairs.into_iter().map(|(k, v)| (k, v.0)).collect())
-}
-
-#[derive(Deserialize)]
-#[serde(untagged, remote = "q::Value")]
-enum GraphQLValue {
-    #[serde(deserialize_with = "deserialize_number")]
-    Int(q::Number),
-    Float(f64),
-    String(String),
-    Boolean(bool),
-    Null,
-    Enum(String),
-    #[serde(deserialize_with = "deserialize_list")]
-    List(Vec<q::Value>),
-    #[serde(deserialize_with = "deserialize_object")]
-    Object(BTreeMap<String, q::Value>),
-}
-
-/// Variable value for a GraphQL query.
-#[derive(Clone, Debug, Deserialize)]
-pub struct DeserializableGraphQlValue(#[serde(with = "GraphQLValue")] q::Value);
-
-fn deserialize_variables<'de, D>(deserializer: D) -> Result<HashMap<String, q::Value>, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let pairs: BTreeMap<String, DeserializableGraphQlValue> =
-        Deserialize::deserialize(deserializer)?;
-    Ok(pairs.into_iter().map(|(k, v)| (k, v.0)).collect())
+lazy_static! {
+    static ref MAX_VARIABLE_DEPTH: usize = env::var("GRAPH_GRAPHQL_MAX_VARIABLE_DEPTH")
+        .ok()
+        .map(|s| usize::from_str(&s)
+            .unwrap_or_else(|_| panic!("failed to parse env var GRAPH_GRAPHQL_MAX_VARIABLE_DEPTH")))
+        .unwrap_or(64);
+    static ref MAX_VARIABLE_KEYS: usize = env::var("GRAPH_GRAPHQL_MAX_VARIABLE_KEYS")
+        .ok()
+        .map(|s| usize::from_str(&s)
+            .unwrap_or_else(|_| panic!("failed to parse env var GRAPH_GRAPHQL_MAX_VARIABLE_KEYS")))
+        .unwrap_or(10_000);
+    static ref MAX_VARIABLE_STRING_LENGTH: usize =
+        env::var("GRAPH_GRAPHQL_MAX_VARIABLE_STRING_LENGTH")
+            .ok()
+            .map(|s| usize::from_str(&s).unwrap_or_else(|_| panic!(
+                "failed to parse env var GRAPH_GRAPHQL_MAX_VARIABLE_STRING_LENGTH"
+            )))
+            .unwrap_or(1_000_000);
+}
+
+/// Limits for the size of input values, i.e. of query variables and of the
+/// argument values written into queries.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InputValueLimits {
+    /// Maximum number of lists and objects a value may be nested in.
+    pub max_depth: usize,
+
+    /// Maximum number of object keys and list items, counted across all
+    /// values together.
+    pub max_keys: usize,
+
+    /// Maximum length of a string or object key, in bytes.
+    pub max_string_length: usize,
+}
+
+impl Default for InputValueLimits {
+    fn default() -> Self {
+        InputValueLimits {
+            max_depth: *MAX_VARIABLE_DEPTH,
+            max_keys: *MAX_VARIABLE_KEYS,
+            max_string_length: *MAX_VARIABLE_STRING_LENGTH,
+        }
+    }
+}
+
+impl InputValueLimits {
+    /// Checks that `value` stays within the limits.
+    pub fn check(&self, value: &q::Value) -> Result<(), InputValueLimitError> {
+        let budget = InputValueBudget::new(self);
+        budget.check(value, 0)
+    }
+}
+
+/// An input value that exceeds one of the `InputValueLimits`. The errors
+/// only name the limit so that they don't repeat large payloads.
+#[derive(Clone, Debug, Fail, PartialEq)]
+pub enum InputValueLimitError {
+    #[fail(display = "Input value is nested more than {} levels deep", _0)]
+    TooDeep(usize),
+    #[fail(display = "Input value has more than {} keys and list items", _0)]
+    TooManyKeys(usize),
+    #[fail(display = "Input value contains a string longer than {} bytes", _0)]
+    StringTooLong(usize),
+}
+
+/// Tracks how much of the `InputValueLimits` the values checked or
+/// deserialized so far have used up.
+struct InputValueBudget<'a> {
+    limits: &'a InputValueLimits,
+    keys: Cell<usize>,
+
+    /// The limit a deserialized value exceeded; `serde` errors only carry
+    /// a message.
+    exceeded: Cell<Option<InputValueLimitError>>,
+}
+
+impl<'a> InputValueBudget<'a> {
+    fn new(limits: &'a InputValueLimits) -> Self {
+        InputValueBudget {
+            limits,
+            keys: Cell::new(0),
+            exceeded: Cell::new(None),
+        }
+    }
+
+    fn enter(&self, depth: usize) -> Result<(), InputValueLimitError> {
+        if depth >= self.limits.max_depth {
+            Err(InputValueLimitError::TooDeep(self.limits.max_depth))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn add_key(&self) -> Result<(), InputValueLimitError> {
+        let keys = self.keys.get() + 1;
+        if keys > self.limits.max_keys {
+            Err(InputValueLimitError::TooManyKeys(self.limits.max_keys))
+        } else {
+            self.keys.set(keys);
+            Ok(())
+        }
+    }
+
+    fn check_string(&self, s: &str) -> Result<(), InputValueLimitError> {
+        if s.len() > self.limits.max_string_length {
+            Err(InputValueLimitError::StringTooLong(
+                self.limits.max_string_length,
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn check(&self, value: &q::Value, depth: usize) -> Result<(), InputValueLimitError> {
+        match value {
+            q::Value::String(s) | q::Value::Enum(s) => self.check_string(s),
+            q::Value::List(values) => {
+                self.enter(depth)?;
+                for value in values {
+                    self.add_key()?;
+                    self.check(value, depth + 1)?;
+                }
+                Ok(())
+            }
+            q::Value::Object(fields) => {
+                self.enter(depth)?;
+                for (name, value) in fields {
+                    self.add_key()?;
+                    self.check_string(name)?;
+                    self.check(value, depth + 1)?;
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Turns `error` into a `serde` error, remembering which limit it was.
+    fn fail<E: de::Error>(&self, error: InputValueLimitError) -> E {
+        let e = E::custom(&error);
+        self.exceeded.set(Some(error));
+        e
+    }
+}
+
+/// Deserializes a JSON value into a GraphQL value, checking the limits
+/// before anything is allocated for it.
+struct LimitedValue<'b, 'a> {
+    budget: &'b InputValueBudget<'a>,
+    depth: usize,
+}
+
+impl<'b, 'a> LimitedValue<'b, 'a> {
+    fn nested(&self) -> Self {
+        LimitedValue {
+            budget: self.budget,
+            depth: self.depth + 1,
+        }
+    }
+}
+
+impl<'de, 'b, 'a> DeserializeSeed<'de> for LimitedValue<'b, 'a> {
+    type Value = q::Value;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de, 'b, 'a> Visitor<'de> for LimitedValue<'b, 'a> {
+    type Value = q::Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a JSON value")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<q::Value, E> {
+        Ok(q::Value::Null)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<q::Value, E> {
+        Ok(q::Value::Null)
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<q::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        self.deserialize(deserializer)
+    }
+
+    fn visit_bool<E: de::Error>(self, b: bool) -> Result<q::Value, E> {
+        Ok(q::Value::Boolean(b))
+    }
+
+    fn visit_i64<E: de::Error>(self, n: i64) -> Result<q::Value, E> {
+        Ok(graphql_value_from_json_number(&n.into()))
+    }
+
+    fn visit_u64<E: de::Error>(self, n: u64) -> Result<q::Value, E> {
+        Ok(graphql_value_from_json_number(&n.into()))
+    }
+
+    fn visit_f64<E: de::Error>(self, f: f64) -> Result<q::Value, E> {
+        Ok(serde_json::Number::from_f64(f)
+            .map(|number| graphql_value_from_json_number(&number))
+            .unwrap_or(q::Value::Null))
+    }
+
+    fn visit_str<E: de::Error>(self, s: &str) -> Result<q::Value, E> {
+        self.budget
+            .check_string(s)
+            .map_err(|e| self.budget.fail(e))?;
+        Ok(q::Value::String(s.to_owned()))
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<q::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let budget = self.budget;
+        budget.enter(self.depth).map_err(|e| budget.fail(e))?;
+
+        let mut values = Vec::new();
+        while let Some(value) = seq.next_element_seed(self.nested())? {
+            budget.add_key().map_err(|e| budget.fail(e))?;
+            values.push(value);
+        }
+        Ok(q::Value::List(values))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<q::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let budget = self.budget;
+
+        // With its `arbitrary_precision` feature, `serde_json` passes
+        // numbers as objects with a single entry under a private key. Only
+        // real objects count towards the depth and the keys
+        let mut next = map.next_key_seed(LimitedKey(budget))?;
+        if next.as_ref().map(String::as_str) == Some(JSON_NUMBER_TOKEN) {
+            let number: String = map.next_value()?;
+            return serde_json::from_str(&number)
+                .map(|number| graphql_value_from_json_number(&number))
+                .map_err(de::Error::custom);
+        }
+
+        budget.enter(self.depth).map_err(|e| budget.fail(e))?;
+        let mut fields = BTreeMap::new();
+        while let Some(name) = next {
+            budget.add_key().map_err(|e| budget.fail(e))?;
+            let value = map.next_value_seed(self.nested())?;
+            fields.insert(name, value);
+            next = map.next_key_seed(LimitedKey(budget))?;
+        }
+        Ok(q::Value::Object(fields))
+    }
+}
+
+/// The key under which `serde_json` passes numbers when its
+/// `arbitrary_precision` feature is enabled.
+const JSON_NUMBER_TOKEN: &str = "$serde_json::private::Number";
+
+/// Deserializes an object key, checking its length before allocating it.
+struct LimitedKey<'b, 'a>(&'b InputValueBudget<'a>);
+
+impl<'de, 'b, 'a> DeserializeSeed<'de> for LimitedKey<'b, 'a> {
+    type Value = String;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_str(self)
+    }
+}
+
+impl<'de, 'b, 'a> Visitor<'de> for LimitedKey<'b, 'a> {
+    type Value = String;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an object key")
+    }
+
+    fn visit_str<E: de::Error>(self, s: &str) -> Result<String, E> {
+        if s != JSON_NUMBER_TOKEN {
+            self.0.check_string(s).map_err(|e| self.0.fail(e))?;
+        }
+        Ok(s.to_owned())
+    }
+}
+
+/// Deserializes the variables object of a request, the names of the
+/// variables counting towards the keys of the limits.
+struct LimitedVariables<'b, 'a>(&'b InputValueBudget<'a>);
+
+impl<'de, 'b, 'a> DeserializeSeed<'de> for LimitedVariables<'b, 'a> {
+    type Value = HashMap<String, q::Value>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, 'b, 'a> Visitor<'de> for LimitedVariables<'b, 'a> {
+    type Value = HashMap<String, q::Value>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an object of query variables")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let budget = self.0;
+        let mut variables = HashMap::new();
+        while let Some(name) = map.next_key_seed(LimitedKey(budget))? {
+            budget.add_key().map_err(|e| budget.fail(e))?;
+            let value = map.next_value_seed(LimitedValue { budget, depth: 0 })?;
+            variables.insert(name, value);
+        }
+        Ok(variables)
+    }
+}
+
+/// Converts a JSON value into a GraphQL value. Integers become `Int`s if
+/// they fit into one, and other numbers become `Float`s if an `f64` holds
+/// them exactly. All remaining numbers, e.g. `BigInt`s beyond 2^53, become
+/// strings with their exact decimal value instead of losing precision.
+fn graphql_value_from_json(value: serde_json::Value) -> q::Value {
+    match value {
+        serde_json::Value::Null => q::Value::Null,
+        serde_json::Value::Bool(b) => q::Value::Boolean(b),
+        serde_json::Value::Number(number) => graphql_value_from_json_number(&number),
+        serde_json::Value::String(s) => q::Value::String(s),
+        serde_json::Value::Array(values) => {
+            q::Value::List(values.into_iter().map(graphql_value_from_json).collect())
+        }
+        serde_json::Value::Object(fields) => q::Value::Object(
+            fields
+                .into_iter()
+                .map(|(name, value)| (name, graphql_value_from_json(value)))
+                .collect(),
+        ),
+    }
+}
+
+fn graphql_value_from_json_number(number: &serde_json::Number) -> q::Value {
+    // Numbers keep the text they were parsed from
+    let text = number.to_string();
+    let is_integer = !text.contains(|c| c == '.' || c == 'e' || c == 'E');
+
+    if is_integer {
+        match number.as_i64().and_then(|n| i32::try_from(n).ok()) {
+            Some(n) => q::Value::Int(n.into()),
+            None => q::Value::String(text),
+        }
+    } else {
+        let exact = BigDecimal::from_str(&text).ok();
+        match number.as_f64() {
+            Some(f) if BigDecimal::from_str(&f.to_string()).ok() == exact => q::Value::Float(f),
+            _ => q::Value::String(text),
+        }
+    }
 }
 
 /// Variable values for a GraphQL query.
-#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
-pub struct QueryVariables(
-    #[serde(deserialize_with = "deserialize_variables")] HashMap<String, q::Value>,
-);
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct QueryVariables(HashMap<String, q::Value>);
 
 impl QueryVariables {
     pub fn new(variables: HashMap<String, q::Value>) -> Self {
         QueryVariables(variables)
     }
+
+    /// Parses variables from JSON, failing as soon as they exceed `limits`.
+    /// The error is an `InputValueLimitError` if they do.
+    pub fn from_json_slice(json: &[u8], limits: &InputValueLimits) -> Result<Self, Error> {
+        let budget = InputValueBudget::new(limits);
+        let mut deserializer = serde_json::Deserializer::from_slice(json);
+        LimitedVariables(&budget)
+            .deserialize(&mut deserializer)
+            .and_then(|variables| deserializer.end().map(|()| variables))
+            .map(QueryVariables)
+            .map_err(|e| match budget.exceeded.take() {
+                Some(exceeded) => exceeded.into(),
+                None => e.into(),
+            })
+    }
+}
+
+/// Deserializing variables applies the default `InputValueLimits`.
+impl<'de> Deserialize<'de> for QueryVariables {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let limits = InputValueLimits::default();
+        LimitedVariables(&InputValueBudget::new(&limits))
+            .deserialize(deserializer)
+            .map(QueryVariables)
+    }
+}
+
+impl From<HashMap<String, q::Value>> for QueryVariables {
+    fn from(variables: HashMap<String, q::Value>) -> Self {
+        QueryVariables(variables)
+    }
+}
+
+/// Converts the variables of a request, which must be a JSON object, the
+/// same way as deserializing them does.
+impl TryFrom<serde_json::Value> for QueryVariables {
+    type Error = Error;
+
+    fn try_from(value: serde_json::Value) -> Result<Self, Self::Error> {
+        match value {
+            serde_json::Value::Object(variables) => Ok(QueryVariables(
+                variables
+                    .into_iter()
+                    .map(|(name, value)| (name, graphql_value_from_json(value)))
+                    .collect(),
+            )),
+            _ => Err(format_err!(
+                "Query variables must be an object, got: {}",
+                value
+            )),
+        }
+    }
 }
 
 impl Deref for QueryVariables {
@@ -93,4 +474,187 @@ pub struct Query {
     pub schema: Arc<Schema>,
     pub document: q::Document,
     pub variables: Option<QueryVariables>,
+
+    /// Only estimate the cost of the query instead of executing it. Set
+    /// through the `dryRun` request extension.
+    pub dry_run: bool,
+
+    /// Warn in the result if the deployment is far behind the chain head.
+    /// Set through the `syncWarning` request extension.
+    pub sync_warning: bool,
+
+    /// The ID that tags the log messages of the query and is returned in
+    /// the `extensions` of its result. Generated when the query is executed
+    /// unless it is set, e.g. for queries made while resolving another
+    /// query, which share its ID.
+    pub query_id: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variable(json: &str) -> q::Value {
+        let variables = serde_json::from_str(&format!("{{ \"v\": {} }}", json)).unwrap();
+        let mut variables = QueryVariables::try_from(variables).unwrap();
+        variables.remove("v").unwrap()
+    }
+
+    #[test]
+    fn json_numbers_keep_their_precision() {
+        assert_eq!(variable("5"), q::Value::Int(5.into()));
+        assert_eq!(
+            variable("-2147483648"),
+            q::Value::Int(i32::min_value().into())
+        );
+        assert_eq!(variable("1.5"), q::Value::Float(1.5));
+        assert_eq!(variable("1e3"), q::Value::Float(1000.0));
+
+        // Integers that don't fit into an `Int` and decimals that an `f64`
+        // can't hold are passed on as strings
+        assert_eq!(
+            variable("2147483648"),
+            q::Value::String(String::from("2147483648"))
+        );
+        assert_eq!(
+            variable("1208925819614629174706176"),
+            q::Value::String(String::from("1208925819614629174706176"))
+        );
+        assert_eq!(
+            variable("0.1234567890123456789"),
+            q::Value::String(String::from("0.1234567890123456789"))
+        );
+    }
+
+    #[test]
+    fn deserializing_and_converting_json_variables_agree() {
+        let json = r#"{ "int": 5, "big": 1208925819614629174706176, "list": [1.5, "s", null] }"#;
+        let deserialized: QueryVariables = serde_json::from_str(json).unwrap();
+        let converted =
+            QueryVariables::try_from(serde_json::from_str::<serde_json::Value>(json).unwrap())
+                .unwrap();
+        assert_eq!(deserialized, converted);
+
+        assert!(QueryVariables::try_from(serde_json::Value::from(5)).is_err());
+    }
+
+    fn limits() -> InputValueLimits {
+        InputValueLimits {
+            max_depth: 8,
+            max_keys: 100,
+            max_string_length: 64,
+        }
+    }
+
+    fn limit_error(json: &str) -> InputValueLimitError {
+        let e = QueryVariables::from_json_slice(json.as_bytes(), &limits())
+            .expect_err("variables exceed the limits");
+        assert!(!e.to_string().contains("xxxx"), "error echoes the payload");
+        e.downcast::<InputValueLimitError>()
+            .expect("error is an InputValueLimitError")
+    }
+
+    #[test]
+    fn variables_within_the_limits_are_accepted() {
+        let json = r#"{ "list": [[[1, 2.5]]], "object": { "a": { "b": "xxxx" } } }"#;
+        let variables = QueryVariables::from_json_slice(json.as_bytes(), &limits()).unwrap();
+        let converted =
+            QueryVariables::try_from(serde_json::from_str::<serde_json::Value>(json).unwrap())
+                .unwrap();
+        assert_eq!(variables, converted);
+    }
+
+    #[test]
+    fn numbers_and_single_entry_objects_are_told_apart() {
+        // Numbers are as deep as the strings next to them can be
+        let json = format!(
+            "{{ \"v\": {}1, \"xxxx\"{}, \"o\": {{ \"a\": \"1\" }} }}",
+            "[".repeat(8),
+            "]".repeat(8)
+        );
+        let variables = QueryVariables::from_json_slice(json.as_bytes(), &limits()).unwrap();
+        let converted =
+            QueryVariables::try_from(serde_json::from_str::<serde_json::Value>(&json).unwrap())
+                .unwrap();
+        assert_eq!(variables, converted);
+    }
+
+    #[test]
+    fn deeply_nested_variables_are_rejected() {
+        // Parsing stops once the limit is reached, long before the end
+        let json = format!("{{ \"v\": {}{} }}", "[".repeat(10_000), "]".repeat(10_000));
+        assert_eq!(limit_error(&json), InputValueLimitError::TooDeep(8));
+
+        let json = format!(
+            "{{ \"v\": {}\"xxxx\"{} }}",
+            "{\"k\": ".repeat(9),
+            "}".repeat(9)
+        );
+        assert_eq!(limit_error(&json), InputValueLimitError::TooDeep(8));
+
+        // Objects are as deep as lists can be, and their values aren't
+        // looked at once they are too deep
+        let json = format!(
+            "{{ \"v\": {}\"xxxx\"{} }}",
+            "{\"k\": ".repeat(8),
+            "}".repeat(8)
+        );
+        assert!(QueryVariables::from_json_slice(json.as_bytes(), &limits()).is_ok());
+        let json = format!(
+            "{{ \"v\": {}\"{}\"{} }}",
+            "{\"k\": ".repeat(9),
+            "x".repeat(1_000),
+            "}".repeat(9)
+        );
+        assert_eq!(limit_error(&json), InputValueLimitError::TooDeep(8));
+    }
+
+    #[test]
+    fn wide_variables_are_rejected() {
+        let items = vec!["\"xxxx\""; 1_000_000].join(",");
+        let json = format!("{{ \"v\": [{}] }}", items);
+        assert_eq!(limit_error(&json), InputValueLimitError::TooManyKeys(100));
+
+        let fields = (0..1_000)
+            .map(|i| format!("\"k{}\": \"xxxx\"", i))
+            .collect::<Vec<_>>()
+            .join(",");
+        assert_eq!(
+            limit_error(&format!("{{ {} }}", fields)),
+            InputValueLimitError::TooManyKeys(100)
+        );
+    }
+
+    #[test]
+    fn long_strings_in_variables_are_rejected() {
+        let long = "x".repeat(1_000_000);
+        assert_eq!(
+            limit_error(&format!("{{ \"v\": \"{}\" }}", long)),
+            InputValueLimitError::StringTooLong(64)
+        );
+        assert_eq!(
+            limit_error(&format!("{{ \"v\": {{ \"{}\": 1 }} }}", long)),
+            InputValueLimitError::StringTooLong(64)
+        );
+    }
+
+    #[test]
+    fn literals_are_checked_against_the_limits() {
+        let nested = (0..9).fold(q::Value::Int(1.into()), |value, _| {
+            q::Value::List(vec![value])
+        });
+        assert_eq!(
+            limits().check(&nested),
+            Err(InputValueLimitError::TooDeep(8))
+        );
+        assert_eq!(
+            limits().check(&q::Value::List(vec![q::Value::Null; 101])),
+            Err(InputValueLimitError::TooManyKeys(100))
+        );
+        assert_eq!(
+            limits().check(&q::Value::String("x".repeat(65))),
+            Err(InputValueLimitError::StringTooLong(64))
+        );
+        assert_eq!(limits().check(&variable(r#"[[{ "a": "b" }]]"#)), Ok(()));
+    }
 }
@@ -1,5 +1,5 @@
 use super::error::{QueryError, QueryExecutionError};
-use crate::data::graphql::SerializableValue;
+use crate::data::graphql::{SerializableValue, ValueMerge};
 use graphql_parser::query as q;
 use serde::ser::*;
 use serde::Serialize;
@@ -21,14 +21,150 @@ pub struct QueryResult {
     pub data: Option<q::Value>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub errors: Option<Vec<QueryError>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extensions: Option<QueryExtensions>,
 }
 
 impl QueryResult {
     pub fn new(data: Option<q::Value>) -> Self {
-        QueryResult { data, errors: None }
+        QueryResult {
+            data,
+            errors: None,
+            extensions: None,
+        }
+    }
+
+    /// Combines two results, e.g. of separate queries whose data make up one
+    /// response: their data is deep-merged with `ValueMerge` and their errors
+    /// are concatenated. If the data conflicts, the combined result has no
+    /// data and reports the conflict as an additional error.
+    pub fn merge(self, other: QueryResult) -> QueryResult {
+        let mut errors: Vec<_> = self
+            .errors
+            .into_iter()
+            .chain(other.errors)
+            .flatten()
+            .collect();
+
+        let data = match (self.data, other.data) {
+            (Some(data), Some(other_data)) => match data.merge(other_data) {
+                Ok(data) => Some(data),
+                Err(e) => {
+                    errors.push(QueryError::from(e));
+                    None
+                }
+            },
+            (data, other_data) => data.or(other_data),
+        };
+
+        QueryResult {
+            data,
+            errors: if errors.is_empty() {
+                None
+            } else {
+                Some(errors)
+            },
+            extensions: self.extensions.or(other.extensions),
+        }
+    }
+
+    /// Reports each error once and in a fixed order. The same error can
+    /// come up many times, e.g. for every element of a list, and errors are
+    /// collected in whatever order their fields were resolved in. Errors
+    /// that serialize identically are kept once, and the rest is ordered by
+    /// where in the query they occurred, then by their message.
+    pub fn dedup_errors(&mut self) {
+        let errors = match self.errors.take() {
+            Some(errors) => errors,
+            None => return,
+        };
+
+        let mut errors: Vec<_> = errors
+            .into_iter()
+            .map(|error| (ErrorKey::new(&error), error))
+            .collect();
+        errors.sort_by(|(key, _), (other_key, _)| key.cmp(other_key));
+        errors.dedup_by(|(key, _), (other_key, _)| key == other_key);
+        self.errors = Some(errors.into_iter().map(|(_, error)| error).collect());
     }
 }
 
+/// What `QueryResult::dedup_errors` tells errors apart and orders them by,
+/// taken from their serialized form. Errors without a location come first.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct ErrorKey {
+    locations: Vec<(u64, u64)>,
+    message: String,
+    serialized: String,
+}
+
+impl ErrorKey {
+    fn new(error: &QueryError) -> Self {
+        let value = serde_json::to_value(error).unwrap_or(serde_json::Value::Null);
+        let locations = value
+            .get("locations")
+            .and_then(|locations| locations.as_array())
+            .map_or(vec![], |locations| {
+                locations
+                    .iter()
+                    .map(|location| {
+                        (
+                            location["line"].as_u64().unwrap_or(0),
+                            location["column"].as_u64().unwrap_or(0),
+                        )
+                    })
+                    .collect()
+            });
+        ErrorKey {
+            locations,
+            message: error.to_string(),
+            serialized: value.to_string(),
+        }
+    }
+}
+
+/// Additional information about a query that is returned in the
+/// `extensions` of the result.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryExtensions {
+    /// The cost estimate of a dry run.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost_estimate: Option<QueryCostEstimate>,
+
+    /// Problems with the query that didn't prevent its execution, e.g. that
+    /// it was not executed the way it was asked to be.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warnings: Option<Vec<String>>,
+
+    /// The ID that tags the log messages of the query, which clients can
+    /// refer to when reporting problems with it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query_id: Option<String>,
+}
+
+/// The estimated cost of a query, as returned by a dry run.
+///
+/// Serialized as `{ "complexity", "depth", "storeQueries", "estimatedRows" }`;
+/// clients rely on these fields, so they must not be renamed or removed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryCostEstimate {
+    /// The complexity of the query, as checked against the maximum complexity.
+    pub complexity: u64,
+
+    /// The number of levels of nested fields in the query.
+    pub depth: u8,
+
+    /// An upper bound for the number of store queries needed to resolve the
+    /// query.
+    pub store_queries: u64,
+
+    /// An upper bound for the number of entities loaded from the store,
+    /// based on the `first` arguments of collection fields.
+    pub estimated_rows: u64,
+}
+
 impl From<QueryExecutionError> for QueryResult {
     fn from(e: QueryExecutionError) -> Self {
         let mut result = Self::new(None);
@@ -42,6 +178,111 @@ impl From<Vec<QueryExecutionError>> for QueryResult {
         QueryResult {
             data: None,
             errors: Some(e.into_iter().map(QueryError::from).collect()),
+            extensions: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graphql_parser::Pos;
+
+    fn object_value(fields: Vec<(&str, q::Value)>) -> q::Value {
+        q::Value::Object(
+            fields
+                .into_iter()
+                .map(|(name, value)| (name.to_owned(), value))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn merging_results_combines_data_and_errors() {
+        let mut left = QueryResult::new(Some(object_value(vec![(
+            "status",
+            object_value(vec![("synced", q::Value::Boolean(true))]),
+        )])));
+        left.errors = Some(vec![QueryError::from(QueryExecutionError::Timeout)]);
+        let right = QueryResult::new(Some(object_value(vec![(
+            "status",
+            object_value(vec![("health", q::Value::Enum(String::from("healthy")))]),
+        )])));
+
+        let result = left.merge(right);
+
+        assert_eq!(
+            result.data,
+            Some(object_value(vec![(
+                "status",
+                object_value(vec![
+                    ("synced", q::Value::Boolean(true)),
+                    ("health", q::Value::Enum(String::from("healthy"))),
+                ])
+            )]))
+        );
+        match result.errors.as_ref().map(Vec::as_slice) {
+            Some([QueryError::ExecutionError(QueryExecutionError::Timeout)]) => (),
+            errors => panic!("unexpected errors: {:?}", errors),
+        }
+    }
+
+    #[test]
+    fn merging_results_with_conflicting_data_fails() {
+        let left = QueryResult::new(Some(object_value(vec![(
+            "synced",
+            q::Value::Boolean(true),
+        )])));
+        let right = QueryResult::new(Some(object_value(vec![(
+            "synced",
+            q::Value::Boolean(false),
+        )])));
+
+        let result = left.merge(right);
+
+        assert_eq!(result.data, None);
+        match result.errors.as_ref().map(Vec::as_slice) {
+            Some([QueryError::ExecutionError(QueryExecutionError::MergeConflict(path))]) => {
+                assert_eq!(path, "synced")
+            }
+            errors => panic!("unexpected errors: {:?}", errors),
         }
     }
+
+    #[test]
+    fn dedup_errors_keeps_each_error_once_in_a_fixed_order() {
+        let pos = |line, column| Pos { line, column };
+        let non_null = |line, path: &str| {
+            QueryError::from(QueryExecutionError::NonNullError(
+                pos(line, 5),
+                path.to_owned(),
+            ))
+        };
+        let mut result = QueryResult::new(None);
+        result.errors = Some(vec![
+            non_null(3, "things.owner"),
+            QueryError::from(QueryExecutionError::Timeout),
+            non_null(2, "things.creator"),
+            non_null(3, "things.owner"),
+            non_null(2, "things.creator"),
+            QueryError::from(QueryExecutionError::Timeout),
+        ]);
+
+        result.dedup_errors();
+
+        let messages: Vec<_> = result
+            .errors
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                QueryExecutionError::Timeout.to_string(),
+                String::from("Null value resolved for non-null field `things.creator`"),
+                String::from("Null value resolved for non-null field `things.owner`"),
+            ]
+        );
+    }
 }
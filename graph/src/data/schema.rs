@@ -1,17 +1,212 @@
 use crate::data::graphql::validation::{
-    get_object_type_definitions, validate_interface_implementation, validate_schema,
-    SchemaValidationError,
+    get_object_type_definitions, validate_id_fields, validate_interface_implementation,
+    validate_schema, SchemaValidationError,
 };
+use crate::data::store::ValueType;
 use crate::data::subgraph::SubgraphDeploymentId;
 use failure::Error;
 use graphql_parser;
 use graphql_parser::{
     query::Name,
-    schema::{self, InterfaceType, ObjectType, TypeDefinition},
+    schema::{self, InterfaceType, ObjectType, TypeDefinition, TypeExtension},
     Pos,
 };
+use lazy_static::lazy_static;
 use std::collections::BTreeMap;
+use std::env;
 use std::iter::FromIterator;
+use std::str::FromStr;
+
+lazy_static! {
+    /// Whether interfaces, like entity types, must declare an `id: ID!` field.
+    static ref INTERFACES_REQUIRE_ID: bool = env::var("GRAPH_SCHEMA_INTERFACES_REQUIRE_ID")
+        .map(|value| value == "true")
+        .unwrap_or(false);
+}
+
+/// Directive on the `Query` type that marks a document as an API schema, i.e.
+/// a schema derived from the input schema of a subgraph.
+pub const API_SCHEMA_DIRECTIVE: &str = "apiSchema";
+
+/// Returns the `name` argument of the `@entity` directive of `object_type`,
+/// if there is one.
+fn entity_name_override(object_type: &ObjectType) -> Option<&schema::Value> {
+    object_type
+        .directives
+        .iter()
+        .find(|directive| directive.name == "entity")
+        .and_then(|directive| {
+            directive
+                .arguments
+                .iter()
+                .find(|(name, _)| name == "name")
+                .map(|(_, value)| value)
+        })
+}
+
+/// Returns the entity type under which entities of `object_type` are stored.
+///
+/// This is the name of the type, unless the type was renamed and declares
+/// the name it used to have with `@entity(name: "...")`, so that the stored
+/// entities don't need to be resynced.
+pub fn stored_entity_type(object_type: &ObjectType) -> &str {
+    match entity_name_override(object_type) {
+        Some(schema::Value::String(name)) if !name.is_empty() => name,
+        _ => &object_type.name,
+    }
+}
+
+/// Removes the `implements A & B` clauses of interfaces from `raw`, which
+/// `graphql_parser` can't parse, and returns the remaining schema with the
+/// interfaces each interface implements by interface name. The clauses are
+/// blanked out, so that all definitions keep their positions.
+fn strip_interface_implementations(raw: &str) -> (String, BTreeMap<Name, Vec<Name>>) {
+    #[derive(PartialEq)]
+    enum Token<'a> {
+        Name(&'a str),
+        Punctuator(char),
+        Other,
+    }
+
+    // Split `raw` into tokens with their byte ranges, skipping whitespace,
+    // commas, comments and the contents of strings
+    let bytes = raw.as_bytes();
+    let is_name_byte = |b: u8| b == b'_' || b.is_ascii_alphanumeric();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        let start = i;
+        let token = match bytes[i] {
+            b if b.is_ascii_whitespace() || b == b',' => {
+                i += 1;
+                continue;
+            }
+            b'#' => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+                continue;
+            }
+            b'"' if bytes[i..].starts_with(b"\"\"\"") => {
+                i += 3;
+                while i < bytes.len() && !bytes[i..].starts_with(b"\"\"\"") {
+                    i += if bytes[i..].starts_with(b"\\\"\"\"") {
+                        4
+                    } else {
+                        1
+                    };
+                }
+                i += 3;
+                Token::Other
+            }
+            b'"' => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != b'"' && bytes[i] != b'\n' {
+                    i += if bytes[i] == b'\\' { 2 } else { 1 };
+                }
+                i += 1;
+                Token::Other
+            }
+            b if b == b'_' || b.is_ascii_alphabetic() => {
+                while i < bytes.len() && is_name_byte(bytes[i]) {
+                    i += 1;
+                }
+                Token::Name(&raw[start..i])
+            }
+            b if b == b'-' || b.is_ascii_digit() => {
+                i += 1;
+                while i < bytes.len() && (is_name_byte(bytes[i]) || bytes[i] == b'.') {
+                    i += 1;
+                }
+                Token::Other
+            }
+            _ => {
+                let c = raw[i..].chars().next().unwrap();
+                i += c.len_utf8();
+                Token::Punctuator(c)
+            }
+        };
+        tokens.push((start, i.min(bytes.len()), token));
+    }
+
+    let name_at = |i: usize| match tokens.get(i) {
+        Some((_, _, Token::Name(name))) => Some(*name),
+        _ => None,
+    };
+    let is_ampersand = |i: usize| match tokens.get(i) {
+        Some((_, _, token)) => *token == Token::Punctuator('&'),
+        None => false,
+    };
+
+    let mut stripped = String::with_capacity(raw.len());
+    let mut parents = BTreeMap::<_, Vec<_>>::new();
+    let mut copied = 0;
+    for i in 0..tokens.len() {
+        if name_at(i) != Some("interface")
+            || name_at(i + 1).is_none()
+            || name_at(i + 2) != Some("implements")
+        {
+            continue;
+        }
+
+        // Collect the interfaces in `implements & A & B`, where the leading
+        // `&` is optional
+        let start = tokens[i + 2].0;
+        let mut end = start;
+        let mut interfaces = vec![];
+        let mut j = if is_ampersand(i + 3) { i + 4 } else { i + 3 };
+        while let Some(name) = name_at(j) {
+            interfaces.push(name.to_owned());
+            end = tokens[j].1;
+            if !is_ampersand(j + 1) {
+                break;
+            }
+            j += 2;
+        }
+        if interfaces.is_empty() {
+            continue;
+        }
+
+        // Extensions of an interface can implement more interfaces
+        parents
+            .entry(name_at(i + 1).unwrap().to_owned())
+            .or_default()
+            .extend(interfaces);
+
+        stripped.push_str(&raw[copied..start]);
+        stripped.extend(raw[start..end].chars().map(|c| match c {
+            '\n' | '\r' => c,
+            _ => ' ',
+        }));
+        copied = end;
+    }
+    stripped.push_str(&raw[copied..]);
+    (stripped, parents)
+}
+
+/// A composite index on the fields of an entity type, declared with
+/// `@index(fields: ["a", "b"])` on the type.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EntityIndex {
+    /// The indexed fields, in the order in which they were declared.
+    pub fields: Vec<Name>,
+}
+
+/// How the value of a field that is not stored with its entity is obtained.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VirtualFieldKind {
+    /// Derived from the entities whose `field` references the entity,
+    /// declared with `@derivedFrom(field: "...")`.
+    Derived { field: Name },
+}
+
+/// A field of an entity type that is computed when it is queried rather
+/// than stored, and must therefore never be written.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VirtualField {
+    pub name: Name,
+    pub kind: VirtualFieldKind,
+}
 
 /// A validated and preprocessed GraphQL schema for a subgraph.
 #[derive(Clone, Debug, PartialEq)]
@@ -24,6 +219,16 @@ pub struct Schema {
 
     // Maps an interface name to the list of entities that implement it.
     pub types_for_interface: BTreeMap<Name, Vec<ObjectType>>,
+
+    // Maps an entity type name to the composite indexes declared on it.
+    pub indexes_for_type: BTreeMap<Name, Vec<EntityIndex>>,
+
+    // Maps the name of an entity type that is stored under another name to
+    // that name.
+    pub stored_entity_types: BTreeMap<Name, Name>,
+
+    // The text the schema was parsed from, if it was parsed from a file.
+    pub raw: Option<String>,
 }
 
 impl Schema {
@@ -36,6 +241,9 @@ impl Schema {
             document,
             interfaces_for_type: BTreeMap::new(),
             types_for_interface: BTreeMap::new(),
+            indexes_for_type: BTreeMap::new(),
+            stored_entity_types: BTreeMap::new(),
+            raw: None,
         }
     }
 
@@ -92,23 +300,448 @@ impl Schema {
         return Ok((interfaces_for_type, types_for_interface));
     }
 
+    /// Collects the composite indexes declared with `@index` on entity types,
+    /// making sure that they only index existing scalar fields.
+    pub fn collect_indexes(
+        document: &schema::Document,
+    ) -> Result<BTreeMap<Name, Vec<EntityIndex>>, SchemaValidationError> {
+        fn invalid(object_type: &ObjectType, reason: String) -> SchemaValidationError {
+            SchemaValidationError::IndexInvalid(object_type.name.to_owned(), reason)
+        }
+
+        // Lists and references to other entities can't be part of an index
+        fn is_scalar(enum_types: &[&Name], field_type: &schema::Type) -> bool {
+            match field_type {
+                schema::Type::NamedType(name) => {
+                    enum_types.contains(&name)
+                        || ValueType::from_str(name)
+                            .map(|value_type| value_type != ValueType::List)
+                            .unwrap_or(false)
+                }
+                schema::Type::NonNullType(inner) => is_scalar(enum_types, inner),
+                schema::Type::ListType(_) => false,
+            }
+        }
+
+        let enum_types: Vec<_> = document
+            .definitions
+            .iter()
+            .filter_map(|d| match d {
+                schema::Definition::TypeDefinition(TypeDefinition::Enum(t)) => Some(&t.name),
+                _ => None,
+            })
+            .collect();
+
+        let mut indexes_for_type = BTreeMap::<_, Vec<_>>::new();
+
+        for object_type in get_object_type_definitions(&document) {
+            for directive in object_type
+                .directives
+                .iter()
+                .filter(|directive| directive.name == "index")
+            {
+                let fields = directive
+                    .arguments
+                    .iter()
+                    .find(|(name, _)| name == "fields")
+                    .and_then(|(_, value)| match value {
+                        schema::Value::List(values) if !values.is_empty() => values
+                            .iter()
+                            .map(|value| match value {
+                                schema::Value::String(field) => Some(field.to_owned()),
+                                _ => None,
+                            })
+                            .collect::<Option<Vec<_>>>(),
+                        _ => None,
+                    })
+                    .ok_or_else(|| {
+                        invalid(
+                            object_type,
+                            String::from(
+                                "the @index directive must have a `fields` argument \
+                                 with a non-empty list of field names",
+                            ),
+                        )
+                    })?;
+
+                for field_name in fields.iter() {
+                    let field = object_type
+                        .fields
+                        .iter()
+                        .find(|field| &field.name == field_name)
+                        .ok_or_else(|| {
+                            invalid(
+                                object_type,
+                                format!("field `{}` does not exist", field_name),
+                            )
+                        })?;
+                    if !is_scalar(&enum_types, &field.field_type) {
+                        return Err(invalid(
+                            object_type,
+                            format!(
+                                "field `{}` of type `{}` is not a scalar",
+                                field_name, field.field_type
+                            ),
+                        ));
+                    }
+                }
+
+                indexes_for_type
+                    .entry(object_type.name.clone())
+                    .or_default()
+                    .push(EntityIndex { fields });
+            }
+        }
+
+        Ok(indexes_for_type)
+    }
+
+    /// Makes sure that all `@entity(name: ...)` overrides are valid and that
+    /// no two entity types are stored under the same name, and returns the
+    /// names under which the types with an override are stored.
+    pub fn collect_entity_names(
+        document: &schema::Document,
+    ) -> Result<BTreeMap<Name, Name>, SchemaValidationError> {
+        let mut types_for_stored_name = BTreeMap::new();
+        let mut stored_entity_types = BTreeMap::new();
+
+        for object_type in get_object_type_definitions(&document) {
+            match entity_name_override(object_type) {
+                Some(schema::Value::String(name)) if !name.is_empty() => {
+                    stored_entity_types.insert(object_type.name.clone(), name.clone());
+                }
+                Some(_) => {
+                    return Err(SchemaValidationError::EntityNameInvalid(
+                        object_type.name.to_owned(),
+                    ))
+                }
+                None => (),
+            }
+
+            let stored_name = stored_entity_type(object_type);
+            if let Some(other_type) = types_for_stored_name.insert(stored_name, &object_type.name) {
+                return Err(SchemaValidationError::EntityNameCollision(
+                    stored_name.to_owned(),
+                    other_type.to_owned(),
+                    object_type.name.to_owned(),
+                ));
+            }
+        }
+
+        Ok(stored_entity_types)
+    }
+
+    /// Merges the `extend type`, `extend interface` and `extend enum`
+    /// definitions of `document` into the types they extend. Extensions can
+    /// add fields, enum values, directives and implemented interfaces, but
+    /// not redefine existing fields or values.
+    pub fn merge_extensions(
+        document: schema::Document,
+    ) -> Result<schema::Document, SchemaValidationError> {
+        fn merge<T>(
+            type_name: &Name,
+            items: &mut Vec<T>,
+            new_items: Vec<T>,
+            name: impl Fn(&T) -> &Name,
+        ) -> Result<(), SchemaValidationError> {
+            for item in new_items {
+                if items.iter().any(|existing| name(existing) == name(&item)) {
+                    return Err(SchemaValidationError::ExtensionRedefinition(
+                        type_name.to_owned(),
+                        name(&item).to_owned(),
+                    ));
+                }
+                items.push(item);
+            }
+            Ok(())
+        }
+
+        fn defined_name(type_definition: &TypeDefinition) -> &Name {
+            match type_definition {
+                TypeDefinition::Scalar(t) => &t.name,
+                TypeDefinition::Object(t) => &t.name,
+                TypeDefinition::Interface(t) => &t.name,
+                TypeDefinition::Union(t) => &t.name,
+                TypeDefinition::Enum(t) => &t.name,
+                TypeDefinition::InputObject(t) => &t.name,
+            }
+        }
+
+        fn missing(kind: &str, type_name: &Name) -> SchemaValidationError {
+            SchemaValidationError::ExtendedTypeMissing(kind.to_owned(), type_name.to_owned())
+        }
+
+        let mut definitions = vec![];
+        let mut extensions = vec![];
+        for definition in document.definitions {
+            match definition {
+                schema::Definition::TypeExtension(extension) => extensions.push(extension),
+                definition => definitions.push(definition),
+            }
+        }
+
+        for extension in extensions {
+            let type_name = match &extension {
+                TypeExtension::Object(t) => &t.name,
+                TypeExtension::Interface(t) => &t.name,
+                TypeExtension::Enum(t) => &t.name,
+                TypeExtension::Scalar(t) => &t.name,
+                TypeExtension::Union(t) => &t.name,
+                TypeExtension::InputObject(t) => &t.name,
+            }
+            .to_owned();
+            let extended_type = definitions
+                .iter_mut()
+                .find_map(|definition| match definition {
+                    schema::Definition::TypeDefinition(t) if defined_name(t) == &type_name => {
+                        Some(t)
+                    }
+                    _ => None,
+                });
+
+            match (extension, extended_type) {
+                (TypeExtension::Object(extension), Some(TypeDefinition::Object(object_type))) => {
+                    merge(
+                        &type_name,
+                        &mut object_type.fields,
+                        extension.fields,
+                        |field| &field.name,
+                    )?;
+                    for interface in extension.implements_interfaces {
+                        if !object_type.implements_interfaces.contains(&interface) {
+                            object_type.implements_interfaces.push(interface);
+                        }
+                    }
+                    object_type.directives.extend(extension.directives);
+                }
+                (TypeExtension::Object(_), _) => return Err(missing("type", &type_name)),
+                (
+                    TypeExtension::Interface(extension),
+                    Some(TypeDefinition::Interface(interface_type)),
+                ) => {
+                    merge(
+                        &type_name,
+                        &mut interface_type.fields,
+                        extension.fields,
+                        |field| &field.name,
+                    )?;
+                    interface_type.directives.extend(extension.directives);
+                }
+                (TypeExtension::Interface(_), _) => return Err(missing("interface", &type_name)),
+                (TypeExtension::Enum(extension), Some(TypeDefinition::Enum(enum_type))) => {
+                    merge(
+                        &type_name,
+                        &mut enum_type.values,
+                        extension.values,
+                        |value| &value.name,
+                    )?;
+                    enum_type.directives.extend(extension.directives);
+                }
+                (TypeExtension::Enum(_), _) => return Err(missing("enum", &type_name)),
+                _ => return Err(SchemaValidationError::ExtensionUnsupported(type_name)),
+            }
+        }
+
+        Ok(schema::Document { definitions })
+    }
+
+    /// Resolves interfaces that implement other interfaces, given the
+    /// interfaces each interface implements directly by interface name.
+    /// Afterwards, every interface has the fields of all interfaces it
+    /// implements, directly or through other interfaces, and every object
+    /// type lists all interfaces it implements in its
+    /// `implements_interfaces`, so that a type that implements an interface
+    /// also counts as implementing its parents.
+    ///
+    /// Also returns all interfaces that each interface implements, by
+    /// interface name.
+    pub fn inherit_interfaces(
+        mut document: schema::Document,
+        implemented_interfaces: &BTreeMap<Name, Vec<Name>>,
+    ) -> Result<(schema::Document, BTreeMap<Name, Vec<InterfaceType>>), SchemaValidationError> {
+        fn collect_ancestors(
+            parents: &BTreeMap<Name, Vec<Name>>,
+            interface: &Name,
+            path: &mut Vec<Name>,
+            ancestors: &mut Vec<Name>,
+        ) -> Result<(), SchemaValidationError> {
+            for parent in &parents[interface] {
+                if path.contains(parent) {
+                    return Err(SchemaValidationError::InterfaceCycle(parent.to_owned()));
+                }
+                if !parents.contains_key(parent) {
+                    return Err(SchemaValidationError::UndefinedInterface(parent.to_owned()));
+                }
+                if !ancestors.contains(parent) {
+                    ancestors.push(parent.to_owned());
+                }
+                path.push(parent.to_owned());
+                collect_ancestors(parents, parent, path, ancestors)?;
+                path.pop();
+            }
+            Ok(())
+        }
+
+        let interface_types: BTreeMap<Name, InterfaceType> = document
+            .definitions
+            .iter()
+            .filter_map(|d| match d {
+                schema::Definition::TypeDefinition(TypeDefinition::Interface(t)) => {
+                    Some((t.name.clone(), t.clone()))
+                }
+                _ => None,
+            })
+            .collect();
+        let parents = interface_types
+            .keys()
+            .map(|name| {
+                let parents = implemented_interfaces.get(name).cloned();
+                (name.clone(), parents.unwrap_or_default())
+            })
+            .collect::<BTreeMap<_, _>>();
+        if parents.values().all(|parents| parents.is_empty()) {
+            return Ok((document, BTreeMap::new()));
+        }
+
+        let mut ancestors_for_interface = BTreeMap::new();
+        for name in parents.keys() {
+            let mut ancestors = vec![];
+            collect_ancestors(&parents, name, &mut vec![name.clone()], &mut ancestors)?;
+            ancestors_for_interface.insert(name.clone(), ancestors);
+        }
+
+        for definition in document.definitions.iter_mut() {
+            match definition {
+                schema::Definition::TypeDefinition(TypeDefinition::Interface(interface_type)) => {
+                    let ancestors = &ancestors_for_interface[&interface_type.name];
+                    if ancestors.is_empty() {
+                        continue;
+                    }
+
+                    for ancestor in ancestors {
+                        for field in &interface_types[ancestor].fields {
+                            match interface_type.fields.iter().find(|f| f.name == field.name) {
+                                Some(f) if f.field_type != field.field_type => {
+                                    return Err(SchemaValidationError::InterfaceFieldConflict(
+                                        interface_type.name.clone(),
+                                        ancestor.clone(),
+                                        field.name.clone(),
+                                    ))
+                                }
+                                Some(_) => (),
+                                None => interface_type.fields.push(field.clone()),
+                            }
+                        }
+                    }
+                }
+                schema::Definition::TypeDefinition(TypeDefinition::Object(object_type)) => {
+                    for implemented in object_type.implements_interfaces.clone() {
+                        for ancestor in ancestors_for_interface
+                            .get(&implemented)
+                            .into_iter()
+                            .flatten()
+                        {
+                            if !object_type.implements_interfaces.contains(ancestor) {
+                                object_type.implements_interfaces.push(ancestor.clone());
+                            }
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        // The interfaces with the fields they inherited themselves
+        let interface_types: BTreeMap<&Name, &InterfaceType> = document
+            .definitions
+            .iter()
+            .filter_map(|d| match d {
+                schema::Definition::TypeDefinition(TypeDefinition::Interface(t)) => {
+                    Some((&t.name, t))
+                }
+                _ => None,
+            })
+            .collect();
+        let interfaces_for_interface = ancestors_for_interface
+            .iter()
+            .filter(|(_, ancestors)| !ancestors.is_empty())
+            .map(|(name, ancestors)| {
+                let ancestors = ancestors
+                    .iter()
+                    .map(|ancestor| interface_types[ancestor].clone())
+                    .collect();
+                (name.clone(), ancestors)
+            })
+            .collect();
+
+        Ok((document, interfaces_for_interface))
+    }
+
     pub fn parse(raw: &str, id: SubgraphDeploymentId) -> Result<Self, Error> {
-        let document = graphql_parser::parse_schema(&raw)?;
+        let text = raw.to_owned();
+        let (raw, implemented_interfaces) = strip_interface_implementations(raw);
+        let document = Self::merge_extensions(graphql_parser::parse_schema(&raw)?)?;
+        let (document, interfaces_for_interface) =
+            Self::inherit_interfaces(document, &implemented_interfaces)?;
         validate_schema(&document)?;
 
-        let (interfaces_for_type, types_for_interface) = Self::collect_interfaces(&document)?;
+        let (mut interfaces_for_type, types_for_interface) = Self::collect_interfaces(&document)?;
+        interfaces_for_type.extend(interfaces_for_interface);
+        let indexes_for_type = Self::collect_indexes(&document)?;
+        let stored_entity_types = Self::collect_entity_names(&document)?;
 
         let mut schema = Schema {
             id: id.clone(),
             document,
             interfaces_for_type,
             types_for_interface,
+            indexes_for_type,
+            stored_entity_types,
+            raw: Some(text),
         };
         schema.add_subgraph_id_directives(id);
 
         Ok(schema)
     }
 
+    /// Validates that all entity types declare an `id: ID!` field, and with
+    /// `GRAPH_SCHEMA_INTERFACES_REQUIRE_ID`, all interfaces too. This is only
+    /// checked when a subgraph is deployed, not when the schema of an existing
+    /// deployment is loaded, since older deployments may use other ids.
+    pub fn validate_id_fields(&self) -> Result<(), SchemaValidationError> {
+        validate_id_fields(&self.document, *INTERFACES_REQUIRE_ID)
+    }
+
+    /// Whether this is an API schema rather than the input schema of a
+    /// subgraph.
+    pub fn is_api_schema(&self) -> bool {
+        Self::is_api_document(&self.document)
+    }
+
+    /// Whether `document` is an API schema, i.e. whether its `Query` type is
+    /// marked with the `@apiSchema` directive.
+    pub fn is_api_document(document: &schema::Document) -> bool {
+        get_object_type_definitions(document)
+            .into_iter()
+            .find(|object_type| object_type.name == "Query")
+            .map_or(false, |query_type| {
+                query_type
+                    .directives
+                    .iter()
+                    .any(|directive| directive.name == API_SCHEMA_DIRECTIVE)
+            })
+    }
+
+    /// Returns the entity type under which entities of the object type
+    /// `type_name` are stored, see `stored_entity_type`. Names that are not
+    /// object types of the schema are returned as they are, and so are all
+    /// names for schemas that were not created through `parse`.
+    pub fn stored_entity_type<'a>(&'a self, type_name: &'a str) -> &'a str {
+        self.stored_entity_types
+            .get(type_name)
+            .map_or(type_name, String::as_str)
+    }
+
     /// Returned map has one an entry for each interface in the schema.
     pub fn types_for_interface(&self) -> &BTreeMap<Name, Vec<ObjectType>> {
         &self.types_for_interface
@@ -119,6 +752,101 @@ impl Schema {
         self.interfaces_for_type.get(type_name)
     }
 
+    /// Returns the interfaces that the object type `object` implements, in
+    /// the order in which they are declared. Unlike `interfaces_for_type`,
+    /// this looks them up in the document, and therefore also works for
+    /// schemas that were not created through `parse`. Returns an empty list
+    /// if there is no such object type.
+    pub fn interfaces_for(
+        &self,
+        object: &str,
+    ) -> Result<Vec<&InterfaceType>, SchemaValidationError> {
+        let object_type = match get_object_type_definitions(&self.document)
+            .into_iter()
+            .find(|object_type| object_type.name == object)
+        {
+            Some(object_type) => object_type,
+            None => return Ok(vec![]),
+        };
+
+        object_type
+            .implements_interfaces
+            .iter()
+            .map(|name| {
+                self.document
+                    .definitions
+                    .iter()
+                    .find_map(|def| match def {
+                        schema::Definition::TypeDefinition(TypeDefinition::Interface(i))
+                            if &i.name == name =>
+                        {
+                            Some(i)
+                        }
+                        _ => None,
+                    })
+                    .ok_or_else(|| SchemaValidationError::UndefinedInterface(name.clone()))
+            })
+            .collect()
+    }
+
+    /// Returns all entity types of the schema, together with the composite
+    /// indexes that the store needs to create for them.
+    pub fn entity_types(&self) -> Vec<(&ObjectType, &[EntityIndex])> {
+        get_object_type_definitions(&self.document)
+            .into_iter()
+            .map(|object_type| {
+                let indexes = self
+                    .indexes_for_type
+                    .get(&object_type.name)
+                    .map_or(&[][..], |indexes| indexes.as_slice());
+                (object_type, indexes)
+            })
+            .collect()
+    }
+
+    /// Returns the fields of the entity type `entity` that are not stored,
+    /// in the order in which they are declared. Returns an empty list if
+    /// there is no such entity type.
+    pub fn virtual_fields(&self, entity: &str) -> Vec<VirtualField> {
+        fn argument<'a>(directive: &'a schema::Directive, name: &str) -> Option<&'a schema::Value> {
+            directive
+                .arguments
+                .iter()
+                .find(|(arg, _)| arg == name)
+                .map(|(_, value)| value)
+        }
+
+        let object_type = match get_object_type_definitions(&self.document)
+            .into_iter()
+            .find(|object_type| object_type.name == entity)
+        {
+            Some(object_type) => object_type,
+            None => return vec![],
+        };
+
+        object_type
+            .fields
+            .iter()
+            .filter_map(|field| {
+                let kind = field.directives.iter().find_map(|directive| {
+                    match directive.name.as_str() {
+                        "derivedFrom" => match argument(directive, "field") {
+                            Some(schema::Value::String(field)) => Some(VirtualFieldKind::Derived {
+                                field: field.to_owned(),
+                            }),
+                            _ => None,
+                        },
+                        _ => None,
+                    }
+                })?;
+                Some(VirtualField {
+                    name: field.name.clone(),
+                    kind,
+                })
+            })
+            .collect()
+    }
+
     // Adds a @subgraphId(id: ...) directive to object/interface/enum types in the schema.
     pub fn add_subgraph_id_directives(&mut self, id: SubgraphDeploymentId) {
         for definition in self.document.definitions.iter_mut() {
@@ -171,6 +899,40 @@ fn non_existing_interface() {
     );
 }
 
+#[test]
+fn interfaces_of_object_types() {
+    let schema = "
+        interface RegEntry { id: ID! }
+        type Meme implements RegEntry @entity { id: ID! }
+        type ParamChange implements RegEntry @entity { id: ID! }
+        type Vote @entity { id: ID! }";
+    let schema = Schema::parse(schema, SubgraphDeploymentId::new("dummy").unwrap()).unwrap();
+    let interface_names = |object: &str| -> Vec<String> {
+        schema
+            .interfaces_for(object)
+            .unwrap()
+            .into_iter()
+            .map(|interface| interface.name.clone())
+            .collect()
+    };
+
+    assert_eq!(interface_names("Meme"), vec!["RegEntry"]);
+    assert_eq!(interface_names("ParamChange"), vec!["RegEntry"]);
+    assert!(interface_names("Vote").is_empty());
+    assert!(interface_names("NotAType").is_empty());
+}
+
+#[test]
+fn interfaces_for_fails_on_undefined_interfaces() {
+    let document =
+        graphql_parser::parse_schema("type Bar implements Baz @entity { foo: Int }").unwrap();
+    let schema = Schema::new(SubgraphDeploymentId::new("dummy").unwrap(), document);
+    assert_eq!(
+        schema.interfaces_for("Bar").unwrap_err(),
+        SchemaValidationError::UndefinedInterface("Baz".to_owned())
+    );
+}
+
 #[test]
 fn invalid_interface_implementation() {
     let schema = "
@@ -190,3 +952,528 @@ fn invalid_interface_implementation() {
          required fields: x: Int, y: Int"
     );
 }
+
+#[test]
+fn entity_without_id() {
+    let schema = "type Foo @entity { foo: Int }";
+    let schema = Schema::parse(schema, SubgraphDeploymentId::new("dummy").unwrap())
+        .expect("schemas of existing deployments without `id` should load");
+    assert_eq!(
+        schema.validate_id_fields(),
+        Err(SchemaValidationError::MissingIdField {
+            type_name: "Foo".to_owned()
+        })
+    );
+}
+
+#[test]
+fn entity_with_nullable_id() {
+    let schema = "type Foo @entity { id: ID, foo: Int }";
+    let schema = Schema::parse(schema, SubgraphDeploymentId::new("dummy").unwrap()).unwrap();
+    assert_eq!(
+        schema.validate_id_fields().unwrap_err().to_string(),
+        "Type `Foo` must have a field `id: ID!`"
+    );
+}
+
+#[test]
+fn entity_with_id() {
+    let schema = "
+        interface Named {
+            name: String!
+        }
+
+        type Foo implements Named @entity {
+            id: ID!
+            name: String!
+        }
+    ";
+    let parsed = Schema::parse(schema, SubgraphDeploymentId::new("dummy").unwrap()).unwrap();
+    assert_eq!(parsed.validate_id_fields(), Ok(()));
+
+    // Interfaces only need an `id` if that is required explicitly
+    let document = graphql_parser::parse_schema(schema).unwrap();
+    assert_eq!(
+        validate_id_fields(&document, true),
+        Err(SchemaValidationError::MissingIdField {
+            type_name: "Named".to_owned()
+        })
+    );
+}
+
+#[test]
+fn non_entity_types_without_id() {
+    let schema = "
+        type Query {
+            foo: Foo
+        }
+
+        type Foo @entity {
+            id: ID!
+        }
+    ";
+    let document = graphql_parser::parse_schema(schema).unwrap();
+    assert_eq!(validate_id_fields(&document, false), Ok(()));
+
+    // Root types are exempt even if they are marked as entities
+    let schema = "
+        type Query @entity {
+            foo: Foo
+        }
+
+        type Subscription @entity {
+            foo: Foo
+        }
+
+        type Foo @entity {
+            id: ID!
+        }
+    ";
+    let document = graphql_parser::parse_schema(schema).unwrap();
+    assert_eq!(validate_id_fields(&document, false), Ok(()));
+}
+
+#[test]
+fn entity_with_composite_index() {
+    let schema = r#"
+        enum Status { Open, Closed }
+        type User @entity { id: ID! }
+        type Order @entity @index(fields: ["owner", "createdAt"]) @index(fields: ["status"]) {
+            id: ID!
+            owner: String!
+            createdAt: BigInt!
+            status: Status
+        }"#;
+    let schema = Schema::parse(schema, SubgraphDeploymentId::new("dummy").unwrap()).unwrap();
+
+    let indexes: Vec<_> = schema
+        .entity_types()
+        .into_iter()
+        .map(|(object_type, indexes)| (object_type.name.as_str(), indexes.to_vec()))
+        .collect();
+    assert_eq!(
+        indexes,
+        vec![
+            ("User", vec![]),
+            (
+                "Order",
+                vec![
+                    EntityIndex {
+                        fields: vec!["owner".to_owned(), "createdAt".to_owned()],
+                    },
+                    EntityIndex {
+                        fields: vec!["status".to_owned()],
+                    },
+                ]
+            ),
+        ]
+    );
+}
+
+#[test]
+fn entity_with_invalid_composite_index() {
+    fn index_error(index: &str) -> SchemaValidationError {
+        let schema = format!(
+            "type User @entity {{ id: ID! }}
+             type Order @entity {} {{
+                id: ID!
+                owner: User!
+                tags: [String!]!
+                createdAt: BigInt!
+             }}",
+            index
+        );
+        Schema::parse(&schema, SubgraphDeploymentId::new("dummy").unwrap())
+            .unwrap_err()
+            .downcast::<SchemaValidationError>()
+            .unwrap()
+    }
+
+    assert_eq!(
+        index_error(r#"@index(fields: ["createdAt", "amount"])"#),
+        SchemaValidationError::IndexInvalid(
+            "Order".to_owned(),
+            "field `amount` does not exist".to_owned()
+        )
+    );
+    assert_eq!(
+        index_error(r#"@index(fields: ["owner"])"#),
+        SchemaValidationError::IndexInvalid(
+            "Order".to_owned(),
+            "field `owner` of type `User!` is not a scalar".to_owned()
+        )
+    );
+    assert_eq!(
+        index_error(r#"@index(fields: ["tags", "createdAt"])"#),
+        SchemaValidationError::IndexInvalid(
+            "Order".to_owned(),
+            "field `tags` of type `[String!]!` is not a scalar".to_owned()
+        )
+    );
+    assert_eq!(
+        index_error(r#"@index(fields: [])"#),
+        SchemaValidationError::IndexInvalid(
+            "Order".to_owned(),
+            "the @index directive must have a `fields` argument \
+             with a non-empty list of field names"
+                .to_owned()
+        )
+    );
+}
+
+#[test]
+fn entity_with_name_override() {
+    let schema = r#"
+        type Account @entity(name: "User") { id: ID! }
+        type Order @entity { id: ID! }"#;
+    let schema = Schema::parse(schema, SubgraphDeploymentId::new("dummy").unwrap()).unwrap();
+
+    let stored_names: Vec<_> = get_object_type_definitions(&schema.document)
+        .into_iter()
+        .map(|object_type| (object_type.name.as_str(), stored_entity_type(object_type)))
+        .collect();
+    assert_eq!(stored_names, vec![("Account", "User"), ("Order", "Order")]);
+
+    // Mappings refer to entity types by their GraphQL name
+    assert_eq!(schema.stored_entity_type("Account"), "User");
+    assert_eq!(schema.stored_entity_type("Order"), "Order");
+    assert_eq!(schema.stored_entity_type("Unknown"), "Unknown");
+}
+
+#[test]
+fn entity_with_invalid_name_override() {
+    fn name_error(schema: &str) -> SchemaValidationError {
+        Schema::parse(schema, SubgraphDeploymentId::new("dummy").unwrap())
+            .unwrap_err()
+            .downcast::<SchemaValidationError>()
+            .unwrap()
+    }
+
+    assert_eq!(
+        name_error(r#"type Account @entity(name: "") { id: ID! }"#),
+        SchemaValidationError::EntityNameInvalid("Account".to_owned())
+    );
+    assert_eq!(
+        name_error(
+            r#"type Account @entity(name: "User") { id: ID! }
+               type Profile @entity(name: "User") { id: ID! }"#
+        ),
+        SchemaValidationError::EntityNameCollision(
+            "User".to_owned(),
+            "Account".to_owned(),
+            "Profile".to_owned()
+        )
+    );
+    assert_eq!(
+        name_error(
+            r#"type User @entity { id: ID! }
+               type Account @entity(name: "User") { id: ID! }"#
+        ),
+        SchemaValidationError::EntityNameCollision(
+            "User".to_owned(),
+            "User".to_owned(),
+            "Account".to_owned()
+        )
+    );
+}
+
+#[test]
+fn schema_with_extensions() {
+    let schema = "
+        interface Named { name: String! }
+        enum Status { Open }
+        type User implements Named @entity { id: ID!, name: String! }
+
+        extend interface Named { nickname: String }
+        extend enum Status { Closed }
+        extend type User { nickname: String, status: Status! }
+    ";
+    let schema = Schema::parse(schema, SubgraphDeploymentId::new("dummy").unwrap()).unwrap();
+
+    // Extensions are merged into the types they extend
+    assert_eq!(schema.document.definitions.len(), 3);
+    for definition in schema.document.definitions.iter() {
+        let (type_name, names): (_, Vec<_>) = match definition {
+            schema::Definition::TypeDefinition(TypeDefinition::Object(t)) => {
+                (&t.name, t.fields.iter().map(|f| f.name.as_str()).collect())
+            }
+            schema::Definition::TypeDefinition(TypeDefinition::Interface(t)) => {
+                (&t.name, t.fields.iter().map(|f| f.name.as_str()).collect())
+            }
+            schema::Definition::TypeDefinition(TypeDefinition::Enum(t)) => {
+                (&t.name, t.values.iter().map(|v| v.name.as_str()).collect())
+            }
+            definition => panic!("unexpected definition: {:?}", definition),
+        };
+        let expected = match type_name.as_str() {
+            "Named" => vec!["name", "nickname"],
+            "Status" => vec!["Open", "Closed"],
+            "User" => vec!["id", "name", "nickname", "status"],
+            type_name => panic!("unexpected type: {}", type_name),
+        };
+        assert_eq!(names, expected);
+    }
+}
+
+#[test]
+fn schema_with_invalid_extensions() {
+    fn extension_error(extension: &str) -> SchemaValidationError {
+        let schema = format!(
+            "enum Status {{ Open }}
+             type User @entity {{ id: ID!, name: String! }}
+             {}",
+            extension
+        );
+        Schema::parse(&schema, SubgraphDeploymentId::new("dummy").unwrap())
+            .unwrap_err()
+            .downcast::<SchemaValidationError>()
+            .unwrap()
+    }
+
+    assert_eq!(
+        extension_error("extend type Account { name: String }"),
+        SchemaValidationError::ExtendedTypeMissing("type".to_owned(), "Account".to_owned())
+    );
+    assert_eq!(
+        extension_error("extend interface User { nickname: String }"),
+        SchemaValidationError::ExtendedTypeMissing("interface".to_owned(), "User".to_owned())
+    );
+    assert_eq!(
+        extension_error("extend type User { name: String }"),
+        SchemaValidationError::ExtensionRedefinition("User".to_owned(), "name".to_owned())
+    );
+    assert_eq!(
+        extension_error("extend enum Status { Open }"),
+        SchemaValidationError::ExtensionRedefinition("Status".to_owned(), "Open".to_owned())
+    );
+    assert_eq!(
+        extension_error("extend scalar String @deprecated"),
+        SchemaValidationError::ExtensionUnsupported("String".to_owned())
+    );
+}
+
+#[test]
+fn schema_with_interfaces_implementing_interfaces() {
+    let schema = r#"
+        # interface Commented implements Nothing
+        interface Node {
+            id: ID!
+        }
+
+        """
+        Anything with a name, see "interface Named implements Node"
+        """
+        interface Named implements & Node @deprecated @implements(via: "mixin") {
+            name: String!
+        }
+
+        type User implements Named @entity {
+            id: ID!
+            name: String!
+        }
+
+        type Pet implements Node @entity {
+            id: ID!
+        }"#;
+    let schema = Schema::parse(schema, SubgraphDeploymentId::new("dummy").unwrap()).unwrap();
+    let names = |types: Vec<&Name>| types.into_iter().cloned().collect::<Vec<_>>();
+
+    // `Named` inherits `id` from `Node`, and `User` implements both
+    let named = schema.interfaces_for_type(&"User".to_owned()).unwrap()[0].clone();
+    assert_eq!(named.name, "Named");
+    assert_eq!(
+        names(named.fields.iter().map(|field| &field.name).collect()),
+        vec!["name", "id"]
+    );
+    assert_eq!(
+        names(
+            schema
+                .interfaces_for_type(&"User".to_owned())
+                .unwrap()
+                .iter()
+                .map(|interface| &interface.name)
+                .collect()
+        ),
+        vec!["Named", "Node"]
+    );
+    assert_eq!(
+        names(
+            schema
+                .interfaces_for_type(&"Named".to_owned())
+                .unwrap()
+                .iter()
+                .map(|interface| &interface.name)
+                .collect()
+        ),
+        vec!["Node"]
+    );
+    assert_eq!(
+        names(
+            schema.types_for_interface()["Node"]
+                .iter()
+                .map(|object_type| &object_type.name)
+                .collect()
+        ),
+        vec!["User", "Pet"]
+    );
+    assert_eq!(
+        names(
+            schema.types_for_interface()["Named"]
+                .iter()
+                .map(|object_type| &object_type.name)
+                .collect()
+        ),
+        vec!["User"]
+    );
+
+    // The description, comment and directives are left alone, and the
+    // definitions keep their lines
+    assert_eq!(
+        named.description.as_ref().map(String::as_str),
+        Some("Anything with a name, see \"interface Named implements Node\"")
+    );
+    assert_eq!(
+        names(
+            named
+                .directives
+                .iter()
+                .map(|directive| &directive.name)
+                .collect()
+        ),
+        vec!["deprecated", "implements"]
+    );
+    assert_eq!(named.position.line, 10);
+}
+
+#[test]
+fn schema_with_invalid_interface_implementations() {
+    fn interface_error(interfaces: &str) -> SchemaValidationError {
+        let schema = format!(
+            "{}
+             type User implements Named @entity {{ id: ID!, name: String! }}",
+            interfaces
+        );
+        Schema::parse(&schema, SubgraphDeploymentId::new("dummy").unwrap())
+            .unwrap_err()
+            .downcast::<SchemaValidationError>()
+            .unwrap()
+    }
+
+    assert_eq!(
+        interface_error("interface Named implements Node { id: ID!, name: String! }"),
+        SchemaValidationError::UndefinedInterface("Node".to_owned())
+    );
+    assert_eq!(
+        interface_error(
+            "interface Node implements Named { id: ID! }
+             interface Named implements Node { name: String! }"
+        ),
+        SchemaValidationError::InterfaceCycle("Named".to_owned())
+    );
+    assert_eq!(
+        interface_error(
+            "interface Node { id: ID! }
+             interface Named implements Node { id: String!, name: String! }"
+        ),
+        SchemaValidationError::InterfaceFieldConflict(
+            "Named".to_owned(),
+            "Node".to_owned(),
+            "id".to_owned()
+        )
+    );
+    assert_eq!(
+        interface_error(
+            "interface Node { id: ID!, createdAt: Int! }
+             interface Named implements Node { name: String! }"
+        )
+        .to_string(),
+        "Entity type `User` cannot implement `Named` because it is missing the \
+         required fields: createdAt: Int!"
+    );
+}
+
+#[test]
+fn virtual_fields_are_derived_fields() {
+    let schema = r#"
+        type User @entity {
+            id: ID!
+            name: String!
+            bio: String
+            orders: [Order!]! @derivedFrom(field: "owner")
+        }
+        type Order @entity {
+            id: ID!
+            owner: User!
+        }"#;
+    let schema = Schema::parse(schema, SubgraphDeploymentId::new("dummy").unwrap()).unwrap();
+
+    assert_eq!(
+        schema.virtual_fields("User"),
+        vec![VirtualField {
+            name: "orders".to_owned(),
+            kind: VirtualFieldKind::Derived {
+                field: "owner".to_owned()
+            },
+        }]
+    );
+    assert_eq!(schema.virtual_fields("Order"), vec![]);
+    assert_eq!(schema.virtual_fields("Missing"), vec![]);
+}
+
+#[test]
+fn schema_with_invalid_derived_fields() {
+    fn derived_from_error(order_fields: &str) -> Option<SchemaValidationError> {
+        let schema = format!(
+            r#"type User @entity {{
+                id: ID!
+                name: String!
+                orders: [Order!]! @derivedFrom(field: "owner")
+               }}
+               type Shop @entity {{ id: ID! }}
+               type Order @entity {{
+                id: ID!
+                {}
+               }}"#,
+            order_fields
+        );
+        Schema::parse(&schema, SubgraphDeploymentId::new("dummy").unwrap())
+            .err()
+            .map(|e| e.downcast::<SchemaValidationError>().unwrap())
+    }
+
+    fn invalid(reason: &str) -> Option<SchemaValidationError> {
+        Some(SchemaValidationError::InvalidDerivedFrom {
+            type_name: "User".to_owned(),
+            field: "orders".to_owned(),
+            reason: reason.to_owned(),
+        })
+    }
+
+    assert_eq!(derived_from_error("owner: User!"), None);
+    assert_eq!(
+        derived_from_error("owner: String!"),
+        invalid(
+            "field `owner` on type `Order` must have one of the following types: \
+             User, User!, [User!], [User!]!"
+        )
+    );
+    assert_eq!(
+        derived_from_error("buyer: User!"),
+        invalid("field `owner` does not exist on type `Order`")
+    );
+    assert_eq!(
+        derived_from_error("owner: Shop!"),
+        invalid(
+            "field `owner` on type `Order` must have one of the following types: \
+             User, User!, [User!], [User!]!"
+        )
+    );
+}
+
+#[test]
+fn parsed_schemas_keep_their_text() {
+    let text = "# Tokens\ntype Token @entity {\n  id: ID!\n}\n";
+    let schema = Schema::parse(text, SubgraphDeploymentId::new("dummy").unwrap()).unwrap();
+
+    assert_eq!(schema.raw, Some(text.to_owned()));
+}
@@ -4,6 +4,7 @@ use graphql_parser::query;
 use graphql_parser::schema;
 use serde::de;
 use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
 use std::collections::{BTreeMap, HashMap};
 use std::convert::TryFrom;
 use std::fmt;
@@ -196,6 +197,24 @@ impl Value {
         })
     }
 
+    /// Orders values of the same type, with `Null` before all other values;
+    /// values of different types are unordered. Numbers are ordered by
+    /// their value, strings byte by byte.
+    pub fn compare(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+            (Value::Int(a), Value::Int(b)) => Some(a.cmp(b)),
+            (Value::BigInt(a), Value::BigInt(b)) => Some(a.cmp(b)),
+            (Value::BigDecimal(a), Value::BigDecimal(b)) => Some(a.cmp(b)),
+            (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+            (Value::Bytes(a), Value::Bytes(b)) => Some(a.as_slice().cmp(b.as_slice())),
+            (Value::Null, Value::Null) => Some(Ordering::Equal),
+            (Value::Null, _) => Some(Ordering::Less),
+            (_, Value::Null) => Some(Ordering::Greater),
+            _ => None,
+        }
+    }
+
     pub fn as_string(self) -> Option<String> {
         if let Value::String(s) = self {
             Some(s)
@@ -2,12 +2,15 @@ use ethabi::Contract;
 use failure;
 use failure::{Error, SyncFailure};
 use futures::stream;
+use lazy_static::lazy_static;
 use parity_wasm;
 use parity_wasm::elements::Module;
 use serde::de;
 use serde::ser;
 use serde_yaml;
-use slog::{info, Logger};
+use slog::{info, warn, Logger};
+use std::collections::{BTreeSet, HashSet};
+use std::env;
 use std::fmt;
 use std::ops::Deref;
 use std::str::FromStr;
@@ -15,8 +18,10 @@ use std::sync::Arc;
 use tokio::prelude::*;
 use web3::types::{Address, H256};
 
+use crate::components::ethereum::EventSignature;
 use crate::components::link_resolver::LinkResolver;
 use crate::components::store::StoreError;
+use crate::data::graphql::validation::SchemaValidationError;
 use crate::data::query::QueryExecutionError;
 use crate::data::schema::Schema;
 use crate::data::subgraph::schema::{
@@ -26,12 +31,23 @@ use crate::data::subgraph::schema::{
     EthereumContractMappingEntity, EthereumContractSourceEntity, SUBGRAPHS_ID,
 };
 use crate::prelude::{format_err, Deserialize, Fail, Serialize};
-use crate::util::ethereum::string_to_h256;
+use crate::util::ethereum::{parse_address, string_to_h256, verify_address_checksum, AddressError};
 
 /// Rust representation of the GraphQL schema for a `SubgraphManifest`.
 pub mod schema;
 
-/// Deserialize an Address (with or without '0x' prefix).
+lazy_static! {
+    /// Whether data source addresses with an invalid EIP-55 checksum are
+    /// rejected. By default, only a warning is logged for them.
+    static ref STRICT_ADDRESS_CHECKSUMS: bool =
+        env::var("GRAPH_MANIFEST_STRICT_ADDRESS_CHECKSUMS")
+            .map(|value| value == "true")
+            .unwrap_or(false);
+}
+
+/// Deserialize an Address (with or without '0x' prefix). Subgraphs that are
+/// deployed now must use the prefix, see `validate_source_addresses`, but
+/// existing deployments may not.
 fn deserialize_address<'de, D>(deserializer: D) -> Result<Option<Address>, D::Error>
 where
     D: de::Deserializer<'de>,
@@ -329,10 +345,14 @@ pub enum SubgraphManifestValidationError {
     MultipleEthereumNetworks,
     #[fail(display = "subgraph must have at least one Ethereum network data source")]
     EthereumNetworkRequired,
-    #[fail(display = "subgraph data source has too many similar block handlers")]
-    DataSourceBlockHandlerLimitExceeded,
     #[fail(display = "the specified block must exist on the Ethereum network")]
     BlockNotFound(String),
+    #[fail(display = "cannot derive a GraphQL API from the schema: {}", _0)]
+    InvalidApiSchema(String),
+    #[fail(display = "invalid schema: {}", _0)]
+    SchemaValidationError(SchemaValidationError),
+    #[fail(display = "data source `{}` has an invalid source address: {}", _0, _1)]
+    InvalidSourceAddress(String, AddressError),
 }
 
 #[derive(Fail, Debug)]
@@ -343,6 +363,11 @@ pub enum SubgraphManifestResolveError {
     NonUtf8,
     #[fail(display = "subgraph is not valid YAML")]
     InvalidFormat,
+    #[fail(
+        display = "data source `{}` has an invalid start block `{}`, it must be a block number of 0 (the genesis block) or more",
+        _0, _1
+    )]
+    InvalidStartBlock(String, String),
     #[fail(display = "resolve error: {}", _0)]
     ResolveError(failure::Error),
 }
@@ -393,6 +418,12 @@ pub struct Source {
     pub abi: String,
     #[serde(rename = "startBlock", default)]
     pub start_block: u64,
+    /// Puts all contracts created by this address in scope of the data
+    /// source, as if each of them was its `address`. Used for factories
+    /// that create many contracts, to avoid a data source template per
+    /// created contract.
+    #[serde(default, deserialize_with = "deserialize_address")]
+    pub factory: Option<Address>,
 }
 
 impl From<EthereumContractSourceEntity> for Source {
@@ -401,6 +432,7 @@ impl From<EthereumContractSourceEntity> for Source {
             address: entity.address,
             abi: entity.abi,
             start_block: entity.start_block,
+            factory: entity.factory,
         }
     }
 }
@@ -464,25 +496,122 @@ impl UnresolvedMappingABI {
     }
 }
 
-#[derive(Clone, Debug, Hash, Eq, PartialEq, Deserialize)]
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
 pub struct MappingBlockHandler {
     pub handler: String,
     pub filter: Option<BlockHandlerFilter>,
 }
 
-#[derive(Clone, Debug, Hash, Eq, PartialEq, Deserialize)]
-#[serde(tag = "kind", rename_all = "lowercase")]
+/// A block handler as it is written in the manifest, before its filter kind
+/// has been checked.
+#[derive(Deserialize)]
+struct UncheckedBlockHandler {
+    handler: String,
+    filter: Option<UncheckedBlockHandlerFilter>,
+}
+
+#[derive(Deserialize)]
+struct UncheckedBlockHandlerFilter {
+    kind: String,
+}
+
+impl<'de> de::Deserialize<'de> for MappingBlockHandler {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        use serde::de::Error;
+
+        let UncheckedBlockHandler { handler, filter } = de::Deserialize::deserialize(deserializer)?;
+        let filter = match filter {
+            Some(filter) => Some(BlockHandlerFilter::from_kind(&filter.kind).ok_or_else(|| {
+                D::Error::custom(format!(
+                    "block handler `{}` has an unknown filter kind `{}`",
+                    handler, filter.kind
+                ))
+            })?),
+            None => None,
+        };
+        Ok(MappingBlockHandler { handler, filter })
+    }
+}
+
+impl MappingBlockHandler {
+    /// Checks that the block handlers of a data source don't conflict, i.e.
+    /// that no two of them are triggered by the same kind of block.
+    pub fn check_conflicts(handlers: &[MappingBlockHandler]) -> Result<(), String> {
+        let mut seen: Vec<&MappingBlockHandler> = Vec::with_capacity(handlers.len());
+        for handler in handlers {
+            if let Some(other) = seen.iter().find(|other| other.filter == handler.filter) {
+                return Err(match handler.filter {
+                    Some(filter) => format!(
+                        "block handlers `{}` and `{}` both have a `{}` filter",
+                        other.handler,
+                        handler.handler,
+                        filter.kind()
+                    ),
+                    None => format!(
+                        "block handlers `{}` and `{}` are both triggered by every block",
+                        other.handler, handler.handler
+                    ),
+                });
+            }
+            seen.push(handler);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
 pub enum BlockHandlerFilter {
     // Call filter will trigger on all blocks where the data source contract
     // address has been called
     Call,
+    // Transaction filter will trigger on all blocks containing a transaction
+    // sent to the data source contract address; unlike the call filter, this
+    // does not require traces
+    Transaction,
+}
+
+impl BlockHandlerFilter {
+    /// Parses the `kind` of a block handler filter in the manifest.
+    pub fn from_kind(kind: &str) -> Option<Self> {
+        match kind {
+            "call" => Some(BlockHandlerFilter::Call),
+            "transaction" => Some(BlockHandlerFilter::Transaction),
+            _ => None,
+        }
+    }
+
+    /// The `kind` of the filter as it is written in the manifest.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            BlockHandlerFilter::Call => "call",
+            BlockHandlerFilter::Transaction => "transaction",
+        }
+    }
+}
+
+fn deserialize_block_handlers<'de, D>(deserializer: D) -> Result<Vec<MappingBlockHandler>, D::Error>
+where
+    D: de::Deserializer<'de>,
+{
+    use serde::de::Error;
+
+    let handlers: Vec<MappingBlockHandler> = de::Deserialize::deserialize(deserializer)?;
+    MappingBlockHandler::check_conflicts(&handlers).map_err(D::Error::custom)?;
+    Ok(handlers)
 }
 
 impl From<EthereumBlockHandlerEntity> for MappingBlockHandler {
     fn from(entity: EthereumBlockHandlerEntity) -> Self {
         Self {
             handler: entity.handler,
-            filter: None,
+            // Filters were checked when the manifest was loaded
+            filter: entity
+                .filter
+                .and_then(|filter| filter.kind)
+                .and_then(|kind| BlockHandlerFilter::from_kind(&kind)),
         }
     }
 }
@@ -534,7 +663,7 @@ pub struct UnresolvedMapping {
     pub language: String,
     pub entities: Vec<String>,
     pub abis: Vec<UnresolvedMappingABI>,
-    #[serde(default)]
+    #[serde(default, deserialize_with = "deserialize_block_handlers")]
     pub block_handlers: Vec<MappingBlockHandler>,
     #[serde(default)]
     pub call_handlers: Vec<MappingCallHandler>,
@@ -594,9 +723,9 @@ impl UnresolvedMapping {
             language,
             entities,
             abis,
-            block_handlers: block_handlers.clone(),
-            call_handlers: call_handlers.clone(),
-            event_handlers: event_handlers.clone(),
+            block_handlers,
+            call_handlers,
+            event_handlers,
             runtime,
             link,
         })
@@ -619,6 +748,25 @@ impl From<EthereumContractMappingEntity> for UnresolvedMapping {
     }
 }
 
+/// The position of a data source in the order in which the handlers of
+/// different data sources are run for the same trigger. Data sources from
+/// the manifest come first, in manifest order, followed by dynamic data
+/// sources in the order in which they were created.
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub enum DataSourceOrdinal {
+    /// A data source from the manifest, by its index in `dataSources`.
+    Static(usize),
+    /// A dynamic data source, by the number of the block in which it was
+    /// created and its index among the data sources created in that block.
+    Dynamic(u64, usize),
+}
+
+impl Default for DataSourceOrdinal {
+    fn default() -> Self {
+        DataSourceOrdinal::Static(0)
+    }
+}
+
 #[derive(Clone, Debug, Hash, Eq, PartialEq, Deserialize)]
 pub struct BaseDataSource<M, T> {
     pub kind: String,
@@ -628,6 +776,10 @@ pub struct BaseDataSource<M, T> {
     pub mapping: M,
     #[serde(default)]
     pub templates: Vec<T>, // Deprecated in manifest spec version 0.0.2
+    /// Not part of the manifest; assigned when the manifest is resolved or
+    /// the data source is created from a template.
+    #[serde(skip)]
+    pub ordinal: DataSourceOrdinal,
 }
 
 pub type UnresolvedDataSource = BaseDataSource<UnresolvedMapping, UnresolvedDataSourceTemplate>;
@@ -646,6 +798,7 @@ impl UnresolvedDataSource {
             source,
             mapping,
             templates,
+            ordinal,
         } = self;
 
         info!(logger, "Resolve data source"; "name" => &name, "source" => &source.start_block);
@@ -666,6 +819,7 @@ impl UnresolvedDataSource {
                 source,
                 mapping,
                 templates,
+                ordinal,
             })
     }
 }
@@ -674,6 +828,7 @@ impl DataSource {
     pub fn try_from_template(
         template: DataSourceTemplate,
         params: &Vec<String>,
+        ordinal: DataSourceOrdinal,
     ) -> Result<Self, failure::Error> {
         // Obtain the address from the parameters
         let string = params
@@ -694,6 +849,12 @@ impl DataSource {
             )
         })?;
 
+        // A data source created at some block has nothing to index before it
+        let start_block = match ordinal {
+            DataSourceOrdinal::Dynamic(block_number, _) => block_number,
+            DataSourceOrdinal::Static(_) => 0,
+        };
+
         Ok(DataSource {
             kind: template.kind,
             network: template.network,
@@ -701,10 +862,12 @@ impl DataSource {
             source: Source {
                 address: Some(address),
                 abi: template.source.abi,
-                start_block: 0,
+                start_block,
+                factory: None,
             },
             mapping: template.mapping,
             templates: Vec::new(),
+            ordinal,
         })
     }
 }
@@ -718,6 +881,7 @@ impl From<EthereumContractDataSourceEntity> for UnresolvedDataSource {
             source: entity.source.into(),
             mapping: entity.mapping.into(),
             templates: entity.templates.into_iter().map(Into::into).collect(),
+            ordinal: DataSourceOrdinal::default(),
         }
     }
 }
@@ -783,11 +947,7 @@ impl DataSourceTemplate {
         self.mapping
             .block_handlers
             .iter()
-            .find(|handler| match handler.filter {
-                Some(BlockHandlerFilter::Call) => true,
-                _ => false,
-            })
-            .is_some()
+            .any(|handler| handler.filter == Some(BlockHandlerFilter::Call))
     }
 }
 
@@ -803,6 +963,10 @@ pub struct BaseSubgraphManifest<S, D, T> {
     pub data_sources: Vec<D>,
     #[serde(default)]
     pub templates: Vec<T>,
+    /// The text of the manifest file as it was loaded from IPFS, before the
+    /// `id` and `location` were injected into it.
+    #[serde(skip)]
+    pub raw: Option<String>,
 }
 
 /// Consider two subgraphs to be equal if they come from the same IPLD link.
@@ -827,6 +991,8 @@ impl SubgraphManifest {
     ) -> impl Future<Item = Self, Error = SubgraphManifestResolveError> + Send {
         info!(logger, "Resolve manifest"; "link" => &link.link);
 
+        let resolve_logger = logger.clone();
+
         resolver
             .cat(&logger, &link)
             .map_err(SubgraphManifestResolveError::ResolveError)
@@ -853,13 +1019,16 @@ impl SubgraphManifest {
                         serde_yaml::Value::from(link.link),
                     );
                 }
+                validate_start_blocks(&raw)?;
+
                 // Parse the YAML data into an UnresolvedSubgraphManifest
-                let unresolved: UnresolvedSubgraphManifest = serde_yaml::from_value(raw)?;
+                let mut unresolved: UnresolvedSubgraphManifest = serde_yaml::from_value(raw)?;
+                unresolved.raw = Some(file);
                 Ok(unresolved)
             })
             .and_then(move |unresolved| {
                 unresolved
-                    .resolve(&*resolver, logger)
+                    .resolve(&*resolver, resolve_logger)
                     .map_err(SubgraphManifestResolveError::ResolveError)
             })
     }
@@ -884,14 +1053,151 @@ impl SubgraphManifest {
         }
     }
 
+    /// Checks the source addresses of the data sources as they are written
+    /// in the manifest file, see `validate_source_addresses`. This is only
+    /// done when a subgraph is deployed, so that subgraphs that were deployed
+    /// before addresses were checked this strictly keep starting.
+    pub fn validate_source_addresses(
+        &self,
+        logger: &Logger,
+    ) -> Result<(), SubgraphManifestValidationError> {
+        // The raw manifest was parsed when the manifest was resolved
+        let raw = match self
+            .raw
+            .as_ref()
+            .and_then(|raw| serde_yaml::from_str(raw).ok())
+        {
+            Some(raw) => raw,
+            None => return Ok(()),
+        };
+        validate_source_addresses(&raw, *STRICT_ADDRESS_CHECKSUMS, logger)
+    }
+
+    /// Returns the distinct start blocks of all data sources, in ascending
+    /// order.
     pub fn start_blocks(&self) -> Vec<u64> {
         self.data_sources
             .iter()
             .map(|data_source| data_source.source.start_block)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    /// Returns the earliest start block of all data sources, or `None` if
+    /// there are no data sources.
+    pub fn min_start_block(&self) -> Option<u64> {
+        self.start_blocks().into_iter().next()
+    }
+
+    /// Returns the signatures of the events that the event handlers of the
+    /// data sources listen to. This is a summary of the events in the log
+    /// filter of the subgraph, without the contracts they are emitted by.
+    /// Events of data source templates are not included.
+    pub fn event_signatures(&self) -> HashSet<EventSignature> {
+        self.data_sources
+            .iter()
+            .flat_map(|data_source| data_source.mapping.event_handlers.iter())
+            .map(|handler| handler.topic0())
+            .collect()
+    }
+
+    /// Returns the data sources whose start block comes after `block`,
+    /// e.g. the chain head. These can not be indexed until the chain
+    /// reaches their start block.
+    pub fn data_sources_starting_after(&self, block: u64) -> Vec<&DataSource> {
+        self.data_sources
+            .iter()
+            .filter(|data_source| data_source.source.start_block > block)
             .collect()
     }
 }
 
+/// Checks the `source.address` of all data sources in a raw manifest, so
+/// that a malformed address is reported along with the data source it
+/// belongs to instead of failing somewhere in deserialization. Invalid
+/// checksums are only logged unless `strict_checksums` is set.
+fn validate_source_addresses(
+    raw: &serde_yaml::Value,
+    strict_checksums: bool,
+    logger: &Logger,
+) -> Result<(), SubgraphManifestValidationError> {
+    fn get<'a>(value: &'a serde_yaml::Value, key: &str) -> Option<&'a serde_yaml::Value> {
+        value
+            .as_mapping()
+            .and_then(|mapping| mapping.get(&serde_yaml::Value::from(key)))
+    }
+
+    let data_sources = get(raw, "dataSources").and_then(|value| value.as_sequence());
+    for data_source in data_sources.into_iter().flatten() {
+        // Missing or non-string addresses are left to deserialization
+        let address = match get(data_source, "source")
+            .and_then(|source| get(source, "address"))
+            .and_then(|address| address.as_str())
+        {
+            Some(address) => address,
+            None => continue,
+        };
+        let name = get(data_source, "name")
+            .and_then(|name| name.as_str())
+            .unwrap_or_default();
+
+        parse_address(address).map_err(|e| {
+            SubgraphManifestValidationError::InvalidSourceAddress(name.to_owned(), e)
+        })?;
+
+        if let Err(e) = verify_address_checksum(address) {
+            if strict_checksums {
+                return Err(SubgraphManifestValidationError::InvalidSourceAddress(
+                    name.to_owned(),
+                    e,
+                ));
+            }
+            warn!(
+                logger,
+                "Data source address has an invalid checksum";
+                "data_source" => name,
+                "error" => e.to_string()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Checks the `source.startBlock` of all data sources in a raw manifest,
+/// which must be block numbers. Negative start blocks would otherwise only
+/// surface as an obscure deserialization error.
+fn validate_start_blocks(raw: &serde_yaml::Value) -> Result<(), SubgraphManifestResolveError> {
+    fn get<'a>(value: &'a serde_yaml::Value, key: &str) -> Option<&'a serde_yaml::Value> {
+        value
+            .as_mapping()
+            .and_then(|mapping| mapping.get(&serde_yaml::Value::from(key)))
+    }
+
+    let data_sources = get(raw, "dataSources").and_then(|value| value.as_sequence());
+    for data_source in data_sources.into_iter().flatten() {
+        let start_block =
+            match get(data_source, "source").and_then(|source| get(source, "startBlock")) {
+                Some(start_block) => start_block,
+                None => continue,
+            };
+        if start_block.as_u64().is_none() {
+            let name = get(data_source, "name")
+                .and_then(|name| name.as_str())
+                .unwrap_or_default();
+            return Err(SubgraphManifestResolveError::InvalidStartBlock(
+                name.to_owned(),
+                match start_block {
+                    serde_yaml::Value::Number(number) => number.to_string(),
+                    serde_yaml::Value::String(s) => s.clone(),
+                    value => format!("{:?}", value),
+                },
+            ));
+        }
+    }
+    Ok(())
+}
+
 impl UnresolvedSubgraphManifest {
     pub fn resolve(
         self,
@@ -907,6 +1213,7 @@ impl UnresolvedSubgraphManifest {
             schema,
             data_sources,
             templates,
+            raw,
         } = self;
 
         match semver::Version::parse(&spec_version) {
@@ -930,11 +1237,12 @@ impl UnresolvedSubgraphManifest {
             schema
                 .resolve(id.clone(), resolver, logger.clone())
                 .join(
-                    stream::futures_ordered(
-                        data_sources
-                            .into_iter()
-                            .map(|ds| ds.resolve(resolver, logger.clone())),
-                    )
+                    stream::futures_ordered(data_sources.into_iter().enumerate().map(
+                        |(i, mut ds)| {
+                            ds.ordinal = DataSourceOrdinal::Static(i);
+                            ds.resolve(resolver, logger.clone())
+                        },
+                    ))
                     .collect(),
                 )
                 .join(
@@ -954,7 +1262,340 @@ impl UnresolvedSubgraphManifest {
                     schema,
                     data_sources,
                     templates,
+                    raw,
                 }),
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::iter::FromIterator;
+
+    const LOWERCASE: &str = "0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed";
+    const CHECKSUMMED: &str = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+    const BAD_CHECKSUM: &str = "0x5AaEb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+
+    fn manifest(address: &str) -> serde_yaml::Value {
+        serde_yaml::from_str(&format!(
+            "
+dataSources:
+  - kind: ethereum/contract
+    name: Token
+    source:
+      address: \"{}\"
+      abi: Token
+",
+            address
+        ))
+        .unwrap()
+    }
+
+    fn validate(address: &str, strict_checksums: bool) -> Result<(), String> {
+        let logger = Logger::root(slog::Discard, slog::o!());
+        validate_source_addresses(&manifest(address), strict_checksums, &logger)
+            .map_err(|e| e.to_string())
+    }
+
+    fn validate_start_block(start_block: &str) -> Result<(), String> {
+        let raw = serde_yaml::from_str(&format!(
+            "
+dataSources:
+  - kind: ethereum/contract
+    name: Token
+    source:
+      address: \"{}\"
+      abi: Token
+      startBlock: {}
+",
+            CHECKSUMMED, start_block
+        ))
+        .unwrap();
+        validate_start_blocks(&raw).map_err(|e| e.to_string())
+    }
+
+    fn source(address: &str) -> Source {
+        serde_yaml::from_str(&format!("address: \"{}\"\nabi: Token", address)).unwrap()
+    }
+
+    #[test]
+    fn rejects_address_without_prefix() {
+        assert_eq!(
+            validate(&LOWERCASE[2..], false),
+            Err(format!(
+                "data source `Token` has an invalid source address: \
+                 address `{}` must start with `0x`",
+                &LOWERCASE[2..]
+            ))
+        );
+
+        // Subgraphs deployed with such addresses still load
+        assert_eq!(source(&LOWERCASE[2..]), source(LOWERCASE));
+    }
+
+    #[test]
+    fn rejects_address_with_wrong_length() {
+        assert!(validate(&LOWERCASE[..41], false).is_err());
+        assert!(validate(&format!("{}0", LOWERCASE), false).is_err());
+    }
+
+    #[test]
+    fn bad_checksum_is_an_error_only_if_strict() {
+        assert_eq!(validate(BAD_CHECKSUM, false), Ok(()));
+        assert_eq!(
+            validate(BAD_CHECKSUM, true),
+            Err(format!(
+                "data source `Token` has an invalid source address: \
+                 address `{}` has an invalid EIP-55 checksum, expected `{}`",
+                BAD_CHECKSUM, CHECKSUMMED
+            ))
+        );
+    }
+
+    #[test]
+    fn lowercase_and_checksummed_addresses_are_equivalent() {
+        assert_eq!(validate(LOWERCASE, true), Ok(()));
+        assert_eq!(validate(CHECKSUMMED, true), Ok(()));
+
+        // Filters are built from the parsed source address, so both
+        // spellings watch the same contract
+        assert_eq!(source(LOWERCASE), source(CHECKSUMMED));
+        assert_eq!(
+            source(CHECKSUMMED).address,
+            Some(Address::from_str(&LOWERCASE[2..]).unwrap())
+        );
+    }
+
+    #[test]
+    fn factory_sources_have_no_address() {
+        let factory_source: Source =
+            serde_yaml::from_str(&format!("factory: \"{}\"\nabi: Token", CHECKSUMMED)).unwrap();
+        assert_eq!(factory_source.address, None);
+        assert_eq!(
+            factory_source.factory,
+            Some(Address::from_str(&LOWERCASE[2..]).unwrap())
+        );
+        assert_eq!(source(LOWERCASE).factory, None);
+    }
+
+    fn mapping(block_handlers: &str) -> Result<UnresolvedMapping, String> {
+        serde_yaml::from_str(&format!(
+            "
+kind: ethereum/events
+apiVersion: 0.0.3
+language: wasm/assemblyscript
+entities: []
+abis: []
+file:
+  /: /ipfs/mapping
+blockHandlers:
+{}
+",
+            block_handlers
+        ))
+        .map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn parses_block_handlers() {
+        let mapping = mapping(
+            "
+  - handler: handleBlock
+  - handler: handleCall
+    filter:
+      kind: call
+  - handler: handleTransaction
+    filter:
+      kind: transaction
+",
+        )
+        .unwrap();
+        assert_eq!(
+            mapping.block_handlers,
+            vec![
+                MappingBlockHandler {
+                    handler: String::from("handleBlock"),
+                    filter: None,
+                },
+                MappingBlockHandler {
+                    handler: String::from("handleCall"),
+                    filter: Some(BlockHandlerFilter::Call),
+                },
+                MappingBlockHandler {
+                    handler: String::from("handleTransaction"),
+                    filter: Some(BlockHandlerFilter::Transaction),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_block_handler_filter_kinds() {
+        let error = mapping(
+            "
+  - handler: handleBlock
+    filter:
+      kind: sometimes
+",
+        )
+        .unwrap_err();
+        assert!(
+            error.contains("block handler `handleBlock` has an unknown filter kind `sometimes`"),
+            "unexpected error: {}",
+            error
+        );
+    }
+
+    #[test]
+    fn rejects_conflicting_block_handlers() {
+        let error = mapping(
+            "
+  - handler: handleBlock
+  - handler: handleOtherBlock
+",
+        )
+        .unwrap_err();
+        assert!(
+            error.contains(
+                "block handlers `handleBlock` and `handleOtherBlock` \
+                 are both triggered by every block"
+            ),
+            "unexpected error: {}",
+            error
+        );
+
+        let error = mapping(
+            "
+  - handler: handleCall
+    filter:
+      kind: call
+  - handler: handleBlock
+  - handler: handleOtherCall
+    filter:
+      kind: call
+",
+        )
+        .unwrap_err();
+        assert!(
+            error.contains(
+                "block handlers `handleCall` and `handleOtherCall` both have a `call` filter"
+            ),
+            "unexpected error: {}",
+            error
+        );
+    }
+
+    fn data_source(name: &str, events: &[&str]) -> DataSource {
+        DataSource {
+            kind: String::from("ethereum/contract"),
+            network: Some(String::from("mainnet")),
+            name: name.to_owned(),
+            source: source(CHECKSUMMED),
+            mapping: Mapping {
+                kind: String::from("ethereum/events"),
+                api_version: String::from("0.0.3"),
+                language: String::from("wasm/assemblyscript"),
+                entities: vec![],
+                abis: vec![],
+                block_handlers: vec![],
+                call_handlers: vec![],
+                event_handlers: events
+                    .iter()
+                    .map(|event| MappingEventHandler {
+                        event: event.to_string(),
+                        topic0: None,
+                        handler: String::from("handleEvent"),
+                    })
+                    .collect(),
+                runtime: Arc::new(Module::default()),
+                link: Link::from(String::from("/ipfs/mapping")),
+            },
+            templates: vec![],
+            ordinal: DataSourceOrdinal::default(),
+        }
+    }
+
+    #[test]
+    fn event_signatures_are_the_topics_of_all_event_handlers() {
+        let data_sources = vec![
+            data_source(
+                "Token",
+                &[
+                    "Transfer(address,address,uint256)",
+                    "Approval(address,address,uint256)",
+                ],
+            ),
+            data_source(
+                "OtherToken",
+                &["Transfer(indexed address,indexed address,uint256)"],
+            ),
+            data_source("Blocks", &[]),
+        ];
+        let manifest = SubgraphManifest {
+            id: SubgraphDeploymentId::new("events").unwrap(),
+            location: String::from("/ipfs/events"),
+            spec_version: String::from("0.0.1"),
+            description: None,
+            repository: None,
+            schema: Schema::parse(
+                "type Token @entity { id: ID! }",
+                SubgraphDeploymentId::new("events").unwrap(),
+            )
+            .unwrap(),
+            data_sources,
+            templates: vec![],
+            raw: None,
+        };
+
+        // Indexed parameters don't change the signature of an event, so both
+        // `Transfer` handlers listen to the same event
+        assert_eq!(
+            manifest.event_signatures(),
+            HashSet::from_iter(vec![
+                string_to_h256("Transfer(address,address,uint256)"),
+                string_to_h256("Approval(address,address,uint256)"),
+            ])
+        );
+    }
+
+    #[test]
+    fn start_blocks_must_not_be_before_genesis() {
+        assert_eq!(validate_start_block("0"), Ok(()));
+        assert_eq!(validate_start_block("9000000"), Ok(()));
+        assert_eq!(
+            validate_start_block("-1"),
+            Err(String::from(
+                "data source `Token` has an invalid start block `-1`, \
+                 it must be a block number of 0 (the genesis block) or more"
+            ))
+        );
+        assert!(validate_start_block("latest").is_err());
+    }
+
+    #[test]
+    fn data_sources_from_templates_start_at_their_creation_block() {
+        let DataSource { mapping, .. } = data_source("Pair", &["Swap(address,uint256)"]);
+        let template = DataSourceTemplate {
+            kind: String::from("ethereum/contract"),
+            network: Some(String::from("mainnet")),
+            name: String::from("Pair"),
+            source: TemplateSource {
+                abi: String::from("Pair"),
+            },
+            mapping,
+        };
+
+        let data_source = DataSource::try_from_template(
+            template,
+            &vec![String::from(LOWERCASE)],
+            DataSourceOrdinal::Dynamic(1234, 0),
+        )
+        .unwrap();
+        assert_eq!(data_source.source.start_block, 1234);
+        assert_eq!(
+            data_source.source.address,
+            Some(Address::from_str(&LOWERCASE[2..]).unwrap())
+        );
+    }
+}
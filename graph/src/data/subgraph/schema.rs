@@ -346,6 +346,41 @@ impl SubgraphDeploymentEntity {
     ) -> Vec<MetadataOperation> {
         let mut entity = Entity::new();
         entity.set("failed", failed);
+        if !failed {
+            entity.set("fatalError", Value::Null);
+        }
+
+        vec![update_metadata_operation(
+            Self::TYPENAME,
+            id.as_str(),
+            entity,
+        )]
+    }
+
+    /// Marks the deployment as failed and records the error that made it
+    /// fail, so that queries can report it.
+    pub fn fatal_error_operations(
+        id: &SubgraphDeploymentId,
+        message: String,
+    ) -> Vec<MetadataOperation> {
+        let mut entity = Entity::new();
+        entity.set("failed", true);
+        entity.set("fatalError", message);
+
+        vec![update_metadata_operation(
+            Self::TYPENAME,
+            id.as_str(),
+            entity,
+        )]
+    }
+
+    /// Records that the deployment skips triggers that the Ethereum node
+    /// can't provide, which makes its results non-deterministic. The mark is
+    /// never removed, since the entities the deployment stored while it
+    /// skipped triggers remain in place when it later stops skipping them.
+    pub fn mark_non_deterministic_operations(id: &SubgraphDeploymentId) -> Vec<MetadataOperation> {
+        let mut entity = Entity::new();
+        entity.set("nonDeterministic", true);
 
         vec![update_metadata_operation(
             Self::TYPENAME,
@@ -373,6 +408,7 @@ impl SubgraphDeploymentEntity {
 pub struct SubgraphDeploymentAssignmentEntity {
     node_id: NodeId,
     cost: u64,
+    assigned_at: u64,
 }
 
 impl TypedEntity for SubgraphDeploymentAssignmentEntity {
@@ -381,8 +417,12 @@ impl TypedEntity for SubgraphDeploymentAssignmentEntity {
 }
 
 impl SubgraphDeploymentAssignmentEntity {
-    pub fn new(node_id: NodeId) -> Self {
-        Self { node_id, cost: 1 }
+    pub fn new(node_id: NodeId, assigned_at: u64) -> Self {
+        Self {
+            node_id,
+            cost: 1,
+            assigned_at,
+        }
     }
 
     pub fn write_operations(self, id: &SubgraphDeploymentId) -> Vec<MetadataOperation> {
@@ -390,10 +430,145 @@ impl SubgraphDeploymentAssignmentEntity {
         entity.set("id", id.to_string());
         entity.set("nodeId", self.node_id.to_string());
         entity.set("cost", self.cost);
+        entity.set("assignedAt", self.assigned_at);
         vec![set_metadata_operation(Self::TYPENAME, id.as_str(), entity)]
     }
 }
 
+/// An assignment of a subgraph deployment to a node that has ended, either
+/// because the deployment was reassigned or because it was unassigned.
+#[derive(Debug)]
+pub struct SubgraphDeploymentPastAssignmentEntity {
+    deployment: SubgraphDeploymentId,
+    node_id: String,
+    assigned_at: Option<u64>,
+    unassigned_at: u64,
+}
+
+impl TypedEntity for SubgraphDeploymentPastAssignmentEntity {
+    const TYPENAME: &'static str = "SubgraphDeploymentPastAssignment";
+    type IdType = String;
+}
+
+impl SubgraphDeploymentPastAssignmentEntity {
+    /// Records the end of the assignment of `deployment` that is stored in
+    /// the `SubgraphDeploymentAssignment` entity `assignment`. Assignments
+    /// made before assignment times were tracked have no `assigned_at`.
+    pub fn from_assignment(
+        deployment: SubgraphDeploymentId,
+        assignment: &Entity,
+        unassigned_at: u64,
+    ) -> Option<Self> {
+        let node_id = assignment.get("nodeId")?.clone().as_string()?;
+        let assigned_at = assignment
+            .get("assignedAt")
+            .cloned()
+            .and_then(Value::as_bigint)
+            .map(|assigned_at| assigned_at.to_u64());
+
+        Some(Self {
+            deployment,
+            node_id,
+            assigned_at,
+            unassigned_at,
+        })
+    }
+
+    pub fn write_operations(self) -> Vec<MetadataOperation> {
+        let id = generate_entity_id();
+
+        let mut entity = Entity::new();
+        entity.set("id", id.clone());
+        entity.set("deployment", self.deployment.to_string());
+        entity.set("nodeId", self.node_id);
+        entity.set("assignedAt", self.assigned_at);
+        entity.set("unassignedAt", self.unassigned_at);
+        vec![set_metadata_operation(Self::TYPENAME, id, entity)]
+    }
+}
+
+/// A contract created by the factory of a data source, recorded so that the
+/// contracts a deployment indexes survive a restart without rescanning the
+/// traces of the blocks it has already processed.
+#[derive(Debug)]
+pub struct FactoryChildEntity {
+    deployment: SubgraphDeploymentId,
+    factory: Address,
+    address: Address,
+    ethereum_block_number: u64,
+}
+
+impl TypedEntity for FactoryChildEntity {
+    const TYPENAME: &'static str = "FactoryChild";
+    type IdType = String;
+}
+
+impl FactoryChildEntity {
+    pub fn new(
+        deployment: SubgraphDeploymentId,
+        factory: Address,
+        address: Address,
+        ethereum_block_number: u64,
+    ) -> Self {
+        Self {
+            deployment,
+            factory,
+            address,
+            ethereum_block_number,
+        }
+    }
+
+    /// A query for the factory children of `deployment`.
+    pub fn query_for_deployment(deployment: &SubgraphDeploymentId) -> EntityQuery {
+        Self::query().filter(EntityFilter::new_equal(
+            "deployment",
+            deployment.to_string(),
+        ))
+    }
+
+    /// The contract creation stored in a `FactoryChild` entity.
+    pub fn creation(entity: &Entity) -> Option<EthereumContractCreation> {
+        let address_of = |attr: &str| {
+            entity
+                .get(attr)
+                .cloned()
+                .and_then(Value::as_bytes)
+                .filter(|bytes| bytes.as_slice().len() == 20)
+                .map(|bytes| Address::from_slice(bytes.as_slice()))
+        };
+        Some(EthereumContractCreation {
+            factory: address_of("factory")?,
+            address: address_of("address")?,
+            block_number: entity
+                .get("ethereumBlockNumber")
+                .cloned()
+                .and_then(Value::as_bigint)?
+                .to_u64(),
+        })
+    }
+
+    /// Operations that record the child in the transaction of the block it
+    /// was created in, so that it is reverted along with that block.
+    pub fn write_entity_operations(self) -> Vec<EntityOperation> {
+        // A contract address is only ever created once, so the address
+        // identifies the child within the deployment
+        let id = format!("{}-{:x}", self.deployment, self.address);
+        WriteOperations::write_entity_operations(self, &id)
+    }
+}
+
+impl WriteOperations for FactoryChildEntity {
+    fn generate(self, id: &str, ops: &mut dyn OperationList) {
+        let mut entity = Entity::new();
+        entity.set("id", id);
+        entity.set("deployment", self.deployment.to_string());
+        entity.set("factory", self.factory);
+        entity.set("address", self.address);
+        entity.set("ethereumBlockNumber", self.ethereum_block_number);
+        ops.add(Self::TYPENAME, id.to_owned(), entity);
+    }
+}
+
 #[derive(Debug)]
 pub struct SubgraphManifestEntity {
     spec_version: String,
@@ -402,6 +577,7 @@ pub struct SubgraphManifestEntity {
     schema: String,
     data_sources: Vec<EthereumContractDataSourceEntity>,
     templates: Vec<EthereumContractDataSourceTemplateEntity>,
+    raw: Option<String>,
 }
 
 impl TypedEntity for SubgraphManifestEntity {
@@ -414,6 +590,19 @@ impl SubgraphManifestEntity {
         format!("{}-manifest", subgraph_id)
     }
 
+    /// The manifest and schema texts stored in a `SubgraphManifest` entity.
+    pub fn files(entity: &Entity) -> Result<SubgraphDeploymentFiles, Error> {
+        let manifest = match entity.get("raw") {
+            Some(Value::String(raw)) => Some(raw.clone()),
+            _ => None,
+        };
+        let schema = match entity.get("schema") {
+            Some(Value::String(schema)) => schema.clone(),
+            _ => return Err(format_err!("Schema not present or has wrong type")),
+        };
+        Ok(SubgraphDeploymentFiles { manifest, schema })
+    }
+
     fn write_operations(self, id: &str) -> Vec<MetadataOperation> {
         let mut ops = vec![];
 
@@ -443,6 +632,7 @@ impl SubgraphManifestEntity {
         entity.set("schema", self.schema);
         entity.set("dataSources", data_source_ids);
         entity.set("templates", template_ids);
+        entity.set("raw", self.raw);
 
         ops.push(set_metadata_operation(Self::TYPENAME, id, entity));
 
@@ -456,13 +646,20 @@ impl<'a> From<&'a super::SubgraphManifest> for SubgraphManifestEntity {
             spec_version: manifest.spec_version.clone(),
             description: manifest.description.clone(),
             repository: manifest.repository.clone(),
-            schema: manifest.schema.document.clone().to_string(),
+            // The text of the schema file, so that its IPFS hash can be
+            // checked against the file the manifest links to
+            schema: manifest
+                .schema
+                .raw
+                .clone()
+                .unwrap_or_else(|| manifest.schema.document.to_string()),
             data_sources: manifest.data_sources.iter().map(Into::into).collect(),
             templates: manifest
                 .templates
                 .iter()
                 .map(EthereumContractDataSourceTemplateEntity::from)
                 .collect(),
+            raw: manifest.raw.clone(),
         }
     }
 }
@@ -562,6 +759,7 @@ pub struct DynamicEthereumContractDataSourceEntity {
     deployment: String,
     ethereum_block_hash: H256,
     ethereum_block_number: u64,
+    creation_index: Option<u64>,
     network: Option<String>,
     name: String,
     source: EthereumContractSourceEntity,
@@ -610,6 +808,7 @@ impl WriteOperations for DynamicEthereumContractDataSourceEntity {
         entity.set("deployment", self.deployment);
         entity.set("ethereumBlockHash", self.ethereum_block_hash);
         entity.set("ethereumBlockNumber", self.ethereum_block_number);
+        entity.set("creationIndex", self.creation_index);
         ops.add(Self::TYPENAME, id.to_owned(), entity);
     }
 }
@@ -629,12 +828,17 @@ impl<'a, 'b, 'c>
         ),
     ) -> Self {
         let (deployment_id, data_source, block_ptr) = data;
+        let creation_index = match data_source.ordinal {
+            super::DataSourceOrdinal::Dynamic(_, index) => Some(index as u64),
+            super::DataSourceOrdinal::Static(_) => None,
+        };
 
         Self {
             kind: data_source.kind.clone(),
             deployment: deployment_id.to_string(),
             ethereum_block_hash: block_ptr.hash.clone(),
             ethereum_block_number: block_ptr.number,
+            creation_index,
             name: data_source.name.clone(),
             network: data_source.network.clone(),
             source: data_source.source.clone().into(),
@@ -653,6 +857,7 @@ pub struct EthereumContractSourceEntity {
     pub address: Option<super::Address>,
     pub abi: String,
     pub start_block: u64,
+    pub factory: Option<super::Address>,
 }
 
 impl TypedEntity for EthereumContractSourceEntity {
@@ -667,6 +872,7 @@ impl WriteOperations for EthereumContractSourceEntity {
         entity.set("address", self.address);
         entity.set("abi", self.abi);
         entity.set("startBlock", self.start_block);
+        entity.set("factory", self.factory);
         ops.add(Self::TYPENAME, id.to_owned(), entity);
     }
 }
@@ -677,6 +883,7 @@ impl From<super::Source> for EthereumContractSourceEntity {
             address: source.address,
             abi: source.abi,
             start_block: source.start_block,
+            factory: source.factory,
         }
     }
 }
@@ -695,6 +902,7 @@ impl TryFromValue for EthereumContractSourceEntity {
             address: map.get_optional("address")?,
             abi: map.get_required("abi")?,
             start_block: map.get_optional("startBlock")?.unwrap_or_default(),
+            factory: map.get_optional("factory")?,
         })
     }
 }
@@ -919,15 +1127,11 @@ impl TypedEntity for EthereumBlockHandlerEntity {
 
 impl From<super::MappingBlockHandler> for EthereumBlockHandlerEntity {
     fn from(block_handler: super::MappingBlockHandler) -> Self {
-        let filter = match block_handler.filter {
-            Some(filter) => match filter {
-                // TODO: Figure out how to use serde to get lowercase spelling here
-                super::BlockHandlerFilter::Call => Some(EthereumBlockHandlerFilterEntity {
-                    kind: Some("call".to_string()),
-                }),
-            },
-            None => None,
-        };
+        let filter = block_handler
+            .filter
+            .map(|filter| EthereumBlockHandlerFilterEntity {
+                kind: Some(filter.kind().to_string()),
+            });
         EthereumBlockHandlerEntity {
             handler: block_handler.handler,
             filter: filter,
@@ -40,21 +40,31 @@ pub mod prelude {
 
     pub use crate::components::ethereum::{
         BlockFinality, BlockStream, BlockStreamBuilder, BlockStreamMetrics, ChainHeadUpdate,
-        ChainHeadUpdateListener, ChainHeadUpdateStream, EthereumAdapter, EthereumAdapterError,
-        EthereumBlock, EthereumBlockData, EthereumBlockFilter, EthereumBlockPointer,
-        EthereumBlockTriggerType, EthereumBlockWithCalls, EthereumBlockWithTriggers, EthereumCall,
-        EthereumCallData, EthereumCallFilter, EthereumContractCall, EthereumContractCallError,
+        ChainHeadUpdateListener, ChainHeadUpdateStream, DeploymentSyncStatus, DeploymentSyncStatuses, EthereumAdapter,
+        EthereumAdapterError, EthereumBlock, EthereumBlockData, EthereumBlockFilter,
+        EthereumBlockPointer, EthereumBlockTriggerType, EthereumBlockWithCalls,
+        EthereumBlockWithTriggers, EthereumCall, EthereumCallData, EthereumCallFilter,
+        EthereumContractCall, EthereumContractCallError, EthereumContractCreation,
         EthereumEventData, EthereumLogFilter, EthereumNetworkIdentifier, EthereumTransactionData,
-        EthereumTrigger, LightEthereumBlock, LightEthereumBlockExt, ProviderEthRpcMetrics,
-        SubgraphEthRpcMetrics,
+        EthereumTrigger, FactoryChildren, LightEthereumBlock, LightEthereumBlockExt,
+        NodeCapabilities, ProviderEthRpcMetrics, SubgraphEthRpcMetrics, TriggerCounts, TriggerPreflight,
+        TriggerPreflightRunner,
     };
     pub use crate::components::graphql::{
-        GraphQlRunner, QueryResultFuture, SubscriptionResultFuture,
+        GraphQlRunner, QueryJsonFuture, QueryJsonStream, QueryResultFuture,
+        SubscriptionResultFuture,
     };
     pub use crate::components::link_resolver::{JsonStreamValue, JsonValueStream, LinkResolver};
     pub use crate::components::metrics::{
-        stopwatch::StopwatchMetrics, Collector, Counter, CounterVec, Gauge, GaugeVec, Histogram,
-        HistogramOpts, HistogramVec, MetricsRegistry, Opts, PrometheusError, Registry,
+        handler_stats::{
+            HandlerKind, HandlerStats, HandlerStatsRegistry, InMemoryHandlerStatsRegistry,
+        },
+        indexing_progress::{
+            InMemoryIndexingProgressRegistry, IndexingProgressRegistry, ProgressSample,
+        },
+        stopwatch::StopwatchMetrics,
+        Collector, Counter, CounterVec, Gauge, GaugeVec, Histogram, HistogramOpts, HistogramVec,
+        MetricFamily, MetricsRegistry, Opts, PrometheusError, Registry,
     };
     pub use crate::components::server::admin::JsonRpcServer;
     pub use crate::components::server::index_node::IndexNodeServer;
@@ -63,10 +73,10 @@ pub mod prelude {
     pub use crate::components::server::subscription::SubscriptionServer;
     pub use crate::components::store::{
         AttributeIndexDefinition, ChainStore, EntityCache, EntityChange, EntityChangeOperation,
-        EntityFilter, EntityKey, EntityModification, EntityOperation, EntityOrder, EntityQuery,
-        EntityRange, EthereumCallCache, MetadataOperation, Store, StoreError, StoreEvent,
-        StoreEventStream, StoreEventStreamBox, SubgraphDeploymentStore, TransactionAbortError,
-        SUBSCRIPTION_THROTTLE_INTERVAL,
+        EntityFilter, EntityKey, EntityModification, EntityOperation, EntityOrder,
+        EntityOrderByChild, EntityQuery, EntityRange, EthereumCallCache, MetadataOperation, Store,
+        StoreError, StoreEvent, StoreEventStream, StoreEventStreamBox, SubgraphDeploymentFiles,
+        SubgraphDeploymentStore, TransactionAbortError, SUBSCRIPTION_THROTTLE_INTERVAL,
     };
     pub use crate::components::subgraph::{
         BlockState, DataSourceLoader, DataSourceTemplateInfo, HostMetrics, RuntimeHost,
@@ -75,9 +85,10 @@ pub mod prelude {
     };
     pub use crate::components::{EventConsumer, EventProducer};
 
-    pub use crate::data::graphql::{SerializableValue, TryFromValue, ValueMap};
+    pub use crate::data::graphql::{SerializableValue, TryFromValue, ValueMap, ValueMerge};
     pub use crate::data::query::{
-        Query, QueryError, QueryExecutionError, QueryResult, QueryVariables,
+        InputValueLimitError, InputValueLimits, Query, QueryCostEstimate, QueryError,
+        QueryExecutionError, QueryExtensions, QueryResult, QueryVariables,
     };
     pub use crate::data::schema::Schema;
     pub use crate::data::store::scalar::{BigDecimal, BigInt, BigIntSign};
@@ -87,11 +98,11 @@ pub mod prelude {
     };
     pub use crate::data::subgraph::schema::{SubgraphDeploymentEntity, TypedEntity};
     pub use crate::data::subgraph::{
-        BlockHandlerFilter, CreateSubgraphResult, DataSource, DataSourceTemplate, Link, MappingABI,
-        MappingBlockHandler, MappingCallHandler, MappingEventHandler,
-        SubgraphAssignmentProviderError, SubgraphAssignmentProviderEvent, SubgraphDeploymentId,
-        SubgraphManifest, SubgraphManifestResolveError, SubgraphManifestValidationError,
-        SubgraphName, SubgraphRegistrarError,
+        BlockHandlerFilter, CreateSubgraphResult, DataSource, DataSourceOrdinal,
+        DataSourceTemplate, Link, MappingABI, MappingBlockHandler, MappingCallHandler,
+        MappingEventHandler, SubgraphAssignmentProviderError, SubgraphAssignmentProviderEvent,
+        SubgraphDeploymentId, SubgraphManifest, SubgraphManifestResolveError,
+        SubgraphManifestValidationError, SubgraphName, SubgraphRegistrarError,
     };
     pub use crate::data::subscription::{
         QueryResultStream, Subscription, SubscriptionError, SubscriptionResult,
@@ -107,4 +118,5 @@ pub mod prelude {
     };
     pub use crate::log::split::split_logger;
     pub use crate::util::futures::retry;
+    pub use crate::util::shutdown::{InFlightWork, ShutdownCoordinator};
 }
@@ -1,6 +1,24 @@
 use ethabi::{Contract, Event, Function, ParamType};
-use tiny_keccak::Keccak;
-use web3::types::H256;
+use failure::Fail;
+use std::str::FromStr;
+use tiny_keccak::{keccak256, Keccak};
+use web3::types::{Address, H256};
+
+#[derive(Debug, Fail, PartialEq)]
+pub enum AddressError {
+    #[fail(display = "address `{}` must start with `0x`", _0)]
+    MissingPrefix(String),
+    #[fail(
+        display = "address `{}` must have exactly 40 hex digits after `0x`",
+        _0
+    )]
+    InvalidDigits(String),
+    #[fail(
+        display = "address `{}` has an invalid EIP-55 checksum, expected `{}`",
+        _0, _1
+    )]
+    InvalidChecksum(String, String),
+}
 
 /// Hashes a string to a H256 hash.
 pub fn string_to_h256(s: &str) -> H256 {
@@ -16,6 +34,66 @@ pub fn string_to_h256(s: &str) -> H256 {
     H256::from_slice(&result)
 }
 
+/// Parses a `0x`-prefixed address with exactly 40 hex digits.
+///
+/// The case of the digits doesn't matter for the resulting address; use
+/// `verify_address_checksum` to check mixed-case addresses.
+pub fn parse_address(s: &str) -> Result<Address, AddressError> {
+    let digits = if s.starts_with("0x") {
+        &s[2..]
+    } else {
+        return Err(AddressError::MissingPrefix(s.to_owned()));
+    };
+
+    if digits.len() != 40 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(AddressError::InvalidDigits(s.to_owned()));
+    }
+
+    Address::from_str(&digits.to_lowercase()).map_err(|_| AddressError::InvalidDigits(s.to_owned()))
+}
+
+/// Returns the EIP-55 checksummed representation of an address.
+pub fn checksum_address(address: &Address) -> String {
+    let digits = hex::encode(address.as_bytes());
+    let hash = keccak256(digits.as_bytes());
+
+    let checksummed = digits
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            // Letters are uppercased if the corresponding nibble of the
+            // hash is 8 or higher
+            let nibble = (hash[i / 2] >> (if i % 2 == 0 { 4 } else { 0 })) & 0x0f;
+            if nibble >= 8 {
+                c.to_ascii_uppercase()
+            } else {
+                c
+            }
+        })
+        .collect::<String>();
+    format!("0x{}", checksummed)
+}
+
+/// Verifies the EIP-55 checksum of an address that was parsed with
+/// `parse_address`. All-lowercase and all-uppercase addresses carry no
+/// checksum and are always accepted.
+pub fn verify_address_checksum(s: &str) -> Result<(), AddressError> {
+    let address = parse_address(s)?;
+    let digits = &s[2..];
+    if digits.chars().all(|c| !c.is_ascii_uppercase())
+        || digits.chars().all(|c| !c.is_ascii_lowercase())
+    {
+        return Ok(());
+    }
+
+    let expected = checksum_address(&address);
+    if expected == s {
+        Ok(())
+    } else {
+        Err(AddressError::InvalidChecksum(s.to_owned(), expected))
+    }
+}
+
 /// Returns a `(uint256,address)` style signature for a tuple type.
 fn tuple_signature(components: &Vec<Box<ParamType>>) -> String {
     format!(
@@ -138,3 +216,53 @@ pub fn contract_function_with_signature<'a>(
         !function.constant && target_signature == actual_signature
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksums_addresses() {
+        // Test vectors from EIP-55
+        for checksummed in &[
+            "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+            "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+            "0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB",
+            "0xD1220A0cf47c7B9Be7A2E6BA89F429762e7b9aDb",
+        ] {
+            let lowercase = checksummed.to_lowercase();
+            assert_eq!(
+                parse_address(checksummed).unwrap(),
+                parse_address(&lowercase).unwrap()
+            );
+            assert_eq!(
+                &checksum_address(&parse_address(&lowercase).unwrap()),
+                checksummed
+            );
+            assert_eq!(verify_address_checksum(checksummed), Ok(()));
+            assert_eq!(verify_address_checksum(&lowercase), Ok(()));
+        }
+
+        assert_eq!(
+            verify_address_checksum("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeD"),
+            Err(AddressError::InvalidChecksum(
+                "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeD".to_owned(),
+                "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed".to_owned()
+            ))
+        );
+    }
+
+    #[test]
+    fn parses_only_prefixed_addresses_with_40_digits() {
+        assert!(parse_address("0x0000000000000000000000000000000000000001").is_ok());
+        assert_eq!(
+            parse_address("0000000000000000000000000000000000000001"),
+            Err(AddressError::MissingPrefix(
+                "0000000000000000000000000000000000000001".to_owned()
+            ))
+        );
+        assert!(parse_address("0x0x00000000000000000000000000000000000001").is_err());
+        assert!(parse_address("0x001").is_err());
+        assert!(parse_address("0x000000000000000000000000000000000000000g").is_err());
+    }
+}
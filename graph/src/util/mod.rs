@@ -6,3 +6,6 @@ pub mod ethereum;
 
 /// Security utilities.
 pub mod security;
+
+/// Coordination of a graceful shutdown.
+pub mod shutdown;
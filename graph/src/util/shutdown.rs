@@ -0,0 +1,213 @@
+use futures::future::{self, Shared};
+use futures::sync::oneshot;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::timer::Delay;
+
+use crate::prelude::*;
+
+struct State {
+    /// The time by which in-flight work has to be done; set once the
+    /// shutdown has started.
+    deadline: Option<Instant>,
+
+    /// Fires the shutdown signal; taken when the shutdown starts.
+    trigger: Option<oneshot::Sender<()>>,
+
+    /// The number of queries and block streams that haven't finished yet.
+    in_flight: usize,
+
+    /// Drains waiting for the last in-flight work to finish.
+    drained: Vec<oneshot::Sender<()>>,
+}
+
+struct Inner {
+    grace_period: Duration,
+    signal: Shared<oneshot::Receiver<()>>,
+    state: Mutex<State>,
+    in_flight_gauge: Box<Gauge>,
+}
+
+/// Coordinates a graceful shutdown of the node.
+///
+/// Queries and block streams register as in-flight work while they run.
+/// Once the shutdown starts, new work is turned away, block streams are
+/// signaled to stop after the block they are processing, and running
+/// queries get a grace period to finish.
+#[derive(Clone)]
+pub struct ShutdownCoordinator {
+    inner: Arc<Inner>,
+}
+
+impl ShutdownCoordinator {
+    /// Creates a coordinator that gives in-flight work `grace_period` to
+    /// finish once the shutdown starts.
+    pub fn new<M: MetricsRegistry>(registry: Arc<M>, grace_period: Duration) -> Self {
+        let in_flight_gauge = registry
+            .new_gauge(
+                String::from("shutdown_in_flight_work"),
+                String::from("Counts the queries and block streams a shutdown waits for"),
+                HashMap::new(),
+            )
+            .expect("failed to create `shutdown_in_flight_work` gauge");
+        Self::with_gauge(in_flight_gauge, grace_period)
+    }
+
+    fn with_gauge(in_flight_gauge: Box<Gauge>, grace_period: Duration) -> Self {
+        let (trigger, signal) = oneshot::channel();
+        ShutdownCoordinator {
+            inner: Arc::new(Inner {
+                grace_period,
+                signal: signal.shared(),
+                state: Mutex::new(State {
+                    deadline: None,
+                    trigger: Some(trigger),
+                    in_flight: 0,
+                    drained: vec![],
+                }),
+                in_flight_gauge,
+            }),
+        }
+    }
+
+    /// Registers work that a shutdown waits for while the returned guard is
+    /// held. Returns `None` once the shutdown has started.
+    pub fn begin_work(&self) -> Option<InFlightWork> {
+        let mut state = self.inner.state.lock().unwrap();
+        if state.deadline.is_some() {
+            return None;
+        }
+        state.in_flight += 1;
+        self.inner.in_flight_gauge.set(state.in_flight as f64);
+        Some(InFlightWork {
+            inner: self.inner.clone(),
+        })
+    }
+
+    /// The time by which in-flight work has to be done, if the shutdown has
+    /// started.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.inner.state.lock().unwrap().deadline
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.deadline().is_some()
+    }
+
+    /// The number of queries and block streams that haven't finished yet.
+    pub fn in_flight(&self) -> usize {
+        self.inner.state.lock().unwrap().in_flight
+    }
+
+    /// Resolves once the shutdown starts.
+    pub fn signal(&self) -> Box<dyn Future<Item = (), Error = ()> + Send> {
+        Box::new(self.inner.signal.clone().then(|_| Ok(())))
+    }
+
+    /// Starts the shutdown and resolves once all in-flight work is done or
+    /// the grace period is over, whichever comes first. Calling this again
+    /// waits for the same deadline.
+    pub fn shutdown(&self) -> Box<dyn Future<Item = (), Error = ()> + Send> {
+        let mut state = self.inner.state.lock().unwrap();
+        let grace_period = self.inner.grace_period;
+        let deadline = *state
+            .deadline
+            .get_or_insert_with(|| Instant::now() + grace_period);
+        if let Some(trigger) = state.trigger.take() {
+            let _ = trigger.send(());
+        }
+
+        if state.in_flight == 0 {
+            return Box::new(future::ok(()));
+        }
+        let (sender, receiver) = oneshot::channel();
+        state.drained.push(sender);
+        Box::new(
+            receiver
+                .map_err(|_| ())
+                .select(Delay::new(deadline).map_err(|_| ()))
+                .then(|_| Ok(())),
+        )
+    }
+}
+
+impl fmt::Debug for ShutdownCoordinator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = self.inner.state.lock().unwrap();
+        f.debug_struct("ShutdownCoordinator")
+            .field("deadline", &state.deadline)
+            .field("in_flight", &state.in_flight)
+            .finish()
+    }
+}
+
+/// Work that a shutdown waits for until the guard is dropped.
+pub struct InFlightWork {
+    inner: Arc<Inner>,
+}
+
+impl Drop for InFlightWork {
+    fn drop(&mut self) {
+        let mut state = self.inner.state.lock().unwrap();
+        state.in_flight -= 1;
+        self.inner.in_flight_gauge.set(state.in_flight as f64);
+        if state.in_flight == 0 {
+            for sender in state.drained.drain(..) {
+                let _ = sender.send(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn coordinator(grace_period: Duration) -> ShutdownCoordinator {
+        ShutdownCoordinator::with_gauge(
+            Box::new(Gauge::new("shutdown_in_flight_work", "test").unwrap()),
+            grace_period,
+        )
+    }
+
+    #[test]
+    fn shutdown_waits_for_in_flight_work() {
+        let coordinator = coordinator(Duration::from_secs(30));
+        let signal = coordinator.signal();
+        let work = coordinator.begin_work().expect("work is accepted");
+        assert!(!coordinator.is_shutting_down());
+
+        let start = Instant::now();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            drop(work);
+        });
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(coordinator.shutdown()).unwrap();
+
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        assert!(start.elapsed() < Duration::from_secs(30));
+        assert_eq!(coordinator.in_flight(), 0);
+        assert_eq!(coordinator.inner.in_flight_gauge.get(), 0.0);
+        assert!(signal.wait().is_ok());
+        assert!(coordinator.begin_work().is_none());
+    }
+
+    #[test]
+    fn shutdown_gives_up_after_grace_period() {
+        let coordinator = coordinator(Duration::from_millis(100));
+        let _work = coordinator.begin_work().expect("work is accepted");
+        assert_eq!(coordinator.inner.in_flight_gauge.get(), 1.0);
+
+        let start = Instant::now();
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(coordinator.shutdown()).unwrap();
+
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        assert_eq!(coordinator.in_flight(), 1);
+        assert!(coordinator.deadline().unwrap() <= Instant::now());
+    }
+}
@@ -0,0 +1,76 @@
+use std::collections::HashSet;
+
+use graph::prelude::SubgraphDeploymentId;
+
+/// Whether a field may be accessed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Decision {
+    Allow,
+    Deny,
+}
+
+/// Decides which types and fields of a deployment queries and subscriptions
+/// may select. Selections are checked when the query is validated, before
+/// anything is loaded from the store, so that denied fields are rejected
+/// without revealing any data.
+pub trait Authorizer: Send + Sync {
+    /// Whether the field `field_name` of the type `type_name` in the schema
+    /// of `deployment` may be accessed.
+    fn can_access(
+        &self,
+        deployment: &SubgraphDeploymentId,
+        type_name: &str,
+        field_name: &str,
+    ) -> Decision;
+}
+
+/// An authorizer that allows access to everything.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AllowAll;
+
+impl Authorizer for AllowAll {
+    fn can_access(&self, _: &SubgraphDeploymentId, _: &str, _: &str) -> Decision {
+        Decision::Allow
+    }
+}
+
+/// An authorizer that denies access to a fixed set of types and fields in
+/// all deployments. Denying a type denies access to all of its fields.
+#[derive(Clone, Debug, Default)]
+pub struct StaticAuthorizer {
+    denied_types: HashSet<String>,
+    denied_fields: HashSet<(String, String)>,
+}
+
+impl StaticAuthorizer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Denies access to all fields of the type `type_name`.
+    pub fn deny_type(mut self, type_name: &str) -> Self {
+        self.denied_types.insert(type_name.to_owned());
+        self
+    }
+
+    /// Denies access to the field `field_name` of the type `type_name`.
+    pub fn deny_field(mut self, type_name: &str, field_name: &str) -> Self {
+        self.denied_fields
+            .insert((type_name.to_owned(), field_name.to_owned()));
+        self
+    }
+}
+
+impl Authorizer for StaticAuthorizer {
+    fn can_access(&self, _: &SubgraphDeploymentId, type_name: &str, field_name: &str) -> Decision {
+        if self.denied_types.contains(type_name)
+            || self
+                .denied_fields
+                .contains(&(type_name.to_owned(), field_name.to_owned()))
+        {
+            Decision::Deny
+        } else {
+            Decision::Allow
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use graph::prelude::*;
+
+/// Metrics that resolvers update while executing queries.
+pub struct QueryMetrics {
+    store_query_duration: Box<HistogramVec>,
+    entities_loaded: Box<CounterVec>,
+}
+
+impl QueryMetrics {
+    pub fn new<M: MetricsRegistry>(registry: Arc<M>) -> Self {
+        let store_query_duration = registry
+            .new_histogram_vec(
+                String::from("query_store_query_duration"),
+                String::from("Measures the time store queries of GraphQL queries take"),
+                HashMap::new(),
+                vec![String::from("deployment")],
+                vec![0.001, 0.01, 0.1, 1.0, 10.0],
+            )
+            .expect("failed to create `query_store_query_duration` histogram");
+        let entities_loaded = registry
+            .new_counter_vec(
+                String::from("query_entities_loaded"),
+                String::from("Counts the entities GraphQL queries load from the store"),
+                HashMap::new(),
+                vec![String::from("deployment")],
+            )
+            .expect("failed to create `query_entities_loaded` counter");
+        QueryMetrics {
+            store_query_duration,
+            entities_loaded,
+        }
+    }
+
+    /// Records a store query for `subgraph_id` that took `duration` and
+    /// loaded `entities` entities.
+    pub fn observe_store_query(
+        &self,
+        subgraph_id: &SubgraphDeploymentId,
+        duration: Duration,
+        entities: usize,
+    ) {
+        self.store_query_duration
+            .with_label_values(&[subgraph_id.as_str()])
+            .observe(duration.as_secs_f64());
+        self.entities_loaded
+            .with_label_values(&[subgraph_id.as_str()])
+            .inc_by(entities as f64);
+    }
+}
+
+/// State that lives for a single query execution and is shared by all
+/// resolver calls made while executing it.
+///
+/// Besides the logger, the limits and the metrics of the execution, resolvers
+/// can store arbitrary values in the context, keyed by their type. This
+/// allows them to compute something once per query and reuse it across
+/// fields.
+pub struct QueryContext {
+    /// The logger to use.
+    pub logger: Logger,
+
+    /// The ID of the query, which is also in the logger. Set when the
+    /// execution of the query starts, so that resolvers can pass it on to
+    /// the store and to queries they make themselves.
+    pub query_id: Option<String>,
+
+    /// Time at which the query times out.
+    pub deadline: Option<Instant>,
+
+    /// Max value for `first`.
+    pub max_first: u32,
+
+    /// Max number of values in an `_in` or `_not_in` filter.
+    pub max_in_list_length: usize,
+
+    /// Queries with an `_in` filter with more values than this are split
+    /// into several store queries, each with a chunk of the values.
+    pub in_list_chunk_size: usize,
+
+    /// If set, `execute_query_to_writer` streams the entities of queries
+    /// that consist of a single collection field in batches of this size.
+    pub stream_batch_size: Option<usize>,
+
+    /// Values of `Int` fields outside of the range of `Int` fail the field
+    /// unless this is set, in which case they are clamped to the range and
+    /// a warning is logged.
+    pub clamp_int_overflow: bool,
+
+    /// Queries that ask for a warning about deployments that are still
+    /// syncing get one if the deployment is more than this many blocks
+    /// behind the chain head.
+    pub sync_warning_blocks: u64,
+
+    /// If set, the query counts as in-flight work of a shutdown; it isn't
+    /// run once the shutdown has started, and is cut short when the grace
+    /// period of the shutdown is over.
+    pub shutdown: Option<ShutdownCoordinator>,
+
+    /// Where resolvers record metrics, if anywhere.
+    pub metrics: Option<Arc<QueryMetrics>>,
+
+    /// Values stored by resolvers, keyed by their type.
+    extensions: Mutex<HashMap<TypeId, Arc<dyn Any + Send + Sync>>>,
+}
+
+impl QueryContext {
+    pub fn new(logger: Logger, deadline: Option<Instant>, max_first: u32) -> Self {
+        QueryContext {
+            logger,
+            query_id: None,
+            deadline,
+            max_first,
+            max_in_list_length: 5000,
+            in_list_chunk_size: 1000,
+            stream_batch_size: None,
+            clamp_int_overflow: false,
+            sync_warning_blocks: 1000,
+            shutdown: None,
+            metrics: None,
+            extensions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Fails if the query ran past its deadline, or past the grace period of
+    /// a shutdown that started while it was running.
+    pub fn check_deadline(&self) -> Result<(), QueryExecutionError> {
+        let now = Instant::now();
+        match self.deadline {
+            Some(deadline) if deadline < now => return Err(QueryExecutionError::Timeout),
+            _ => (),
+        }
+        match self
+            .shutdown
+            .as_ref()
+            .and_then(|shutdown| shutdown.deadline())
+        {
+            Some(deadline) if deadline < now => Err(QueryExecutionError::ShuttingDown),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the value of type `T` stored in the context, if there is one.
+    pub fn extension<T>(&self) -> Option<Arc<T>>
+    where
+        T: Any + Send + Sync,
+    {
+        self.extensions
+            .lock()
+            .unwrap()
+            .get(&TypeId::of::<T>())
+            .cloned()
+            .map(|value| value.downcast::<T>().unwrap())
+    }
+
+    /// Stores `value` in the context, replacing any value of the same type.
+    pub fn insert_extension<T>(&self, value: T)
+    where
+        T: Any + Send + Sync,
+    {
+        self.extensions
+            .lock()
+            .unwrap()
+            .insert(TypeId::of::<T>(), Arc::new(value));
+    }
+
+    /// Returns the value of type `T` stored in the context, computing and
+    /// storing it with `f` if there is none yet.
+    pub fn extension_or_insert_with<T, F>(&self, f: F) -> Arc<T>
+    where
+        T: Any + Send + Sync,
+        F: FnOnce() -> T,
+    {
+        if let Some(value) = self.extension::<T>() {
+            return value;
+        }
+
+        // Compute the value without holding the lock, so that `f` can use
+        // the context itself
+        let value: Arc<dyn Any + Send + Sync> = Arc::new(f());
+        self.extensions
+            .lock()
+            .unwrap()
+            .entry(TypeId::of::<T>())
+            .or_insert(value)
+            .clone()
+            .downcast::<T>()
+            .unwrap()
+    }
+}
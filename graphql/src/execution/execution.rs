@@ -1,10 +1,13 @@
 use graphql_parser::query as q;
 use graphql_parser::schema as s;
+use graphql_parser::Pos;
 use indexmap::IndexMap;
 use std::cmp;
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::iter;
 use std::ops::Deref;
-use std::time::Instant;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use graph::data::graphql::validation::get_base_type;
 use graph::prelude::*;
@@ -12,6 +15,7 @@ use graph::prelude::*;
 use crate::introspection::INTROSPECTION_DOCUMENT;
 use crate::prelude::*;
 use crate::query::ast as qast;
+use crate::schema::api::META_FIELD;
 use crate::schema::ast as sast;
 use crate::values::coercion;
 
@@ -21,8 +25,8 @@ pub struct ExecutionContext<'a, R>
 where
     R: Resolver,
 {
-    /// The logger to use.
-    pub logger: Logger,
+    /// State of the query execution that is shared with the resolver.
+    pub query: Arc<QueryContext>,
 
     /// The schema to execute the query against.
     pub schema: Arc<Schema>,
@@ -39,11 +43,57 @@ where
     /// Variable values.
     pub variable_values: Arc<HashMap<q::Name, q::Value>>,
 
-    /// Time at which the query times out.
-    pub deadline: Option<Instant>,
+    /// The approximate size of the response built so far.
+    pub response_size: Arc<ResponseSize>,
 
-    /// Max value for `first`.
-    pub max_first: u32,
+    /// Decides which fields the query may select.
+    pub authorizer: Arc<dyn Authorizer>,
+}
+
+/// Tracks the approximate serialized size of a response while it is being
+/// built, so that execution can stop once the response grows too large.
+///
+/// The size is the sum of the lengths of all keys and scalar values plus
+/// the punctuation of objects and lists.
+#[derive(Debug, Default)]
+pub struct ResponseSize {
+    bytes: AtomicUsize,
+    max: Option<usize>,
+}
+
+impl ResponseSize {
+    pub fn new(max: Option<usize>) -> Self {
+        ResponseSize {
+            bytes: AtomicUsize::new(0),
+            max,
+        }
+    }
+
+    fn add(&self, bytes: usize) {
+        self.bytes.fetch_add(bytes, Ordering::SeqCst);
+    }
+
+    fn add_value(&self, value: &q::Value) {
+        let bytes = match value {
+            q::Value::Null => 4,
+            q::Value::Boolean(b) => b.to_string().len(),
+            q::Value::Int(n) => n.as_i64().map_or(0, |n| n.to_string().len()),
+            q::Value::Float(n) => n.to_string().len(),
+            q::Value::String(s) | q::Value::Enum(s) | q::Value::Variable(s) => s.len() + 2,
+            // Lists and objects are accounted for as they are completed
+            q::Value::List(_) | q::Value::Object(_) => 0,
+        };
+        self.add(bytes)
+    }
+
+    /// Returns an error if the response has grown beyond the limit.
+    pub fn check(&self) -> Result<(), QueryExecutionError> {
+        let bytes = self.bytes.load(Ordering::SeqCst);
+        match self.max {
+            Some(max) if bytes > max => Err(QueryExecutionError::ResponseTooLarge { bytes, max }),
+            _ => Ok(()),
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -82,20 +132,29 @@ where
         ctx
     }
 
+    /// The response keys of the field stack, e.g. `allUsers.friends.name`.
+    pub fn response_path(&self) -> String {
+        self.fields
+            .iter()
+            .map(|field| qast::get_response_key(field).as_str())
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+
     pub fn as_introspection_context(&self) -> ExecutionContext<IntrospectionResolver> {
         // Create an introspection type store and resolver
         let introspection_schema = introspection_schema(self.schema.id.clone());
-        let introspection_resolver = IntrospectionResolver::new(&self.logger, &self.schema);
+        let introspection_resolver = IntrospectionResolver::new(&self.query.logger, &self.schema);
 
         ExecutionContext {
-            logger: self.logger.clone(),
+            query: self.query.clone(),
             resolver: Arc::new(introspection_resolver),
             schema: Arc::new(introspection_schema),
             document: &self.document,
             fields: vec![],
             variable_values: self.variable_values.clone(),
-            deadline: self.deadline,
-            max_first: std::u32::MAX,
+            response_size: self.response_size.clone(),
+            authorizer: self.authorizer.clone(),
         }
     }
 
@@ -179,10 +238,16 @@ where
                                 _ => None,
                             })
                             .unwrap_or(100);
+
+                        // Long `_in` lists make the query harder for the
+                        // store, however few entities it returns
                         max_entities
                             .checked_add(
                                 max_entities.checked_mul(field_complexity).ok_or(Overflow)?,
                             )
+                            .and_then(|complexity| {
+                                complexity.checked_add(self.in_list_length(field))
+                            })
                             .ok_or(Overflow)
                     }
                     q::Selection::FragmentSpread(fragment) => {
@@ -206,6 +271,329 @@ where
             })
     }
 
+    /// The number of values passed to the `_in` and `_not_in` filters in
+    /// the `where` argument of `field`.
+    fn in_list_length(&self, field: &q::Field) -> u64 {
+        fn resolve<'v>(
+            value: &'v q::Value,
+            variables: &'v HashMap<q::Name, q::Value>,
+        ) -> Option<&'v q::Value> {
+            match value {
+                q::Value::Variable(name) => variables.get(name),
+                value => Some(value),
+            }
+        }
+
+        let variables = self.variable_values.as_ref();
+        match qast::get_argument_value(&field.arguments, "where")
+            .and_then(|value| resolve(value, variables))
+        {
+            Some(q::Value::Object(filters)) => filters
+                .iter()
+                .filter(|(name, _)| name.ends_with("_in"))
+                .map(|(_, value)| match resolve(value, variables) {
+                    Some(q::Value::List(values)) => values.len() as u64,
+                    _ => 0,
+                })
+                .sum(),
+            _ => 0,
+        }
+    }
+
+    /// Fails with `QueryExecutionError::TooManyFields` if a selection set of
+    /// the query has more than `max_fields` fields.
+    ///
+    /// Fields are counted by their response key, after expanding fragments
+    /// regardless of their type condition, and the selection sets of fields
+    /// with the same response key are counted together since they are merged
+    /// during execution. Selection sets nested deeper than `max_depth` are
+    /// not checked; such queries are too deep anyway.
+    pub(crate) fn check_fields_per_selection(
+        &self,
+        root_selection_set: &q::SelectionSet,
+        max_fields: Option<usize>,
+        max_depth: u8,
+    ) -> Result<(), QueryExecutionError> {
+        match max_fields {
+            Some(max_fields) => {
+                self.check_merged_selection_sets(&[root_selection_set], max_fields, max_depth, 0)
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn check_merged_selection_sets(
+        &self,
+        selection_sets: &[&q::SelectionSet],
+        max_fields: usize,
+        max_depth: u8,
+        depth: u8,
+    ) -> Result<(), QueryExecutionError> {
+        if depth >= max_depth {
+            return Ok(());
+        }
+
+        let mut fields = BTreeMap::new();
+        let mut visited_fragments = HashSet::new();
+        for selection_set in selection_sets {
+            self.collect_selection_sets(selection_set, &mut fields, &mut visited_fragments);
+        }
+
+        if fields.len() > max_fields {
+            return Err(QueryExecutionError::TooManyFields {
+                found: fields.len(),
+                max: max_fields,
+            });
+        }
+
+        fields.values().try_for_each(|selection_sets| {
+            self.check_merged_selection_sets(selection_sets, max_fields, max_depth, depth + 1)
+        })
+    }
+
+    /// Adds the selection sets of the fields in `selection_set` and its
+    /// fragments to `fields`, keyed by the response keys of the fields.
+    fn collect_selection_sets<'b>(
+        &'b self,
+        selection_set: &'b q::SelectionSet,
+        fields: &mut BTreeMap<&'b str, Vec<&'b q::SelectionSet>>,
+        visited_fragments: &mut HashSet<&'b str>,
+    ) {
+        for selection in &selection_set.items {
+            match selection {
+                q::Selection::Field(field) => {
+                    let selection_sets = fields
+                        .entry(qast::get_response_key(field).as_str())
+                        .or_default();
+                    if !field.selection_set.items.is_empty() {
+                        selection_sets.push(&field.selection_set);
+                    }
+                }
+                q::Selection::FragmentSpread(spread) => {
+                    if visited_fragments.insert(spread.fragment_name.as_str()) {
+                        if let Some(fragment) =
+                            qast::get_fragment(&self.document, &spread.fragment_name)
+                        {
+                            self.collect_selection_sets(
+                                &fragment.selection_set,
+                                fields,
+                                visited_fragments,
+                            );
+                        }
+                    }
+                }
+                q::Selection::InlineFragment(fragment) => {
+                    self.collect_selection_sets(&fragment.selection_set, fields, visited_fragments)
+                }
+            }
+        }
+    }
+
+    /// Estimates the cost of a query without executing it. Only asks the
+    /// resolver to plan the store queries that each field needs; nothing is
+    /// loaded from the store.
+    pub(crate) fn root_query_cost_estimate(
+        &self,
+        root_type: &s::TypeDefinition,
+        root_selection_set: &q::SelectionSet,
+        complexity: u64,
+    ) -> Result<QueryCostEstimate, Vec<QueryExecutionError>> {
+        let mut estimate = QueryCostEstimate {
+            complexity,
+            ..QueryCostEstimate::default()
+        };
+        self.estimate_selection_set(root_type, root_selection_set, 1, 0, &mut estimate)?;
+        Ok(estimate)
+    }
+
+    /// Adds the cost of resolving `selection_set` on `parents` objects of
+    /// type `ty` to `estimate`.
+    fn estimate_selection_set(
+        &self,
+        ty: &s::TypeDefinition,
+        selection_set: &q::SelectionSet,
+        parents: u64,
+        depth: u8,
+        estimate: &mut QueryCostEstimate,
+    ) -> Result<(), Vec<QueryExecutionError>> {
+        let schema = &self.schema.document;
+
+        for selection in &selection_set.items {
+            match selection {
+                q::Selection::Field(field) => {
+                    estimate.depth = cmp::max(estimate.depth, depth + 1);
+
+                    // Introspection fields never query the store
+                    if field.name.starts_with("__") {
+                        continue;
+                    }
+
+                    // Arguments are defined the same way on an interface and
+                    // the types implementing it
+                    let object_type = match ty {
+                        s::TypeDefinition::Object(t) => Some(t),
+                        s::TypeDefinition::Interface(t) => self
+                            .schema
+                            .types_for_interface()
+                            .get(&t.name)
+                            .and_then(|types| types.first()),
+                        _ => None,
+                    };
+                    let (object_type, field_definition) = match object_type
+                        .and_then(|t| sast::get_field(t, &field.name).map(|def| (t, def)))
+                    {
+                        Some(pair) => pair,
+                        None => continue,
+                    };
+
+                    let field_type = match sast::get_named_type(
+                        schema,
+                        get_base_type(&field_definition.field_type),
+                    ) {
+                        Some(field_type) => field_type,
+                        None => continue,
+                    };
+                    let entity_type: ObjectOrInterface = match field_type {
+                        s::TypeDefinition::Object(t) => t.into(),
+                        s::TypeDefinition::Interface(t) => t.into(),
+                        _ => continue,
+                    };
+
+                    let argument_values = coerce_argument_values(self, object_type, field)?;
+                    let children = match self
+                        .resolver
+                        .estimate_rows(
+                            &self.query,
+                            field_definition,
+                            entity_type,
+                            &argument_values,
+                            self.schema.types_for_interface(),
+                        )
+                        .map_err(|e| vec![e])?
+                    {
+                        Some(rows) => {
+                            let rows = parents.saturating_mul(rows);
+                            estimate.store_queries = estimate.store_queries.saturating_add(parents);
+                            estimate.estimated_rows = estimate.estimated_rows.saturating_add(rows);
+                            rows
+                        }
+                        None => parents,
+                    };
+
+                    self.estimate_selection_set(
+                        field_type,
+                        &field.selection_set,
+                        children,
+                        depth + 1,
+                        estimate,
+                    )?;
+                }
+                q::Selection::FragmentSpread(spread) => {
+                    if let Some(fragment) =
+                        qast::get_fragment(&self.document, &spread.fragment_name)
+                    {
+                        let q::TypeCondition::On(type_name) = &fragment.type_condition;
+                        if let Some(ty) = sast::get_named_type(schema, type_name) {
+                            self.estimate_selection_set(
+                                ty,
+                                &fragment.selection_set,
+                                parents,
+                                depth,
+                                estimate,
+                            )?;
+                        }
+                    }
+                }
+                q::Selection::InlineFragment(fragment) => {
+                    let ty = match &fragment.type_condition {
+                        Some(q::TypeCondition::On(type_name)) => {
+                            match sast::get_named_type(schema, type_name) {
+                                Some(ty) => ty,
+                                None => continue,
+                            }
+                        }
+                        None => ty,
+                    };
+                    self.estimate_selection_set(
+                        ty,
+                        &fragment.selection_set,
+                        parents,
+                        depth,
+                        estimate,
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the authorizer allows selecting `field_name` of `type_name`.
+    /// The introspection schema is always accessible.
+    fn can_access(&self, type_name: &Name, field_name: &Name) -> bool {
+        if type_name.starts_with("__") || field_name == "__schema" || field_name == "__type" {
+            return true;
+        }
+        self.authorizer
+            .can_access(&self.schema.id, type_name, field_name)
+            == Decision::Allow
+    }
+
+    /// Errors for accessing `field_name` of `type_name` if the authorizer
+    /// denies it. A field of an interface is only accessible if it is
+    /// accessible in the interface and in all types implementing it.
+    fn check_access(
+        &self,
+        pos: Pos,
+        type_name: &Name,
+        field_name: &Name,
+    ) -> Vec<QueryExecutionError> {
+        let implementations = self
+            .schema
+            .types_for_interface()
+            .get(type_name)
+            .into_iter()
+            .flatten()
+            .map(|object_type| &object_type.name);
+        iter::once(type_name)
+            .chain(implementations)
+            .filter(|type_name| !self.can_access(type_name, field_name))
+            .map(|type_name| {
+                QueryExecutionError::Unauthorized(pos, type_name.clone(), field_name.clone())
+            })
+            .collect()
+    }
+
+    /// The fields of the entities returned by `field` that its `where` and
+    /// `orderBy` arguments refer to.
+    fn argument_fields(&self, field: &q::Field) -> Vec<Name> {
+        field
+            .arguments
+            .iter()
+            .filter_map(|(name, value)| {
+                let value = match value {
+                    q::Value::Variable(variable) => self.variable_values.get(variable)?,
+                    value => value,
+                };
+                match (name.as_str(), value) {
+                    ("where", q::Value::Object(filter)) => Some(
+                        filter
+                            .keys()
+                            .map(|key| sast::parse_field_as_filter(key).0)
+                            .collect(),
+                    ),
+                    // Ordering by a field of a referenced entity uses the
+                    // form `<field>__<child_field>`
+                    ("orderBy", q::Value::Enum(order_by)) => order_by
+                        .splitn(2, "__")
+                        .next()
+                        .map(|name| vec![name.to_owned()]),
+                    _ => None,
+                }
+            })
+            .flatten()
+            .collect()
+    }
+
     // Checks for invalid selections.
     pub(crate) fn validate_fields(
         &self,
@@ -236,13 +624,42 @@ where
 
                         match s_field {
                             Some(s_field) => {
+                                let denied =
+                                    self.check_access(field.position, type_name, &field.name);
+                                if !denied.is_empty() {
+                                    errors.extend(denied);
+                                    return errors;
+                                }
+
                                 let base_type = get_base_type(&s_field.field_type);
                                 match get_named_type(schema, base_type) {
-                                    Some(ty) => errors.extend(self.validate_fields(
-                                        base_type,
-                                        &ty,
-                                        &field.selection_set,
-                                    )),
+                                    Some(ty) => {
+                                        // Filtering and ordering by a field reveals
+                                        // its values as much as selecting it
+                                        for name in self.argument_fields(field) {
+                                            let defined = match ty {
+                                                s::TypeDefinition::Object(t) => {
+                                                    get_field(t, &name).is_some()
+                                                }
+                                                s::TypeDefinition::Interface(t) => {
+                                                    get_field(t, &name).is_some()
+                                                }
+                                                _ => false,
+                                            };
+                                            if defined {
+                                                errors.extend(self.check_access(
+                                                    field.position,
+                                                    base_type,
+                                                    &name,
+                                                ));
+                                            }
+                                        }
+                                        errors.extend(self.validate_fields(
+                                            base_type,
+                                            &ty,
+                                            &field.selection_set,
+                                        ))
+                                    }
                                     None => errors.push(QueryExecutionError::NamedTypeError(
                                         base_type.clone(),
                                     )),
@@ -395,22 +812,49 @@ where
     // Group fields with the same response key, so we can execute them together
     let grouped_field_set = collect_fields(ctx.clone(), object_type, selection_set, None);
 
+    // Fields of objects that the resolver returned complete don't need to
+    // be resolved one by one
+    let object_is_complete = match object_value {
+        Some(value @ q::Value::Object(_)) => {
+            ctx.resolver
+                .is_complete_value(&ctx.query, object_type, value)
+        }
+        _ => false,
+    };
+
+    // Account for the braces around the object
+    ctx.response_size.add(2);
+
     // Process all field groups in order
     for (response_key, fields) in grouped_field_set {
-        match ctx.deadline {
-            Some(deadline) if deadline < Instant::now() => {
-                errors.push(QueryExecutionError::Timeout);
-                break;
-            }
-            _ => (),
+        if let Err(e) = ctx.query.check_deadline() {
+            errors.push(e);
+            break;
         }
 
+        // Stop adding fields once the response is too large; the caller
+        // reports the error alongside the data gathered so far
+        if ctx.response_size.check().is_err() {
+            break;
+        }
+
+        // The quoted key, the colon and the comma
+        ctx.response_size.add(response_key.len() + 4);
+
         // If the field exists on the object, execute it and add its result to the result map
         if let Some(ref field) = sast::get_field(object_type, &fields[0].name) {
             // Push the new field onto the context's field stack
             let ctx = ctx.for_field(&fields[0]);
 
-            match execute_field(&ctx, object_type, object_value, &fields[0], field, fields) {
+            match execute_field(
+                &ctx,
+                object_type,
+                object_value,
+                object_is_complete,
+                &fields[0],
+                field,
+                fields,
+            ) {
                 Ok(v) => {
                     result_map.insert(response_key.to_owned(), v);
                 }
@@ -427,7 +871,7 @@ where
         }
     }
 
-    if errors.is_empty() && !result_map.is_empty() {
+    if errors.is_empty() && (!result_map.is_empty() || ctx.response_size.check().is_err()) {
         Ok(result_map)
     } else {
         if errors.is_empty() {
@@ -558,7 +1002,7 @@ fn does_fragment_type_apply(
 
     match named_type {
         // The fragment applies to the object type if its type is the same object type
-        Some(s::TypeDefinition::Object(ot)) => object_type == ot,
+        Some(s::TypeDefinition::Object(ot)) => object_type.name == ot.name,
 
         // The fragment also applies to the object type if its type is an interface
         // that the object type implements
@@ -580,6 +1024,7 @@ fn execute_field<'a, R>(
     ctx: &ExecutionContext<'a, R>,
     object_type: &s::ObjectType,
     object_value: &Option<q::Value>,
+    object_is_complete: bool,
     field: &'a q::Field,
     field_definition: &s::Field,
     fields: Vec<&'a q::Field>,
@@ -587,8 +1032,26 @@ fn execute_field<'a, R>(
 where
     R: Resolver,
 {
+    // The `__typename` of an object is the name of its concrete type, no matter
+    // what the resolver returned for the object
+    if field.name == "__typename" {
+        return Ok(q::Value::String(object_type.name.clone()));
+    }
+
     coerce_argument_values(ctx, object_type, field)
         .and_then(|argument_values| {
+            // Fields with arguments are always resolved since their values
+            // depend on the arguments. Values taken from complete objects
+            // still have to match the type of the field, e.g. non-null fields
+            // must not be null
+            if object_is_complete && argument_values.is_empty() {
+                if let Some(value) =
+                    complete_field_value(ctx, object_type, object_value, field, field_definition)
+                {
+                    return value;
+                }
+            }
+
             resolve_field_value(
                 ctx,
                 object_type,
@@ -597,11 +1060,86 @@ where
                 field_definition,
                 &field_definition.field_type,
                 &argument_values,
+                &fields,
             )
         })
         .and_then(|value| complete_value(ctx, field, &field_definition.field_type, fields, value))
 }
 
+/// Takes the value of `field` from `object_value`, an object that the
+/// resolver returned complete. This works for scalar and enum fields, and
+/// for object fields whose objects are nested in `object_value` already.
+/// Returns `None` if the field has to be resolved, e.g. because
+/// `object_value` doesn't have it.
+fn complete_field_value<'a, R>(
+    ctx: &ExecutionContext<'a, R>,
+    object_type: &s::ObjectType,
+    object_value: &Option<q::Value>,
+    field: &q::Field,
+    field_definition: &s::Field,
+) -> Option<Result<q::Value, Vec<QueryExecutionError>>>
+where
+    R: Resolver,
+{
+    let object = match object_value {
+        Some(q::Value::Object(object)) => object,
+        _ => return None,
+    };
+    let value = object.get(&field.name);
+
+    match sast::get_named_type(
+        &ctx.schema.document,
+        get_base_type(&field_definition.field_type),
+    )? {
+        // `Int`s still have to be in range
+        s::TypeDefinition::Scalar(t) => Some(match value?.clone() {
+            q::Value::List(values) => {
+                let mut errors = vec![];
+                let values = values
+                    .into_iter()
+                    .filter_map(|value| {
+                        check_int_range(ctx, object_type, object, field, t, value)
+                            .map_err(|e| errors.push(e))
+                            .ok()
+                    })
+                    .collect();
+                match errors.is_empty() {
+                    true => Ok(q::Value::List(values)),
+                    false => Err(errors),
+                }
+            }
+            value => {
+                check_int_range(ctx, object_type, object, field, t, value).map_err(|e| vec![e])
+            }
+        }),
+
+        s::TypeDefinition::Enum(_) => Some(Ok(value?.clone())),
+
+        // Anything but nested objects, e.g. the IDs of referenced entities,
+        // still has to be resolved
+        s::TypeDefinition::Object(_) | s::TypeDefinition::Interface(_) => match value {
+            Some(value @ q::Value::Object(_)) => Some(Ok(value.clone())),
+            Some(value @ q::Value::List(_)) if is_list_of_objects(value) => Some(Ok(value.clone())),
+            _ => None,
+        },
+
+        _ => None,
+    }
+}
+
+fn is_list_of_objects(value: &q::Value) -> bool {
+    match value {
+        q::Value::List(values) => {
+            !values.is_empty()
+                && values.iter().all(|value| match value {
+                    q::Value::Object(_) => true,
+                    _ => false,
+                })
+        }
+        _ => false,
+    }
+}
+
 /// Resolves the value of a field.
 fn resolve_field_value<'a, R>(
     ctx: &ExecutionContext<'a, R>,
@@ -611,6 +1149,7 @@ fn resolve_field_value<'a, R>(
     field_definition: &s::Field,
     field_type: &s::Type,
     argument_values: &HashMap<&q::Name, q::Value>,
+    fields: &[&'a q::Field],
 ) -> Result<q::Value, Vec<QueryExecutionError>>
 where
     R: Resolver,
@@ -624,6 +1163,7 @@ where
             field_definition,
             inner_type.as_ref(),
             argument_values,
+            fields,
         ),
 
         s::Type::NamedType(ref name) => resolve_field_value_for_named_type(
@@ -634,6 +1174,7 @@ where
             field_definition,
             name,
             argument_values,
+            fields,
         ),
 
         s::Type::ListType(inner_type) => resolve_field_value_for_list_type(
@@ -644,6 +1185,7 @@ where
             field_definition,
             inner_type.as_ref(),
             argument_values,
+            fields,
         ),
     }
 }
@@ -657,6 +1199,7 @@ fn resolve_field_value_for_named_type<'a, R>(
     field_definition: &s::Field,
     type_name: &s::Name,
     argument_values: &HashMap<&q::Name, q::Value>,
+    fields: &[&'a q::Field],
 ) -> Result<q::Value, Vec<QueryExecutionError>>
 where
     R: Resolver,
@@ -665,16 +1208,46 @@ where
     let named_type = sast::get_named_type(&ctx.schema.document, type_name)
         .ok_or_else(|| QueryExecutionError::NamedTypeError(type_name.to_string()))?;
 
+    let id = entity_id_argument(argument_values);
+
     match named_type {
+        // The top-level `_meta` field reports the state of the deployment
+        // rather than an entity
+        s::TypeDefinition::Object(_) if object_value.is_none() && field.name == META_FIELD => {
+            ctx.resolver.resolve_meta(&ctx.query, &ctx.schema.id)
+        }
+
+        // Top-level fields that look up a single entity by its ID
+        s::TypeDefinition::Object(t) if object_value.is_none() && id.is_some() => resolve_entity(
+            ctx,
+            field,
+            field_definition,
+            t.into(),
+            id.unwrap(),
+            argument_values,
+        ),
+        s::TypeDefinition::Interface(i) if object_value.is_none() && id.is_some() => {
+            resolve_entity(
+                ctx,
+                field,
+                field_definition,
+                i.into(),
+                id.unwrap(),
+                argument_values,
+            )
+        }
+
         // Let the resolver decide how the field (with the given object type)
         // is resolved into an entity based on the (potential) parent object
-        s::TypeDefinition::Object(t) => ctx.resolver.resolve_object(
+        s::TypeDefinition::Object(t) => ctx.resolver.resolve_object_with_selection(
+            &ctx.query,
             object_value,
             field,
             field_definition,
             t.into(),
             argument_values,
             ctx.schema.types_for_interface(),
+            &field_selection(ctx, t.into(), fields)?,
         ),
 
         // Let the resolver decide how values in the resolved object value
@@ -682,7 +1255,7 @@ where
         s::TypeDefinition::Enum(t) => match object_value {
             Some(q::Value::Object(o)) => {
                 ctx.resolver
-                    .resolve_enum_value(field, t, o.get(&field.name))
+                    .resolve_enum_value(&ctx.query, field, t, o.get(&field.name))
             }
             _ => Ok(q::Value::Null),
         },
@@ -690,20 +1263,22 @@ where
         // Let the resolver decide how values in the resolved object value
         // map to values of GraphQL scalars
         s::TypeDefinition::Scalar(t) => match object_value {
-            Some(q::Value::Object(o)) => {
-                ctx.resolver
-                    .resolve_scalar_value(object_type, o, field, t, o.get(&field.name))
-            }
+            Some(q::Value::Object(o)) => ctx
+                .resolver
+                .resolve_scalar_value(&ctx.query, object_type, o, field, t, o.get(&field.name))
+                .and_then(|value| check_int_range(ctx, object_type, o, field, t, value)),
             _ => Ok(q::Value::Null),
         },
 
-        s::TypeDefinition::Interface(i) => ctx.resolver.resolve_object(
+        s::TypeDefinition::Interface(i) => ctx.resolver.resolve_object_with_selection(
+            &ctx.query,
             object_value,
             field,
             field_definition,
             i.into(),
             argument_values,
             ctx.schema.types_for_interface(),
+            &field_selection(ctx, i.into(), fields)?,
         ),
 
         s::TypeDefinition::Union(_) => Err(QueryExecutionError::Unimplemented("unions".to_owned())),
@@ -713,6 +1288,42 @@ where
     .map_err(|e| vec![e])
 }
 
+/// Resolves a top-level field that looks up the entity with ID `id`.
+fn resolve_entity<'a, R>(
+    ctx: &ExecutionContext<'a, R>,
+    field: &q::Field,
+    field_definition: &s::Field,
+    object_type: ObjectOrInterface<'_>,
+    id: &str,
+    argument_values: &HashMap<&q::Name, q::Value>,
+) -> Result<q::Value, QueryExecutionError>
+where
+    R: Resolver,
+{
+    ctx.resolver
+        .resolve_entity(
+            &ctx.query,
+            field,
+            field_definition,
+            object_type,
+            id,
+            argument_values,
+            ctx.schema.types_for_interface(),
+        )
+        .map(|entity| entity.unwrap_or(q::Value::Null))
+}
+
+/// The `id` argument of a field, if it is a string.
+fn entity_id_argument<'v>(argument_values: &'v HashMap<&q::Name, q::Value>) -> Option<&'v str> {
+    argument_values
+        .iter()
+        .find(|(name, _)| name.as_str() == "id")
+        .and_then(|(_, value)| match value {
+            q::Value::String(id) => Some(id.as_str()),
+            _ => None,
+        })
+}
+
 /// Resolves the value of a field that corresponds to a list type.
 fn resolve_field_value_for_list_type<'a, R>(
     ctx: &ExecutionContext<'a, R>,
@@ -722,6 +1333,7 @@ fn resolve_field_value_for_list_type<'a, R>(
     field_definition: &s::Field,
     inner_type: &s::Type,
     argument_values: &HashMap<&q::Name, q::Value>,
+    fields: &[&'a q::Field],
 ) -> Result<q::Value, Vec<QueryExecutionError>>
 where
     R: Resolver,
@@ -735,6 +1347,7 @@ where
             field_definition,
             inner_type,
             argument_values,
+            fields,
         ),
 
         s::Type::NamedType(ref type_name) => {
@@ -744,17 +1357,19 @@ where
             match named_type {
                 // Let the resolver decide how the list field (with the given item object type)
                 // is resolved into a entities based on the (potential) parent object
-                s::TypeDefinition::Object(t) => ctx
-                    .resolver
-                    .resolve_objects(
-                        object_value,
-                        &field.name,
-                        field_definition,
-                        t.into(),
-                        argument_values,
-                        ctx.schema.types_for_interface(),
-                        ctx.max_first,
-                    )
+                s::TypeDefinition::Object(t) => field_selection(ctx, t.into(), fields)
+                    .and_then(|selection| {
+                        ctx.resolver.resolve_objects_with_selection(
+                            &ctx.query,
+                            object_value,
+                            &field.name,
+                            field_definition,
+                            t.into(),
+                            argument_values,
+                            ctx.schema.types_for_interface(),
+                            &selection,
+                        )
+                    })
                     .map_err(|e| vec![e]),
 
                 // Let the resolver decide how values in the resolved object value
@@ -762,7 +1377,7 @@ where
                 s::TypeDefinition::Enum(t) => match object_value {
                     Some(q::Value::Object(o)) => {
                         ctx.resolver
-                            .resolve_enum_values(field, &t, o.get(&field.name))
+                            .resolve_enum_values(&ctx.query, field, &t, o.get(&field.name))
                     }
                     _ => Ok(q::Value::Null),
                 },
@@ -770,24 +1385,43 @@ where
                 // Let the resolver decide how values in the resolved object value
                 // map to values of GraphQL scalars
                 s::TypeDefinition::Scalar(t) => match object_value {
-                    Some(q::Value::Object(o)) => {
-                        ctx.resolver
-                            .resolve_scalar_values(field, &t, o.get(&field.name))
-                    }
+                    Some(q::Value::Object(o)) => ctx
+                        .resolver
+                        .resolve_scalar_values(&ctx.query, field, &t, o.get(&field.name))
+                        .and_then(|value| match value {
+                            q::Value::List(values) => {
+                                let mut errors = vec![];
+                                let values = values
+                                    .into_iter()
+                                    .filter_map(|value| {
+                                        check_int_range(ctx, object_type, o, field, t, value)
+                                            .map_err(|e| errors.push(e))
+                                            .ok()
+                                    })
+                                    .collect();
+                                match errors.is_empty() {
+                                    true => Ok(q::Value::List(values)),
+                                    false => Err(errors),
+                                }
+                            }
+                            value => Ok(value),
+                        }),
                     _ => Ok(q::Value::Null),
                 },
 
-                s::TypeDefinition::Interface(t) => ctx
-                    .resolver
-                    .resolve_objects(
-                        object_value,
-                        &field.name,
-                        field_definition,
-                        t.into(),
-                        argument_values,
-                        ctx.schema.types_for_interface(),
-                        ctx.max_first,
-                    )
+                s::TypeDefinition::Interface(t) => field_selection(ctx, t.into(), fields)
+                    .and_then(|selection| {
+                        ctx.resolver.resolve_objects_with_selection(
+                            &ctx.query,
+                            object_value,
+                            &field.name,
+                            field_definition,
+                            t.into(),
+                            argument_values,
+                            ctx.schema.types_for_interface(),
+                            &selection,
+                        )
+                    })
                     .map_err(|e| vec![e]),
 
                 s::TypeDefinition::Union(_) => Err(vec![QueryExecutionError::Unimplemented(
@@ -807,6 +1441,254 @@ where
     }
 }
 
+/// Determines the fields selected from the objects of type `object_type`
+/// that `fields` resolve to, for every object type these objects can have.
+fn field_selection<R>(
+    ctx: &ExecutionContext<'_, R>,
+    object_type: ObjectOrInterface<'_>,
+    fields: &[&q::Field],
+) -> Result<Selection, QueryExecutionError>
+where
+    R: Resolver,
+{
+    let selection_set = merge_selection_sets(fields.to_vec());
+    let mut field_names = BTreeSet::new();
+    for object_type in object_type.object_types(ctx.schema.types_for_interface())? {
+        for (_, fields) in collect_fields(ctx.clone(), object_type, &selection_set, None) {
+            field_names.extend(fields.into_iter().map(|field| field.name.clone()));
+        }
+    }
+    Ok(Selection::new(field_names))
+}
+
+/// The only field of a query whose objects can be resolved in batches, as
+/// returned by `streamable_root_field`.
+pub struct StreamableField<'a> {
+    /// The key of the field in the response.
+    pub response_key: &'a str,
+    field: &'a q::Field,
+    fields: Vec<&'a q::Field>,
+    field_definition: s::Field,
+}
+
+/// Returns the type of the items of a list type.
+fn list_item_type(field_type: &s::Type) -> Option<&s::Type> {
+    match field_type {
+        s::Type::NonNullType(inner) => list_item_type(inner),
+        s::Type::ListType(inner) => Some(inner),
+        s::Type::NamedType(_) => None,
+    }
+}
+
+/// Returns the object or interface type of the objects a field resolves to.
+fn object_type_of_field<'a>(
+    schema: &'a s::Document,
+    field_definition: &s::Field,
+) -> Option<ObjectOrInterface<'a>> {
+    match sast::get_named_type(schema, get_base_type(&field_definition.field_type)) {
+        Some(s::TypeDefinition::Object(t)) => Some(t.into()),
+        Some(s::TypeDefinition::Interface(t)) => Some(t.into()),
+        _ => None,
+    }
+}
+
+/// Checks whether the root selection set of a query can be executed with
+/// `execute_field_in_batches` and returns its only field if it can. That
+/// is the case if it consists of a single collection field of entities
+/// without range or order arguments, and selects no lists from the
+/// entities. Otherwise, returns why the query can't be executed like that.
+pub fn streamable_root_field<'a, R>(
+    ctx: &ExecutionContext<'a, R>,
+    selection_set: &'a q::SelectionSet,
+) -> Result<StreamableField<'a>, String>
+where
+    R: Resolver,
+{
+    let query_type = sast::get_root_query_type(&ctx.schema.document)
+        .ok_or_else(|| String::from("the schema has no root query type"))?;
+
+    let mut grouped_field_set = collect_fields(ctx.clone(), query_type, selection_set, None);
+    let (response_key, fields) = match grouped_field_set.pop() {
+        Some(group) if grouped_field_set.is_empty() => group,
+        _ => return Err(String::from("the query selects more than one field")),
+    };
+
+    let field = fields[0];
+    let field_definition = sast::get_field(query_type, &field.name)
+        .filter(|_| !field.name.starts_with("__") && field.name != META_FIELD)
+        .filter(|field_definition| sast::is_list_or_non_null_list_field(field_definition))
+        .ok_or_else(|| format!("field `{}` is not a collection of entities", response_key))?
+        .clone();
+    let object_type = object_type_of_field(&ctx.schema.document, &field_definition)
+        .ok_or_else(|| format!("field `{}` is not a collection of entities", response_key))?;
+
+    // Entities are streamed in the order of their IDs, from the first one
+    for argument in &["first", "skip", "orderBy", "orderDirection"] {
+        if fields
+            .iter()
+            .any(|field| qast::get_argument_value(&field.arguments, argument).is_some())
+        {
+            return Err(format!(
+                "field `{}` has a `{}` argument",
+                response_key, argument
+            ));
+        }
+    }
+
+    // Nested lists could make any single entity arbitrarily large
+    let selection_set = merge_selection_sets(fields.clone());
+    for object_type in object_type
+        .object_types(ctx.schema.types_for_interface())
+        .map_err(|e| e.to_string())?
+    {
+        for (nested_key, nested_fields) in
+            collect_fields(ctx.clone(), object_type, &selection_set, None)
+        {
+            let is_list = sast::get_field(object_type, &nested_fields[0].name)
+                .map_or(false, |field| sast::is_list_or_non_null_list_field(field));
+            if is_list {
+                return Err(format!(
+                    "field `{}` selects the list `{}`",
+                    response_key, nested_key
+                ));
+            }
+        }
+    }
+
+    Ok(StreamableField {
+        response_key,
+        field,
+        fields,
+        field_definition,
+    })
+}
+
+/// Executes a field returned by `streamable_root_field`, resolving its
+/// entities in batches of `batch_size`. The completed values of each batch
+/// are passed to `sink` in order, which can stop the execution by returning
+/// an error. Values that can't be completed are left out and their errors
+/// are returned once all batches have been passed to `sink`.
+pub fn execute_field_in_batches<'a, R>(
+    ctx: &ExecutionContext<'a, R>,
+    streamable: &StreamableField<'a>,
+    batch_size: usize,
+    sink: &mut dyn FnMut(Vec<q::Value>) -> Result<(), QueryExecutionError>,
+) -> Result<(), Vec<QueryExecutionError>>
+where
+    R: Resolver,
+{
+    let query_type = sast::get_root_query_type(&ctx.schema.document)
+        .ok_or_else(|| vec![QueryExecutionError::NoRootQueryObjectType])?;
+    let field_definition = &streamable.field_definition;
+    let object_type = object_type_of_field(&ctx.schema.document, field_definition)
+        .expect("streamable fields are collections of entities");
+    let item_type = list_item_type(&field_definition.field_type)
+        .expect("streamable fields are collections of entities");
+
+    let ctx = ctx.for_field(streamable.field);
+    let argument_values = coerce_argument_values(&ctx, query_type, streamable.field)?;
+    let selection = field_selection(&ctx, object_type, &streamable.fields).map_err(|e| vec![e])?;
+
+    let mut errors = Vec::new();
+    let result = ctx.resolver.resolve_objects_in_batches(
+        &ctx.query,
+        field_definition,
+        object_type,
+        &argument_values,
+        ctx.schema.types_for_interface(),
+        &selection,
+        batch_size,
+        &mut |objects| {
+            ctx.query.check_deadline()?;
+
+            let mut values = Vec::with_capacity(objects.len());
+            for object in objects {
+                match complete_value(
+                    &ctx,
+                    streamable.field,
+                    item_type,
+                    streamable.fields.clone(),
+                    object,
+                ) {
+                    Ok(value) => values.push(value),
+                    Err(errs) => errors.extend(errs),
+                }
+            }
+            sink(values)
+        },
+    );
+
+    if let Err(e) = result {
+        errors.push(e);
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Checks that a resolved value of an `Int` field fits into an `Int`.
+/// Values that don't, e.g. because the entity stores a larger number than
+/// the schema allows, fail the field, or are clamped to the range of `Int`
+/// with a warning if the query context allows it.
+fn check_int_range<'a, R>(
+    ctx: &ExecutionContext<'a, R>,
+    object_type: &s::ObjectType,
+    object: &BTreeMap<String, q::Value>,
+    field: &q::Field,
+    scalar_type: &s::ScalarType,
+    value: q::Value,
+) -> Result<q::Value, QueryExecutionError>
+where
+    R: Resolver,
+{
+    if scalar_type.name != "Int" {
+        return Ok(value);
+    }
+
+    let number = match &value {
+        q::Value::Int(n) => match n.as_i64() {
+            Some(n) => BigInt::from(n),
+            None => return Ok(value),
+        },
+        q::Value::String(s) => match BigInt::from_str(s) {
+            Ok(n) => n,
+            // Not a number; completing the value reports that
+            Err(_) => return Ok(value),
+        },
+        _ => return Ok(value),
+    };
+    let (min, max) = (i32::min_value(), i32::max_value());
+    if BigInt::from(min) <= number && number <= BigInt::from(max) {
+        return Ok(value);
+    }
+
+    let entity = match object.get("id") {
+        Some(q::Value::String(id)) => format!("`{}` entity `{}`", object_type.name, id),
+        _ => format!("`{}` entity", object_type.name),
+    };
+    if ctx.query.clamp_int_overflow {
+        let clamped = if number < BigInt::from(0) { min } else { max };
+        warn!(
+            ctx.query.logger,
+            "Clamped value of `Int` field that is out of range";
+            "entity" => &entity,
+            "field" => &field.name,
+            "value" => number.to_string(),
+            "clamped" => clamped,
+        );
+        Ok(q::Value::Int(clamped.into()))
+    } else {
+        Err(QueryExecutionError::IntOverflow {
+            pos: field.position,
+            entity,
+            field: field.name.clone(),
+            value: number.to_string(),
+        })
+    }
+}
+
 /// Ensures that a value matches the expected return type.
 fn complete_value<'a, R>(
     ctx: &ExecutionContext<'a, R>,
@@ -819,12 +1701,13 @@ where
     R: Resolver,
 {
     match field_type {
-        // Fail if the field type is non-null but the value is null
+        // Fail if the field type is non-null but the value is null, e.g.
+        // because a non-null reference points to an entity that doesn't exist
         s::Type::NonNullType(inner_type) => {
             return match complete_value(ctx, field, inner_type, fields, resolved_value)? {
                 q::Value::Null => Err(vec![QueryExecutionError::NonNullError(
                     field.position,
-                    field.name.to_string(),
+                    ctx.response_path(),
                 )]),
 
                 v => Ok(v),
@@ -833,6 +1716,7 @@ where
 
         // If the resolved value is null, return null
         _ if resolved_value == q::Value::Null => {
+            ctx.response_size.add_value(&resolved_value);
             return Ok(resolved_value);
         }
 
@@ -843,7 +1727,14 @@ where
                 q::Value::List(values) => {
                     let mut errors = Vec::new();
                     let mut out = Vec::with_capacity(values.len());
+                    ctx.response_size.add(2);
                     for value in values.into_iter() {
+                        // Leave out the remaining values once the response
+                        // is too large
+                        if ctx.response_size.check().is_err() {
+                            break;
+                        }
+                        ctx.response_size.add(1);
                         match complete_value(ctx, field, inner_type, fields.clone(), value) {
                             Ok(value) => out.push(value),
                             Err(errs) => errors.extend(errs),
@@ -869,19 +1760,21 @@ where
             match named_type {
                 // Complete scalar values
                 s::TypeDefinition::Scalar(scalar_type) => {
-                    resolved_value.coerce(scalar_type).ok_or_else(|| {
+                    let value = resolved_value.coerce(scalar_type).ok_or_else(|| {
                         vec![QueryExecutionError::ScalarCoercionError(
                             field.position.clone(),
                             field.name.to_owned(),
                             resolved_value.clone(),
                             scalar_type.name.to_owned(),
                         )]
-                    })
+                    })?;
+                    ctx.response_size.add_value(&value);
+                    Ok(value)
                 }
 
                 // Complete enum values
                 s::TypeDefinition::Enum(enum_type) => {
-                    resolved_value.coerce(enum_type).ok_or_else(|| {
+                    let value = resolved_value.coerce(enum_type).ok_or_else(|| {
                         vec![QueryExecutionError::EnumCoercionError(
                             field.position.clone(),
                             field.name.to_owned(),
@@ -893,7 +1786,9 @@ where
                                 .map(|value| value.name.to_owned())
                                 .collect(),
                         )]
-                    })
+                    })?;
+                    ctx.response_size.add_value(&value);
+                    Ok(value)
                 }
 
                 // Complete object types recursively
@@ -948,7 +1843,12 @@ where
     // Let the resolver handle the type resolution, return an error if the resolution
     // yields nothing
     ctx.resolver
-        .resolve_abstract_type(&ctx.schema.document, abstract_type, object_value)
+        .resolve_abstract_type(
+            &ctx.query,
+            &ctx.schema.document,
+            abstract_type,
+            object_value,
+        )
         .ok_or_else(|| {
             vec![QueryExecutionError::AbstractTypeError(
                 sast::get_type_name(abstract_type).to_string(),
@@ -997,12 +1897,34 @@ where
     let mut errors = vec![];
 
     let resolver = |name: &Name| sast::get_named_type(&ctx.schema.document, name);
+    let argument_defs = sast::get_argument_definitions(object_type, &field.name);
+
+    // Reject arguments that the field doesn't define
+    for (name, _) in &field.arguments {
+        if !argument_defs.map_or(false, |defs| defs.iter().any(|def| &def.name == name)) {
+            errors.push(QueryExecutionError::UnknownArgument {
+                pos: field.position,
+                field: field.name.clone(),
+                argument: name.clone(),
+            });
+        }
+    }
 
-    for argument_def in sast::get_argument_definitions(object_type, &field.name)
-        .into_iter()
-        .flatten()
-    {
-        let value = qast::get_argument_value(&field.arguments, &argument_def.name).cloned();
+    // Variables are limited while they are deserialized, values written
+    // into the query are limited here before they are coerced
+    let limits = InputValueLimits::default();
+
+    for argument_def in argument_defs.into_iter().flatten() {
+        let value = qast::get_argument_value(&field.arguments, &argument_def.name);
+        if let Some(Err(error)) = value.map(|value| limits.check(value)) {
+            errors.push(QueryExecutionError::InputValueTooLarge {
+                pos: field.position,
+                argument: argument_def.name.clone(),
+                error,
+            });
+            continue;
+        }
+        let value = value.cloned();
         match coercion::coerce_input_value(value, &argument_def, &resolver, &ctx.variable_values) {
             Ok(Some(value)) => {
                 coerced_values.insert(&argument_def.name, value);
@@ -1079,15 +2001,23 @@ fn coerce_variable_value(
     variable_def: &q::VariableDefinition,
     value: &q::Value,
 ) -> Result<q::Value, Vec<QueryExecutionError>> {
-    use crate::values::coercion::coerce_value;
+    use crate::values::coercion::{coerce_value, invalid_enum_value};
 
     let resolver = |name: &Name| sast::get_named_type(&schema.document, name);
 
     coerce_value(&value, &variable_def.var_type, &resolver, &HashMap::new()).ok_or_else(|| {
-        vec![QueryExecutionError::InvalidArgumentError(
+        vec![invalid_enum_value(
             variable_def.position,
-            variable_def.name.to_owned(),
-            value.clone(),
-        )]
+            value,
+            &variable_def.var_type,
+            &resolver,
+        )
+        .unwrap_or_else(|| {
+            QueryExecutionError::InvalidArgumentError(
+                variable_def.position,
+                variable_def.name.to_owned(),
+                value.clone(),
+            )
+        })]
     })
 }
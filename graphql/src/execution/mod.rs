@@ -1,8 +1,16 @@
+/// Access control for the types and fields of a deployment.
+mod authorization;
+
+/// State shared by all resolver calls of a query execution.
+mod context;
+
 /// Implementation of the GraphQL execution algorithm.
 mod execution;
 
 /// Common trait for field resolvers used in the execution.
 mod resolver;
 
+pub use self::authorization::{AllowAll, Authorizer, Decision, StaticAuthorizer};
+pub use self::context::{QueryContext, QueryMetrics};
 pub use self::execution::*;
-pub use self::resolver::{ObjectOrInterface, Resolver};
+pub use self::resolver::{ObjectOrInterface, Resolver, Selection};
@@ -1,9 +1,12 @@
 use graphql_parser::{query as q, schema as s};
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
 use crate::prelude::*;
 use crate::schema::ast::get_named_type;
-use graph::prelude::{QueryExecutionError, StoreEventStreamBox};
+use graph::prelude::{
+    format_err, DeploymentSyncStatus, Error, QueryExecutionError, StoreEventStreamBox,
+    SubgraphDeploymentId, TryFromValue,
+};
 
 #[derive(Copy, Clone, Debug)]
 pub enum ObjectOrInterface<'a> {
@@ -38,31 +41,113 @@ impl<'a> ObjectOrInterface<'a> {
         }
     }
 
+    /// Returns the directive `name` of the type, if it has one. Types keep
+    /// the directives of the input schema in the API schema, so resolvers
+    /// can use them to customize how they resolve values of the type.
+    pub fn directive(self, name: &str) -> Option<&'a s::Directive> {
+        self.directives()
+            .iter()
+            .find(|directive| directive.name == name)
+    }
+
+    /// Returns the `argument` of the directive `name` of the type, converted
+    /// to `T`. Returns `Ok(None)` if the type doesn't have the directive or
+    /// the directive doesn't have the argument, and fails if the argument
+    /// can't be converted to `T`.
+    pub fn directive_argument<T>(self, name: &str, argument: &str) -> Result<Option<T>, Error>
+    where
+        T: TryFromValue,
+    {
+        self.directive(name)
+            .and_then(|directive| {
+                directive
+                    .arguments
+                    .iter()
+                    .find(|(argument_name, _)| argument_name == argument)
+            })
+            .map(|(_, value)| {
+                T::try_from_value(value).map_err(|e| {
+                    format_err!(
+                        "invalid argument `{}` of directive `@{}` on type `{}`: {}",
+                        argument,
+                        name,
+                        self.name(),
+                        e
+                    )
+                })
+            })
+            .transpose()
+    }
+
     pub fn fields(self) -> &'a Vec<s::Field> {
         match self {
             ObjectOrInterface::Object(object) => &object.fields,
             ObjectOrInterface::Interface(interface) => &interface.fields,
         }
     }
+
+    /// Returns the concrete types a value of this type can have, i.e. the
+    /// object type itself or the object types implementing the interface.
+    /// Resolvers use this to set `__typename` on interface-typed values.
+    ///
+    /// Fails with `AbstractTypeError` if the interface is not in
+    /// `types_for_interface`.
+    pub fn object_types(
+        self,
+        types_for_interface: &'a BTreeMap<Name, Vec<s::ObjectType>>,
+    ) -> Result<Vec<&'a s::ObjectType>, QueryExecutionError> {
+        match self {
+            ObjectOrInterface::Object(object) => Ok(vec![object]),
+            ObjectOrInterface::Interface(interface) => types_for_interface
+                .get(&interface.name)
+                .map(|types| types.iter().collect())
+                .ok_or_else(|| QueryExecutionError::AbstractTypeError(interface.name.clone())),
+        }
+    }
+}
+
+/// The fields selected from the objects a field resolves to, after fragments
+/// have been expanded and `@skip`/`@include` directives have been applied.
+/// For fields of interface type, this includes the fields selected for any
+/// of the object types implementing the interface.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Selection {
+    field_names: BTreeSet<Name>,
+}
+
+impl Selection {
+    pub fn new(field_names: impl IntoIterator<Item = Name>) -> Self {
+        Selection {
+            field_names: field_names.into_iter().collect(),
+        }
+    }
+
+    /// The names of the selected fields, not their aliases.
+    pub fn field_names(&self) -> &BTreeSet<Name> {
+        &self.field_names
+    }
 }
 
 /// A GraphQL resolver that can resolve entities, enum values, scalar types and interfaces/unions.
+///
+/// All methods receive the `QueryContext` of the query execution they are called for.
 pub trait Resolver: Clone + Send + Sync {
     /// Resolves entities referenced by a parent object.
     fn resolve_objects(
         &self,
+        ctx: &QueryContext,
         parent: &Option<q::Value>,
         field: &q::Name,
         field_definition: &s::Field,
         object_type: ObjectOrInterface<'_>,
         arguments: &HashMap<&q::Name, q::Value>,
         types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
-        max_first: u32,
     ) -> Result<q::Value, QueryExecutionError>;
 
     /// Resolves an entity referenced by a parent object.
     fn resolve_object(
         &self,
+        ctx: &QueryContext,
         parent: &Option<q::Value>,
         field: &q::Field,
         field_definition: &s::Field,
@@ -71,9 +156,143 @@ pub trait Resolver: Clone + Send + Sync {
         types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
     ) -> Result<q::Value, QueryExecutionError>;
 
+    /// Resolves entities referenced by a parent object, knowing the
+    /// `selection` that is made from them, e.g. to load only what is needed
+    /// to resolve the selected fields. Resolvers that have no use for the
+    /// selection don't need to implement this.
+    fn resolve_objects_with_selection(
+        &self,
+        ctx: &QueryContext,
+        parent: &Option<q::Value>,
+        field: &q::Name,
+        field_definition: &s::Field,
+        object_type: ObjectOrInterface<'_>,
+        arguments: &HashMap<&q::Name, q::Value>,
+        types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+        _selection: &Selection,
+    ) -> Result<q::Value, QueryExecutionError> {
+        self.resolve_objects(
+            ctx,
+            parent,
+            field,
+            field_definition,
+            object_type,
+            arguments,
+            types_for_interface,
+        )
+    }
+
+    /// Resolves an entity referenced by a parent object, knowing the
+    /// `selection` that is made from it. Resolvers that have no use for the
+    /// selection don't need to implement this.
+    fn resolve_object_with_selection(
+        &self,
+        ctx: &QueryContext,
+        parent: &Option<q::Value>,
+        field: &q::Field,
+        field_definition: &s::Field,
+        object_type: ObjectOrInterface<'_>,
+        arguments: &HashMap<&q::Name, q::Value>,
+        types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+        _selection: &Selection,
+    ) -> Result<q::Value, QueryExecutionError> {
+        self.resolve_object(
+            ctx,
+            parent,
+            field,
+            field_definition,
+            object_type,
+            arguments,
+            types_for_interface,
+        )
+    }
+
+    /// Resolves the entity of `object_type` with the given `id` for a
+    /// top-level field that looks up a single entity by its ID, e.g.
+    /// `user(id: "1")`. Resolvers that can look entities up by their primary
+    /// key should implement this; by default, the field is resolved through
+    /// `resolve_object`.
+    fn resolve_entity(
+        &self,
+        ctx: &QueryContext,
+        field: &q::Field,
+        field_definition: &s::Field,
+        object_type: ObjectOrInterface<'_>,
+        _id: &str,
+        arguments: &HashMap<&q::Name, q::Value>,
+        types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+    ) -> Result<Option<q::Value>, QueryExecutionError> {
+        self.resolve_object(
+            ctx,
+            &None,
+            field,
+            field_definition,
+            object_type,
+            arguments,
+            types_for_interface,
+        )
+        .map(|value| match value {
+            q::Value::Null => None,
+            value => Some(value),
+        })
+    }
+
+    /// Resolves the entities of a top-level collection field in batches of
+    /// at most `batch_size` entities, ordered by their ID, and passes each
+    /// batch to `sink` before loading the next one. The range and order
+    /// arguments of the field are ignored. Resolvers that can't load
+    /// entities in batches don't need to implement this.
+    fn resolve_objects_in_batches(
+        &self,
+        _ctx: &QueryContext,
+        _field_definition: &s::Field,
+        _object_type: ObjectOrInterface<'_>,
+        _arguments: &HashMap<&q::Name, q::Value>,
+        _types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+        _selection: &Selection,
+        _batch_size: usize,
+        _sink: &mut dyn FnMut(Vec<q::Value>) -> Result<(), QueryExecutionError>,
+    ) -> Result<(), QueryExecutionError> {
+        Err(QueryExecutionError::NotSupported(String::from(
+            "Resolving objects in batches is not supported by this resolver",
+        )))
+    }
+
+    /// Estimates how many entities resolving `field_definition` for a single
+    /// parent object loads from the store, for dry runs. Returns `None` if
+    /// resolving the field doesn't query the store and `Some(rows)` if it takes
+    /// one store query that returns at most `rows` entities.
+    fn estimate_rows(
+        &self,
+        _ctx: &QueryContext,
+        _field_definition: &s::Field,
+        _object_type: ObjectOrInterface<'_>,
+        _arguments: &HashMap<&q::Name, q::Value>,
+        _types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+    ) -> Result<Option<u64>, QueryExecutionError> {
+        Ok(None)
+    }
+
+    /// Whether `value`, an object of type `object_type` that this resolver
+    /// returned, is complete: its scalar and enum fields hold values in
+    /// their canonical form and any objects nested in it are complete, too.
+    /// The executor takes the values of the fields of complete objects as
+    /// they are instead of resolving them one by one, and only checks them
+    /// against the schema. Resolvers that can't vouch for their objects
+    /// don't need to implement this.
+    fn is_complete_value(
+        &self,
+        _ctx: &QueryContext,
+        _object_type: &s::ObjectType,
+        _value: &q::Value,
+    ) -> bool {
+        false
+    }
+
     /// Resolves an enum value for a given enum type.
     fn resolve_enum_value(
         &self,
+        _ctx: &QueryContext,
         _field: &q::Field,
         _enum_type: &s::EnumType,
         value: Option<&q::Value>,
@@ -81,21 +300,37 @@ pub trait Resolver: Clone + Send + Sync {
         Ok(value.cloned().unwrap_or(q::Value::Null))
     }
 
-    /// Resolves a scalar value for a given scalar type.
+    /// Whether the default `resolve_scalar_value` and
+    /// `resolve_scalar_values` serialize values of built-in scalar types
+    /// into their canonical form. Values loaded from the store already are,
+    /// so this is off by default; resolvers that produce values themselves
+    /// can turn it on instead of overriding the scalar resolvers.
+    fn serializes_scalars(&self) -> bool {
+        false
+    }
+
+    /// Resolves a scalar value for a given scalar type. Values of built-in
+    /// scalar types are serialized into their canonical form if the
+    /// resolver `serializes_scalars`.
     fn resolve_scalar_value(
         &self,
+        _ctx: &QueryContext,
         _parent_object_type: &s::ObjectType,
         _parent: &BTreeMap<String, q::Value>,
         _field: &q::Field,
-        _scalar_type: &s::ScalarType,
+        scalar_type: &s::ScalarType,
         value: Option<&q::Value>,
     ) -> Result<q::Value, QueryExecutionError> {
-        Ok(value.cloned().unwrap_or(q::Value::Null))
+        Ok(match value {
+            Some(value) if self.serializes_scalars() => serialize_scalar_value(scalar_type, value),
+            value => value.cloned().unwrap_or(q::Value::Null),
+        })
     }
 
     /// Resolves a list of enum values for a given enum type.
     fn resolve_enum_values(
         &self,
+        _ctx: &QueryContext,
         _field: &q::Field,
         _enum_type: &s::EnumType,
         value: Option<&q::Value>,
@@ -103,42 +338,80 @@ pub trait Resolver: Clone + Send + Sync {
         Ok(value.cloned().unwrap_or(q::Value::Null))
     }
 
-    /// Resolves a list of scalar values for a given list type.
+    /// Resolves a list of scalar values for a given list type, serializing
+    /// the values like `resolve_scalar_value`.
     fn resolve_scalar_values(
         &self,
+        _ctx: &QueryContext,
         _field: &q::Field,
-        _scalar_type: &s::ScalarType,
+        scalar_type: &s::ScalarType,
         value: Option<&q::Value>,
     ) -> Result<q::Value, Vec<QueryExecutionError>> {
-        Ok(value.cloned().unwrap_or(q::Value::Null))
+        Ok(match value {
+            Some(q::Value::List(values)) if self.serializes_scalars() => q::Value::List(
+                values
+                    .iter()
+                    .map(|value| serialize_scalar_value(scalar_type, value))
+                    .collect(),
+            ),
+            value => value.cloned().unwrap_or(q::Value::Null),
+        })
     }
 
     // Resolves an abstract type into the specific type of an object.
     fn resolve_abstract_type<'a>(
         &self,
+        _ctx: &QueryContext,
         schema: &'a s::Document,
-        _abstract_type: &s::TypeDefinition,
+        abstract_type: &s::TypeDefinition,
         object_value: &q::Value,
     ) -> Option<&'a s::ObjectType> {
+        // Resolvers set `__typename` on all objects of abstract types; without
+        // it, the type can't be resolved
         let concrete_type_name = match object_value {
-            // All objects contain `__typename`
-            q::Value::Object(data) => match &data["__typename"] {
-                q::Value::String(name) => name.clone(),
-                _ => unreachable!("__typename must be a string"),
+            q::Value::Object(data) => match data.get("__typename") {
+                Some(q::Value::String(name)) => name,
+                _ => return None,
             },
-            _ => unreachable!("abstract type value must be an object"),
+            _ => return None,
         };
 
-        // A name returned in a `__typename` must exist in the schema.
-        match get_named_type(schema, &concrete_type_name).unwrap() {
-            s::TypeDefinition::Object(object) => Some(object),
-            _ => unreachable!("only objects may implement interfaces"),
+        // The concrete type must be one of the possible types of the abstract type
+        let object = match get_named_type(schema, concrete_type_name) {
+            Some(s::TypeDefinition::Object(object)) => object,
+            _ => return None,
+        };
+        let is_possible_type = match abstract_type {
+            s::TypeDefinition::Interface(interface) => {
+                object.implements_interfaces.contains(&interface.name)
+            }
+            s::TypeDefinition::Union(union) => union.types.contains(&object.name),
+            _ => false,
+        };
+        if is_possible_type {
+            Some(object)
+        } else {
+            None
         }
     }
 
+    /// Whether the resolver can resolve field streams, and therefore
+    /// subscriptions. Subscriptions against resolvers that can't are
+    /// rejected before anything is resolved.
+    fn supports_subscriptions(&self) -> bool {
+        false
+    }
+
+    /// Whether the resolver can apply mutations. Mutations against
+    /// resolvers that can't are rejected before anything is resolved.
+    fn supports_mutations(&self) -> bool {
+        false
+    }
+
     // Resolves a change stream for a given field.
     fn resolve_field_stream<'a, 'b>(
         &self,
+        _ctx: &QueryContext,
         _schema: &'a s::Document,
         _object_type: &'a s::ObjectType,
         _field: &'b q::Field,
@@ -147,4 +420,90 @@ pub trait Resolver: Clone + Send + Sync {
             "Resolving field streams is not supported by this resolver",
         )))
     }
+
+    // Checks whether the subgraph deployment that is being queried has
+    // failed, and returns the error that made it fail if it has.
+    fn check_deployment_health(
+        &self,
+        _ctx: &QueryContext,
+        _subgraph_id: &SubgraphDeploymentId,
+    ) -> Result<(), QueryExecutionError> {
+        Ok(())
+    }
+
+    // Returns how far the subgraph deployment that is being queried has
+    // synced, if the resolver knows.
+    fn sync_status(
+        &self,
+        _ctx: &QueryContext,
+        _subgraph_id: &SubgraphDeploymentId,
+    ) -> Option<DeploymentSyncStatus> {
+        None
+    }
+
+    // Resolves the top-level `_meta` field of the subgraph deployment that
+    // is being queried into a `_Meta_` object: the block the deployment has
+    // processed, its ID, whether it has failed and how far it has synced.
+    fn resolve_meta(
+        &self,
+        _ctx: &QueryContext,
+        _subgraph_id: &SubgraphDeploymentId,
+    ) -> Result<q::Value, QueryExecutionError> {
+        Err(QueryExecutionError::NotSupported(String::from(
+            "Resolving `_meta` is not supported by this resolver",
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use graph::prelude::{QueryExecutionError, Schema, SubgraphDeploymentId};
+
+    use super::ObjectOrInterface;
+    use crate::schema::ast::{get_interface_type_definitions, get_object_type_definitions};
+
+    const SCHEMA: &str = "
+        interface RegEntry { id: ID! }
+        type Meme implements RegEntry @entity { id: ID! }
+        type Vote @entity { id: ID! }
+        type ParamChange implements RegEntry @entity { id: ID! }";
+
+    #[test]
+    fn object_types_of_interface() {
+        let schema = Schema::parse(SCHEMA, SubgraphDeploymentId::new("dummy").unwrap()).unwrap();
+        let interface = get_interface_type_definitions(&schema.document)
+            .into_iter()
+            .find(|interface| interface.name == "RegEntry")
+            .expect("RegEntry interface not found");
+
+        let names: Vec<_> = ObjectOrInterface::Interface(interface)
+            .object_types(schema.types_for_interface())
+            .unwrap()
+            .into_iter()
+            .map(|object| object.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["Meme", "ParamChange"]);
+
+        // Without the implementing types, resolving the interface fails
+        match ObjectOrInterface::Interface(interface).object_types(&BTreeMap::new()) {
+            Err(QueryExecutionError::AbstractTypeError(name)) => assert_eq!(name, "RegEntry"),
+            result => panic!("expected an abstract type error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn object_types_of_object() {
+        let schema = Schema::parse(SCHEMA, SubgraphDeploymentId::new("dummy").unwrap()).unwrap();
+        let vote = get_object_type_definitions(&schema.document)
+            .into_iter()
+            .find(|object| object.name == "Vote")
+            .expect("Vote type not found");
+
+        let types = ObjectOrInterface::Object(vote)
+            .object_types(&BTreeMap::new())
+            .unwrap();
+        assert_eq!(types, vec![vote]);
+    }
 }
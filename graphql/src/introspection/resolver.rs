@@ -5,6 +5,7 @@ use graph::prelude::*;
 
 use crate::prelude::*;
 use crate::schema::ast as sast;
+use crate::values::coercion;
 
 type TypeObjectsMap = BTreeMap<String, q::Value>;
 
@@ -83,7 +84,7 @@ fn type_definition_object(
                 object_type_object(schema, type_objects, object_type)
             }
             s::TypeDefinition::Scalar(scalar_type) => scalar_type_object(scalar_type),
-            s::TypeDefinition::Union(union_type) => union_type_object(schema, union_type),
+            s::TypeDefinition::Union(union_type) => union_type_object(union_type),
         };
 
         type_objects.insert(type_name.to_owned(), type_object.clone());
@@ -165,14 +166,24 @@ fn interface_type_object(
             field_objects(schema, type_objects, &interface_type.fields),
         ),
         (
-            "possibleTypes",
+            "interfaces",
             q::Value::List(
-                schema.types_for_interface()[&interface_type.name]
+                schema
+                    .interfaces_for_type(&interface_type.name)
+                    .unwrap_or(&vec![])
                     .iter()
-                    .map(|object_type| q::Value::String(object_type.name.to_owned()))
+                    .map(|typedef| interface_type_object(schema, type_objects, typedef))
                     .collect(),
             ),
         ),
+        (
+            "possibleTypes",
+            possible_type_names(
+                schema.types_for_interface()[&interface_type.name]
+                    .iter()
+                    .map(|object_type| &object_type.name),
+            ),
+        ),
     ])
 }
 
@@ -274,7 +285,7 @@ fn scalar_type_object(scalar_type: &s::ScalarType) -> q::Value {
     ])
 }
 
-fn union_type_object(schema: &Schema, union_type: &s::UnionType) -> q::Value {
+fn union_type_object(union_type: &s::UnionType) -> q::Value {
     object_value(vec![
         ("name", q::Value::String(union_type.name.to_owned())),
         ("kind", q::Value::Enum(String::from("UNION"))),
@@ -287,22 +298,26 @@ fn union_type_object(schema: &Schema, union_type: &s::UnionType) -> q::Value {
         ),
         (
             "possibleTypes",
-            q::Value::List(
-                sast::get_object_type_definitions(&schema.document)
-                    .iter()
-                    .filter(|object_type| {
-                        object_type
-                            .implements_interfaces
-                            .iter()
-                            .any(|implemented_name| implemented_name == &union_type.name)
-                    })
-                    .map(|object_type| q::Value::String(object_type.name.to_owned()))
-                    .collect(),
-            ),
+            possible_type_names(union_type.types.iter()),
         ),
     ])
 }
 
+/// Lists the names of the object types that values of an abstract type can
+/// have, i.e. the implementations of an interface or the members of a union,
+/// in alphabetical order.
+fn possible_type_names<'a>(type_names: impl Iterator<Item = &'a s::Name>) -> q::Value {
+    let mut type_names: Vec<_> = type_names.collect();
+    type_names.sort();
+    type_names.dedup();
+    q::Value::List(
+        type_names
+            .into_iter()
+            .map(|name| q::Value::String(name.to_owned()))
+            .collect(),
+    )
+}
+
 fn schema_directive_objects(schema: &Schema, type_objects: &mut TypeObjectsMap) -> q::Value {
     q::Value::List(
         schema
@@ -382,12 +397,7 @@ fn input_value(
         ),
         (
             "defaultValue",
-            input_value
-                .default_value
-                .as_ref()
-                .map_or(q::Value::Null, |value| {
-                    q::Value::String(format!("{}", value))
-                }),
+            coercion::default_value_literal(input_value).map_or(q::Value::Null, q::Value::String),
         ),
     ])
 }
@@ -457,13 +467,13 @@ impl<'a> IntrospectionResolver<'a> {
 impl<'a> Resolver for IntrospectionResolver<'a> {
     fn resolve_objects(
         &self,
+        _ctx: &QueryContext,
         parent: &Option<q::Value>,
         field: &q::Name,
         _field_definition: &s::Field,
         _object_type: ObjectOrInterface<'_>,
         _arguments: &HashMap<&q::Name, q::Value>,
         _types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
-        _max_first: u32,
     ) -> Result<q::Value, QueryExecutionError> {
         match field.as_str() {
             "possibleTypes" => {
@@ -496,6 +506,7 @@ impl<'a> Resolver for IntrospectionResolver<'a> {
 
     fn resolve_object(
         &self,
+        _ctx: &QueryContext,
         parent: &Option<q::Value>,
         field: &q::Field,
         _field_definition: &s::Field,
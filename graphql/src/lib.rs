@@ -23,15 +23,31 @@ mod values;
 /// Utilities for querying `Store` components.
 mod store;
 
+/// A resolver over an in-memory dataset.
+mod memory;
+
 /// Prelude that exports the most important traits and types.
 pub mod prelude {
-    pub use super::execution::{ExecutionContext, ObjectOrInterface, Resolver};
+    pub use super::execution::{
+        AllowAll, Authorizer, Decision, ExecutionContext, ObjectOrInterface, QueryContext,
+        QueryMetrics, Resolver, Selection, StaticAuthorizer,
+    };
     pub use super::introspection::{introspection_schema, IntrospectionResolver};
-    pub use super::query::{execute_query, QueryExecutionOptions};
-    pub use super::schema::{api_schema, ast::validate_entity, APISchemaError};
+    pub use super::memory::InMemoryResolver;
+    pub use super::query::{
+        execute_query, execute_query_to_writer, normalize::NormalizedQuery, QueryExecutionOptions,
+    };
+    pub use super::schema::{
+        api_schema, api_schema_with_options, api_schema_with_pluralization,
+        ast::validate_entity, APISchemaError, ApiSchemaExt, ApiSchemaOptions, Pluralization,
+    };
     pub use super::store::{build_query, StoreResolver};
-    pub use super::subscription::{execute_subscription, SubscriptionExecutionOptions};
-    pub use super::values::{object_value, MaybeCoercible};
+    pub use super::subscription::{
+        execute_subscription, SubscriptionExecutionOptions, SubscriptionMultiplexer,
+    };
+    pub use super::values::{
+        object_value, scalar_serializer, serialize_scalar_value, MaybeCoercible, ScalarSerializer,
+    };
 
     pub use super::graphql_parser::{query::Name, schema::ObjectType};
 }
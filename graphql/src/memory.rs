@@ -0,0 +1,293 @@
+use graphql_parser::{query as q, schema as s};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+
+use graph::data::graphql::validation::get_base_type;
+use graph::prelude::*;
+
+use crate::execution::{ObjectOrInterface, QueryContext, Resolver};
+use crate::prelude::{Name, ObjectType};
+use crate::schema::ast::{self as sast, FilterOp};
+
+/// A resolver that serves a fixed dataset held in memory, which makes it
+/// possible to execute queries without a store, e.g. in tests and demos.
+///
+/// The dataset maps entity types to their objects. Objects refer to other
+/// objects by id, i.e., a field with an object type holds the id of the
+/// object and a list field the ids of the objects, though they may also
+/// hold the objects themselves. Objects get the type they are listed under
+/// as their `__typename` unless they have one already, so that objects of
+/// interfaces resolve to their concrete types; objects listed under an
+/// interface must have a `__typename`.
+///
+/// List fields support the `where`, `orderBy`, `orderDirection`, `skip` and
+/// `first` arguments of the API schema. Objects are ordered by `id` unless
+/// `orderBy` says otherwise, and by `id` among objects with the same value
+/// of the `orderBy` field, like the store does. Lists of an interface are
+/// ordered across all the types that implement it before they are
+/// paginated.
+#[derive(Clone, Debug, Default)]
+pub struct InMemoryResolver {
+    entities: HashMap<String, Vec<q::Value>>,
+}
+
+impl InMemoryResolver {
+    pub fn new(entities: HashMap<String, Vec<q::Value>>) -> Self {
+        InMemoryResolver { entities }
+    }
+
+    /// The objects listed under `type_name`.
+    fn objects_listed_under(&self, type_name: &str) -> impl Iterator<Item = q::Value> + '_ {
+        let type_name = type_name.to_owned();
+        self.entities
+            .get(&type_name)
+            .into_iter()
+            .flatten()
+            .map(move |object| with_typename(object.clone(), &type_name))
+    }
+
+    /// All objects of `object_type`, including those of the object types
+    /// that implement it if it is an interface.
+    fn objects_of_type(
+        &self,
+        object_type: ObjectOrInterface<'_>,
+        types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+    ) -> Result<Vec<q::Value>, QueryExecutionError> {
+        let mut objects: Vec<q::Value> = self.objects_listed_under(object_type.name()).collect();
+        if let ObjectOrInterface::Interface(_) = object_type {
+            for implementation in object_type.object_types(types_for_interface)? {
+                objects.extend(self.objects_listed_under(&implementation.name));
+            }
+        }
+        Ok(objects)
+    }
+
+    /// Resolves `value`, an id or an object, to an object of `object_type`.
+    fn dereference(
+        &self,
+        value: &q::Value,
+        object_type: ObjectOrInterface<'_>,
+        types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+    ) -> Result<Option<q::Value>, QueryExecutionError> {
+        Ok(match value {
+            q::Value::String(id) => self
+                .objects_of_type(object_type, types_for_interface)?
+                .into_iter()
+                .find(|object| has_id(object, id)),
+            q::Value::Object(_) => Some(with_typename(value.clone(), object_type.name())),
+            _ => None,
+        })
+    }
+}
+
+impl Resolver for InMemoryResolver {
+    /// The values of the dataset are given by hand and may not be in the
+    /// canonical form of their scalar types.
+    fn serializes_scalars(&self) -> bool {
+        true
+    }
+
+    fn resolve_objects(
+        &self,
+        _ctx: &QueryContext,
+        parent: &Option<q::Value>,
+        field: &q::Name,
+        _field_definition: &s::Field,
+        object_type: ObjectOrInterface<'_>,
+        arguments: &HashMap<&q::Name, q::Value>,
+        types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        let mut objects = match parent {
+            None => self.objects_of_type(object_type, types_for_interface)?,
+            Some(parent) => match field_value(parent, field) {
+                Some(q::Value::List(values)) => {
+                    let mut objects = vec![];
+                    for value in values {
+                        objects.extend(self.dereference(
+                            value,
+                            object_type,
+                            types_for_interface,
+                        )?);
+                    }
+                    objects
+                }
+                _ => vec![],
+            },
+        };
+
+        if let Some(q::Value::Object(filter)) = arguments.get(&"where".to_string()) {
+            objects.retain(|object| matches_filter(object, object_type, filter));
+        }
+
+        let order_by = match arguments.get(&"orderBy".to_string()) {
+            Some(q::Value::Enum(name)) | Some(q::Value::String(name)) => name.as_str(),
+            _ => "id",
+        };
+        let descending = match arguments.get(&"orderDirection".to_string()) {
+            Some(q::Value::Enum(direction)) | Some(q::Value::String(direction)) => {
+                direction == "desc"
+            }
+            _ => false,
+        };
+        let compare_fields = |a: &q::Value, b: &q::Value, field: &str| {
+            compare_values(
+                field_value(a, field).unwrap_or(&q::Value::Null),
+                field_value(b, field).unwrap_or(&q::Value::Null),
+                sast::get_field(object_type, &field.to_owned()),
+            )
+            .unwrap_or(Ordering::Equal)
+        };
+        objects.sort_by(|a, b| {
+            let order = compare_fields(a, b, order_by);
+            let order = if descending { order.reverse() } else { order };
+            order.then_with(|| compare_fields(a, b, "id"))
+        });
+
+        let skip = int_argument(arguments, "skip").unwrap_or(0);
+        let first = int_argument(arguments, "first").unwrap_or(objects.len());
+        Ok(q::Value::List(
+            objects.into_iter().skip(skip).take(first).collect(),
+        ))
+    }
+
+    fn resolve_object(
+        &self,
+        _ctx: &QueryContext,
+        parent: &Option<q::Value>,
+        field: &q::Field,
+        _field_definition: &s::Field,
+        object_type: ObjectOrInterface<'_>,
+        arguments: &HashMap<&q::Name, q::Value>,
+        types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        let reference = match arguments.get(&"id".to_string()) {
+            Some(id) => Some(id),
+            None => parent
+                .as_ref()
+                .and_then(|parent| field_value(parent, &field.name)),
+        };
+
+        Ok(match reference {
+            Some(reference) => self
+                .dereference(reference, object_type, types_for_interface)?
+                .unwrap_or(q::Value::Null),
+            None => q::Value::Null,
+        })
+    }
+}
+
+/// Sets the `__typename` of `object` to `type_name` unless it has one.
+fn with_typename(mut object: q::Value, type_name: &str) -> q::Value {
+    if let q::Value::Object(map) = &mut object {
+        map.entry(String::from("__typename"))
+            .or_insert_with(|| q::Value::String(type_name.to_owned()));
+    }
+    object
+}
+
+fn field_value<'a>(object: &'a q::Value, field: &str) -> Option<&'a q::Value> {
+    match object {
+        q::Value::Object(map) => map.get(field),
+        _ => None,
+    }
+}
+
+fn has_id(object: &q::Value, id: &str) -> bool {
+    match field_value(object, "id") {
+        Some(q::Value::String(object_id)) => object_id == id,
+        _ => false,
+    }
+}
+
+fn int_argument(arguments: &HashMap<&q::Name, q::Value>, name: &str) -> Option<usize> {
+    match arguments.get(&name.to_string()) {
+        Some(q::Value::Int(n)) => n.as_i64().map(|n| n.max(0) as usize),
+        _ => None,
+    }
+}
+
+/// Converts `value`, a value of `field`, to a store value, so that values
+/// are compared the way the store compares them, e.g. `BigInt`s by their
+/// numeric value. Returns `None` for values that aren't of the type of the
+/// field, like objects given in place of their id, and for unknown fields.
+fn store_value(value: &q::Value, field: Option<&s::Field>) -> Option<Value> {
+    let named_type = s::Type::NamedType(get_base_type(&field?.field_type).to_owned());
+    Value::from_query_value(value, &named_type).ok()
+}
+
+/// Orders values of `field` like the store does; see `Value::compare`.
+fn compare_values(a: &q::Value, b: &q::Value, field: Option<&s::Field>) -> Option<Ordering> {
+    store_value(a, field)?.compare(&store_value(b, field)?)
+}
+
+fn values_equal(a: &q::Value, b: &q::Value, field: Option<&s::Field>) -> bool {
+    match (store_value(a, field), store_value(b, field)) {
+        (Some(a), Some(b)) => a
+            .compare(&b)
+            .map_or(a == b, |ordering| ordering == Ordering::Equal),
+        _ => a == b,
+    }
+}
+
+/// Whether `object` matches all conditions of the `where` argument `filter`.
+fn matches_filter(
+    object: &q::Value,
+    object_type: ObjectOrInterface<'_>,
+    filter: &BTreeMap<q::Name, q::Value>,
+) -> bool {
+    filter.iter().all(|(key, expected)| {
+        let (field, op) = sast::parse_field_as_filter(key);
+        let actual = field_value(object, &field).unwrap_or(&q::Value::Null);
+        matches_op(actual, op, expected, sast::get_field(object_type, &field))
+    })
+}
+
+fn matches_op(
+    actual: &q::Value,
+    op: FilterOp,
+    expected: &q::Value,
+    field: Option<&s::Field>,
+) -> bool {
+    use self::FilterOp::*;
+
+    let ordering = match (actual, expected) {
+        // Nothing is greater or less than a missing value
+        (q::Value::Null, _) | (_, q::Value::Null) => None,
+        _ => compare_values(actual, expected, field),
+    };
+    let equal = |a: &q::Value, b: &q::Value| values_equal(a, b, field);
+    let is_in = |values: &q::Value| match values {
+        q::Value::List(values) => values.iter().any(|value| equal(actual, value)),
+        _ => false,
+    };
+    let contains = || match (actual, expected) {
+        (q::Value::String(actual), q::Value::String(expected)) => {
+            actual.contains(expected.as_str())
+        }
+        (q::Value::List(actual), q::Value::List(expected)) => expected
+            .iter()
+            .all(|value| actual.iter().any(|element| equal(element, value))),
+        _ => false,
+    };
+    let string_test = |test: fn(&str, &str) -> bool| match (actual, expected) {
+        (q::Value::String(actual), q::Value::String(expected)) => test(actual, expected),
+        _ => false,
+    };
+
+    match op {
+        Equal => equal(actual, expected),
+        Not => !equal(actual, expected),
+        GreaterThan => ordering == Some(Ordering::Greater),
+        LessThan => ordering == Some(Ordering::Less),
+        GreaterOrEqual => ordering.map_or(false, |ordering| ordering != Ordering::Less),
+        LessOrEqual => ordering.map_or(false, |ordering| ordering != Ordering::Greater),
+        In => is_in(expected),
+        NotIn => !is_in(expected),
+        Contains => contains(),
+        NotContains => !contains(),
+        StartsWith => string_test(|actual, expected| actual.starts_with(expected)),
+        NotStartsWith => !string_test(|actual, expected| actual.starts_with(expected)),
+        EndsWith => string_test(|actual, expected| actual.ends_with(expected)),
+        NotEndsWith => !string_test(|actual, expected| actual.ends_with(expected)),
+    }
+}
@@ -1,37 +1,51 @@
+use graph::data::graphql::SerializableValue;
 use graph::prelude::*;
 use graphql_parser::{query as q, Style};
-use std::time::Instant;
+use std::io;
 use uuid::Uuid;
 
 use crate::execution::*;
 use crate::query::ast as qast;
+use crate::query::normalize::NormalizedQuery;
+use crate::schema::api::META_FIELD;
 use crate::schema::ast as sast;
 
 /// Utilities for working with GraphQL query ASTs.
 pub mod ast;
 
+/// Canonical forms of queries, e.g. to recognize repetitions of a query.
+pub mod normalize;
+
 /// Options available for query execution.
 pub struct QueryExecutionOptions<R>
 where
     R: Resolver,
 {
-    /// The logger to use during query execution.
-    pub logger: Logger,
+    /// The context of the query execution, i.e. the logger, deadline and
+    /// limits to use, and any state shared by the resolver across fields.
+    pub context: QueryContext,
 
     /// The resolver to use.
     pub resolver: R,
 
-    /// Time at which the query times out.
-    pub deadline: Option<Instant>,
-
     /// Maximum complexity for a query.
     pub max_complexity: Option<u64>,
 
     /// Maximum depth for a query.
     pub max_depth: u8,
 
-    /// Maximum value for the `first` argument.
-    pub max_first: u32,
+    /// Maximum approximate size of the response in bytes. Execution stops
+    /// once the response grows beyond it, and the data gathered until then
+    /// is returned along with an error.
+    pub max_response_size: Option<usize>,
+
+    /// Maximum number of fields in a single selection set, counting each
+    /// response key once and including the fields of its fragments.
+    pub max_fields_per_selection: Option<usize>,
+
+    /// Decides which fields the query may select; use `AllowAll` to not
+    /// restrict access.
+    pub authorizer: Arc<dyn Authorizer>,
 }
 
 /// Executes a query and returns a result.
@@ -39,11 +53,195 @@ pub fn execute_query<R>(query: &Query, options: QueryExecutionOptions<R>) -> Que
 where
     R: Resolver,
 {
-    let query_id = Uuid::new_v4().to_string();
-    let query_logger = options.logger.new(o!(
+    execute_query_with(query, options, |ctx, selection_set| {
+        execute_root_selection_set(ctx, selection_set, &None)
+    })
+}
+
+/// Executes a query and writes its result to `writer` as JSON.
+///
+/// If `stream_batch_size` is set in the context and the query consists of
+/// a single collection field of entities that selects no lists from them,
+/// the entities are loaded and written in batches of that size, ordered by
+/// their ID, so that the memory needed doesn't grow with the number of
+/// entities. Once the entities written exceed the maximum response size, no
+/// further batches are loaded and the result reports the error. Other
+/// queries are executed like `execute_query` does, with a warning about why
+/// they weren't streamed in the `extensions` of the result.
+pub fn execute_query_to_writer<R, W>(
+    query: &Query,
+    options: QueryExecutionOptions<R>,
+    writer: &mut W,
+) -> io::Result<()>
+where
+    R: Resolver,
+    W: io::Write,
+{
+    let batch_size = match options.context.stream_batch_size {
+        Some(batch_size) if !query.dry_run => batch_size,
+        _ => return write_result(writer, &execute_query(query, options)),
+    };
+
+    let mut warning = None;
+    let mut started = false;
+    let mut write_error = None;
+    let mut result = execute_query_with(query, options, |ctx, selection_set| {
+        let streamable = match streamable_root_field(ctx, selection_set) {
+            Ok(streamable) => streamable,
+            Err(reason) => {
+                warning = Some(format!("The query was not streamed: {}", reason));
+                return execute_root_selection_set(ctx, selection_set, &None);
+            }
+        };
+
+        // The opening of the response is written with the first entity, so
+        // that nothing is written if the resolver can't stream entities
+        let mut write_value = |value: &q::Value| -> io::Result<()> {
+            if started {
+                writer.write_all(b",")?;
+            } else {
+                started = true;
+                write_streamed_data_opening(writer, streamable.response_key)?;
+            }
+            serde_json::to_writer(&mut *writer, &SerializableValue(value))?;
+            Ok(())
+        };
+        let result = execute_field_in_batches(ctx, &streamable, batch_size, &mut |values| {
+            for value in values.iter() {
+                if let Err(e) = write_value(value) {
+                    let message = e.to_string();
+                    write_error = Some(e);
+                    return Err(QueryExecutionError::ResponseWriteError(message));
+                }
+            }
+            ctx.response_size.check()
+        });
+
+        match result {
+            Err(errors) if !started => match errors.as_slice() {
+                [QueryExecutionError::NotSupported(reason)] => {
+                    warning = Some(format!("The query was not streamed: {}", reason));
+                    execute_root_selection_set(ctx, selection_set, &None)
+                }
+                _ => Err(errors),
+            },
+            // The data has been written, or will be written as an empty
+            // list; errors are reported alongside it
+            result => {
+                if !started {
+                    started = true;
+                    if let Err(e) = write_streamed_data_opening(writer, streamable.response_key) {
+                        write_error = Some(e);
+                    }
+                }
+                result.map(|()| q::Value::Null)
+            }
+        }
+    });
+
+    if let Some(e) = write_error {
+        return Err(e);
+    }
+
+    if let Some(warning) = warning {
+        result
+            .extensions
+            .get_or_insert_with(QueryExtensions::default)
+            .warnings
+            .get_or_insert_with(Vec::new)
+            .push(warning);
+    }
+
+    if !started {
+        return write_result(writer, &result);
+    }
+
+    // Close the streamed data and add the rest of the result
+    writer.write_all(b"]}")?;
+    if let Some(errors) = &result.errors {
+        writer.write_all(b",\"errors\":")?;
+        serde_json::to_writer(&mut *writer, errors)?;
+    }
+    if let Some(extensions) = &result.extensions {
+        writer.write_all(b",\"extensions\":")?;
+        serde_json::to_writer(&mut *writer, extensions)?;
+    }
+    writer.write_all(b"}")
+}
+
+fn write_result<W: io::Write>(writer: &mut W, result: &QueryResult) -> io::Result<()> {
+    serde_json::to_writer(writer, result).map_err(io::Error::from)
+}
+
+/// Writes the beginning of a result whose data is the list of values of the
+/// field `response_key`, up to the opening bracket of the list.
+fn write_streamed_data_opening<W: io::Write>(writer: &mut W, response_key: &str) -> io::Result<()> {
+    writer.write_all(b"{\"data\":{")?;
+    serde_json::to_writer(&mut *writer, response_key)?;
+    writer.write_all(b":[")
+}
+
+/// Executes a query, using `execute_data` to execute the root selection set
+/// of queries that fetch data. Each error is reported once, in a fixed order.
+///
+/// The query gets a new ID unless it has one already. The ID tags the log
+/// messages of the execution and is returned in the `extensions` of the
+/// result.
+fn execute_query_with<R, F>(
+    query: &Query,
+    options: QueryExecutionOptions<R>,
+    execute_data: F,
+) -> QueryResult
+where
+    R: Resolver,
+    F: for<'a> FnOnce(
+        &ExecutionContext<'a, R>,
+        &'a q::SelectionSet,
+    ) -> Result<q::Value, Vec<QueryExecutionError>>,
+{
+    let query_id = query
+        .query_id
+        .clone()
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    let mut result = execute_operation_with(query, options, query_id.clone(), execute_data);
+    result.dedup_errors();
+    result
+        .extensions
+        .get_or_insert_with(QueryExtensions::default)
+        .query_id = Some(query_id);
+    result
+}
+
+fn execute_operation_with<R, F>(
+    query: &Query,
+    options: QueryExecutionOptions<R>,
+    query_id: String,
+    execute_data: F,
+) -> QueryResult
+where
+    R: Resolver,
+    F: for<'a> FnOnce(
+        &ExecutionContext<'a, R>,
+        &'a q::SelectionSet,
+    ) -> Result<q::Value, Vec<QueryExecutionError>>,
+{
+    let mut context = options.context;
+    context.logger = context.logger.new(o!(
         "subgraph_id" => (*query.schema.id).clone(),
-        "query_id" => query_id
+        "query_id" => query_id.clone()
     ));
+    context.query_id = Some(query_id);
+
+    // Count the query as in-flight work of a shutdown, so that the shutdown
+    // waits for it; once the shutdown has started, no new queries are run
+    let _in_flight = match &context.shutdown {
+        Some(shutdown) => match shutdown.begin_work() {
+            Some(work) => Some(work),
+            None => return QueryResult::from(QueryExecutionError::ShuttingDown),
+        },
+        None => None,
+    };
 
     // Obtain the only operation of the query (fail if there is none or more than one)
     let operation = match qast::get_operation(&query.document, None) {
@@ -60,16 +258,27 @@ where
 
     // Create a fresh execution context
     let ctx = ExecutionContext {
-        logger: query_logger.clone(),
+        query: Arc::new(context),
         resolver: Arc::new(options.resolver),
         schema: query.schema.clone(),
         document: &query.document,
         fields: vec![],
         variable_values: Arc::new(coerced_variable_values),
-        deadline: options.deadline,
-        max_first: options.max_first,
+        response_size: Arc::new(ResponseSize::new(options.max_response_size)),
+        authorizer: options.authorizer,
     };
 
+    // The error of a failed deployment, if the query allows returning data
+    // regardless of it
+    let mut deployment_error = None;
+
+    // The cost estimate of a dry run
+    let mut cost_estimate = None;
+
+    // The warning about a deployment that is far behind the chain head, if
+    // the query asked for it
+    let mut sync_warning = None;
+
     let result = match operation {
         // Execute top-level `query { ... }` and `{ ... }` expressions.
         q::OperationDefinition::Query(q::Query { selection_set, .. })
@@ -81,12 +290,22 @@ where
                 return QueryResult::from(validation_errors);
             }
 
-            let complexity = ctx.root_query_complexity(root_type, selection_set, options.max_depth);
+            let complexity = ctx
+                .root_query_complexity(root_type, selection_set, options.max_depth)
+                .and_then(|complexity| {
+                    ctx.check_fields_per_selection(
+                        selection_set,
+                        options.max_fields_per_selection,
+                        options.max_depth,
+                    )
+                    .map(|()| complexity)
+                });
 
             info!(
-                query_logger,
+                ctx.query.logger,
                 "Execute query";
                 "query" => query.document.format(&Style::default().indent(0)).replace('\n', " "),
+                "query_shape" => format!("{:016x}", NormalizedQuery::new(&query.document).shape_hash),
                 "complexity" => format!("{:?}", complexity),
             );
 
@@ -98,17 +317,163 @@ where
                         max_complexity,
                     )])
                 }
-                (Ok(_), _) => execute_root_selection_set(&ctx, selection_set, &None),
+                // Dry runs only estimate the cost of the query; they neither
+                // check the health of the deployment nor fetch any data
+                (Ok(complexity), _) if query.dry_run => ctx
+                    .root_query_cost_estimate(root_type, selection_set, complexity)
+                    .map(|estimate| {
+                        cost_estimate = Some(estimate);
+                        q::Value::Null
+                    }),
+                (Ok(_), _) => {
+                    match check_deployment_health(&ctx, &query.schema.id, selection_set) {
+                        Ok(error) => {
+                            deployment_error = error;
+                            if query.sync_warning {
+                                sync_warning = check_sync_status(&ctx, &query.schema.id);
+                            }
+                            execute_data(&ctx, selection_set)
+                        }
+                        Err(e) => Err(vec![e]),
+                    }
+                }
             }
         }
-        // Everything else (e.g. mutations) is unsupported
+        // Mutations fail early if the resolver can't apply them
+        q::OperationDefinition::Mutation(_) if !ctx.resolver.supports_mutations() => {
+            Err(vec![QueryExecutionError::NotSupported(
+                "Mutations are not supported for this query".to_string(),
+            )])
+        }
+        // Everything else (e.g. subscriptions) is unsupported
         _ => Err(vec![QueryExecutionError::NotSupported(
             "Only queries are supported".to_string(),
         )]),
     };
 
-    match result {
+    let mut result = match result {
         Ok(value) => QueryResult::new(Some(value)),
         Err(e) => QueryResult::from(e),
+    };
+
+    if let Some(estimate) = cost_estimate {
+        result
+            .extensions
+            .get_or_insert_with(QueryExtensions::default)
+            .cost_estimate = Some(estimate);
+    }
+
+    if let Some(warning) = sync_warning {
+        result
+            .extensions
+            .get_or_insert_with(QueryExtensions::default)
+            .warnings
+            .get_or_insert_with(Vec::new)
+            .push(warning);
+    }
+
+    // Report a response that was cut short alongside the partial data
+    if let Err(e) = ctx.response_size.check() {
+        result
+            .errors
+            .get_or_insert_with(Vec::new)
+            .push(QueryError::from(e));
+    }
+
+    // Report the failure of the deployment alongside the data
+    if let Some(e) = deployment_error {
+        result
+            .errors
+            .get_or_insert_with(Vec::new)
+            .push(QueryError::from(e));
+    }
+    result
+}
+
+/// Returns a warning if the deployment is more than `sync_warning_blocks`
+/// behind the chain head, so that clients can tell that its data is still
+/// incomplete rather than missing.
+fn check_sync_status<R>(
+    ctx: &ExecutionContext<'_, R>,
+    subgraph_id: &SubgraphDeploymentId,
+) -> Option<String>
+where
+    R: Resolver,
+{
+    let status = ctx.resolver.sync_status(&ctx.query, subgraph_id)?;
+    if status.blocks_behind() <= ctx.query.sync_warning_blocks {
+        return None;
+    }
+
+    let estimate = match status.estimated_seconds_to_sync() {
+        Some(seconds) => format!("it is estimated to catch up in {} seconds", seconds),
+        None => String::from("there is no estimate yet of when it catches up"),
+    };
+    Some(format!(
+        "The deployment `{}` is still syncing and its data may be incomplete: \
+         it has indexed up to block {} of {} ({} blocks behind) and {}",
+        subgraph_id,
+        status.latest_block,
+        status.chain_head_block,
+        status.blocks_behind(),
+        estimate
+    ))
+}
+
+/// Checks the health of the deployment if the query requests any data from
+/// it, which is not the case for queries that only introspect the schema or
+/// ask for `_meta`.
+///
+/// If the deployment has failed, its error is returned as `Err` unless all
+/// top-level fields pass `subgraphError: allow`, in which case it is returned
+/// as `Ok(Some(..))` so that it can be reported together with the data.
+fn check_deployment_health<R>(
+    ctx: &ExecutionContext<'_, R>,
+    subgraph_id: &SubgraphDeploymentId,
+    selection_set: &q::SelectionSet,
+) -> Result<Option<QueryExecutionError>, QueryExecutionError>
+where
+    R: Resolver,
+{
+    let mut queries_data = false;
+    let mut allow_errors = true;
+    for selection in &selection_set.items {
+        match selection {
+            q::Selection::Field(field)
+                if field.name.starts_with("__") || field.name == META_FIELD => {}
+            q::Selection::Field(field) => {
+                queries_data = true;
+                let policy = match qast::get_argument_value(&field.arguments, "subgraphError") {
+                    Some(q::Value::Variable(name)) => ctx.variable_values.get(name),
+                    value => value,
+                };
+                allow_errors &= match policy {
+                    Some(q::Value::Enum(policy)) | Some(q::Value::String(policy)) => {
+                        policy == "allow"
+                    }
+                    _ => false,
+                };
+            }
+            // Fragments are not inspected, so they never allow errors
+            _ => {
+                queries_data = true;
+                allow_errors = false;
+            }
+        }
+    }
+
+    if !queries_data {
+        return Ok(None);
+    }
+
+    match ctx
+        .resolver
+        .check_deployment_health(&ctx.query, subgraph_id)
+    {
+        Ok(()) => Ok(None),
+        Err(e) => match e {
+            QueryExecutionError::SubgraphFailed { .. } if allow_errors => Ok(Some(e)),
+            _ => Err(e),
+        },
     }
 }
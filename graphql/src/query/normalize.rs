@@ -0,0 +1,233 @@
+use graphql_parser::{query as q, Style};
+
+/// A query in canonical form. Queries that differ only in whitespace,
+/// comments, the order of arguments or aliases that repeat the field name
+/// have the same canonical form.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NormalizedQuery {
+    /// The query on a single line, with every literal value replaced by a
+    /// placeholder `$_<n>` for the `n`-th entry in `literals`.
+    pub text: String,
+
+    /// The literal values of the query, in the order of their placeholders.
+    pub literals: Vec<q::Value>,
+
+    /// Hash of `text`, the same for all queries that only differ in their
+    /// literal values.
+    pub shape_hash: u64,
+
+    /// Hash of `text` and `literals`, the same only for queries with the
+    /// same canonical form.
+    pub hash: u64,
+}
+
+impl NormalizedQuery {
+    pub fn new(document: &q::Document) -> Self {
+        let mut document = document.clone();
+        let mut literals = vec![];
+        for definition in document.definitions.iter_mut() {
+            match definition {
+                q::Definition::Operation(operation) => {
+                    normalize_operation(operation, &mut literals)
+                }
+                q::Definition::Fragment(fragment) => {
+                    normalize_directives(&mut fragment.directives, &mut literals);
+                    normalize_selection_set(&mut fragment.selection_set, &mut literals);
+                }
+            }
+        }
+
+        let text = document
+            .format(&Style::default().indent(0))
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        let shape_hash = stable_hash(0, text.as_bytes());
+        let hash = literals.iter().fold(shape_hash, |hash, literal| {
+            stable_hash(hash, format!("{:?}", literal).as_bytes())
+        });
+
+        NormalizedQuery {
+            text,
+            literals,
+            shape_hash,
+            hash,
+        }
+    }
+}
+
+/// FNV-1a hash of `bytes`, continuing from `hash`. Unlike the hashers in
+/// `std`, it is guaranteed to produce the same hashes in every process.
+fn stable_hash(hash: u64, bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    bytes.iter().fold(hash ^ OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(PRIME)
+    })
+}
+
+fn normalize_operation(operation: &mut q::OperationDefinition, literals: &mut Vec<q::Value>) {
+    let (variable_definitions, directives, selection_set) = match operation {
+        q::OperationDefinition::SelectionSet(selection_set) => {
+            return normalize_selection_set(selection_set, literals)
+        }
+        q::OperationDefinition::Query(query) => (
+            &mut query.variable_definitions,
+            &mut query.directives,
+            &mut query.selection_set,
+        ),
+        q::OperationDefinition::Mutation(mutation) => (
+            &mut mutation.variable_definitions,
+            &mut mutation.directives,
+            &mut mutation.selection_set,
+        ),
+        q::OperationDefinition::Subscription(subscription) => (
+            &mut subscription.variable_definitions,
+            &mut subscription.directives,
+            &mut subscription.selection_set,
+        ),
+    };
+
+    variable_definitions.sort_by(|a, b| a.name.cmp(&b.name));
+    for definition in variable_definitions.iter_mut() {
+        if let Some(default_value) = definition.default_value.as_mut() {
+            normalize_value(default_value, literals);
+        }
+    }
+    normalize_directives(directives, literals);
+    normalize_selection_set(selection_set, literals);
+}
+
+/// Normalizes the selections without reordering them, since their order
+/// determines the order of the fields in the response.
+fn normalize_selection_set(selection_set: &mut q::SelectionSet, literals: &mut Vec<q::Value>) {
+    for selection in selection_set.items.iter_mut() {
+        match selection {
+            q::Selection::Field(field) => {
+                if field.alias.as_ref() == Some(&field.name) {
+                    field.alias = None;
+                }
+                normalize_arguments(&mut field.arguments, literals);
+                normalize_directives(&mut field.directives, literals);
+                normalize_selection_set(&mut field.selection_set, literals);
+            }
+            q::Selection::FragmentSpread(spread) => {
+                normalize_directives(&mut spread.directives, literals)
+            }
+            q::Selection::InlineFragment(fragment) => {
+                normalize_directives(&mut fragment.directives, literals);
+                normalize_selection_set(&mut fragment.selection_set, literals);
+            }
+        }
+    }
+}
+
+fn normalize_directives(directives: &mut Vec<q::Directive>, literals: &mut Vec<q::Value>) {
+    for directive in directives.iter_mut() {
+        normalize_arguments(&mut directive.arguments, literals);
+    }
+}
+
+fn normalize_arguments(arguments: &mut Vec<(q::Name, q::Value)>, literals: &mut Vec<q::Value>) {
+    arguments.sort_by(|(a, _), (b, _)| a.cmp(b));
+    for (_, value) in arguments.iter_mut() {
+        normalize_value(value, literals);
+    }
+}
+
+/// Replaces the literals in `value` with placeholders. The keys of object
+/// values are already sorted, and lists keep their order since it can be
+/// significant.
+fn normalize_value(value: &mut q::Value, literals: &mut Vec<q::Value>) {
+    match value {
+        q::Value::Variable(_) => (),
+        q::Value::List(values) => {
+            for value in values.iter_mut() {
+                normalize_value(value, literals);
+            }
+        }
+        q::Value::Object(fields) => {
+            for value in fields.values_mut() {
+                normalize_value(value, literals);
+            }
+        }
+        q::Value::Int(_)
+        | q::Value::Float(_)
+        | q::Value::String(_)
+        | q::Value::Boolean(_)
+        | q::Value::Null
+        | q::Value::Enum(_) => {
+            let placeholder = q::Value::Variable(format!("_{}", literals.len()));
+            literals.push(std::mem::replace(value, placeholder));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalize(query: &str) -> NormalizedQuery {
+        NormalizedQuery::new(&graphql_parser::parse_query(query).unwrap())
+    }
+
+    #[test]
+    fn equivalent_queries_have_the_same_hash() {
+        let query = normalize(
+            r#"{ musicians(first: 10, where: { name: "John", id_in: ["m1", "m2"] }) { id name } }"#,
+        );
+        let equivalent = normalize(
+            r#"
+            # The musicians
+            {
+              musicians(
+                where: { id_in: ["m1", "m2"], name: "John" },
+                first: 10
+              ) {
+                id: id
+                name
+              }
+            }
+            "#,
+        );
+
+        assert_eq!(query, equivalent);
+        assert_eq!(
+            query.text,
+            "{ musicians(first: $_0, where: {id_in: [$_1, $_2], name: $_3}) { id name } }"
+        );
+        assert_eq!(
+            query.literals,
+            vec![
+                q::Value::Int(10.into()),
+                q::Value::String("m1".to_owned()),
+                q::Value::String("m2".to_owned()),
+                q::Value::String("John".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn queries_with_different_arguments_have_different_hashes() {
+        let query = normalize("{ musicians(first: 10) { id } }");
+        let other_value = normalize("{ musicians(first: 20) { id } }");
+        let other_argument = normalize("{ musicians(skip: 10) { id } }");
+
+        // Only the values differ, the queries have the same shape
+        assert_ne!(query.hash, other_value.hash);
+        assert_eq!(query.shape_hash, other_value.shape_hash);
+
+        assert_ne!(query.hash, other_argument.hash);
+        assert_ne!(query.shape_hash, other_argument.shape_hash);
+    }
+
+    #[test]
+    fn aliases_and_field_order_are_kept() {
+        let query = normalize("{ a: musicians { id } b: musicians { name } }");
+        let swapped = normalize("{ b: musicians { name } a: musicians { id } }");
+
+        assert_eq!(query.text, "{ a: musicians { id } b: musicians { name } }");
+        assert_ne!(query.hash, swapped.hash);
+    }
+}
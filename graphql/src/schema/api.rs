@@ -1,40 +1,271 @@
+use std::collections::HashMap;
+
 use crate::schema::ast;
+use crate::schema::plural::pluralize;
+use graph::data::schema::API_SCHEMA_DIRECTIVE;
 use graph::prelude::*;
 use graphql_parser::schema::{Value, *};
 use graphql_parser::Pos;
 use inflector::Inflector;
 
+/// The name of the `Query` field that reports the state of the deployment.
+pub(crate) const META_FIELD: &str = "_meta";
+
+/// The type of the `_meta` field.
+pub(crate) const META_TYPE: &str = "_Meta_";
+
+/// The type of the block that `_meta` reports.
+pub(crate) const BLOCK_TYPE: &str = "_Block_";
+
 #[derive(Fail, Debug)]
 pub enum APISchemaError {
     #[fail(display = "type {} already exists in the input schema", _0)]
     TypeExists(String),
     #[fail(display = "Type {} not found", _0)]
     TypeNotFound(String),
+    #[fail(display = "the plural of type {} must be a non-empty string", _0)]
+    InvalidPluralName(String),
+    #[fail(
+        display = "types {} and {} both generate the query field `{}`; \
+                   use `@entity(plural: ...)` to rename one of them",
+        _0, _1, _2
+    )]
+    QueryFieldCollision(String, String, String),
+    #[fail(
+        display = "the plural of type {} is the same as its singular, so both \
+                   generate the query field `{}`; use `@entity(plural: ...)` \
+                   to give it a different plural",
+        _0, _1
+    )]
+    PluralEqualsSingular(String, String),
+    #[fail(
+        display = "invalid `@defaultOrder` for type {}; `by` must be a field of the type \
+                   and `direction` either `asc` or `desc`",
+        _0
+    )]
+    InvalidDefaultOrder(String),
+}
+
+/// The rules used to name the collection fields (e.g. `users`) of types.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pluralization {
+    /// The rules of the `Inflector` crate. Deployments that predate the
+    /// English rules keep using these so that their API doesn't change.
+    Legacy,
+
+    /// English pluralization of the last word of the type name, which can be
+    /// overridden with `@entity(plural: "...")`.
+    English,
+}
+
+impl Pluralization {
+    /// Subgraphs with a data source whose mapping uses API version 0.0.4 or
+    /// later use the English rules.
+    pub fn for_api_versions<'a>(api_versions: impl IntoIterator<Item = &'a str>) -> Self {
+        let english =
+            api_versions
+                .into_iter()
+                .any(|api_version| match semver::Version::parse(api_version) {
+                    Ok(version) => version >= semver::Version::new(0, 0, 4),
+                    Err(_) => false,
+                });
+        if english {
+            Pluralization::English
+        } else {
+            Pluralization::Legacy
+        }
+    }
+}
+
+/// The choices made when deriving the API schema of a deployment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ApiSchemaOptions {
+    /// The rules used to name the collection fields of types.
+    pub pluralization: Pluralization,
+
+    /// Whether the `*_orderBy` enums offer the fields of referenced entities.
+    /// Only deployments with relational storage can order by them.
+    pub order_by_child_fields: bool,
+}
+
+impl From<Pluralization> for ApiSchemaOptions {
+    fn from(pluralization: Pluralization) -> Self {
+        ApiSchemaOptions {
+            pluralization,
+            order_by_child_fields: true,
+        }
+    }
 }
 
-/// Derives a full-fledged GraphQL API schema from an input schema.
+/// Derives a full-fledged GraphQL API schema from an input schema, using the
+/// legacy pluralization rules.
 ///
 /// The input schema should only have type/enum/interface/union definitions
 /// and must not include a root Query type. This Query type is derived,
 /// with all its fields and their input arguments, based on the existing
 /// types.
-pub fn api_schema(input_schema: &Document) -> Result<Document, APISchemaError> {
+pub fn api_schema(logger: &Logger, input_schema: &Document) -> Result<Document, APISchemaError> {
+    api_schema_with_pluralization(logger, input_schema, Pluralization::Legacy)
+}
+
+/// Derives a full-fledged GraphQL API schema from an input schema, naming
+/// collection fields according to `pluralization`.
+pub fn api_schema_with_pluralization(
+    logger: &Logger,
+    input_schema: &Document,
+    pluralization: Pluralization,
+) -> Result<Document, APISchemaError> {
+    api_schema_with_options(logger, input_schema, pluralization.into())
+}
+
+/// Derives a full-fledged GraphQL API schema from an input schema according
+/// to `options`.
+///
+/// API schemas are returned unchanged and a warning is logged, since deriving
+/// an API schema from them again would fail or duplicate the generated types.
+///
+/// The types of the input schema keep their directives, including custom
+/// ones like `@language`, unchanged, and so do their fields; resolvers can
+/// read them through `ObjectOrInterface::directive`. Directive definitions
+/// are kept as well, so that introspection reports them. The generated
+/// types, i.e. the `<Type>_orderBy` and `<Type>_filter` types, `Query`,
+/// `Subscription` and the built-in types, don't inherit any directives.
+pub fn api_schema_with_options(
+    logger: &Logger,
+    input_schema: &Document,
+    options: ApiSchemaOptions,
+) -> Result<Document, APISchemaError> {
+    if Schema::is_api_document(input_schema) {
+        warn!(
+            logger,
+            "Not deriving an API schema from a schema that already is one"
+        );
+        return Ok(input_schema.clone());
+    }
+
     // Refactor: Take `input_schema` by value.
     let object_types = ast::get_object_type_definitions(input_schema);
     let interface_types = ast::get_interface_type_definitions(input_schema);
+    let plural_names = plural_field_names(&object_types, &interface_types, options.pluralization)?;
 
     // Refactor: Don't clone the schema.
     let mut schema = input_schema.clone();
     add_builtin_scalar_types(&mut schema)?;
     add_order_direction_enum(&mut schema);
-    add_types_for_object_types(&mut schema, &object_types)?;
-    add_types_for_interface_types(&mut schema, &interface_types)?;
+    add_subgraph_error_policy_enum(&mut schema);
+    add_meta_types(&mut schema)?;
+    add_types_for_object_types(&mut schema, &object_types, options)?;
+    add_types_for_interface_types(&mut schema, &interface_types, options)?;
     add_field_arguments(&mut schema, &input_schema)?;
-    add_query_type(&mut schema, &object_types, &interface_types)?;
-    add_subscription_type(&mut schema, &object_types, &interface_types)?;
+    add_query_type(&mut schema, &object_types, &interface_types, &plural_names)?;
+    add_subscription_type(&mut schema, &object_types, &interface_types, &plural_names)?;
     Ok(schema)
 }
 
+/// Derives API schemas from the input schemas of subgraphs, as an alternative
+/// to replacing the `document` of a `Schema` by hand.
+pub trait ApiSchemaExt: Sized {
+    /// Derives the API schema of the input schema `schema` according to
+    /// `options`, e.g. a `Pluralization`. All types of the API schema get
+    /// a `@subgraphId` directive.
+    ///
+    /// If `schema` already is an API schema, it is returned unchanged and a
+    /// warning is logged.
+    fn api<O: Into<ApiSchemaOptions>>(
+        logger: &Logger,
+        schema: Schema,
+        options: O,
+    ) -> Result<Self, APISchemaError>;
+}
+
+impl ApiSchemaExt for Schema {
+    fn api<O: Into<ApiSchemaOptions>>(
+        logger: &Logger,
+        mut schema: Schema,
+        options: O,
+    ) -> Result<Self, APISchemaError> {
+        let logger = logger.new(o!("subgraph_id" => schema.id.to_string()));
+        if schema.is_api_schema() {
+            warn!(
+                logger,
+                "Not deriving an API schema from a schema that already is one"
+            );
+            return Ok(schema);
+        }
+
+        schema.document = api_schema_with_options(&logger, &schema.document, options.into())?;
+        schema.raw = None;
+        let id = schema.id.clone();
+        schema.add_subgraph_id_directives(id);
+        Ok(schema)
+    }
+}
+
+/// Determines the name of the collection field of each object and interface
+/// type.
+///
+/// With the English rules, this also makes sure that no two types generate
+/// the same `Query` field.
+fn plural_field_names(
+    object_types: &[&ObjectType],
+    interface_types: &[&InterfaceType],
+    pluralization: Pluralization,
+) -> Result<HashMap<Name, Name>, APISchemaError> {
+    let types = object_types
+        .iter()
+        .map(|t| (&t.name, &t.directives))
+        .chain(interface_types.iter().map(|t| (&t.name, &t.directives)));
+
+    if pluralization == Pluralization::Legacy {
+        return Ok(types
+            .map(|(name, _)| (name.clone(), name.to_plural().to_camel_case()))
+            .collect());
+    }
+
+    let mut plural_names = HashMap::new();
+    let mut field_owners: HashMap<Name, &Name> = HashMap::new();
+    for (name, directives) in types {
+        let plural = match plural_override(directives) {
+            Some(Value::String(plural)) if !plural.is_empty() => plural.clone(),
+            Some(_) => return Err(APISchemaError::InvalidPluralName(name.clone())),
+            None => pluralize(name),
+        }
+        .to_camel_case();
+
+        let singular = name.as_str().to_camel_case();
+        if singular == plural {
+            return Err(APISchemaError::PluralEqualsSingular(name.clone(), plural));
+        }
+
+        for field_name in vec![singular, plural.clone()] {
+            if let Some(owner) = field_owners.get(&field_name) {
+                return Err(APISchemaError::QueryFieldCollision(
+                    (*owner).clone(),
+                    name.clone(),
+                    field_name,
+                ));
+            }
+            field_owners.insert(field_name, name);
+        }
+        plural_names.insert(name.clone(), plural);
+    }
+    Ok(plural_names)
+}
+
+/// Returns the `plural` argument of the `@entity` directive, if there is one.
+fn plural_override(directives: &[Directive]) -> Option<&Value> {
+    directives
+        .iter()
+        .find(|directive| directive.name == "entity")
+        .and_then(|directive| {
+            directive
+                .arguments
+                .iter()
+                .find(|(name, _)| name == "plural")
+                .map(|(_, value)| value)
+        })
+}
+
 /// Adds built-in GraphQL scalar types (`Int`, `String` etc.) to the schema.
 fn add_builtin_scalar_types(schema: &mut Document) -> Result<(), APISchemaError> {
     for name in [
@@ -86,12 +317,86 @@ fn add_order_direction_enum(schema: &mut Document) {
     schema.definitions.push(def);
 }
 
+/// Adds a global `_SubgraphErrorPolicy_` type to the schema.
+fn add_subgraph_error_policy_enum(schema: &mut Document) {
+    let typedef = TypeDefinition::Enum(EnumType {
+        position: Pos::default(),
+        description: None,
+        name: "_SubgraphErrorPolicy_".to_string(),
+        directives: vec![],
+        values: ["allow", "deny"]
+            .into_iter()
+            .map(|name| EnumValue {
+                position: Pos::default(),
+                description: None,
+                name: name.to_string(),
+                directives: vec![],
+            })
+            .collect(),
+    });
+    let def = Definition::TypeDefinition(typedef);
+    schema.definitions.push(def);
+}
+
+/// Adds the `_Meta_` type of the `_meta` field and the `_Block_` type it
+/// refers to to the schema.
+fn add_meta_types(schema: &mut Document) -> Result<(), APISchemaError> {
+    let field = |name: &str, field_type: Type| Field {
+        position: Pos::default(),
+        description: None,
+        name: name.to_string(),
+        arguments: vec![],
+        field_type,
+        directives: vec![],
+    };
+    let named = |name: &str| Type::NamedType(name.to_string());
+    let non_null = |name: &str| Type::NonNullType(Box::new(named(name)));
+
+    for (name, fields) in vec![
+        (
+            BLOCK_TYPE,
+            vec![
+                field("hash", named("Bytes")),
+                field("number", non_null("Int")),
+            ],
+        ),
+        (
+            META_TYPE,
+            vec![
+                // Deployments that haven't processed a block yet have no block
+                field("block", named(BLOCK_TYPE)),
+                field("deployment", non_null("String")),
+                field("hasIndexingErrors", non_null("Boolean")),
+                // Only known for deployments that are running on this node
+                field("chainHeadBlockNumber", named("Int")),
+                field("estimatedSyncSeconds", named("Int")),
+            ],
+        ),
+    ] {
+        if ast::get_named_type(schema, &name.to_string()).is_some() {
+            return Err(APISchemaError::TypeExists(name.to_string()));
+        }
+
+        let typedef = TypeDefinition::Object(ObjectType {
+            position: Pos::default(),
+            description: None,
+            name: name.to_string(),
+            implements_interfaces: vec![],
+            directives: vec![],
+            fields,
+        });
+        schema.definitions.push(Definition::TypeDefinition(typedef));
+    }
+    Ok(())
+}
+
 fn add_types_for_object_types(
     schema: &mut Document,
     object_types: &Vec<&ObjectType>,
+    options: ApiSchemaOptions,
 ) -> Result<(), APISchemaError> {
     for object_type in object_types {
-        add_order_by_type(schema, &object_type.name, &object_type.fields)?;
+        add_order_by_type(schema, &object_type.name, &object_type.fields, options)?;
         add_filter_type(schema, &object_type.name, &object_type.fields)?;
     }
     Ok(())
@@ -101,36 +406,57 @@ fn add_types_for_object_types(
 fn add_types_for_interface_types(
     schema: &mut Document,
     interface_types: &[&InterfaceType],
+    options: ApiSchemaOptions,
 ) -> Result<(), APISchemaError> {
     for interface_type in interface_types {
-        add_order_by_type(schema, &interface_type.name, &interface_type.fields)?;
+        add_order_by_type(
+            schema,
+            &interface_type.name,
+            &interface_type.fields,
+            options,
+        )?;
         add_filter_type(schema, &interface_type.name, &interface_type.fields)?;
     }
     Ok(())
 }
 
 /// Adds a `<type_name>_orderBy` enum type for the given fields to the schema.
+///
+/// Besides the fields themselves, entities can be ordered by the fields of
+/// the entity a field references, which are named `<field>__<child_field>`.
+/// This only goes one level deep and leaves out list and derived fields, and
+/// it only happens if `options` allow ordering by child fields.
 fn add_order_by_type(
     schema: &mut Document,
     type_name: &Name,
     fields: &[Field],
+    options: ApiSchemaOptions,
 ) -> Result<(), APISchemaError> {
     let type_name = format!("{}_orderBy", type_name).to_string();
 
     match ast::get_named_type(schema, &type_name) {
         None => {
+            let child_values = if options.order_by_child_fields {
+                child_order_by_values(schema, fields)
+            } else {
+                vec![]
+            };
+            let values = fields
+                .iter()
+                .map(|field| field.name.clone())
+                .chain(child_values)
+                .collect::<Vec<_>>();
             let typedef = TypeDefinition::Enum(EnumType {
                 position: Pos::default(),
                 description: None,
                 name: type_name,
                 directives: vec![],
-                values: fields
-                    .iter()
-                    .map(|field| &field.name)
+                values: values
+                    .into_iter()
                     .map(|name| EnumValue {
                         position: Pos::default(),
                         description: None,
-                        name: name.to_owned(),
+                        name,
                         directives: vec![],
                     })
                     .collect(),
@@ -143,6 +469,35 @@ fn add_order_by_type(
     Ok(())
 }
 
+/// Generates the `<field>__<child_field>` values of an `*_orderBy` enum for
+/// the fields among `fields` that reference other entities.
+fn child_order_by_values(schema: &Document, fields: &[Field]) -> Vec<Name> {
+    fn is_orderable(field: &Field) -> bool {
+        !ast::is_list_or_non_null_list_field(field)
+            && ast::get_derived_from_directive(field).is_none()
+    }
+
+    fields
+        .iter()
+        .filter(|field| is_orderable(field))
+        .filter_map(|field| {
+            let child_fields =
+                match ast::get_named_type(schema, &ast::get_field_name(&field.field_type)) {
+                    Some(TypeDefinition::Object(t)) => &t.fields,
+                    Some(TypeDefinition::Interface(t)) => &t.fields,
+                    _ => return None,
+                };
+            Some(
+                child_fields
+                    .iter()
+                    .filter(|child_field| is_orderable(child_field))
+                    .map(move |child_field| format!("{}__{}", field.name, child_field.name)),
+            )
+        })
+        .flatten()
+        .collect()
+}
+
 /// Adds a `<type_name>_filter` enum type for the given fields to the schema.
 fn add_filter_type(
     schema: &mut Document,
@@ -362,6 +717,7 @@ fn add_query_type(
     schema: &mut Document,
     object_types: &[&ObjectType],
     interface_types: &[&InterfaceType],
+    plural_names: &HashMap<Name, Name>,
 ) -> Result<(), APISchemaError> {
     let type_name = String::from("Query");
 
@@ -369,29 +725,62 @@ fn add_query_type(
         return Err(APISchemaError::TypeExists(type_name));
     }
 
+    let mut fields = vec![];
+    for name in object_types
+        .iter()
+        .map(|t| &t.name)
+        .chain(interface_types.iter().map(|t| &t.name))
+    {
+        for mut field in query_fields_for_type(schema, name, &plural_names[name])? {
+            field.arguments.push(subgraph_error_argument());
+            fields.push(field);
+        }
+    }
+    fields.push(Field {
+        position: Pos::default(),
+        description: None,
+        name: META_FIELD.to_string(),
+        arguments: vec![],
+        field_type: Type::NamedType(META_TYPE.to_string()),
+        directives: vec![],
+    });
+
     let typedef = TypeDefinition::Object(ObjectType {
         position: Pos::default(),
         description: None,
         name: type_name,
         implements_interfaces: vec![],
-        directives: vec![],
-        fields: object_types
-            .iter()
-            .map(|t| &t.name)
-            .chain(interface_types.iter().map(|t| &t.name))
-            .flat_map(|name| query_fields_for_type(schema, name))
-            .collect(),
+        // Marks the document as an API schema
+        directives: vec![Directive {
+            position: Pos::default(),
+            name: API_SCHEMA_DIRECTIVE.to_owned(),
+            arguments: vec![],
+        }],
+        fields,
     });
     let def = Definition::TypeDefinition(typedef);
     schema.definitions.push(def);
     Ok(())
 }
 
+/// Generates the `subgraphError` argument of `Query` fields, which decides
+/// whether data of a failed subgraph may be returned.
+fn subgraph_error_argument() -> InputValue {
+    let mut argument = input_value(
+        &"subgraphError".to_string(),
+        "",
+        Type::NamedType("_SubgraphErrorPolicy_".to_string()),
+    );
+    argument.default_value = Some(Value::Enum("deny".to_string()));
+    argument
+}
+
 /// Adds a root `Subscription` object type to the schema.
 fn add_subscription_type(
     schema: &mut Document,
     object_types: &[&ObjectType],
     interface_types: &[&InterfaceType],
+    plural_names: &HashMap<Name, Name>,
 ) -> Result<(), APISchemaError> {
     let type_name = String::from("Subscription");
 
@@ -399,18 +788,22 @@ fn add_subscription_type(
         return Err(APISchemaError::TypeExists(type_name));
     }
 
+    let mut fields = vec![];
+    for name in object_types
+        .iter()
+        .map(|t| &t.name)
+        .chain(interface_types.iter().map(|t| &t.name))
+    {
+        fields.extend(query_fields_for_type(schema, name, &plural_names[name])?);
+    }
+
     let typedef = TypeDefinition::Object(ObjectType {
         position: Pos::default(),
         description: None,
         name: type_name,
         implements_interfaces: vec![],
         directives: vec![],
-        fields: object_types
-            .iter()
-            .map(|t| &t.name)
-            .chain(interface_types.iter().map(|t| &t.name))
-            .flat_map(|name| query_fields_for_type(schema, name))
-            .collect(),
+        fields,
     });
     let def = Definition::TypeDefinition(typedef);
     schema.definitions.push(def);
@@ -418,9 +811,21 @@ fn add_subscription_type(
 }
 
 /// Generates `Query` fields for the given type name (e.g. `users` and `user`).
-fn query_fields_for_type(schema: &Document, type_name: &Name) -> Vec<Field> {
+fn query_fields_for_type(
+    schema: &Document,
+    type_name: &Name,
+    plural_name: &Name,
+) -> Result<Vec<Field>, APISchemaError> {
     let input_objects = ast::get_input_object_definitions(schema);
-    vec![
+    let no_directives = vec![];
+    let type_directives = match ast::get_named_type(schema, type_name) {
+        Some(TypeDefinition::Object(t)) => &t.directives,
+        Some(TypeDefinition::Interface(t)) => &t.directives,
+        _ => &no_directives,
+    };
+    let order = default_order(schema, type_name, &[type_directives])?;
+
+    Ok(vec![
         Field {
             position: Pos::default(),
             description: None,
@@ -439,20 +844,23 @@ fn query_fields_for_type(schema: &Document, type_name: &Name) -> Vec<Field> {
         Field {
             position: Pos::default(),
             description: None,
-            name: type_name.to_plural().to_camel_case(),
-            arguments: collection_arguments_for_named_type(&input_objects, type_name),
+            name: plural_name.to_owned(),
+            arguments: collection_arguments_for_named_type(&input_objects, type_name, order),
             field_type: Type::NonNullType(Box::new(Type::ListType(Box::new(Type::NonNullType(
                 Box::new(Type::NamedType(type_name.to_owned())),
             ))))),
             directives: vec![],
         },
-    ]
+    ])
 }
 
 /// Generates arguments for collection queries of a named type (e.g. User).
+///
+/// If `default_order` is set, `orderBy` and `orderDirection` default to it.
 fn collection_arguments_for_named_type(
     input_objects: &[InputObjectType],
     type_name: &Name,
+    default_order: Option<(Name, Name)>,
 ) -> Vec<InputValue> {
     // `first` and `skip` should be non-nullable, but the Apollo graphql client
     // exhibts non-conforming behaviour by erroing if no value is provided for a
@@ -463,20 +871,22 @@ fn collection_arguments_for_named_type(
     let mut first = input_value(&"first".to_string(), "", Type::NamedType("Int".to_string()));
     first.default_value = Some(Value::Int(100.into()));
 
-    let mut args = vec![
-        skip,
-        first,
-        input_value(
-            &"orderBy".to_string(),
-            "",
-            Type::NamedType(format!("{}_orderBy", type_name)),
-        ),
-        input_value(
-            &"orderDirection".to_string(),
-            "",
-            Type::NamedType("OrderDirection".to_string()),
-        ),
-    ];
+    let mut order_by = input_value(
+        &"orderBy".to_string(),
+        "",
+        Type::NamedType(format!("{}_orderBy", type_name)),
+    );
+    let mut order_direction = input_value(
+        &"orderDirection".to_string(),
+        "",
+        Type::NamedType("OrderDirection".to_string()),
+    );
+    if let Some((field, direction)) = default_order {
+        order_by.default_value = Some(Value::Enum(field));
+        order_direction.default_value = Some(Value::Enum(direction));
+    }
+
+    let mut args = vec![skip, first, order_by, order_direction];
 
     // Not all types have filter types, see comment in `add_filter_type`.
     let filter_name = format!("{}_filter", type_name);
@@ -491,6 +901,10 @@ fn collection_arguments_for_named_type(
     args
 }
 
+/// Gives every list field that references entities the collection arguments
+/// `skip`, `first`, `orderBy`, `orderDirection` and `where`, which the store
+/// resolver applies when it resolves the referenced entities. They replace
+/// any arguments the field declares in the input schema.
 fn add_field_arguments(
     schema: &mut Document,
     input_schema: &Document,
@@ -506,6 +920,13 @@ fn add_field_arguments(
                 ast::get_referenced_entity_type(input_schema, &input_field)
             {
                 if ast::is_list_or_non_null_list_field(&input_field) {
+                    let arguments = list_field_arguments(
+                        schema,
+                        &input_objects,
+                        input_field,
+                        input_reference_type,
+                    )?;
+
                     // Get corresponding object type and field in the output schema
                     let object_type = ast::get_object_type_mut(schema, &input_object_type.name)
                         .expect("object type from input schema is missing in API schema");
@@ -514,20 +935,7 @@ fn add_field_arguments(
                         .iter_mut()
                         .find(|field| field.name == input_field.name)
                         .expect("field from input schema is missing in API schema");
-
-                    match input_reference_type {
-                        TypeDefinition::Object(ot) => {
-                            field.arguments =
-                                collection_arguments_for_named_type(&input_objects, &ot.name);
-                        }
-                        TypeDefinition::Interface(it) => {
-                            field.arguments =
-                                collection_arguments_for_named_type(&input_objects, &it.name);
-                        }
-                        _ => unreachable!(
-                            "referenced entity types can only be object or interface types"
-                        ),
-                    }
+                    field.arguments = arguments;
                 }
             }
         }
@@ -539,6 +947,13 @@ fn add_field_arguments(
                 ast::get_referenced_entity_type(input_schema, &input_field)
             {
                 if ast::is_list_or_non_null_list_field(&input_field) {
+                    let arguments = list_field_arguments(
+                        schema,
+                        &input_objects,
+                        input_field,
+                        input_reference_type,
+                    )?;
+
                     // Get corresponding interface type and field in the output schema
                     let interface_type =
                         ast::get_interface_type_mut(schema, &input_interface_type.name)
@@ -548,20 +963,7 @@ fn add_field_arguments(
                         .iter_mut()
                         .find(|field| field.name == input_field.name)
                         .expect("field from input schema is missing in API schema");
-
-                    match input_reference_type {
-                        TypeDefinition::Object(ot) => {
-                            field.arguments =
-                                collection_arguments_for_named_type(&input_objects, &ot.name);
-                        }
-                        TypeDefinition::Interface(it) => {
-                            field.arguments =
-                                collection_arguments_for_named_type(&input_objects, &it.name);
-                        }
-                        _ => unreachable!(
-                            "referenced entity types can only be object or interface types"
-                        ),
-                    }
+                    field.arguments = arguments;
                 }
             }
         }
@@ -570,18 +972,101 @@ fn add_field_arguments(
     Ok(())
 }
 
+/// Generates the collection arguments of a list field that references the
+/// entity type `reference_type`.
+fn list_field_arguments(
+    schema: &Document,
+    input_objects: &[InputObjectType],
+    field: &Field,
+    reference_type: &TypeDefinition,
+) -> Result<Vec<InputValue>, APISchemaError> {
+    let (type_name, type_directives) = match reference_type {
+        TypeDefinition::Object(ot) => (&ot.name, &ot.directives),
+        TypeDefinition::Interface(it) => (&it.name, &it.directives),
+        _ => unreachable!("referenced entity types can only be object or interface types"),
+    };
+    let order = default_order(schema, type_name, &[&field.directives, type_directives])?;
+    Ok(collection_arguments_for_named_type(
+        input_objects,
+        type_name,
+        order,
+    ))
+}
+
+/// Determines the order of a collection of `type_name` entities for queries
+/// that don't specify `orderBy`, as a `(field, direction)` pair.
+///
+/// The order can be set with `@defaultOrder(by: <field>, direction: asc|desc)`
+/// on the list field or on the entity type; the first of `directives` that
+/// contains the directive wins. Without it, entities are ordered by `id`,
+/// ascending, if the type has an `id` field.
+fn default_order(
+    schema: &Document,
+    type_name: &Name,
+    directives: &[&Vec<Directive>],
+) -> Result<Option<(Name, Name)>, APISchemaError> {
+    let order_by_values: Vec<&Name> =
+        match ast::get_named_type(schema, &format!("{}_orderBy", type_name)) {
+            Some(TypeDefinition::Enum(enum_type)) => {
+                enum_type.values.iter().map(|value| &value.name).collect()
+            }
+            _ => vec![],
+        };
+
+    let directive = directives
+        .iter()
+        .flat_map(|directives| directives.iter())
+        .find(|directive| directive.name == "defaultOrder");
+
+    let directive = match directive {
+        Some(directive) => directive,
+        None if order_by_values.iter().any(|value| *value == "id") => {
+            return Ok(Some(("id".to_owned(), "asc".to_owned())));
+        }
+        None => return Ok(None),
+    };
+
+    let argument = |name: &str| {
+        directive
+            .arguments
+            .iter()
+            .find(|(argument, _)| argument == name)
+            .map(|(_, value)| match value {
+                Value::Enum(value) | Value::String(value) => Ok(value.clone()),
+                _ => Err(APISchemaError::InvalidDefaultOrder(type_name.clone())),
+            })
+    };
+
+    let field =
+        argument("by").ok_or_else(|| APISchemaError::InvalidDefaultOrder(type_name.clone()))??;
+    let direction = argument("direction").unwrap_or_else(|| Ok("asc".to_owned()))?;
+
+    if !order_by_values.contains(&&field) || !["asc", "desc"].contains(&direction.as_str()) {
+        return Err(APISchemaError::InvalidDefaultOrder(type_name.clone()));
+    }
+    Ok(Some((field, direction)))
+}
+
 #[cfg(test)]
 mod tests {
     use graphql_parser::schema::*;
 
-    use super::api_schema;
+    use super::{
+        api_schema, api_schema_with_options, api_schema_with_pluralization, APISchemaError,
+        ApiSchemaOptions, Pluralization,
+    };
     use crate::schema::ast;
+    use graph::prelude::{o, slog, Logger};
+
+    fn logger() -> Logger {
+        Logger::root(slog::Discard, o!())
+    }
 
     #[test]
     fn api_schema_contains_built_in_scalar_types() {
         let input_schema =
             parse_schema("type User { id: ID! }").expect("Failed to parse input schema");
-        let schema = api_schema(&input_schema).expect("Failed to derive API schema");
+        let schema = api_schema(&logger(), &input_schema).expect("Failed to derive API schema");
 
         ast::get_named_type(&schema, &"Boolean".to_string())
             .expect("Boolean type is missing in API schema");
@@ -598,7 +1083,7 @@ mod tests {
     fn api_schema_contains_order_direction_enum() {
         let input_schema = parse_schema("type User { id: ID!, name: String! }")
             .expect("Failed to parse input schema");
-        let schema = api_schema(&input_schema).expect("Failed to derived API schema");
+        let schema = api_schema(&logger(), &input_schema).expect("Failed to derived API schema");
 
         let order_direction = ast::get_named_type(&schema, &"OrderDirection".to_string())
             .expect("OrderDirection type is missing in derived API schema");
@@ -612,20 +1097,129 @@ mod tests {
         assert_eq!(values, [&"asc".to_string(), &"desc".to_string()]);
     }
 
+    #[test]
+    fn api_schema_contains_subgraph_error_policy_enum() {
+        let input_schema =
+            parse_schema("type User { id: ID! }").expect("Failed to parse input schema");
+        let schema = api_schema(&logger(), &input_schema).expect("Failed to derive API schema");
+
+        let policy = ast::get_named_type(&schema, &"_SubgraphErrorPolicy_".to_string())
+            .expect("_SubgraphErrorPolicy_ type is missing in derived API schema");
+        let enum_type = match policy {
+            TypeDefinition::Enum(t) => Some(t),
+            _ => None,
+        }
+        .expect("_SubgraphErrorPolicy_ type is not an enum");
+
+        let values: Vec<&Name> = enum_type.values.iter().map(|value| &value.name).collect();
+        assert_eq!(values, [&"allow".to_string(), &"deny".to_string()]);
+    }
+
     #[test]
     fn api_schema_contains_query_type() {
         let input_schema =
             parse_schema("type User { id: ID! }").expect("Failed to parse input schema");
-        let schema = api_schema(&input_schema).expect("Failed to derive API schema");
+        let schema = api_schema(&logger(), &input_schema).expect("Failed to derive API schema");
         ast::get_named_type(&schema, &"Query".to_string())
             .expect("Root Query type is missing in API schema");
     }
 
+    #[test]
+    fn api_schema_contains_meta_field() {
+        let input_schema =
+            parse_schema("type User { id: ID! }").expect("Failed to parse input schema");
+        let schema = api_schema(&logger(), &input_schema).expect("Failed to derive API schema");
+
+        let query_type = ast::get_root_query_type(&schema).expect("Query type is missing");
+        let meta_field = ast::get_field(query_type, &"_meta".to_string())
+            .expect("_meta field is missing on the Query type");
+        assert_eq!(meta_field.field_type, Type::NamedType("_Meta_".to_string()));
+
+        let field_names =
+            |type_name: &str| match ast::get_named_type(&schema, &type_name.to_string()) {
+                Some(TypeDefinition::Object(t)) => {
+                    t.fields.iter().map(|f| f.name.as_str()).collect::<Vec<_>>()
+                }
+                _ => panic!("{} type is missing in API schema", type_name),
+            };
+        assert_eq!(
+            field_names("_Meta_"),
+            vec![
+                "block",
+                "deployment",
+                "hasIndexingErrors",
+                "chainHeadBlockNumber",
+                "estimatedSyncSeconds"
+            ]
+        );
+        assert_eq!(field_names("_Block_"), vec!["hash", "number"]);
+    }
+
+    #[test]
+    fn api_schema_keeps_directives_of_input_types() {
+        let input_schema = parse_schema(
+            "directive @language(language: String = \"English\") on OBJECT | INTERFACE
+
+             interface Node @language(language: \"German\") { id: ID! }
+
+             type User implements Node @entity @language(language: \"French\") {
+               id: ID!
+               name: String! @language(language: \"Spanish\")
+             }",
+        )
+        .expect("Failed to parse input schema");
+        let schema = api_schema(&logger(), &input_schema).expect("Failed to derive API schema");
+
+        let directive_names = |type_name: &str| {
+            let directives = match ast::get_named_type(&schema, &type_name.to_string()) {
+                Some(TypeDefinition::Object(t)) => &t.directives,
+                Some(TypeDefinition::Interface(t)) => &t.directives,
+                Some(TypeDefinition::Enum(t)) => &t.directives,
+                Some(TypeDefinition::InputObject(t)) => &t.directives,
+                _ => panic!("{} type is missing in API schema", type_name),
+            };
+            directives
+                .iter()
+                .map(|directive| directive.name.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(directive_names("User"), vec!["entity", "language"]);
+        assert_eq!(directive_names("Node"), vec!["language"]);
+        assert!(directive_names("User_orderBy").is_empty());
+        assert!(directive_names("User_filter").is_empty());
+        assert!(directive_names("Query").is_empty());
+
+        let user = ast::get_object_type_definitions(&schema)
+            .into_iter()
+            .find(|t| t.name == "User")
+            .expect("User type is missing in API schema");
+        assert_eq!(
+            ast::get_object_type_directive(user, "language".to_string())
+                .map(|directive| directive.arguments.clone()),
+            Some(vec![(
+                "language".to_string(),
+                Value::String("French".to_string())
+            )])
+        );
+        let name = ast::get_field(user, &"name".to_string()).expect("name field is missing");
+        assert_eq!(name.directives[0].name, "language");
+
+        let definitions: Vec<_> = schema
+            .definitions
+            .iter()
+            .filter_map(|definition| match definition {
+                Definition::DirectiveDefinition(definition) => Some(definition.name.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(definitions, vec!["language"]);
+    }
+
     #[test]
     fn api_schema_contains_field_order_by_enum() {
         let input_schema = parse_schema("type User { id: ID!, name: String! }")
             .expect("Failed to parse input schema");
-        let schema = api_schema(&input_schema).expect("Failed to derived API schema");
+        let schema = api_schema(&logger(), &input_schema).expect("Failed to derived API schema");
 
         let user_order_by = ast::get_named_type(&schema, &"User_orderBy".to_string())
             .expect("User_orderBy type is missing in derived API schema");
@@ -663,7 +1257,7 @@ mod tests {
             "#,
         )
         .expect("Failed to parse input schema");
-        let schema = api_schema(&input_schema).expect("Failed to derived API schema");
+        let schema = api_schema(&logger(), &input_schema).expect("Failed to derived API schema");
 
         let user_filter = ast::get_named_type(&schema, &"User_filter".to_string())
             .expect("User_filter type is missing in derived API schema");
@@ -738,7 +1332,7 @@ mod tests {
             "type User { id: ID!, name: String! } type UserProfile { id: ID!, title: String! }",
         )
         .expect("Failed to parse input schema");
-        let schema = api_schema(&input_schema).expect("Failed to derived API schema");
+        let schema = api_schema(&logger(), &input_schema).expect("Failed to derived API schema");
 
         let query_type = ast::get_named_type(&schema, &"Query".to_string())
             .expect("Query type is missing in derived API schema");
@@ -760,7 +1354,7 @@ mod tests {
                 .iter()
                 .map(|input_value| input_value.name.to_owned())
                 .collect::<Vec<String>>(),
-            vec!["id".to_string()],
+            vec!["id".to_string(), "subgraphError".to_string()],
         );
 
         let user_plural_field = match query_type {
@@ -782,10 +1376,17 @@ mod tests {
                 .iter()
                 .map(|input_value| input_value.name.to_owned())
                 .collect::<Vec<String>>(),
-            ["skip", "first", "orderBy", "orderDirection", "where",]
-                .into_iter()
-                .map(|name| name.to_string())
-                .collect::<Vec<String>>()
+            [
+                "skip",
+                "first",
+                "orderBy",
+                "orderDirection",
+                "where",
+                "subgraphError",
+            ]
+            .into_iter()
+            .map(|name| name.to_string())
+            .collect::<Vec<String>>()
         );
 
         let user_profile_singular_field = match query_type {
@@ -822,7 +1423,7 @@ mod tests {
             ",
         )
         .expect("Failed to parse input schema");
-        let schema = api_schema(&input_schema).expect("Failed to derived API schema");
+        let schema = api_schema(&logger(), &input_schema).expect("Failed to derived API schema");
 
         let query_type = ast::get_named_type(&schema, &"Query".to_string())
             .expect("Query type is missing in derived API schema");
@@ -844,7 +1445,7 @@ mod tests {
                 .iter()
                 .map(|input_value| input_value.name.to_owned())
                 .collect::<Vec<String>>(),
-            vec!["id".to_string()],
+            vec!["id".to_string(), "subgraphError".to_string()],
         );
 
         let plural_field = match query_type {
@@ -866,10 +1467,310 @@ mod tests {
                 .iter()
                 .map(|input_value| input_value.name.to_owned())
                 .collect::<Vec<String>>(),
-            ["skip", "first", "orderBy", "orderDirection", "where",]
-                .into_iter()
-                .map(|name| name.to_string())
-                .collect::<Vec<String>>()
+            [
+                "skip",
+                "first",
+                "orderBy",
+                "orderDirection",
+                "where",
+                "subgraphError",
+            ]
+            .into_iter()
+            .map(|name| name.to_string())
+            .collect::<Vec<String>>()
+        );
+    }
+
+    /// The names of the `Query` fields generated for the types of `schema`,
+    /// leaving out `_meta`.
+    fn query_field_names(schema: &Document) -> Vec<Name> {
+        match ast::get_named_type(schema, &"Query".to_string()) {
+            Some(TypeDefinition::Object(t)) => t
+                .fields
+                .iter()
+                .map(|f| f.name.clone())
+                .filter(|name| name != "_meta")
+                .collect(),
+            _ => panic!("Query type is missing in derived API schema"),
+        }
+    }
+
+    #[test]
+    fn api_schema_pluralizes_according_to_version() {
+        let input_schema =
+            parse_schema("type Registry @entity { id: ID! } type Address @entity { id: ID! }")
+                .expect("Failed to parse input schema");
+
+        let schema =
+            api_schema_with_pluralization(&logger(), &input_schema, Pluralization::English)
+                .expect("Failed to derive API schema");
+        assert_eq!(
+            query_field_names(&schema),
+            vec!["registry", "registries", "address", "addresses"]
+        );
+
+        // Existing deployments keep their field names
+        let legacy_schema =
+            api_schema(&logger(), &input_schema).expect("Failed to derive API schema");
+        assert_eq!(
+            query_field_names(&legacy_schema),
+            api_schema_with_pluralization(&logger(), &input_schema, Pluralization::Legacy)
+                .map(|schema| query_field_names(&schema))
+                .unwrap()
+        );
+
+        assert_eq!(
+            Pluralization::for_api_versions(vec!["0.0.2", "0.0.3"]),
+            Pluralization::Legacy
+        );
+        assert_eq!(
+            Pluralization::for_api_versions(vec!["0.0.3", "0.0.4"]),
+            Pluralization::English
+        );
+        assert_eq!(
+            Pluralization::for_api_versions(vec![]),
+            Pluralization::Legacy
+        );
+    }
+
+    #[test]
+    fn api_schema_uses_plural_override() {
+        let input_schema = parse_schema(
+            r#"type Person @entity(plural: "Folks") { id: ID! } type Sheep @entity(plural: "Flock") { id: ID! }"#,
+        )
+        .expect("Failed to parse input schema");
+
+        let schema =
+            api_schema_with_pluralization(&logger(), &input_schema, Pluralization::English)
+                .expect("Failed to derive API schema");
+        assert_eq!(
+            query_field_names(&schema),
+            vec!["person", "folks", "sheep", "flock"]
+        );
+    }
+
+    #[test]
+    fn api_schema_uses_graphql_names_of_renamed_types() {
+        let input_schema = parse_schema(r#"type Account @entity(name: "User") { id: ID! }"#)
+            .expect("Failed to parse input schema");
+
+        let schema =
+            api_schema_with_pluralization(&logger(), &input_schema, Pluralization::English)
+                .expect("Failed to derive API schema");
+        assert_eq!(query_field_names(&schema), vec!["account", "accounts"]);
+        for type_name in vec!["Account_filter", "Account_orderBy"] {
+            assert!(ast::get_named_type(&schema, &type_name.to_owned()).is_some());
+        }
+        for type_name in vec!["User", "User_filter", "User_orderBy"] {
+            assert!(ast::get_named_type(&schema, &type_name.to_owned()).is_none());
+        }
+    }
+
+    #[test]
+    fn api_schema_rejects_invalid_plural_override() {
+        let input_schema = parse_schema(r#"type Person @entity(plural: "") { id: ID! }"#)
+            .expect("Failed to parse input schema");
+
+        match api_schema_with_pluralization(&logger(), &input_schema, Pluralization::English) {
+            Err(APISchemaError::InvalidPluralName(name)) => assert_eq!(name, "Person"),
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn api_schema_detects_query_field_collisions() {
+        // The override of `Person` collides with the generated plural of `Folk`
+        let input_schema = parse_schema(
+            r#"type Person @entity(plural: "Folks") { id: ID! } type Folk @entity { id: ID! }"#,
+        )
+        .expect("Failed to parse input schema");
+
+        match api_schema_with_pluralization(&logger(), &input_schema, Pluralization::English) {
+            Err(APISchemaError::QueryFieldCollision(first, second, field)) => {
+                assert_eq!(first, "Person");
+                assert_eq!(second, "Folk");
+                assert_eq!(field, "folks");
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+
+        // Uncountable names collide with their own singular field
+        let input_schema =
+            parse_schema("type Sheep @entity { id: ID! }").expect("Failed to parse input schema");
+        match api_schema_with_pluralization(&logger(), &input_schema, Pluralization::English) {
+            Err(APISchemaError::PluralEqualsSingular(name, field)) => {
+                assert_eq!(name, "Sheep");
+                assert_eq!(field, "sheep");
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    /// Returns the defaults of the `orderBy` and `orderDirection` arguments of
+    /// `field_name` on `type_name`.
+    fn default_order_of(schema: &Document, type_name: &str, field_name: &str) -> (Value, Value) {
+        let field = match ast::get_named_type(schema, &type_name.to_string()) {
+            Some(TypeDefinition::Object(t)) => ast::get_field(t, &field_name.to_string()),
+            _ => None,
+        }
+        .expect("field is missing in derived API schema");
+        let default = |name: &str| {
+            field
+                .arguments
+                .iter()
+                .find(|argument| argument.name == name)
+                .and_then(|argument| argument.default_value.clone())
+                .unwrap_or(Value::Null)
+        };
+        (default("orderBy"), default("orderDirection"))
+    }
+
+    #[test]
+    fn api_schema_orders_collections_by_id_by_default() {
+        let input_schema = parse_schema(
+            "
+            type User @entity { id: ID!, name: String!, posts: [Post!]! }
+            type Post @entity { id: ID!, title: String! }
+            interface Named { name: String! }
+            ",
+        )
+        .expect("Failed to parse input schema");
+        let schema =
+            api_schema_with_pluralization(&logger(), &input_schema, Pluralization::English)
+                .expect("Failed to derive API schema");
+
+        let by_id = (
+            Value::Enum("id".to_string()),
+            Value::Enum("asc".to_string()),
+        );
+        assert_eq!(default_order_of(&schema, "Query", "users"), by_id);
+        assert_eq!(default_order_of(&schema, "Subscription", "users"), by_id);
+        assert_eq!(default_order_of(&schema, "User", "posts"), by_id);
+
+        // Types without an `id` field have no default order
+        assert_eq!(
+            default_order_of(&schema, "Query", "nameds"),
+            (Value::Null, Value::Null)
+        );
+    }
+
+    #[test]
+    fn api_schema_uses_default_order_override() {
+        let input_schema = parse_schema(
+            "
+            type User @entity @defaultOrder(by: name, direction: desc) {
+              id: ID!
+              name: String!
+              posts: [Post!]! @defaultOrder(by: title)
+              friends: [User!]!
+            }
+            type Post @entity { id: ID!, title: String! }
+            ",
+        )
+        .expect("Failed to parse input schema");
+        let schema = api_schema(&logger(), &input_schema).expect("Failed to derive API schema");
+
+        let by_name = (
+            Value::Enum("name".to_string()),
+            Value::Enum("desc".to_string()),
+        );
+        assert_eq!(default_order_of(&schema, "Query", "users"), by_name);
+        assert_eq!(default_order_of(&schema, "User", "friends"), by_name);
+        assert_eq!(
+            default_order_of(&schema, "User", "posts"),
+            (
+                Value::Enum("title".to_string()),
+                Value::Enum("asc".to_string())
+            )
+        );
+    }
+
+    #[test]
+    fn api_schema_rejects_invalid_default_order() {
+        for directive in &[
+            "@defaultOrder(by: age)",
+            "@defaultOrder(by: name, direction: up)",
+            "@defaultOrder(direction: asc)",
+        ] {
+            let input_schema = parse_schema(&format!(
+                "type User @entity {} {{ id: ID!, name: String! }}",
+                directive
+            ))
+            .expect("Failed to parse input schema");
+
+            match api_schema(&logger(), &input_schema) {
+                Err(APISchemaError::InvalidDefaultOrder(name)) => assert_eq!(name, "User"),
+                result => panic!("unexpected result for {}: {:?}", directive, result),
+            }
+        }
+    }
+
+    #[test]
+    fn api_schema_is_idempotent() {
+        let input_schema = parse_schema(
+            "interface Named { name: String! }
+             type User implements Named @entity { id: ID!, name: String!, friends: [User!]! }",
+        )
+        .expect("Failed to parse input schema");
+
+        let schema = api_schema(&logger(), &input_schema).expect("Failed to derive API schema");
+        assert!(graph::prelude::Schema::is_api_document(&schema));
+        assert!(!graph::prelude::Schema::is_api_document(&input_schema));
+
+        let twice = api_schema(&logger(), &schema).expect("Failed to derive API schema twice");
+        assert_eq!(twice, schema);
+    }
+
+    #[test]
+    fn schema_api_is_a_no_op_for_api_schemas() {
+        use super::ApiSchemaExt;
+        use graph::prelude::{Schema, SubgraphDeploymentId};
+
+        let logger = logger();
+        let input_schema = Schema::parse(
+            "type User @entity { id: ID!, name: String! }",
+            SubgraphDeploymentId::new("idempotent").unwrap(),
+        )
+        .expect("Failed to parse input schema");
+        assert!(!input_schema.is_api_schema());
+
+        let schema = Schema::api(&logger, input_schema, Pluralization::Legacy)
+            .expect("Failed to derive API schema");
+        assert!(schema.is_api_schema());
+
+        let twice = Schema::api(&logger, schema.clone(), Pluralization::English)
+            .expect("Failed to derive API schema twice");
+        assert_eq!(twice, schema);
+    }
+
+    #[test]
+    fn api_schema_orders_by_child_fields_only_if_asked_to() {
+        let input_schema = parse_schema(
+            "
+            type User @entity { id: ID!, name: String!, best: Post }
+            type Post @entity { id: ID!, title: String! }
+            ",
+        )
+        .expect("Failed to parse input schema");
+        let order_by_values = |order_by_child_fields| -> Vec<Name> {
+            let options = ApiSchemaOptions {
+                pluralization: Pluralization::English,
+                order_by_child_fields,
+            };
+            let schema = api_schema_with_options(&logger(), &input_schema, options)
+                .expect("Failed to derive API schema");
+            match ast::get_named_type(&schema, &"User_orderBy".to_string()) {
+                Some(TypeDefinition::Enum(t)) => {
+                    t.values.iter().map(|value| value.name.clone()).collect()
+                }
+                _ => panic!("User_orderBy enum is missing"),
+            }
+        };
+
+        assert_eq!(
+            order_by_values(true),
+            vec!["id", "name", "best", "best__id", "best__title"]
         );
+        assert_eq!(order_by_values(false), vec!["id", "name", "best"]);
     }
 }
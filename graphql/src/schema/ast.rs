@@ -6,6 +6,7 @@ use std::str::FromStr;
 
 use crate::execution::ObjectOrInterface;
 use crate::query::ast as qast;
+use graph::data::schema::stored_entity_type;
 use graph::data::store;
 use graph::prelude::*;
 
@@ -485,7 +486,7 @@ pub fn validate_entity(
     let object_type_definitions = get_object_type_definitions(schema);
     let object_type = object_type_definitions
         .iter()
-        .find(|object_type| object_type.name == key.entity_type)
+        .find(|object_type| stored_entity_type(object_type) == key.entity_type)
         .ok_or_else(|| {
             format_err!(
                 "Entity {}[{}]: unknown entity type `{}`",
@@ -4,4 +4,10 @@ pub mod api;
 /// Utilities for working with GraphQL schema ASTs.
 pub mod ast;
 
-pub use self::api::{api_schema, APISchemaError};
+/// English pluralization of type names.
+mod plural;
+
+pub use self::api::{
+    api_schema, api_schema_with_options, api_schema_with_pluralization, APISchemaError,
+    ApiSchemaExt, ApiSchemaOptions, Pluralization,
+};
@@ -0,0 +1,124 @@
+/// Words whose plural doesn't follow the regular rules.
+const IRREGULAR: &[(&str, &str)] = &[
+    ("analysis", "analyses"),
+    ("child", "children"),
+    ("criterion", "criteria"),
+    ("datum", "data"),
+    ("foot", "feet"),
+    ("goose", "geese"),
+    ("index", "indices"),
+    ("man", "men"),
+    ("matrix", "matrices"),
+    ("mouse", "mice"),
+    ("ox", "oxen"),
+    ("person", "people"),
+    ("tooth", "teeth"),
+    ("vertex", "vertices"),
+    ("woman", "women"),
+];
+
+/// Words that are the same in singular and plural.
+const UNCOUNTABLE: &[&str] = &[
+    "data",
+    "deer",
+    "equipment",
+    "fish",
+    "information",
+    "metadata",
+    "money",
+    "news",
+    "series",
+    "sheep",
+    "species",
+];
+
+/// Returns the English plural of a type name like `TokenRegistry`.
+///
+/// Only the last word of a camel-cased name is pluralized, so that
+/// `TokenRegistry` becomes `TokenRegistries`.
+pub fn pluralize(name: &str) -> String {
+    // The last word starts at the last uppercase letter
+    let split = name
+        .char_indices()
+        .filter(|(_, c)| c.is_uppercase())
+        .map(|(i, _)| i)
+        .last()
+        .unwrap_or(0);
+    let (prefix, word) = name.split_at(split);
+    format!("{}{}", prefix, pluralize_word(word))
+}
+
+fn pluralize_word(word: &str) -> String {
+    let lower = word.to_lowercase();
+
+    if UNCOUNTABLE.contains(&lower.as_str()) {
+        return word.to_owned();
+    }
+
+    if let Some((_, plural)) = IRREGULAR.iter().find(|(singular, _)| *singular == lower) {
+        // Keep the case of the first letter
+        return match word.chars().next() {
+            Some(first) if first.is_uppercase() => {
+                let mut chars = plural.chars();
+                let first = chars.next().unwrap().to_uppercase();
+                first.chain(chars).collect()
+            }
+            _ => (*plural).to_owned(),
+        };
+    }
+
+    let ends_with_consonant_y = lower.ends_with('y')
+        && lower
+            .chars()
+            .rev()
+            .nth(1)
+            .map_or(false, |c| !"aeiou".contains(c));
+    if ends_with_consonant_y {
+        return format!("{}ies", &word[..word.len() - 1]);
+    }
+
+    if ["s", "x", "z", "ch", "sh"]
+        .iter()
+        .any(|suffix| lower.ends_with(suffix))
+    {
+        return format!("{}es", word);
+    }
+
+    format!("{}s", word)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::pluralize;
+
+    #[test]
+    fn pluralizes_regular_names() {
+        for (singular, plural) in &[
+            ("User", "Users"),
+            ("Day", "Days"),
+            ("Registry", "Registries"),
+            ("Address", "Addresses"),
+            ("Box", "Boxes"),
+            ("Match", "Matches"),
+            ("Wish", "Wishes"),
+            ("TokenRegistry", "TokenRegistries"),
+            ("ERC20Transfer", "ERC20Transfers"),
+            ("lowercase", "lowercases"),
+        ] {
+            assert_eq!(&pluralize(singular), plural);
+        }
+    }
+
+    #[test]
+    fn pluralizes_irregular_names() {
+        for (singular, plural) in &[
+            ("Person", "People"),
+            ("Child", "Children"),
+            ("TokenIndex", "TokenIndices"),
+            ("Sheep", "Sheep"),
+            ("PriceData", "PriceData"),
+        ] {
+            assert_eq!(&pluralize(singular), plural);
+        }
+    }
+}
@@ -1,10 +1,12 @@
 use graphql_parser::{query as q, query::Name, schema as s, schema::ObjectType};
-use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use std::mem::discriminant;
 
+use graph::data::schema::stored_entity_type;
 use graph::prelude::*;
 
-use crate::execution::ObjectOrInterface;
+use crate::execution::{ObjectOrInterface, Selection};
 use crate::schema::ast as sast;
 
 /// Builds a EntityQuery from GraphQL arguments.
@@ -17,23 +19,69 @@ pub fn build_query<'a>(
     max_first: u32,
 ) -> Result<EntityQuery, QueryExecutionError> {
     let entity = entity.into();
-    let entity_types = match &entity {
-        ObjectOrInterface::Object(object) => vec![object.name.clone()],
-        ObjectOrInterface::Interface(interface) => types_for_interface[&interface.name]
-            .iter()
-            .map(|o| o.name.clone())
-            .collect(),
+    let entity_types = entity
+        .object_types(types_for_interface)?
+        .into_iter()
+        .map(|o| stored_entity_type(o).to_owned())
+        .collect();
+    let (order_by, order_by_child) = match build_order_by(entity, arguments)? {
+        Some(OrderBy::Attribute(name, value_type)) => (Some((name, value_type)), None),
+        Some(OrderBy::ChildAttribute(child)) => (None, Some(child)),
+        None => (None, None),
     };
     Ok(EntityQuery {
         subgraph_id: parse_subgraph_id(entity)?,
         entity_types,
         range: build_range(arguments, max_first)?,
         filter: build_filter(entity, arguments)?,
-        order_by: build_order_by(entity, arguments)?,
+        order_by,
+        order_by_child,
         order_direction: build_order_direction(arguments)?,
+        select: None,
+        query_id: None,
+        block: None,
     })
 }
 
+/// Determines the attributes to load for entities of type `entity` to
+/// resolve the fields in `selection`: the `id` and the selected fields that
+/// are stored with the entities, which leaves out derived fields and
+/// introspection fields like `__typename`.
+pub fn build_select<'a>(
+    entity: impl Into<ObjectOrInterface<'a>>,
+    selection: &Selection,
+    types_for_interface: &'a BTreeMap<Name, Vec<ObjectType>>,
+) -> Result<BTreeSet<String>, QueryExecutionError> {
+    let object_types = entity.into().object_types(types_for_interface)?;
+    let is_stored = |name: &Name| {
+        !name.starts_with("__")
+            && object_types.iter().any(|object_type| {
+                sast::get_field(*object_type, name).map_or(false, |field| {
+                    sast::get_derived_from_directive(field).is_none()
+                })
+            })
+    };
+
+    Ok(std::iter::once(String::from("id"))
+        .chain(
+            selection
+                .field_names()
+                .iter()
+                .filter(|name| is_stored(name))
+                .cloned(),
+        )
+        .collect())
+}
+
+/// What the `orderBy` argument orders entities by.
+enum OrderBy {
+    /// An attribute of the entities.
+    Attribute(String, ValueType),
+    /// An attribute of the entities a reference attribute points to, for
+    /// `orderBy` values of the form `<field>__<child_field>`.
+    ChildAttribute(EntityOrderByChild),
+}
+
 /// Parses GraphQL arguments into a EntityRange, if present.
 fn build_range(
     arguments: &HashMap<&q::Name, q::Value>,
@@ -165,31 +213,211 @@ fn list_values(value: Value, filter_type: &str) -> Result<Vec<Value>, QueryExecu
     }
 }
 
+/// Fails with `QueryExecutionError::ListTooLong` if an `_in` or `_not_in`
+/// filter in `filter` has more than `max_length` values. Huge lists exceed
+/// the parameter limits of the store or lead to pathological query plans.
+pub fn check_list_lengths(
+    filter: &Option<EntityFilter>,
+    max_length: usize,
+) -> Result<(), QueryExecutionError> {
+    fn check(filter: &EntityFilter, max_length: usize) -> Result<(), QueryExecutionError> {
+        let (attribute, values, suffix) = match filter {
+            EntityFilter::And(filters) | EntityFilter::Or(filters) => {
+                return filters
+                    .iter()
+                    .try_for_each(|filter| check(filter, max_length));
+            }
+            EntityFilter::In(attribute, values) => (attribute, values, "_in"),
+            EntityFilter::NotIn(attribute, values) => (attribute, values, "_not_in"),
+            _ => return Ok(()),
+        };
+        if values.len() > max_length {
+            Err(QueryExecutionError::ListTooLong {
+                filter: format!("{}{}", attribute, suffix),
+                length: values.len(),
+                max: max_length,
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    filter
+        .as_ref()
+        .map_or(Ok(()), |filter| check(filter, max_length))
+}
+
+/// Splits `query` into several queries if one of its top-level `_in`
+/// filters has more than `chunk_size` distinct values; each query filters
+/// by a chunk of the values. Since the results of the queries are merged
+/// and cut down to the range of `query` by `merge_chunks`, each query asks
+/// for as many entities as the range spans, and loads the attribute they
+/// are ordered by.
+///
+/// Returns `None` if the query doesn't need to be split, or can't be
+/// because it is ordered by the attribute of a referenced entity.
+pub fn split_query(query: &EntityQuery, chunk_size: usize) -> Option<Vec<EntityQuery>> {
+    if chunk_size == 0 || query.order_by_child.is_some() {
+        return None;
+    }
+
+    let (filters, index, attribute, values) = split_in_filter(query)?;
+
+    // Entities with a given value of `attribute` would otherwise be
+    // returned by several queries
+    let mut seen = HashSet::new();
+    let values: Vec<_> = values
+        .iter()
+        .filter(|value| seen.insert(value.to_string()))
+        .cloned()
+        .collect();
+    if values.len() <= chunk_size {
+        return None;
+    }
+
+    let range = EntityRange {
+        first: query.range.first.map(|first| first + query.range.skip),
+        skip: 0,
+    };
+    let select = query.select.clone().map(|mut select| {
+        if let Some((order_by, _)) = &query.order_by {
+            select.insert(order_by.clone());
+        }
+        select
+    });
+
+    Some(
+        values
+            .chunks(chunk_size)
+            .map(|chunk| {
+                let mut filters = filters.clone();
+                filters[index] = EntityFilter::In(attribute.clone(), chunk.to_vec());
+                EntityQuery {
+                    filter: Some(EntityFilter::And(filters)),
+                    range: range.clone(),
+                    select: select.clone(),
+                    ..query.clone()
+                }
+            })
+            .collect(),
+    )
+}
+
+/// The top-level filters of `query`, and the position, attribute and
+/// values of the longest `_in` filter among them, which `split_query`
+/// splits.
+fn split_in_filter(
+    query: &EntityQuery,
+) -> Option<(&Vec<EntityFilter>, usize, &Attribute, &Vec<Value>)> {
+    let filters = match &query.filter {
+        Some(EntityFilter::And(filters)) => filters,
+        _ => return None,
+    };
+    filters
+        .iter()
+        .enumerate()
+        .filter_map(|(index, filter)| match filter {
+            EntityFilter::In(attribute, values) => Some((filters, index, attribute, values)),
+            _ => None,
+        })
+        .max_by_key(|(_, _, _, values)| values.len())
+}
+
+/// Combines the results of the queries `split_query` split `query` into,
+/// ordering them by the attribute `query` is ordered by and then by ID, and
+/// applying the range of `query`. Values are ordered like `Value::compare`
+/// orders them, with missing values last in ascending order; strings are
+/// therefore ordered byte by byte, regardless of the collation of the store.
+pub fn merge_chunks(query: &EntityQuery, results: Vec<Vec<Entity>>) -> Vec<Entity> {
+    fn compare(a: Option<&Value>, b: Option<&Value>) -> Ordering {
+        match (a, b) {
+            (None, None) | (Some(Value::Null), Some(Value::Null)) => Ordering::Equal,
+            (None, _) | (Some(Value::Null), _) => Ordering::Greater,
+            (_, None) | (_, Some(Value::Null)) => Ordering::Less,
+            (Some(a), Some(b)) => a.compare(b).unwrap_or(Ordering::Equal),
+        }
+    }
+
+    let order_by = query
+        .order_by
+        .as_ref()
+        .map_or("id", |(attribute, _)| attribute.as_str());
+
+    let mut entities: Vec<_> = results.into_iter().flatten().collect();
+    entities.sort_by(|a, b| {
+        let order = compare(a.get(order_by), b.get(order_by));
+        let order = match query.order_direction {
+            Some(EntityOrder::Descending) => order.reverse(),
+            _ => order,
+        };
+        order.then_with(|| compare(a.get("id"), b.get("id")))
+    });
+
+    let entities = entities.into_iter().skip(query.range.skip as usize);
+    match query.range.first {
+        Some(first) => entities.take(first as usize).collect(),
+        None => entities.collect(),
+    }
+}
+
 /// Parses GraphQL arguments into an field name to order by, if present.
 fn build_order_by(
     entity: ObjectOrInterface,
     arguments: &HashMap<&q::Name, q::Value>,
-) -> Result<Option<(String, ValueType)>, QueryExecutionError> {
+) -> Result<Option<OrderBy>, QueryExecutionError> {
     arguments
         .get(&"orderBy".to_string())
         .map_or(Ok(None), |value| match value {
-            q::Value::Enum(name) => {
-                let field = sast::get_field(entity, &name).ok_or_else(|| {
-                    QueryExecutionError::EntityFieldError(entity.name().to_owned(), name.clone())
-                })?;
-                sast::get_field_value_type(&field.field_type)
-                    .map(|value_type| Some((name.to_owned(), value_type)))
+            q::Value::Enum(name) => match sast::get_field(entity, &name) {
+                Some(field) => sast::get_field_value_type(&field.field_type)
+                    .map(|value_type| Some(OrderBy::Attribute(name.to_owned(), value_type)))
                     .map_err(|_| {
                         QueryExecutionError::OrderByNotSupportedError(
                             entity.name().to_owned(),
                             name.clone(),
                         )
-                    })
-            }
+                    }),
+                None => build_order_by_child(entity, name).map(Some),
+            },
             _ => Ok(None),
         })
 }
 
+/// Parses an `orderBy` value of the form `<field>__<child_field>` into the
+/// reference field and the field of the referenced entities to order by.
+fn build_order_by_child(
+    entity: ObjectOrInterface,
+    name: &str,
+) -> Result<OrderBy, QueryExecutionError> {
+    let mut parts = name.splitn(2, "__");
+    let (relation, attribute) = match (parts.next(), parts.next()) {
+        (Some(relation), Some(attribute)) => (relation, attribute),
+        _ => {
+            return Err(QueryExecutionError::EntityFieldError(
+                entity.name().to_owned(),
+                name.to_owned(),
+            ))
+        }
+    };
+
+    let field = sast::get_field(entity, &relation.to_owned()).ok_or_else(|| {
+        QueryExecutionError::EntityFieldError(entity.name().to_owned(), name.to_owned())
+    })?;
+    if sast::is_list_or_non_null_list_field(field)
+        || sast::get_derived_from_directive(field).is_some()
+    {
+        return Err(QueryExecutionError::OrderByNotSupportedError(
+            entity.name().to_owned(),
+            name.to_owned(),
+        ));
+    }
+
+    Ok(OrderBy::ChildAttribute(EntityOrderByChild {
+        relation: relation.to_owned(),
+        attribute: attribute.to_owned(),
+    }))
+}
+
 /// Parses GraphQL arguments into a EntityOrder, if present.
 fn build_order_direction(
     arguments: &HashMap<&q::Name, q::Value>,
@@ -203,6 +431,34 @@ fn build_order_direction(
         }))
 }
 
+/// Sets the `__typename` of entities loaded from the store to the name of
+/// their type among `object_types`. The store sets it to the entity type the
+/// entities are stored as, which differs for types with an
+/// `@entity(name: ...)` override.
+pub fn set_graphql_type_names<'a>(
+    entities: impl IntoIterator<Item = &'a mut Entity>,
+    object_types: &[&ObjectType],
+) {
+    let renamed_types: HashMap<_, _> = object_types
+        .iter()
+        .map(|object_type| (stored_entity_type(object_type), &object_type.name))
+        .filter(|(stored_name, name)| stored_name != name)
+        .collect();
+    if renamed_types.is_empty() {
+        return;
+    }
+
+    for entity in entities {
+        let name = match entity.get("__typename") {
+            Some(Value::String(stored_name)) => renamed_types.get(stored_name.as_str()),
+            _ => None,
+        };
+        if let Some(name) = name {
+            entity.set("__typename", name.to_string());
+        }
+    }
+}
+
 /// Parses the subgraph ID from the ObjectType directives.
 pub fn parse_subgraph_id<'a>(
     entity: impl Into<ObjectOrInterface<'a>>,
@@ -257,7 +513,8 @@ pub fn collect_entities_from_query_field(
                         // Obtain the subgraph ID from the object type
                         if let Ok(subgraph_id) = parse_subgraph_id(object_type) {
                             // Add the (subgraph_id, entity_name) tuple to the result set
-                            entities.insert((subgraph_id, object_type.name.to_owned()));
+                            entities
+                                .insert((subgraph_id, stored_entity_type(object_type).to_owned()));
                         }
                     }
 
@@ -276,6 +533,26 @@ pub fn collect_entities_from_query_field(
     entities.into_iter().collect()
 }
 
+/// Collects all entity types of the given subgraph deployment.
+pub fn collect_entities_for_deployment(
+    schema: &s::Document,
+    deployment: &SubgraphDeploymentId,
+) -> Vec<SubgraphEntityPair> {
+    sast::get_object_type_definitions(schema)
+        .into_iter()
+        .filter(|object_type| {
+            sast::get_object_type_directive(object_type, String::from("entity")).is_some()
+        })
+        .filter(|object_type| parse_subgraph_id(*object_type).ok().as_ref() == Some(deployment))
+        .map(|object_type| {
+            (
+                deployment.clone(),
+                stored_entity_type(object_type).to_owned(),
+            )
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use graphql_parser::{
@@ -287,7 +564,9 @@ mod tests {
 
     use graph::prelude::*;
 
-    use super::build_query;
+    use super::{build_query, collect_entities_for_deployment, collect_entities_from_query_field};
+    use crate::schema::api::{ApiSchemaExt, Pluralization};
+    use crate::schema::ast as sast;
 
     fn default_object() -> ObjectType {
         let subgraph_id_argument = (
@@ -435,6 +714,52 @@ mod tests {
         );
     }
 
+    #[test]
+    fn build_query_parses_order_by_child_field() {
+        let token = ObjectType {
+            fields: vec![
+                field("owner", Type::NamedType("User".to_owned())),
+                field(
+                    "holders",
+                    Type::ListType(Box::new(Type::NamedType("User".to_owned()))),
+                ),
+            ],
+            ..default_object()
+        };
+        let order_by = "orderBy".to_string();
+
+        let mut args = default_arguments();
+        args.insert(&order_by, q::Value::Enum("owner__name".to_string()));
+        let query = build_query(&token, &args, &BTreeMap::new(), std::u32::MAX).unwrap();
+        assert_eq!(query.order_by, None);
+        assert_eq!(
+            query.order_by_child,
+            Some(EntityOrderByChild {
+                relation: "owner".to_string(),
+                attribute: "name".to_string(),
+            })
+        );
+
+        // Only single references can be ordered by
+        let mut args = default_arguments();
+        args.insert(&order_by, q::Value::Enum("holders__name".to_string()));
+        match build_query(&token, &args, &BTreeMap::new(), std::u32::MAX) {
+            Err(QueryExecutionError::OrderByNotSupportedError(_, name)) => {
+                assert_eq!(name, "holders__name")
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+
+        let mut args = default_arguments();
+        args.insert(&order_by, q::Value::Enum("creator__name".to_string()));
+        match build_query(&token, &args, &BTreeMap::new(), std::u32::MAX) {
+            Err(QueryExecutionError::EntityFieldError(_, name)) => {
+                assert_eq!(name, "creator__name")
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
     #[test]
     fn build_query_ignores_order_by_from_non_enum_values() {
         let order_by = "orderBy".to_string();
@@ -570,4 +895,64 @@ mod tests {
             )]))
         )
     }
+
+    #[test]
+    fn build_query_uses_the_stored_entity_name() {
+        let entity_directive = Directive {
+            name: "entity".to_string(),
+            position: Pos::default(),
+            arguments: vec![(
+                s::Name::from("name"),
+                s::Value::String("LegacyEntity".to_string()),
+            )],
+        };
+        let mut object = object("Entity");
+        object.directives.push(entity_directive);
+
+        assert_eq!(
+            build_query(
+                &object,
+                &default_arguments(),
+                &BTreeMap::new(),
+                std::u32::MAX
+            )
+            .unwrap()
+            .entity_types,
+            vec!["LegacyEntity".to_string()]
+        );
+    }
+
+    #[test]
+    fn subscriptions_use_the_stored_entity_name() {
+        let id = SubgraphDeploymentId::new("renamedEntities").unwrap();
+        let logger = Logger::root(slog::Discard, o!());
+        let schema = Schema::parse(
+            r#"type Account @entity(name: "User") { id: ID!, name: String! }"#,
+            id.clone(),
+        )
+        .unwrap();
+        let schema = Schema::api(&logger, schema, Pluralization::Legacy).unwrap();
+
+        let subscription_type = sast::get_root_subscription_type(&schema.document).unwrap();
+        let query = graphql_parser::parse_query("subscription { accounts { name } }").unwrap();
+        let field = match &query.definitions[0] {
+            q::Definition::Operation(q::OperationDefinition::Subscription(subscription)) => {
+                match &subscription.selection_set.items[0] {
+                    q::Selection::Field(field) => field.clone(),
+                    _ => unreachable!(),
+                }
+            }
+            _ => unreachable!(),
+        };
+
+        let expected = vec![(id.clone(), "User".to_string())];
+        assert_eq!(
+            collect_entities_from_query_field(&schema.document, subscription_type, &field),
+            expected
+        );
+        assert_eq!(
+            collect_entities_for_deployment(&schema.document, &id),
+            expected
+        );
+    }
 }
@@ -1,19 +1,75 @@
 use graphql_parser::{query as q, schema as s};
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::convert::TryFrom;
 use std::result;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use graph::components::store::*;
+use graph::data::graphql::ToGraphQlValue;
+use graph::data::schema::stored_entity_type;
 use graph::prelude::*;
 
 use crate::prelude::*;
+use crate::schema::api::{BLOCK_TYPE, META_TYPE};
 use crate::schema::ast as sast;
-use crate::store::query::{collect_entities_from_query_field, parse_subgraph_id};
+use crate::store::query::{
+    build_select, check_list_lengths, collect_entities_for_deployment,
+    collect_entities_from_query_field, merge_chunks, parse_subgraph_id, set_graphql_type_names,
+    split_query,
+};
+use crate::subscription::SubscriptionMultiplexer;
+
+/// The blocks that deployments had processed when a query first asked for
+/// them. Stored in the `QueryContext`, so that all fields of a query see the
+/// same block.
+#[derive(Default)]
+struct DeploymentBlocks(Mutex<HashMap<SubgraphDeploymentId, Option<EthereumBlockPointer>>>);
+
+/// How long the failure of a deployment, or that it hasn't failed, is reused
+/// for later queries before it is looked up again.
+const DEPLOYMENT_HEALTH_TTL: Duration = Duration::from_secs(1);
+
+/// The failures of deployments, with the time they were looked up at.
+#[derive(Default)]
+struct DeploymentHealth(Mutex<HashMap<SubgraphDeploymentId, (Instant, Option<(u64, String)>)>>);
+
+impl DeploymentHealth {
+    /// Returns the failure of `subgraph_id`, looking it up with `lookup`
+    /// unless it was looked up less than `DEPLOYMENT_HEALTH_TTL` ago.
+    fn failure<F>(
+        &self,
+        subgraph_id: &SubgraphDeploymentId,
+        lookup: F,
+    ) -> Result<Option<(u64, String)>, Error>
+    where
+        F: FnOnce() -> Result<Option<(u64, String)>, Error>,
+    {
+        if let Some((looked_up_at, failure)) = self.0.lock().unwrap().get(subgraph_id) {
+            if looked_up_at.elapsed() < DEPLOYMENT_HEALTH_TTL {
+                return Ok(failure.clone());
+            }
+        }
+
+        let failure = lookup()?;
+        self.0
+            .lock()
+            .unwrap()
+            .insert(subgraph_id.clone(), (Instant::now(), failure.clone()));
+        Ok(failure)
+    }
+}
 
 /// A resolver that fetches entities from a `Store`.
+///
+/// Clones of the resolver share the health of deployments they looked up, so
+/// that not every query has to look it up again.
 pub struct StoreResolver<S> {
     logger: Logger,
     store: Arc<S>,
+    multiplexer: Option<SubscriptionMultiplexer>,
+    sync_statuses: Option<Arc<DeploymentSyncStatuses>>,
+    health: Arc<DeploymentHealth>,
 }
 
 impl<S> Clone for StoreResolver<S>
@@ -24,6 +80,9 @@ where
         StoreResolver {
             logger: self.logger.clone(),
             store: self.store.clone(),
+            multiplexer: self.multiplexer.clone(),
+            sync_statuses: self.sync_statuses.clone(),
+            health: self.health.clone(),
         }
     }
 }
@@ -36,9 +95,26 @@ where
         StoreResolver {
             logger: logger.new(o!("component" => "StoreResolver")),
             store,
+            multiplexer: None,
+            sync_statuses: None,
+            health: Default::default(),
         }
     }
 
+    /// Shares store subscriptions for GraphQL subscriptions through the
+    /// given multiplexer instead of subscribing to the store directly.
+    pub fn with_multiplexer(mut self, multiplexer: SubscriptionMultiplexer) -> Self {
+        self.multiplexer = Some(multiplexer);
+        self
+    }
+
+    /// Reads how far deployments have synced from `sync_statuses`; without
+    /// it, their sync status is unknown.
+    pub fn with_sync_statuses(mut self, sync_statuses: Arc<DeploymentSyncStatuses>) -> Self {
+        self.sync_statuses = Some(sync_statuses);
+        self
+    }
+
     /// Adds a filter for matching entities that correspond to a derived field.
     ///
     /// Returns true if the field is a derived field (i.e., if it is defined with
@@ -154,24 +230,22 @@ where
             })
             .unwrap_or(true)
     }
-}
 
-impl<S> Resolver for StoreResolver<S>
-where
-    S: Store,
-{
-    fn resolve_objects(
+    /// Resolves the entities referenced by a parent object, loading only the
+    /// attributes in `select`, or all of them if `select` is `None`.
+    fn find_objects(
         &self,
+        ctx: &QueryContext,
         parent: &Option<q::Value>,
-        _field: &q::Name,
         field_definition: &s::Field,
         object_type: ObjectOrInterface<'_>,
         arguments: &HashMap<&q::Name, q::Value>,
         types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
-        max_first: u32,
+        select: Option<BTreeSet<String>>,
     ) -> Result<q::Value, QueryExecutionError> {
-        let object_type = object_type.into();
-        let mut query = build_query(object_type, arguments, types_for_interface, max_first)?;
+        let mut query = build_query(object_type, arguments, types_for_interface, ctx.max_first)?;
+        check_list_lengths(&query.filter, ctx.max_in_list_length)?;
+        query.select = select;
 
         // Add matching filter for derived fields
         let derived_from_field = sast::get_derived_from_field(object_type, field_definition);
@@ -195,15 +269,242 @@ where
             Self::add_filter_for_reference_field(&mut query, parent, field_definition, object_type);
         }
 
-        let mut entity_values = Vec::new();
-        for entity in self.store.find(query)? {
-            entity_values.push(entity.into())
+        let mut entities = self.find_in_chunks(ctx, query)?;
+        set_graphql_type_names(
+            &mut entities,
+            &object_type.object_types(types_for_interface)?,
+        );
+        Ok(q::Value::List(
+            entities.into_iter().map(|entity| entity.into()).collect(),
+        ))
+    }
+
+    /// Runs `query` against the store on behalf of the GraphQL query that
+    /// `ctx` belongs to.
+    fn find(
+        &self,
+        ctx: &QueryContext,
+        mut query: EntityQuery,
+    ) -> Result<Vec<Entity>, QueryExecutionError> {
+        trace!(ctx.logger, "Query store";
+               "entity_types" => query.entity_types.join(", "));
+        query.query_id = ctx.query_id.clone();
+        let subgraph_id = query.subgraph_id.clone();
+        let start = Instant::now();
+        let entities = self.store.find(query)?;
+        if let Some(metrics) = &ctx.metrics {
+            metrics.observe_store_query(&subgraph_id, start.elapsed(), entities.len());
+        }
+        Ok(entities)
+    }
+
+    /// Returns the block number and error at which `subgraph_id` failed, if
+    /// it has failed.
+    fn deployment_failure(
+        &self,
+        subgraph_id: &SubgraphDeploymentId,
+    ) -> Result<Option<(u64, String)>, Error> {
+        self.health.failure(subgraph_id, || {
+            self.store.deployment_failure(subgraph_id.clone())
+        })
+    }
+
+    /// Returns the block that `subgraph_id` has processed. It is only looked
+    /// up once per query.
+    fn block_ptr(
+        &self,
+        ctx: &QueryContext,
+        subgraph_id: &SubgraphDeploymentId,
+    ) -> Result<Option<EthereumBlockPointer>, QueryExecutionError> {
+        let blocks = ctx.extension_or_insert_with(DeploymentBlocks::default);
+        if let Some(ptr) = blocks.0.lock().unwrap().get(subgraph_id) {
+            return Ok(ptr.clone());
+        }
+
+        let ptr = self
+            .store
+            .block_ptr(subgraph_id.clone())
+            .map_err(QueryExecutionError::StoreError)?;
+        Ok(blocks
+            .0
+            .lock()
+            .unwrap()
+            .entry(subgraph_id.clone())
+            .or_insert(ptr)
+            .clone())
+    }
+
+    /// Runs `query` against the store, split into several queries if it has
+    /// an `_in` filter with more than `ctx.in_list_chunk_size` values. The
+    /// entities those queries find are merged in memory.
+    fn find_in_chunks(
+        &self,
+        ctx: &QueryContext,
+        query: EntityQuery,
+    ) -> Result<Vec<Entity>, QueryExecutionError> {
+        match split_query(&query, ctx.in_list_chunk_size) {
+            Some(chunks) => {
+                let results = chunks
+                    .into_iter()
+                    .map(|chunk| self.find(ctx, chunk))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(merge_chunks(&query, results))
+            }
+            None => self.find(ctx, query),
+        }
+    }
+
+    /// Loads the entity of `object_type` with ID `id`. Entities of object
+    /// types are looked up by their primary key, entities of interfaces
+    /// with a query across the types that implement the interface.
+    fn entity_by_id(
+        &self,
+        ctx: &QueryContext,
+        object_type: ObjectOrInterface<'_>,
+        id: &str,
+        types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+    ) -> Result<Option<Entity>, QueryExecutionError> {
+        // The subgraph_id directive is injected in all types.
+        let subgraph_id = parse_subgraph_id(object_type).unwrap();
+
+        match object_type {
+            ObjectOrInterface::Object(object) => self.store.get(EntityKey {
+                subgraph_id,
+                entity_type: stored_entity_type(object).to_owned(),
+                entity_id: id.to_owned(),
+            }),
+            ObjectOrInterface::Interface(_) => {
+                let entity_types = object_type
+                    .object_types(types_for_interface)?
+                    .into_iter()
+                    .map(|o| stored_entity_type(o).to_owned())
+                    .collect();
+                let range = EntityRange::first(1);
+                let mut query = EntityQuery::new(subgraph_id, entity_types, range);
+                query.filter = Some(EntityFilter::Equal(String::from("id"), Value::from(id)));
+                Ok(self.find(ctx, query)?.into_iter().next())
+            }
+        }
+    }
+}
+
+impl<S> Resolver for StoreResolver<S>
+where
+    S: Store,
+{
+    fn resolve_objects(
+        &self,
+        ctx: &QueryContext,
+        parent: &Option<q::Value>,
+        _field: &q::Name,
+        field_definition: &s::Field,
+        object_type: ObjectOrInterface<'_>,
+        arguments: &HashMap<&q::Name, q::Value>,
+        types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        self.find_objects(
+            ctx,
+            parent,
+            field_definition,
+            object_type,
+            arguments,
+            types_for_interface,
+            None,
+        )
+    }
+
+    fn resolve_objects_with_selection(
+        &self,
+        ctx: &QueryContext,
+        parent: &Option<q::Value>,
+        _field: &q::Name,
+        field_definition: &s::Field,
+        object_type: ObjectOrInterface<'_>,
+        arguments: &HashMap<&q::Name, q::Value>,
+        types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+        selection: &Selection,
+    ) -> Result<q::Value, QueryExecutionError> {
+        // Only load the attributes needed for the selected fields, which
+        // keeps rows narrow for entities with many attributes
+        let select = build_select(object_type, selection, types_for_interface)?;
+        self.find_objects(
+            ctx,
+            parent,
+            field_definition,
+            object_type,
+            arguments,
+            types_for_interface,
+            Some(select),
+        )
+    }
+
+    fn resolve_objects_in_batches(
+        &self,
+        ctx: &QueryContext,
+        _field_definition: &s::Field,
+        object_type: ObjectOrInterface<'_>,
+        arguments: &HashMap<&q::Name, q::Value>,
+        types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+        selection: &Selection,
+        batch_size: usize,
+        sink: &mut dyn FnMut(Vec<q::Value>) -> Result<(), QueryExecutionError>,
+    ) -> Result<(), QueryExecutionError> {
+        let mut query = build_query(object_type, arguments, types_for_interface, ctx.max_first)?;
+        check_list_lengths(&query.filter, ctx.max_in_list_length)?;
+        query.select = Some(build_select(object_type, selection, types_for_interface)?);
+        query.order_by = Some((String::from("id"), ValueType::ID));
+        query.order_by_child = None;
+        query.order_direction = Some(EntityOrder::Ascending);
+        query.range = EntityRange::first(batch_size as u32);
+        let filter = query.filter.take();
+        let object_types = object_type.object_types(types_for_interface)?;
+
+        // All batches are loaded at the same block, so that blocks processed
+        // while the entities are written don't change the result
+        query.block = self
+            .block_ptr(ctx, &query.subgraph_id)?
+            .map(|ptr| ptr.number);
+
+        // Each batch continues after the last entity of the previous one,
+        // which keeps the store from skipping over all earlier entities
+        let mut last_id: Option<String> = None;
+        loop {
+            let mut batch_query = query.clone();
+            batch_query.filter = match (&filter, &last_id) {
+                (filter, None) => filter.clone(),
+                (None, Some(id)) => Some(EntityFilter::GreaterThan(
+                    String::from("id"),
+                    Value::from(id),
+                )),
+                (Some(filter), Some(id)) => Some(EntityFilter::And(vec![
+                    filter.clone(),
+                    EntityFilter::GreaterThan(String::from("id"), Value::from(id)),
+                ])),
+            };
+
+            let mut entities = self.find(ctx, batch_query)?;
+            let is_last_batch = entities.len() < batch_size;
+            last_id = match entities.last() {
+                Some(entity) => Some(
+                    entity
+                        .id()
+                        .map_err(|e| QueryExecutionError::EntityParseError(e.to_string()))?,
+                ),
+                None => return Ok(()),
+            };
+
+            set_graphql_type_names(&mut entities, &object_types);
+            sink(entities.into_iter().map(|entity| entity.into()).collect())?;
+
+            if is_last_batch {
+                return Ok(());
+            }
         }
-        Ok(q::Value::List(entity_values))
     }
 
     fn resolve_object(
         &self,
+        ctx: &QueryContext,
         parent: &Option<q::Value>,
         field: &q::Field,
         field_definition: &s::Field,
@@ -211,38 +512,23 @@ where
         arguments: &HashMap<&q::Name, q::Value>,
         types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
     ) -> Result<q::Value, QueryExecutionError> {
+        // The block of `_meta` is part of the `_Meta_` object already
+        if object_type.name() == BLOCK_TYPE {
+            return Ok(match parent {
+                Some(q::Value::Object(meta)) => {
+                    meta.get(&field.name).cloned().unwrap_or(q::Value::Null)
+                }
+                _ => q::Value::Null,
+            });
+        }
+
         let id = arguments.get(&"id".to_string()).and_then(|id| match id {
             q::Value::String(s) => Some(s),
             _ => None,
         });
 
-        // The subgraph_id directive is injected in all types.
-        let subgraph_id = parse_subgraph_id(object_type).unwrap();
-        let subgraph_id_for_resolve_object = subgraph_id.clone();
-
-        let resolve_object_with_id = |id: &String| -> Result<Option<Entity>, QueryExecutionError> {
-            match object_type {
-                ObjectOrInterface::Object(_) => self.store.get(EntityKey {
-                    subgraph_id: subgraph_id_for_resolve_object,
-                    entity_type: object_type.name().to_owned(),
-                    entity_id: id.to_owned(),
-                }),
-                ObjectOrInterface::Interface(interface) => {
-                    let entity_types = types_for_interface[&interface.name]
-                        .iter()
-                        .map(|o| o.name.clone())
-                        .collect();
-                    let range = EntityRange::first(1);
-                    let mut query =
-                        EntityQuery::new(subgraph_id_for_resolve_object, entity_types, range);
-                    query.filter = Some(EntityFilter::Equal(String::from("id"), Value::from(id)));
-                    Ok(self.store.find(query)?.into_iter().next())
-                }
-            }
-        };
-
-        let entity = if let Some(id) = id {
-            resolve_object_with_id(id)?
+        let mut entity = if let Some(id) = id {
+            self.entity_by_id(ctx, object_type, id, types_for_interface)?
         } else {
             // Identify whether the field is derived with @derivedFrom
             let derived_from_field = sast::get_derived_from_field(object_type, field_definition);
@@ -263,7 +549,7 @@ where
                 Self::add_filter_for_derived_field(&mut query, parent, derived_from_field);
 
                 // Find the entity or entities that reference the parent entity
-                let entities = self.store.find(query)?;
+                let entities = self.find(ctx, query)?;
 
                 if entities.len() > 1 {
                     return Err(QueryExecutionError::AmbiguousDerivedFromResult(
@@ -276,9 +562,14 @@ where
                     entities.into_iter().next()
                 }
             } else {
+                // Both an unset reference and a reference to an entity that
+                // doesn't exist resolve to `null`; for non-null fields, the
+                // executor turns that into an error
                 match parent {
                     Some(q::Value::Object(parent_object)) => match parent_object.get(&field.name) {
-                        Some(q::Value::String(id)) => resolve_object_with_id(id)?,
+                        Some(q::Value::String(id)) => {
+                            self.entity_by_id(ctx, object_type, id, types_for_interface)?
+                        }
                         _ => None,
                     },
                     _ => panic!("top level queries must either take an `id` or return a list"),
@@ -286,11 +577,70 @@ where
             }
         };
 
+        set_graphql_type_names(&mut entity, &object_type.object_types(types_for_interface)?);
         Ok(entity.map_or(q::Value::Null, Into::into))
     }
 
+    fn resolve_entity(
+        &self,
+        ctx: &QueryContext,
+        _field: &q::Field,
+        _field_definition: &s::Field,
+        object_type: ObjectOrInterface<'_>,
+        id: &str,
+        _arguments: &HashMap<&q::Name, q::Value>,
+        types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+    ) -> Result<Option<q::Value>, QueryExecutionError> {
+        let mut entity = self.entity_by_id(ctx, object_type, id, types_for_interface)?;
+        set_graphql_type_names(&mut entity, &object_type.object_types(types_for_interface)?);
+        Ok(entity.map(Into::into))
+    }
+
+    fn estimate_rows(
+        &self,
+        ctx: &QueryContext,
+        field_definition: &s::Field,
+        object_type: ObjectOrInterface<'_>,
+        arguments: &HashMap<&q::Name, q::Value>,
+        types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+    ) -> Result<Option<u64>, QueryExecutionError> {
+        // `_meta` doesn't load any entities
+        if object_type.name() == META_TYPE || object_type.name() == BLOCK_TYPE {
+            return Ok(None);
+        }
+
+        // Plan collections the way `resolve_objects` does, which also checks
+        // the range arguments; single objects are looked up by id or through
+        // a reference and load at most one entity
+        if sast::is_list_or_non_null_list_field(field_definition) {
+            let query = build_query(object_type, arguments, types_for_interface, ctx.max_first)?;
+            check_list_lengths(&query.filter, ctx.max_in_list_length)?;
+            // `build_query` fills in the default of `first`; a range without
+            // a limit can still return no more than `max_first` entities
+            Ok(Some(u64::from(query.range.first.unwrap_or(ctx.max_first))))
+        } else {
+            Ok(Some(1))
+        }
+    }
+
+    fn is_complete_value(
+        &self,
+        _ctx: &QueryContext,
+        _object_type: &s::ObjectType,
+        _value: &q::Value,
+    ) -> bool {
+        // Entities and `_meta` hold the canonical values of their scalar
+        // fields; references to other entities are IDs and still resolved
+        true
+    }
+
+    fn supports_subscriptions(&self) -> bool {
+        true
+    }
+
     fn resolve_field_stream<'a, 'b>(
         &self,
+        _ctx: &QueryContext,
         schema: &'a s::Document,
         object_type: &'a s::ObjectType,
         field: &'b q::Field,
@@ -309,11 +659,95 @@ where
 
         // Subscribe to the store and return the entity change stream
         let deployment_id = parse_subgraph_id(object_type)?;
-        Ok(self.store.subscribe(entities).throttle_while_syncing(
+        let stream = match &self.multiplexer {
+            Some(multiplexer) => {
+                let store = self.store.clone();
+                let deployment_entities = collect_entities_for_deployment(schema, &deployment_id);
+                multiplexer.subscribe(deployment_id.clone(), entities, move || {
+                    store.subscribe(deployment_entities)
+                })
+            }
+            None => self.store.subscribe(entities),
+        };
+        Ok(stream.throttle_while_syncing(
             &self.logger,
             self.store.clone(),
             deployment_id,
             *SUBSCRIPTION_THROTTLE_INTERVAL,
         ))
     }
+
+    fn check_deployment_health(
+        &self,
+        _ctx: &QueryContext,
+        subgraph_id: &SubgraphDeploymentId,
+    ) -> result::Result<(), QueryExecutionError> {
+        match self.deployment_failure(subgraph_id) {
+            Ok(None) => Ok(()),
+            Ok(Some((block, message))) => {
+                Err(QueryExecutionError::SubgraphFailed { block, message })
+            }
+            Err(e) => Err(QueryExecutionError::StoreError(e)),
+        }
+    }
+
+    fn sync_status(
+        &self,
+        _ctx: &QueryContext,
+        subgraph_id: &SubgraphDeploymentId,
+    ) -> Option<DeploymentSyncStatus> {
+        self.sync_statuses
+            .as_ref()
+            .and_then(|sync_statuses| sync_statuses.get(subgraph_id))
+    }
+
+    fn resolve_meta(
+        &self,
+        ctx: &QueryContext,
+        subgraph_id: &SubgraphDeploymentId,
+    ) -> result::Result<q::Value, QueryExecutionError> {
+        let block = match self.block_ptr(ctx, subgraph_id)? {
+            Some(ptr) => {
+                let number = i32::try_from(ptr.number).map_err(|_| {
+                    QueryExecutionError::ValueParseError(
+                        String::from("Int"),
+                        format!("block number {} is out of range", ptr.number),
+                    )
+                })?;
+                object_value(vec![
+                    ("__typename", q::Value::String(String::from(BLOCK_TYPE))),
+                    ("hash", ptr.hash.to_graphql_value()),
+                    ("number", q::Value::Int(q::Number::from(number))),
+                ])
+            }
+            None => q::Value::Null,
+        };
+        let has_indexing_errors = self
+            .deployment_failure(subgraph_id)
+            .map_err(QueryExecutionError::StoreError)?
+            .is_some();
+        let sync_status = self.sync_status(ctx, subgraph_id);
+        let int_value =
+            |value: u64| q::Value::Int(q::Number::from(value.min(i32::max_value() as u64) as i32));
+
+        Ok(object_value(vec![
+            ("__typename", q::Value::String(String::from(META_TYPE))),
+            ("block", block),
+            ("deployment", q::Value::String(subgraph_id.to_string())),
+            ("hasIndexingErrors", q::Value::Boolean(has_indexing_errors)),
+            (
+                "chainHeadBlockNumber",
+                sync_status
+                    .as_ref()
+                    .map_or(q::Value::Null, |status| int_value(status.chain_head_block)),
+            ),
+            (
+                "estimatedSyncSeconds",
+                sync_status
+                    .as_ref()
+                    .and_then(|status| status.estimated_seconds_to_sync())
+                    .map_or(q::Value::Null, int_value),
+            ),
+        ]))
+    }
 }
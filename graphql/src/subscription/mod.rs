@@ -9,6 +9,10 @@ use crate::execution::*;
 use crate::query::ast as qast;
 use crate::schema::ast as sast;
 
+mod multiplexer;
+
+pub use self::multiplexer::SubscriptionMultiplexer;
+
 /// Options available for subscription execution.
 pub struct SubscriptionExecutionOptions<R>
 where
@@ -31,6 +35,10 @@ where
 
     /// Maximum value for the `first` argument.
     pub max_first: u32,
+
+    /// Decides which fields the subscription may select; use `AllowAll` to
+    /// not restrict access.
+    pub authorizer: Arc<dyn Authorizer>,
 }
 
 pub fn execute_subscription<R>(
@@ -43,6 +51,13 @@ where
     // Obtain the only operation of the subscription (fail if there is none or more than one)
     let operation = qast::get_operation(&subscription.query.document, None)?;
 
+    // Fail early if the resolver can't provide the streams of subscriptions
+    if !options.resolver.supports_subscriptions() {
+        return Err(SubscriptionError::from(QueryExecutionError::NotSupported(
+            "Subscriptions are not supported for this query".to_string(),
+        )));
+    }
+
     // Parse variable values
     let coerced_variable_values = match coerce_variable_values(
         &subscription.query.schema,
@@ -55,14 +70,14 @@ where
 
     // Create a fresh execution context
     let ctx = ExecutionContext {
-        logger: options.logger,
+        query: Arc::new(QueryContext::new(options.logger, None, options.max_first)),
         resolver: Arc::new(options.resolver),
         schema: subscription.query.schema.clone(),
         document: &subscription.query.document,
         fields: vec![],
         variable_values: Arc::new(coerced_variable_values),
-        deadline: None,
-        max_first: options.max_first,
+        response_size: Arc::new(ResponseSize::default()),
+        authorizer: options.authorizer,
     };
 
     match operation {
@@ -80,7 +95,7 @@ where
                 .map_err(|e| vec![e])?;
 
             info!(
-                ctx.logger,
+                ctx.query.logger,
                 "Execute subscription";
                 "query" => ctx.document.format(&Style::default().indent(0)).replace('\n', " "),
                 "complexity" => complexity,
@@ -147,7 +162,7 @@ where
     R: Resolver,
 {
     ctx.resolver
-        .resolve_field_stream(&ctx.schema.document, object_type, field)
+        .resolve_field_stream(&ctx.query, &ctx.schema.document, object_type, field)
         .map_err(SubscriptionError::from)
 }
 
@@ -160,13 +175,14 @@ fn map_source_to_response_stream<'a, R>(
 where
     R: Resolver + 'static,
 {
-    let logger = ctx.logger.clone();
+    let logger = ctx.query.logger.clone();
     let resolver = ctx.resolver.clone();
     let schema = ctx.schema.clone();
     let document = ctx.document.clone();
     let selection_set = selection_set.to_owned();
     let variable_values = ctx.variable_values.clone();
-    let max_first = ctx.max_first;
+    let max_first = ctx.query.max_first;
+    let authorizer = ctx.authorizer.clone();
 
     // Create a stream with a single empty event. By chaining this in front
     // of the real events, we trick the subscription into executing its query
@@ -190,6 +206,7 @@ where
                 event,
                 timeout.clone(),
                 max_first,
+                authorizer.clone(),
             )
         },
     )))
@@ -205,6 +222,7 @@ fn execute_subscription_event<R1>(
     event: StoreEvent,
     timeout: Option<Duration>,
     max_first: u32,
+    authorizer: Arc<dyn Authorizer>,
 ) -> QueryResult
 where
     R1: Resolver + 'static,
@@ -213,14 +231,18 @@ where
 
     // Create a fresh execution context with deadline.
     let ctx = ExecutionContext {
-        logger: logger,
+        query: Arc::new(QueryContext::new(
+            logger,
+            timeout.map(|t| Instant::now() + t),
+            max_first,
+        )),
         resolver: resolver,
         schema: schema,
         document: &document,
         fields: vec![],
         variable_values,
-        deadline: timeout.map(|t| Instant::now() + t),
-        max_first,
+        response_size: Arc::new(ResponseSize::default()),
+        authorizer,
     };
 
     // We have established that this exists earlier in the subscription execution
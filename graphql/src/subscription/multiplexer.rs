@@ -0,0 +1,508 @@
+use futures::sync::{mpsc, oneshot};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use graph::prelude::*;
+
+struct SubscriptionMultiplexerMetrics {
+    subscriber_count: Box<GaugeVec>,
+    dropped_events: Box<CounterVec>,
+}
+
+impl SubscriptionMultiplexerMetrics {
+    fn new<M: MetricsRegistry>(registry: Arc<M>) -> Self {
+        let subscriber_count = registry
+            .new_gauge_vec(
+                String::from("subscription_subscriber_count"),
+                String::from(
+                    "Counts the number of GraphQL subscriptions attached to a subgraph deployment",
+                ),
+                HashMap::new(),
+                vec![String::from("deployment")],
+            )
+            .expect("failed to create `subscription_subscriber_count` gauge");
+        let dropped_events = registry
+            .new_counter_vec(
+                String::from("subscription_dropped_events"),
+                String::from(
+                    "Counts the store events dropped for GraphQL subscriptions that fell behind",
+                ),
+                HashMap::new(),
+                vec![String::from("deployment")],
+            )
+            .expect("failed to create `subscription_dropped_events` counter");
+        Self {
+            subscriber_count,
+            dropped_events,
+        }
+    }
+
+    fn set_subscriber_count(&self, deployment: &SubgraphDeploymentId, count: usize) {
+        self.subscriber_count
+            .with_label_values(&[deployment.as_str()])
+            .set(count as f64);
+    }
+
+    fn inc_dropped_events(&self, deployment: &SubgraphDeploymentId) {
+        self.dropped_events
+            .with_label_values(&[deployment.as_str()])
+            .inc();
+    }
+}
+
+/// A downstream subscriber attached to a deployment's upstream store
+/// subscription.
+struct Subscriber {
+    entities: Vec<SubgraphEntityPair>,
+    sender: mpsc::Sender<StoreEvent>,
+
+    /// Changes that could not be delivered because the subscriber's channel
+    /// was full. They are handed to the subscriber as a single event once it
+    /// has caught up; until then, all further changes are added to them, so
+    /// that the subscriber receives changes in the order in which they were
+    /// made.
+    lagged: Arc<Mutex<Option<StoreEvent>>>,
+}
+
+impl Subscriber {
+    fn matches(&self, event: &StoreEvent) -> bool {
+        event.changes.iter().any(|change| {
+            self.entities.iter().any(|(subgraph_id, entity_type)| {
+                subgraph_id == &change.subgraph_id && entity_type == &change.entity_type
+            })
+        })
+    }
+}
+
+/// The upstream store subscription of a deployment and all subscribers that
+/// its events are fanned out to.
+struct Fanout {
+    id: usize,
+    subscribers: HashMap<usize, Subscriber>,
+
+    /// The upstream store subscription and the signal that cancels it; taken
+    /// when the task that forwards its events is spawned.
+    upstream: Option<(StoreEventStreamBox, oneshot::Receiver<()>)>,
+
+    /// Dropping this stops the forwarding task and with it the upstream
+    /// store subscription.
+    _cancel: oneshot::Sender<()>,
+}
+
+struct Inner {
+    logger: Logger,
+    buffer_size: usize,
+    metrics: SubscriptionMultiplexerMetrics,
+    fanouts: Mutex<HashMap<SubgraphDeploymentId, Fanout>>,
+    next_id: AtomicUsize,
+}
+
+impl Inner {
+    fn next_id(&self) -> usize {
+        self.next_id.fetch_add(1, Ordering::SeqCst)
+    }
+
+    /// Spawns the task that forwards upstream events for the given fanout,
+    /// unless that has happened already.
+    fn start(inner: &Arc<Inner>, deployment: &SubgraphDeploymentId, fanout_id: usize) {
+        let upstream = match inner.fanouts.lock().unwrap().get_mut(deployment) {
+            Some(fanout) if fanout.id == fanout_id => fanout.upstream.take(),
+            _ => None,
+        };
+
+        if let Some((upstream, cancel)) = upstream {
+            let inner = inner.clone();
+            let deployment = deployment.clone();
+
+            tokio::spawn(
+                upstream
+                    .for_each({
+                        let inner = inner.clone();
+                        let deployment = deployment.clone();
+                        move |event| {
+                            inner.dispatch(&deployment, fanout_id, event);
+                            Ok(())
+                        }
+                    })
+                    .select(cancel.then(|_| Ok(())))
+                    .then(move |_| -> Result<(), ()> {
+                        inner.close(&deployment, fanout_id);
+                        Ok(())
+                    }),
+            );
+        }
+    }
+
+    /// Sends an upstream event to all subscribers of a fanout. Subscribers
+    /// that can't keep up are marked as lagged instead of blocking the
+    /// fanout, and only resync once they have caught up; subscribers whose
+    /// stream was dropped are removed.
+    fn dispatch(&self, deployment: &SubgraphDeploymentId, fanout_id: usize, event: StoreEvent) {
+        let mut fanouts = self.fanouts.lock().unwrap();
+        let fanout = match fanouts.get_mut(deployment) {
+            Some(fanout) if fanout.id == fanout_id => fanout,
+            _ => return,
+        };
+
+        let mut disconnected = vec![];
+        for (id, subscriber) in fanout.subscribers.iter_mut() {
+            if !subscriber.matches(&event) {
+                continue;
+            }
+
+            // Sending while holding the lock makes sure that the subscriber
+            // does not hand out its lagged changes while newer ones are
+            // put into the channel
+            let mut lagged = subscriber.lagged.lock().unwrap();
+            if let Some(pending) = lagged.take() {
+                *lagged = Some(pending.extend(event.clone()));
+                self.metrics.inc_dropped_events(deployment);
+                continue;
+            }
+
+            match subscriber.sender.try_send(event.clone()) {
+                Ok(()) => (),
+                Err(ref e) if e.is_full() => {
+                    *lagged = Some(event.clone());
+                    self.metrics.inc_dropped_events(deployment);
+                }
+                Err(_) => disconnected.push(*id),
+            }
+        }
+
+        for id in disconnected {
+            debug!(self.logger, "Unsubscribe"; "deployment" => deployment.as_str(), "id" => id);
+            fanout.subscribers.remove(&id);
+        }
+        self.metrics
+            .set_subscriber_count(deployment, fanout.subscribers.len());
+    }
+
+    /// Removes a single subscriber; the upstream subscription is closed
+    /// along with the last one.
+    fn unsubscribe(&self, deployment: &SubgraphDeploymentId, fanout_id: usize, id: usize) {
+        let mut fanouts = self.fanouts.lock().unwrap();
+        let is_empty = match fanouts.get_mut(deployment) {
+            Some(fanout) if fanout.id == fanout_id => {
+                fanout.subscribers.remove(&id);
+                self.metrics
+                    .set_subscriber_count(deployment, fanout.subscribers.len());
+                fanout.subscribers.is_empty()
+            }
+            _ => return,
+        };
+
+        if is_empty {
+            debug!(self.logger, "Close store subscription"; "deployment" => deployment.as_str());
+            fanouts.remove(deployment);
+        }
+    }
+
+    /// Removes a fanout after its upstream subscription has ended, which
+    /// ends the streams of all its subscribers.
+    fn close(&self, deployment: &SubgraphDeploymentId, fanout_id: usize) {
+        let mut fanouts = self.fanouts.lock().unwrap();
+        if fanouts
+            .get(deployment)
+            .map_or(false, |fanout| fanout.id == fanout_id)
+        {
+            debug!(self.logger, "Store subscription ended"; "deployment" => deployment.as_str());
+            fanouts.remove(deployment);
+            self.metrics.set_subscriber_count(deployment, 0);
+        }
+    }
+}
+
+/// Shares a single store subscription per subgraph deployment between any
+/// number of GraphQL subscriptions.
+///
+/// Events from the upstream store subscription are fanned out to bounded
+/// per-subscriber channels. A subscriber that doesn't keep up has events
+/// dropped rather than holding up the others; the dropped changes, and any
+/// that are made in the meantime, are delivered as one combined event once
+/// the subscriber has caught up.
+#[derive(Clone)]
+pub struct SubscriptionMultiplexer {
+    inner: Arc<Inner>,
+}
+
+impl SubscriptionMultiplexer {
+    /// Creates a new multiplexer whose subscribers can each have up to
+    /// `buffer_size` undelivered events.
+    pub fn new<M: MetricsRegistry>(logger: &Logger, registry: Arc<M>, buffer_size: usize) -> Self {
+        SubscriptionMultiplexer {
+            inner: Arc::new(Inner {
+                logger: logger.new(o!("component" => "SubscriptionMultiplexer")),
+                buffer_size,
+                metrics: SubscriptionMultiplexerMetrics::new(registry),
+                fanouts: Mutex::new(HashMap::new()),
+                next_id: AtomicUsize::new(0),
+            }),
+        }
+    }
+
+    /// Subscribes to changes of `entities` in `deployment`. If there is no
+    /// upstream subscription for the deployment yet, one is created by
+    /// calling `upstream`; it must deliver all changes to the deployment
+    /// that any subscriber may be interested in.
+    pub fn subscribe<F>(
+        &self,
+        deployment: SubgraphDeploymentId,
+        entities: Vec<SubgraphEntityPair>,
+        upstream: F,
+    ) -> StoreEventStreamBox
+    where
+        F: FnOnce() -> StoreEventStreamBox,
+    {
+        let (sender, receiver) = mpsc::channel(self.inner.buffer_size);
+        let lagged = Arc::new(Mutex::new(None));
+        let id = self.inner.next_id();
+
+        let mut fanouts = self.inner.fanouts.lock().unwrap();
+        let fanout = fanouts.entry(deployment.clone()).or_insert_with(|| {
+            debug!(self.inner.logger, "Open store subscription"; "deployment" => deployment.as_str());
+            let (cancel_sender, cancel_receiver) = oneshot::channel();
+            Fanout {
+                id: self.inner.next_id(),
+                subscribers: HashMap::new(),
+                upstream: Some((upstream(), cancel_receiver)),
+                _cancel: cancel_sender,
+            }
+        });
+        fanout.subscribers.insert(
+            id,
+            Subscriber {
+                entities,
+                sender,
+                lagged: lagged.clone(),
+            },
+        );
+        self.inner
+            .metrics
+            .set_subscriber_count(&deployment, fanout.subscribers.len());
+        let fanout_id = fanout.id;
+        drop(fanouts);
+
+        StoreEventStream::new(Box::new(MultiplexedStream {
+            inner: self.inner.clone(),
+            deployment,
+            fanout_id,
+            id,
+            started: false,
+            receiver,
+            lagged,
+        }))
+    }
+}
+
+/// The stream of events of a single subscriber. Dropping it deregisters the
+/// subscriber.
+struct MultiplexedStream {
+    inner: Arc<Inner>,
+    deployment: SubgraphDeploymentId,
+    fanout_id: usize,
+    id: usize,
+    started: bool,
+    receiver: mpsc::Receiver<StoreEvent>,
+    lagged: Arc<Mutex<Option<StoreEvent>>>,
+}
+
+impl Stream for MultiplexedStream {
+    type Item = StoreEvent;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Option<StoreEvent>, ()> {
+        // Forwarding upstream events is only started once a subscriber is
+        // polled, so that subscribing does not require a running executor
+        if !self.started {
+            self.started = true;
+            Inner::start(&self.inner, &self.deployment, self.fanout_id);
+        }
+
+        // While the lock is held, no events can be added to the channel,
+        // which therefore only holds events older than the lagged ones
+        let mut lagged = self.lagged.lock().unwrap();
+        match self.receiver.poll()? {
+            Async::Ready(Some(event)) => Ok(Async::Ready(Some(event))),
+
+            // Once the channel is drained, hand out whatever was dropped
+            // while the subscriber was lagging behind
+            Async::Ready(None) => Ok(Async::Ready(lagged.take())),
+            Async::NotReady => match lagged.take() {
+                Some(event) => Ok(Async::Ready(Some(event))),
+                None => Ok(Async::NotReady),
+            },
+        }
+    }
+}
+
+impl Drop for MultiplexedStream {
+    fn drop(&mut self) {
+        self.inner
+            .unsubscribe(&self.deployment, self.fanout_id, self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph::prelude::tokio::runtime::current_thread::Runtime;
+    use graph_mock::MockMetricsRegistry;
+    use std::collections::HashSet;
+
+    fn deployment() -> SubgraphDeploymentId {
+        SubgraphDeploymentId::new("testsubgraph").unwrap()
+    }
+
+    fn entities() -> Vec<SubgraphEntityPair> {
+        vec![(deployment(), String::from("Musician"))]
+    }
+
+    fn change(entity_id: &str) -> EntityChange {
+        EntityChange {
+            subgraph_id: deployment(),
+            entity_type: String::from("Musician"),
+            entity_id: String::from(entity_id),
+            operation: EntityChangeOperation::Set,
+        }
+    }
+
+    fn multiplexer(buffer_size: usize) -> SubscriptionMultiplexer {
+        let logger = Logger::root(slog::Discard, o!());
+        SubscriptionMultiplexer::new(&logger, Arc::new(MockMetricsRegistry::new()), buffer_size)
+    }
+
+    fn subscriber_count(multiplexer: &SubscriptionMultiplexer) -> f64 {
+        multiplexer
+            .inner
+            .metrics
+            .subscriber_count
+            .with_label_values(&[deployment().as_str()])
+            .get()
+    }
+
+    fn dropped_events(multiplexer: &SubscriptionMultiplexer) -> f64 {
+        multiplexer
+            .inner
+            .metrics
+            .dropped_events
+            .with_label_values(&[deployment().as_str()])
+            .get()
+    }
+
+    #[test]
+    fn shares_upstream_subscription_between_subscribers() {
+        let multiplexer = multiplexer(10);
+        let (sender, receiver) = mpsc::channel(10);
+        let receiver = Mutex::new(Some(receiver));
+        let upstream_calls = AtomicUsize::new(0);
+
+        let streams: Vec<_> = (0..50)
+            .map(|_| {
+                multiplexer.subscribe(deployment(), entities(), || {
+                    upstream_calls.fetch_add(1, Ordering::SeqCst);
+                    StoreEventStream::new(Box::new(receiver.lock().unwrap().take().unwrap()))
+                })
+            })
+            .collect();
+
+        assert_eq!(upstream_calls.load(Ordering::SeqCst), 1);
+        assert_eq!(subscriber_count(&multiplexer), 50.0);
+
+        let event = StoreEvent::new(vec![change("m1")]);
+        sender.send(event.clone()).wait().unwrap();
+
+        let mut runtime = Runtime::new().unwrap();
+        let results = runtime
+            .block_on(future::join_all(
+                streams.into_iter().map(|stream| stream.take(1).collect()),
+            ))
+            .unwrap();
+
+        assert_eq!(results.len(), 50);
+        assert!(results
+            .iter()
+            .all(|events| events.len() == 1 && events[0].tag == event.tag));
+        assert_eq!(subscriber_count(&multiplexer), 0.0);
+        assert!(multiplexer.inner.fanouts.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn lagging_subscriber_does_not_lose_changes() {
+        let multiplexer = multiplexer(1);
+        let (sender, receiver) = mpsc::channel(100);
+        let stream = multiplexer.subscribe(deployment(), entities(), || {
+            StoreEventStream::new(Box::new(receiver))
+        });
+
+        let ids: Vec<_> = (0..10).map(|i| format!("m{}", i)).collect();
+        let sender = ids.iter().fold(sender, |sender, id| {
+            sender
+                .send(StoreEvent::new(vec![change(id)]))
+                .wait()
+                .unwrap()
+        });
+        // Ending the upstream subscription ends the subscriber's stream
+        // once it has received everything
+        drop(sender);
+
+        let mut runtime = Runtime::new().unwrap();
+        let events = runtime.block_on(stream.collect()).unwrap();
+
+        assert!(events.len() < ids.len());
+        assert!(dropped_events(&multiplexer) > 0.0);
+
+        let received: HashSet<_> = events
+            .into_iter()
+            .flat_map(|event| event.changes.into_iter())
+            .map(|change| change.entity_id)
+            .collect();
+        assert_eq!(received, ids.into_iter().collect::<HashSet<_>>());
+    }
+
+    #[test]
+    fn lagging_subscriber_receives_changes_in_order() {
+        let multiplexer = multiplexer(1);
+        let (_sender, receiver) = mpsc::channel(10);
+        let stream = multiplexer.subscribe(deployment(), entities(), || {
+            StoreEventStream::new(Box::new(receiver))
+        });
+
+        // Dispatch events directly rather than through the upstream
+        // subscription, so that the subscriber can catch up in between
+        let fanout_id = {
+            let mut fanouts = multiplexer.inner.fanouts.lock().unwrap();
+            let fanout = fanouts.get_mut(&deployment()).unwrap();
+            fanout.upstream.take();
+            fanout.id
+        };
+        let dispatch = |i: usize| {
+            multiplexer.inner.dispatch(
+                &deployment(),
+                fanout_id,
+                StoreEvent::new(vec![change(&i.to_string())]),
+            )
+        };
+
+        (0..5).for_each(dispatch);
+        let mut events = stream.wait();
+        let first = events.next().unwrap().unwrap();
+        (5..10).for_each(dispatch);
+        multiplexer.inner.close(&deployment(), fanout_id);
+
+        let mut received: Vec<usize> = vec![];
+        for event in std::iter::once(first).chain(events.map(Result::unwrap)) {
+            let mut ids: Vec<usize> = event
+                .changes
+                .into_iter()
+                .map(|change| change.entity_id.parse().unwrap())
+                .collect();
+            ids.sort();
+            assert!(received.last().map_or(true, |last| last < &ids[0]));
+            received.extend(ids);
+        }
+        assert_eq!(received, (0..10).collect::<Vec<_>>());
+        assert!(dropped_events(&multiplexer) > 0.0);
+    }
+}
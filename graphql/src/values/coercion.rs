@@ -1,8 +1,10 @@
 use crate::schema;
-use graph::prelude::QueryExecutionError;
+use graph::prelude::{BigDecimal, BigInt, QueryExecutionError};
 use graphql_parser::query as q;
 use graphql_parser::schema::{EnumType, InputValue, Name, ScalarType, Type, TypeDefinition, Value};
+use graphql_parser::Pos;
 use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
 
 /// A GraphQL value that can be coerced according to a type.
 pub trait MaybeCoercible<T> {
@@ -30,7 +32,7 @@ impl MaybeCoercible<ScalarType> for Value {
             ("Boolean", v @ Value::Boolean(_)) => Some(v.clone()),
             ("BigDecimal", Value::Float(f)) => Some(Value::String(f.to_string())),
             ("BigDecimal", Value::Int(i)) => Some(Value::String(i.as_i64()?.to_string())),
-            ("BigDecimal", v @ Value::String(_)) => Some(v.clone()),
+            ("BigDecimal", v @ Value::String(s)) => BigDecimal::from_str(s).ok().map(|_| v.clone()),
             ("Int", Value::Int(num)) => {
                 let num = num.as_i64()?;
                 if i32::min_value() as i64 <= num && num <= i32::max_value() as i64 {
@@ -43,7 +45,12 @@ impl MaybeCoercible<ScalarType> for Value {
             ("ID", v @ Value::String(_)) => Some(v.clone()),
             ("ID", Value::Int(num)) => Some(Value::String(num.as_i64()?.to_string())),
             ("Bytes", v @ Value::String(_)) => Some(v.clone()),
-            ("BigInt", v @ Value::String(_)) => Some(v.clone()),
+            // Numbers in variables that don't fit into an `Int` arrive as
+            // strings with their exact value. Floats are rejected since
+            // they may already have lost digits
+            ("BigInt", Value::String(s)) => BigInt::from_str(s)
+                .ok()
+                .map(|n| Value::String(n.to_string())),
             ("BigInt", Value::Int(num)) => Some(Value::String(num.as_i64()?.to_string())),
             _ => None,
         }
@@ -85,6 +92,20 @@ fn coerce_to_definition<'a>(
     }
 }
 
+/// The default value of an argument or input field, which is used when a
+/// query doesn't provide a value for it. Both coercing arguments and
+/// rendering them for introspection get defaults from here so that they
+/// always agree.
+pub(crate) fn default_value(def: &InputValue) -> Option<&Value> {
+    def.default_value.as_ref()
+}
+
+/// The default value of an argument or input field as the GraphQL literal
+/// that introspection reports as its `defaultValue`.
+pub(crate) fn default_value_literal(def: &InputValue) -> Option<String> {
+    default_value(def).map(|value| format!("{}", value))
+}
+
 /// Coerces an argument into a GraphQL value.
 ///
 /// `Ok(None)` happens when no value is found for a nullabe type.
@@ -99,7 +120,7 @@ pub(crate) fn coerce_input_value<'a>(
     };
 
     // Use the default value if necessary and present.
-    value = value.or(def.default_value.clone());
+    value = value.or_else(|| default_value(def).cloned());
 
     // Extract value, checking for null or missing.
     let value = match value {
@@ -118,15 +139,92 @@ pub(crate) fn coerce_input_value<'a>(
 
     Ok(Some(
         coerce_value(&value, &def.value_type, resolver, variable_values).ok_or_else(|| {
-            QueryExecutionError::InvalidArgumentError(
-                def.position.clone(),
-                def.name.to_owned(),
-                value.clone(),
+            invalid_enum_value(def.position, &value, &def.value_type, resolver).unwrap_or_else(
+                || {
+                    QueryExecutionError::InvalidArgumentError(
+                        def.position.clone(),
+                        def.name.to_owned(),
+                        value.clone(),
+                    )
+                },
             )
         })?,
     ))
 }
 
+/// Returns an `InvalidEnumValue` error if `value`, or one of its elements
+/// for list types or fields for input object types, names a value that the
+/// enum type `ty` doesn't declare. Enum values only match exactly, whether
+/// they are written into the query or passed as strings in variables.
+pub(crate) fn invalid_enum_value<'a>(
+    pos: Pos,
+    value: &Value,
+    ty: &Type,
+    resolver: &impl Fn(&Name) -> Option<&'a TypeDefinition>,
+) -> Option<QueryExecutionError> {
+    match (ty, value) {
+        (Type::NonNullType(t), _) => invalid_enum_value(pos, value, t, resolver),
+        (Type::ListType(t), Value::List(values)) => values
+            .iter()
+            .find_map(|value| invalid_enum_value(pos, value, t, resolver)),
+        (Type::NamedType(name), Value::String(v)) | (Type::NamedType(name), Value::Enum(v)) => {
+            match resolver(name)? {
+                TypeDefinition::Enum(t) if !t.values.iter().any(|value| &value.name == v) => {
+                    Some(QueryExecutionError::InvalidEnumValue {
+                        pos,
+                        enum_name: t.name.clone(),
+                        value: v.clone(),
+                        allowed: t.values.iter().map(|value| value.name.clone()).collect(),
+                        suggestion: closest_enum_value(t, v).map(str::to_owned),
+                    })
+                }
+                _ => None,
+            }
+        }
+        (Type::NamedType(name), Value::Object(fields)) => match resolver(name)? {
+            TypeDefinition::InputObject(t) => fields.iter().find_map(|(name, value)| {
+                let def = t.fields.iter().find(|f| &f.name == name)?;
+                invalid_enum_value(pos, value, &def.value_type, resolver)
+            }),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The value of `enum_type` that `value` most likely meant: one that only
+/// differs from it in case or, failing that, in at most two characters.
+fn closest_enum_value<'a>(enum_type: &'a EnumType, value: &str) -> Option<&'a str> {
+    let names = enum_type.values.iter().map(|value| value.name.as_str());
+    names
+        .clone()
+        .find(|name| name.eq_ignore_ascii_case(value))
+        .or_else(|| {
+            names
+                .map(|name| (edit_distance(name, value), name))
+                .filter(|(distance, _)| *distance <= 2)
+                .min_by_key(|(distance, _)| *distance)
+                .map(|(_, name)| name)
+        })
+}
+
+/// The number of characters that have to be inserted, removed or replaced
+/// to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let replaced = diagonal + if ca == *cb { 0 } else { 1 };
+            diagonal = row[j + 1];
+            row[j + 1] = replaced.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
 /// `R` is a name resolver.
 pub(crate) fn coerce_value<'a>(
     value: &Value,
@@ -172,12 +270,15 @@ pub(crate) fn coerce_value<'a>(
 
 #[cfg(test)]
 mod tests {
+    use graph::prelude::QueryExecutionError;
     use graphql_parser::query::Value;
-    use graphql_parser::schema::{EnumType, EnumValue, ScalarType, TypeDefinition};
+    use graphql_parser::schema::{
+        EnumType, EnumValue, InputObjectType, InputValue, ScalarType, Type, TypeDefinition,
+    };
     use graphql_parser::Pos;
-    use std::collections::HashMap;
+    use std::collections::{BTreeMap, HashMap};
 
-    use super::coerce_to_definition;
+    use super::{coerce_to_definition, invalid_enum_value};
 
     #[test]
     fn coercion_using_enum_type_definitions_is_correct() {
@@ -577,6 +678,23 @@ mod tests {
         );
     }
 
+    #[test]
+    fn coerce_big_int_scalar_from_large_numbers() {
+        let big_int_type = TypeDefinition::Scalar(ScalarType::new("BigInt".to_string()));
+        let resolver = |_: &String| Some(&big_int_type);
+        let coerce =
+            |value: Value| coerce_to_definition(&value, &String::new(), &resolver, &HashMap::new());
+
+        // Numbers beyond `Int` arrive as strings
+        assert_eq!(
+            coerce(Value::String("1208925819614629174706176".to_string())),
+            Some(Value::String("1208925819614629174706176".to_string()))
+        );
+        assert_eq!(coerce(Value::Float(1e30)), None);
+        assert_eq!(coerce(Value::Float(1.5)), None);
+        assert_eq!(coerce(Value::String("12abc".to_string())), None);
+    }
+
     #[test]
     fn coerce_bytes_scalar() {
         let bytes_type = TypeDefinition::Scalar(ScalarType::new("Bytes".to_string()));
@@ -618,4 +736,94 @@ mod tests {
             Some(Value::Int((-13289123 as i32).into()))
         );
     }
+
+    #[test]
+    fn invalid_enum_values_suggest_the_closest_value() {
+        let enum_value = |name: &str| EnumValue {
+            name: name.to_string(),
+            position: Pos::default(),
+            description: None,
+            directives: vec![],
+        };
+        let kind = TypeDefinition::Enum(EnumType {
+            name: "Kind".to_string(),
+            description: None,
+            directives: vec![],
+            position: Pos::default(),
+            values: vec![enum_value("Big"), enum_value("Small")],
+        });
+        let filter = TypeDefinition::InputObject(InputObjectType {
+            name: "Filter".to_string(),
+            description: None,
+            directives: vec![],
+            position: Pos::default(),
+            fields: vec![InputValue {
+                name: "kind_in".to_string(),
+                description: None,
+                value_type: Type::ListType(Box::new(Type::NamedType("Kind".to_string()))),
+                default_value: None,
+                directives: vec![],
+                position: Pos::default(),
+            }],
+        });
+        let resolver = |name: &String| match name.as_str() {
+            "Kind" => Some(&kind),
+            "Filter" => Some(&filter),
+            _ => None,
+        };
+        let suggestion = |value: Value, ty: &str| match invalid_enum_value(
+            Pos::default(),
+            &value,
+            &Type::NamedType(ty.to_string()),
+            &resolver,
+        ) {
+            Some(QueryExecutionError::InvalidEnumValue {
+                allowed,
+                suggestion,
+                ..
+            }) => {
+                assert_eq!(allowed, vec!["Big".to_string(), "Small".to_string()]);
+                Some(suggestion)
+            }
+            Some(e) => panic!("unexpected error: {:?}", e),
+            None => None,
+        };
+
+        // Valid values, whether enum literals or strings, are accepted
+        assert_eq!(suggestion(Value::Enum("Big".to_string()), "Kind"), None);
+        assert_eq!(suggestion(Value::String("Small".to_string()), "Kind"), None);
+
+        // Values that only differ in case, or in a few characters, are
+        // rejected with a suggestion
+        assert_eq!(
+            suggestion(Value::Enum("big".to_string()), "Kind"),
+            Some(Some("Big".to_string()))
+        );
+        assert_eq!(
+            suggestion(Value::String("SMALL".to_string()), "Kind"),
+            Some(Some("Small".to_string()))
+        );
+        assert_eq!(
+            suggestion(Value::Enum("Smal".to_string()), "Kind"),
+            Some(Some("Small".to_string()))
+        );
+        assert_eq!(
+            suggestion(Value::Enum("Medium".to_string()), "Kind"),
+            Some(None)
+        );
+
+        // Enum values inside of input objects are checked as well
+        let mut fields = BTreeMap::new();
+        fields.insert(
+            "kind_in".to_string(),
+            Value::List(vec![
+                Value::Enum("Big".to_string()),
+                Value::Enum("smal".to_string()),
+            ]),
+        );
+        assert_eq!(
+            suggestion(Value::Object(fields), "Filter"),
+            Some(Some("Small".to_string()))
+        );
+    }
 }
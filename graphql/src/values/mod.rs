@@ -5,7 +5,11 @@ use std::iter::FromIterator;
 /// Utilties for coercing GraphQL values based on GraphQL types.
 pub mod coercion;
 
+/// Canonical serialization of the values of built-in scalar types.
+pub mod scalars;
+
 pub use self::coercion::MaybeCoercible;
+pub use self::scalars::{scalar_serializer, serialize_scalar_value, ScalarSerializer};
 
 /// Creates a `graphql_parser::query::Value::Object` from key/value pairs.
 pub fn object_value(data: Vec<(&str, Value)>) -> Value {
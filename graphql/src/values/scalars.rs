@@ -0,0 +1,107 @@
+use graph::data::store::scalar::Bytes;
+use graph::prelude::{BigDecimal, BigInt};
+use graphql_parser::query::Value;
+use graphql_parser::schema::ScalarType;
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Converts a value of a scalar type into its canonical form, or returns
+/// `None` if the value is not a value of the type.
+pub type ScalarSerializer = fn(&Value) -> Option<Value>;
+
+lazy_static! {
+    /// The serializers of the built-in scalar types, by type name.
+    static ref SCALAR_SERIALIZERS: HashMap<&'static str, ScalarSerializer> = {
+        let mut serializers: HashMap<&'static str, ScalarSerializer> = HashMap::new();
+        serializers.insert("ID", serialize_id);
+        serializers.insert("String", serialize_string);
+        serializers.insert("Boolean", serialize_boolean);
+        serializers.insert("Int", serialize_int);
+        serializers.insert("BigInt", serialize_big_int);
+        serializers.insert("BigDecimal", serialize_big_decimal);
+        serializers.insert("Bytes", serialize_bytes);
+        serializers
+    };
+}
+
+/// Returns the serializer for the scalar type `name`, or `None` if it is
+/// not a built-in scalar type.
+pub fn scalar_serializer(name: &str) -> Option<ScalarSerializer> {
+    SCALAR_SERIALIZERS.get(name).cloned()
+}
+
+/// Serializes `value` as a value of `scalar_type`. Values of scalar types
+/// that aren't built in, and values the serializer doesn't accept, are
+/// returned unchanged.
+pub fn serialize_scalar_value(scalar_type: &ScalarType, value: &Value) -> Value {
+    match value {
+        Value::Null => Value::Null,
+        _ => scalar_serializer(&scalar_type.name)
+            .and_then(|serialize| serialize(value))
+            .unwrap_or_else(|| value.clone()),
+    }
+}
+
+fn serialize_id(value: &Value) -> Option<Value> {
+    match value {
+        Value::String(_) => Some(value.clone()),
+        Value::Int(n) => Some(Value::String(n.as_i64()?.to_string())),
+        _ => None,
+    }
+}
+
+fn serialize_string(value: &Value) -> Option<Value> {
+    match value {
+        Value::String(_) => Some(value.clone()),
+        _ => None,
+    }
+}
+
+fn serialize_boolean(value: &Value) -> Option<Value> {
+    match value {
+        Value::Boolean(_) => Some(value.clone()),
+        _ => None,
+    }
+}
+
+fn serialize_int(value: &Value) -> Option<Value> {
+    match value {
+        Value::Int(_) => Some(value.clone()),
+        Value::String(s) => i32::from_str(s).ok().map(|n| Value::Int(n.into())),
+        _ => None,
+    }
+}
+
+/// `BigInt`s are serialized as decimal strings.
+fn serialize_big_int(value: &Value) -> Option<Value> {
+    match value {
+        Value::String(s) => BigInt::from_str(s)
+            .ok()
+            .map(|n| Value::String(n.to_string())),
+        Value::Int(n) => Some(Value::String(n.as_i64()?.to_string())),
+        _ => None,
+    }
+}
+
+/// `BigDecimal`s are serialized as decimal strings.
+fn serialize_big_decimal(value: &Value) -> Option<Value> {
+    match value {
+        Value::String(s) => BigDecimal::from_str(s)
+            .ok()
+            .map(|n| Value::String(n.to_string())),
+        Value::Int(n) => Some(Value::String(n.as_i64()?.to_string())),
+        Value::Float(f) => Some(Value::String(f.to_string())),
+        _ => None,
+    }
+}
+
+/// `Bytes` are serialized as lowercase hex strings prefixed by `0x`.
+fn serialize_bytes(value: &Value) -> Option<Value> {
+    match value {
+        Value::String(s) => Bytes::from_str(s)
+            .ok()
+            .map(|bytes| Value::String(bytes.to_string())),
+        _ => None,
+    }
+}
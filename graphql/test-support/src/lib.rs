@@ -0,0 +1,214 @@
+//! Resolvers, schemas and helpers shared by tests of GraphQL execution.
+
+pub use graphql_parser;
+pub use pretty_assertions;
+
+use graphql_parser::query as q;
+use std::fmt::Write;
+
+use graph::prelude::*;
+use graph_graphql::prelude::*;
+
+/// A resolver with scripted values.
+mod resolver;
+
+/// Schemas to execute queries against.
+pub mod schemas;
+
+pub use self::resolver::ScriptedResolver;
+
+/// Options for executing queries with `resolver` without any limits.
+pub fn default_options<R>(resolver: R) -> QueryExecutionOptions<R>
+where
+    R: Resolver,
+{
+    QueryExecutionOptions {
+        context: QueryContext::new(Logger::root(slog::Discard, o!()), None, std::u32::MAX),
+        resolver,
+        max_complexity: None,
+        max_depth: 100,
+        max_response_size: None,
+        max_fields_per_selection: None,
+        authorizer: Arc::new(AllowAll),
+    }
+}
+
+/// Parses `query` and executes it against `schema`.
+pub fn execute<R>(schema: Schema, query: &str, options: QueryExecutionOptions<R>) -> QueryResult
+where
+    R: Resolver,
+{
+    let query = Query {
+        schema: Arc::new(schema),
+        document: graphql_parser::parse_query(query).unwrap(),
+        variables: None,
+        dry_run: false,
+        sync_warning: false,
+        query_id: None,
+    };
+    execute_query(&query, options)
+}
+
+/// Conversion into GraphQL values for the `object!` and `list!` macros.
+pub trait IntoValue {
+    fn into_value(self) -> q::Value;
+}
+
+impl IntoValue for q::Value {
+    fn into_value(self) -> q::Value {
+        self
+    }
+}
+
+impl IntoValue for &str {
+    fn into_value(self) -> q::Value {
+        q::Value::String(self.to_owned())
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self) -> q::Value {
+        q::Value::String(self)
+    }
+}
+
+impl IntoValue for i32 {
+    fn into_value(self) -> q::Value {
+        q::Value::Int(self.into())
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value(self) -> q::Value {
+        q::Value::Boolean(self)
+    }
+}
+
+impl<T: IntoValue> IntoValue for Option<T> {
+    fn into_value(self) -> q::Value {
+        self.map_or(q::Value::Null, IntoValue::into_value)
+    }
+}
+
+/// Builds a GraphQL object value, e.g.
+/// `object! { "name" => "John", "age" => 42, "band" => None::<&str> }`.
+#[macro_export]
+macro_rules! object {
+    ($($name:expr => $value:expr),* $(,)?) => {
+        $crate::graphql_parser::query::Value::Object(
+            vec![$(($name.to_string(), $crate::IntoValue::into_value($value))),*]
+                .into_iter()
+                .collect::<::std::collections::BTreeMap<_, _>>(),
+        )
+    };
+}
+
+/// Builds a GraphQL list value, e.g. `list!["a", "b"]`.
+#[macro_export]
+macro_rules! list {
+    ($($value:expr),* $(,)?) => {
+        $crate::graphql_parser::query::Value::List(
+            vec![$($crate::IntoValue::into_value($value)),*]
+        )
+    };
+}
+
+/// Asserts that two GraphQL values are equal, showing a line-by-line diff
+/// of their canonical forms if they aren't.
+#[macro_export]
+macro_rules! assert_values_eq {
+    ($left:expr, $right:expr) => {
+        $crate::pretty_assertions::assert_eq!(
+            $crate::canonical(&$left),
+            $crate::canonical(&$right)
+        )
+    };
+}
+
+/// Formats `value` with one object field or list element per line. Object
+/// fields are sorted by name, so equal values have the same canonical form.
+pub fn canonical(value: &q::Value) -> String {
+    let mut out = String::new();
+    write_canonical(&mut out, value, 0);
+    out
+}
+
+fn write_canonical(out: &mut String, value: &q::Value, indent: usize) {
+    let pad = |depth: usize| "  ".repeat(depth);
+    match value {
+        q::Value::Object(object) if object.is_empty() => out.push_str("{}"),
+        q::Value::Object(object) => {
+            out.push_str("{\n");
+            for (name, value) in object {
+                write!(out, "{}{}: ", pad(indent + 1), name).unwrap();
+                write_canonical(out, value, indent + 1);
+                out.push('\n');
+            }
+            write!(out, "{}}}", pad(indent)).unwrap();
+        }
+        q::Value::List(values) if values.is_empty() => out.push_str("[]"),
+        q::Value::List(values) => {
+            out.push_str("[\n");
+            for value in values {
+                out.push_str(&pad(indent + 1));
+                write_canonical(out, value, indent + 1);
+                out.push('\n');
+            }
+            write!(out, "{}]", pad(indent)).unwrap();
+        }
+        q::Value::String(s) => write!(out, "{:?}", s).unwrap(),
+        q::Value::Int(n) => write!(out, "{}", n.as_i64().unwrap_or_default()).unwrap(),
+        q::Value::Float(f) => write!(out, "{:?}", f).unwrap(),
+        q::Value::Boolean(b) => write!(out, "{}", b).unwrap(),
+        q::Value::Null => out.push_str("null"),
+        q::Value::Enum(e) => out.push_str(e),
+        q::Value::Variable(v) => write!(out, "${}", v).unwrap(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn canonical_values_show_one_field_per_line() {
+        let value = object! {
+            "name" => "John",
+            "bands" => list!["b1", "b2"],
+            "age" => 42,
+            "active" => true,
+            "mainBand" => None::<&str>,
+            "songs" => list![],
+        };
+
+        assert_eq!(
+            canonical(&value),
+            "{\n  active: true\n  age: 42\n  bands: [\n    \"b1\"\n    \"b2\"\n  ]\n  \
+             mainBand: null\n  name: \"John\"\n  songs: []\n}"
+        );
+        assert_values_eq!(value.clone(), value);
+    }
+
+    #[test]
+    fn scripted_values_are_looked_up_by_type_and_field() {
+        let resolver = ScriptedResolver::new()
+            .with_value(
+                "Query",
+                "allUsers",
+                list![object! { "__typename" => "User" }],
+            )
+            .with_value("User", "name", q::Value::String("John".to_owned()));
+        let user = Some(object! { "__typename" => "User" });
+
+        assert_eq!(
+            resolver.value(&None, "allUsers"),
+            list![object! { "__typename" => "User" }]
+        );
+        assert_eq!(
+            resolver.value(&user, "name"),
+            q::Value::String("John".to_owned())
+        );
+        assert_eq!(resolver.value(&user, "allUsers"), q::Value::Null);
+        assert_eq!(resolver.value(&Some(object! {}), "name"), q::Value::Null);
+    }
+}
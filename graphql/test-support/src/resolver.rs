@@ -0,0 +1,79 @@
+use graphql_parser::{query as q, schema as s};
+use std::collections::{BTreeMap, HashMap};
+
+use graph::prelude::*;
+use graph_graphql::prelude::*;
+
+/// A resolver that returns scripted values for object and list fields.
+///
+/// Values are scripted per field of a type: the fields of the root query
+/// type are looked up under `Query`, all other fields under the
+/// `__typename` of their parent object. Fields without a scripted value
+/// resolve to `null`. Scalar and enum fields are read from their parent
+/// object, so they are scripted as part of the parent.
+#[derive(Clone, Debug, Default)]
+pub struct ScriptedResolver {
+    values: HashMap<(String, String), q::Value>,
+}
+
+impl ScriptedResolver {
+    /// A resolver that resolves every field to `null`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resolves the field `field_name` of objects of type `type_name` to
+    /// `value`.
+    pub fn with_value(mut self, type_name: &str, field_name: &str, value: q::Value) -> Self {
+        self.values
+            .insert((type_name.to_owned(), field_name.to_owned()), value);
+        self
+    }
+
+    pub(crate) fn value(&self, parent: &Option<q::Value>, field_name: &str) -> q::Value {
+        let type_name = match parent {
+            None => "Query",
+            Some(q::Value::Object(object)) => match object.get("__typename") {
+                Some(q::Value::String(type_name)) => type_name.as_str(),
+                _ => return q::Value::Null,
+            },
+            Some(_) => return q::Value::Null,
+        };
+        self.values
+            .get(&(type_name.to_owned(), field_name.to_owned()))
+            .cloned()
+            .unwrap_or(q::Value::Null)
+    }
+}
+
+impl Resolver for ScriptedResolver {
+    fn serializes_scalars(&self) -> bool {
+        true
+    }
+
+    fn resolve_objects(
+        &self,
+        _ctx: &QueryContext,
+        parent: &Option<q::Value>,
+        field: &q::Name,
+        _field_definition: &s::Field,
+        _object_type: ObjectOrInterface<'_>,
+        _arguments: &HashMap<&q::Name, q::Value>,
+        _types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        Ok(self.value(parent, field))
+    }
+
+    fn resolve_object(
+        &self,
+        _ctx: &QueryContext,
+        parent: &Option<q::Value>,
+        field: &q::Field,
+        _field_definition: &s::Field,
+        _object_type: ObjectOrInterface<'_>,
+        _arguments: &HashMap<&q::Name, q::Value>,
+        _types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        Ok(self.value(parent, &field.name))
+    }
+}
@@ -0,0 +1,267 @@
+use graph::prelude::*;
+use graph_graphql::prelude::*;
+
+/// Creates a basic GraphQL schema that exercies scalars, directives,
+/// enums, interfaces, input objects, object types and field arguments.
+pub fn mock_schema() -> Schema {
+    Schema::parse(
+        "
+             scalar ID
+             scalar Int
+             scalar String
+             scalar Boolean
+
+             directive @language(
+               language: String = \"English\"
+             ) on FIELD_DEFINITION
+
+             enum Role {
+               USER
+               ADMIN
+             }
+
+             interface Node {
+               id: ID!
+             }
+
+             type User implements Node @entity {
+               id: ID!
+               name: String! @language(language: \"English\")
+               role: Role!
+             }
+
+             enum User_orderBy {
+               id
+               name
+             }
+
+             input User_filter {
+               name_eq: String = \"default name\",
+               name_not: String,
+             }
+
+             type Query @entity {
+               allUsers(orderBy: User_orderBy, filter: User_filter): [User!]
+               anyUserWithAge(age: Int = 99): User
+               User: User
+             }
+             ",
+        SubgraphDeploymentId::new("mockschema").unwrap(),
+    )
+    .unwrap()
+}
+
+/// The schema of a registry of memes, with interfaces, enums and many
+/// relationships between entities.
+pub const COMPLEX_SCHEMA: &str = "
+enum RegEntryStatus {
+  regEntry_status_challengePeriod
+  regEntry_status_commitPeriod
+  regEntry_status_revealPeriod
+  regEntry_status_blacklisted
+  regEntry_status_whitelisted
+}
+
+interface RegEntry {
+  regEntry_address: ID
+  regEntry_version: Int
+  regEntry_status: RegEntryStatus
+  regEntry_creator: User
+  regEntry_deposit: Int
+  regEntry_createdOn: String
+  regEntry_challengePeriodEnd: String
+  challenge_challenger: User
+  challenge_createdOn: String
+  challenge_comment: String
+  challenge_votingToken: String
+  challenge_rewardPool: Int
+  challenge_commitPeriodEnd: String
+  challenge_revealPeriodEnd: String
+  challenge_votesFor: Int
+  challenge_votesAgainst: Int
+  challenge_votesTotal: Int
+  challenge_claimedRewardOn: String
+  challenge_vote(vote_voter: ID!): Vote
+}
+
+enum VoteOption {
+  voteOption_noVote
+  voteOption_voteFor
+  voteOption_voteAgainst
+}
+
+type Vote @entity {
+  id: ID!
+  vote_secretHash: String
+  vote_option: VoteOption
+  vote_amount: Int
+  vote_revealedOn: String
+  vote_claimedRewardOn: String
+  vote_reward: Int
+}
+
+type Meme implements RegEntry @entity {
+  id: ID!
+  regEntry_address: ID
+  regEntry_version: Int
+  regEntry_status: RegEntryStatus
+  regEntry_creator: User
+  regEntry_deposit: Int
+  regEntry_createdOn: String
+  regEntry_challengePeriodEnd: String
+  challenge_challenger: User
+  challenge_createdOn: String
+  challenge_comment: String
+  challenge_votingToken: String
+  challenge_rewardPool: Int
+  challenge_commitPeriodEnd: String
+  challenge_revealPeriodEnd: String
+  challenge_votesFor: Int
+  challenge_votesAgainst: Int
+  challenge_votesTotal: Int
+  challenge_claimedRewardOn: String
+  challenge_vote(vote_voter: ID!): Vote
+  # Balance of voting token of a voter. This is client-side only, server doesn't return this
+  challenge_availableVoteAmount(voter: ID!): Int
+  meme_title: String
+  meme_number: Int
+  meme_metaHash: String
+  meme_imageHash: String
+  meme_totalSupply: Int
+  meme_totalMinted: Int
+  meme_tokenIdStart: Int
+  meme_totalTradeVolume: Int
+  meme_totalTradeVolumeRank: Int
+  meme_ownedMemeTokens(owner: String): [MemeToken]
+  meme_tags: [Tag]
+}
+
+type Tag  @entity {
+  id: ID!
+  tag_id: ID
+  tag_name: String
+}
+
+type MemeToken @entity {
+  id: ID!
+  memeToken_tokenId: ID
+  memeToken_number: Int
+  memeToken_owner: User
+  memeToken_meme: Meme
+}
+
+enum MemeAuctionStatus {
+  memeAuction_status_active
+  memeAuction_status_canceled
+  memeAuction_status_done
+}
+
+type MemeAuction @entity {
+  id: ID!
+  memeAuction_address: ID
+  memeAuction_seller: User
+  memeAuction_buyer: User
+  memeAuction_startPrice: Int
+  memeAuction_endPrice: Int
+  memeAuction_duration: Int
+  memeAuction_startedOn: String
+  memeAuction_boughtOn: String
+  memeAuction_status: MemeAuctionStatus
+  memeAuction_memeToken: MemeToken
+}
+
+type ParamChange implements RegEntry @entity {
+  id: ID!
+  regEntry_address: ID
+  regEntry_version: Int
+  regEntry_status: RegEntryStatus
+  regEntry_creator: User
+  regEntry_deposit: Int
+  regEntry_createdOn: String
+  regEntry_challengePeriodEnd: String
+  challenge_challenger: User
+  challenge_createdOn: String
+  challenge_comment: String
+  challenge_votingToken: String
+  challenge_rewardPool: Int
+  challenge_commitPeriodEnd: String
+  challenge_revealPeriodEnd: String
+  challenge_votesFor: Int
+  challenge_votesAgainst: Int
+  challenge_votesTotal: Int
+  challenge_claimedRewardOn: String
+  challenge_vote(vote_voter: ID!): Vote
+  # Balance of voting token of a voter. This is client-side only, server doesn't return this
+  challenge_availableVoteAmount(voter: ID!): Int
+  paramChange_db: String
+  paramChange_key: String
+  paramChange_value: Int
+  paramChange_originalValue: Int
+  paramChange_appliedOn: String
+}
+
+type User @entity {
+  id: ID!
+  # Ethereum address of an user
+  user_address: ID
+  # Total number of memes submitted by user
+  user_totalCreatedMemes: Int
+  # Total number of memes submitted by user, which successfully got into TCR
+  user_totalCreatedMemesWhitelisted: Int
+  # Largest sale creator has done with his newly minted meme
+  user_creatorLargestSale: MemeAuction
+  # Position of a creator in leaderboard according to user_totalCreatedMemesWhitelisted
+  user_creatorRank: Int
+  # Amount of meme tokenIds owned by user
+  user_totalCollectedTokenIds: Int
+  # Amount of unique memes owned by user
+  user_totalCollectedMemes: Int
+  # Largest auction user sold, in terms of price
+  user_largestSale: MemeAuction
+  # Largest auction user bought into, in terms of price
+  user_largestBuy: MemeAuction
+  # Amount of challenges user created
+  user_totalCreatedChallenges: Int
+  # Amount of challenges user created and ended up in his favor
+  user_totalCreatedChallengesSuccess: Int
+  # Total amount of DANK token user received from challenger rewards
+  user_challengerTotalEarned: Int
+  # Total amount of DANK token user received from challenger rewards
+  user_challengerRank: Int
+  # Amount of different votes user participated in
+  user_totalParticipatedVotes: Int
+  # Amount of different votes user voted for winning option
+  user_totalParticipatedVotesSuccess: Int
+  # Amount of DANK token user received for voting for winning option
+  user_voterTotalEarned: Int
+  # Position of voter in leaderboard according to user_voterTotalEarned
+  user_voterRank: Int
+  # Sum of user_challengerTotalEarned and user_voterTotalEarned
+  user_curatorTotalEarned: Int
+  # Position of curator in leaderboard according to user_curatorTotalEarned
+  user_curatorRank: Int
+}
+
+type Parameter @entity {
+  id: ID!
+  param_db: ID
+  param_key: ID
+  param_value: Int
+}
+";
+
+/// Parses `raw_schema` and derives its API schema.
+pub fn api_schema_from(raw_schema: &str, id: &str) -> Schema {
+    let schema = Schema::parse(raw_schema, SubgraphDeploymentId::new(id).unwrap()).unwrap();
+    Schema::api(
+        &Logger::root(slog::Discard, o!()),
+        schema,
+        Pluralization::Legacy,
+    )
+    .unwrap()
+}
+
+/// The API schema of `raw_schema`, usually `COMPLEX_SCHEMA` with additions.
+pub fn complex_api_schema(raw_schema: &str) -> Schema {
+    api_schema_from(raw_schema, "complexschema")
+}
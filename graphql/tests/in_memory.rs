@@ -0,0 +1,236 @@
+use graphql_parser::query as q;
+use std::collections::HashMap;
+
+use graph_graphql::prelude::*;
+use graph_graphql_test_support::schemas::{api_schema_from, complex_api_schema, COMPLEX_SCHEMA};
+use graph_graphql_test_support::{assert_values_eq, default_options, execute, list, object};
+
+const SCHEMA: &str = "
+interface Thing {
+  id: ID!
+  name: String!
+}
+
+type User implements Thing @entity {
+  id: ID!
+  name: String!
+  age: Int!
+  karma: BigInt!
+  memes: [Meme!]!
+}
+
+type Meme implements Thing @entity {
+  id: ID!
+  name: String!
+  owner: User!
+}
+";
+
+/// A resolver over three users and the memes they own.
+fn resolver() -> InMemoryResolver {
+    let mut entities = HashMap::new();
+    entities.insert(
+        "User".to_owned(),
+        vec![
+            object! { "id" => "u1", "name" => "Alice", "age" => 30, "karma" => "100", "memes" => list!["m1", "m3"] },
+            object! { "id" => "u2", "name" => "Bob", "age" => 25, "karma" => "9", "memes" => list![] },
+            object! { "id" => "u3", "name" => "Carol", "age" => 40, "karma" => "20", "memes" => list!["m2"] },
+        ],
+    );
+    entities.insert(
+        "Meme".to_owned(),
+        vec![
+            object! { "id" => "m1", "name" => "Cat", "owner" => "u1" },
+            object! { "id" => "m2", "name" => "Doge", "owner" => "u3" },
+            object! { "id" => "m3", "name" => "Carl", "owner" => "u1" },
+        ],
+    );
+    InMemoryResolver::new(entities)
+}
+
+fn query_data(query: &str) -> q::Value {
+    let schema = api_schema_from(SCHEMA, "inmemory");
+    let result = execute(schema, query, default_options(resolver()));
+    assert!(result.errors.is_none(), format!("{:#?}", result.errors));
+    result.data.expect("Query returned no data")
+}
+
+#[test]
+fn filters_and_orders_objects() {
+    let data = query_data(
+        "{ users(where: { age_gt: 26 }, orderBy: age, orderDirection: desc) { id name } }",
+    );
+
+    assert_values_eq!(
+        data,
+        object! {
+            "users" => list![
+                object! { "id" => "u3", "name" => "Carol" },
+                object! { "id" => "u1", "name" => "Alice" },
+            ],
+        }
+    );
+}
+
+#[test]
+fn compares_big_numbers_by_their_value() {
+    let data = query_data("{ users(where: { karma_gt: \"10\" }, orderBy: karma) { id } }");
+
+    assert_values_eq!(
+        data,
+        object! { "users" => list![object! { "id" => "u3" }, object! { "id" => "u1" }] }
+    );
+}
+
+#[test]
+fn orders_objects_by_id_and_paginates() {
+    let data = query_data("{ users(first: 1, skip: 1) { id } }");
+
+    assert_values_eq!(data, object! { "users" => list![object! { "id" => "u2" }] });
+}
+
+#[test]
+fn resolves_objects_by_id_and_relationships() {
+    let data = query_data(
+        "{
+           user(id: \"u1\") {
+             name
+             memes(orderBy: name) { name owner { id } }
+           }
+           meme(id: \"m9\") { id }
+         }",
+    );
+
+    assert_values_eq!(
+        data,
+        object! {
+            "user" => object! {
+                "name" => "Alice",
+                "memes" => list![
+                    object! { "name" => "Carl", "owner" => object! { "id" => "u1" } },
+                    object! { "name" => "Cat", "owner" => object! { "id" => "u1" } },
+                ],
+            },
+            "meme" => q::Value::Null,
+        }
+    );
+}
+
+#[test]
+fn resolves_interfaces_to_their_implementations() {
+    let data = query_data(
+        "{
+           things(where: { name_starts_with: \"C\" }, orderBy: name) {
+             __typename
+             name
+             ... on User { age }
+           }
+         }",
+    );
+
+    assert_values_eq!(
+        data,
+        object! {
+            "things" => list![
+                object! { "__typename" => "Meme", "name" => "Carl" },
+                object! { "__typename" => "User", "name" => "Carol", "age" => 40 },
+                object! { "__typename" => "Meme", "name" => "Cat" },
+            ],
+        }
+    );
+}
+
+#[test]
+fn resolves_interfaces_implemented_by_interfaces() {
+    let schema = api_schema_from(
+        "
+        interface Node {
+          id: ID!
+        }
+
+        interface Named implements Node {
+          name: String!
+        }
+
+        type User implements Named @entity {
+          id: ID!
+          name: String!
+        }
+
+        type Pet implements Node @entity {
+          id: ID!
+        }
+        ",
+        "inmemoryhierarchy",
+    );
+    let mut entities = HashMap::new();
+    entities.insert(
+        "User".to_owned(),
+        vec![object! { "id" => "u1", "name" => "Alice" }],
+    );
+    entities.insert("Pet".to_owned(), vec![object! { "id" => "p1" }]);
+
+    let result = execute(
+        schema,
+        "{
+           nodes { __typename id ... on Named { name } }
+           named(id: \"u1\") { id name }
+         }",
+        default_options(InMemoryResolver::new(entities)),
+    );
+
+    assert!(result.errors.is_none(), format!("{:#?}", result.errors));
+    assert_values_eq!(
+        result.data.unwrap(),
+        object! {
+            "nodes" => list![
+                object! { "__typename" => "Pet", "id" => "p1" },
+                object! { "__typename" => "User", "id" => "u1", "name" => "Alice" },
+            ],
+            "named" => object! { "id" => "u1", "name" => "Alice" },
+        }
+    );
+}
+
+#[test]
+fn orders_and_paginates_interfaces_across_types() {
+    let mut entities = HashMap::new();
+    entities.insert(
+        "Meme".to_owned(),
+        vec![
+            object! { "id" => "m1", "regEntry_createdOn" => "2020-01-03" },
+            object! { "id" => "m2", "regEntry_createdOn" => "2020-01-05" },
+        ],
+    );
+    entities.insert(
+        "ParamChange".to_owned(),
+        vec![
+            object! { "id" => "p1", "regEntry_createdOn" => "2020-01-05" },
+            object! { "id" => "p2", "regEntry_createdOn" => "2020-01-04" },
+        ],
+    );
+
+    // Entries created at the same time are ordered by id in both directions
+    let result = execute(
+        complex_api_schema(COMPLEX_SCHEMA),
+        "{
+           regEntries(orderBy: regEntry_createdOn, orderDirection: desc, skip: 1, first: 2) {
+             __typename
+             ... on Meme { id }
+             ... on ParamChange { id }
+           }
+         }",
+        default_options(InMemoryResolver::new(entities)),
+    );
+
+    assert!(result.errors.is_none(), format!("{:#?}", result.errors));
+    assert_values_eq!(
+        result.data.unwrap(),
+        object! {
+            "regEntries" => list![
+                object! { "__typename" => "ParamChange", "id" => "p1" },
+                object! { "__typename" => "ParamChange", "id" => "p2" },
+            ],
+        }
+    );
+}
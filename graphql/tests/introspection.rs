@@ -3,90 +3,16 @@ extern crate pretty_assertions;
 
 use graphql_parser::{query as q, schema as s};
 use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
 
 use graph::prelude::*;
 use graph_graphql::prelude::*;
-
-/// Mock resolver used in tests that don't need a resolver.
-#[derive(Clone)]
-pub struct MockResolver;
-
-impl Resolver for MockResolver {
-    fn resolve_objects<'a>(
-        &self,
-        _parent: &Option<q::Value>,
-        _field: &q::Name,
-        _field_definition: &s::Field,
-        _object_type: ObjectOrInterface<'_>,
-        _arguments: &HashMap<&q::Name, q::Value>,
-        _types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
-        _max_first: u32,
-    ) -> Result<q::Value, QueryExecutionError> {
-        Ok(q::Value::Null)
-    }
-
-    fn resolve_object(
-        &self,
-        _parent: &Option<q::Value>,
-        _field: &q::Field,
-        _field_definition: &s::Field,
-        _object_type: ObjectOrInterface<'_>,
-        _arguments: &HashMap<&q::Name, q::Value>,
-        _types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
-    ) -> Result<q::Value, QueryExecutionError> {
-        Ok(q::Value::Null)
-    }
-}
-
-/// Creates a basic GraphQL schema that exercies scalars, directives,
-/// enums, interfaces, input objects, object types and field arguments.
-fn mock_schema() -> Schema {
-    Schema::parse(
-        "
-             scalar ID
-             scalar Int
-             scalar String
-             scalar Boolean
-
-             directive @language(
-               language: String = \"English\"
-             ) on FIELD_DEFINITION
-
-             enum Role {
-               USER
-               ADMIN
-             }
-
-             interface Node {
-               id: ID!
-             }
-
-             type User implements Node @entity {
-               id: ID!
-               name: String! @language(language: \"English\")
-               role: Role!
-             }
-
-             enum User_orderBy {
-               id
-               name
-             }
-
-             input User_filter {
-               name_eq: String = \"default name\",
-               name_not: String,
-             }
-
-             type Query @entity {
-               allUsers(orderBy: User_orderBy, filter: User_filter): [User!]
-               anyUserWithAge(age: Int = 99): User
-               User: User
-             }
-             ",
-        SubgraphDeploymentId::new("mockschema").unwrap(),
-    )
-    .unwrap()
-}
+use graph_graphql_test_support::schemas::{
+    api_schema_from, complex_api_schema, mock_schema, COMPLEX_SCHEMA,
+};
+use graph_graphql_test_support::{
+    assert_values_eq, default_options, execute, list, object, ScriptedResolver,
+};
 
 /// Builds the expected result for GraphiQL's introspection query that we are
 /// using for testing.
@@ -193,7 +119,7 @@ fn expected_mock_schema_introspection() -> q::Value {
         ),
         ("inputFields", q::Value::Null),
         ("enumValues", q::Value::Null),
-        ("interfaces", q::Value::Null),
+        ("interfaces", q::Value::List(vec![])),
         (
             "possibleTypes",
             q::Value::List(vec![object_value(vec![
@@ -536,25 +462,7 @@ fn expected_mock_schema_introspection() -> q::Value {
 
 /// Execute an introspection query.
 fn introspection_query(schema: Schema, query: &str) -> QueryResult {
-    // Create the query
-    let query = Query {
-        schema: Arc::new(schema),
-        document: graphql_parser::parse_query(query).unwrap(),
-        variables: None,
-    };
-
-    // Execute it
-    execute_query(
-        &query,
-        QueryExecutionOptions {
-            logger: Logger::root(slog::Discard, o!()),
-            resolver: MockResolver,
-            deadline: None,
-            max_complexity: None,
-            max_depth: 100,
-            max_first: std::u32::MAX,
-        },
-    )
+    execute(schema, query, default_options(ScriptedResolver::new()))
 }
 
 #[test]
@@ -908,203 +816,9 @@ fn satisfies_graphiql_introspection_query_with_fragments() {
     assert_eq!(data, expected_mock_schema_introspection());
 }
 
-const COMPLEX_SCHEMA: &str = "
-enum RegEntryStatus {
-  regEntry_status_challengePeriod
-  regEntry_status_commitPeriod
-  regEntry_status_revealPeriod
-  regEntry_status_blacklisted
-  regEntry_status_whitelisted
-}
-
-interface RegEntry {
-  regEntry_address: ID
-  regEntry_version: Int
-  regEntry_status: RegEntryStatus
-  regEntry_creator: User
-  regEntry_deposit: Int
-  regEntry_createdOn: String
-  regEntry_challengePeriodEnd: String
-  challenge_challenger: User
-  challenge_createdOn: String
-  challenge_comment: String
-  challenge_votingToken: String
-  challenge_rewardPool: Int
-  challenge_commitPeriodEnd: String
-  challenge_revealPeriodEnd: String
-  challenge_votesFor: Int
-  challenge_votesAgainst: Int
-  challenge_votesTotal: Int
-  challenge_claimedRewardOn: String
-  challenge_vote(vote_voter: ID!): Vote
-}
-
-enum VoteOption {
-  voteOption_noVote
-  voteOption_voteFor
-  voteOption_voteAgainst
-}
-
-type Vote @entity {
-  vote_secretHash: String
-  vote_option: VoteOption
-  vote_amount: Int
-  vote_revealedOn: String
-  vote_claimedRewardOn: String
-  vote_reward: Int
-}
-
-type Meme implements RegEntry @entity {
-  regEntry_address: ID
-  regEntry_version: Int
-  regEntry_status: RegEntryStatus
-  regEntry_creator: User
-  regEntry_deposit: Int
-  regEntry_createdOn: String
-  regEntry_challengePeriodEnd: String
-  challenge_challenger: User
-  challenge_createdOn: String
-  challenge_comment: String
-  challenge_votingToken: String
-  challenge_rewardPool: Int
-  challenge_commitPeriodEnd: String
-  challenge_revealPeriodEnd: String
-  challenge_votesFor: Int
-  challenge_votesAgainst: Int
-  challenge_votesTotal: Int
-  challenge_claimedRewardOn: String
-  challenge_vote(vote_voter: ID!): Vote
-  # Balance of voting token of a voter. This is client-side only, server doesn't return this
-  challenge_availableVoteAmount(voter: ID!): Int
-  meme_title: String
-  meme_number: Int
-  meme_metaHash: String
-  meme_imageHash: String
-  meme_totalSupply: Int
-  meme_totalMinted: Int
-  meme_tokenIdStart: Int
-  meme_totalTradeVolume: Int
-  meme_totalTradeVolumeRank: Int
-  meme_ownedMemeTokens(owner: String): [MemeToken]
-  meme_tags: [Tag]
-}
-
-type Tag  @entity {
-  tag_id: ID
-  tag_name: String
-}
-
-type MemeToken @entity {
-  memeToken_tokenId: ID
-  memeToken_number: Int
-  memeToken_owner: User
-  memeToken_meme: Meme
-}
-
-enum MemeAuctionStatus {
-  memeAuction_status_active
-  memeAuction_status_canceled
-  memeAuction_status_done
-}
-
-type MemeAuction @entity {
-  memeAuction_address: ID
-  memeAuction_seller: User
-  memeAuction_buyer: User
-  memeAuction_startPrice: Int
-  memeAuction_endPrice: Int
-  memeAuction_duration: Int
-  memeAuction_startedOn: String
-  memeAuction_boughtOn: String
-  memeAuction_status: MemeAuctionStatus
-  memeAuction_memeToken: MemeToken
-}
-
-type ParamChange implements RegEntry @entity {
-  regEntry_address: ID
-  regEntry_version: Int
-  regEntry_status: RegEntryStatus
-  regEntry_creator: User
-  regEntry_deposit: Int
-  regEntry_createdOn: String
-  regEntry_challengePeriodEnd: String
-  challenge_challenger: User
-  challenge_createdOn: String
-  challenge_comment: String
-  challenge_votingToken: String
-  challenge_rewardPool: Int
-  challenge_commitPeriodEnd: String
-  challenge_revealPeriodEnd: String
-  challenge_votesFor: Int
-  challenge_votesAgainst: Int
-  challenge_votesTotal: Int
-  challenge_claimedRewardOn: String
-  challenge_vote(vote_voter: ID!): Vote
-  # Balance of voting token of a voter. This is client-side only, server doesn't return this
-  challenge_availableVoteAmount(voter: ID!): Int
-  paramChange_db: String
-  paramChange_key: String
-  paramChange_value: Int
-  paramChange_originalValue: Int
-  paramChange_appliedOn: String
-}
-
-type User @entity {
-  # Ethereum address of an user
-  user_address: ID
-  # Total number of memes submitted by user
-  user_totalCreatedMemes: Int
-  # Total number of memes submitted by user, which successfully got into TCR
-  user_totalCreatedMemesWhitelisted: Int
-  # Largest sale creator has done with his newly minted meme
-  user_creatorLargestSale: MemeAuction
-  # Position of a creator in leaderboard according to user_totalCreatedMemesWhitelisted
-  user_creatorRank: Int
-  # Amount of meme tokenIds owned by user
-  user_totalCollectedTokenIds: Int
-  # Amount of unique memes owned by user
-  user_totalCollectedMemes: Int
-  # Largest auction user sold, in terms of price
-  user_largestSale: MemeAuction
-  # Largest auction user bought into, in terms of price
-  user_largestBuy: MemeAuction
-  # Amount of challenges user created
-  user_totalCreatedChallenges: Int
-  # Amount of challenges user created and ended up in his favor
-  user_totalCreatedChallengesSuccess: Int
-  # Total amount of DANK token user received from challenger rewards
-  user_challengerTotalEarned: Int
-  # Total amount of DANK token user received from challenger rewards
-  user_challengerRank: Int
-  # Amount of different votes user participated in
-  user_totalParticipatedVotes: Int
-  # Amount of different votes user voted for winning option
-  user_totalParticipatedVotesSuccess: Int
-  # Amount of DANK token user received for voting for winning option
-  user_voterTotalEarned: Int
-  # Position of voter in leaderboard according to user_voterTotalEarned
-  user_voterRank: Int
-  # Sum of user_challengerTotalEarned and user_voterTotalEarned
-  user_curatorTotalEarned: Int
-  # Position of curator in leaderboard according to user_curatorTotalEarned
-  user_curatorRank: Int
-}
-
-type Parameter @entity {
-  param_db: ID
-  param_key: ID
-  param_value: Int
-}
-";
-
 #[test]
 fn successfully_runs_introspection_query_against_complex_schema() {
-    let mut schema = Schema::parse(
-        COMPLEX_SCHEMA,
-        SubgraphDeploymentId::new("complexschema").unwrap(),
-    )
-    .unwrap();
-    schema.document = api_schema(&schema.document).unwrap();
+    let schema = complex_api_schema(COMPLEX_SCHEMA);
 
     let result = introspection_query(
         schema.clone(),
@@ -1208,12 +922,7 @@ fn successfully_runs_introspection_query_against_complex_schema() {
 
 #[test]
 fn introspection_possible_types() {
-    let mut schema = Schema::parse(
-        COMPLEX_SCHEMA,
-        SubgraphDeploymentId::new("complexschema").unwrap(),
-    )
-    .unwrap();
-    schema.document = api_schema(&schema.document).unwrap();
+    let schema = complex_api_schema(COMPLEX_SCHEMA);
 
     // Test "possibleTypes" introspection in interfaces
     let response = introspection_query(
@@ -1247,3 +956,321 @@ fn introspection_possible_types() {
         )])
     )
 }
+
+#[test]
+fn order_by_enums_include_fields_of_referenced_entities() {
+    let schema = complex_api_schema(COMPLEX_SCHEMA);
+    let order_by_values = |type_name: &str| -> Vec<String> {
+        match graph_graphql::schema::ast::get_named_type(
+            &schema.document,
+            &format!("{}_orderBy", type_name),
+        ) {
+            Some(s::TypeDefinition::Enum(t)) => {
+                t.values.iter().map(|value| value.name.clone()).collect()
+            }
+            _ => panic!("{}_orderBy enum is missing", type_name),
+        }
+    };
+
+    // References to object types and interfaces get one value per field of
+    // the referenced type
+    let values = order_by_values("MemeToken");
+    assert!(values.contains(&"memeToken_owner__user_curatorRank".to_owned()));
+    assert!(values.contains(&"memeToken_owner__id".to_owned()));
+    assert!(values.contains(&"memeToken_meme__meme_title".to_owned()));
+    assert!(values.contains(&"memeToken_meme__regEntry_status".to_owned()));
+
+    // Ordering only goes one level deep and leaves out list fields
+    assert!(!values.contains(&"memeToken_meme__meme_tags".to_owned()));
+    assert!(!values
+        .iter()
+        .any(|value| value.starts_with("memeToken_meme__meme_tags__")));
+    assert!(!order_by_values("Meme")
+        .iter()
+        .any(|value| value.starts_with("meme_tags__")));
+
+    // Fields that don't reference entities get no child values
+    assert!(!order_by_values("Meme")
+        .iter()
+        .any(|value| value.starts_with("regEntry_status__")));
+    assert_eq!(
+        order_by_values("Parameter"),
+        vec!["id", "param_db", "param_key", "param_value"]
+    );
+}
+
+#[test]
+fn relationship_list_fields_have_collection_arguments() {
+    let schema = complex_api_schema(COMPLEX_SCHEMA);
+    let meme = graph_graphql::schema::ast::get_object_type_definitions(&schema.document)
+        .into_iter()
+        .find(|object_type| object_type.name == "Meme")
+        .expect("Meme type not found");
+    let field = graph_graphql::schema::ast::get_field(meme, &"meme_ownedMemeTokens".to_owned())
+        .expect("meme_ownedMemeTokens field not found");
+
+    // The `owner` argument of the input schema is replaced
+    let arguments: Vec<_> = field
+        .arguments
+        .iter()
+        .map(|argument| {
+            (
+                argument.name.as_str(),
+                format!("{}", argument.value_type),
+                argument
+                    .default_value
+                    .as_ref()
+                    .map(|value| format!("{}", value)),
+            )
+        })
+        .collect();
+    assert_eq!(
+        arguments,
+        vec![
+            ("skip", "Int".to_owned(), Some("0".to_owned())),
+            ("first", "Int".to_owned(), Some("100".to_owned())),
+            (
+                "orderBy",
+                "MemeToken_orderBy".to_owned(),
+                Some("id".to_owned())
+            ),
+            (
+                "orderDirection",
+                "OrderDirection".to_owned(),
+                Some("asc".to_owned())
+            ),
+            ("where", "MemeToken_filter".to_owned(), None),
+        ]
+    );
+}
+
+#[test]
+fn introspection_possible_types_of_union() {
+    // Members are declared out of order on purpose
+    let raw_schema = format!("{}\nunion Searchable = User | Tag | Meme\n", COMPLEX_SCHEMA);
+    let schema = complex_api_schema(&raw_schema);
+
+    let response = introspection_query(
+        schema,
+        "query {
+          __type(name: \"Searchable\") {
+              name
+              kind
+              possibleTypes {
+                name
+              }
+          }
+        }",
+    )
+    .data
+    .unwrap();
+
+    assert_eq!(
+        response,
+        object_value(vec![(
+            "__type",
+            object_value(vec![
+                ("name", q::Value::String("Searchable".to_string())),
+                ("kind", q::Value::Enum("UNION".to_string())),
+                (
+                    "possibleTypes",
+                    q::Value::List(vec![
+                        object_value(vec![("name", q::Value::String("Meme".to_owned()))]),
+                        object_value(vec![("name", q::Value::String("Tag".to_owned()))]),
+                        object_value(vec![("name", q::Value::String("User".to_owned()))]),
+                    ])
+                )
+            ])
+        )])
+    )
+}
+
+#[test]
+fn introspection_includes_fields_of_type_extensions() {
+    let schema = Schema::parse(
+        "
+            scalar ID
+            scalar Int
+
+            type User @entity {
+                id: ID!
+            }
+
+            type Query @entity {
+                user(id: ID!): User
+            }
+
+            extend type Query {
+                userCount: Int
+            }
+        ",
+        SubgraphDeploymentId::new("extendedschema").unwrap(),
+    )
+    .unwrap();
+
+    // The root `Query` type needs no `id`, even when the schema is deployed
+    assert_eq!(schema.validate_id_fields(), Ok(()));
+
+    let result = introspection_query(schema, "{ __type(name: \"Query\") { fields { name } } }");
+
+    assert!(result.errors.is_none(), format!("{:#?}", result.errors));
+    assert_eq!(
+        result.data.unwrap(),
+        object_value(vec![(
+            "__type",
+            object_value(vec![(
+                "fields",
+                q::Value::List(vec![
+                    object_value(vec![("name", q::Value::String("user".to_owned()))]),
+                    object_value(vec![("name", q::Value::String("userCount".to_owned()))]),
+                ])
+            )])
+        )])
+    );
+}
+
+#[test]
+fn introspection_includes_interfaces_implemented_by_interfaces() {
+    let schema = api_schema_from(
+        "
+        interface Node {
+            id: ID!
+        }
+
+        interface Named implements Node {
+            name: String!
+        }
+
+        type User implements Named @entity {
+            id: ID!
+            name: String!
+        }
+
+        type Pet implements Node @entity {
+            id: ID!
+        }
+        ",
+        "interfacehierarchy",
+    );
+
+    let result = introspection_query(
+        schema,
+        "{
+          node: __type(name: \"Node\") { interfaces { name } possibleTypes { name } }
+          named: __type(name: \"Named\") {
+            fields { name }
+            interfaces { name }
+            possibleTypes { name }
+          }
+          user: __type(name: \"User\") { interfaces { name } }
+        }",
+    );
+
+    assert!(result.errors.is_none(), format!("{:#?}", result.errors));
+    assert_values_eq!(
+        result.data.unwrap(),
+        object! {
+            "node" => object! {
+                "interfaces" => list![],
+                "possibleTypes" => list![object! { "name" => "User" }, object! { "name" => "Pet" }],
+            },
+            "named" => object! {
+                "fields" => list![object! { "name" => "name" }, object! { "name" => "id" }],
+                "interfaces" => list![object! { "name" => "Node" }],
+                "possibleTypes" => list![object! { "name" => "User" }],
+            },
+            "user" => object! {
+                "interfaces" => list![object! { "name" => "Named" }, object! { "name" => "Node" }],
+            },
+        }
+    );
+}
+
+/// A resolver that records the `age` argument it is given.
+#[derive(Clone, Default)]
+struct AgeResolver {
+    ages: Arc<Mutex<Vec<q::Value>>>,
+}
+
+impl Resolver for AgeResolver {
+    fn resolve_objects(
+        &self,
+        _ctx: &QueryContext,
+        _parent: &Option<q::Value>,
+        _field: &q::Name,
+        _field_definition: &s::Field,
+        _object_type: ObjectOrInterface<'_>,
+        _arguments: &HashMap<&q::Name, q::Value>,
+        _types_for_interface: &BTreeMap<q::Name, Vec<s::ObjectType>>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        Ok(q::Value::Null)
+    }
+
+    fn resolve_object(
+        &self,
+        _ctx: &QueryContext,
+        _parent: &Option<q::Value>,
+        _field: &q::Field,
+        _field_definition: &s::Field,
+        _object_type: ObjectOrInterface<'_>,
+        arguments: &HashMap<&q::Name, q::Value>,
+        _types_for_interface: &BTreeMap<q::Name, Vec<s::ObjectType>>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        let age = arguments
+            .get(&"age".to_string())
+            .cloned()
+            .unwrap_or(q::Value::Null);
+        self.ages.lock().unwrap().push(age);
+        Ok(q::Value::Null)
+    }
+}
+
+#[test]
+fn argument_defaults_agree_between_introspection_and_execution() {
+    let resolver = AgeResolver::default();
+    let result = execute(
+        mock_schema(),
+        "{
+          defaulted: anyUserWithAge { name }
+          given: anyUserWithAge(age: 42) { name }
+          __type(name: \"Query\") { fields { name args { name defaultValue } } }
+        }",
+        default_options(resolver.clone()),
+    );
+    assert!(result.errors.is_none(), format!("{:#?}", result.errors));
+
+    // The resolver sees the default if the argument is omitted
+    let ages = resolver.ages.lock().unwrap().clone();
+    assert_eq!(
+        ages,
+        vec![q::Value::Int(99.into()), q::Value::Int(42.into())]
+    );
+
+    // Introspection reports the same default
+    let default_value = match result.data.unwrap() {
+        q::Value::Object(data) => match &data["__type"] {
+            q::Value::Object(query_type) => match &query_type["fields"] {
+                q::Value::List(fields) => fields
+                    .iter()
+                    .find(|field| match field {
+                        q::Value::Object(field) => {
+                            field["name"] == q::Value::String("anyUserWithAge".to_string())
+                        }
+                        _ => false,
+                    })
+                    .cloned(),
+                _ => None,
+            },
+            _ => None,
+        },
+        _ => None,
+    }
+    .expect("`anyUserWithAge` is missing from the introspection result");
+    assert_values_eq!(
+        default_value,
+        object! {
+            "name" => "anyUserWithAge",
+            "args" => list![object! { "name" => "age", "defaultValue" => ages[0].to_string() }],
+        }
+    );
+}
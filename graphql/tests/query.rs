@@ -1,13 +1,19 @@
 #[macro_use]
 extern crate pretty_assertions;
 
-use graphql_parser::{query as q, Pos};
+use graphql_parser::{query as q, schema as s, Pos};
 use lazy_static::lazy_static;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::convert::TryFrom;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::thread;
 use std::time::{Duration, Instant};
 
 use graph::prelude::*;
 use graph_graphql::prelude::*;
+use graph_graphql_test_support::schemas::{complex_api_schema, COMPLEX_SCHEMA};
+use graph_graphql_test_support::{default_options, execute, ScriptedResolver};
 use test_store::{transact_entity_operations, GENESIS_PTR, STORE};
 
 lazy_static! {
@@ -17,6 +23,18 @@ lazy_static! {
         insert_test_entities(&**STORE, id.clone());
         id
     };
+    static ref FAILED_SUBGRAPH_ID: SubgraphDeploymentId = {
+        // Populate the store and mark the deployment as failed
+        let id = SubgraphDeploymentId::new("graphqlTestsQueryFailed").unwrap();
+        insert_test_entities(&**STORE, id.clone());
+        STORE
+            .apply_metadata_operations(SubgraphDeploymentEntity::fatal_error_operations(
+                &id,
+                String::from("mapping aborted"),
+            ))
+            .unwrap();
+        id
+    };
 }
 
 fn test_schema(id: SubgraphDeploymentId) -> Schema {
@@ -56,10 +74,13 @@ fn test_schema(id: SubgraphDeploymentId) -> Schema {
 }
 
 fn api_test_schema() -> Schema {
-    let mut schema = test_schema(TEST_SUBGRAPH_ID.clone());
-    schema.document = api_schema(&schema.document).expect("Failed to derive API schema");
-    schema.add_subgraph_id_directives(TEST_SUBGRAPH_ID.clone());
-    schema
+    api_test_schema_for(TEST_SUBGRAPH_ID.clone())
+}
+
+fn api_test_schema_for(id: SubgraphDeploymentId) -> Schema {
+    let logger = Logger::root(slog::Discard, o!());
+    Schema::api(&logger, test_schema(id), Pluralization::Legacy)
+        .expect("Failed to derive API schema")
 }
 
 fn insert_test_entities(store: &impl Store, id: SubgraphDeploymentId) {
@@ -75,6 +96,7 @@ fn insert_test_entities(store: &impl Store, id: SubgraphDeploymentId) {
         schema: schema.clone(),
         data_sources: vec![],
         templates: vec![],
+        raw: None,
     };
 
     let ops = SubgraphDeploymentEntity::new(&manifest, false, false, None, None)
@@ -195,6 +217,25 @@ fn insert_test_entities(store: &impl Store, id: SubgraphDeploymentId) {
     .unwrap();
 }
 
+/// A query of `document` against `schema`, without variables or request
+/// extensions.
+fn new_query(schema: impl Into<Arc<Schema>>, document: &str) -> Query {
+    Query {
+        schema: schema.into(),
+        document: graphql_parser::parse_query(document).unwrap(),
+        variables: None,
+        dry_run: false,
+        sync_warning: false,
+        query_id: None,
+    }
+}
+
+/// Executes `query` with the entities in `store`, without any limits.
+fn execute_mock_store_query(store: Arc<graph_mock::MockStore>, query: &Query) -> QueryResult {
+    let logger = Logger::root(slog::Discard, o!());
+    execute_query(query, default_options(StoreResolver::new(&logger, store)))
+}
+
 fn execute_query_document(query: q::Document) -> QueryResult {
     execute_query_document_with_variables(query, None)
 }
@@ -202,24 +243,28 @@ fn execute_query_document(query: q::Document) -> QueryResult {
 fn execute_query_document_with_variables(
     query: q::Document,
     variables: Option<QueryVariables>,
+) -> QueryResult {
+    execute_subgraph_query_document(TEST_SUBGRAPH_ID.clone(), query, variables)
+}
+
+fn execute_subgraph_query_document(
+    id: SubgraphDeploymentId,
+    query: q::Document,
+    variables: Option<QueryVariables>,
 ) -> QueryResult {
     let query = Query {
-        schema: Arc::new(api_test_schema()),
+        schema: Arc::new(api_test_schema_for(id)),
         document: query,
         variables,
+        dry_run: false,
+        sync_warning: false,
+        query_id: None,
     };
 
     let logger = Logger::root(slog::Discard, o!());
     let store_resolver = StoreResolver::new(&logger, STORE.clone());
 
-    let options = QueryExecutionOptions {
-        logger: logger,
-        resolver: store_resolver,
-        deadline: None,
-        max_complexity: None,
-        max_depth: 100,
-        max_first: std::u32::MAX,
-    };
+    let options = default_options(store_resolver);
 
     execute_query(&query, options)
 }
@@ -704,15 +749,19 @@ fn query_complexity() {
         )
         .unwrap(),
         variables: None,
+        dry_run: false,
+        sync_warning: false,
+        query_id: None,
     };
     let max_complexity = Some(1_010_100);
     let options = QueryExecutionOptions {
-        logger: logger.clone(),
+        context: QueryContext::new(logger.clone(), None, std::u32::MAX),
         resolver: store_resolver.clone(),
-        deadline: None,
         max_complexity,
         max_depth: 100,
-        max_first: std::u32::MAX,
+        max_response_size: None,
+        max_fields_per_selection: None,
+        authorizer: Arc::new(AllowAll),
     };
 
     // This query is exactly at the maximum complexity.
@@ -741,15 +790,19 @@ fn query_complexity() {
         )
         .unwrap(),
         variables: None,
+        dry_run: false,
+        sync_warning: false,
+        query_id: None,
     };
 
     let options = QueryExecutionOptions {
-        logger,
+        context: QueryContext::new(logger, None, std::u32::MAX),
         resolver: store_resolver,
-        deadline: None,
         max_complexity,
         max_depth: 100,
-        max_first: std::u32::MAX,
+        max_response_size: None,
+        max_fields_per_selection: None,
+        authorizer: Arc::new(AllowAll),
     };
 
     // The extra introspection causes the complexity to go over.
@@ -782,6 +835,9 @@ fn query_complexity_subscriptions() {
         )
         .unwrap(),
         variables: None,
+        dry_run: false,
+        sync_warning: false,
+        query_id: None,
     };
     let max_complexity = Some(1_010_100);
     let options = SubscriptionExecutionOptions {
@@ -791,6 +847,7 @@ fn query_complexity_subscriptions() {
         max_complexity,
         max_depth: 100,
         max_first: std::u32::MAX,
+        authorizer: Arc::new(AllowAll),
     };
 
     // This query is exactly at the maximum complexity.
@@ -818,6 +875,9 @@ fn query_complexity_subscriptions() {
         )
         .unwrap(),
         variables: None,
+        dry_run: false,
+        sync_warning: false,
+        query_id: None,
     };
 
     let options = SubscriptionExecutionOptions {
@@ -827,6 +887,7 @@ fn query_complexity_subscriptions() {
         max_complexity,
         max_depth: 100,
         max_first: std::u32::MAX,
+        authorizer: Arc::new(AllowAll),
     };
 
     // The extra introspection causes the complexity to go over.
@@ -846,17 +907,21 @@ fn instant_timeout() {
         schema: Arc::new(api_test_schema()),
         document: graphql_parser::parse_query("query { musicians(first: 100) { name } }").unwrap(),
         variables: None,
+        dry_run: false,
+        sync_warning: false,
+        query_id: None,
     };
     let logger = Logger::root(slog::Discard, o!());
     let store_resolver = StoreResolver::new(&logger, STORE.clone());
 
     let options = QueryExecutionOptions {
-        logger: logger,
+        context: QueryContext::new(logger, Some(Instant::now()), std::u32::MAX),
         resolver: store_resolver,
-        deadline: Some(Instant::now()),
         max_complexity: None,
         max_depth: 100,
-        max_first: std::u32::MAX,
+        max_response_size: None,
+        max_fields_per_selection: None,
+        authorizer: Arc::new(AllowAll),
     };
 
     match execute_query(&query, options).errors.unwrap()[0] {
@@ -1153,6 +1218,9 @@ fn subscription_gets_result_even_without_events() {
         )
         .unwrap(),
         variables: None,
+        dry_run: false,
+        sync_warning: false,
+        query_id: None,
     };
 
     let options = SubscriptionExecutionOptions {
@@ -1162,6 +1230,7 @@ fn subscription_gets_result_even_without_events() {
         max_complexity: None,
         max_depth: 100,
         max_first: std::u32::MAX,
+        authorizer: Arc::new(AllowAll),
     };
 
     // Execute the subscription and expect at least one result to be
@@ -1240,3 +1309,3461 @@ fn can_use_nested_filter() {
         )])
     )
 }
+
+#[test]
+fn nested_collection_arguments_are_applied() {
+    let result = execute_query_document(
+        graphql_parser::parse_query(
+            "
+        query {
+            bands(orderBy: id) {
+                id
+                members(where: { name_not: \"Lisa\" }, orderBy: name, orderDirection: desc, first: 1) {
+                    name
+                }
+                originalSongs(skip: 1, first: 1) { id }
+            }
+        }
+        ",
+        )
+        .expect("invalid test query"),
+    );
+
+    assert_eq!(
+        result.data.unwrap(),
+        object_value(vec![(
+            "bands",
+            q::Value::List(vec![
+                object_value(vec![
+                    ("id", q::Value::String(String::from("b1"))),
+                    (
+                        "members",
+                        q::Value::List(vec![object_value(vec![(
+                            "name",
+                            q::Value::String(String::from("Tom"))
+                        )])])
+                    ),
+                    (
+                        "originalSongs",
+                        q::Value::List(vec![object_value(vec![(
+                            "id",
+                            q::Value::String(String::from("s2"))
+                        )])])
+                    ),
+                ]),
+                object_value(vec![
+                    ("id", q::Value::String(String::from("b2"))),
+                    (
+                        "members",
+                        q::Value::List(vec![object_value(vec![(
+                            "name",
+                            q::Value::String(String::from("Tom"))
+                        )])])
+                    ),
+                    (
+                        "originalSongs",
+                        q::Value::List(vec![object_value(vec![(
+                            "id",
+                            q::Value::String(String::from("s3"))
+                        )])])
+                    ),
+                ]),
+            ])
+        )])
+    )
+}
+
+/// A value that is expensive to compute and should only be looked up once
+/// per query.
+struct CachedLookup;
+
+/// Wraps a resolver and looks up a `CachedLookup` through the query context
+/// for every list field it resolves.
+#[derive(Clone)]
+struct CachingResolver<R> {
+    inner: R,
+    lookups: Arc<AtomicUsize>,
+}
+
+impl<R> Resolver for CachingResolver<R>
+where
+    R: Resolver,
+{
+    fn resolve_objects(
+        &self,
+        ctx: &QueryContext,
+        parent: &Option<q::Value>,
+        field: &q::Name,
+        field_definition: &s::Field,
+        object_type: ObjectOrInterface<'_>,
+        arguments: &HashMap<&q::Name, q::Value>,
+        types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        let lookups = self.lookups.clone();
+        ctx.extension_or_insert_with(|| {
+            lookups.fetch_add(1, Ordering::SeqCst);
+            CachedLookup
+        });
+        self.inner.resolve_objects(
+            ctx,
+            parent,
+            field,
+            field_definition,
+            object_type,
+            arguments,
+            types_for_interface,
+        )
+    }
+
+    fn resolve_object(
+        &self,
+        ctx: &QueryContext,
+        parent: &Option<q::Value>,
+        field: &q::Field,
+        field_definition: &s::Field,
+        object_type: ObjectOrInterface<'_>,
+        arguments: &HashMap<&q::Name, q::Value>,
+        types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        self.inner.resolve_object(
+            ctx,
+            parent,
+            field,
+            field_definition,
+            object_type,
+            arguments,
+            types_for_interface,
+        )
+    }
+}
+
+#[test]
+fn fields_share_query_context_state() {
+    let query = new_query(
+        api_test_schema(),
+        "
+            query {
+                musicians(first: 1, orderBy: id) { id }
+                bands(first: 1, orderBy: id) { id }
+            }
+            ",
+    );
+
+    let logger = Logger::root(slog::Discard, o!());
+    let lookups = Arc::new(AtomicUsize::new(0));
+    let resolver = CachingResolver {
+        inner: StoreResolver::new(&logger, STORE.clone()),
+        lookups: lookups.clone(),
+    };
+
+    let result = execute_query(&query, default_options(resolver));
+
+    assert!(result.errors.is_none());
+    assert_eq!(
+        result.data.unwrap(),
+        object_value(vec![
+            (
+                "musicians",
+                q::Value::List(vec![object_value(vec![(
+                    "id",
+                    q::Value::String(String::from("m1"))
+                )])])
+            ),
+            (
+                "bands",
+                q::Value::List(vec![object_value(vec![(
+                    "id",
+                    q::Value::String(String::from("b1"))
+                )])])
+            ),
+        ])
+    );
+
+    // Both fields were resolved, but the lookup only happened once
+    assert_eq!(lookups.load(Ordering::SeqCst), 1);
+}
+
+/// Wraps a resolver, reports when it starts resolving a list field and then
+/// takes `delay` to resolve it.
+#[derive(Clone)]
+struct SlowResolver<R> {
+    inner: R,
+    delay: Duration,
+    started: Arc<Mutex<mpsc::Sender<()>>>,
+}
+
+impl<R> Resolver for SlowResolver<R>
+where
+    R: Resolver,
+{
+    fn resolve_objects(
+        &self,
+        ctx: &QueryContext,
+        parent: &Option<q::Value>,
+        field: &q::Name,
+        field_definition: &s::Field,
+        object_type: ObjectOrInterface<'_>,
+        arguments: &HashMap<&q::Name, q::Value>,
+        types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        self.started.lock().unwrap().send(()).unwrap();
+        thread::sleep(self.delay);
+        self.inner.resolve_objects(
+            ctx,
+            parent,
+            field,
+            field_definition,
+            object_type,
+            arguments,
+            types_for_interface,
+        )
+    }
+
+    fn resolve_object(
+        &self,
+        ctx: &QueryContext,
+        parent: &Option<q::Value>,
+        field: &q::Field,
+        field_definition: &s::Field,
+        object_type: ObjectOrInterface<'_>,
+        arguments: &HashMap<&q::Name, q::Value>,
+        types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        self.inner.resolve_object(
+            ctx,
+            parent,
+            field,
+            field_definition,
+            object_type,
+            arguments,
+            types_for_interface,
+        )
+    }
+}
+
+/// Executes a query for the first musician as in-flight work of `shutdown`.
+fn execute_query_during_shutdown<R>(resolver: R, shutdown: &ShutdownCoordinator) -> QueryResult
+where
+    R: Resolver,
+{
+    let query = new_query(
+        api_test_schema(),
+        "query { musicians(first: 1, orderBy: id) { id } }",
+    );
+
+    let mut context = QueryContext::new(Logger::root(slog::Discard, o!()), None, std::u32::MAX);
+    context.shutdown = Some(shutdown.clone());
+
+    execute_query(
+        &query,
+        QueryExecutionOptions {
+            context,
+            ..default_options(resolver)
+        },
+    )
+}
+
+#[test]
+fn shutdown_lets_running_queries_finish_and_rejects_new_ones() {
+    let logger = Logger::root(slog::Discard, o!());
+    let shutdown = ShutdownCoordinator::new(
+        Arc::new(graph_mock::MockMetricsRegistry::new()),
+        Duration::from_secs(30),
+    );
+
+    // Start a slow query and begin shutting down while it runs
+    let (started_sender, started_receiver) = mpsc::channel();
+    let slow_query = {
+        let resolver = SlowResolver {
+            inner: StoreResolver::new(&logger, STORE.clone()),
+            delay: Duration::from_millis(300),
+            started: Arc::new(Mutex::new(started_sender)),
+        };
+        let shutdown = shutdown.clone();
+        thread::spawn(move || execute_query_during_shutdown(resolver, &shutdown))
+    };
+    started_receiver.recv().unwrap();
+    let drained = shutdown.shutdown();
+    assert_eq!(shutdown.in_flight(), 1);
+
+    // Queries submitted after the shutdown started are rejected
+    let rejected =
+        execute_query_during_shutdown(StoreResolver::new(&logger, STORE.clone()), &shutdown);
+    match rejected.errors.expect("query is rejected").as_slice() {
+        [QueryError::ExecutionError(QueryExecutionError::ShuttingDown)] => (),
+        errors => panic!("unexpected errors: {:?}", errors),
+    }
+
+    // The slow query still completes, which ends the shutdown
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.block_on(drained).unwrap();
+    let result = slow_query.join().unwrap();
+    assert!(result.errors.is_none());
+    assert_eq!(
+        result.data.unwrap(),
+        object_value(vec![(
+            "musicians",
+            q::Value::List(vec![object_value(vec![(
+                "id",
+                q::Value::String(String::from("m1"))
+            )])])
+        )])
+    );
+    assert_eq!(shutdown.in_flight(), 0);
+}
+
+#[test]
+fn rejects_queries_against_failed_deployment() {
+    let result = execute_subgraph_query_document(
+        FAILED_SUBGRAPH_ID.clone(),
+        graphql_parser::parse_query("query { musicians(first: 100, orderBy: id) { name } }")
+            .expect("invalid test query"),
+        None,
+    );
+
+    assert_eq!(result.data, None);
+    let errors = result
+        .errors
+        .expect("failed deployment must return an error");
+    assert_eq!(errors.len(), 1);
+    match &errors[0] {
+        QueryError::ExecutionError(QueryExecutionError::SubgraphFailed { block, message }) => {
+            assert_eq!(*block, 0);
+            assert_eq!(message, "mapping aborted");
+        }
+        e => panic!("unexpected error: {:?}", e),
+    }
+}
+
+#[test]
+fn returns_data_and_error_of_failed_deployment_when_allowed() {
+    let query = graphql_parser::parse_query(
+        "
+        query($policy: _SubgraphErrorPolicy_) {
+            musicians(first: 100, orderBy: id, subgraphError: $policy) {
+                name
+            }
+        }
+        ",
+    )
+    .expect("invalid test query");
+    let variables = Some(QueryVariables::new(HashMap::from_iter(
+        vec![(
+            String::from("policy"),
+            q::Value::Enum(String::from("allow")),
+        )]
+        .into_iter(),
+    )));
+    let result = execute_subgraph_query_document(FAILED_SUBGRAPH_ID.clone(), query, variables);
+
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![(
+            "musicians",
+            q::Value::List(vec![
+                object_value(vec![("name", q::Value::String(String::from("John")))]),
+                object_value(vec![("name", q::Value::String(String::from("Lisa")))]),
+                object_value(vec![("name", q::Value::String(String::from("Tom")))]),
+                object_value(vec![("name", q::Value::String(String::from("Valerie")))]),
+            ]),
+        )]))
+    );
+    let errors = result
+        .errors
+        .expect("failed deployment must report its error");
+    assert_eq!(errors.len(), 1);
+    match &errors[0] {
+        QueryError::ExecutionError(QueryExecutionError::SubgraphFailed { message, .. }) => {
+            assert_eq!(message, "mapping aborted")
+        }
+        e => panic!("unexpected error: {:?}", e),
+    }
+}
+
+#[test]
+fn healthy_deployment_returns_data_regardless_of_error_policy() {
+    for query in &[
+        "query { musician(id: \"m1\") { name } }",
+        "query { musician(id: \"m1\", subgraphError: allow) { name } }",
+        "query { musician(id: \"m1\", subgraphError: deny) { name } }",
+    ] {
+        let result =
+            execute_query_document(graphql_parser::parse_query(query).expect("invalid test query"));
+
+        assert!(
+            result.errors.is_none(),
+            format!("Unexpected errors return for query: {:#?}", result.errors)
+        );
+        assert_eq!(
+            result.data,
+            Some(object_value(vec![(
+                "musician",
+                object_value(vec![("name", q::Value::String(String::from("John")))]),
+            )]))
+        );
+    }
+}
+
+#[test]
+fn rejects_unknown_arguments() {
+    let result = execute_query_document(
+        graphql_parser::parse_query("query { musicians(bogus: 1) { name } }")
+            .expect("invalid test query"),
+    );
+
+    let errors = result.errors.expect("unknown argument must be rejected");
+    assert_eq!(errors.len(), 1);
+    match &errors[0] {
+        QueryError::ExecutionError(QueryExecutionError::UnknownArgument {
+            field,
+            argument,
+            ..
+        }) => {
+            assert_eq!(field, "musicians");
+            assert_eq!(argument, "bogus");
+        }
+        e => panic!("unexpected error: {:?}", e),
+    }
+}
+
+#[test]
+fn accepts_known_arguments() {
+    let result = execute_query_document(
+        graphql_parser::parse_query("query { musicians(first: 1, orderBy: id) { name } }")
+            .expect("invalid test query"),
+    );
+
+    assert!(
+        result.errors.is_none(),
+        format!("Unexpected errors return for query: {:#?}", result.errors)
+    );
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![(
+            "musicians",
+            q::Value::List(vec![object_value(vec![(
+                "name",
+                q::Value::String(String::from("John"))
+            )])]),
+        )]))
+    );
+}
+
+#[test]
+fn known_argument_with_invalid_value_is_not_unknown() {
+    let result = execute_query_document(
+        graphql_parser::parse_query("query { musicians(first: \"one\") { name } }")
+            .expect("invalid test query"),
+    );
+
+    let errors = result
+        .errors
+        .expect("invalid argument value must be rejected");
+    assert_eq!(errors.len(), 1);
+    match &errors[0] {
+        QueryError::ExecutionError(QueryExecutionError::InvalidArgumentError(_, argument, _)) => {
+            assert_eq!(argument, "first");
+        }
+        e => panic!("unexpected error: {:?}", e),
+    }
+}
+
+#[test]
+fn list_fields_are_ordered_by_id_by_default() {
+    let result = execute_query_document(
+        graphql_parser::parse_query(
+            "
+            query {
+              musicians {
+                id
+                writtenSongs { id }
+              }
+              bands(orderDirection: desc) { id }
+            }
+            ",
+        )
+        .expect("invalid test query"),
+    );
+
+    assert!(
+        result.errors.is_none(),
+        format!("Unexpected errors return for query: {:#?}", result.errors)
+    );
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![
+            (
+                "musicians",
+                q::Value::List(vec![
+                    object_value(vec![
+                        ("id", q::Value::String(String::from("m1"))),
+                        (
+                            "writtenSongs",
+                            q::Value::List(vec![
+                                object_value(vec![("id", q::Value::String(String::from("s1")))]),
+                                object_value(vec![("id", q::Value::String(String::from("s3")))]),
+                            ])
+                        ),
+                    ]),
+                    object_value(vec![
+                        ("id", q::Value::String(String::from("m2"))),
+                        (
+                            "writtenSongs",
+                            q::Value::List(vec![object_value(vec![(
+                                "id",
+                                q::Value::String(String::from("s2"))
+                            )])])
+                        ),
+                    ]),
+                    object_value(vec![
+                        ("id", q::Value::String(String::from("m3"))),
+                        (
+                            "writtenSongs",
+                            q::Value::List(vec![object_value(vec![(
+                                "id",
+                                q::Value::String(String::from("s4"))
+                            )])])
+                        ),
+                    ]),
+                    object_value(vec![
+                        ("id", q::Value::String(String::from("m4"))),
+                        ("writtenSongs", q::Value::List(vec![])),
+                    ]),
+                ])
+            ),
+            (
+                "bands",
+                q::Value::List(vec![
+                    object_value(vec![("id", q::Value::String(String::from("b2")))]),
+                    object_value(vec![("id", q::Value::String(String::from("b1")))]),
+                ])
+            ),
+        ]))
+    );
+}
+
+#[test]
+fn explicit_order_by_overrides_default_order() {
+    let result = execute_query_document(
+        graphql_parser::parse_query(
+            "query { musicians(orderBy: name, orderDirection: desc) { name } }",
+        )
+        .expect("invalid test query"),
+    );
+
+    assert!(
+        result.errors.is_none(),
+        format!("Unexpected errors return for query: {:#?}", result.errors)
+    );
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![(
+            "musicians",
+            q::Value::List(vec![
+                object_value(vec![("name", q::Value::String(String::from("Valerie")))]),
+                object_value(vec![("name", q::Value::String(String::from("Tom")))]),
+                object_value(vec![("name", q::Value::String(String::from("Lisa")))]),
+                object_value(vec![("name", q::Value::String(String::from("John")))]),
+            ])
+        )]))
+    );
+}
+
+/// Queries the first musician ordered by `order_by`, which is either an enum
+/// literal or the variable `$orderBy` with the value `variable`.
+fn execute_order_by_query(order_by: &str, variable: Option<q::Value>) -> QueryResult {
+    let query = format!(
+        "query musicians($orderBy: Musician_orderBy) {{ \
+         musicians(first: 1, orderBy: {}) {{ name }} }}",
+        order_by
+    );
+    let variables = variable.map(|value| {
+        QueryVariables::new(HashMap::from_iter(
+            vec![(String::from("orderBy"), value)].into_iter(),
+        ))
+    });
+
+    execute_query_document_with_variables(
+        graphql_parser::parse_query(&query).expect("invalid test query"),
+        variables,
+    )
+}
+
+fn assert_invalid_enum_value(result: QueryResult, expected_value: &str) {
+    assert_invalid_enum_value_with_suggestion(result, expected_value, None)
+}
+
+fn assert_invalid_enum_value_with_suggestion(
+    result: QueryResult,
+    expected_value: &str,
+    expected_suggestion: Option<&str>,
+) {
+    let errors = result.errors.expect("invalid enum value must be rejected");
+    assert_eq!(errors.len(), 1);
+    match &errors[0] {
+        QueryError::ExecutionError(QueryExecutionError::InvalidEnumValue {
+            enum_name,
+            value,
+            allowed,
+            suggestion,
+            ..
+        }) => {
+            assert_eq!(enum_name, "Musician_orderBy");
+            assert_eq!(value, expected_value);
+            assert!(allowed.contains(&String::from("name")), "{:?}", allowed);
+            assert_eq!(suggestion.as_ref().map(String::as_str), expected_suggestion);
+        }
+        e => panic!("unexpected error: {:?}", e),
+    }
+    assert!(result.data.is_none());
+}
+
+#[test]
+fn enum_arguments_accept_declared_values() {
+    let expected = Some(object_value(vec![(
+        "musicians",
+        q::Value::List(vec![object_value(vec![(
+            "name",
+            q::Value::String(String::from("John")),
+        )])]),
+    )]));
+
+    let result = execute_order_by_query("name", None);
+    assert!(result.errors.is_none(), "{:#?}", result.errors);
+    assert_eq!(result.data, expected);
+
+    let result = execute_order_by_query("$orderBy", Some(q::Value::String(String::from("name"))));
+    assert!(result.errors.is_none(), "{:#?}", result.errors);
+    assert_eq!(result.data, expected);
+}
+
+#[test]
+fn enum_arguments_reject_undeclared_values() {
+    assert_invalid_enum_value(execute_order_by_query("bogus", None), "bogus");
+}
+
+#[test]
+fn enum_variables_reject_undeclared_values() {
+    assert_invalid_enum_value(
+        execute_order_by_query("$orderBy", Some(q::Value::String(String::from("bogus")))),
+        "bogus",
+    );
+}
+
+#[test]
+fn enum_values_must_match_exactly() {
+    // Wrong casing and near-misses are rejected the same way whether they
+    // are written into the query or passed in a variable
+    for (order_by, suggestion) in &[("Name", "name"), ("NAME", "name"), ("nmae", "name")] {
+        assert_invalid_enum_value_with_suggestion(
+            execute_order_by_query(order_by, None),
+            order_by,
+            Some(suggestion),
+        );
+        assert_invalid_enum_value_with_suggestion(
+            execute_order_by_query("$orderBy", Some(q::Value::String(order_by.to_string()))),
+            order_by,
+            Some(suggestion),
+        );
+    }
+
+    let result = execute_order_by_query("Name", None);
+    let message = result.errors.unwrap()[0].to_string();
+    assert!(message.contains("Did you mean `name`?"), "{}", message);
+    assert!(
+        message.contains("Allowed values are: `id`, "),
+        "{}",
+        message
+    );
+}
+
+/// Runs `query` against a mock store with `MemeToken`s whose references to
+/// `User`s are unset or point to users that don't exist.
+fn execute_reference_query(query: &str) -> QueryResult {
+    let id = SubgraphDeploymentId::new("graphqlTestsReferences").unwrap();
+    let schema = Schema::parse(
+        "
+            type User @entity {
+                id: ID!
+                name: String!
+            }
+
+            type MemeToken @entity {
+                id: ID!
+                owner: User
+                creator: User!
+            }
+        ",
+        id.clone(),
+    )
+    .expect("Test schema invalid");
+    let logger = Logger::root(slog::Discard, o!());
+    let schema =
+        Schema::api(&logger, schema, Pluralization::Legacy).expect("Failed to derive API schema");
+
+    let store = graph_mock::MockStore::new(vec![]);
+    let entities = vec![
+        (
+            "User",
+            vec![("id", Value::from("u1")), ("name", Value::from("Alice"))],
+        ),
+        // `owner` is unset
+        (
+            "MemeToken",
+            vec![("id", Value::from("t1")), ("creator", Value::from("u1"))],
+        ),
+        // `owner` points to a user that doesn't exist
+        (
+            "MemeToken",
+            vec![
+                ("id", Value::from("t2")),
+                ("owner", Value::from("u2")),
+                ("creator", Value::from("u1")),
+            ],
+        ),
+        // `creator` points to a user that doesn't exist
+        (
+            "MemeToken",
+            vec![("id", Value::from("t3")), ("creator", Value::from("u3"))],
+        ),
+        (
+            "MemeToken",
+            vec![("id", Value::from("t4")), ("creator", Value::from("u4"))],
+        ),
+    ];
+    for (entity_type, data) in entities {
+        let entity = Entity::from(data);
+        store.set(
+            EntityKey {
+                subgraph_id: id.clone(),
+                entity_type: entity_type.to_owned(),
+                entity_id: entity.id().unwrap(),
+            },
+            entity,
+        );
+    }
+
+    execute_mock_store_query(Arc::new(store), &new_query(schema, query))
+}
+
+#[test]
+fn unset_nullable_reference_resolves_to_null() {
+    let result =
+        execute_reference_query("{ memeToken(id: \"t1\") { id owner { name } creator { name } } }");
+
+    assert!(result.errors.is_none(), format!("{:#?}", result.errors));
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![(
+            "memeToken",
+            object_value(vec![
+                ("id", q::Value::String(String::from("t1"))),
+                ("owner", q::Value::Null),
+                (
+                    "creator",
+                    object_value(vec![("name", q::Value::String(String::from("Alice")))])
+                ),
+            ])
+        )]))
+    );
+}
+
+#[test]
+fn dangling_nullable_reference_resolves_to_null() {
+    let result = execute_reference_query("{ memeToken(id: \"t2\") { id owner { name } } }");
+
+    assert!(result.errors.is_none(), format!("{:#?}", result.errors));
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![(
+            "memeToken",
+            object_value(vec![
+                ("id", q::Value::String(String::from("t2"))),
+                ("owner", q::Value::Null),
+            ])
+        )]))
+    );
+}
+
+#[test]
+fn dangling_non_null_reference_fails_with_path() {
+    let result =
+        execute_reference_query("{ token: memeToken(id: \"t3\") { id creator { name } } }");
+
+    match result.errors.expect("query should fail").as_slice() {
+        [QueryError::ExecutionError(QueryExecutionError::NonNullError(_, path))] => {
+            assert_eq!(path, "token.creator")
+        }
+        errors => panic!("unexpected errors: {:?}", errors),
+    }
+}
+
+#[test]
+fn identical_errors_are_reported_once_in_a_fixed_order() {
+    // Both `t3` and `t4` have a dangling `creator`, which fails the same
+    // way for each of them
+    let result = execute_reference_query(
+        "{
+            token: memeToken(id: \"t4\") { creator { name } }
+            memeTokens(where: { id_in: [\"t4\", \"t3\", \"t1\"] }) { id creator { name } }
+        }",
+    );
+
+    // The errors are ordered by where they occurred in the query
+    let errors: Vec<_> = result
+        .errors
+        .expect("query should fail")
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    assert_eq!(
+        errors,
+        vec![
+            "Null value resolved for non-null field `token.creator`",
+            "Null value resolved for non-null field `memeTokens.creator`",
+        ]
+    );
+}
+
+/// Runs `query` against `COMPLEX_SCHEMA` with memes and parameter changes
+/// whose creation dates interleave, so that the `RegEntry`s of one type
+/// can't be paginated on their own.
+fn execute_reg_entry_query(query: &str) -> QueryResult {
+    let schema = complex_api_schema(COMPLEX_SCHEMA);
+
+    let store = graph_mock::MockStore::new(vec![]);
+    let entities = vec![
+        ("Meme", "m1", "2020-01-03"),
+        ("Meme", "m2", "2020-01-01"),
+        ("Meme", "m3", "2020-01-05"),
+        ("ParamChange", "p1", "2020-01-02"),
+        ("ParamChange", "p2", "2020-01-04"),
+        // Created at the same time as `m3`
+        ("ParamChange", "p3", "2020-01-05"),
+    ];
+    for (entity_type, id, created_on) in entities {
+        store.set(
+            EntityKey {
+                subgraph_id: schema.id.clone(),
+                entity_type: entity_type.to_owned(),
+                entity_id: id.to_owned(),
+            },
+            Entity::from(vec![
+                ("id", Value::from(id)),
+                ("regEntry_createdOn", Value::from(created_on)),
+            ]),
+        );
+    }
+
+    execute_mock_store_query(Arc::new(store), &new_query(schema, query))
+}
+
+fn reg_entry(type_name: &str, created_on: &str) -> q::Value {
+    object_value(vec![
+        ("__typename", q::Value::String(type_name.to_owned())),
+        (
+            "regEntry_createdOn",
+            q::Value::String(created_on.to_owned()),
+        ),
+    ])
+}
+
+#[test]
+fn interface_lists_are_ordered_and_paginated_across_types() {
+    let result = execute_reg_entry_query(
+        "{
+            regEntries(orderBy: regEntry_createdOn, skip: 1, first: 3) {
+                __typename
+                regEntry_createdOn
+            }
+        }",
+    );
+
+    assert!(result.errors.is_none(), format!("{:#?}", result.errors));
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![(
+            "regEntries",
+            q::Value::List(vec![
+                reg_entry("ParamChange", "2020-01-02"),
+                reg_entry("Meme", "2020-01-03"),
+                reg_entry("ParamChange", "2020-01-04"),
+            ])
+        )]))
+    );
+}
+
+#[test]
+fn interface_lists_break_ties_across_types_by_id() {
+    // `m3` and `p3` have the same creation date and come in the order of
+    // their IDs, in both directions like the store orders them
+    let result = execute_reg_entry_query(
+        "{
+            regEntries(orderBy: regEntry_createdOn, orderDirection: desc, first: 3) {
+                __typename
+                regEntry_createdOn
+                ... on Meme { id }
+                ... on ParamChange { id }
+            }
+        }",
+    );
+
+    assert!(result.errors.is_none(), format!("{:#?}", result.errors));
+    let ids: Vec<_> = match result.data {
+        Some(q::Value::Object(data)) => match &data["regEntries"] {
+            q::Value::List(entries) => entries
+                .iter()
+                .map(|entry| match entry {
+                    q::Value::Object(entry) => entry["id"].clone(),
+                    _ => panic!("reg entry is not an object"),
+                })
+                .collect(),
+            _ => panic!("regEntries is not a list"),
+        },
+        _ => panic!("query returned no data"),
+    };
+    assert_eq!(
+        ids,
+        vec![
+            q::Value::String(String::from("m3")),
+            q::Value::String(String::from("p3")),
+            q::Value::String(String::from("p2")),
+        ]
+    );
+}
+
+/// Runs `query` against an empty mock store and returns the result along
+/// with the number of entity reads from the store.
+fn execute_query_against_mock_store(query: &str, dry_run: bool) -> (QueryResult, usize) {
+    let id = SubgraphDeploymentId::new("graphqlTestsDryRun").unwrap();
+    let store = Arc::new(graph_mock::MockStore::new(vec![]));
+
+    let query = Query {
+        dry_run,
+        ..new_query(api_test_schema_for(id), query)
+    };
+    let result = execute_mock_store_query(store.clone(), &query);
+    (result, store.entity_reads())
+}
+
+#[test]
+fn dry_run_estimates_cost_without_reading_from_the_store() {
+    let query = "
+        query {
+            musicians(first: 5) {
+                name
+                mainBand { name }
+                bands(first: 3) {
+                    name
+                    members(first: 2) { id }
+                }
+            }
+        }
+    ";
+
+    let (result, entity_reads) = execute_query_against_mock_store(query, true);
+
+    assert!(result.errors.is_none(), format!("{:#?}", result.errors));
+    assert_eq!(entity_reads, 0);
+    assert_eq!(result.data, Some(q::Value::Null));
+    let query_id = result
+        .extensions
+        .as_ref()
+        .and_then(|extensions| extensions.query_id.clone())
+        .expect("queries are given an ID");
+    assert_eq!(
+        result.extensions,
+        Some(QueryExtensions {
+            cost_estimate: Some(QueryCostEstimate {
+                complexity: 50,
+                depth: 4,
+                // 1 for `musicians`, then one per musician for `mainBand`
+                // and `bands` and one per band for `members`
+                store_queries: 1 + 5 + 5 + 15,
+                estimated_rows: 5 + 5 + 15 + 30,
+            }),
+            warnings: None,
+            query_id: Some(query_id.clone()),
+        })
+    );
+    assert_eq!(
+        serde_json::to_value(&result).unwrap(),
+        serde_json::json!({
+            "data": null,
+            "extensions": {
+                "costEstimate": {
+                    "complexity": 50,
+                    "depth": 4,
+                    "storeQueries": 26,
+                    "estimatedRows": 55,
+                },
+                "queryId": query_id,
+            }
+        })
+    );
+
+    // Without a dry run, the same query reads from the store
+    let (result, entity_reads) = execute_query_against_mock_store(query, false);
+    assert!(result.errors.is_none(), format!("{:#?}", result.errors));
+    assert!(result
+        .extensions
+        .and_then(|extensions| extensions.cost_estimate)
+        .is_none());
+    assert!(entity_reads > 0);
+}
+
+#[test]
+fn dry_run_estimates_rows_from_first() {
+    for (query, rows) in vec![
+        ("query { musicians(first: 7) { name } }", 7),
+        ("query { musicians(first: 1000) { name } }", 1000),
+        ("query { musicians { name } }", 100),
+    ] {
+        let (result, _) = execute_query_against_mock_store(query, true);
+        assert!(result.errors.is_none(), format!("{:#?}", result.errors));
+        let estimate = result
+            .extensions
+            .and_then(|extensions| extensions.cost_estimate)
+            .expect("dry runs return a cost estimate");
+        assert_eq!(estimate.store_queries, 1);
+        assert_eq!(estimate.estimated_rows, rows, "{}", query);
+    }
+}
+
+#[test]
+fn dry_run_validates_range_arguments() {
+    let (result, entity_reads) =
+        execute_query_against_mock_store("query { musicians(first: -1) { name } }", true);
+
+    assert_eq!(entity_reads, 0);
+    assert!(result
+        .extensions
+        .as_ref()
+        .and_then(|extensions| extensions.cost_estimate.as_ref())
+        .is_none());
+    match &result
+        .errors
+        .expect("dry run of an invalid query must fail")[..]
+    {
+        [QueryError::ExecutionError(QueryExecutionError::RangeArgumentsError(args, _))] => {
+            assert_eq!(args, &vec!["first"])
+        }
+        errors => panic!("unexpected errors: {:?}", errors),
+    }
+}
+
+/// A drain that keeps the messages logged through it along with their
+/// key-value pairs.
+#[derive(Clone, Default)]
+struct CapturingDrain {
+    records: Arc<Mutex<Vec<(String, HashMap<String, String>)>>>,
+}
+
+impl CapturingDrain {
+    /// Returns the key-value pairs of the records with message `msg`.
+    fn records(&self, msg: &str) -> Vec<HashMap<String, String>> {
+        self.records
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(record_msg, _)| record_msg == msg)
+            .map(|(_, kvs)| kvs.clone())
+            .collect()
+    }
+}
+
+struct KeyValues(HashMap<String, String>);
+
+impl slog::Serializer for KeyValues {
+    fn emit_arguments(&mut self, key: slog::Key, value: &std::fmt::Arguments) -> slog::Result {
+        self.0.insert(key.to_string(), value.to_string());
+        Ok(())
+    }
+}
+
+impl slog::Drain for CapturingDrain {
+    type Ok = ();
+    type Err = slog::Never;
+
+    fn log(
+        &self,
+        record: &slog::Record,
+        values: &slog::OwnedKVList,
+    ) -> Result<Self::Ok, Self::Err> {
+        use slog::KV;
+
+        let mut kvs = KeyValues(HashMap::new());
+        record.kv().serialize(record, &mut kvs).unwrap();
+        values.serialize(record, &mut kvs).unwrap();
+        self.records
+            .lock()
+            .unwrap()
+            .push((record.msg().to_string(), kvs.0));
+        Ok(())
+    }
+}
+
+/// Runs `query` against an empty mock store, logging to `drain`, and
+/// returns the result along with the store.
+fn execute_logged_query(
+    query: &str,
+    query_id: Option<String>,
+    drain: &CapturingDrain,
+) -> (QueryResult, Arc<graph_mock::MockStore>) {
+    let id = SubgraphDeploymentId::new("graphqlTestsQueryId").unwrap();
+    let logger = Logger::root(drain.clone(), o!());
+    let store = Arc::new(graph_mock::MockStore::new(vec![]));
+
+    let options = QueryExecutionOptions {
+        context: QueryContext::new(logger.clone(), None, std::u32::MAX),
+        ..default_options(StoreResolver::new(&logger, store.clone()))
+    };
+    let query = Query {
+        query_id,
+        ..new_query(api_test_schema_for(id), query)
+    };
+    (execute_query(&query, options), store)
+}
+
+#[test]
+fn queries_are_tagged_with_their_id() {
+    let drain = CapturingDrain::default();
+    let (result, store) = execute_logged_query(
+        "{ musicians(first: 5) { name bands(first: 3) { name } } }",
+        None,
+        &drain,
+    );
+
+    assert!(result.errors.is_none(), format!("{:#?}", result.errors));
+    let query_id = result
+        .extensions
+        .and_then(|extensions| extensions.query_id)
+        .expect("queries are given an ID");
+
+    // Both the executor and the resolver log with the ID of the query and
+    // the deployment it is made against
+    let executions = drain.records("Execute query");
+    assert_eq!(executions.len(), 1);
+    assert_eq!(executions[0]["query_id"], query_id);
+    assert_eq!(executions[0]["subgraph_id"], "graphqlTestsQueryId");
+
+    let store_queries = drain.records("Query store");
+    assert!(!store_queries.is_empty());
+    for kvs in store_queries {
+        assert_eq!(kvs["query_id"], query_id);
+        assert_eq!(kvs["subgraph_id"], "graphqlTestsQueryId");
+    }
+
+    // The store gets the ID with every query
+    let queries = store.queries();
+    assert!(!queries.is_empty());
+    assert!(queries
+        .iter()
+        .all(|query| query.query_id.as_ref() == Some(&query_id)));
+
+    // Every query gets a new ID
+    let (result, _) = execute_logged_query("{ musicians { name } }", None, &drain);
+    let other_query_id = result
+        .extensions
+        .and_then(|extensions| extensions.query_id)
+        .expect("queries are given an ID");
+    assert_ne!(other_query_id, query_id);
+}
+
+#[test]
+fn queries_keep_an_id_they_already_have() {
+    let drain = CapturingDrain::default();
+    let (result, store) = execute_logged_query(
+        "{ musicians { name } }",
+        Some(String::from("parentQuery")),
+        &drain,
+    );
+
+    assert!(result.errors.is_none(), format!("{:#?}", result.errors));
+    assert_eq!(
+        result.extensions.and_then(|extensions| extensions.query_id),
+        Some(String::from("parentQuery"))
+    );
+    assert_eq!(drain.records("Execute query")[0]["query_id"], "parentQuery");
+    assert!(store
+        .queries()
+        .iter()
+        .all(|query| query.query_id == Some(String::from("parentQuery"))));
+}
+
+/// Resolver that returns `count` musicians with names of `name_len` bytes
+/// for every list field.
+#[derive(Clone)]
+struct LongNamesResolver {
+    count: usize,
+    name_len: usize,
+}
+
+impl Resolver for LongNamesResolver {
+    fn resolve_objects(
+        &self,
+        _ctx: &QueryContext,
+        _parent: &Option<q::Value>,
+        _field: &q::Name,
+        _field_definition: &s::Field,
+        _object_type: ObjectOrInterface<'_>,
+        _arguments: &HashMap<&q::Name, q::Value>,
+        _types_for_interface: &BTreeMap<q::Name, Vec<s::ObjectType>>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        Ok(q::Value::List(
+            (0..self.count)
+                .map(|i| {
+                    object_value(vec![
+                        ("id", q::Value::String(format!("m{}", i))),
+                        ("name", q::Value::String("x".repeat(self.name_len))),
+                    ])
+                })
+                .collect(),
+        ))
+    }
+
+    fn resolve_object(
+        &self,
+        _ctx: &QueryContext,
+        _parent: &Option<q::Value>,
+        _field: &q::Field,
+        _field_definition: &s::Field,
+        _object_type: ObjectOrInterface<'_>,
+        _arguments: &HashMap<&q::Name, q::Value>,
+        _types_for_interface: &BTreeMap<q::Name, Vec<s::ObjectType>>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        Ok(q::Value::Null)
+    }
+}
+
+fn execute_long_names_query(name_len: usize, max_response_size: usize) -> QueryResult {
+    let id = SubgraphDeploymentId::new("graphqlTestsResponseSize").unwrap();
+    let query = new_query(
+        api_test_schema_for(id),
+        "query { musicians(first: 10) { id name } }",
+    );
+    let options = QueryExecutionOptions {
+        max_response_size: Some(max_response_size),
+        ..default_options(LongNamesResolver {
+            count: 10,
+            name_len,
+        })
+    };
+    execute_query(&query, options)
+}
+
+fn musicians_in(result: &QueryResult) -> usize {
+    match &result.data {
+        Some(q::Value::Object(data)) => match data.get("musicians") {
+            Some(q::Value::List(musicians)) => musicians.len(),
+            musicians => panic!("unexpected musicians: {:?}", musicians),
+        },
+        data => panic!("unexpected data: {:?}", data),
+    }
+}
+
+#[test]
+fn too_large_response_returns_partial_data_and_error() {
+    let result = execute_long_names_query(1_000, 2_500);
+
+    // Some musicians fit within the limit, but not all of them
+    let musicians = musicians_in(&result);
+    assert!(musicians > 0 && musicians < 10, "{} musicians", musicians);
+
+    match &result.errors.expect("too large response must fail")[..] {
+        [QueryError::ExecutionError(QueryExecutionError::ResponseTooLarge { bytes, max })] => {
+            assert_eq!(*max, 2_500);
+            assert!(*bytes > 2_500);
+        }
+        errors => panic!("unexpected errors: {:?}", errors),
+    }
+}
+
+#[test]
+fn response_within_size_limit_succeeds() {
+    let result = execute_long_names_query(10, 2_500);
+
+    assert!(result.errors.is_none(), format!("{:#?}", result.errors));
+    assert_eq!(musicians_in(&result), 10);
+}
+
+/// Runs `query` with at most three fields per selection set.
+fn execute_many_fields_query(query: &str) -> QueryResult {
+    let id = SubgraphDeploymentId::new("graphqlTestsFieldsPerSelection").unwrap();
+    let query = new_query(api_test_schema_for(id), query);
+    let options = QueryExecutionOptions {
+        max_fields_per_selection: Some(3),
+        ..default_options(LongNamesResolver {
+            count: 2,
+            name_len: 5,
+        })
+    };
+    execute_query(&query, options)
+}
+
+#[test]
+fn selection_sets_within_field_limit_succeed() {
+    let result = execute_many_fields_query(
+        "
+        query {
+          musicians { id ...MusicianFields name }
+        }
+
+        fragment MusicianFields on Musician {
+          otherId: id
+          name
+        }
+        ",
+    );
+
+    assert!(result.errors.is_none(), format!("{:#?}", result.errors));
+    assert_eq!(musicians_in(&result), 2);
+}
+
+#[test]
+fn selection_sets_with_too_many_fields_fail() {
+    let result = execute_many_fields_query(
+        "
+        query {
+          musicians { id ...MusicianFields name }
+        }
+
+        fragment MusicianFields on Musician {
+          otherId: id
+          otherName: name
+        }
+        ",
+    );
+
+    assert!(result.data.is_none());
+    match &result.errors.expect("too many fields must fail")[..] {
+        [QueryError::ExecutionError(QueryExecutionError::TooManyFields { found, max })] => {
+            assert_eq!(*found, 4);
+            assert_eq!(*max, 3);
+        }
+        errors => panic!("unexpected errors: {:?}", errors),
+    }
+}
+
+/// Runs `query` against a mock store with the entities of the type
+/// `Account`, which were stored as `User` before the type was renamed.
+fn execute_renamed_type_query(query: &str) -> QueryResult {
+    let id = SubgraphDeploymentId::new("graphqlTestsRenamedType").unwrap();
+    let schema = Schema::parse(
+        r#"
+            interface Owner {
+                id: ID!
+                name: String!
+            }
+
+            type Account implements Owner @entity(name: "User") {
+                id: ID!
+                name: String!
+            }
+
+            type MemeToken @entity {
+                id: ID!
+                owner: Account!
+            }
+        "#,
+        id.clone(),
+    )
+    .expect("Test schema invalid");
+    let logger = Logger::root(slog::Discard, o!());
+    let schema =
+        Schema::api(&logger, schema, Pluralization::Legacy).expect("Failed to derive API schema");
+
+    // Like the real store, the mock store returns entities with the
+    // `__typename` they were stored as
+    let store = graph_mock::MockStore::new(vec![]);
+    let entities = vec![
+        (
+            "User",
+            vec![
+                ("__typename", Value::from("User")),
+                ("id", Value::from("u1")),
+                ("name", Value::from("Alice")),
+            ],
+        ),
+        (
+            "User",
+            vec![
+                ("__typename", Value::from("User")),
+                ("id", Value::from("u2")),
+                ("name", Value::from("Bob")),
+            ],
+        ),
+        (
+            "MemeToken",
+            vec![
+                ("__typename", Value::from("MemeToken")),
+                ("id", Value::from("t1")),
+                ("owner", Value::from("u2")),
+            ],
+        ),
+    ];
+    for (entity_type, data) in entities {
+        let entity = Entity::from(data);
+        store.set(
+            EntityKey {
+                subgraph_id: id.clone(),
+                entity_type: entity_type.to_owned(),
+                entity_id: entity.id().unwrap(),
+            },
+            entity,
+        );
+    }
+
+    execute_mock_store_query(Arc::new(store), &new_query(schema, query))
+}
+
+#[test]
+fn renamed_types_query_entities_under_their_stored_name() {
+    let result = execute_renamed_type_query(
+        "{
+            accounts(where: { name: \"Alice\" }) { id name }
+            account(id: \"u2\") { name }
+            memeToken(id: \"t1\") { owner { name } }
+            owners(orderBy: id) { __typename name }
+        }",
+    );
+
+    assert!(result.errors.is_none(), format!("{:#?}", result.errors));
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![
+            (
+                "accounts",
+                q::Value::List(vec![object_value(vec![
+                    ("id", q::Value::String(String::from("u1"))),
+                    ("name", q::Value::String(String::from("Alice"))),
+                ])])
+            ),
+            (
+                "account",
+                object_value(vec![("name", q::Value::String(String::from("Bob")))])
+            ),
+            (
+                "memeToken",
+                object_value(vec![(
+                    "owner",
+                    object_value(vec![("name", q::Value::String(String::from("Bob")))])
+                )])
+            ),
+            (
+                "owners",
+                q::Value::List(vec![
+                    object_value(vec![
+                        ("__typename", q::Value::String(String::from("Account"))),
+                        ("name", q::Value::String(String::from("Alice"))),
+                    ]),
+                    object_value(vec![
+                        ("__typename", q::Value::String(String::from("Account"))),
+                        ("name", q::Value::String(String::from("Bob"))),
+                    ]),
+                ])
+            ),
+        ]))
+    );
+}
+
+#[test]
+fn renamed_types_are_introspected_under_their_graphql_name() {
+    let result = execute_renamed_type_query(
+        "{
+            account: __type(name: \"Account\") { name }
+            user: __type(name: \"User\") { name }
+        }",
+    );
+
+    assert!(result.errors.is_none(), format!("{:#?}", result.errors));
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![
+            (
+                "account",
+                object_value(vec![("name", q::Value::String(String::from("Account")))])
+            ),
+            ("user", q::Value::Null),
+        ]))
+    );
+}
+
+fn execute_meta_query(store: graph_mock::MockStore, query: &str) -> QueryResult {
+    let id = SubgraphDeploymentId::new("graphqlTestsMeta").unwrap();
+    execute_mock_store_query(Arc::new(store), &new_query(api_test_schema_for(id), query))
+}
+
+#[test]
+fn meta_reports_block_and_deployment() {
+    let id = SubgraphDeploymentId::new("graphqlTestsMeta").unwrap();
+    let store = graph_mock::MockStore::new(vec![]);
+    store.set_block_ptr(
+        id.clone(),
+        EthereumBlockPointer {
+            hash: web3::types::H256::from_low_u64_be(42),
+            number: 42,
+        },
+    );
+
+    let result = execute_meta_query(
+        store,
+        "{ _meta { block { number hash } deployment hasIndexingErrors } }",
+    );
+
+    assert!(result.errors.is_none(), format!("{:#?}", result.errors));
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![(
+            "_meta",
+            object_value(vec![
+                (
+                    "block",
+                    object_value(vec![
+                        ("number", q::Value::Int(q::Number::from(42))),
+                        (
+                            "hash",
+                            q::Value::String(format!(
+                                "0x{:x}",
+                                web3::types::H256::from_low_u64_be(42)
+                            ))
+                        ),
+                    ])
+                ),
+                ("deployment", q::Value::String(id.to_string())),
+                ("hasIndexingErrors", q::Value::Boolean(false)),
+            ])
+        )]))
+    );
+}
+
+#[test]
+fn fields_of_a_query_share_the_block_of_the_deployment() {
+    let id = SubgraphDeploymentId::new("graphqlTestsMeta").unwrap();
+    let store = Arc::new(graph_mock::MockStore::new(vec![]));
+    store.set_block_ptr(
+        id.clone(),
+        EthereumBlockPointer {
+            hash: web3::types::H256::from_low_u64_be(42),
+            number: 42,
+        },
+    );
+
+    let result = execute_mock_store_query(
+        store.clone(),
+        &new_query(
+            api_test_schema_for(id),
+            "{ a: _meta { block { number } } b: _meta { block { number } } }",
+        ),
+    );
+
+    assert!(result.errors.is_none(), format!("{:#?}", result.errors));
+    let block = object_value(vec![(
+        "block",
+        object_value(vec![("number", q::Value::Int(q::Number::from(42)))]),
+    )]);
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![("a", block.clone()), ("b", block)]))
+    );
+    assert_eq!(store.block_ptr_lookups(), 1);
+}
+
+#[test]
+fn meta_reports_indexing_errors_of_failed_deployments() {
+    let id = SubgraphDeploymentId::new("graphqlTestsMeta").unwrap();
+    let store = graph_mock::MockStore::new(vec![]);
+    store.set(
+        SubgraphDeploymentEntity::key(id.clone()),
+        Entity::from(vec![
+            ("id", Value::from(id.to_string())),
+            ("failed", Value::from(true)),
+        ]),
+    );
+
+    // The deployment hasn't processed any blocks; querying `_meta` doesn't
+    // fail even though the deployment has
+    let result = execute_meta_query(store, "{ _meta { block { number } hasIndexingErrors } }");
+
+    assert!(result.errors.is_none(), format!("{:#?}", result.errors));
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![(
+            "_meta",
+            object_value(vec![
+                ("block", q::Value::Null),
+                ("hasIndexingErrors", q::Value::Boolean(true)),
+            ])
+        )]))
+    );
+}
+
+#[test]
+fn resolvers_share_the_health_of_deployments_across_queries() {
+    let id = SubgraphDeploymentId::new("graphqlTestsMeta").unwrap();
+    let store = Arc::new(graph_mock::MockStore::new(vec![]));
+    store.set(
+        SubgraphDeploymentEntity::key(id.clone()),
+        Entity::from(vec![
+            ("id", Value::from(id.to_string())),
+            ("failed", Value::from(true)),
+            ("fatalError", Value::from("mapping aborted")),
+        ]),
+    );
+
+    let logger = Logger::root(slog::Discard, o!());
+    let resolver = StoreResolver::new(&logger, store.clone());
+    let query = new_query(api_test_schema_for(id), "{ musicians { id } }");
+    for _ in 0..2 {
+        let result = execute_query(&query, default_options(resolver.clone()));
+        match result
+            .errors
+            .expect("failed deployment must return an error")
+            .as_slice()
+        {
+            [QueryError::ExecutionError(QueryExecutionError::SubgraphFailed { message, .. })] => {
+                assert_eq!(message, "mapping aborted")
+            }
+            errors => panic!("unexpected errors: {:?}", errors),
+        }
+    }
+
+    // The failure is only looked up for the first query
+    assert_eq!(store.entity_reads(), 1);
+}
+
+/// Runs `query` with the `syncWarning` extension set against a deployment
+/// whose block stream reports that it has processed `latest_block` and is
+/// `blocks_behind` the chain head, at `blocks_per_second`.
+fn execute_sync_warning_query(
+    query: &str,
+    latest_block: u64,
+    blocks_behind: u64,
+    blocks_per_second: f64,
+) -> QueryResult {
+    let id = SubgraphDeploymentId::new("graphqlTestsSyncWarning").unwrap();
+    let logger = Logger::root(slog::Discard, o!());
+    let registry = Arc::new(graph_mock::MockMetricsRegistry::new());
+    let sync_statuses = Arc::new(DeploymentSyncStatuses::new());
+    let metrics = BlockStreamMetrics::new(
+        registry.clone(),
+        Arc::new(SubgraphEthRpcMetrics::new(registry.clone(), id.to_string())),
+        id.clone(),
+        String::from("mainnet"),
+        StopwatchMetrics::new(logger.clone(), id.clone(), registry),
+    )
+    .with_sync_statuses(sync_statuses.clone());
+    metrics.latest_block_number.set(latest_block as f64);
+    metrics.blocks_behind.set(blocks_behind as f64);
+    metrics.blocks_per_second.set(blocks_per_second);
+
+    let options = default_options(
+        StoreResolver::new(&logger, Arc::new(graph_mock::MockStore::new(vec![])))
+            .with_sync_statuses(sync_statuses),
+    );
+    let query = Query {
+        sync_warning: true,
+        ..new_query(api_test_schema_for(id), query)
+    };
+    execute_query(&query, options)
+}
+
+#[test]
+fn deployments_far_behind_the_chain_head_are_reported() {
+    let result = execute_sync_warning_query(
+        "{ musicians { id } _meta { chainHeadBlockNumber estimatedSyncSeconds } }",
+        1_200_000,
+        16_800_000,
+        100.0,
+    );
+
+    // The data is returned along with the warning
+    assert!(result.errors.is_none(), format!("{:#?}", result.errors));
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![
+            ("musicians", q::Value::List(vec![])),
+            (
+                "_meta",
+                object_value(vec![
+                    (
+                        "chainHeadBlockNumber",
+                        q::Value::Int(q::Number::from(18_000_000))
+                    ),
+                    (
+                        "estimatedSyncSeconds",
+                        q::Value::Int(q::Number::from(168_000))
+                    ),
+                ])
+            ),
+        ]))
+    );
+    assert_eq!(
+        result.extensions.and_then(|extensions| extensions.warnings),
+        Some(vec![String::from(
+            "The deployment `graphqlTestsSyncWarning` is still syncing and its data may be \
+             incomplete: it has indexed up to block 1200000 of 18000000 (16800000 blocks \
+             behind) and it is estimated to catch up in 168000 seconds"
+        )])
+    );
+}
+
+#[test]
+fn synced_deployments_are_not_reported() {
+    let result = execute_sync_warning_query(
+        "{ musicians { id } _meta { chainHeadBlockNumber estimatedSyncSeconds } }",
+        18_000_000,
+        2,
+        10.0,
+    );
+
+    assert!(result.errors.is_none(), format!("{:#?}", result.errors));
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![
+            ("musicians", q::Value::List(vec![])),
+            (
+                "_meta",
+                object_value(vec![
+                    (
+                        "chainHeadBlockNumber",
+                        q::Value::Int(q::Number::from(18_000_002))
+                    ),
+                    ("estimatedSyncSeconds", q::Value::Int(q::Number::from(1))),
+                ])
+            ),
+        ]))
+    );
+    assert_eq!(
+        result.extensions.and_then(|extensions| extensions.warnings),
+        None
+    );
+}
+
+#[test]
+fn can_order_by_fields_of_referenced_entities() {
+    let id = SubgraphDeploymentId::new("graphqlTestsOrderByChild").unwrap();
+    let store = graph_mock::MockStore::new(vec![]);
+    let entities = vec![
+        (
+            "Band",
+            vec![
+                ("id", Value::from("b1")),
+                ("name", Value::from("The Musicians")),
+            ],
+        ),
+        (
+            "Band",
+            vec![
+                ("id", Value::from("b2")),
+                ("name", Value::from("The Amateurs")),
+            ],
+        ),
+        (
+            "Musician",
+            vec![("id", Value::from("m1")), ("mainBand", Value::from("b1"))],
+        ),
+        (
+            "Musician",
+            vec![("id", Value::from("m2")), ("mainBand", Value::from("b2"))],
+        ),
+        // `mainBand` is unset
+        ("Musician", vec![("id", Value::from("m3"))]),
+        (
+            "Musician",
+            vec![("id", Value::from("m4")), ("mainBand", Value::from("b1"))],
+        ),
+    ];
+    for (entity_type, data) in entities {
+        let entity = Entity::from(data);
+        store.set(
+            EntityKey {
+                subgraph_id: id.clone(),
+                entity_type: entity_type.to_owned(),
+                entity_id: entity.id().unwrap(),
+            },
+            entity,
+        );
+    }
+
+    let query = new_query(
+        api_test_schema_for(id),
+        "{ musicians(orderBy: mainBand__name, orderDirection: desc) { id } }",
+    );
+    let result = execute_mock_store_query(Arc::new(store), &query);
+
+    // Musicians in the same band are ordered by ID, musicians without a
+    // band come last
+    assert!(result.errors.is_none(), format!("{:#?}", result.errors));
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![(
+            "musicians",
+            q::Value::List(
+                vec!["m1", "m4", "m2", "m3"]
+                    .into_iter()
+                    .map(|id| object_value(vec![("id", q::Value::String(id.to_owned()))]))
+                    .collect()
+            )
+        )]))
+    );
+}
+
+#[test]
+fn queries_only_load_selected_attributes() {
+    let id = SubgraphDeploymentId::new("graphqlTestsSelectedAttributes").unwrap();
+    let store = Arc::new(graph_mock::MockStore::new(vec![]));
+    let entity = Entity::from(vec![
+        ("id", Value::from("m1")),
+        ("name", Value::from("John")),
+        ("mainBand", Value::from("b1")),
+        ("bands", Value::List(vec![Value::from("b1")])),
+    ]);
+    store.set(
+        EntityKey {
+            subgraph_id: id.clone(),
+            entity_type: "Musician".to_owned(),
+            entity_id: entity.id().unwrap(),
+        },
+        entity,
+    );
+
+    let query = new_query(
+        api_test_schema_for(id),
+        "{ musicians { name ... on Musician { __typename writtenSongs { id } } } }",
+    );
+    let result = execute_mock_store_query(store.clone(), &query);
+
+    assert!(result.errors.is_none(), format!("{:#?}", result.errors));
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![(
+            "musicians",
+            q::Value::List(vec![object_value(vec![
+                ("name", q::Value::String("John".to_owned())),
+                ("__typename", q::Value::String("Musician".to_owned())),
+                ("writtenSongs", q::Value::List(vec![])),
+            ])])
+        )]))
+    );
+
+    // Derived fields, introspection fields and fields that were not
+    // selected are not loaded from the store
+    let queries = store.queries();
+    assert_eq!(
+        queries[0].select,
+        Some(
+            vec!["id", "name"]
+                .into_iter()
+                .map(String::from)
+                .collect::<BTreeSet<_>>()
+        )
+    );
+}
+
+/// Runs `query` against a mock store with the musicians `m00` to `m19`,
+/// splitting `_in` filters with more than `chunk_size` values. Returns the
+/// result and the number of queries the store received.
+fn execute_in_list_query(
+    query: &str,
+    max_in_list_length: usize,
+    chunk_size: usize,
+    max_complexity: Option<u64>,
+) -> (QueryResult, usize) {
+    let id = SubgraphDeploymentId::new("graphqlTestsInLists").unwrap();
+    let store = Arc::new(graph_mock::MockStore::new(vec![]));
+    for i in 0..20 {
+        let entity = Entity::from(vec![
+            ("id", Value::from(format!("m{:02}", i))),
+            ("name", Value::from(format!("Musician {}", i))),
+        ]);
+        store.set(
+            EntityKey {
+                subgraph_id: id.clone(),
+                entity_type: "Musician".to_owned(),
+                entity_id: entity.id().unwrap(),
+            },
+            entity,
+        );
+    }
+
+    let logger = Logger::root(slog::Discard, o!());
+    let mut context = QueryContext::new(logger.clone(), None, std::u32::MAX);
+    context.max_in_list_length = max_in_list_length;
+    context.in_list_chunk_size = chunk_size;
+    let options = QueryExecutionOptions {
+        context,
+        max_complexity,
+        ..default_options(StoreResolver::new(&logger, store.clone()))
+    };
+    let query = new_query(api_test_schema_for(id), query);
+    let result = execute_query(&query, options);
+    (result, store.queries().len())
+}
+
+fn musician_ids(result: &QueryResult) -> Vec<String> {
+    match &result.data {
+        Some(q::Value::Object(data)) => match data.get("musicians") {
+            Some(q::Value::List(musicians)) => musicians
+                .iter()
+                .map(|musician| match musician {
+                    q::Value::Object(musician) => match musician.get("id") {
+                        Some(q::Value::String(id)) => id.clone(),
+                        id => panic!("unexpected id: {:?}", id),
+                    },
+                    musician => panic!("unexpected musician: {:?}", musician),
+                })
+                .collect(),
+            musicians => panic!("unexpected musicians: {:?}", musicians),
+        },
+        data => panic!("unexpected data: {:?}", data),
+    }
+}
+
+#[test]
+fn in_filters_with_too_many_values_are_rejected() {
+    let (result, queries) = execute_in_list_query(
+        r#"{ musicians(where: { id_in: ["m01", "m02", "m03", "m04"] }) { id } }"#,
+        3,
+        1000,
+        None,
+    );
+
+    assert_eq!(queries, 0);
+    match &result.errors.expect("too long list must fail")[..] {
+        [QueryError::ExecutionError(QueryExecutionError::ListTooLong {
+            filter,
+            length,
+            max,
+        })] => {
+            assert_eq!(filter, "id_in");
+            assert_eq!(*length, 4);
+            assert_eq!(*max, 3);
+        }
+        errors => panic!("unexpected errors: {:?}", errors),
+    }
+}
+
+#[test]
+fn in_filters_are_split_into_chunks() {
+    let query = r#"{
+        musicians(orderBy: id, where: { id_in: ["m13", "m02", "m07", "m11", "m05", "m17", "m02"] }) {
+            id
+            name
+        }
+    }"#;
+
+    let (single, single_queries) = execute_in_list_query(query, 1000, 1000, None);
+    let (chunked, chunked_queries) = execute_in_list_query(query, 1000, 2, None);
+
+    // The duplicate value is dropped before splitting the list, and the
+    // entities found for each chunk are merged without another query
+    assert_eq!(single_queries, 1);
+    assert_eq!(chunked_queries, 3);
+    assert!(chunked.errors.is_none(), format!("{:#?}", chunked.errors));
+    assert_eq!(chunked.data, single.data);
+    assert_eq!(
+        musician_ids(&chunked),
+        vec!["m02", "m05", "m07", "m11", "m13", "m17"]
+    );
+}
+
+#[test]
+fn chunked_in_filters_respect_the_range() {
+    let (result, queries) = execute_in_list_query(
+        r#"{
+            musicians(first: 3, skip: 1, orderBy: id, where: { id_in: ["m13", "m02", "m07", "m11", "m05", "m17"] }) {
+                id
+            }
+        }"#,
+        1000,
+        2,
+        None,
+    );
+
+    assert_eq!(queries, 3);
+    assert!(result.errors.is_none(), format!("{:#?}", result.errors));
+    assert_eq!(musician_ids(&result), vec!["m05", "m07", "m11"]);
+}
+
+#[test]
+fn chunked_in_filters_on_other_attributes_are_ordered_like_a_single_query() {
+    let query = r#"{
+        musicians(first: 4, orderBy: name, orderDirection: desc, where: {
+            name_in: ["Musician 3", "Musician 14", "Musician 8", "Musician 1", "Musician 12"]
+        }) {
+            id
+            name
+        }
+    }"#;
+
+    let (single, _) = execute_in_list_query(query, 1000, 1000, None);
+    let (chunked, chunked_queries) = execute_in_list_query(query, 1000, 2, None);
+
+    assert_eq!(chunked_queries, 3);
+    assert!(chunked.errors.is_none(), format!("{:#?}", chunked.errors));
+    assert_eq!(chunked.data, single.data);
+    assert_eq!(musician_ids(&chunked), vec!["m08", "m03", "m14", "m12"]);
+}
+
+#[test]
+fn in_filters_add_to_query_complexity() {
+    let (result, _) = execute_in_list_query(
+        r#"{ musicians(first: 1, where: { id_in: ["m01", "m02", "m03"] }) { id } }"#,
+        1000,
+        1000,
+        Some(1),
+    );
+
+    // One entity plus three values in the list
+    match &result.errors.expect("query must be too complex")[..] {
+        [QueryError::ExecutionError(QueryExecutionError::TooComplex(4, 1))] => (),
+        errors => panic!("unexpected errors: {:?}", errors),
+    }
+}
+
+/// Streams the result of `query` against a mock store with `count`
+/// musicians in batches of `batch_size`, limiting the response to
+/// `max_response_size`. Returns the parsed response and the store.
+fn execute_streamed_query(
+    query: &str,
+    count: usize,
+    batch_size: usize,
+    max_response_size: Option<usize>,
+) -> (serde_json::Value, Arc<graph_mock::MockStore>) {
+    let id = SubgraphDeploymentId::new("graphqlTestsStreaming").unwrap();
+    let store = Arc::new(graph_mock::MockStore::new(vec![]));
+    for i in 0..count {
+        let entity = Entity::from(vec![
+            ("id", Value::from(format!("m{:05}", i))),
+            ("name", Value::from(format!("Musician {}", i))),
+            ("bands", Value::List(vec![])),
+        ]);
+        store.set(
+            EntityKey {
+                subgraph_id: id.clone(),
+                entity_type: "Musician".to_owned(),
+                entity_id: entity.id().unwrap(),
+            },
+            entity,
+        );
+    }
+    store.set_block_ptr(
+        id.clone(),
+        EthereumBlockPointer {
+            hash: web3::types::H256::from_low_u64_be(42),
+            number: 42,
+        },
+    );
+
+    let logger = Logger::root(slog::Discard, o!());
+    let mut context = QueryContext::new(logger.clone(), None, std::u32::MAX);
+    context.stream_batch_size = Some(batch_size);
+    let options = QueryExecutionOptions {
+        context,
+        max_response_size,
+        ..default_options(StoreResolver::new(&logger, store.clone()))
+    };
+    let query = new_query(api_test_schema_for(id), query);
+
+    let mut response = Vec::new();
+    execute_query_to_writer(&query, options, &mut response).expect("failed to write response");
+    let response = serde_json::from_slice(&response).expect("response is not valid JSON");
+    (response, store)
+}
+
+fn streamed_musician_ids(response: &serde_json::Value) -> Vec<String> {
+    response["data"]["musicians"]
+        .as_array()
+        .expect("musicians must be a list")
+        .iter()
+        .map(|musician| {
+            musician["id"]
+                .as_str()
+                .expect("id must be a string")
+                .to_owned()
+        })
+        .collect()
+}
+
+#[test]
+fn large_collections_are_streamed_in_batches() {
+    let (response, store) = execute_streamed_query("{ musicians { id name } }", 10000, 1000, None);
+
+    assert_eq!(response.get("errors"), None);
+    assert_eq!(response["extensions"].get("warnings"), None);
+    let ids = streamed_musician_ids(&response);
+    assert_eq!(ids.len(), 10000);
+    assert_eq!(
+        ids,
+        (0..10000).map(|i| format!("m{:05}", i)).collect::<Vec<_>>()
+    );
+    assert_eq!(response["data"]["musicians"][42]["name"], "Musician 42");
+
+    // Ten full batches, plus one that finds there is nothing left; no
+    // query loads more than one batch, and all of them load the entities
+    // at the same block
+    let queries = store.queries();
+    assert_eq!(queries.len(), 11);
+    assert!(queries
+        .iter()
+        .all(|query| query.range == EntityRange::first(1000) && query.block == Some(42)));
+}
+
+#[test]
+fn streaming_stops_once_the_response_is_too_large() {
+    let (response, store) =
+        execute_streamed_query("{ musicians { id name } }", 10000, 1000, Some(50000));
+
+    // The batch that crosses the limit is written, cut short like other
+    // responses that are too large, and no later batches are loaded
+    let musicians = response["data"]["musicians"]
+        .as_array()
+        .expect("musicians must be a list");
+    assert_eq!(musicians.len(), 2000);
+    assert_eq!(musicians[0]["id"], "m00000");
+    assert_eq!(store.queries().len(), 2);
+    match response["errors"].as_array() {
+        Some(errors) => {
+            assert_eq!(errors.len(), 1);
+            assert!(errors[0]["message"]
+                .as_str()
+                .unwrap()
+                .starts_with("query response of about"));
+        }
+        None => panic!("missing errors: {}", response),
+    }
+}
+
+#[test]
+fn queries_that_cannot_be_streamed_fall_back_with_a_warning() {
+    let (response, store) =
+        execute_streamed_query("{ musicians(first: 5, orderBy: id) { id } }", 20, 3, None);
+
+    assert_eq!(response.get("errors"), None);
+    assert_eq!(
+        streamed_musician_ids(&response),
+        vec!["m00000", "m00001", "m00002", "m00003", "m00004"]
+    );
+    assert_eq!(store.queries().len(), 1);
+    match response["extensions"]["warnings"].as_array() {
+        Some(warnings) => {
+            assert_eq!(warnings.len(), 1);
+            assert!(warnings[0]
+                .as_str()
+                .unwrap()
+                .starts_with("The query was not streamed"));
+        }
+        None => panic!("missing warnings: {}", response),
+    }
+}
+
+/// Resolver that returns a single object with the values of built-in
+/// scalars in non-canonical forms, and leaves them to the default
+/// `resolve_scalar_value`, which serializes them if `canonical` is set.
+#[derive(Clone)]
+struct RawScalarsResolver {
+    canonical: bool,
+}
+
+impl Resolver for RawScalarsResolver {
+    fn serializes_scalars(&self) -> bool {
+        self.canonical
+    }
+
+    fn resolve_objects(
+        &self,
+        _ctx: &QueryContext,
+        _parent: &Option<q::Value>,
+        _field: &q::Name,
+        _field_definition: &s::Field,
+        _object_type: ObjectOrInterface<'_>,
+        _arguments: &HashMap<&q::Name, q::Value>,
+        _types_for_interface: &BTreeMap<q::Name, Vec<s::ObjectType>>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        Ok(q::Value::List(vec![object_value(vec![
+            ("id", q::Value::Int(7.into())),
+            ("name", q::Value::String("Seven".to_owned())),
+            ("active", q::Value::Boolean(true)),
+            ("count", q::Value::String("42".to_owned())),
+            ("amount", q::Value::Int(12345.into())),
+            ("price", q::Value::String("1.5".to_owned())),
+            ("data", q::Value::String("0xDEADbeef".to_owned())),
+            (
+                "amounts",
+                q::Value::List(vec![
+                    q::Value::Int(1.into()),
+                    q::Value::String("2".to_owned()),
+                ]),
+            ),
+        ])]))
+    }
+
+    fn resolve_object(
+        &self,
+        _ctx: &QueryContext,
+        _parent: &Option<q::Value>,
+        _field: &q::Field,
+        _field_definition: &s::Field,
+        _object_type: ObjectOrInterface<'_>,
+        _arguments: &HashMap<&q::Name, q::Value>,
+        _types_for_interface: &BTreeMap<q::Name, Vec<s::ObjectType>>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        Ok(q::Value::Null)
+    }
+}
+
+/// Runs `query` against `RawScalarsResolver`.
+fn execute_raw_scalars_query(query: &str, canonical: bool) -> QueryResult {
+    let id = SubgraphDeploymentId::new("graphqlTestsScalars").unwrap();
+    let schema = Schema::parse(
+        "
+            type Thing @entity {
+                id: ID!
+                name: String!
+                active: Boolean!
+                count: Int!
+                amount: BigInt!
+                price: BigDecimal!
+                data: Bytes!
+                amounts: [BigInt!]!
+            }
+        ",
+        id,
+    )
+    .expect("Test schema invalid");
+    let logger = Logger::root(slog::Discard, o!());
+    let query = new_query(
+        Schema::api(&logger, schema, Pluralization::Legacy).expect("Failed to derive API schema"),
+        query,
+    );
+    execute_query(&query, default_options(RawScalarsResolver { canonical }))
+}
+
+#[test]
+fn built_in_scalars_are_serialized_canonically_if_resolvers_opt_in() {
+    let result = execute_raw_scalars_query(
+        "{ things { id name active count amount price data amounts } }",
+        true,
+    );
+
+    assert!(result.errors.is_none(), format!("{:#?}", result.errors));
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![(
+            "things",
+            q::Value::List(vec![object_value(vec![
+                ("id", q::Value::String("7".to_owned())),
+                ("name", q::Value::String("Seven".to_owned())),
+                ("active", q::Value::Boolean(true)),
+                ("count", q::Value::Int(42.into())),
+                ("amount", q::Value::String("12345".to_owned())),
+                ("price", q::Value::String("1.5".to_owned())),
+                ("data", q::Value::String("0xdeadbeef".to_owned())),
+                (
+                    "amounts",
+                    q::Value::List(vec![
+                        q::Value::String("1".to_owned()),
+                        q::Value::String("2".to_owned())
+                    ])
+                ),
+            ])])
+        )]))
+    );
+}
+
+#[test]
+fn scalars_are_returned_as_resolved_by_default() {
+    let result = execute_raw_scalars_query("{ things { amount data } }", false);
+
+    assert!(result.errors.is_none(), format!("{:#?}", result.errors));
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![(
+            "things",
+            q::Value::List(vec![object_value(vec![
+                ("amount", q::Value::Int(12345.into())),
+                ("data", q::Value::String("0xDEADbeef".to_owned())),
+            ])])
+        )]))
+    );
+}
+
+/// Resolver that records the arguments of the collections it resolves.
+#[derive(Clone, Default)]
+struct ArgumentsResolver {
+    arguments: Arc<Mutex<Vec<BTreeMap<q::Name, q::Value>>>>,
+}
+
+impl Resolver for ArgumentsResolver {
+    fn resolve_objects(
+        &self,
+        _ctx: &QueryContext,
+        _parent: &Option<q::Value>,
+        _field: &q::Name,
+        _field_definition: &s::Field,
+        _object_type: ObjectOrInterface<'_>,
+        arguments: &HashMap<&q::Name, q::Value>,
+        _types_for_interface: &BTreeMap<q::Name, Vec<s::ObjectType>>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        self.arguments.lock().unwrap().push(
+            arguments
+                .iter()
+                .map(|(name, value)| ((*name).clone(), value.clone()))
+                .collect(),
+        );
+        Ok(q::Value::List(vec![]))
+    }
+
+    fn resolve_object(
+        &self,
+        _ctx: &QueryContext,
+        _parent: &Option<q::Value>,
+        _field: &q::Field,
+        _field_definition: &s::Field,
+        _object_type: ObjectOrInterface<'_>,
+        _arguments: &HashMap<&q::Name, q::Value>,
+        _types_for_interface: &BTreeMap<q::Name, Vec<s::ObjectType>>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        Ok(q::Value::Null)
+    }
+}
+
+#[test]
+fn big_int_variables_keep_their_precision() {
+    const TWO_TO_THE_80: &str = "1208925819614629174706176";
+
+    let id = SubgraphDeploymentId::new("graphqlTestsVariables").unwrap();
+    let schema = Schema::parse(
+        "
+            type Thing @entity {
+                id: ID!
+                amount: BigInt!
+            }
+        ",
+        id,
+    )
+    .expect("Test schema invalid");
+    let logger = Logger::root(slog::Discard, o!());
+    let schema = Arc::new(
+        Schema::api(&logger, schema, Pluralization::Legacy).expect("Failed to derive API schema"),
+    );
+
+    // The `where` argument that the resolver receives for the variables
+    let amount_filter = |variables: QueryVariables| {
+        let query = Query {
+            variables: Some(variables),
+            ..new_query(
+                schema.clone(),
+                "query things($amount: BigInt) { things(where: { amount: $amount }) { id } }",
+            )
+        };
+        let resolver = ArgumentsResolver::default();
+        let options = default_options(resolver.clone());
+        let result = execute_query(&query, options);
+        assert!(result.errors.is_none(), format!("{:#?}", result.errors));
+
+        let arguments = resolver.arguments.lock().unwrap();
+        arguments[0].get("where").cloned().expect("missing `where`")
+    };
+    let expected = object_value(vec![(
+        "amount",
+        q::Value::String(String::from(TWO_TO_THE_80)),
+    )]);
+
+    // Variables that are already GraphQL values
+    let typed = QueryVariables::from(HashMap::from_iter(vec![(
+        String::from("amount"),
+        q::Value::String(String::from(TWO_TO_THE_80)),
+    )]));
+    assert_eq!(amount_filter(typed), expected);
+
+    // Variables from a JSON request, with the amount as a number
+    let json: serde_json::Value =
+        serde_json::from_str(&format!(r#"{{ "amount": {} }}"#, TWO_TO_THE_80)).unwrap();
+    let converted = QueryVariables::try_from(json.clone()).unwrap();
+    assert_eq!(amount_filter(converted), expected);
+    let deserialized: QueryVariables = serde_json::from_value(json).unwrap();
+    assert_eq!(amount_filter(deserialized), expected);
+}
+
+fn execute_authorized_query(authorizer: Arc<dyn Authorizer>, query: &str) -> QueryResult {
+    let query = new_query(api_test_schema(), query);
+
+    let logger = Logger::root(slog::Discard, o!());
+    let options = QueryExecutionOptions {
+        authorizer,
+        ..default_options(StoreResolver::new(&logger, STORE.clone()))
+    };
+
+    execute_query(&query, options)
+}
+
+fn unauthorized_fields(errors: &[QueryError]) -> Vec<(String, String)> {
+    errors
+        .iter()
+        .map(|error| match error {
+            QueryError::ExecutionError(QueryExecutionError::Unauthorized(_, type_name, field)) => {
+                (type_name.clone(), field.clone())
+            }
+            e => panic!("expected an unauthorized error, got {}", e),
+        })
+        .collect()
+}
+
+#[test]
+fn authorizer_rejects_denied_fields() {
+    let authorizer = StaticAuthorizer::new().deny_field("Musician", "name");
+    let result = execute_authorized_query(
+        Arc::new(authorizer),
+        "query { musicians(orderBy: id) { id name bands { name } } }",
+    );
+
+    // Nothing is resolved once a field is denied
+    assert!(result.data.is_none());
+    let errors = result.errors.expect("query should fail");
+    assert_eq!(
+        unauthorized_fields(&errors),
+        vec![("Musician".to_owned(), "name".to_owned())]
+    );
+    assert_eq!(
+        errors[0].to_string(),
+        "Access to field `name` of type `Musician` is not authorized"
+    );
+
+    // Other fields of the type can still be selected
+    let result = execute_authorized_query(
+        Arc::new(StaticAuthorizer::new().deny_field("Musician", "name")),
+        "query { musicians(orderBy: id, first: 1) { id } }",
+    );
+    assert!(result.errors.is_none(), format!("{:?}", result.errors));
+}
+
+#[test]
+fn authorizer_rejects_all_fields_of_denied_types() {
+    let authorizer = Arc::new(StaticAuthorizer::new().deny_type("Band"));
+
+    let result = execute_authorized_query(
+        authorizer.clone(),
+        "query { musicians { id mainBand { id name } } }",
+    );
+    assert!(result.data.is_none());
+    assert_eq!(
+        unauthorized_fields(&result.errors.expect("query should fail")),
+        vec![
+            ("Band".to_owned(), "id".to_owned()),
+            ("Band".to_owned(), "name".to_owned())
+        ]
+    );
+
+    // Fragments on the type are checked as well
+    let result =
+        execute_authorized_query(authorizer.clone(), "query { bands { ... on Band { id } } }");
+    assert_eq!(
+        unauthorized_fields(&result.errors.expect("query should fail")),
+        vec![("Band".to_owned(), "id".to_owned())]
+    );
+
+    // The schema can still be introspected
+    let result = execute_authorized_query(
+        authorizer,
+        "query { __type(name: \"Band\") { name fields { name } } }",
+    );
+    assert!(result.errors.is_none(), format!("{:?}", result.errors));
+}
+
+#[test]
+fn authorizer_rejects_filtering_and_ordering_by_denied_fields() {
+    let authorizer = Arc::new(StaticAuthorizer::new().deny_field("Musician", "name"));
+
+    for query in &[
+        "query { musicians(where: { name: \"John\" }) { id } }",
+        "query { musicians(where: { name_starts_with: \"J\" }) { id } }",
+        "query { musicians(orderBy: name) { id } }",
+        "query { bands { members(orderBy: name) { id } } }",
+    ] {
+        let result = execute_authorized_query(authorizer.clone(), query);
+        assert!(result.data.is_none(), "{}", query);
+        assert_eq!(
+            unauthorized_fields(&result.errors.expect("query should fail")),
+            vec![("Musician".to_owned(), "name".to_owned())],
+            "{}",
+            query
+        );
+    }
+
+    // Filtering by other fields of the type is allowed
+    let result = execute_authorized_query(
+        authorizer,
+        "query { musicians(where: { id: \"m1\" }, orderBy: id) { id } }",
+    );
+    assert!(result.errors.is_none(), format!("{:?}", result.errors));
+}
+
+#[test]
+fn allow_all_authorizer_allows_every_field() {
+    let query = "query { musicians(orderBy: id) { id name mainBand { name } } }";
+    let result = execute_authorized_query(Arc::new(AllowAll), query);
+    assert!(result.errors.is_none(), format!("{:?}", result.errors));
+    assert_eq!(
+        result.data,
+        execute_query_document(graphql_parser::parse_query(query).unwrap()).data
+    );
+
+    // A static authorizer that denies nothing allows everything, too
+    let result = execute_authorized_query(Arc::new(StaticAuthorizer::new()), query);
+    assert!(result.errors.is_none(), format!("{:?}", result.errors));
+}
+
+#[test]
+fn authorizer_rejects_subscriptions_with_denied_fields() {
+    let logger = Logger::root(slog::Discard, o!());
+    let query = new_query(api_test_schema(), "subscription { musicians { id name } }");
+    let options = SubscriptionExecutionOptions {
+        logger: logger.clone(),
+        resolver: StoreResolver::new(&logger, STORE.clone()),
+        timeout: None,
+        max_complexity: None,
+        max_depth: 100,
+        max_first: std::u32::MAX,
+        authorizer: Arc::new(StaticAuthorizer::new().deny_field("Musician", "name")),
+    };
+
+    // The subscription is rejected before it subscribes to the store
+    match execute_subscription(&Subscription { query }, options) {
+        Err(SubscriptionError::GraphQLError(errors)) => match errors.as_slice() {
+            [QueryExecutionError::Unauthorized(_, type_name, field)] => {
+                assert_eq!(type_name, "Musician");
+                assert_eq!(field, "name");
+            }
+            errors => panic!("expected an unauthorized error, got {:?}", errors),
+        },
+        Err(e) => panic!("expected an unauthorized error, got {}", e),
+        Ok(_) => panic!("expected the subscription to be rejected"),
+    }
+}
+
+/// Resolver for things whose `Int` fields hold the values of `count` and
+/// `counts` given to it, the way a store returns numbers that don't fit
+/// into an `Int`.
+#[derive(Clone)]
+struct IntsResolver {
+    count: q::Value,
+    counts: Vec<q::Value>,
+}
+
+impl Resolver for IntsResolver {
+    fn serializes_scalars(&self) -> bool {
+        true
+    }
+
+    fn resolve_objects(
+        &self,
+        _ctx: &QueryContext,
+        _parent: &Option<q::Value>,
+        _field: &q::Name,
+        _field_definition: &s::Field,
+        _object_type: ObjectOrInterface<'_>,
+        _arguments: &HashMap<&q::Name, q::Value>,
+        _types_for_interface: &BTreeMap<q::Name, Vec<s::ObjectType>>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        Ok(q::Value::List(vec![object_value(vec![
+            ("id", q::Value::String("t1".to_owned())),
+            ("count", self.count.clone()),
+            ("counts", q::Value::List(self.counts.clone())),
+        ])]))
+    }
+
+    fn resolve_object(
+        &self,
+        _ctx: &QueryContext,
+        _parent: &Option<q::Value>,
+        _field: &q::Field,
+        _field_definition: &s::Field,
+        _object_type: ObjectOrInterface<'_>,
+        _arguments: &HashMap<&q::Name, q::Value>,
+        _types_for_interface: &BTreeMap<q::Name, Vec<s::ObjectType>>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        Ok(q::Value::Null)
+    }
+}
+
+fn execute_ints_query(
+    resolver: IntsResolver,
+    query: &str,
+    variables: Option<QueryVariables>,
+    clamp_int_overflow: bool,
+) -> QueryResult {
+    let id = SubgraphDeploymentId::new("graphqlTestsInts").unwrap();
+    let schema = Schema::parse(
+        "
+            type Thing @entity {
+                id: ID!
+                count: Int
+                counts: [Int!]
+            }
+        ",
+        id,
+    )
+    .expect("Test schema invalid");
+    let logger = Logger::root(slog::Discard, o!());
+    let query = Query {
+        variables,
+        ..new_query(
+            Schema::api(&logger, schema, Pluralization::Legacy)
+                .expect("Failed to derive API schema"),
+            query,
+        )
+    };
+    let mut context = QueryContext::new(logger, None, std::u32::MAX);
+    context.clamp_int_overflow = clamp_int_overflow;
+    let options = QueryExecutionOptions {
+        context,
+        ..default_options(resolver)
+    };
+    execute_query(&query, options)
+}
+
+#[test]
+fn int_fields_within_range_are_resolved() {
+    let resolver = IntsResolver {
+        count: q::Value::String("2147483647".to_owned()),
+        counts: vec![
+            q::Value::Int(i32::min_value().into()),
+            q::Value::Int(7.into()),
+        ],
+    };
+    let result = execute_ints_query(resolver, "{ things { count counts } }", None, false);
+
+    assert!(result.errors.is_none(), format!("{:#?}", result.errors));
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![(
+            "things",
+            q::Value::List(vec![object_value(vec![
+                ("count", q::Value::Int(i32::max_value().into())),
+                (
+                    "counts",
+                    q::Value::List(vec![
+                        q::Value::Int(i32::min_value().into()),
+                        q::Value::Int(7.into())
+                    ])
+                ),
+            ])])
+        )]))
+    );
+}
+
+#[test]
+fn int_fields_out_of_range_fail() {
+    let resolver = IntsResolver {
+        count: q::Value::String("3000000000".to_owned()),
+        counts: vec![
+            q::Value::Int(7.into()),
+            q::Value::String("-3000000000".to_owned()),
+        ],
+    };
+    let result = execute_ints_query(resolver, "{ things { count counts } }", None, false);
+
+    let errors = result.errors.expect("out of range values should fail");
+    let overflows: Vec<_> = errors
+        .iter()
+        .map(|error| match error {
+            QueryError::ExecutionError(QueryExecutionError::IntOverflow {
+                entity,
+                field,
+                value,
+                ..
+            }) => (entity.as_str(), field.as_str(), value.as_str()),
+            e => panic!("expected an overflow error, got {}", e),
+        })
+        .collect();
+    assert_eq!(
+        overflows,
+        vec![
+            ("`Thing` entity `t1`", "count", "3000000000"),
+            ("`Thing` entity `t1`", "counts", "-3000000000"),
+        ]
+    );
+    assert!(errors[0]
+        .to_string()
+        .starts_with("Value `3000000000` of field `count` of `Thing` entity `t1` is out of range"));
+}
+
+#[test]
+fn int_fields_out_of_range_can_be_clamped() {
+    let resolver = IntsResolver {
+        count: q::Value::String("3000000000".to_owned()),
+        counts: vec![q::Value::String("-3000000000".to_owned())],
+    };
+    let result = execute_ints_query(resolver, "{ things { count counts } }", None, true);
+
+    assert!(result.errors.is_none(), format!("{:#?}", result.errors));
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![(
+            "things",
+            q::Value::List(vec![object_value(vec![
+                ("count", q::Value::Int(i32::max_value().into())),
+                (
+                    "counts",
+                    q::Value::List(vec![q::Value::Int(i32::min_value().into())])
+                ),
+            ])])
+        )]))
+    );
+}
+
+#[test]
+fn int_arguments_out_of_range_are_rejected() {
+    let resolver = || IntsResolver {
+        count: q::Value::Int(1.into()),
+        counts: vec![],
+    };
+    let is_invalid_argument = |result: QueryResult| match result.errors.as_ref().map(Vec::as_slice)
+    {
+        Some([QueryError::ExecutionError(QueryExecutionError::InvalidArgumentError(..))]) => true,
+        _ => false,
+    };
+
+    // Literals
+    let result = execute_ints_query(
+        resolver(),
+        "{ things(first: 3000000000) { count } }",
+        None,
+        false,
+    );
+    assert!(is_invalid_argument(result));
+    let result = execute_ints_query(
+        resolver(),
+        "{ things(where: { count_gt: -3000000000 }) { count } }",
+        None,
+        false,
+    );
+    assert!(is_invalid_argument(result));
+
+    // Variables
+    let query = "query things($first: Int) { things(first: $first) { count } }";
+    let variables = |first: &str| {
+        Some(
+            QueryVariables::try_from(
+                serde_json::from_str::<serde_json::Value>(&format!(r#"{{ "first": {} }}"#, first))
+                    .unwrap(),
+            )
+            .unwrap(),
+        )
+    };
+    let result = execute_ints_query(resolver(), query, variables("3000000000"), false);
+    assert!(is_invalid_argument(result));
+    let result = execute_ints_query(resolver(), query, variables("10"), false);
+    assert!(result.errors.is_none(), format!("{:#?}", result.errors));
+}
+
+fn execute_subscription_with<R>(resolver: R) -> Result<SubscriptionResult, SubscriptionError>
+where
+    R: Resolver + 'static,
+{
+    let query = new_query(api_test_schema(), "subscription { musicians { name } }");
+    let options = SubscriptionExecutionOptions {
+        logger: Logger::root(slog::Discard, o!()),
+        resolver,
+        timeout: None,
+        max_complexity: None,
+        max_depth: 100,
+        max_first: std::u32::MAX,
+        authorizer: Arc::new(AllowAll),
+    };
+    execute_subscription(&Subscription { query }, options)
+}
+
+#[test]
+fn subscriptions_fail_early_without_resolver_support() {
+    let resolver = ArgumentsResolver::default();
+    assert!(!resolver.supports_subscriptions());
+
+    match execute_subscription_with(resolver.clone()) {
+        Err(SubscriptionError::GraphQLError(errors)) => match errors.as_slice() {
+            [QueryExecutionError::NotSupported(message)] => {
+                assert_eq!(message, "Subscriptions are not supported for this query")
+            }
+            errors => panic!("expected a single error, got {:?}", errors),
+        },
+        Ok(_) => panic!("expected the subscription to fail"),
+    }
+
+    // Nothing was resolved
+    assert!(resolver.arguments.lock().unwrap().is_empty());
+}
+
+#[test]
+fn subscriptions_proceed_with_resolver_support() {
+    let logger = Logger::root(slog::Discard, o!());
+    let resolver = StoreResolver::new(&logger, STORE.clone());
+    assert!(resolver.supports_subscriptions());
+
+    assert!(execute_subscription_with(resolver).is_ok());
+}
+
+#[test]
+fn mutations_fail_early_without_resolver_support() {
+    let result = execute_query_document(
+        graphql_parser::parse_query("mutation { musicians { name } }").unwrap(),
+    );
+    match result.errors.as_ref().map(Vec::as_slice) {
+        Some([QueryError::ExecutionError(QueryExecutionError::NotSupported(message))]) => {
+            assert_eq!(message, "Mutations are not supported for this query")
+        }
+        errors => panic!("expected a single error, got {:?}", errors),
+    }
+}
+
+/// Resolver for things whose objects are complete, including the `owner`
+/// nested in each of them, and that counts how often the executor calls it
+/// to resolve a field.
+#[derive(Clone)]
+struct CompleteThingsResolver {
+    things: Vec<q::Value>,
+    complete: bool,
+    calls: Arc<AtomicUsize>,
+}
+
+impl CompleteThingsResolver {
+    fn new(things: Vec<q::Value>, complete: bool) -> Self {
+        CompleteThingsResolver {
+            things,
+            complete,
+            calls: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    fn calls(&self) -> usize {
+        self.calls.load(Ordering::SeqCst)
+    }
+}
+
+impl Resolver for CompleteThingsResolver {
+    fn resolve_objects(
+        &self,
+        _ctx: &QueryContext,
+        parent: &Option<q::Value>,
+        field: &q::Name,
+        _field_definition: &s::Field,
+        _object_type: ObjectOrInterface<'_>,
+        arguments: &HashMap<&q::Name, q::Value>,
+        _types_for_interface: &BTreeMap<q::Name, Vec<s::ObjectType>>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        match parent {
+            // Nested lists, like `friends`, are cut to their `first` argument
+            Some(q::Value::Object(parent)) => {
+                self.calls.fetch_add(1, Ordering::SeqCst);
+                let first = match arguments.get(&"first".to_owned()) {
+                    Some(q::Value::Int(first)) => first.as_i64().unwrap() as usize,
+                    _ => std::usize::MAX,
+                };
+                Ok(match parent.get(field) {
+                    Some(q::Value::List(values)) => {
+                        q::Value::List(values.iter().take(first).cloned().collect())
+                    }
+                    _ => q::Value::Null,
+                })
+            }
+            _ => Ok(q::Value::List(self.things.clone())),
+        }
+    }
+
+    fn resolve_object(
+        &self,
+        _ctx: &QueryContext,
+        parent: &Option<q::Value>,
+        field: &q::Field,
+        _field_definition: &s::Field,
+        _object_type: ObjectOrInterface<'_>,
+        _arguments: &HashMap<&q::Name, q::Value>,
+        _types_for_interface: &BTreeMap<q::Name, Vec<s::ObjectType>>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        Ok(match parent {
+            Some(q::Value::Object(parent)) => {
+                parent.get(&field.name).cloned().unwrap_or(q::Value::Null)
+            }
+            _ => q::Value::Null,
+        })
+    }
+
+    fn is_complete_value(
+        &self,
+        _ctx: &QueryContext,
+        _object_type: &s::ObjectType,
+        _value: &q::Value,
+    ) -> bool {
+        self.complete
+    }
+
+    fn resolve_enum_value(
+        &self,
+        _ctx: &QueryContext,
+        _field: &q::Field,
+        _enum_type: &s::EnumType,
+        value: Option<&q::Value>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        Ok(value.cloned().unwrap_or(q::Value::Null))
+    }
+
+    fn resolve_scalar_value(
+        &self,
+        _ctx: &QueryContext,
+        _parent_object_type: &s::ObjectType,
+        _parent: &BTreeMap<String, q::Value>,
+        _field: &q::Field,
+        scalar_type: &s::ScalarType,
+        value: Option<&q::Value>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        Ok(value.map_or(q::Value::Null, |value| {
+            serialize_scalar_value(scalar_type, value)
+        }))
+    }
+
+    fn resolve_scalar_values(
+        &self,
+        _ctx: &QueryContext,
+        _field: &q::Field,
+        _scalar_type: &s::ScalarType,
+        value: Option<&q::Value>,
+    ) -> Result<q::Value, Vec<QueryExecutionError>> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        Ok(value.cloned().unwrap_or(q::Value::Null))
+    }
+}
+
+fn complete_thing(i: usize) -> q::Value {
+    object_value(vec![
+        ("id", q::Value::String(format!("t{}", i))),
+        ("name", q::Value::String(format!("Thing {}", i))),
+        (
+            "kind",
+            q::Value::Enum(if i % 2 == 0 { "Big" } else { "Small" }.to_owned()),
+        ),
+        ("amount", q::Value::String((i * 1000).to_string())),
+        (
+            "tags",
+            q::Value::List(vec![
+                q::Value::String("a".to_owned()),
+                q::Value::String(format!("tag{}", i)),
+            ]),
+        ),
+        (
+            "owner",
+            object_value(vec![
+                ("id", q::Value::String(format!("o{}", i % 10))),
+                ("name", q::Value::String(format!("Owner {}", i % 10))),
+            ]),
+        ),
+    ])
+}
+
+fn execute_complete_things_query(resolver: CompleteThingsResolver) -> QueryResult {
+    execute_things_query(
+        resolver,
+        "{ things { id __typename name kind amount tags owner { id name } } }",
+    )
+}
+
+fn execute_things_query(resolver: CompleteThingsResolver, query: &str) -> QueryResult {
+    let id = SubgraphDeploymentId::new("graphqlTestsCompleteValues").unwrap();
+    let schema = Schema::parse(
+        "
+            enum Kind { Big, Small }
+            type Owner @entity {
+                id: ID!
+                name: String!
+            }
+            type Thing @entity {
+                id: ID!
+                name: String!
+                kind: Kind!
+                amount: BigInt!
+                tags: [String!]!
+                owner: Owner!
+                friends: [Owner!]!
+            }
+        ",
+        id,
+    )
+    .expect("Test schema invalid");
+    let logger = Logger::root(slog::Discard, o!());
+    let query = new_query(
+        Schema::api(&logger, schema, Pluralization::Legacy).expect("Failed to derive API schema"),
+        query,
+    );
+    let options = default_options(resolver);
+    execute_query(&query, options)
+}
+
+#[test]
+fn complete_values_are_not_resolved_field_by_field() {
+    let things: Vec<_> = (0..1000).map(complete_thing).collect();
+    let slow = CompleteThingsResolver::new(things.clone(), false);
+    let fast = CompleteThingsResolver::new(things, true);
+
+    let slow_result = execute_complete_things_query(slow.clone());
+    let fast_result = execute_complete_things_query(fast.clone());
+
+    assert!(
+        slow_result.errors.is_none(),
+        format!("{:#?}", slow_result.errors)
+    );
+    assert!(
+        fast_result.errors.is_none(),
+        format!("{:#?}", fast_result.errors)
+    );
+    assert_eq!(fast_result.data, slow_result.data);
+    match &fast_result.data {
+        Some(q::Value::Object(data)) => match &data["things"] {
+            q::Value::List(things) => assert_eq!(things.len(), 1000),
+            things => panic!("unexpected things: {:?}", things),
+        },
+        data => panic!("unexpected data: {:?}", data),
+    }
+
+    // Three scalars, the tags, the kind, the owner and its two scalars
+    assert_eq!(slow.calls(), 8 * 1000);
+    assert_eq!(fast.calls(), 0);
+}
+
+#[test]
+fn complete_values_are_checked_against_the_schema() {
+    let mut without_name = complete_thing(1);
+    if let q::Value::Object(thing) = &mut without_name {
+        thing.insert("name".to_owned(), q::Value::Null);
+    }
+    let result =
+        execute_complete_things_query(CompleteThingsResolver::new(vec![without_name], true));
+    match result.errors.as_ref().map(|errors| errors.as_slice()) {
+        Some([QueryError::ExecutionError(QueryExecutionError::NonNullError(_, path))]) => {
+            assert!(path.ends_with("name"), "unexpected path: {}", path)
+        }
+        errors => panic!("expected a non-null error, got {:?}", errors),
+    }
+
+    let mut huge = complete_thing(2);
+    if let q::Value::Object(thing) = &mut huge {
+        thing.insert("kind".to_owned(), q::Value::Enum("Huge".to_owned()));
+    }
+    let result = execute_complete_things_query(CompleteThingsResolver::new(vec![huge], true));
+    match result.errors.as_ref().map(|errors| errors.as_slice()) {
+        Some(
+            [QueryError::ExecutionError(QueryExecutionError::EnumCoercionError(
+                _,
+                field,
+                _,
+                enum_type,
+                _,
+            ))],
+        ) => {
+            assert_eq!(field, "kind");
+            assert_eq!(enum_type, "Kind");
+        }
+        errors => panic!("expected an enum coercion error, got {:?}", errors),
+    }
+}
+
+#[test]
+fn complete_values_without_a_field_resolve_it() {
+    let mut without_name = complete_thing(1);
+    if let q::Value::Object(thing) = &mut without_name {
+        thing.remove("name");
+    }
+    let resolver = CompleteThingsResolver::new(vec![without_name], true);
+    let result = execute_things_query(resolver.clone(), "{ things { id name } }");
+
+    // The resolver doesn't know the name either
+    match result.errors.as_ref().map(|errors| errors.as_slice()) {
+        Some([QueryError::ExecutionError(QueryExecutionError::NonNullError(_, path))]) => {
+            assert!(path.ends_with("name"), "unexpected path: {}", path)
+        }
+        errors => panic!("expected a non-null error, got {:?}", errors),
+    }
+    assert_eq!(resolver.calls(), 1);
+}
+
+#[test]
+fn fields_of_complete_values_with_arguments_are_resolved() {
+    let mut with_friends = complete_thing(1);
+    if let q::Value::Object(thing) = &mut with_friends {
+        thing.insert(
+            "friends".to_owned(),
+            q::Value::List(vec![
+                object_value(vec![("id", q::Value::String("o1".to_owned()))]),
+                object_value(vec![("id", q::Value::String("o2".to_owned()))]),
+            ]),
+        );
+    }
+    let resolver = CompleteThingsResolver::new(vec![with_friends], true);
+    let result = execute_things_query(resolver.clone(), "{ things { friends(first: 1) { id } } }");
+
+    assert!(result.errors.is_none(), format!("{:#?}", result.errors));
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![(
+            "things",
+            q::Value::List(vec![object_value(vec![(
+                "friends",
+                q::Value::List(vec![object_value(vec![(
+                    "id",
+                    q::Value::String("o1".to_owned())
+                )])]),
+            )])]),
+        )]))
+    );
+    assert_eq!(resolver.calls(), 1);
+}
+
+/// A resolver that names users after the language of the `@language`
+/// directive on the type they are resolved as.
+#[derive(Clone)]
+struct LanguageResolver;
+
+impl Resolver for LanguageResolver {
+    fn resolve_objects(
+        &self,
+        _ctx: &QueryContext,
+        _parent: &Option<q::Value>,
+        _field: &q::Name,
+        _field_definition: &s::Field,
+        object_type: ObjectOrInterface<'_>,
+        _arguments: &HashMap<&q::Name, q::Value>,
+        _types_for_interface: &BTreeMap<q::Name, Vec<s::ObjectType>>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        let language = object_type
+            .directive_argument::<String>("language", "language")
+            .map_err(|e| {
+                QueryExecutionError::ValueParseError("language".to_owned(), e.to_string())
+            })?
+            .unwrap_or_else(|| String::from("none"));
+        Ok(q::Value::List(vec![object_value(vec![
+            ("id", q::Value::String(String::from("u1"))),
+            ("name", q::Value::String(language)),
+        ])]))
+    }
+
+    fn resolve_object(
+        &self,
+        _ctx: &QueryContext,
+        _parent: &Option<q::Value>,
+        _field: &q::Field,
+        _field_definition: &s::Field,
+        _object_type: ObjectOrInterface<'_>,
+        _arguments: &HashMap<&q::Name, q::Value>,
+        _types_for_interface: &BTreeMap<q::Name, Vec<s::ObjectType>>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        Ok(q::Value::Null)
+    }
+}
+
+#[test]
+fn type_directives_are_readable_by_resolvers() {
+    let id = SubgraphDeploymentId::new("graphqlTestsDirectives").unwrap();
+    let schema = Schema::parse(
+        "
+            directive @language(language: String = \"English\") on OBJECT
+            type User @entity @language(language: \"French\") {
+                id: ID!
+                name: String!
+            }
+            type Account @entity {
+                id: ID!
+                name: String!
+            }
+        ",
+        id,
+    )
+    .expect("Test schema invalid");
+    let logger = Logger::root(slog::Discard, o!());
+    let query = new_query(
+        Schema::api(&logger, schema, Pluralization::Legacy).expect("Failed to derive API schema"),
+        "{ users { id name } accounts { id name } }",
+    );
+    let options = default_options(LanguageResolver);
+    let result = execute_query(&query, options);
+
+    assert!(result.errors.is_none(), format!("{:#?}", result.errors));
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![
+            (
+                "users",
+                q::Value::List(vec![object_value(vec![
+                    ("id", q::Value::String(String::from("u1"))),
+                    ("name", q::Value::String(String::from("French"))),
+                ])])
+            ),
+            (
+                "accounts",
+                q::Value::List(vec![object_value(vec![
+                    ("id", q::Value::String(String::from("u1"))),
+                    ("name", q::Value::String(String::from("none"))),
+                ])])
+            ),
+        ]))
+    );
+}
+
+/// A resolver that records the entities looked up by their ID and how
+/// often it resolved objects otherwise.
+#[derive(Clone, Default)]
+struct EntityLookupResolver {
+    lookups: Arc<Mutex<Vec<(String, String)>>>,
+    object_calls: Arc<AtomicUsize>,
+}
+
+impl Resolver for EntityLookupResolver {
+    fn resolve_objects(
+        &self,
+        _ctx: &QueryContext,
+        _parent: &Option<q::Value>,
+        _field: &q::Name,
+        _field_definition: &s::Field,
+        _object_type: ObjectOrInterface<'_>,
+        _arguments: &HashMap<&q::Name, q::Value>,
+        _types_for_interface: &BTreeMap<q::Name, Vec<s::ObjectType>>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        Ok(q::Value::List(vec![]))
+    }
+
+    fn resolve_object(
+        &self,
+        _ctx: &QueryContext,
+        _parent: &Option<q::Value>,
+        _field: &q::Field,
+        _field_definition: &s::Field,
+        _object_type: ObjectOrInterface<'_>,
+        _arguments: &HashMap<&q::Name, q::Value>,
+        _types_for_interface: &BTreeMap<q::Name, Vec<s::ObjectType>>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        self.object_calls.fetch_add(1, Ordering::SeqCst);
+        Ok(q::Value::Null)
+    }
+
+    fn resolve_entity(
+        &self,
+        _ctx: &QueryContext,
+        _field: &q::Field,
+        _field_definition: &s::Field,
+        object_type: ObjectOrInterface<'_>,
+        id: &str,
+        _arguments: &HashMap<&q::Name, q::Value>,
+        _types_for_interface: &BTreeMap<q::Name, Vec<s::ObjectType>>,
+    ) -> Result<Option<q::Value>, QueryExecutionError> {
+        self.lookups
+            .lock()
+            .unwrap()
+            .push((object_type.name().to_owned(), id.to_owned()));
+        Ok(match id {
+            "u1" => Some(object_value(vec![
+                ("id", q::Value::String(String::from("u1"))),
+                ("name", q::Value::String(String::from("Alice"))),
+            ])),
+            _ => None,
+        })
+    }
+}
+
+#[test]
+fn entities_are_looked_up_by_id() {
+    let id = SubgraphDeploymentId::new("graphqlTestsEntityLookup").unwrap();
+    let schema = Schema::parse(
+        "
+            type User @entity {
+                id: ID!
+                name: String!
+            }
+        ",
+        id,
+    )
+    .expect("Test schema invalid");
+    let logger = Logger::root(slog::Discard, o!());
+    let query = new_query(
+        Schema::api(&logger, schema, Pluralization::Legacy).expect("Failed to derive API schema"),
+        "{ user(id: \"u1\") { id name } missing: user(id: \"u2\") { id } }",
+    );
+    let resolver = EntityLookupResolver::default();
+    let options = default_options(resolver.clone());
+    let result = execute_query(&query, options);
+
+    assert!(result.errors.is_none(), format!("{:#?}", result.errors));
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![
+            (
+                "user",
+                object_value(vec![
+                    ("id", q::Value::String(String::from("u1"))),
+                    ("name", q::Value::String(String::from("Alice"))),
+                ])
+            ),
+            ("missing", q::Value::Null),
+        ]))
+    );
+
+    // Both fields went to the lookup by ID, and neither to `resolve_object`
+    assert_eq!(
+        *resolver.lookups.lock().unwrap(),
+        vec![
+            (String::from("User"), String::from("u1")),
+            (String::from("User"), String::from("u2")),
+        ]
+    );
+    assert_eq!(resolver.object_calls.load(Ordering::SeqCst), 0);
+}
+
+/// Executes `query` with the same `RegEntry` rows for the top-level list
+/// fields `regEntries` and `memes`.
+fn execute_scripted_reg_entry_query(rows: Vec<q::Value>, query: &str) -> QueryResult {
+    let resolver = ScriptedResolver::new()
+        .with_value("Query", "regEntries", q::Value::List(rows.clone()))
+        .with_value("Query", "memes", q::Value::List(rows));
+    execute(
+        complex_api_schema(COMPLEX_SCHEMA),
+        query,
+        default_options(resolver),
+    )
+}
+
+fn scripted_reg_entries() -> Vec<q::Value> {
+    vec![
+        object_value(vec![
+            ("__typename", q::Value::String(String::from("Meme"))),
+            ("id", q::Value::String(String::from("1"))),
+            ("regEntry_address", q::Value::String(String::from("0x1"))),
+            ("meme_title", q::Value::String(String::from("Doge"))),
+        ]),
+        object_value(vec![
+            ("__typename", q::Value::String(String::from("ParamChange"))),
+            ("id", q::Value::String(String::from("2"))),
+            ("regEntry_address", q::Value::String(String::from("0x2"))),
+            ("paramChange_key", q::Value::String(String::from("deposit"))),
+            // Must not leak into the result through a `Meme` fragment
+            ("meme_title", q::Value::String(String::from("stale"))),
+        ]),
+    ]
+}
+
+#[test]
+fn interface_fields_with_inline_fragments_resolve_per_concrete_type() {
+    let result = execute_scripted_reg_entry_query(
+        scripted_reg_entries(),
+        "query {
+          regEntries {
+            __typename
+            regEntry_address
+            ... on Meme { meme_title }
+            ... on ParamChange { paramChange_key }
+          }
+        }",
+    );
+
+    assert!(result.errors.is_none(), format!("{:#?}", result.errors));
+    assert_eq!(
+        result.data.unwrap(),
+        object_value(vec![(
+            "regEntries",
+            q::Value::List(vec![
+                object_value(vec![
+                    ("__typename", q::Value::String(String::from("Meme"))),
+                    ("regEntry_address", q::Value::String(String::from("0x1"))),
+                    ("meme_title", q::Value::String(String::from("Doge"))),
+                ]),
+                object_value(vec![
+                    ("__typename", q::Value::String(String::from("ParamChange"))),
+                    ("regEntry_address", q::Value::String(String::from("0x2"))),
+                    ("paramChange_key", q::Value::String(String::from("deposit"))),
+                ]),
+            ])
+        )])
+    );
+}
+
+#[test]
+fn interface_fields_skip_fragments_on_other_types() {
+    // Fragments on the interface itself apply to all rows, fragments on a
+    // concrete type only to rows of that type
+    let result = execute_scripted_reg_entry_query(
+        scripted_reg_entries(),
+        "query {
+          regEntries {
+            ... on RegEntry { regEntry_address }
+            ... on Meme { id meme_title }
+          }
+        }",
+    );
+
+    assert!(result.errors.is_none(), format!("{:#?}", result.errors));
+    assert_eq!(
+        result.data.unwrap(),
+        object_value(vec![(
+            "regEntries",
+            q::Value::List(vec![
+                object_value(vec![
+                    ("regEntry_address", q::Value::String(String::from("0x1"))),
+                    ("id", q::Value::String(String::from("1"))),
+                    ("meme_title", q::Value::String(String::from("Doge"))),
+                ]),
+                object_value(vec![(
+                    "regEntry_address",
+                    q::Value::String(String::from("0x2"))
+                )]),
+            ])
+        )])
+    );
+}
+
+#[test]
+fn typename_resolves_to_the_concrete_type() {
+    // `__typename` doesn't depend on the resolver setting it on an object
+    let rows = vec![object_value(vec![
+        ("__typename", q::Value::String(String::from("Meme"))),
+        ("id", q::Value::String(String::from("1"))),
+    ])];
+    let result = execute_scripted_reg_entry_query(
+        rows,
+        "query { __typename memes { __typename id } regEntries { __typename } }",
+    );
+
+    assert!(result.errors.is_none(), format!("{:#?}", result.errors));
+    assert_eq!(
+        result.data.unwrap(),
+        object_value(vec![
+            ("__typename", q::Value::String(String::from("Query"))),
+            (
+                "memes",
+                q::Value::List(vec![object_value(vec![
+                    ("__typename", q::Value::String(String::from("Meme"))),
+                    ("id", q::Value::String(String::from("1"))),
+                ])])
+            ),
+            (
+                "regEntries",
+                q::Value::List(vec![object_value(vec![(
+                    "__typename",
+                    q::Value::String(String::from("Meme"))
+                )])])
+            ),
+        ])
+    );
+
+    // Rows of an interface need a `__typename` that implements the interface
+    let rows = vec![object_value(vec![
+        ("__typename", q::Value::String(String::from("Tag"))),
+        ("id", q::Value::String(String::from("1"))),
+    ])];
+    let result = execute_scripted_reg_entry_query(rows, "query { regEntries { id } }");
+
+    match &result.errors.expect("query should fail")[0] {
+        QueryError::ExecutionError(QueryExecutionError::AbstractTypeError(name)) => {
+            assert_eq!(name, "RegEntry")
+        }
+        e => panic!("unexpected error: {}", e),
+    }
+}
@@ -1,13 +1,70 @@
 use graph::components::ethereum::*;
 use graph::prelude::{
-    ethabi, future,
-    web3::types::{Log, H256},
-    Arc, ChainStore, Error, EthereumCallCache, Future, Logger, Stream,
+    ethabi, format_err, future, stream,
+    web3::types::{Address, Log, Transaction, H256},
+    Arc, CancelHandle, ChainStore, Error, EthereumCallCache, Future, Logger, Stream,
 };
 use std::collections::HashSet;
 
+/// An Ethereum adapter for tests. It knows only the logs it is seeded
+/// with; the hash of block `n` is `H256::from_low_u64_be(n)`.
 #[derive(Default)]
-pub struct MockEthereumAdapter {}
+pub struct MockEthereumAdapter {
+    logs: Vec<Log>,
+    head_block_number: u64,
+    unreachable: bool,
+    no_traces: bool,
+    pruned: bool,
+}
+
+impl MockEthereumAdapter {
+    /// Creates an adapter that returns `logs` from `logs_in_block_range`.
+    /// Each log must have a `block_number`.
+    pub fn with_logs(logs: Vec<Log>) -> Self {
+        let logs = logs
+            .into_iter()
+            .map(|mut log| {
+                let number = log.block_number.expect("log without block number");
+                log.block_hash = Some(Self::block_hash(number.as_u64()));
+                log
+            })
+            .collect();
+        MockEthereumAdapter {
+            logs,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the number of the latest block of the adapter.
+    pub fn with_head(mut self, head_block_number: u64) -> Self {
+        self.head_block_number = head_block_number;
+        self
+    }
+
+    /// Makes requests for the latest block fail, as if the Ethereum node
+    /// was unreachable.
+    pub fn unreachable(mut self) -> Self {
+        self.unreachable = true;
+        self
+    }
+
+    /// Makes the adapter report that it can't trace calls.
+    pub fn without_traces(mut self) -> Self {
+        self.no_traces = true;
+        self
+    }
+
+    /// Makes the adapter report that it is not an archive node.
+    pub fn pruned(mut self) -> Self {
+        self.pruned = true;
+        self
+    }
+
+    /// The hash of the block with the given number.
+    pub fn block_hash(number: u64) -> H256 {
+        H256::from_low_u64_be(number)
+    }
+}
 
 impl EthereumAdapter for MockEthereumAdapter {
     fn net_identifiers(
@@ -17,11 +74,41 @@ impl EthereumAdapter for MockEthereumAdapter {
         unimplemented!();
     }
 
+    fn capabilities(&self) -> NodeCapabilities {
+        NodeCapabilities {
+            traces: !self.no_traces,
+        }
+    }
+
+    fn is_archive(&self, _: &Logger) -> Box<dyn Future<Item = bool, Error = Error> + Send> {
+        if self.unreachable {
+            return Box::new(future::err(format_err!("Ethereum node is unreachable")));
+        }
+        Box::new(future::ok(!self.pruned))
+    }
+
+    fn known_archive(&self) -> Option<bool> {
+        if self.unreachable {
+            None
+        } else {
+            Some(!self.pruned)
+        }
+    }
+
     fn latest_block(
         &self,
         _: &Logger,
     ) -> Box<dyn Future<Item = LightEthereumBlock, Error = EthereumAdapterError> + Send> {
-        unimplemented!();
+        if self.unreachable {
+            return Box::new(future::err(EthereumAdapterError::Unknown(format_err!(
+                "Ethereum node is unreachable"
+            ))));
+        }
+        Box::new(future::ok(LightEthereumBlock {
+            hash: Some(Self::block_hash(self.head_block_number)),
+            number: Some(self.head_block_number.into()),
+            ..Default::default()
+        }))
     }
 
     fn load_block(
@@ -43,8 +130,38 @@ impl EthereumAdapter for MockEthereumAdapter {
     fn load_full_block(
         &self,
         _: &Logger,
-        _: LightEthereumBlock,
+        block: LightEthereumBlock,
     ) -> Box<dyn Future<Item = EthereumBlock, Error = EthereumAdapterError> + Send> {
+        // Blocks of the mock adapter have no transactions
+        Box::new(future::ok(EthereumBlock {
+            block,
+            transaction_receipts: vec![],
+        }))
+    }
+
+    fn logs_for_transaction(
+        &self,
+        _: &Logger,
+        _: H256,
+    ) -> Box<dyn Future<Item = Vec<Log>, Error = EthereumAdapterError> + Send> {
+        unimplemented!();
+    }
+
+    fn transaction_by_hash(
+        &self,
+        _: &Logger,
+        _: H256,
+    ) -> Box<dyn Future<Item = Option<Transaction>, Error = EthereumAdapterError> + Send> {
+        unimplemented!();
+    }
+
+    fn transaction_receipts(
+        &self,
+        _: &Logger,
+        _: Vec<H256>,
+        _: bool,
+    ) -> Box<dyn Future<Item = Vec<Option<TransactionReceipt>>, Error = EthereumAdapterError> + Send>
+    {
         unimplemented!();
     }
 
@@ -62,9 +179,9 @@ impl EthereumAdapter for MockEthereumAdapter {
     fn block_hash_by_block_number(
         &self,
         _: &Logger,
-        _: u64,
+        block_number: u64,
     ) -> Box<dyn Future<Item = Option<H256>, Error = Error> + Send> {
-        unimplemented!();
+        Box::new(future::ok(Some(Self::block_hash(block_number))))
     }
 
     fn is_on_main_chain(
@@ -90,11 +207,21 @@ impl EthereumAdapter for MockEthereumAdapter {
         &self,
         _: &Logger,
         _: Arc<SubgraphEthRpcMetrics>,
-        _: u64,
-        _: u64,
-        _: EthereumLogFilter,
+        from: u64,
+        to: u64,
+        log_filter: EthereumLogFilter,
+        _: Option<CancelHandle>,
     ) -> Box<dyn Future<Item = Vec<Log>, Error = Error> + Send> {
-        unimplemented!();
+        Box::new(future::ok(
+            self.logs
+                .iter()
+                .filter(|log| {
+                    let number = log.block_number.unwrap().as_u64();
+                    from <= number && number <= to && log_filter.matches(log, Some(number))
+                })
+                .cloned()
+                .collect(),
+        ))
     }
 
     fn calls_in_block_range(
@@ -108,6 +235,17 @@ impl EthereumAdapter for MockEthereumAdapter {
         unimplemented!();
     }
 
+    fn contracts_created_in_block_range(
+        &self,
+        _: &Logger,
+        _: Arc<SubgraphEthRpcMetrics>,
+        _: u64,
+        _: u64,
+        _: Vec<Address>,
+    ) -> Box<dyn Future<Item = Vec<EthereumContractCreation>, Error = Error> + Send> {
+        unimplemented!();
+    }
+
     fn contract_call(
         &self,
         _: &Logger,
@@ -117,6 +255,17 @@ impl EthereumAdapter for MockEthereumAdapter {
         unimplemented!();
     }
 
+    fn get_storage_at(
+        &self,
+        _: &Logger,
+        _: Arc<SubgraphEthRpcMetrics>,
+        _: Address,
+        _: H256,
+        _: EthereumBlockPointer,
+    ) -> Box<dyn Future<Item = H256, Error = EthereumAdapterError> + Send> {
+        unimplemented!();
+    }
+
     fn triggers_in_block(
         self: Arc<Self>,
         _: Logger,
@@ -135,9 +284,18 @@ impl EthereumAdapter for MockEthereumAdapter {
         &self,
         _: Logger,
         _: Arc<dyn ChainStore>,
-        _: HashSet<H256>,
+        block_hashes: HashSet<H256>,
     ) -> Box<dyn Stream<Item = LightEthereumBlock, Error = Error> + Send> {
-        unimplemented!()
+        let mut blocks: Vec<_> = block_hashes
+            .into_iter()
+            .map(|hash| LightEthereumBlock {
+                hash: Some(hash),
+                number: Some(hash.to_low_u64_be().into()),
+                ..Default::default()
+            })
+            .collect();
+        blocks.sort_by_key(|block| block.number);
+        Box::new(stream::iter_ok(blocks))
     }
 
     fn block_range_to_ptrs(
@@ -145,6 +303,7 @@ impl EthereumAdapter for MockEthereumAdapter {
         _: Logger,
         _: u64,
         _: u64,
+        _: bool,
     ) -> Box<dyn Future<Item = Vec<EthereumBlockPointer>, Error = Error> + Send> {
         unimplemented!()
     }
@@ -1,22 +1,37 @@
 use graph::components::metrics::{
-    Collector, Counter, CounterVec, Gauge, GaugeVec, Histogram, HistogramOpts, HistogramVec, Opts,
-    PrometheusError,
+    Collector, Counter, CounterVec, Gauge, GaugeVec, Histogram, HistogramOpts, HistogramVec,
+    MetricFamily, Opts, PrometheusError, Registry,
 };
 use graph::prelude::MetricsRegistry as MetricsRegistryTrait;
 
 use std::collections::HashMap;
+use std::sync::Arc;
 
-pub struct MockMetricsRegistry {}
+/// A registry that keeps the metrics created through it to itself. Tests can
+/// read their values back with `gather`.
+pub struct MockMetricsRegistry {
+    registry: Arc<Registry>,
+}
 
 impl MockMetricsRegistry {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            registry: Arc::new(Registry::new()),
+        }
+    }
+
+    fn register(&self, metric: Box<dyn Collector>) {
+        // Metrics that clash with ones registered before are not gathered,
+        // which tests don't mind
+        let _ = self.registry.register(metric);
     }
 }
 
 impl Clone for MockMetricsRegistry {
     fn clone(&self) -> Self {
-        Self {}
+        Self {
+            registry: self.registry.clone(),
+        }
     }
 }
 
@@ -29,6 +44,7 @@ impl MetricsRegistryTrait for MockMetricsRegistry {
     ) -> Result<Box<Gauge>, PrometheusError> {
         let opts = Opts::new(name, help).const_labels(const_labels);
         let gauge = Box::new(Gauge::with_opts(opts)?);
+        self.register(gauge.clone());
         Ok(gauge)
     }
 
@@ -48,6 +64,7 @@ impl MetricsRegistryTrait for MockMetricsRegistry {
                 .collect::<Vec<&str>>()
                 .as_slice(),
         )?);
+        self.register(gauges.clone());
         Ok(gauges)
     }
 
@@ -59,6 +76,7 @@ impl MetricsRegistryTrait for MockMetricsRegistry {
     ) -> Result<Box<Counter>, PrometheusError> {
         let opts = Opts::new(name, help).const_labels(const_labels);
         let counter = Box::new(Counter::with_opts(opts)?);
+        self.register(counter.clone());
         Ok(counter)
     }
 
@@ -83,6 +101,7 @@ impl MetricsRegistryTrait for MockMetricsRegistry {
                 .collect::<Vec<&str>>()
                 .as_slice(),
         )?);
+        self.register(counters.clone());
         Ok(counters)
     }
 
@@ -97,6 +116,7 @@ impl MetricsRegistryTrait for MockMetricsRegistry {
             .const_labels(const_labels)
             .buckets(buckets);
         let histogram = Box::new(Histogram::with_opts(opts)?);
+        self.register(histogram.clone());
         Ok(histogram)
     }
 
@@ -120,10 +140,15 @@ impl MetricsRegistryTrait for MockMetricsRegistry {
                 .collect::<Vec<&str>>()
                 .as_slice(),
         )?);
+        self.register(histogram.clone());
         Ok(histogram)
     }
 
-    fn unregister(&self, _: Box<dyn Collector>) {
-        return;
+    fn unregister(&self, metric: Box<dyn Collector>) {
+        let _ = self.registry.unregister(metric);
+    }
+
+    fn gather(&self) -> Vec<MetricFamily> {
+        self.registry.gather()
     }
 }
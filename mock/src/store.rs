@@ -3,12 +3,13 @@ use rand::rngs::OsRng;
 use rand::seq::SliceRandom;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Mutex;
 
 use graph::components::store::*;
 use graph::data::subgraph::schema::*;
 use graph::prelude::*;
-use graph_graphql::prelude::api_schema;
+use graph_graphql::prelude::{ApiSchemaExt, Pluralization};
 use web3::types::H256;
 
 #[derive(Debug)]
@@ -19,6 +20,35 @@ pub struct MockStore {
     entities: Mutex<HashMap<SubgraphDeploymentId, HashMap<String, HashMap<String, Entity>>>>,
 
     subscriptions: Mutex<Vec<(HashSet<SubgraphEntityPair>, mpsc::Sender<StoreEvent>)>>,
+
+    // Number of entity lookups and queries, across `get`, `find` and `find_one`
+    entity_reads: AtomicUsize,
+
+    // The queries passed to `find` and `find_one`, in order
+    queries: Mutex<Vec<EntityQuery>>,
+
+    // The block each deployment has processed
+    block_ptrs: Mutex<HashMap<SubgraphDeploymentId, EthereumBlockPointer>>,
+
+    // Number of calls to `block_ptr`
+    block_ptr_lookups: AtomicUsize,
+
+    // Number of calls to `chain_head_updates`
+    chain_head_listeners: AtomicUsize,
+
+    // The chain head block
+    chain_head: Mutex<Option<LightEthereumBlock>>,
+
+    // The blocks added to the chain store with `upsert_light_blocks`
+    light_blocks: Mutex<Vec<LightEthereumBlock>>,
+}
+
+/// Orders values of the same type, with missing values first.
+fn compare_values(a: &Option<Value>, b: &Option<Value>) -> ::std::cmp::Ordering {
+    a.as_ref()
+        .unwrap_or(&Value::Null)
+        .compare(b.as_ref().unwrap_or(&Value::Null))
+        .unwrap_or(::std::cmp::Ordering::Equal)
 }
 
 fn entity_matches_filter(entity: &Entity, filter: &EntityFilter) -> bool {
@@ -39,6 +69,10 @@ fn entity_matches_filter(entity: &Entity, filter: &EntityFilter) -> bool {
                 .iter()
                 .any(|allowed_attr_value| attr_value == allowed_attr_value)
         }
+        EntityFilter::GreaterThan(attr_name, attr_value) => {
+            compare_values(&entity.get(attr_name).cloned(), &Some(attr_value.clone()))
+                == ::std::cmp::Ordering::Greater
+        }
         _ => unimplemented!(),
     }
 }
@@ -50,6 +84,13 @@ impl MockStore {
             schemas: schemas.into_iter().collect(),
             entities: Default::default(),
             subscriptions: Default::default(),
+            entity_reads: Default::default(),
+            queries: Default::default(),
+            block_ptrs: Default::default(),
+            block_ptr_lookups: Default::default(),
+            chain_head_listeners: Default::default(),
+            chain_head: Default::default(),
+            light_blocks: Default::default(),
         }
     }
 
@@ -93,6 +134,64 @@ impl MockStore {
         store
     }
 
+    /// Adds `entity` to the store, replacing any entity with the same key.
+    pub fn set(&self, key: EntityKey, entity: Entity) {
+        self.entities
+            .lock()
+            .unwrap()
+            .entry(key.subgraph_id)
+            .or_default()
+            .entry(key.entity_type)
+            .or_default()
+            .insert(key.entity_id, entity);
+    }
+
+    /// Sets the block that the deployment `subgraph_id` has processed.
+    pub fn set_block_ptr(
+        &self,
+        subgraph_id: SubgraphDeploymentId,
+        block_ptr: EthereumBlockPointer,
+    ) {
+        self.block_ptrs
+            .lock()
+            .unwrap()
+            .insert(subgraph_id, block_ptr);
+    }
+
+    /// Sets the chain head of the chain store to `block`, which must have a
+    /// hash and a number.
+    pub fn set_chain_head(&self, block: LightEthereumBlock) {
+        *self.chain_head.lock().unwrap() = Some(block);
+    }
+
+    /// Returns the blocks added to the chain store with
+    /// `upsert_light_blocks` so far.
+    pub fn light_blocks(&self) -> Vec<LightEthereumBlock> {
+        self.light_blocks.lock().unwrap().clone()
+    }
+
+    /// Returns how often entities were read from the store with `get`,
+    /// `find` or `find_one`.
+    pub fn entity_reads(&self) -> usize {
+        self.entity_reads.load(Ordering::SeqCst)
+    }
+
+    /// Returns how often the block pointer of a deployment was looked up.
+    pub fn block_ptr_lookups(&self) -> usize {
+        self.block_ptr_lookups.load(Ordering::SeqCst)
+    }
+
+    /// Returns how many streams of chain head updates were requested from
+    /// the chain store.
+    pub fn chain_head_listeners(&self) -> usize {
+        self.chain_head_listeners.load(Ordering::SeqCst)
+    }
+
+    /// Returns the queries passed to `find` and `find_one` so far.
+    pub fn queries(&self) -> Vec<EntityQuery> {
+        self.queries.lock().unwrap().clone()
+    }
+
     fn execute_query(
         &self,
         entities: &HashMap<SubgraphDeploymentId, HashMap<String, HashMap<String, Entity>>>,
@@ -103,41 +202,81 @@ impl MockStore {
             entity_types,
             filter,
             order_by,
+            order_by_child,
             order_direction,
-            range: _,
+            range,
+            select,
+            query_id: _,
+            block: _,
         } = query;
 
-        // List all entities with correct type
-        let empty1 = HashMap::default();
-        let empty2 = HashMap::default();
-        let entities_of_type = entities
-            .get(&subgraph_id)
-            .unwrap_or(&empty1)
-            .get(&entity_types[0]) // This does not support querying interfaces.
-            .unwrap_or(&empty2)
-            .values();
+        // List all entities of the queried types, which are all the types
+        // that implement an interface when querying one. Like the store,
+        // mark each entity with its type.
+        let empty = HashMap::default();
+        let entities_in_subgraph = entities.get(&subgraph_id).unwrap_or(&empty);
+        let entities_of_types = entity_types.iter().flat_map(move |entity_type| {
+            entities_in_subgraph
+                .get(entity_type)
+                .into_iter()
+                .flat_map(|entities_of_type| entities_of_type.values())
+                .map(move |entity| {
+                    let mut entity = entity.clone();
+                    entity.set("__typename", entity_type.clone());
+                    entity
+                })
+        });
 
         // Apply filter, if any
         let filtered_entities: Vec<_> = if let Some(filter) = filter {
-            entities_of_type
+            entities_of_types
                 .filter(|entity| entity_matches_filter(entity, &filter))
                 .collect()
         } else {
-            entities_of_type.collect()
+            entities_of_types.collect()
         };
 
-        // Sort results
+        // Sort results across all types, so that `skip` and `first` apply to
+        // the merged list
         let sorted_entities = if let Some((order_by_attr_name, _order_by_attr_type)) = order_by {
-            if order_by_attr_name == "id" {
-                let mut sorted_entities = filtered_entities;
-                sorted_entities.sort_by(|a, b| match (a.get("id"), b.get("id")) {
-                    (Some(Value::String(a_id)), Some(Value::String(b_id))) => a_id.cmp(&b_id),
-                    _ => ::std::cmp::Ordering::Equal,
-                });
-                sorted_entities
-            } else {
-                unimplemented!("only ordering by `id` is support in the mock store");
-            }
+            let mut sorted_entities = filtered_entities;
+            sorted_entities.sort_by(|a, b| {
+                let order = compare_values(
+                    &a.get(&order_by_attr_name).cloned(),
+                    &b.get(&order_by_attr_name).cloned(),
+                );
+                let order = match order_direction {
+                    Some(EntityOrder::Descending) => order.reverse(),
+                    _ => order,
+                };
+                order.then_with(|| compare_values(&a.get("id").cloned(), &b.get("id").cloned()))
+            });
+            sorted_entities
+        } else if let Some(EntityOrderByChild {
+            relation,
+            attribute,
+        }) = order_by_child
+        {
+            // Referenced entities are looked up by ID among the entities of
+            // all types, which is good enough for tests that don't reuse IDs
+            let child_value = |entity: &Entity| match entity.get(&relation) {
+                Some(Value::String(id)) => entities_in_subgraph
+                    .values()
+                    .find_map(|entities_of_type| entities_of_type.get(id))
+                    .and_then(|child| child.get(&attribute).cloned()),
+                _ => None,
+            };
+
+            let mut sorted_entities = filtered_entities;
+            sorted_entities.sort_by(|a, b| {
+                let order = compare_values(&child_value(a), &child_value(b));
+                let order = match order_direction {
+                    Some(EntityOrder::Descending) => order.reverse(),
+                    _ => order,
+                };
+                order.then_with(|| compare_values(&a.get("id").cloned(), &b.get("id").cloned()))
+            });
+            sorted_entities
         } else {
             assert_eq!(order_direction, None);
 
@@ -147,12 +286,34 @@ impl MockStore {
             sorted_entities
         };
 
-        Ok(sorted_entities.into_iter().cloned().collect())
+        Ok(sorted_entities
+            .into_iter()
+            .skip(range.skip as usize)
+            .take(
+                range
+                    .first
+                    .map_or(usize::max_value(), |first| first as usize),
+            )
+            .map(|entity| match &select {
+                Some(select) => entity
+                    .iter()
+                    .filter(|(name, _)| {
+                        name.as_str() == "id"
+                            || name.as_str() == "__typename"
+                            || select.contains(*name)
+                    })
+                    .map(|(name, value)| (name.clone(), value.clone()))
+                    .collect::<HashMap<_, _>>()
+                    .into(),
+                None => entity,
+            })
+            .collect())
     }
 }
 
 impl Store for MockStore {
     fn get(&self, key: EntityKey) -> Result<Option<Entity>, QueryExecutionError> {
+        self.entity_reads.fetch_add(1, Ordering::SeqCst);
         Ok(self
             .entities
             .lock()
@@ -164,6 +325,8 @@ impl Store for MockStore {
     }
 
     fn find(&self, query: EntityQuery) -> Result<Vec<Entity>, QueryExecutionError> {
+        self.entity_reads.fetch_add(1, Ordering::SeqCst);
+        self.queries.lock().unwrap().push(query.clone());
         self.execute_query(&self.entities.lock().unwrap(), query)
     }
 
@@ -179,8 +342,12 @@ impl Store for MockStore {
         }
     }
 
-    fn block_ptr(&self, _: SubgraphDeploymentId) -> Result<Option<EthereumBlockPointer>, Error> {
-        unimplemented!();
+    fn block_ptr(
+        &self,
+        subgraph_id: SubgraphDeploymentId,
+    ) -> Result<Option<EthereumBlockPointer>, Error> {
+        self.block_ptr_lookups.fetch_add(1, Ordering::SeqCst);
+        Ok(self.block_ptrs.lock().unwrap().get(&subgraph_id).cloned())
     }
 
     fn transact_block_operations(
@@ -380,14 +547,31 @@ impl SubgraphDeploymentStore for MockStore {
     }
 
     fn api_schema(&self, subgraph_id: &SubgraphDeploymentId) -> Result<Arc<Schema>, Error> {
-        let mut schema = self.input_schema(subgraph_id)?.as_ref().clone();
-        schema.document = api_schema(&schema.document)?;
-        return Ok(Arc::new(schema));
+        let schema = self.input_schema(subgraph_id)?.as_ref().clone();
+        let logger = Logger::root(slog::Discard, o!());
+        Ok(Arc::new(Schema::api(
+            &logger,
+            schema,
+            Pluralization::Legacy,
+        )?))
     }
 
     fn uses_relational_schema(&self, _: &SubgraphDeploymentId) -> Result<bool, Error> {
         Ok(true)
     }
+
+    fn deployment_files(
+        &self,
+        subgraph_id: &SubgraphDeploymentId,
+    ) -> Result<Option<SubgraphDeploymentFiles>, Error> {
+        self.get(EntityKey {
+            subgraph_id: SUBGRAPHS_ID.clone(),
+            entity_type: SubgraphManifestEntity::TYPENAME.to_owned(),
+            entity_id: SubgraphManifestEntity::id(subgraph_id),
+        })?
+        .map(|entity| SubgraphManifestEntity::files(&entity))
+        .transpose()
+    }
 }
 
 impl ChainStore for MockStore {
@@ -406,8 +590,9 @@ impl ChainStore for MockStore {
         unimplemented!();
     }
 
-    fn upsert_light_blocks(&self, _: Vec<LightEthereumBlock>) -> Result<(), Error> {
-        unimplemented!();
+    fn upsert_light_blocks(&self, blocks: Vec<LightEthereumBlock>) -> Result<(), Error> {
+        self.light_blocks.lock().unwrap().extend(blocks);
+        Ok(())
     }
 
     fn attempt_chain_head_update(&self, _: u64) -> Result<Vec<H256>, Error> {
@@ -415,15 +600,29 @@ impl ChainStore for MockStore {
     }
 
     fn chain_head_updates(&self) -> ChainHeadUpdateStream {
-        unimplemented!();
+        self.chain_head_listeners.fetch_add(1, Ordering::SeqCst);
+        Box::new(stream::empty())
     }
 
     fn chain_head_ptr(&self) -> Result<Option<EthereumBlockPointer>, Error> {
-        Ok(None)
+        Ok(self
+            .chain_head
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(EthereumBlockPointer::from))
     }
 
-    fn blocks(&self, _: Vec<H256>) -> Result<Vec<LightEthereumBlock>, Error> {
-        unimplemented!();
+    fn blocks(&self, hashes: Vec<H256>) -> Result<Vec<LightEthereumBlock>, Error> {
+        Ok(self
+            .chain_head
+            .lock()
+            .unwrap()
+            .iter()
+            .chain(self.light_blocks.lock().unwrap().iter())
+            .filter(|block| hashes.contains(&block.hash.unwrap()))
+            .cloned()
+            .collect())
     }
 
     fn ancestor_block(
@@ -4,7 +4,7 @@ use git_testament::{git_testament, render_testament};
 use ipfs_api::IpfsClient;
 use lazy_static::lazy_static;
 use prometheus::Registry;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::env;
 use std::str::FromStr;
 use std::time::Duration;
@@ -15,20 +15,24 @@ use graph::prelude::{
     EthereumAdapter as EthereumAdapterTrait, IndexNodeServer as _, JsonRpcServer as _, *,
 };
 use graph::util::security::SafeDisplay;
-use graph_chain_ethereum::{BlockIngestor, BlockStreamBuilder, Transport};
+use graph_chain_ethereum::{
+    BlockIngestor, BlockStreamBuilder, ChainHeadBroadcaster, EthereumTriggerPreflightRunner,
+    Transport,
+};
 use graph_core::{
     LinkResolver, MetricsRegistry, SubgraphAssignmentProvider as IpfsSubgraphAssignmentProvider,
     SubgraphInstanceManager, SubgraphRegistrar as IpfsSubgraphRegistrar,
 };
 use graph_runtime_wasm::RuntimeHostBuilder as WASMRuntimeHostBuilder;
 use graph_server_http::GraphQLServer as GraphQLQueryServer;
-use graph_server_index_node::IndexNodeServer;
+use graph_server_index_node::{EthereumNetwork, IndexNodeServer};
 use graph_server_json_rpc::JsonRpcServer;
 use graph_server_metrics::PrometheusMetricsServer;
 use graph_server_websocket::SubscriptionServer as GraphQLSubscriptionServer;
 use graph_store_postgres::connection_pool::create_connection_pool;
 use graph_store_postgres::{Store as DieselStore, StoreConfig};
 
+use tokio_signal::unix::{Signal, SIGINT, SIGTERM};
 use tokio_timer::timer::Timer;
 
 lazy_static! {
@@ -51,6 +55,13 @@ lazy_static! {
         .map(|s| usize::from_str(&s)
              .unwrap_or_else(|_| panic!("failed to parse env var GRAPH_TOKIO_THREAD_COUNT")))
         .unwrap_or(100);
+
+    // Default to giving queries 30 seconds to finish when shutting down
+    static ref SHUTDOWN_GRACE_PERIOD: Duration = env::var("GRAPH_SHUTDOWN_GRACE_PERIOD")
+        .ok()
+        .map(|s| Duration::from_secs(u64::from_str(&s)
+             .unwrap_or_else(|_| panic!("failed to parse env var GRAPH_SHUTDOWN_GRACE_PERIOD"))))
+        .unwrap_or(Duration::from_secs(30));
 }
 
 git_testament!(TESTAMENT);
@@ -177,6 +188,30 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
                      Ethereum IPC pipe, separated by a ':'",
                 ),
         )
+        .arg(
+            Arg::with_name("ethereum-consistency-check")
+                .takes_value(true)
+                .multiple(true)
+                .long("ethereum-consistency-check")
+                .value_name("NETWORK_NAME")
+                .help(
+                    "Ethereum network name (e.g. 'mainnet') for which retried requests \
+                     for logs and calls in final blocks must return the same result as \
+                     the previous attempt, to detect inconsistent Ethereum nodes",
+                ),
+        )
+        .arg(
+            Arg::with_name("ethereum-no-traces")
+                .takes_value(true)
+                .multiple(true)
+                .long("ethereum-no-traces")
+                .value_name("NETWORK_NAME")
+                .help(
+                    "Ethereum network name (e.g. 'mainnet') whose Ethereum nodes can't \
+                     trace calls; subgraphs on it with call handlers or block handlers \
+                     with a call filter fail to start",
+                ),
+        )
         .arg(
             Arg::with_name("ipfs")
                 .takes_value(true)
@@ -304,6 +339,14 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
     let ethereum_rpc = matches.values_of("ethereum-rpc");
     let ethereum_ipc = matches.values_of("ethereum-ipc");
     let ethereum_ws = matches.values_of("ethereum-ws");
+    let consistency_check_networks: HashSet<String> = matches
+        .values_of("ethereum-consistency-check")
+        .map(|networks| networks.map(String::from).collect())
+        .unwrap_or_default();
+    let no_traces_networks: HashSet<String> = matches
+        .values_of("ethereum-no-traces")
+        .map(|networks| networks.map(String::from).collect())
+        .unwrap_or_default();
 
     let block_polling_interval = Duration::from_millis(
         matches
@@ -450,8 +493,11 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
     let mut metrics_server =
         PrometheusMetricsServer::new(&logger_factory, prometheus_registry.clone());
 
-    // Ethereum clients
-    let eth_adapters = [
+    // Lets queries and block streams finish their work when shutting down
+    let shutdown = ShutdownCoordinator::new(metrics_registry.clone(), *SHUTDOWN_GRACE_PERIOD);
+
+    // Ethereum clients and the URLs of their Ethereum nodes
+    let eth_nodes = [
         (ConnectionType::RPC, ethereum_rpc),
         (ConnectionType::IPC, ethereum_ipc),
         (ConnectionType::WS, ethereum_ws),
@@ -465,6 +511,8 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
             values.unwrap(),
             connection_type,
             metrics_registry.clone(),
+            &consistency_check_networks,
+            &no_traces_networks,
         ) {
             Ok(adapter) => adapters.into_iter().chain(adapter).collect(),
             Err(e) => {
@@ -476,6 +524,17 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
         }
     });
 
+    // The index node server reports the Ethereum node of each network by
+    // its host name
+    let provider_urls: HashMap<String, Vec<String>> = eth_nodes
+        .iter()
+        .map(|(name, (_, url))| (name.clone(), vec![url.clone()]))
+        .collect();
+    let eth_adapters: HashMap<String, Arc<dyn EthereumAdapterTrait>> = eth_nodes
+        .into_iter()
+        .map(|(name, (eth_adapter, _))| (name, eth_adapter))
+        .collect();
+
     // Set up Store
     info!(
         logger,
@@ -488,10 +547,13 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
         create_connection_pool(postgres_url.clone(), store_conn_pool_size, &logger);
 
     let stores_metrics_registry = metrics_registry.clone();
+    let graphql_metrics_registry = metrics_registry.clone();
     let stores_logger = logger.clone();
     let stores_error_logger = logger.clone();
     let stores_eth_adapters = eth_adapters.clone();
     let contention_logger = logger.clone();
+    let shutdown_logger = logger.clone();
+    let components_shutdown = shutdown.clone();
 
     tokio::spawn(
         futures::stream::futures_ordered(stores_eth_adapters.into_iter().map(
@@ -500,6 +562,27 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
                     logger, "Connecting to Ethereum...";
                     "network" => &network_name,
                 );
+
+                // Subgraphs that make calls against old blocks fail on
+                // Ethereum nodes that prune old state
+                let archive_logger = logger.new(o!("network" => network_name.clone()));
+                tokio::spawn(eth_adapter.is_archive(&logger).then(move |result| {
+                    match result {
+                        Ok(true) => {}
+                        Ok(false) => warn!(
+                            archive_logger,
+                            "Ethereum node is not an archive node; contract calls \
+                             against old blocks will fail"
+                        ),
+                        Err(e) => warn!(
+                            archive_logger,
+                            "Could not find out whether the Ethereum node is an archive node";
+                            "error" => e.to_string(),
+                        ),
+                    }
+                    Ok(())
+                }));
+
                 eth_adapter
                     .net_identifiers(&logger)
                     .map(|network_identifier| (network_name, network_identifier))
@@ -524,40 +607,106 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
                         network_name: network_name.to_string(),
                     },
                     &stores_logger,
-                    network_identifier,
+                    network_identifier.clone(),
                     postgres_conn_pool.clone(),
                     stores_metrics_registry.clone(),
                 )),
+                network_identifier,
             )
         })
         .collect()
-        .map(|stores| HashMap::from_iter(stores.into_iter()))
-        .and_then(move |stores| {
+        .map(|networks| {
+            let mut stores = HashMap::new();
+            let mut identifiers = HashMap::new();
+            for (network_name, store, identifier) in networks {
+                identifiers.insert(network_name.clone(), identifier);
+                stores.insert(network_name, store);
+            }
+            (stores, identifiers)
+        })
+        .and_then(move |(stores, identifiers)| {
             let generic_store = stores.values().next().expect("error creating stores");
 
-            let graphql_runner = Arc::new(graph_core::GraphQlRunner::new(
-                &logger,
-                generic_store.clone(),
-            ));
+            // The block streams of the subgraphs running on this node record
+            // how far they have synced, which queries report
+            let sync_statuses = Arc::new(DeploymentSyncStatuses::new());
+
+            let graphql_runner = Arc::new(
+                graph_core::GraphQlRunner::new(
+                    &logger,
+                    generic_store.clone(),
+                    graphql_metrics_registry.clone(),
+                )
+                .with_shutdown(components_shutdown.clone())
+                .with_sync_statuses(sync_statuses.clone()),
+            );
             let mut graphql_server = GraphQLQueryServer::new(
                 &logger_factory,
                 graphql_runner.clone(),
                 generic_store.clone(),
                 node_id.clone(),
-            );
+            )
+            .with_shutdown(components_shutdown.clone());
             let mut subscription_server = GraphQLSubscriptionServer::new(
                 &logger,
                 graphql_runner.clone(),
                 generic_store.clone(),
             );
 
+            // Handler statistics are collected by the subgraphs running on
+            // this node and served by its index node server
+            let handler_stats = Arc::new(InMemoryHandlerStatsRegistry::new());
+
+            // Likewise for samples of the progress of these subgraphs
+            let indexing_progress = Arc::new(InMemoryIndexingProgressRegistry::new());
+
+            // Lets deployers check where the triggers of a subgraph are
+            // through the index node server
+            let trigger_preflight = Arc::new(EthereumTriggerPreflightRunner::new(
+                link_resolver.clone(),
+                stores.clone(),
+                eth_adapters.clone(),
+                *REORG_THRESHOLD,
+                metrics_registry.clone(),
+            ));
+
+            // Lets operators check the Ethereum nodes and chain stores of
+            // all networks through the index node server
+            let networks = stores
+                .iter()
+                .filter_map(|(network_name, chain_store)| {
+                    eth_adapters.get(network_name).map(|eth_adapter| {
+                        (
+                            network_name.clone(),
+                            EthereumNetwork {
+                                eth_adapter: eth_adapter.clone(),
+                                chain_store: chain_store.clone(),
+                                identifier: identifiers[network_name].clone(),
+                                provider_urls: provider_urls
+                                    .get(network_name)
+                                    .cloned()
+                                    .unwrap_or_default(),
+                            },
+                        )
+                    })
+                })
+                .collect::<BTreeMap<_, _>>();
+
             let mut index_node_server = IndexNodeServer::new(
                 &logger_factory,
                 graphql_runner.clone(),
                 generic_store.clone(),
+                handler_stats.clone(),
+                indexing_progress.clone(),
+                trigger_preflight,
+                networks,
+                metrics_registry.clone(),
                 node_id.clone(),
             );
 
+            // Chain head broadcasters for the networks this node ingests blocks for
+            let mut chain_head_broadcasters = HashMap::new();
+
             if !disable_block_ingestor {
                 // BlockIngestor must be configured to keep at least REORG_THRESHOLD ancestors,
                 // otherwise BlockStream will not work properly.
@@ -569,6 +718,13 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
 
                 // Create Ethereum block ingestors and spawn a thread to run each
                 eth_adapters.iter().for_each(|(network_name, eth_adapter)| {
+                    let chain_head_broadcaster = ChainHeadBroadcaster::new(
+                        network_name.to_string(),
+                        metrics_registry.clone(),
+                    );
+                    chain_head_broadcasters
+                        .insert(network_name.to_string(), chain_head_broadcaster.clone());
+
                     let block_ingestor = BlockIngestor::new(
                         stores.get(network_name).expect("network with name").clone(),
                         eth_adapter.clone(),
@@ -576,6 +732,7 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
                         network_name.to_string(),
                         &logger_factory,
                         block_polling_interval,
+                        chain_head_broadcaster,
                     )
                     .expect("failed to create Ethereum block ingestor");
 
@@ -587,11 +744,13 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
             let block_stream_builder = BlockStreamBuilder::new(
                 generic_store.clone(),
                 stores.clone(),
+                chain_head_broadcasters,
                 eth_adapters.clone(),
                 node_id.clone(),
                 *REORG_THRESHOLD,
                 metrics_registry.clone(),
-            );
+            )
+            .with_shutdown(components_shutdown);
             let runtime_host_builder = WASMRuntimeHostBuilder::new(
                 eth_adapters.clone(),
                 link_resolver.clone(),
@@ -605,6 +764,9 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
                 runtime_host_builder,
                 block_stream_builder,
                 metrics_registry.clone(),
+                handler_stats,
+                indexing_progress,
+                sync_statuses,
             );
 
             // Create IPFS-based subgraph provider
@@ -743,16 +905,44 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
         }
     });
 
-    future::empty()
+    // Shut down on SIGINT or SIGTERM: turn new queries away, let block
+    // streams finish the block they are processing and give running queries
+    // a grace period to finish
+    Signal::new(SIGINT)
+        .flatten_stream()
+        .select(Signal::new(SIGTERM).flatten_stream())
+        .into_future()
+        .map_err(|(e, _)| panic!("failed to listen for shutdown signals: {}", e))
+        .and_then(move |_| {
+            info!(
+                shutdown_logger,
+                "Shutting down";
+                "in_flight_work" => shutdown.in_flight(),
+                "grace_period_secs" => SHUTDOWN_GRACE_PERIOD.as_secs(),
+            );
+            shutdown
+                .shutdown()
+                .map(move |()| match shutdown.in_flight() {
+                    0 => info!(shutdown_logger, "All in-flight work finished"),
+                    in_flight => warn!(
+                        shutdown_logger,
+                        "Grace period is over, abandoning in-flight work";
+                        "in_flight_work" => in_flight,
+                    ),
+                })
+        })
 }
 
-/// Parses an Ethereum connection string and returns the network name and Ethereum adapter.
+/// Parses Ethereum connection strings and returns the Ethereum adapter and
+/// node URL of each network by network name.
 fn parse_ethereum_networks_and_nodes(
     logger: Logger,
     networks: clap::Values,
     connection_type: ConnectionType,
     registry: Arc<MetricsRegistry>,
-) -> Result<HashMap<String, Arc<dyn EthereumAdapterTrait>>, Error> {
+    consistency_check_networks: &HashSet<String>,
+    no_traces_networks: &HashSet<String>,
+) -> Result<HashMap<String, (Arc<dyn EthereumAdapterTrait>, String)>, Error> {
     let eth_rpc_metrics = Arc::new(ProviderEthRpcMetrics::new(registry));
     networks
         .map(|network| {
@@ -803,12 +993,24 @@ fn parse_ethereum_networks_and_nodes(
                 // For now it's fine to just leak it.
                 std::mem::forget(transport_event_loop);
 
+                let mut adapter =
+                    graph_chain_ethereum::EthereumAdapter::new(transport, eth_rpc_metrics.clone())
+                        .with_reorg_threshold(*REORG_THRESHOLD);
+                if consistency_check_networks.contains(name) {
+                    info!(logger, "Enabling consistency check"; "network" => &name);
+                    adapter = adapter.with_consistency_check();
+                }
+                if no_traces_networks.contains(name) {
+                    info!(logger, "Disabling traces"; "network" => &name);
+                    adapter = adapter.without_traces();
+                }
+
                 Ok((
                     name.to_string(),
-                    Arc::new(graph_chain_ethereum::EthereumAdapter::new(
-                        transport,
-                        eth_rpc_metrics.clone(),
-                    )) as Arc<dyn EthereumAdapter>,
+                    (
+                        Arc::new(adapter) as Arc<dyn EthereumAdapter>,
+                        loc.to_string(),
+                    ),
                 ))
             }
         })
@@ -17,7 +17,7 @@ use graph::prelude::{
     RuntimeHost as RuntimeHostTrait, RuntimeHostBuilder as RuntimeHostBuilderTrait, *,
 };
 use graph::util;
-use web3::types::{Log, Transaction};
+use web3::types::{Address, Log, Transaction};
 
 pub(crate) const TIMEOUT_ENV_VAR: &str = "GRAPH_MAPPING_HANDLER_TIMEOUT";
 
@@ -26,8 +26,10 @@ struct RuntimeHostConfig {
     mapping: Mapping,
     data_source_network: String,
     data_source_name: String,
+    data_source_ordinal: DataSourceOrdinal,
     contract: Source,
     templates: Vec<DataSourceTemplate>,
+    factory_children: FactoryChildren,
 }
 
 pub struct RuntimeHostBuilder<S> {
@@ -90,6 +92,7 @@ where
         top_level_templates: Vec<DataSourceTemplate>,
         mapping_request_sender: Sender<MappingRequest>,
         metrics: Arc<HostMetrics>,
+        factory_children: FactoryChildren,
     ) -> Result<Self::Host, Error> {
         let store = self.stores.get(&network_name).ok_or_else(|| {
             format_err!(
@@ -122,8 +125,10 @@ where
                 mapping: data_source.mapping,
                 data_source_network: network_name,
                 data_source_name: data_source.name,
+                data_source_ordinal: data_source.ordinal,
                 contract: data_source.source,
                 templates,
+                factory_children,
             },
             mapping_request_sender,
             metrics,
@@ -134,11 +139,13 @@ where
 #[derive(Debug)]
 pub struct RuntimeHost {
     data_source_name: String,
+    data_source_ordinal: DataSourceOrdinal,
     data_source_contract: Source,
     data_source_contract_abi: MappingABI,
     data_source_event_handlers: Vec<MappingEventHandler>,
     data_source_call_handlers: Vec<MappingCallHandler>,
     data_source_block_handlers: Vec<MappingBlockHandler>,
+    factory_children: FactoryChildren,
     mapping_request_sender: Sender<MappingRequest>,
     host_exports: Arc<HostExports>,
     metrics: Arc<HostMetrics>,
@@ -155,9 +162,9 @@ impl RuntimeHost {
         metrics: Arc<HostMetrics>,
     ) -> Result<Self, Error> {
         let api_version = Version::parse(&config.mapping.api_version)?;
-        if !VersionReq::parse("<= 0.0.3").unwrap().matches(&api_version) {
+        if !VersionReq::parse("<= 0.0.4").unwrap().matches(&api_version) {
             return Err(format_err!(
-                "This Graph Node only supports mapping API versions <= 0.0.3, but subgraph `{}` uses `{}`",
+                "This Graph Node only supports mapping API versions <= 0.0.4, but subgraph `{}` uses `{}`",
                 config.subgraph_id,
                 api_version
             ));
@@ -178,6 +185,7 @@ impl RuntimeHost {
             .clone();
 
         let data_source_name = config.data_source_name;
+        let schema = store.input_schema(&config.subgraph_id)?;
 
         // Create new instance of externally hosted functions invoker. The `Arc` is simply to avoid
         // implementing `Clone` for `HostExports`.
@@ -193,6 +201,7 @@ impl RuntimeHost {
             link_resolver,
             store,
             call_cache,
+            schema,
             std::env::var(TIMEOUT_ENV_VAR)
                 .ok()
                 .and_then(|s| u64::from_str(&s).ok())
@@ -201,24 +210,41 @@ impl RuntimeHost {
 
         Ok(RuntimeHost {
             data_source_name,
+            data_source_ordinal: config.data_source_ordinal,
             data_source_contract: config.contract,
             data_source_contract_abi,
             data_source_event_handlers: config.mapping.event_handlers,
             data_source_call_handlers: config.mapping.call_handlers,
             data_source_block_handlers: config.mapping.block_handlers,
+            factory_children: config.factory_children,
             mapping_request_sender,
             host_exports,
             metrics,
         })
     }
 
+    fn matches_address(&self, address: &Address) -> bool {
+        match &self.data_source_contract {
+            // Only contracts created by the factory, as far as they have
+            // been discovered by the block stream
+            Source {
+                factory: Some(factory),
+                ..
+            } => self.factory_children.factory_of(address).as_ref() == Some(factory),
+            Source {
+                address: Some(addr),
+                ..
+            } => addr == address,
+            _ => true,
+        }
+    }
+
     fn matches_call_address(&self, call: &EthereumCall) -> bool {
         // The runtime host matches the contract address of the `EthereumCall`
-        // if the data source contains the same contract address or
-        // if the data source doesn't have a contract address at all
-        self.data_source_contract
-            .address
-            .map_or(true, |addr| addr == call.to)
+        // if the data source contains the same contract address, if its
+        // factory created the contract or if the data source doesn't have
+        // a contract address at all
+        self.matches_address(&call.to)
     }
 
     fn matches_call_function(&self, call: &EthereumCall) -> bool {
@@ -232,11 +258,10 @@ impl RuntimeHost {
 
     fn matches_log_address(&self, log: &Log) -> bool {
         // The runtime host matches the contract address of the `Log`
-        // if the data source contains the same contract address or
-        // if the data source doesn't have a contract address at all
-        self.data_source_contract
-            .address
-            .map_or(true, |addr| addr == log.address)
+        // if the data source contains the same contract address, if its
+        // factory created the contract or if the data source doesn't have
+        // a contract address at all
+        self.matches_address(&log.address)
     }
 
     fn matches_log_signature(&self, log: &Log) -> bool {
@@ -252,7 +277,8 @@ impl RuntimeHost {
 
     fn matches_block_trigger(&self, block_trigger_type: EthereumBlockTriggerType) -> bool {
         let source_address_matches = match block_trigger_type {
-            EthereumBlockTriggerType::WithCallTo(address) => {
+            EthereumBlockTriggerType::WithCallTo(address)
+            | EthereumBlockTriggerType::WithTransactionTo(address) => {
                 self.data_source_contract
                     .address
                     // Do not match if this datasource has no address
@@ -334,10 +360,7 @@ impl RuntimeHost {
             EthereumBlockTriggerType::WithCallTo(_address) => self
                 .data_source_block_handlers
                 .iter()
-                .find(move |handler| {
-                    handler.filter.is_some()
-                        && handler.filter.clone().unwrap() == BlockHandlerFilter::Call
-                })
+                .find(move |handler| handler.filter == Some(BlockHandlerFilter::Call))
                 .cloned()
                 .ok_or_else(|| {
                     format_err!(
@@ -346,11 +369,27 @@ impl RuntimeHost {
                         self.data_source_name,
                     )
                 }),
+            EthereumBlockTriggerType::WithTransactionTo(_address) => self
+                .data_source_block_handlers
+                .iter()
+                .find(move |handler| handler.filter == Some(BlockHandlerFilter::Transaction))
+                .cloned()
+                .ok_or_else(|| {
+                    format_err!(
+                        "No block handler for `WithTransactionTo` block trigger \
+                         type found in data source \"{}\"",
+                        self.data_source_name,
+                    )
+                }),
         }
     }
 }
 
 impl RuntimeHostTrait for RuntimeHost {
+    fn data_source_ordinal(&self) -> DataSourceOrdinal {
+        self.data_source_ordinal
+    }
+
     fn matches_log(&self, log: &Log) -> bool {
         self.matches_log_address(log)
             && self.matches_log_signature(log)
@@ -511,8 +550,10 @@ impl RuntimeHostTrait for RuntimeHost {
                 .and_then(move |(result, _)| {
                     let elapsed = start_time.elapsed();
                     metrics.observe_handler_execution_time(
-                        elapsed.as_secs_f64(),
+                        elapsed,
+                        HandlerKind::Call,
                         call_handler.handler.clone(),
+                        result.is_err(),
                     );
                     info!(
                         logger, "Done processing Ethereum call";
@@ -575,8 +616,10 @@ impl RuntimeHostTrait for RuntimeHost {
                 .and_then(move |(result, _)| {
                     let elapsed = start_time.elapsed();
                     metrics.observe_handler_execution_time(
-                        elapsed.as_secs_f64(),
+                        elapsed,
+                        HandlerKind::Block,
                         block_handler.handler.clone(),
+                        result.is_err(),
                     );
                     info!(
                         logger, "Done processing Ethereum block";
@@ -761,8 +804,10 @@ impl RuntimeHostTrait for RuntimeHost {
                     let elapsed = start_time.elapsed();
                     let logger = logger.clone();
                     metrics.observe_handler_execution_time(
-                        elapsed.as_secs_f64(),
+                        elapsed,
+                        HandlerKind::Event,
                         event_handler.handler.clone(),
+                        result.is_err(),
                     );
                     info!(
                         logger, "Done processing Ethereum event";
@@ -49,6 +49,9 @@ pub(crate) struct HostExports {
     link_resolver: Arc<dyn LinkResolver>,
     call_cache: Arc<dyn EthereumCallCache>,
     store: Arc<dyn crate::RuntimeStore>,
+    /// The input schema of the subgraph, which maps the entity types that
+    /// mappings use to the types they are stored under.
+    schema: Arc<Schema>,
     handler_timeout: Option<Duration>,
 }
 
@@ -72,6 +75,7 @@ impl HostExports {
         link_resolver: Arc<dyn LinkResolver>,
         store: Arc<dyn crate::RuntimeStore>,
         call_cache: Arc<dyn EthereumCallCache>,
+        schema: Arc<Schema>,
         handler_timeout: Option<Duration>,
     ) -> Self {
         Self {
@@ -86,6 +90,7 @@ impl HostExports {
             link_resolver,
             call_cache,
             store,
+            schema,
             handler_timeout,
         }
     }
@@ -139,12 +144,11 @@ impl HostExports {
 
         let key = EntityKey {
             subgraph_id: self.subgraph_id.clone(),
-            entity_type,
+            entity_type: self.schema.stored_entity_type(&entity_type).to_owned(),
             entity_id,
         };
         let entity = Entity::from(data);
-        let schema = self.store.input_schema(&self.subgraph_id)?;
-        let is_valid = validate_entity(&schema.document, &key, &entity).is_ok();
+        let is_valid = validate_entity(&self.schema.document, &key, &entity).is_ok();
         state.entity_cache.set(key.clone(), entity);
 
         // Validate the changes against the subgraph schema.
@@ -155,7 +159,7 @@ impl HostExports {
                 .get(self.store.as_ref(), &key)
                 .map_err(|e| HostExportError(e.to_string()))?
                 .expect("we just stored this entity");
-            validate_entity(&schema.document, &key, &entity)?;
+            validate_entity(&self.schema.document, &key, &entity)?;
         }
         Ok(())
     }
@@ -165,13 +169,14 @@ impl HostExports {
         state: &mut BlockState,
         entity_type: String,
         entity_id: String,
-    ) {
+    ) -> Result<(), HostExportError<impl ExportError>> {
         let key = EntityKey {
             subgraph_id: self.subgraph_id.clone(),
-            entity_type,
+            entity_type: self.schema.stored_entity_type(&entity_type).to_owned(),
             entity_id,
         };
         state.entity_cache.remove(key);
+        Ok(())
     }
 
     pub(crate) fn store_get(
@@ -184,14 +189,14 @@ impl HostExports {
         let start_time = Instant::now();
         let store_key = EntityKey {
             subgraph_id: self.subgraph_id.clone(),
-            entity_type: entity_type.clone(),
+            entity_type: self.schema.stored_entity_type(&entity_type).to_owned(),
             entity_id: entity_id.clone(),
         };
 
         let result = state
             .entity_cache
             .get(self.store.as_ref(), &store_key)
-            .map_err(HostExportError)
+            .map_err(|e| HostExportError(e.to_string()))
             .map(|ok| ok.to_owned());
 
         debug!(logger, "Store get finished";
@@ -453,7 +453,7 @@ where
         let id = self.asc_get(id_ptr);
         self.ctx
             .host_exports
-            .store_remove(&mut self.ctx.state, entity, id);
+            .store_remove(&mut self.ctx.state, entity, id)?;
         Ok(None)
     }
 
@@ -46,8 +46,9 @@ fn test_valid_module_and_store(
     );
     let host_metrics = Arc::new(HostMetrics::new(
         metrics_registry,
-        deployment_id.to_string(),
+        deployment_id.clone(),
         stopwatch_metrics,
+        Arc::new(InMemoryHandlerStatsRegistry::new()),
     ));
 
     let (task_sender, task_receiver) = channel(100);
@@ -88,6 +89,7 @@ fn mock_data_source(path: &str) -> DataSource {
             address: Some(Address::from_str("0123123123012312312301231231230123123123").unwrap()),
             abi: String::from("123123"),
             start_block: 0,
+            factory: None,
         },
         mapping: Mapping {
             kind: String::from("ethereum/events"),
@@ -125,6 +127,7 @@ fn mock_data_source(path: &str) -> DataSource {
                 runtime: Arc::new(runtime),
             },
         }],
+        ordinal: DataSourceOrdinal::default(),
     }
 }
 
@@ -143,7 +146,8 @@ fn mock_host_exports(data_source: DataSource, store: Arc<MockStore>) -> HostExpo
             ipfs_api::IpfsClient::default(),
         )),
         store.clone(),
-        store,
+        store.clone(),
+        store.input_schema(&MockStore::user_subgraph_id()).unwrap(),
         std::env::var(crate::host::TIMEOUT_ENV_VAR)
             .ok()
             .and_then(|s| u64::from_str(&s).ok())
@@ -5,6 +5,14 @@ use hyper::Chunk;
 use graph::components::server::query::GraphQLServerError;
 use graph::prelude::*;
 
+/// The JSON body of a GraphQL request.
+#[derive(Deserialize)]
+struct RequestBody {
+    query: Option<serde_json::Value>,
+    variables: Option<Box<serde_json::value::RawValue>>,
+    extensions: Option<serde_json::Value>,
+}
+
 /// Future for a query parsed from an HTTP request.
 pub struct GraphQLRequest {
     body: Chunk,
@@ -26,17 +34,13 @@ impl Future for GraphQLRequest {
         // Fail if no schema is available
         let schema = self.schema.clone();
 
-        // Parse request body as JSON
-        let json: serde_json::Value = serde_json::from_slice(&self.body)
+        // Parse request body as JSON, leaving the variables to be checked
+        // against their limits while they are parsed
+        let body: RequestBody = serde_json::from_slice(&self.body)
             .map_err(|e| GraphQLServerError::ClientError(format!("{}", e)))?;
 
-        // Ensure the JSON data is an object
-        let obj = json.as_object().ok_or_else(|| {
-            GraphQLServerError::ClientError(String::from("Request data is not an object"))
-        })?;
-
         // Ensure the JSON data has a "query" field
-        let query_value = obj.get("query").ok_or_else(|| {
+        let query_value = body.query.ok_or_else(|| {
             GraphQLServerError::ClientError(String::from(
                 "The \"query\" field missing in request data",
             ))
@@ -52,26 +56,64 @@ impl Future for GraphQLRequest {
             .map_err(|e| GraphQLServerError::from(QueryError::from(e)))?;
 
         // Parse the "variables" field of the JSON body, if present
-        let variables = match obj.get("variables") {
+        let variables = match body.variables {
+            None => None,
+            Some(variables) => Some(
+                QueryVariables::from_json_slice(
+                    variables.get().as_bytes(),
+                    &InputValueLimits::default(),
+                )
+                .map_err(|e| match e.downcast::<InputValueLimitError>() {
+                    Ok(e) => GraphQLServerError::from(QueryError::from(
+                        QueryExecutionError::VariablesTooLarge(e),
+                    )),
+                    Err(e) => GraphQLServerError::ClientError(e.to_string()),
+                })?,
+            ),
+        };
+
+        // Parse the "extensions" field of the JSON body, if present
+        let extensions = match &body.extensions {
             None | Some(serde_json::Value::Null) => Ok(None),
-            Some(variables @ serde_json::Value::Object(_)) => {
-                serde_json::from_value(variables.clone())
-                    .map_err(|e| GraphQLServerError::ClientError(e.to_string()))
-                    .map(Some)
-            }
+            Some(serde_json::Value::Object(extensions)) => Ok(Some(extensions)),
             _ => Err(GraphQLServerError::ClientError(
-                "Invalid query variables provided".to_string(),
+                "Invalid query extensions provided".to_string(),
             )),
         }?;
 
+        // Only estimate the cost of the query if the "dryRun" extension is set
+        let dry_run = bool_extension(extensions, "dryRun")?;
+
+        // Warn about deployments that are far behind the chain head if the
+        // "syncWarning" extension is set
+        let sync_warning = bool_extension(extensions, "syncWarning")?;
+
         Ok(Async::Ready(Query {
             document,
             variables,
             schema,
+            dry_run,
+            sync_warning,
+            query_id: None,
         }))
     }
 }
 
+/// The value of the boolean request extension `name`; `false` if it isn't set.
+fn bool_extension(
+    extensions: Option<&serde_json::Map<String, serde_json::Value>>,
+    name: &str,
+) -> Result<bool, GraphQLServerError> {
+    match extensions.and_then(|extensions| extensions.get(name)) {
+        None | Some(serde_json::Value::Null) => Ok(false),
+        Some(serde_json::Value::Bool(value)) => Ok(*value),
+        _ => Err(GraphQLServerError::ClientError(format!(
+            "The \"{}\" extension is not a boolean",
+            name
+        ))),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use graphql_parser;
@@ -79,6 +121,7 @@ mod tests {
     use hyper;
     use std::collections::{BTreeMap, HashMap};
 
+    use graph::components::server::query::GraphQLServerError;
     use graph::prelude::*;
 
     use super::GraphQLRequest;
@@ -211,4 +254,131 @@ mod tests {
         assert_eq!(query.document, expected_query);
         assert_eq!(query.variables, Some(expected_variables));
     }
+
+    #[test]
+    fn parses_dry_run_extension() {
+        let schema =
+            Schema::parse(EXAMPLE_SCHEMA, SubgraphDeploymentId::new("test").unwrap()).unwrap();
+        let schema = Arc::new(schema);
+
+        let request = GraphQLRequest::new(
+            hyper::Chunk::from("{\"query\": \"{ user { name } }\"}"),
+            schema.clone(),
+        );
+        let query = request
+            .wait()
+            .expect("Should accept queries without extensions");
+        assert!(!query.dry_run);
+
+        let request = GraphQLRequest::new(
+            hyper::Chunk::from(
+                "\
+                 {\
+                 \"query\": \"{ user { name } }\", \
+                 \"extensions\": { \"dryRun\": true } \
+                 }",
+            ),
+            schema.clone(),
+        );
+        let query = request.wait().expect("Should accept the dryRun extension");
+        assert!(query.dry_run);
+
+        let request = GraphQLRequest::new(
+            hyper::Chunk::from(
+                "\
+                 {\
+                 \"query\": \"{ user { name } }\", \
+                 \"extensions\": { \"dryRun\": \"yes\" } \
+                 }",
+            ),
+            schema,
+        );
+        request
+            .wait()
+            .expect_err("Should reject a non-boolean dryRun extension");
+    }
+
+    #[test]
+    fn parses_sync_warning_extension() {
+        let schema =
+            Schema::parse(EXAMPLE_SCHEMA, SubgraphDeploymentId::new("test").unwrap()).unwrap();
+        let schema = Arc::new(schema);
+
+        let request = GraphQLRequest::new(
+            hyper::Chunk::from(
+                "\
+                 {\
+                 \"query\": \"{ user { name } }\", \
+                 \"extensions\": { \"dryRun\": true } \
+                 }",
+            ),
+            schema.clone(),
+        );
+        let query = request.wait().expect("Should accept the dryRun extension");
+        assert!(!query.sync_warning);
+
+        let request = GraphQLRequest::new(
+            hyper::Chunk::from(
+                "\
+                 {\
+                 \"query\": \"{ user { name } }\", \
+                 \"extensions\": { \"syncWarning\": true } \
+                 }",
+            ),
+            schema.clone(),
+        );
+        let query = request
+            .wait()
+            .expect("Should accept the syncWarning extension");
+        assert!(query.sync_warning);
+        assert!(!query.dry_run);
+
+        let request = GraphQLRequest::new(
+            hyper::Chunk::from(
+                "\
+                 {\
+                 \"query\": \"{ user { name } }\", \
+                 \"extensions\": { \"syncWarning\": 1 } \
+                 }",
+            ),
+            schema,
+        );
+        request
+            .wait()
+            .expect_err("Should reject a non-boolean syncWarning extension");
+    }
+
+    #[test]
+    fn rejects_variables_beyond_the_limits() {
+        let schema =
+            Schema::parse(EXAMPLE_SCHEMA, SubgraphDeploymentId::new("test").unwrap()).unwrap();
+        let schema = Arc::new(schema);
+
+        let limits = InputValueLimits::default();
+        let deep = format!(
+            "{}\"secret\"{}",
+            "[".repeat(limits.max_depth + 1),
+            "]".repeat(limits.max_depth + 1)
+        );
+        let long = format!("\"secret{}\"", "x".repeat(limits.max_string_length));
+
+        for value in vec![deep, long] {
+            let request = GraphQLRequest::new(
+                hyper::Chunk::from(format!(
+                    "{{\"query\": \"{{ user {{ name }} }}\", \"variables\": {{ \"v\": {} }} }}",
+                    value
+                )),
+                schema.clone(),
+            );
+            match request.wait() {
+                Err(GraphQLServerError::QueryError(QueryError::ExecutionError(
+                    e @ QueryExecutionError::VariablesTooLarge(_),
+                ))) => assert!(
+                    !e.to_string().contains("secret"),
+                    "error echoes the payload"
+                ),
+                _ => panic!("Should reject variables beyond the limits"),
+            }
+        }
+    }
 }
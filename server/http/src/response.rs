@@ -1,6 +1,7 @@
 use http::status::StatusCode;
 use hyper::{Body, Response};
-use serde::ser::*;
+use std::io;
+use std::time::Duration;
 
 use graph::components::server::query::GraphQLServerError;
 use graph::data::query::QueryResult;
@@ -9,19 +10,47 @@ use graph::prelude::*;
 
 /// Future for HTTP responses to GraphQL query requests.
 pub struct GraphQLResponse {
-    result: Result<QueryResult, GraphQLServerError>,
+    result: Result<Option<QueryJsonStream>, GraphQLServerError>,
+    /// Keeps a shutdown waiting until the body of the response is written.
+    in_flight: Option<InFlightWork>,
 }
 
 impl GraphQLResponse {
     /// Creates a new GraphQLResponse future based on the result generated by
     /// running a query.
     pub fn new(result: Result<QueryResult, GraphQLServerError>) -> Self {
-        GraphQLResponse { result }
+        GraphQLResponse::from_json(result.map(|result| {
+            let json =
+                serde_json::to_vec(&result).expect("Failed to serialize GraphQL response to JSON");
+            Box::new(stream::once(Ok(json))) as QueryJsonStream
+        }))
+    }
+
+    /// Creates a new GraphQLResponse future based on the result generated by
+    /// running a query, serialized as JSON. The JSON is sent as the query
+    /// writes it.
+    pub fn from_json(result: Result<QueryJsonStream, GraphQLServerError>) -> Self {
+        GraphQLResponse {
+            result: result.map(Some),
+            in_flight: None,
+        }
+    }
+
+    /// Holds `in_flight` until the body of the response has been written.
+    pub fn with_in_flight_work(mut self, in_flight: Option<InFlightWork>) -> Self {
+        self.in_flight = in_flight;
+        self
     }
 
     fn status_code_from_result(&self) -> StatusCode {
         match self.result {
             Ok(_) => StatusCode::OK,
+            Err(GraphQLServerError::QueryError(QueryError::ExecutionError(
+                QueryExecutionError::DeploymentBusy { .. },
+            )))
+            | Err(GraphQLServerError::QueryError(QueryError::ExecutionError(
+                QueryExecutionError::ShuttingDown,
+            ))) => StatusCode::SERVICE_UNAVAILABLE,
             Err(GraphQLServerError::ClientError(_)) | Err(GraphQLServerError::QueryError(_)) => {
                 StatusCode::BAD_REQUEST
             }
@@ -30,20 +59,27 @@ impl GraphQLResponse {
             }
         }
     }
-}
 
-impl Serialize for GraphQLResponse {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
+    /// How long clients should wait before retrying, if the query was
+    /// turned away because its deployment was too busy.
+    fn retry_after(&self) -> Option<Duration> {
         match self.result {
-            Ok(ref result) => result.serialize(serializer),
+            Err(GraphQLServerError::QueryError(QueryError::ExecutionError(
+                QueryExecutionError::DeploymentBusy { retry_after, .. },
+            ))) => Some(retry_after),
+            _ => None,
+        }
+    }
+
+    fn json(&mut self) -> QueryJsonStream {
+        match self.result {
+            Ok(ref mut json) => json
+                .take()
+                .unwrap_or_else(|| Box::new(stream::empty()) as QueryJsonStream),
             Err(ref e) => {
-                let mut map = serializer.serialize_map(Some(1))?;
-                let errors = vec![e];
-                map.serialize_entry("errors", &errors)?;
-                map.end()
+                let json = serde_json::to_vec(&serde_json::json!({ "errors": [e] }))
+                    .expect("Failed to serialize GraphQL response to JSON");
+                Box::new(stream::once(Ok(json)))
             }
         }
     }
@@ -55,16 +91,33 @@ impl Future for GraphQLResponse {
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
         let status_code = self.status_code_from_result();
-        let json =
-            serde_json::to_string(self).expect("Failed to serialize GraphQL response to JSON");
-        let response = Response::builder()
+        // The status has been sent by the time the query fails to write the
+        // rest of its JSON; all that's left is to end the response early
+        let json = self
+            .json()
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()));
+        let mut builder = Response::builder();
+        builder
             .status(status_code)
             .header("Access-Control-Allow-Origin", "*")
             .header("Access-Control-Allow-Headers", "Content-Type")
             .header("Access-Control-Allow-Methods", "GET, OPTIONS, POST")
-            .header("Content-Type", "application/json")
-            .body(Body::from(json))
-            .unwrap();
+            .header("Content-Type", "application/json");
+        if let Some(retry_after) = self.retry_after() {
+            builder.header(
+                "Retry-After",
+                retry_after.as_secs().max(1).to_string().as_str(),
+            );
+        }
+        // Release the in-flight work once the body has been taken off the
+        // stream, or when the stream is dropped because the connection was
+        // closed
+        let mut in_flight = self.in_flight.take();
+        let body = Body::wrap_stream(json.chain(stream::poll_fn(move || {
+            in_flight.take();
+            Ok(Async::Ready(None))
+        })));
+        let response = builder.body(body).unwrap();
         Ok(Async::Ready(response))
     }
 }
@@ -75,9 +128,11 @@ mod tests {
     use futures::sync::oneshot;
     use graph::components::server::query::GraphQLServerError;
     use graph::prelude::*;
+    use graph_mock::MockMetricsRegistry;
     use graphql_parser;
     use http::status::StatusCode;
     use std::collections::BTreeMap;
+    use std::time::Duration;
 
     use crate::test_utils;
 
@@ -105,6 +160,74 @@ mod tests {
         test_utils::assert_error_response(response, StatusCode::BAD_REQUEST);
     }
 
+    #[test]
+    fn generates_503_while_shutting_down() {
+        let error = QueryError::from(QueryExecutionError::ShuttingDown);
+        let future = GraphQLResponse::new(Err(GraphQLServerError::from(error)));
+        let response = future.wait().expect("Should generate a response");
+        assert!(response.headers().get("Retry-After").is_none());
+        test_utils::assert_error_response(response, StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[test]
+    fn holds_in_flight_work_until_the_body_is_written() {
+        let shutdown = ShutdownCoordinator::new(
+            Arc::new(MockMetricsRegistry::new()),
+            Duration::from_secs(30),
+        );
+        let json = br#"{"data":{}}"#.to_vec();
+        let future = GraphQLResponse::from_json(Ok(Box::new(stream::once(Ok(json.clone())))))
+            .with_in_flight_work(shutdown.begin_work());
+        let response = future.wait().expect("Should generate a response");
+        assert_eq!(shutdown.in_flight(), 1);
+
+        let body = response.into_body().concat2().wait().unwrap();
+        assert_eq!(body.to_vec(), json);
+        assert_eq!(shutdown.in_flight(), 0);
+    }
+
+    #[test]
+    fn sends_json_in_the_chunks_it_is_written_in() {
+        let chunks = vec![br#"{"data":"#.to_vec(), br#"{}}"#.to_vec()];
+        let future = GraphQLResponse::from_json(Ok(Box::new(stream::iter_ok(chunks))));
+        let response = future.wait().expect("Should generate a response");
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().concat2().wait().unwrap();
+        assert_eq!(body.to_vec(), br#"{"data":{}}"#.to_vec());
+    }
+
+    #[test]
+    fn ends_the_body_early_if_writing_json_fails() {
+        let error = QueryError::from(QueryExecutionError::ResponseWriteError(String::from(
+            "the store went away",
+        )));
+        let chunks = stream::once(Ok(br#"{"data":"#.to_vec())).chain(stream::once(Err(error)));
+        let future = GraphQLResponse::from_json(Ok(Box::new(chunks)));
+        let response = future.wait().expect("Should generate a response");
+        assert!(response.into_body().concat2().wait().is_err());
+    }
+
+    #[test]
+    fn generates_503_with_retry_after_for_busy_deployments() {
+        let error = QueryExecutionError::DeploymentBusy {
+            deployment: SubgraphDeploymentId::new("busy").unwrap(),
+            retry_after: Duration::from_secs(5),
+        };
+        let future = GraphQLResponse::new(Err(GraphQLServerError::from(QueryError::from(error))));
+        let response = future.wait().expect("Should generate a response");
+        assert_eq!(
+            response
+                .headers()
+                .get("Retry-After")
+                .expect("Response has no Retry-After header")
+                .to_str()
+                .unwrap(),
+            "5"
+        );
+        test_utils::assert_error_response(response, StatusCode::SERVICE_UNAVAILABLE);
+    }
+
     #[test]
     fn generates_200_for_query_results() {
         let data = graphql_parser::query::Value::Object(BTreeMap::new());
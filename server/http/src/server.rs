@@ -44,6 +44,7 @@ pub struct GraphQLServer<Q, S> {
     graphql_runner: Arc<Q>,
     store: Arc<S>,
     node_id: NodeId,
+    shutdown: Option<ShutdownCoordinator>,
 }
 
 impl<Q, S> GraphQLServer<Q, S> {
@@ -68,8 +69,17 @@ impl<Q, S> GraphQLServer<Q, S> {
             graphql_runner,
             store,
             node_id,
+            shutdown: None,
         }
     }
+
+    /// Holds off a shutdown coordinated by `shutdown` until the responses
+    /// to running queries are written, and turns new queries away once it
+    /// has started.
+    pub fn with_shutdown(mut self, shutdown: ShutdownCoordinator) -> Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
 }
 
 impl<Q, S> GraphQLServerTrait for GraphQLServer<Q, S>
@@ -99,6 +109,7 @@ where
         let graphql_runner = self.graphql_runner.clone();
         let store = self.store.clone();
         let node_id = self.node_id.clone();
+        let shutdown = self.shutdown.clone();
         let new_service = move || {
             let service = GraphQLService::new(
                 logger_for_service.clone(),
@@ -107,6 +118,10 @@ where
                 ws_port,
                 node_id.clone(),
             );
+            let service = match &shutdown {
+                Some(shutdown) => service.with_shutdown(shutdown.clone()),
+                None => service,
+            };
             future::ok::<GraphQLService<Q, S>, hyper::Error>(service)
         };
 
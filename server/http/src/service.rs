@@ -23,6 +23,7 @@ pub struct GraphQLService<Q, S> {
     store: Arc<S>,
     ws_port: u16,
     node_id: NodeId,
+    shutdown: Option<ShutdownCoordinator>,
 }
 
 impl<Q, S> Clone for GraphQLService<Q, S> {
@@ -33,6 +34,7 @@ impl<Q, S> Clone for GraphQLService<Q, S> {
             store: self.store.clone(),
             ws_port: self.ws_port,
             node_id: self.node_id.clone(),
+            shutdown: self.shutdown.clone(),
         }
     }
 }
@@ -56,9 +58,18 @@ where
             store,
             ws_port,
             node_id,
+            shutdown: None,
         }
     }
 
+    /// Makes queries count as in-flight work of a shutdown coordinated by
+    /// `shutdown` until their responses are written, and turns new queries
+    /// away once it has started.
+    pub fn with_shutdown(mut self, shutdown: ShutdownCoordinator) -> Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
+
     fn graphiql_html(&self) -> String {
         include_str!("../assets/index.html")
             .replace("__WS_PORT__", format!("{}", self.ws_port).as_str())
@@ -188,6 +199,20 @@ where
         let logger = self.logger.clone();
         let sd_id = id.clone();
 
+        // Count the query as in-flight work of a shutdown until its response
+        // is written; once the shutdown has started, no new queries are run
+        let in_flight = match &self.shutdown {
+            Some(shutdown) => match shutdown.begin_work() {
+                Some(work) => Some(work),
+                None => {
+                    return Box::new(GraphQLResponse::new(Err(GraphQLServerError::from(
+                        QueryError::from(QueryExecutionError::ShuttingDown),
+                    ))));
+                }
+            },
+            None => None,
+        };
+
         match self.store.is_deployed(id) {
             Err(e) => {
                 return Box::new(future::err(GraphQLServerError::InternalError(
@@ -222,7 +247,7 @@ where
                     // Run the query using the query runner
                     service
                         .graphql_runner
-                        .run_query(query)
+                        .run_query_to_json(query)
                         .map_err(|e| GraphQLServerError::from(e))
                 })
                 .then(move |result| {
@@ -244,7 +269,7 @@ where
                             "code" => LogCode::GraphQlQueryFailure,
                         ),
                     }
-                    GraphQLResponse::new(result)
+                    GraphQLResponse::from_json(result).with_in_flight_work(in_flight)
                 }),
         )
     }
@@ -406,12 +431,13 @@ where
 
 #[cfg(test)]
 mod tests {
-    use graph_mock::MockStore;
+    use graph_mock::{MockMetricsRegistry, MockStore};
     use graphql_parser::query as q;
     use http::status::StatusCode;
     use hyper::service::Service;
     use hyper::{Body, Method, Request};
     use std::collections::BTreeMap;
+    use std::time::Duration;
     use web3::types::H256;
 
     use graph::data::subgraph::schema::*;
@@ -470,6 +496,7 @@ mod tests {
             schema,
             data_sources: vec![],
             templates: vec![],
+            raw: None,
         };
 
         let graphql_runner = Arc::new(TestGraphQlRunner);
@@ -537,6 +564,7 @@ mod tests {
             schema,
             data_sources: vec![],
             templates: vec![],
+            raw: None,
         };
         let graphql_runner = Arc::new(TestGraphQlRunner);
 
@@ -589,4 +617,34 @@ mod tests {
             }))
             .unwrap()
     }
+
+    #[test]
+    fn queries_are_turned_away_with_503_once_the_shutdown_started() {
+        let logger = Logger::root(slog::Discard, o!());
+        let store = Arc::new(MockStore::user_store());
+        let shutdown = ShutdownCoordinator::new(
+            Arc::new(MockMetricsRegistry::new()),
+            Duration::from_secs(30),
+        );
+        let node_id = NodeId::new("test").unwrap();
+        let mut service =
+            GraphQLService::new(logger, Arc::new(TestGraphQlRunner), store, 8001, node_id)
+                .with_shutdown(shutdown.clone());
+        let _ = shutdown.shutdown();
+
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(format!(
+                "http://localhost:8000/subgraphs/id/{}",
+                MockStore::user_subgraph_id()
+            ))
+            .body(Body::from("{\"query\": \"{ name }\"}"))
+            .unwrap();
+        let response = service
+            .call(request)
+            .wait()
+            .expect("Should return a response");
+        test_utils::assert_error_response(response, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(shutdown.in_flight(), 0);
+    }
 }
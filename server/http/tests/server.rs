@@ -88,6 +88,7 @@ mod test {
             schema: schema.clone(),
             data_sources: vec![],
             templates: vec![],
+            raw: None,
         };
 
         let store = Arc::new(MockStore::new(vec![(id, schema)]));
@@ -6,6 +6,7 @@ mod server;
 mod service;
 
 pub use self::request::IndexNodeRequest;
+pub use self::resolver::EthereumNetwork;
 pub use self::response::IndexNodeResponse;
 pub use self::server::IndexNodeServer;
 pub use self::service::{IndexNodeService, IndexNodeServiceResponse};
@@ -69,6 +69,9 @@ impl Future for IndexNodeRequest {
             document,
             variables,
             schema,
+            dry_run: false,
+            sync_warning: false,
+            query_id: None,
         }))
     }
 }
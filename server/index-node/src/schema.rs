@@ -1,4 +1,5 @@
 use lazy_static::lazy_static;
+use std::collections::BTreeMap;
 
 use graph::prelude::*;
 
@@ -14,6 +15,9 @@ lazy_static! {
             document: document,
             interfaces_for_type,
             types_for_interface,
+            indexes_for_type: BTreeMap::new(),
+            stored_entity_types: BTreeMap::new(),
+            raw: Some(raw_schema.to_owned()),
         })
     };
 }
@@ -1,11 +1,13 @@
 use hyper;
 use hyper::Server;
+use std::collections::BTreeMap;
 use std::error::Error;
 use std::fmt;
 use std::net::{Ipv4Addr, SocketAddrV4};
 
 use graph::prelude::{IndexNodeServer as IndexNodeServerTrait, *};
 
+use crate::resolver::EthereumNetwork;
 use crate::service::IndexNodeService;
 
 /// Errors that may occur when starting the server.
@@ -45,6 +47,11 @@ pub struct IndexNodeServer<Q, S> {
     logger: Logger,
     graphql_runner: Arc<Q>,
     store: Arc<S>,
+    handler_stats: Arc<dyn HandlerStatsRegistry>,
+    indexing_progress: Arc<dyn IndexingProgressRegistry>,
+    trigger_preflight: Arc<dyn TriggerPreflightRunner>,
+    networks: Arc<BTreeMap<String, EthereumNetwork>>,
+    metrics_registry: Arc<dyn MetricsRegistry>,
     node_id: NodeId,
 }
 
@@ -54,6 +61,11 @@ impl<Q, S> IndexNodeServer<Q, S> {
         logger_factory: &LoggerFactory,
         graphql_runner: Arc<Q>,
         store: Arc<S>,
+        handler_stats: Arc<dyn HandlerStatsRegistry>,
+        indexing_progress: Arc<dyn IndexingProgressRegistry>,
+        trigger_preflight: Arc<dyn TriggerPreflightRunner>,
+        networks: BTreeMap<String, EthereumNetwork>,
+        metrics_registry: Arc<dyn MetricsRegistry>,
         node_id: NodeId,
     ) -> Self {
         let logger = logger_factory.component_logger(
@@ -69,6 +81,11 @@ impl<Q, S> IndexNodeServer<Q, S> {
             logger,
             graphql_runner,
             store,
+            handler_stats,
+            indexing_progress,
+            trigger_preflight,
+            networks: Arc::new(networks),
+            metrics_registry,
             node_id,
         }
     }
@@ -99,12 +116,22 @@ where
         let logger_for_service = self.logger.clone();
         let graphql_runner = self.graphql_runner.clone();
         let store = self.store.clone();
+        let handler_stats = self.handler_stats.clone();
+        let indexing_progress = self.indexing_progress.clone();
+        let trigger_preflight = self.trigger_preflight.clone();
+        let networks = self.networks.clone();
+        let metrics_registry = self.metrics_registry.clone();
         let node_id = self.node_id.clone();
         let new_service = move || {
             let service = IndexNodeService::new(
                 logger_for_service.clone(),
                 graphql_runner.clone(),
                 store.clone(),
+                handler_stats.clone(),
+                indexing_progress.clone(),
+                trigger_preflight.clone(),
+                networks.clone(),
+                metrics_registry.clone(),
                 node_id.clone(),
             );
             future::ok::<IndexNodeService<Q, S>, hyper::Error>(service)
@@ -1,14 +1,16 @@
 use http::header;
 use hyper::service::Service;
 use hyper::{Body, Method, Request, Response, StatusCode};
+use std::collections::BTreeMap;
+use std::fmt;
 use std::time::Instant;
 
 use graph::components::server::query::GraphQLServerError;
 use graph::prelude::*;
-use graph_graphql::prelude::{execute_query, QueryExecutionOptions};
+use graph_graphql::prelude::{execute_query, AllowAll, QueryContext, QueryExecutionOptions};
 
 use crate::request::IndexNodeRequest;
-use crate::resolver::IndexNodeResolver;
+use crate::resolver::{EthereumNetwork, IndexNodeResolver};
 use crate::response::IndexNodeResponse;
 use crate::schema::SCHEMA;
 
@@ -17,20 +19,40 @@ pub type IndexNodeServiceResponse =
     Box<dyn Future<Item = Response<Body>, Error = GraphQLServerError> + Send>;
 
 /// A Hyper Service that serves GraphQL over a POST / endpoint.
-#[derive(Debug)]
 pub struct IndexNodeService<Q, S> {
     logger: Logger,
     graphql_runner: Arc<Q>,
     store: Arc<S>,
+    handler_stats: Arc<dyn HandlerStatsRegistry>,
+    indexing_progress: Arc<dyn IndexingProgressRegistry>,
+    trigger_preflight: Arc<dyn TriggerPreflightRunner>,
+    networks: Arc<BTreeMap<String, EthereumNetwork>>,
+    metrics_registry: Arc<dyn MetricsRegistry>,
     node_id: NodeId,
 }
 
+impl<Q, S> fmt::Debug for IndexNodeService<Q, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The registries and runners this service reads from are trait
+        // objects that can't all be formatted
+        f.debug_struct("IndexNodeService")
+            .field("logger", &self.logger)
+            .field("node_id", &self.node_id)
+            .finish()
+    }
+}
+
 impl<Q, S> Clone for IndexNodeService<Q, S> {
     fn clone(&self) -> Self {
         Self {
             logger: self.logger.clone(),
             graphql_runner: self.graphql_runner.clone(),
             store: self.store.clone(),
+            handler_stats: self.handler_stats.clone(),
+            indexing_progress: self.indexing_progress.clone(),
+            trigger_preflight: self.trigger_preflight.clone(),
+            networks: self.networks.clone(),
+            metrics_registry: self.metrics_registry.clone(),
             node_id: self.node_id.clone(),
         }
     }
@@ -42,11 +64,26 @@ where
     S: SubgraphDeploymentStore + Store,
 {
     /// Creates a new GraphQL service.
-    pub fn new(logger: Logger, graphql_runner: Arc<Q>, store: Arc<S>, node_id: NodeId) -> Self {
+    pub fn new(
+        logger: Logger,
+        graphql_runner: Arc<Q>,
+        store: Arc<S>,
+        handler_stats: Arc<dyn HandlerStatsRegistry>,
+        indexing_progress: Arc<dyn IndexingProgressRegistry>,
+        trigger_preflight: Arc<dyn TriggerPreflightRunner>,
+        networks: Arc<BTreeMap<String, EthereumNetwork>>,
+        metrics_registry: Arc<dyn MetricsRegistry>,
+        node_id: NodeId,
+    ) -> Self {
         IndexNodeService {
             logger,
             graphql_runner,
             store,
+            handler_stats,
+            indexing_progress,
+            trigger_preflight,
+            networks,
+            metrics_registry,
             node_id,
         }
     }
@@ -93,6 +130,11 @@ where
         let store = self.store.clone();
         let result_logger = self.logger.clone();
         let graphql_runner = self.graphql_runner.clone();
+        let handler_stats = self.handler_stats.clone();
+        let indexing_progress = self.indexing_progress.clone();
+        let trigger_preflight = self.trigger_preflight.clone();
+        let networks = self.networks.clone();
+        let metrics_registry = self.metrics_registry.clone();
 
         // Obtain the schema for the index node GraphQL API
         let schema = SCHEMA.clone();
@@ -107,18 +149,32 @@ where
                     let logger = logger.clone();
                     let graphql_runner = graphql_runner.clone();
 
-                    // Run the query using the index node resolver
-                    Box::new(future::ok(execute_query(
-                        &query,
-                        QueryExecutionOptions {
-                            logger: logger.clone(),
-                            resolver: IndexNodeResolver::new(&logger, graphql_runner, store),
-                            deadline: None,
-                            max_complexity: None,
-                            max_depth: 100,
-                            max_first: std::u32::MAX,
-                        },
-                    )))
+                    // Run the query using the index node resolver. Resolving
+                    // fields like `triggerPreflight` waits for the Ethereum
+                    // node, so this runs as blocking work
+                    graph::util::futures::blocking(future::lazy(move || {
+                        future::ok(execute_query(
+                            &query,
+                            QueryExecutionOptions {
+                                context: QueryContext::new(logger.clone(), None, std::u32::MAX),
+                                resolver: IndexNodeResolver::new(
+                                    &logger,
+                                    graphql_runner,
+                                    store,
+                                    handler_stats,
+                                    indexing_progress,
+                                    trigger_preflight,
+                                    networks,
+                                    metrics_registry,
+                                ),
+                                max_complexity: None,
+                                max_depth: 100,
+                                max_response_size: None,
+                                max_fields_per_selection: None,
+                                authorizer: Arc::new(AllowAll),
+                            },
+                        ))
+                    }))
                 })
                 .then(move |result| {
                     let elapsed = start.elapsed().as_millis();
@@ -294,6 +294,9 @@ where
                             schema: schema.clone(),
                             document: query,
                             variables,
+                            dry_run: false,
+                            sync_warning: false,
+                            query_id: None,
                         },
                     };
 
@@ -33,7 +33,7 @@ use diesel::{OptionalExtension, QueryDsl, RunQueryDsl};
 use inflector::cases::snakecase::to_snake_case;
 use lazy_static::lazy_static;
 use std::collections::hash_map::DefaultHasher;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::convert::TryInto;
 use std::hash::{Hash, Hasher};
 use std::sync::{Arc, Mutex};
@@ -48,12 +48,12 @@ use graph::prelude::{
     SubgraphDeploymentId, SubgraphDeploymentStore, ValueType,
 };
 
-use crate::block_range::{block_number, BlockNumber};
+use crate::block_range::{block_number, BlockNumber, BLOCK_NUMBER_MAX};
 use crate::filter::build_filter;
 use crate::history_event::HistoryEvent;
 use crate::jsonb::PgJsonbExpressionMethods as _;
 use crate::notification_listener::JsonNotification;
-use crate::relational::{IdType, Layout};
+use crate::relational::{EntityOrderKey, IdType, Layout};
 use crate::store::Store;
 
 lazy_static! {
@@ -421,16 +421,30 @@ impl Connection {
         &self,
         entity_types: Vec<String>,
         filter: Option<EntityFilter>,
-        order: Option<(String, ValueType, &str)>,
+        order: Option<(EntityOrderKey, &str)>,
+        select: Option<&BTreeSet<String>>,
         first: Option<u32>,
         skip: u32,
         block: BlockNumber,
     ) -> Result<Vec<Entity>, QueryExecutionError> {
         match &*self.storage {
-            Storage::Json(json) => json.query(&self.conn, entity_types, filter, order, first, skip),
-            Storage::Relational(layout) => {
-                layout.query(&self.conn, entity_types, filter, order, first, skip, block)
+            // JSON storage keeps no history and always loads all attributes
+            Storage::Json(_) if block != BLOCK_NUMBER_MAX => {
+                Err(QueryExecutionError::NotSupported(
+                    "querying entities at a block requires relational storage".to_owned(),
+                ))
             }
+            Storage::Json(json) => json.query(&self.conn, entity_types, filter, order, first, skip),
+            Storage::Relational(layout) => layout.query(
+                &self.conn,
+                entity_types,
+                filter,
+                order,
+                select,
+                first,
+                skip,
+                block,
+            ),
         }
     }
 
@@ -836,6 +850,24 @@ fn find_schema(
         .optional()?)
 }
 
+/// Whether the entities of `subgraph` are stored in a relational schema.
+/// Subgraphs that don't have a database schema yet will get one with the
+/// default storage scheme.
+pub(crate) fn uses_relational_schema(
+    conn: &diesel::pg::PgConnection,
+    subgraph: &SubgraphDeploymentId,
+) -> Result<bool, StoreError> {
+    use self::public::DeploymentSchemaVersion as v;
+
+    let version = find_schema(conn, subgraph)?
+        .map(|schema| schema.version)
+        .unwrap_or(*GRAPH_STORAGE_SCHEME);
+    Ok(match version {
+        v::Split => false,
+        v::Relational => true,
+    })
+}
+
 fn entity_to_json(key: &EntityKey, entity: &Entity) -> Result<serde_json::Value, Error> {
     serde_json::to_value(entity).map_err(|e| {
         format_err!(
@@ -878,7 +910,7 @@ impl JsonStorage {
         conn: &PgConnection,
         entity_types: Vec<String>,
         filter: Option<EntityFilter>,
-        order: Option<(String, ValueType, &str)>,
+        order: Option<(EntityOrderKey, &str)>,
         first: Option<u32>,
         skip: u32,
     ) -> Result<Vec<Entity>, QueryExecutionError> {
@@ -908,6 +940,19 @@ impl JsonStorage {
             query = query.filter(filter);
         }
 
+        let order = match order {
+            Some((EntityOrderKey::Attribute(attribute, value_type), direction)) => {
+                Some((attribute, value_type, direction))
+            }
+            // Only relational storage can join with referenced entities
+            Some((EntityOrderKey::ChildAttribute(child), _)) => {
+                return Err(QueryExecutionError::NotSupported(format!(
+                    "ordering by `{}__{}`, an attribute of referenced entities",
+                    child.relation, child.attribute
+                )));
+            }
+            None => None,
+        };
         if let Some((attribute, value_type, direction)) = order {
             let cast = match value_type {
                 ValueType::BigInt | ValueType::BigDecimal => "::numeric",
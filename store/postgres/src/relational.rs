@@ -11,18 +11,20 @@ use diesel::{debug_query, OptionalExtension, PgConnection, RunQueryDsl};
 use graphql_parser::query as q;
 use graphql_parser::schema as s;
 use inflector::Inflector;
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fmt::{self, Write};
 use std::str::FromStr;
 use std::sync::Arc;
 
 use crate::relational_queries::{
     ClampRangeQuery, ConflictingEntityQuery, EntityData, FilterQuery, FindQuery, InsertQuery,
-    QueryFilter, RevertClampQuery, RevertRemoveQuery,
+    QueryFilter, RevertClampQuery, RevertRemoveQuery, SortKey,
 };
+use graph::data::schema::stored_entity_type;
 use graph::prelude::{
     format_err, Entity, EntityChange, EntityChangeOperation, EntityFilter, EntityKey,
-    QueryExecutionError, StoreError, StoreEvent, SubgraphDeploymentId, ValueType,
+    EntityOrderByChild, QueryExecutionError, StoreError, StoreEvent, SubgraphDeploymentId,
+    ValueType,
 };
 
 use crate::block_range::{BlockNumber, BLOCK_RANGE_COLUMN};
@@ -119,11 +121,23 @@ pub enum IdType {
 
 type EnumMap = BTreeMap<String, Vec<String>>;
 
+/// What `Layout::query` orders entities by, besides their `id`
+#[derive(Clone, Debug)]
+pub enum EntityOrderKey {
+    /// An attribute of the entities, and its type
+    Attribute(String, ValueType),
+    /// An attribute of the entities that a reference attribute of the
+    /// entities points to
+    ChildAttribute(EntityOrderByChild),
+}
+
 #[derive(Debug, Clone)]
 pub struct Layout {
     /// The SQL type for columns with GraphQL type `ID`
     id_type: IdType,
-    /// Maps the GraphQL name of a type to the relational table
+    /// Maps the entity type of a GraphQL type to the relational table; that
+    /// is the name of the type unless it declares a different name with
+    /// `@entity(name: ...)`
     pub tables: HashMap<String, Arc<Table>>,
     /// The subgraph id
     pub subgraph: SubgraphDeploymentId,
@@ -318,6 +332,20 @@ impl Layout {
             .ok_or_else(|| StoreError::UnknownTable(entity.to_owned()))
     }
 
+    /// Find the tables that contain the entities of the GraphQL object or
+    /// interface type `type_name`
+    fn tables_for_type(&self, type_name: &str) -> Result<Vec<&Arc<Table>>, StoreError> {
+        match self.interfaces.get(type_name) {
+            Some(tables) => Ok(tables.iter().collect()),
+            None => self
+                .tables
+                .values()
+                .find(|table| table.type_name == type_name)
+                .map(|table| vec![table])
+                .ok_or_else(|| StoreError::UnknownTable(type_name.to_owned())),
+        }
+    }
+
     pub fn find(
         &self,
         conn: &PgConnection,
@@ -358,13 +386,15 @@ impl Layout {
             .map(|data| data.entity))
     }
 
-    /// order is a tuple (attribute, value_type, direction)
+    /// order is a tuple (key, direction); select lists the attributes to
+    /// load, besides the `id`, or is `None` to load all of them
     pub fn query(
         &self,
         conn: &PgConnection,
         entity_types: Vec<String>,
         filter: Option<EntityFilter>,
-        order: Option<(String, ValueType, &str)>,
+        order: Option<(EntityOrderKey, &str)>,
+        select: Option<&BTreeSet<String>>,
         first: Option<u32>,
         skip: u32,
         block: BlockNumber,
@@ -398,14 +428,43 @@ impl Layout {
             (_, None) => {
                 unreachable!("an entity query always contains at least one entity type/table");
             }
-            (Some((ref attribute, _, direction)), Some((table, _))) => {
+            (Some((EntityOrderKey::Attribute(ref attribute, _), direction)), Some((table, _))) => {
                 let column = table.column_for_field(&attribute)?;
-                Some((&column.name, direction))
+                Some((SortKey::Column(&column.name), direction))
+            }
+            (Some((EntityOrderKey::ChildAttribute(ref child), direction)), Some((table, _))) => {
+                // The referenced entities can be of any type implementing
+                // an interface, in which case we look in all their tables
+                let relation = table.column_for_field(&child.relation)?;
+                let children = self
+                    .tables_for_type(named_type(&relation.field_type))?
+                    .into_iter()
+                    .map(|table| {
+                        table
+                            .column_for_field(&child.attribute)
+                            .map(|column| (table.as_ref(), &column.name))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Some((
+                    SortKey::ChildColumn {
+                        relation: &relation.name,
+                        children,
+                    },
+                    direction,
+                ))
             }
             (None, _) => None,
         };
 
-        let query = FilterQuery::new(&self.schema, table_filter_pairs, order, first, skip, block);
+        let query = FilterQuery::new(
+            &self.schema,
+            table_filter_pairs,
+            order,
+            select,
+            first,
+            skip,
+            block,
+        );
         let query_debug_info = query.clone();
 
         let values = query.load::<EntityData>(conn).map_err(|e| {
@@ -675,8 +734,12 @@ pub(crate) const VID_COLUMN: &str = "vid";
 
 #[derive(Clone, Debug)]
 pub struct Table {
-    /// The name of the GraphQL object type ('Thing')
+    /// The entity type of the entities in this table ('Thing'); that is
+    /// the name of the GraphQL object type unless the type declares a
+    /// different name with `@entity(name: ...)`
     pub object: s::Name,
+    /// The name of the GraphQL object type
+    pub type_name: s::Name,
     /// The name of the database table for this type ('thing'), snakecased
     /// version of `object`
     pub name: SqlName,
@@ -697,9 +760,12 @@ impl Table {
         id_type: IdType,
         position: u32,
     ) -> Result<Table, StoreError> {
-        SqlName::check_valid_identifier(&*defn.name, "object")?;
+        // Types renamed with `@entity(name: ...)` keep using the table of
+        // the name they are stored under
+        let object = stored_entity_type(defn);
+        SqlName::check_valid_identifier(object, "object")?;
 
-        let table_name = SqlName::from(&*defn.name);
+        let table_name = SqlName::from(object);
         let columns = defn
             .fields
             .iter()
@@ -707,7 +773,8 @@ impl Table {
             .map(|field| Column::new(field, schema, enums, id_type))
             .collect::<Result<Vec<_>, _>>()?;
         let table = Table {
-            object: defn.name.clone(),
+            object: object.to_owned(),
+            type_name: defn.name.clone(),
             name: table_name.clone(),
             columns,
             position,
@@ -863,6 +930,18 @@ mod tests {
         assert!(table.column(&bad_sql_name).is_err());
     }
 
+    #[test]
+    fn renamed_types_use_the_table_of_their_stored_name() {
+        let layout =
+            test_layout(r#"type Account @entity(name: "User") { id: ID!, name: String! }"#);
+        let table = layout
+            .table_for_entity("User")
+            .expect("failed to get the table for 'User'");
+        assert_eq!(SqlName::from("user"), table.name);
+        assert_eq!("User", table.object);
+        assert!(layout.table_for_entity("Account").is_err());
+    }
+
     #[test]
     fn generate_ddl() {
         let layout = test_layout(THING_GQL);
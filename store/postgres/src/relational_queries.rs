@@ -11,6 +11,7 @@ use diesel::query_dsl::{LoadQuery, RunQueryDsl};
 use diesel::result::QueryResult;
 use diesel::sql_types::{Array, Binary, Bool, Integer, Jsonb, Numeric, Range, Text};
 use diesel::Connection;
+use std::collections::BTreeSet;
 use std::convert::TryFrom;
 use std::str::FromStr;
 
@@ -938,11 +939,34 @@ impl<'a> LoadQuery<PgConnection, ConflictingEntityData> for ConflictingEntityQue
 
 impl<'a, Conn> RunQueryDsl<Conn> for ConflictingEntityQuery<'a> {}
 
+/// The name under which queries over several tables select the value of a
+/// `SortKey::ChildColumn`
+const CHILD_SORT_KEY: &str = "child_sort_key";
+
+/// The number of columns that `FilterQuery` passes to one call of
+/// `jsonb_build_object`, which takes two arguments per column
+const MAX_JSONB_BUILD_OBJECT_COLUMNS: usize = 50;
+
+/// What a `FilterQuery` orders entities by, besides their `id`
+#[derive(Debug, Clone)]
+pub enum SortKey<'a> {
+    /// A column of the queried tables
+    Column(&'a SqlName),
+    /// A column of the entities that the column `relation` of the queried
+    /// tables references. The referenced entities are in one of the tables
+    /// in `children`, each paired with its column to order by
+    ChildColumn {
+        relation: &'a SqlName,
+        children: Vec<(&'a Table, &'a SqlName)>,
+    },
+}
+
 #[derive(Debug, Clone, Constructor)]
 pub struct FilterQuery<'a> {
     schema: &'a str,
     table_filter_pairs: Vec<(&'a Table, Option<QueryFilter<'a>>)>,
-    order: Option<(&'a SqlName, &'a str)>,
+    order: Option<(SortKey<'a>, &'a str)>,
+    select: Option<&'a BTreeSet<String>>,
     first: Option<String>,
     skip: Option<String>,
     block: BlockNumber,
@@ -951,26 +975,127 @@ pub struct FilterQuery<'a> {
 impl<'a> FilterQuery<'a> {
     fn order_by(&self, out: &mut AstPass<Pg>) -> QueryResult<()> {
         out.push_sql("\n order by ");
-        if let Some((name, direction)) = &self.order {
-            out.push_identifier(name.as_str())?;
-            out.push_sql(" ");
-            out.push_sql(direction);
-            if name.as_str() != PRIMARY_KEY_COLUMN {
+        match &self.order {
+            Some((SortKey::Column(name), direction)) => {
+                out.push_identifier(name.as_str())?;
+                out.push_sql(" ");
+                out.push_sql(direction);
+                if name.as_str() != PRIMARY_KEY_COLUMN {
+                    out.push_sql(", ");
+                    out.push_identifier(PRIMARY_KEY_COLUMN)?;
+                }
+                Ok(())
+            }
+            Some((SortKey::ChildColumn { relation, children }, direction)) => {
+                if self.table_filter_pairs.len() == 1 {
+                    self.child_sort_key(relation, children, out)?;
+                } else {
+                    out.push_identifier(CHILD_SORT_KEY)?;
+                }
+                out.push_sql(" ");
+                out.push_sql(direction);
                 out.push_sql(", ");
-                out.push_identifier(PRIMARY_KEY_COLUMN)?;
+                out.push_identifier(PRIMARY_KEY_COLUMN)
             }
-            Ok(())
-        } else {
-            out.push_identifier(PRIMARY_KEY_COLUMN)
+            None => out.push_identifier(PRIMARY_KEY_COLUMN),
         }
     }
 
     fn add_sort_key(&self, out: &mut AstPass<Pg>) -> QueryResult<()> {
-        if let Some((name, _)) = self.order {
-            if name.as_str() != PRIMARY_KEY_COLUMN {
+        match &self.order {
+            Some((SortKey::Column(name), _)) => {
+                if name.as_str() != PRIMARY_KEY_COLUMN {
+                    out.push_sql(", e.");
+                    out.push_identifier(name.as_str())?;
+                }
+            }
+            Some((SortKey::ChildColumn { relation, children }, _)) => {
+                out.push_sql(", ");
+                self.child_sort_key(relation, children, out)?;
+                out.push_sql(" as ");
+                out.push_identifier(CHILD_SORT_KEY)?;
+            }
+            None => (),
+        }
+        Ok(())
+    }
+
+    /// Generate the value of the column `children` of the entity that the
+    /// column `relation` of the row `e` references
+    fn child_sort_key(
+        &self,
+        relation: &SqlName,
+        children: &[(&Table, &SqlName)],
+        out: &mut AstPass<Pg>,
+    ) -> QueryResult<()> {
+        // Generate
+        //   coalesce((select c.column
+        //               from schema.child c
+        //              where c.id = e.relation
+        //                and block_range @> $block), ...)
+        // Entity IDs are unique across the types implementing an interface,
+        // so at most one of the subqueries finds the referenced entity
+        out.push_sql("coalesce(");
+        for (i, (table, column)) in children.iter().enumerate() {
+            if i > 0 {
+                out.push_sql(", ");
+            }
+            out.push_sql("(select c.");
+            out.push_identifier(column.as_str())?;
+            out.push_sql(" from ");
+            out.push_identifier(&self.schema)?;
+            out.push_sql(".");
+            out.push_identifier(table.name.as_str())?;
+            out.push_sql(" c where c.");
+            out.push_identifier(PRIMARY_KEY_COLUMN)?;
+            out.push_sql(" = e.");
+            out.push_identifier(relation.as_str())?;
+            out.push_sql(" and ");
+            BlockRangeContainsClause::new(self.block).walk_ast(out.reborrow())?;
+            out.push_sql(")");
+        }
+        out.push_sql(")");
+        Ok(())
+    }
+
+    /// Generate the JSONB data for the row `e` of `table`; that is all of
+    /// the row, or just the columns for the attributes in `select` and the
+    /// `id`
+    fn data(&self, table: &Table, out: &mut AstPass<Pg>) -> QueryResult<()> {
+        let select = match self.select {
+            Some(select) => select,
+            None => {
+                out.push_sql("to_jsonb(e.*)");
+                return Ok(());
+            }
+        };
+
+        // Generate
+        //   jsonb_build_object('id', e.id, 'name', e.name, ...)
+        //     || jsonb_build_object(...)
+        // Postgres functions take at most 100 arguments, and we therefore
+        // build the object from chunks of columns
+        let columns = table
+            .columns
+            .iter()
+            .filter(|column| {
+                column.name.as_str() == PRIMARY_KEY_COLUMN || select.contains(&column.field)
+            })
+            .collect::<Vec<_>>();
+        for (i, chunk) in columns.chunks(MAX_JSONB_BUILD_OBJECT_COLUMNS).enumerate() {
+            if i > 0 {
+                out.push_sql(" || ");
+            }
+            out.push_sql("jsonb_build_object(");
+            for (j, column) in chunk.iter().enumerate() {
+                if j > 0 {
+                    out.push_sql(", ");
+                }
+                out.push_bind_param::<Text, _>(column.name.as_str())?;
                 out.push_sql(", e.");
-                out.push_identifier(name.as_str())?;
+                out.push_identifier(column.name.as_str())?;
             }
+            out.push_sql(")");
         }
         Ok(())
     }
@@ -1021,7 +1146,8 @@ impl<'a> QueryFragment<Pg> for FilterQuery<'a> {
         // The queries are a little tricky because we need to defer generating
         // JSONB until we actually know which rows we really need to make these
         // queries fast; otherwise, Postgres spends too much time generating
-        // JSONB, most of which will never get used
+        // JSONB, most of which will never get used. For the same reason, the
+        // JSONB only contains the selected columns if there is a `select`
         if self.table_filter_pairs.len() == 1 {
             // The common case is that we have just one table; for that we
             // can generate a simpler/faster query. We generate
@@ -1040,10 +1166,9 @@ impl<'a> QueryFragment<Pg> for FilterQuery<'a> {
                 .expect("we just checked that there is exactly one");
             out.push_sql("select ");
             out.push_bind_param::<Text, _>(&table.object)?;
-            out.push_sql(
-                " as entity, to_jsonb(e.*) as data\n  from (\n  select *\
-                 \n",
-            );
+            out.push_sql(" as entity, ");
+            self.data(table, &mut out)?;
+            out.push_sql(" as data\n  from (\n  select *\n");
             self.filtered_rows(table, filter, out.reborrow())?;
             self.order_by(&mut out)?;
             self.limit(&mut out);
@@ -1095,7 +1220,9 @@ impl<'a> QueryFragment<Pg> for FilterQuery<'a> {
                 if i > 0 {
                     out.push_sql("\nunion all\n");
                 }
-                out.push_sql("select matches.entity, to_jsonb(e.*) as data, e.id");
+                out.push_sql("select matches.entity, ");
+                self.data(table, &mut out)?;
+                out.push_sql(" as data, e.id");
                 self.add_sort_key(&mut out)?;
                 out.push_sql("\n  from ");
                 out.push_identifier(&self.schema)?;
@@ -15,15 +15,16 @@ use graph::components::store::Store as StoreTrait;
 use graph::data::subgraph::schema::*;
 use graph::prelude::serde_json;
 use graph::prelude::{ChainHeadUpdateListener as _, *};
-use graph_graphql::prelude::api_schema;
+use graph_graphql::prelude::{ApiSchemaExt, ApiSchemaOptions, Pluralization};
 use tokio::timer::Interval;
 use web3::types::H256;
 
-use crate::block_range::BLOCK_NUMBER_MAX;
+use crate::block_range::{BlockNumber, BLOCK_NUMBER_MAX};
 use crate::chain_head_listener::ChainHeadUpdateListener;
 use crate::entities as e;
 use crate::functions::{attempt_chain_head_update, lookup_ancestor_block};
 use crate::history_event::HistoryEvent;
+use crate::relational::EntityOrderKey;
 use crate::store_events::StoreEventListener;
 
 embed_migrations!("./migrations");
@@ -349,29 +350,47 @@ impl Store {
         query: EntityQuery,
     ) -> Result<Vec<Entity>, QueryExecutionError> {
         // Add order by filters to query
-        let order = match query.order_by {
-            Some((attribute, value_type)) => {
-                let direction = query
-                    .order_direction
-                    .map(|direction| match direction {
-                        EntityOrder::Ascending => "ASC",
-                        EntityOrder::Descending => "DESC",
-                    })
-                    .unwrap_or("ASC");
-                Some((attribute, value_type, direction))
+        let direction = query
+            .order_direction
+            .map(|direction| match direction {
+                EntityOrder::Ascending => "ASC",
+                EntityOrder::Descending => "DESC",
+            })
+            .unwrap_or("ASC");
+        let order = match (query.order_by, query.order_by_child) {
+            (Some((attribute, value_type)), _) => {
+                Some((EntityOrderKey::Attribute(attribute, value_type), direction))
             }
-            None => None,
+            (None, Some(child)) => Some((EntityOrderKey::ChildAttribute(child), direction)),
+            (None, None) => None,
         };
 
+        let start = Instant::now();
+        let subgraph_id = query.subgraph_id;
+        let query_id = query.query_id;
+        let entity_types = query.entity_types.join(", ");
+        let block = query
+            .block
+            .map(|block| block.min(BLOCK_NUMBER_MAX as u64) as BlockNumber)
+            .unwrap_or(BLOCK_NUMBER_MAX);
+
         // Process results; deserialize JSON data
-        conn.query(
+        let result = conn.query(
             query.entity_types,
             query.filter,
             order,
+            query.select.as_ref(),
             query.range.first,
             query.range.skip,
-            BLOCK_NUMBER_MAX,
-        )
+            block,
+        );
+
+        trace!(self.logger, "Query entities";
+               "subgraph_id" => subgraph_id.to_string(),
+               "query_id" => query_id.unwrap_or_else(|| String::from("none")),
+               "entity_types" => entity_types,
+               "query_time_ms" => start.elapsed().as_millis());
+        result
     }
 
     fn check_interface_entity_uniqueness(
@@ -697,9 +716,12 @@ impl Store {
         }
         trace!(self.logger, "schema cache miss"; "id" => subgraph_id.to_string());
 
-        let input_schema = if *subgraph_id == *SUBGRAPHS_ID {
+        let (input_schema, pluralization) = if *subgraph_id == *SUBGRAPHS_ID {
             // The subgraph of subgraphs schema is built-in.
-            include_str!("subgraphs.graphql").to_owned()
+            (
+                include_str!("subgraphs.graphql").to_owned(),
+                Pluralization::Legacy,
+            )
         } else {
             let manifest_entity = self
                 .get(EntityKey {
@@ -709,8 +731,14 @@ impl Store {
                 })?
                 .ok_or_else(|| format_err!("Subgraph entity not found {}", subgraph_id))?;
 
+            // Collection fields are named according to the rules of the
+            // mapping API versions the subgraph was deployed with
+            let api_versions = self.mapping_api_versions(&manifest_entity)?;
+            let pluralization =
+                Pluralization::for_api_versions(api_versions.iter().map(String::as_str));
+
             match manifest_entity.get("schema") {
-                Some(Value::String(raw)) => raw.clone(),
+                Some(Value::String(raw)) => (raw.clone(), pluralization),
                 _ => {
                     return Err(format_err!(
                         "Schema not present or has wrong type, subgraph: {}",
@@ -722,12 +750,14 @@ impl Store {
 
         // Parse the schema and add @subgraphId directives
         let input_schema = Schema::parse(&input_schema, subgraph_id.clone())?;
-        let mut schema = input_schema.clone();
 
-        // Generate an API schema for the subgraph and make sure all types in the
-        // API schema have a @subgraphId directive as well
-        schema.document = api_schema(&schema.document)?;
-        schema.add_subgraph_id_directives(subgraph_id.clone());
+        // Generate an API schema for the subgraph. Only relational storage
+        // can order entities by the fields of the entities they reference
+        let options = ApiSchemaOptions {
+            pluralization,
+            order_by_child_fields: e::uses_relational_schema(&*self.get_conn()?, subgraph_id)?,
+        };
+        let schema = Schema::api(&self.logger, input_schema.clone(), options)?;
 
         let pair = SchemaPair {
             input: Arc::new(input_schema),
@@ -741,6 +771,44 @@ impl Store {
         Ok(cache.get(&subgraph_id).unwrap().clone())
     }
 
+    /// The API versions of the mappings of the data sources in the
+    /// `SubgraphManifest` entity `manifest`.
+    fn mapping_api_versions(&self, manifest: &Entity) -> Result<Vec<String>, Error> {
+        let metadata = |entity_type: &str, id: Option<&Value>| match id {
+            Some(Value::String(id)) => self.get(EntityKey {
+                subgraph_id: SUBGRAPHS_ID.clone(),
+                entity_type: entity_type.to_owned(),
+                entity_id: id.clone(),
+            }),
+            _ => Ok(None),
+        };
+
+        let data_source_ids = match manifest.get("dataSources") {
+            Some(Value::List(ids)) => ids.clone(),
+            _ => vec![],
+        };
+
+        let mut api_versions = vec![];
+        for data_source_id in data_source_ids {
+            let data_source = metadata(
+                EthereumContractDataSourceEntity::TYPENAME,
+                Some(&data_source_id),
+            )?;
+            let mapping = metadata(
+                EthereumContractMappingEntity::TYPENAME,
+                data_source
+                    .as_ref()
+                    .and_then(|entity| entity.get("mapping")),
+            )?;
+            if let Some(Value::String(api_version)) =
+                mapping.as_ref().and_then(|entity| entity.get("apiVersion"))
+            {
+                api_versions.push(api_version.clone());
+            }
+        }
+        Ok(api_versions)
+    }
+
     fn block_ptr_with_conn(
         &self,
         subgraph_id: SubgraphDeploymentId,
@@ -1059,6 +1127,19 @@ impl SubgraphDeploymentStore for Store {
         self.get_entity_conn(subgraph)
             .map(|econn| econn.uses_relational_schema())
     }
+
+    fn deployment_files(
+        &self,
+        subgraph_id: &SubgraphDeploymentId,
+    ) -> Result<Option<SubgraphDeploymentFiles>, Error> {
+        self.get(EntityKey {
+            subgraph_id: SUBGRAPHS_ID.clone(),
+            entity_type: SubgraphManifestEntity::TYPENAME.to_owned(),
+            entity_id: SubgraphManifestEntity::id(subgraph_id),
+        })?
+        .map(|entity| SubgraphManifestEntity::files(&entity))
+        .transpose()
+    }
 }
 
 impl ChainStore for Store {
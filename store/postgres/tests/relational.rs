@@ -5,15 +5,19 @@ use diesel::prelude::*;
 use futures::future::{self, IntoFuture};
 use hex_literal::hex;
 use lazy_static::lazy_static;
+use std::collections::BTreeSet;
 use std::fmt::Debug;
+use std::iter::FromIterator;
 use std::str::FromStr;
 
 use graph::data::store::scalar::{BigDecimal, BigInt, Bytes};
 use graph::prelude::{
-    bigdecimal::One, web3::types::H256, Entity, EntityFilter, EntityKey, EntityOrder, EntityQuery,
-    EntityRange, Schema, SubgraphDeploymentId, Value, ValueType,
+    bigdecimal::One, web3::types::H256, Entity, EntityFilter, EntityKey, EntityOrder,
+    EntityOrderByChild, EntityQuery, EntityRange, Schema, SubgraphDeploymentId, Value, ValueType,
+};
+use graph_store_postgres::layout_for_tests::{
+    EntityOrderKey, Layout, BLOCK_NUMBER_MAX, STRING_PREFIX_SIZE,
 };
-use graph_store_postgres::layout_for_tests::{Layout, BLOCK_NUMBER_MAX, STRING_PREFIX_SIZE};
 
 use test_store::*;
 
@@ -26,7 +30,7 @@ const THINGS_GQL: &str = "
     enum Color { yellow, red, BLUE }
 
     type Scalar @entity {
-        id: ID,
+        id: ID!,
         bool: Boolean,
         int: Int,
         bigDecimal: BigDecimal,
@@ -430,6 +434,7 @@ fn count_scalar_entities(conn: &PgConnection, layout: &Layout) -> usize {
             Some(filter),
             None,
             None,
+            None,
             0,
             BLOCK_NUMBER_MAX,
         )
@@ -506,18 +511,19 @@ fn test_find(expected_entity_ids: Vec<&str>, query: EntityQuery) {
         insert_users(conn, layout);
         insert_pets(conn, layout);
 
-        let order = match query.order_by {
-            Some((attribute, value_type)) => {
-                let direction = query
-                    .order_direction
-                    .map(|direction| match direction {
-                        EntityOrder::Ascending => "ASC",
-                        EntityOrder::Descending => "DESC",
-                    })
-                    .unwrap_or("ASC");
-                Some((attribute, value_type, direction))
+        let direction = query
+            .order_direction
+            .map(|direction| match direction {
+                EntityOrder::Ascending => "ASC",
+                EntityOrder::Descending => "DESC",
+            })
+            .unwrap_or("ASC");
+        let order = match (query.order_by, query.order_by_child) {
+            (Some((attribute, value_type)), _) => {
+                Some((EntityOrderKey::Attribute(attribute, value_type), direction))
             }
-            None => None,
+            (None, Some(child)) => Some((EntityOrderKey::ChildAttribute(child), direction)),
+            (None, None) => None,
         };
 
         let entities = layout
@@ -526,6 +532,7 @@ fn test_find(expected_entity_ids: Vec<&str>, query: EntityQuery) {
                 query.entity_types,
                 query.filter,
                 order,
+                query.select.as_ref(),
                 query.range.first,
                 query.range.skip,
                 BLOCK_NUMBER_MAX,
@@ -556,8 +563,12 @@ fn find_interface() {
             entity_types: vec!["Cat".to_owned(), "Dog".to_owned()],
             filter: None,
             order_by: None,
+            order_by_child: None,
+            select: None,
             order_direction: None,
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     );
 
@@ -568,8 +579,12 @@ fn find_interface() {
             entity_types: vec!["Cat".to_owned(), "Dog".to_owned()],
             filter: None,
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     );
 
@@ -580,8 +595,12 @@ fn find_interface() {
             entity_types: vec!["Cat".to_owned(), "Dog".to_owned()],
             filter: Some(EntityFilter::StartsWith("name".into(), Value::from("Gar"))),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     );
 
@@ -593,8 +612,12 @@ fn find_interface() {
             entity_types: vec!["Cat".to_owned(), "Dog".to_owned()],
             filter: None,
             order_by: Some(("id".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     );
 
@@ -605,8 +628,12 @@ fn find_interface() {
             entity_types: vec!["Cat".to_owned(), "Dog".to_owned()],
             filter: None,
             order_by: Some(("id".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: None,
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     );
 }
@@ -623,8 +650,12 @@ fn find_string_contains() {
                 "ind".into(),
             )])),
             order_by: None,
+            order_by_child: None,
+            select: None,
             order_direction: None,
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -644,8 +675,12 @@ fn find_list_contains() {
             entity_types: vec!["User".to_owned()],
             filter,
             order_by: None,
+            order_by_child: None,
+            select: None,
             order_direction: None,
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         }
     }
 
@@ -669,8 +704,12 @@ fn find_string_equal() {
                 "Cindini".into(),
             )])),
             order_by: None,
+            order_by_child: None,
+            select: None,
             order_direction: None,
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     );
 
@@ -685,8 +724,12 @@ fn find_string_equal() {
                 "Cindini".into(),
             )])),
             order_by: Some(("id".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -703,8 +746,12 @@ fn find_string_not_equal() {
                 "Cindini".into(),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -721,8 +768,12 @@ fn find_string_greater_than() {
                 "Kundi".into(),
             )])),
             order_by: None,
+            order_by_child: None,
+            select: None,
             order_direction: None,
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -739,8 +790,12 @@ fn find_string_less_than_order_by_asc() {
                 "Kundi".into(),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -757,8 +812,12 @@ fn find_string_less_than_order_by_desc() {
                 "Kundi".into(),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -775,11 +834,15 @@ fn find_string_less_than_range() {
                 "ZZZ".into(),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange {
                 first: Some(1),
                 skip: 1,
             },
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -796,8 +859,12 @@ fn find_string_multiple_and() {
                 EntityFilter::Equal("name".to_owned(), "Cindini".into()),
             ])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -814,8 +881,12 @@ fn find_string_ends_with() {
                 "ini".into(),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -832,8 +903,12 @@ fn find_string_not_ends_with() {
                 "ini".into(),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -850,8 +925,12 @@ fn find_string_in() {
                 vec!["Johnton".into(), "Nobody".into(), "Still nobody".into()],
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -868,8 +947,12 @@ fn find_string_not_in() {
                 vec!["Shaqueeena".into()],
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -886,8 +969,12 @@ fn find_float_equal() {
                 Value::BigDecimal(184.4.into()),
             )])),
             order_by: None,
+            order_by_child: None,
+            select: None,
             order_direction: None,
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -904,8 +991,12 @@ fn find_float_not_equal() {
                 Value::BigDecimal(184.4.into()),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -922,8 +1013,12 @@ fn find_float_greater_than() {
                 Value::BigDecimal(160.0.into()),
             )])),
             order_by: None,
+            order_by_child: None,
+            select: None,
             order_direction: None,
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -940,8 +1035,12 @@ fn find_float_less_than() {
                 Value::BigDecimal(160.0.into()),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -958,8 +1057,12 @@ fn find_float_less_than_order_by_desc() {
                 Value::BigDecimal(160.0.into()),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -976,11 +1079,15 @@ fn find_float_less_than_range() {
                 Value::BigDecimal(161.0.into()),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange {
                 first: Some(1),
                 skip: 1,
             },
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -1000,8 +1107,12 @@ fn find_float_in() {
                 ],
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(5),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -1021,8 +1132,12 @@ fn find_float_not_in() {
                 ],
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(5),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -1039,8 +1154,12 @@ fn find_int_equal() {
                 Value::Int(67 as i32),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -1057,8 +1176,12 @@ fn find_int_not_equal() {
                 Value::Int(67 as i32),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -1075,8 +1198,12 @@ fn find_int_greater_than() {
                 Value::Int(43 as i32),
             )])),
             order_by: None,
+            order_by_child: None,
+            select: None,
             order_direction: None,
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -1093,8 +1220,12 @@ fn find_int_greater_or_equal() {
                 Value::Int(43 as i32),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -1111,8 +1242,12 @@ fn find_int_less_than() {
                 Value::Int(50 as i32),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -1129,8 +1264,12 @@ fn find_int_less_or_equal() {
                 Value::Int(43 as i32),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -1147,8 +1286,12 @@ fn find_int_less_than_order_by_desc() {
                 Value::Int(50 as i32),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -1165,11 +1308,15 @@ fn find_int_less_than_range() {
                 Value::Int(67 as i32),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange {
                 first: Some(1),
                 skip: 1,
             },
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -1186,8 +1333,12 @@ fn find_int_in() {
                 vec![Value::Int(67 as i32), Value::Int(43 as i32)],
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(5),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -1204,8 +1355,12 @@ fn find_int_not_in() {
                 vec![Value::Int(67 as i32), Value::Int(43 as i32)],
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(5),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -1222,8 +1377,12 @@ fn find_bool_equal() {
                 Value::Bool(true),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -1240,8 +1399,12 @@ fn find_bool_not_equal() {
                 Value::Bool(true),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -1258,8 +1421,12 @@ fn find_bool_in() {
                 vec![Value::Bool(true)],
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(5),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -1276,8 +1443,12 @@ fn find_bool_not_in() {
                 vec![Value::Bool(true)],
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(5),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -1294,8 +1465,12 @@ fn find_bytes_equal() {
                 Value::Bytes("Johnton".as_bytes().into()),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -1312,8 +1487,12 @@ fn find_null_equal() {
                 Value::Null,
             )),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -1327,8 +1506,12 @@ fn find_null_not_equal() {
             entity_types: vec!["User".to_owned()],
             filter: Some(EntityFilter::Not("favorite_color".to_owned(), Value::Null)),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -1345,8 +1528,12 @@ fn find_null_not_in() {
                 vec![Value::Null],
             )),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     );
 
@@ -1360,8 +1547,12 @@ fn find_null_not_in() {
                 vec!["red".into(), Value::Null],
             )),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     );
 }
@@ -1375,8 +1566,12 @@ fn find_order_by_float() {
             entity_types: vec!["User".to_owned()],
             filter: None,
             order_by: Some(("weight".to_owned(), ValueType::BigDecimal)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     );
     test_find(
@@ -1386,8 +1581,12 @@ fn find_order_by_float() {
             entity_types: vec!["User".to_owned()],
             filter: None,
             order_by: Some(("weight".to_owned(), ValueType::BigDecimal)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     );
 }
@@ -1401,8 +1600,12 @@ fn find_order_by_id() {
             entity_types: vec!["User".to_owned()],
             filter: None,
             order_by: Some(("id".to_owned(), ValueType::ID)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     );
     test_find(
@@ -1412,8 +1615,12 @@ fn find_order_by_id() {
             entity_types: vec!["User".to_owned()],
             filter: None,
             order_by: Some(("id".to_owned(), ValueType::ID)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     );
 }
@@ -1427,8 +1634,12 @@ fn find_order_by_int() {
             entity_types: vec!["User".to_owned()],
             filter: None,
             order_by: Some(("age".to_owned(), ValueType::Int)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     );
     test_find(
@@ -1438,8 +1649,12 @@ fn find_order_by_int() {
             entity_types: vec!["User".to_owned()],
             filter: None,
             order_by: Some(("age".to_owned(), ValueType::Int)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     );
 }
@@ -1453,8 +1668,12 @@ fn find_order_by_string() {
             entity_types: vec!["User".to_owned()],
             filter: None,
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     );
     test_find(
@@ -1464,8 +1683,12 @@ fn find_order_by_string() {
             entity_types: vec!["User".to_owned()],
             filter: None,
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     );
 }
@@ -1482,8 +1705,12 @@ fn find_where_nested_and_or() {
                 EntityFilter::Equal("id".to_owned(), Value::from("2")),
             ])])),
             order_by: Some(("id".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -1500,8 +1727,12 @@ fn find_enum_equal() {
                 "red".into(),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -1518,8 +1749,12 @@ fn find_enum_not_equal() {
                 "red".into(),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -1536,8 +1771,12 @@ fn find_enum_in() {
                 vec!["red".into()],
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(5),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -1554,8 +1793,12 @@ fn find_enum_not_in() {
                 vec!["red".into()],
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(5),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -1592,22 +1835,27 @@ fn text_find(expected_entity_ids: Vec<&str>, filter: EntityFilter) {
             entity_types: vec!["Ferret".to_owned()],
             filter: Some(filter),
             order_by: Some(("id".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         };
 
-        let order = match query.order_by {
-            Some((attribute, value_type)) => {
-                let direction = query
-                    .order_direction
-                    .map(|direction| match direction {
-                        EntityOrder::Ascending => "ASC",
-                        EntityOrder::Descending => "DESC",
-                    })
-                    .unwrap_or("ASC");
-                Some((attribute, value_type, direction))
+        let direction = query
+            .order_direction
+            .map(|direction| match direction {
+                EntityOrder::Ascending => "ASC",
+                EntityOrder::Descending => "DESC",
+            })
+            .unwrap_or("ASC");
+        let order = match (query.order_by, query.order_by_child) {
+            (Some((attribute, value_type)), _) => {
+                Some((EntityOrderKey::Attribute(attribute, value_type), direction))
             }
-            None => None,
+            (None, Some(child)) => Some((EntityOrderKey::ChildAttribute(child), direction)),
+            (None, None) => None,
         };
 
         let entities = layout
@@ -1616,6 +1864,7 @@ fn text_find(expected_entity_ids: Vec<&str>, filter: EntityFilter) {
                 query.entity_types,
                 query.filter,
                 order,
+                query.select.as_ref(),
                 query.range.first,
                 query.range.skip,
                 BLOCK_NUMBER_MAX,
@@ -1765,8 +2014,12 @@ fn find_empty_and_or() {
             entity_types: vec!["User".to_owned()],
             filter: Some(EntityFilter::And(vec![EntityFilter::Or(vec![])])),
             order_by: None,
+            order_by_child: None,
+            select: None,
             order_direction: None,
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     );
 
@@ -1778,8 +2031,95 @@ fn find_empty_and_or() {
             entity_types: vec!["User".to_owned()],
             filter: Some(EntityFilter::Or(vec![EntityFilter::And(vec![])])),
             order_by: None,
+            order_by_child: None,
+            select: None,
             order_direction: None,
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
+
+#[test]
+fn find_order_by_child_attribute() {
+    run_test(|conn, layout| -> Result<(), ()> {
+        for (id, big_thing) in &[("a", "c"), ("b", "a"), ("c", "b")] {
+            let mut thing = Entity::new();
+            thing.set("id", *id);
+            thing.set("bigThing", *big_thing);
+            insert_entity(conn, layout, "Thing", thing);
+        }
+
+        let order = Some((
+            EntityOrderKey::ChildAttribute(EntityOrderByChild {
+                relation: "bigThing".to_owned(),
+                attribute: "id".to_owned(),
+            }),
+            "DESC",
+        ));
+        let entities = layout
+            .query(
+                conn,
+                vec!["Thing".to_owned()],
+                None,
+                order,
+                None,
+                None,
+                0,
+                BLOCK_NUMBER_MAX,
+            )
+            .expect("layout.query failed to order by a child attribute");
+        let entity_ids: Vec<_> = entities
+            .into_iter()
+            .map(|entity| entity.id().unwrap())
+            .collect();
+
+        // Ordered by the id of the referenced things: c, b, a
+        assert_eq!(vec!["a", "c", "b"], entity_ids);
+        Ok(())
+    })
+}
+
+#[test]
+fn query_loads_only_selected_attributes() {
+    run_test(|conn, layout| -> Result<(), ()> {
+        insert_users(conn, layout);
+        insert_pets(conn, layout);
+
+        let select = BTreeSet::from_iter(vec!["name".to_owned()]);
+        let query = |entity_types: Vec<&str>| {
+            layout
+                .query(
+                    conn,
+                    entity_types.into_iter().map(str::to_owned).collect(),
+                    None,
+                    None,
+                    Some(&select),
+                    None,
+                    0,
+                    BLOCK_NUMBER_MAX,
+                )
+                .expect("layout.query failed to select attributes")
+        };
+
+        // One table
+        let users = query(vec!["User"]);
+        assert_eq!(3, users.len());
+        let mut expected = Entity::new();
+        expected.set("id", "1");
+        expected.set("name", "Johnton");
+        expected.set("__typename", "User");
+        assert_eq!(expected, users[0]);
+
+        // Several tables
+        let pets = query(vec!["Cat", "Dog"]);
+        assert_eq!(2, pets.len());
+        let mut expected = Entity::new();
+        expected.set("id", "garfield");
+        expected.set("name", "Garfield");
+        expected.set("__typename", "Cat");
+        assert_eq!(expected, pets[0]);
+        Ok(())
+    })
+}
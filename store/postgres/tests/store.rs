@@ -4,8 +4,9 @@ use graph_mock::MockMetricsRegistry;
 use graphql_parser::schema as s;
 use hex_literal::hex;
 use lazy_static::lazy_static;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
+use std::sync::Mutex;
 use std::time::Duration;
 use test_store::*;
 
@@ -14,8 +15,10 @@ use graph::data::store::scalar;
 use graph::data::subgraph::schema::*;
 use graph::data::subgraph::*;
 use graph::prelude::*;
+use graph_store_postgres::connection_pool::create_connection_pool;
 use graph_store_postgres::layout_for_tests::STRING_PREFIX_SIZE;
 use graph_store_postgres::Store as DieselStore;
+use graph_store_postgres::StoreConfig;
 use web3::types::{Address, H256};
 
 const USER_GQL: &str = "
@@ -147,6 +150,7 @@ fn insert_test_data(store: Arc<DieselStore>) {
         schema: TEST_SUBGRAPH_SCHEMA.clone(),
         data_sources: vec![],
         templates: vec![],
+        raw: None,
     };
 
     // Create SubgraphDeploymentEntity
@@ -570,8 +574,12 @@ fn find_string_contains() {
                 "ind".into(),
             )])),
             order_by: None,
+            order_by_child: None,
+            select: None,
             order_direction: None,
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -588,8 +596,12 @@ fn find_string_equal() {
                 "Cindini".into(),
             )])),
             order_by: None,
+            order_by_child: None,
+            select: None,
             order_direction: None,
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -606,8 +618,12 @@ fn find_string_not_equal() {
                 "Cindini".into(),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -624,8 +640,12 @@ fn find_string_greater_than() {
                 "Kundi".into(),
             )])),
             order_by: None,
+            order_by_child: None,
+            select: None,
             order_direction: None,
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -642,8 +662,12 @@ fn find_string_less_than_order_by_asc() {
                 "Kundi".into(),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -660,8 +684,12 @@ fn find_string_less_than_order_by_desc() {
                 "Kundi".into(),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -678,11 +706,15 @@ fn find_string_less_than_range() {
                 "ZZZ".into(),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange {
                 first: Some(1),
                 skip: 1,
             },
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -699,8 +731,12 @@ fn find_string_multiple_and() {
                 EntityFilter::Equal("name".to_owned(), "Cindini".into()),
             ])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -717,8 +753,12 @@ fn find_string_ends_with() {
                 "ini".into(),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -735,8 +775,12 @@ fn find_string_not_ends_with() {
                 "ini".into(),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -753,8 +797,12 @@ fn find_string_in() {
                 vec!["Johnton".into()],
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -771,8 +819,12 @@ fn find_string_not_in() {
                 vec!["Shaqueeena".into()],
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -789,8 +841,12 @@ fn find_float_equal() {
                 Value::BigDecimal(184.4.into()),
             )])),
             order_by: None,
+            order_by_child: None,
+            select: None,
             order_direction: None,
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -807,8 +863,12 @@ fn find_float_not_equal() {
                 Value::BigDecimal(184.4.into()),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -825,8 +885,12 @@ fn find_float_greater_than() {
                 Value::BigDecimal(160.0.into()),
             )])),
             order_by: None,
+            order_by_child: None,
+            select: None,
             order_direction: None,
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -843,8 +907,12 @@ fn find_float_less_than() {
                 Value::BigDecimal(160.0.into()),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -861,8 +929,12 @@ fn find_float_less_than_order_by_desc() {
                 Value::BigDecimal(160.0.into()),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -879,11 +951,15 @@ fn find_float_less_than_range() {
                 Value::BigDecimal(161.0.into()),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange {
                 first: Some(1),
                 skip: 1,
             },
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -903,8 +979,12 @@ fn find_float_in() {
                 ],
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(5),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -924,8 +1004,12 @@ fn find_float_not_in() {
                 ],
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(5),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -942,8 +1026,12 @@ fn find_int_equal() {
                 Value::Int(67 as i32),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -960,8 +1048,12 @@ fn find_int_not_equal() {
                 Value::Int(67 as i32),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -978,8 +1070,12 @@ fn find_int_greater_than() {
                 Value::Int(43 as i32),
             )])),
             order_by: None,
+            order_by_child: None,
+            select: None,
             order_direction: None,
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -996,8 +1092,12 @@ fn find_int_greater_or_equal() {
                 Value::Int(43 as i32),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -1014,8 +1114,12 @@ fn find_int_less_than() {
                 Value::Int(50 as i32),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -1032,8 +1136,12 @@ fn find_int_less_or_equal() {
                 Value::Int(43 as i32),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -1050,8 +1158,12 @@ fn find_int_less_than_order_by_desc() {
                 Value::Int(50 as i32),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -1068,11 +1180,15 @@ fn find_int_less_than_range() {
                 Value::Int(67 as i32),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange {
                 first: Some(1),
                 skip: 1,
             },
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -1089,8 +1205,12 @@ fn find_int_in() {
                 vec![Value::Int(67 as i32), Value::Int(43 as i32)],
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(5),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -1107,8 +1227,12 @@ fn find_int_not_in() {
                 vec![Value::Int(67 as i32), Value::Int(43 as i32)],
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(5),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -1125,8 +1249,12 @@ fn find_bool_equal() {
                 Value::Bool(true),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -1143,8 +1271,12 @@ fn find_bool_not_equal() {
                 Value::Bool(true),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -1161,8 +1293,12 @@ fn find_bool_in() {
                 vec![Value::Bool(true)],
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(5),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -1179,8 +1315,12 @@ fn find_bool_not_in() {
                 vec![Value::Bool(true)],
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(5),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -1197,8 +1337,12 @@ fn find_bytes_equal() {
                 Value::Bytes("Johnton".as_bytes().into()),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -1215,8 +1359,12 @@ fn find_null_equal() {
                 Value::Null,
             )),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -1230,8 +1378,12 @@ fn find_null_not_equal() {
             entity_types: vec![USER.to_owned()],
             filter: Some(EntityFilter::Not("favorite_color".to_owned(), Value::Null)),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -1248,8 +1400,12 @@ fn find_null_not_in() {
                 vec![Value::Null],
             )),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
@@ -1263,8 +1419,12 @@ fn find_order_by_float() {
             entity_types: vec![USER.to_owned()],
             filter: None,
             order_by: Some(("weight".to_owned(), ValueType::BigDecimal)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     );
     test_find(
@@ -1274,8 +1434,12 @@ fn find_order_by_float() {
             entity_types: vec![USER.to_owned()],
             filter: None,
             order_by: Some(("weight".to_owned(), ValueType::BigDecimal)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     );
 }
@@ -1289,8 +1453,12 @@ fn find_order_by_id() {
             entity_types: vec![USER.to_owned()],
             filter: None,
             order_by: Some(("id".to_owned(), ValueType::ID)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     );
     test_find(
@@ -1300,8 +1468,12 @@ fn find_order_by_id() {
             entity_types: vec![USER.to_owned()],
             filter: None,
             order_by: Some(("id".to_owned(), ValueType::ID)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     );
 }
@@ -1315,8 +1487,12 @@ fn find_order_by_int() {
             entity_types: vec![USER.to_owned()],
             filter: None,
             order_by: Some(("age".to_owned(), ValueType::Int)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     );
     test_find(
@@ -1326,8 +1502,12 @@ fn find_order_by_int() {
             entity_types: vec![USER.to_owned()],
             filter: None,
             order_by: Some(("age".to_owned(), ValueType::Int)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     );
 }
@@ -1341,8 +1521,12 @@ fn find_order_by_string() {
             entity_types: vec![USER.to_owned()],
             filter: None,
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     );
     test_find(
@@ -1352,8 +1536,12 @@ fn find_order_by_string() {
             entity_types: vec![USER.to_owned()],
             filter: None,
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     );
 }
@@ -1370,12 +1558,112 @@ fn find_where_nested_and_or() {
                 EntityFilter::Equal("id".to_owned(), Value::from("2")),
             ])])),
             order_by: Some(("id".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         },
     )
 }
 
+/// A drain that keeps the messages logged through it along with their
+/// key-value pairs.
+#[derive(Clone, Default)]
+struct CapturingDrain {
+    records: Arc<Mutex<Vec<(String, HashMap<String, String>)>>>,
+}
+
+impl CapturingDrain {
+    /// Returns the key-value pairs of the records with message `msg`.
+    fn records(&self, msg: &str) -> Vec<HashMap<String, String>> {
+        self.records
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(record_msg, _)| record_msg == msg)
+            .map(|(_, kvs)| kvs.clone())
+            .collect()
+    }
+}
+
+struct KeyValues(HashMap<String, String>);
+
+impl slog::Serializer for KeyValues {
+    fn emit_arguments(&mut self, key: slog::Key, value: &std::fmt::Arguments) -> slog::Result {
+        self.0.insert(key.to_string(), value.to_string());
+        Ok(())
+    }
+}
+
+impl slog::Drain for CapturingDrain {
+    type Ok = ();
+    type Err = slog::Never;
+
+    fn log(
+        &self,
+        record: &slog::Record,
+        values: &slog::OwnedKVList,
+    ) -> Result<Self::Ok, Self::Err> {
+        use slog::KV;
+
+        let mut kvs = KeyValues(HashMap::new());
+        record.kv().serialize(record, &mut kvs).unwrap();
+        values.serialize(record, &mut kvs).unwrap();
+        self.records
+            .lock()
+            .unwrap()
+            .push((record.msg().to_string(), kvs.0));
+        Ok(())
+    }
+}
+
+#[test]
+fn entity_queries_are_logged_with_their_id() {
+    run_test(|_| -> Result<(), ()> {
+        // A store of its own that logs to `drain`
+        let drain = CapturingDrain::default();
+        let logger = Logger::root(drain.clone(), o!());
+        let postgres_url = postgres_test_url();
+        let store = DieselStore::new(
+            StoreConfig {
+                postgres_url: postgres_url.clone(),
+                network_name: NETWORK_NAME.to_owned(),
+            },
+            &logger,
+            EthereumNetworkIdentifier {
+                net_version: NETWORK_VERSION.to_owned(),
+                genesis_block_hash: GENESIS_PTR.hash,
+            },
+            create_connection_pool(postgres_url, 2, &logger),
+            Arc::new(MockMetricsRegistry::new()),
+        );
+
+        store
+            .find(EntityQuery {
+                subgraph_id: TEST_SUBGRAPH_ID.clone(),
+                entity_types: vec![USER.to_owned()],
+                filter: None,
+                order_by: None,
+                order_by_child: None,
+                select: None,
+                order_direction: None,
+                range: EntityRange::first(100),
+                query_id: Some(String::from("storeQuery")),
+                block: None,
+            })
+            .expect("store.find failed to execute query");
+
+        let queries = drain.records("Query entities");
+        assert_eq!(queries.len(), 1);
+        assert_eq!(queries[0]["query_id"], "storeQuery");
+        assert_eq!(queries[0]["subgraph_id"], TEST_SUBGRAPH_ID_STRING.as_str());
+        assert_eq!(queries[0]["entity_types"], USER);
+        Ok(())
+    })
+}
+
 fn make_entity_change(
     entity_type: &str,
     entity_id: &str,
@@ -1495,8 +1783,12 @@ fn check_basic_revert(
             Value::String("Shaqueeena".to_owned()),
         )])),
         order_by: Some(("name".to_owned(), ValueType::String)),
+        order_by_child: None,
+        select: None,
         order_direction: Some(EntityOrder::Descending),
         range: EntityRange::first(100),
+        query_id: None,
+        block: None,
     };
 
     let subscription = subscribe_and_consume(store.clone(), subgraph_id, entity_type);
@@ -1567,8 +1859,12 @@ fn revert_block_with_delete() {
                 Value::String("Cindini".to_owned()),
             )])),
             order_by: Some(("name".to_owned(), ValueType::String)),
+            order_by_child: None,
+            select: None,
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            query_id: None,
+            block: None,
         };
 
         // Delete entity with id=2
@@ -1705,6 +2001,7 @@ fn mock_data_source(path: &str) -> DataSource {
             address: Some(Address::from_str("0123123123012312312301231231230123123123").unwrap()),
             abi: String::from("123123"),
             start_block: 0,
+            factory: None,
         },
         mapping: Mapping {
             kind: String::from("ethereum/events"),
@@ -1742,6 +2039,7 @@ fn mock_data_source(path: &str) -> DataSource {
                 runtime: Arc::new(runtime),
             },
         }],
+        ordinal: DataSourceOrdinal::default(),
     }
 }
 
@@ -1897,6 +2195,7 @@ fn entity_changes_are_fired_and_forwarded_to_subscriptions() {
             schema: schema.clone(),
             data_sources: vec![],
             templates: vec![],
+            raw: None,
         };
 
         // Create SubgraphDeploymentEntity